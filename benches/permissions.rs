@@ -0,0 +1,100 @@
+// criterion benchmarks for the queries behind permission checks (`satisfies`,
+// including those loaded by `PermsEvaluator`) and the membership functions,
+// run against the same synthetic organization as `hive-bench` (which is seeded
+// with its defaults first if it isn't there yet)
+//
+// needs a (non-production!) database in $HIVE_DB_URL, and is skipped otherwise
+// (membership periods are interpreted in $HIVE_TIMEZONE, as in Hive itself)
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use rocket::tokio::runtime::{Builder, Runtime};
+use sqlx::PgPool;
+
+#[path = "../src/clock.rs"]
+#[allow(dead_code)]
+mod clock;
+#[path = "../src/bin/hive-bench/fixtures.rs"]
+mod fixtures;
+#[path = "../src/perms/queries.rs"]
+mod queries;
+
+use fixtures::{Lcg, Scenario, USERNAME_PREFIX};
+
+const DB_URL_VAR: &str = "HIVE_DB_URL";
+const TIMEZONE_VAR: &str = "HIVE_TIMEZONE";
+
+fn setup() -> Option<(Runtime, PgPool, u32)> {
+    let Ok(db_url) = std::env::var(DB_URL_VAR) else {
+        eprintln!("skipping benchmarks: no database specified (set ${DB_URL_VAR})");
+        return None;
+    };
+
+    clock::init(std::env::var(TIMEZONE_VAR).ok());
+
+    let runtime = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build runtime");
+
+    let (db, n_users) = runtime.block_on(async {
+        let db = clock::pool_options()
+            .max_connections(1)
+            .connect(&db_url)
+            .await
+            .expect("Failed to connect to database");
+
+        // assumes the database is already fully migrated (e.g., Hive ran once)
+        let mut n_users = fixtures::count_users(&db)
+            .await
+            .expect("Failed to count seeded users");
+
+        if n_users == 0 {
+            fixtures::seed(10_000, 2_000, 3, 3, &db)
+                .await
+                .expect("Failed to seed benchmark data");
+
+            n_users = fixtures::count_users(&db)
+                .await
+                .expect("Failed to count seeded users");
+        }
+
+        (db, n_users)
+    });
+
+    Some((runtime, db, n_users as u32))
+}
+
+fn permissions(c: &mut Criterion) {
+    let Some((runtime, db, n_users)) = setup() else {
+        return;
+    };
+
+    let mut group = c.benchmark_group("permissions");
+
+    for scenario in Scenario::ALL {
+        // same inputs for every scenario, so they're comparable
+        let mut rng = Lcg(0xbe7c4);
+
+        group.bench_function(scenario.name(), |b| {
+            b.iter_batched(
+                || {
+                    (
+                        format!("{USERNAME_PREFIX}{}", rng.next(n_users)),
+                        format!("s{}", rng.next(37)),
+                    )
+                },
+                |(username, scope)| {
+                    runtime
+                        .block_on(scenario.run(&username, &scope, &db))
+                        .expect("Query failed")
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, permissions);
+criterion_main!(benches);
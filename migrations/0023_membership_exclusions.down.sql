@@ -0,0 +1,66 @@
+-- Below are just the previous versions of the functions
+
+DROP FUNCTION all_members_of(group_id SLUG, group_domain DOMAIN, at DATE);
+
+CREATE FUNCTION all_members_of(group_id SLUG, group_domain DOMAIN, at DATE)
+RETURNS TABLE (username USERNAME, manager BOOL, "from" DATE, "until" DATE, path GROUP_REF[])
+AS $$
+    -- direct members
+    SELECT
+        dm.username,
+        dm.manager,
+        dm."from",
+        dm."until",
+        ARRAY[(dm.group_id, dm.group_domain)::GROUP_REF] AS path
+    FROM direct_memberships dm
+    WHERE dm.group_id = all_members_of.group_id
+        AND dm.group_domain = all_members_of.group_domain
+        AND all_members_of.at BETWEEN dm."from" AND dm."until" -- between is inclusive
+
+    UNION -- removes duplicates (vs. UNION ALL)
+
+    -- indirect members
+    SELECT
+        dm.username,
+        sg.manager,
+        dm."from",
+        dm."until",
+        sg.path || (all_members_of.group_id, all_members_of.group_domain)::GROUP_REF AS path
+    FROM all_subgroups_of(group_id, group_domain) sg
+    JOIN direct_memberships dm
+        ON dm.group_id = sg.child_id
+        AND dm.group_domain = sg.child_domain
+        AND all_members_of.at BETWEEN dm."from" AND dm."until" -- between is inclusive
+$$ LANGUAGE SQL;
+
+DROP FUNCTION all_groups_of(username USERNAME, at DATE);
+
+CREATE FUNCTION all_groups_of(username USERNAME, at DATE)
+RETURNS TABLE (id SLUG, domain DOMAIN, path GROUP_REF[])
+AS $$
+    WITH RECURSIVE group_hierarchy(group_id, group_domain, path) AS (
+        SELECT
+            dm.group_id,
+            dm.group_domain,
+            ARRAY[(dm.group_id, dm.group_domain)::GROUP_REF]
+        FROM direct_memberships dm
+        WHERE dm.username = all_groups_of.username
+        AND all_groups_of.at BETWEEN dm."from" AND dm."until" -- between is inclusive
+
+        UNION -- removes duplicates (vs. UNION ALL)
+
+        SELECT
+            sg.parent_id AS group_id,
+            sg.parent_domain AS group_domain,
+            gh.path || (sg.parent_id, sg.parent_domain)::GROUP_REF AS path
+        FROM subgroups sg
+        JOIN group_hierarchy gh
+            ON gh.group_id = sg.child_id
+            AND gh.group_domain = sg.child_domain
+        WHERE NOT (sg.parent_id, sg.parent_domain)::GROUP_REF = ANY(gh.path) -- prevent cycles
+    )
+    SELECT group_id AS id, group_domain AS domain, path
+    FROM group_hierarchy
+$$ LANGUAGE SQL;
+
+DROP TABLE "membership_exclusions";
@@ -0,0 +1,99 @@
+-- Exclusions allow a specific user to be carved out of a group they would
+-- otherwise inherit through one of its subgroups (e.g., someone who is in
+-- `drek` but should not be part of some parent group that includes `drek`).
+
+-- Exclusions only ever apply to indirect memberships: being a direct member of
+-- a group always takes precedence. Excluding a user from a group also prevents
+-- them from inheriting anything else *through* that group.
+
+CREATE TABLE "membership_exclusions" (
+    id             UUID     PRIMARY KEY DEFAULT gen_random_uuid(),
+    username       USERNAME NOT NULL,
+    group_id       SLUG     NOT NULL,
+    group_domain   DOMAIN   NOT NULL,
+
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain) ON DELETE CASCADE,
+    UNIQUE (username, group_id, group_domain)
+);
+
+DROP FUNCTION all_members_of(group_id SLUG, group_domain DOMAIN, at DATE);
+
+CREATE FUNCTION all_members_of(group_id SLUG, group_domain DOMAIN, at DATE)
+RETURNS TABLE (username USERNAME, manager BOOL, "from" DATE, "until" DATE, path GROUP_REF[])
+AS $$
+    -- direct members
+    SELECT
+        dm.username,
+        dm.manager,
+        dm."from",
+        dm."until",
+        ARRAY[(dm.group_id, dm.group_domain)::GROUP_REF] AS path
+    FROM direct_memberships dm
+    WHERE dm.group_id = all_members_of.group_id
+        AND dm.group_domain = all_members_of.group_domain
+        AND all_members_of.at BETWEEN dm."from" AND dm."until" -- between is inclusive
+
+    UNION -- removes duplicates (vs. UNION ALL)
+
+    -- indirect members
+    SELECT
+        dm.username,
+        sg.manager,
+        dm."from",
+        dm."until",
+        sg.path || (all_members_of.group_id, all_members_of.group_domain)::GROUP_REF AS path
+    FROM all_subgroups_of(group_id, group_domain) sg
+    JOIN direct_memberships dm
+        ON dm.group_id = sg.child_id
+        AND dm.group_domain = sg.child_domain
+        AND all_members_of.at BETWEEN dm."from" AND dm."until" -- between is inclusive
+    WHERE NOT EXISTS (
+        -- the last element of sg.path is where the user is a direct member,
+        -- so it's not subject to exclusions; all others (plus the group
+        -- itself) are only reached indirectly
+        SELECT 1
+        FROM membership_exclusions me
+        WHERE me.username = dm.username
+            AND (
+                (me.group_id = all_members_of.group_id AND me.group_domain = all_members_of.group_domain)
+                OR (me.group_id, me.group_domain)::GROUP_REF = ANY(trim_array(sg.path, 1))
+            )
+    )
+$$ LANGUAGE SQL;
+
+DROP FUNCTION all_groups_of(username USERNAME, at DATE);
+
+CREATE FUNCTION all_groups_of(username USERNAME, at DATE)
+RETURNS TABLE (id SLUG, domain DOMAIN, path GROUP_REF[])
+AS $$
+    WITH RECURSIVE group_hierarchy(group_id, group_domain, path) AS (
+        SELECT
+            dm.group_id,
+            dm.group_domain,
+            ARRAY[(dm.group_id, dm.group_domain)::GROUP_REF]
+        FROM direct_memberships dm
+        WHERE dm.username = all_groups_of.username
+        AND all_groups_of.at BETWEEN dm."from" AND dm."until" -- between is inclusive
+
+        UNION -- removes duplicates (vs. UNION ALL)
+
+        SELECT
+            sg.parent_id AS group_id,
+            sg.parent_domain AS group_domain,
+            gh.path || (sg.parent_id, sg.parent_domain)::GROUP_REF AS path
+        FROM subgroups sg
+        JOIN group_hierarchy gh
+            ON gh.group_id = sg.child_id
+            AND gh.group_domain = sg.child_domain
+        WHERE NOT (sg.parent_id, sg.parent_domain)::GROUP_REF = ANY(gh.path) -- prevent cycles
+            AND NOT EXISTS ( -- stop climbing at groups the user is excluded from
+                SELECT 1
+                FROM membership_exclusions me
+                WHERE me.username = all_groups_of.username
+                    AND me.group_id = sg.parent_id
+                    AND me.group_domain = sg.parent_domain
+            )
+    )
+    SELECT group_id AS id, group_domain AS domain, path
+    FROM group_hierarchy
+$$ LANGUAGE SQL;
@@ -0,0 +1,25 @@
+-- Calendar feeds are iCal documents served at unauthenticated, "secret" URLs,
+-- so that they can be subscribed to from any calendar application (which
+-- cannot log in via OIDC). Each feed belongs to a user, and is either a
+-- personal feed with their own memberships (NULL group) or a feed with all
+-- memberships of a specific group.
+
+-- Unlike API token secrets, feed secrets are not hashed: they only grant
+-- read-only access to (non-sensitive) dates, and the owner must always be able
+-- to see their feed URL again to subscribe from other devices.
+
+CREATE TABLE "calendar_feeds" (
+    secret         UUID     PRIMARY KEY DEFAULT gen_random_uuid(),
+    owner          USERNAME NOT NULL,
+    group_id       SLUG,
+    group_domain   DOMAIN,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain) ON DELETE CASCADE,
+    CHECK ((group_id IS NULL) = (group_domain IS NULL)),
+    UNIQUE (owner, group_id, group_domain) -- only applies to group feeds
+);
+
+-- at most one personal feed per user (NULLs are distinct in UNIQUE above)
+CREATE UNIQUE INDEX "calendar_feeds_personal_idx" ON "calendar_feeds" (owner)
+WHERE group_id IS NULL;
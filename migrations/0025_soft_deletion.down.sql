@@ -0,0 +1,15 @@
+DROP TRIGGER archive_deleted_subtag ON "subtags";
+DROP TRIGGER archive_deleted_tag_assignment ON "tag_assignments";
+DROP TRIGGER archive_deleted_tag ON "tags";
+DROP TRIGGER archive_deleted_permission_assignment ON "permission_assignments";
+DROP TRIGGER archive_deleted_permission ON "permissions";
+DROP TRIGGER archive_deleted_calendar_feed ON "calendar_feeds";
+DROP TRIGGER archive_deleted_membership_exclusion ON "membership_exclusions";
+DROP TRIGGER archive_deleted_subgroup ON "subgroups";
+DROP TRIGGER archive_deleted_direct_membership ON "direct_memberships";
+DROP TRIGGER archive_deleted_group ON "groups";
+
+DROP FUNCTION archive_deleted_row;
+
+DROP TABLE "deleted_rows";
+DROP TABLE "deletions";
@@ -0,0 +1,63 @@
+-- Deletions of groups, permissions, tags and memberships can be undone by
+-- their actor for a short while. Instead of adding deleted_at columns to
+-- every table (and having to filter them out in every single query), each
+-- deleted row is archived as JSON together with everything that was deleted
+-- along with it by ON DELETE CASCADE, so that it can all be restored verbatim.
+
+CREATE TABLE "deletions" (
+    id          UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    target_kind TARGET_KIND NOT NULL,
+    target_id   TEXT        NOT NULL,
+    actor       USERNAME    NOT NULL,
+    deleted_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE "deleted_rows" (
+    seq         BIGSERIAL PRIMARY KEY, -- preserves deletion order
+    deletion_id UUID      NOT NULL,
+    table_name  TEXT      NOT NULL,
+    row_data    JSONB     NOT NULL,
+
+    FOREIGN KEY (deletion_id) REFERENCES "deletions" (id) ON DELETE CASCADE
+);
+
+CREATE INDEX "deleted_rows_deletion_idx" ON "deleted_rows" (deletion_id);
+
+-- Rows are only archived while `hive.deletion_id` is set (locally to the
+-- current transaction), so other deletions (e.g., cleanups) are unaffected.
+-- BEFORE triggers fire for a parent row before its cascades happen, so rows
+-- are archived in an order in which they can safely be re-inserted.
+CREATE FUNCTION archive_deleted_row() RETURNS TRIGGER AS $$
+DECLARE
+    deletion_id TEXT := current_setting('hive.deletion_id', TRUE);
+BEGIN
+    -- reverts to '' (not NULL) after SET LOCAL once the transaction ends
+    IF deletion_id IS NOT NULL AND deletion_id <> '' THEN
+        INSERT INTO deleted_rows (deletion_id, table_name, row_data)
+        VALUES (deletion_id::UUID, TG_TABLE_NAME, to_jsonb(OLD));
+    END IF;
+
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER archive_deleted_group BEFORE DELETE ON "groups"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
+CREATE TRIGGER archive_deleted_direct_membership BEFORE DELETE ON "direct_memberships"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
+CREATE TRIGGER archive_deleted_subgroup BEFORE DELETE ON "subgroups"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
+CREATE TRIGGER archive_deleted_membership_exclusion BEFORE DELETE ON "membership_exclusions"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
+CREATE TRIGGER archive_deleted_calendar_feed BEFORE DELETE ON "calendar_feeds"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
+CREATE TRIGGER archive_deleted_permission BEFORE DELETE ON "permissions"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
+CREATE TRIGGER archive_deleted_permission_assignment BEFORE DELETE ON "permission_assignments"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
+CREATE TRIGGER archive_deleted_tag BEFORE DELETE ON "tags"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
+CREATE TRIGGER archive_deleted_tag_assignment BEFORE DELETE ON "tag_assignments"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
+CREATE TRIGGER archive_deleted_subtag BEFORE DELETE ON "subtags"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
@@ -0,0 +1,11 @@
+-- Daily request counters for API tokens, incremented by the API layer whenever
+-- a token is used, so that system owners can see whether their tokens (and
+-- thus their integrations) are actually being used.
+
+CREATE TABLE "api_token_usage" (
+    api_token_id UUID    NOT NULL REFERENCES "api_tokens" (id) ON DELETE CASCADE,
+    day          DATE    NOT NULL,
+    n_requests   INTEGER NOT NULL DEFAULT 1,
+
+    PRIMARY KEY (api_token_id, day)
+);
@@ -0,0 +1,9 @@
+-- Tokens that have never been used can only be considered idle if we know
+-- when they were created. Pre-existing tokens are left without a creation
+-- stamp (NULL), which is treated as "a long time ago".
+
+ALTER TABLE "api_tokens"
+    ADD COLUMN created_at TIMESTAMPTZ;
+
+ALTER TABLE "api_tokens"
+    ALTER COLUMN created_at SET DEFAULT NOW();
@@ -0,0 +1,4 @@
+DROP TABLE "api_token_group_restrictions";
+
+ALTER TABLE "api_tokens"
+    DROP COLUMN restricted_to_groups;
@@ -0,0 +1,29 @@
+-- API tokens can be restricted to only "see" some groups through the API,
+-- namely those in given domains or tagged with given tags (belonging to the
+-- token's own system), so that a leaked token for a small system cannot be
+-- used to enumerate the whole organization's structure.
+--
+-- The flag on the token itself is what marks it as restricted, such that it
+-- doesn't suddenly become unrestricted if e.g. all of its tags are deleted.
+
+ALTER TABLE "api_tokens"
+    ADD COLUMN restricted_to_groups BOOL NOT NULL DEFAULT FALSE;
+
+CREATE TABLE "api_token_group_restrictions" (
+    id           UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    api_token_id UUID NOT NULL,
+    group_domain DOMAIN,
+    system_id    SLUG,
+    tag_id       SLUG,
+
+    FOREIGN KEY (api_token_id)      REFERENCES "api_tokens" (id)                ON DELETE CASCADE,
+    FOREIGN KEY (system_id, tag_id) REFERENCES "tags"       (system_id, tag_id) ON DELETE CASCADE,
+    CONSTRAINT xor_domain_tag CHECK ((group_domain IS NULL) <> (tag_id IS NULL))
+);
+
+CREATE INDEX "api_token_group_restrictions_token_idx"
+    ON "api_token_group_restrictions" (api_token_id);
+
+-- (so that a restored token doesn't lose its restrictions and see no groups)
+CREATE TRIGGER archive_deleted_api_token_group_restriction BEFORE DELETE ON "api_token_group_restrictions"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
@@ -0,0 +1,6 @@
+-- API tokens can require API requests to be signed (HMAC) instead of sending
+-- the secret along with every request, mitigating replay attacks and secrets
+-- accidentally leaking through logs. Such tokens cannot be used otherwise.
+
+ALTER TABLE "api_tokens"
+    ADD COLUMN requires_signing BOOL NOT NULL DEFAULT FALSE;
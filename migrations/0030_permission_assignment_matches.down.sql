@@ -0,0 +1,3 @@
+ALTER TABLE "permission_assignments"
+    DROP COLUMN created_at,
+    DROP COLUMN last_matched_at;
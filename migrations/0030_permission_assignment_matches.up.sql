@@ -0,0 +1,12 @@
+-- Tracks when each permission assignment last satisfied a permission check
+-- made through the API, so that grants nobody relies on can be found and
+-- pruned. Stamps are only written periodically (and coarsely), so they are
+-- approximate. As with API tokens, pre-existing assignments are left without
+-- a creation stamp (NULL), which is treated as "a long time ago".
+
+ALTER TABLE "permission_assignments"
+    ADD COLUMN created_at      TIMESTAMPTZ,
+    ADD COLUMN last_matched_at TIMESTAMPTZ;
+
+ALTER TABLE "permission_assignments"
+    ALTER COLUMN created_at SET DEFAULT NOW();
@@ -0,0 +1,4 @@
+DELETE FROM "permissions"
+WHERE system_id = 'hive'
+    AND perm_id = 'api-apply-manifest';
+-- ^ this cascades to permission_assignments
@@ -0,0 +1,2 @@
+INSERT INTO "permissions" (system_id, perm_id, has_scope, description) VALUES
+    ('hive', 'api-apply-manifest', FALSE, 'Declaratively manage the associated system''s permissions and tags via Hive''s API');
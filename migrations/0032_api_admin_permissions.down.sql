@@ -0,0 +1,4 @@
+DELETE FROM "permissions"
+WHERE system_id = 'hive'
+    AND perm_id IN ('api-list-groups', 'api-manage-members', 'api-run-integrations');
+-- ^ this cascades to permission_assignments
@@ -0,0 +1,4 @@
+INSERT INTO "permissions" (system_id, perm_id, has_scope, description) VALUES
+    ('hive', 'api-list-groups', FALSE, 'List all groups and their direct memberships via Hive''s API'),
+    ('hive', 'api-manage-members', FALSE, 'Add and remove direct group members via Hive''s API'),
+    ('hive', 'api-run-integrations', FALSE, 'Trigger integration task runs via Hive''s API');
@@ -0,0 +1,14 @@
+DROP FUNCTION all_group_refs_of(username USERNAME, at DATE);
+
+DROP TRIGGER invalidate_membership_closure ON subgroups;
+DROP FUNCTION invalidate_membership_closure_trigger();
+
+DROP TRIGGER refresh_membership_closure ON membership_exclusions;
+DROP TRIGGER refresh_membership_closure ON direct_memberships;
+DROP FUNCTION refresh_membership_closure_trigger();
+
+DROP FUNCTION rebuild_membership_closure();
+DROP FUNCTION refresh_membership_closure_of(username USERNAME);
+
+DROP TABLE "membership_closure_state";
+DROP TABLE "membership_closure";
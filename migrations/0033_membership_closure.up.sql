@@ -0,0 +1,184 @@
+-- Materialized closure of (direct + indirect) group memberships, so that
+-- permission checks don't need to recursively climb the group hierarchy every
+-- time. Each row means that the user is a member of the group (directly or
+-- through subgroups, respecting exclusions) on every day between "from" and
+-- "until", both inclusive, as inherited from one of their direct memberships.
+
+-- Rows for a single user are kept up-to-date by triggers whenever their direct
+-- memberships or exclusions change. Changes to the group hierarchy itself can
+-- affect arbitrarily many users, so they instead just mark the closure as
+-- invalid, in which case lookups fall back to the recursive `all_groups_of`
+-- until the closure is rebuilt (which is done in the background).
+
+CREATE TABLE "membership_closure" (
+    username       USERNAME NOT NULL,
+    group_id       SLUG     NOT NULL,
+    group_domain   DOMAIN   NOT NULL,
+    "from"         DATE     NOT NULL,
+    "until"        DATE     NOT NULL
+);
+
+CREATE INDEX ON "membership_closure" (username, "from", "until");
+
+CREATE TABLE "membership_closure_state" (
+    singleton   BOOL  PRIMARY KEY DEFAULT TRUE CHECK (singleton),
+    valid       BOOL  NOT NULL
+);
+
+INSERT INTO "membership_closure_state" (valid) VALUES (FALSE);
+
+CREATE FUNCTION refresh_membership_closure_of(username USERNAME)
+RETURNS VOID
+AS $$
+    -- serializes concurrent refreshes for the same user, since otherwise rows
+    -- inserted by one could survive the other's deletion
+    SELECT pg_advisory_xact_lock(hashtext(refresh_membership_closure_of.username));
+
+    DELETE FROM membership_closure mc
+    WHERE mc.username = refresh_membership_closure_of.username;
+
+    INSERT INTO membership_closure (username, group_id, group_domain, "from", "until")
+    WITH RECURSIVE group_hierarchy(group_id, group_domain, "from", "until", path) AS (
+        SELECT
+            dm.group_id,
+            dm.group_domain,
+            dm."from",
+            dm."until",
+            ARRAY[(dm.group_id, dm.group_domain)::GROUP_REF]
+        FROM direct_memberships dm
+        WHERE dm.username = refresh_membership_closure_of.username
+
+        UNION -- removes duplicates (vs. UNION ALL)
+
+        SELECT
+            sg.parent_id AS group_id,
+            sg.parent_domain AS group_domain,
+            gh."from",
+            gh."until",
+            gh.path || (sg.parent_id, sg.parent_domain)::GROUP_REF AS path
+        FROM subgroups sg
+        JOIN group_hierarchy gh
+            ON gh.group_id = sg.child_id
+            AND gh.group_domain = sg.child_domain
+        WHERE NOT (sg.parent_id, sg.parent_domain)::GROUP_REF = ANY(gh.path) -- prevent cycles
+            AND NOT EXISTS ( -- stop climbing at groups the user is excluded from
+                SELECT 1
+                FROM membership_exclusions me
+                WHERE me.username = refresh_membership_closure_of.username
+                    AND me.group_id = sg.parent_id
+                    AND me.group_domain = sg.parent_domain
+            )
+    )
+    SELECT DISTINCT refresh_membership_closure_of.username, group_id, group_domain, "from", "until"
+    FROM group_hierarchy
+$$ LANGUAGE SQL;
+
+CREATE FUNCTION rebuild_membership_closure()
+RETURNS VOID
+AS $$
+    -- block (and wait for pending) changes to anything the closure depends on
+    -- until we're done, since otherwise it could be marked as valid despite
+    -- not reflecting them
+    LOCK TABLE direct_memberships, membership_exclusions, subgroups IN SHARE MODE;
+
+    DELETE FROM membership_closure;
+
+    INSERT INTO membership_closure (username, group_id, group_domain, "from", "until")
+    WITH RECURSIVE group_hierarchy(username, group_id, group_domain, "from", "until", path) AS (
+        SELECT
+            dm.username,
+            dm.group_id,
+            dm.group_domain,
+            dm."from",
+            dm."until",
+            ARRAY[(dm.group_id, dm.group_domain)::GROUP_REF]
+        FROM direct_memberships dm
+
+        UNION -- removes duplicates (vs. UNION ALL)
+
+        SELECT
+            gh.username,
+            sg.parent_id AS group_id,
+            sg.parent_domain AS group_domain,
+            gh."from",
+            gh."until",
+            gh.path || (sg.parent_id, sg.parent_domain)::GROUP_REF AS path
+        FROM subgroups sg
+        JOIN group_hierarchy gh
+            ON gh.group_id = sg.child_id
+            AND gh.group_domain = sg.child_domain
+        WHERE NOT (sg.parent_id, sg.parent_domain)::GROUP_REF = ANY(gh.path) -- prevent cycles
+            AND NOT EXISTS ( -- stop climbing at groups the user is excluded from
+                SELECT 1
+                FROM membership_exclusions me
+                WHERE me.username = gh.username
+                    AND me.group_id = sg.parent_id
+                    AND me.group_domain = sg.parent_domain
+            )
+    )
+    SELECT DISTINCT username, group_id, group_domain, "from", "until"
+    FROM group_hierarchy;
+
+    UPDATE membership_closure_state SET valid = TRUE;
+$$ LANGUAGE SQL;
+
+CREATE FUNCTION refresh_membership_closure_trigger()
+RETURNS TRIGGER
+AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        PERFORM refresh_membership_closure_of(OLD.username);
+    END IF;
+
+    IF TG_OP = 'INSERT' OR (TG_OP = 'UPDATE' AND NEW.username <> OLD.username) THEN
+        PERFORM refresh_membership_closure_of(NEW.username);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER refresh_membership_closure
+AFTER INSERT OR UPDATE OR DELETE ON direct_memberships
+FOR EACH ROW EXECUTE FUNCTION refresh_membership_closure_trigger();
+
+CREATE TRIGGER refresh_membership_closure
+AFTER INSERT OR UPDATE OR DELETE ON membership_exclusions
+FOR EACH ROW EXECUTE FUNCTION refresh_membership_closure_trigger();
+
+CREATE FUNCTION invalidate_membership_closure_trigger()
+RETURNS TRIGGER
+AS $$
+BEGIN
+    UPDATE membership_closure_state SET valid = FALSE WHERE valid;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER invalidate_membership_closure
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON subgroups
+FOR EACH STATEMENT EXECUTE FUNCTION invalidate_membership_closure_trigger();
+
+SELECT rebuild_membership_closure();
+
+-- Same columns as `all_groups_of`, minus the path (which the closure doesn't
+-- keep); meant for hot paths like permission checks, where only the set of
+-- groups matters. The planner only evaluates the branch matching the
+-- closure's current validity.
+
+CREATE FUNCTION all_group_refs_of(username USERNAME, at DATE)
+RETURNS TABLE (id SLUG, domain DOMAIN)
+AS $$
+    SELECT mc.group_id AS id, mc.group_domain AS domain
+    FROM membership_closure mc
+    WHERE (SELECT valid FROM membership_closure_state)
+        AND mc.username = all_group_refs_of.username
+        AND all_group_refs_of.at BETWEEN mc."from" AND mc."until" -- between is inclusive
+
+    UNION -- removes duplicates (vs. UNION ALL)
+
+    SELECT ag.id, ag.domain
+    FROM all_groups_of(username, at) ag
+    WHERE NOT (SELECT valid FROM membership_closure_state)
+$$ LANGUAGE SQL STABLE;
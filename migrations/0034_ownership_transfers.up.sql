@@ -0,0 +1,29 @@
+-- A pending handover of a group's primary responsibility (i.e., its set of
+-- managers) to either a single user or a (managing) subgroup. It only takes
+-- effect once accepted by the receiving party, and is then removed again.
+
+-- There can be at most one pending transfer per group; proposing another one
+-- replaces it.
+
+CREATE TABLE "ownership_transfers" (
+    id              UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    group_id        SLUG        NOT NULL,
+    group_domain    DOMAIN      NOT NULL,
+
+    to_username     USERNAME,
+    to_group_id     SLUG,
+    to_group_domain DOMAIN,
+    "until"         DATE, -- end of the new manager's membership (users only)
+
+    proposed_by     USERNAME    NOT NULL,
+    proposed_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain) ON DELETE CASCADE,
+    FOREIGN KEY (to_group_id, to_group_domain) REFERENCES "groups" (id, domain) ON DELETE CASCADE,
+    UNIQUE (group_id, group_domain),
+    CONSTRAINT xor_user_group CHECK ((to_username IS NULL) <> (to_group_id IS NULL)),
+    CONSTRAINT until_iff_user CHECK ((to_username IS NULL) = ("until" IS NULL))
+);
+
+CREATE TRIGGER archive_deleted_ownership_transfer BEFORE DELETE ON "ownership_transfers"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
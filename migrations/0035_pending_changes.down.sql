@@ -0,0 +1,3 @@
+DROP TABLE "pending_changes";
+
+DROP TYPE "pending_change_kind";
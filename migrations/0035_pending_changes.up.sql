@@ -0,0 +1,41 @@
+-- Structural changes (new subgroups and permission assignments) to groups in
+-- protected domains are not applied immediately, but instead stored here until
+-- a second authorized person approves (or rejects) them.
+
+CREATE TYPE "pending_change_kind" AS ENUM ('add_subgroup', 'assign_permission');
+
+CREATE TABLE "pending_changes" (
+    id           UUID                  PRIMARY KEY DEFAULT gen_random_uuid(),
+    kind         PENDING_CHANGE_KIND   NOT NULL,
+    group_id     SLUG                  NOT NULL, -- parent group or assignee
+    group_domain DOMAIN                NOT NULL,
+
+    -- add_subgroup
+    child_id     SLUG,
+    child_domain DOMAIN,
+    manager      BOOL,
+
+    -- assign_permission
+    system_id    SLUG,
+    perm_id      SLUG,
+    scope        TEXT                  CHECK (scope <> ''),
+
+    proposed_by  USERNAME              NOT NULL,
+    proposed_at  TIMESTAMPTZ           NOT NULL DEFAULT now(),
+
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain) ON DELETE CASCADE,
+    FOREIGN KEY (child_id, child_domain) REFERENCES "groups" (id, domain) ON DELETE CASCADE,
+    FOREIGN KEY (system_id, perm_id) REFERENCES "permissions" (system_id, perm_id) ON DELETE CASCADE,
+    CONSTRAINT add_subgroup_fields CHECK (
+        (kind = 'add_subgroup') = (child_id IS NOT NULL AND manager IS NOT NULL)
+    ),
+    CONSTRAINT assign_permission_fields CHECK (
+        (kind = 'assign_permission') = (system_id IS NOT NULL)
+    )
+);
+
+CREATE INDEX ON "pending_changes" (group_id, group_domain);
+
+-- (so that restoring a group or permission brings back changes awaiting approval)
+CREATE TRIGGER archive_deleted_pending_change BEFORE DELETE ON "pending_changes"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
@@ -0,0 +1,5 @@
+ALTER TABLE "tag_assignments"
+    DROP CONSTRAINT unverified_has_secret,
+    DROP COLUMN verification_sent_at,
+    DROP COLUMN verification_secret,
+    DROP COLUMN verified;
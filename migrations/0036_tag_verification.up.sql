@@ -0,0 +1,11 @@
+-- Self-service tags whose content is an email address (e.g., a personal email)
+-- are only considered verified once the user clicks a link sent to it, so that
+-- typos don't propagate into integrations. Everything else is trusted as-is.
+
+ALTER TABLE "tag_assignments"
+    ADD COLUMN verified             BOOL        NOT NULL DEFAULT TRUE,
+    ADD COLUMN verification_secret  UUID        UNIQUE,
+    ADD COLUMN verification_sent_at TIMESTAMPTZ,
+    ADD CONSTRAINT unverified_has_secret CHECK (
+        verified OR (verification_secret IS NOT NULL AND verification_sent_at IS NOT NULL)
+    );
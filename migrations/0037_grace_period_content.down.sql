@@ -0,0 +1,9 @@
+UPDATE "tag_assignments"
+SET content = NULL
+WHERE system_id = 'gworkspace'
+    AND tag_id = 'grace-period';
+
+UPDATE "tags"
+SET has_content = FALSE
+WHERE system_id = 'gworkspace'
+    AND tag_id = 'grace-period';
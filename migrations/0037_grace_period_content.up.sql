@@ -0,0 +1,14 @@
+-- The gworkspace `grace-period` tag now specifies how long the grace period
+-- is, so existing assignments keep their previous (hard-coded) length of one
+-- month. The tag itself is otherwise updated upon startup from the manifest.
+
+UPDATE "tags"
+SET has_content = TRUE
+WHERE system_id = 'gworkspace'
+    AND tag_id = 'grace-period';
+
+UPDATE "tag_assignments"
+SET content = 'P1M'
+WHERE system_id = 'gworkspace'
+    AND tag_id = 'grace-period'
+    AND content IS NULL;
@@ -0,0 +1,4 @@
+DELETE FROM "permissions"
+WHERE system_id = 'hive'
+    AND perm_id = 'assign-tag';
+-- ^ this cascades to permission_assignments
@@ -0,0 +1,2 @@
+INSERT INTO "permissions" (system_id, perm_id, has_scope, description) VALUES
+    ('hive', 'assign-tag', TRUE, 'Assign and unassign a specific tag (scope is `<system>:<tag>`)');
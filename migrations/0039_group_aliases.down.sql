@@ -0,0 +1,50 @@
+DROP TABLE "group_aliases";
+
+ALTER TABLE "direct_memberships"
+    DROP CONSTRAINT direct_memberships_group_id_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE;
+
+ALTER TABLE "subgroups"
+    DROP CONSTRAINT subgroups_parent_id_parent_domain_fkey,
+    DROP CONSTRAINT subgroups_child_id_child_domain_fkey,
+    ADD FOREIGN KEY (parent_id, parent_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE,
+    ADD FOREIGN KEY (child_id, child_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE;
+
+ALTER TABLE "tag_assignments"
+    DROP CONSTRAINT tag_assignments_group_id_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE;
+
+ALTER TABLE "permission_assignments"
+    DROP CONSTRAINT permission_assignments_group_id_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE;
+
+ALTER TABLE "membership_exclusions"
+    DROP CONSTRAINT membership_exclusions_group_id_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE;
+
+ALTER TABLE "calendar_feeds"
+    DROP CONSTRAINT calendar_feeds_group_id_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE;
+
+ALTER TABLE "ownership_transfers"
+    DROP CONSTRAINT ownership_transfers_group_id_group_domain_fkey,
+    DROP CONSTRAINT ownership_transfers_to_group_id_to_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE,
+    ADD FOREIGN KEY (to_group_id, to_group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE;
+
+ALTER TABLE "pending_changes"
+    DROP CONSTRAINT pending_changes_group_id_group_domain_fkey,
+    DROP CONSTRAINT pending_changes_child_id_child_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE,
+    ADD FOREIGN KEY (child_id, child_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE;
@@ -0,0 +1,72 @@
+-- Groups can now be renamed (i.e., have their ID and/or domain changed) without
+-- being recreated, so every reference to a group must follow along. The old
+-- key is kept as an alias, so that old links (and API consumers) can still
+-- find the group, and so that integrations can rename what they mirror.
+
+ALTER TABLE "direct_memberships"
+    DROP CONSTRAINT direct_memberships_group_id_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE "subgroups"
+    DROP CONSTRAINT subgroups_parent_id_parent_domain_fkey,
+    DROP CONSTRAINT subgroups_child_id_child_domain_fkey,
+    ADD FOREIGN KEY (parent_id, parent_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE,
+    ADD FOREIGN KEY (child_id, child_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE "tag_assignments"
+    DROP CONSTRAINT tag_assignments_group_id_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE "permission_assignments"
+    DROP CONSTRAINT permission_assignments_group_id_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE "membership_exclusions"
+    DROP CONSTRAINT membership_exclusions_group_id_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE "calendar_feeds"
+    DROP CONSTRAINT calendar_feeds_group_id_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE "ownership_transfers"
+    DROP CONSTRAINT ownership_transfers_group_id_group_domain_fkey,
+    DROP CONSTRAINT ownership_transfers_to_group_id_to_group_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE,
+    ADD FOREIGN KEY (to_group_id, to_group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE "pending_changes"
+    DROP CONSTRAINT pending_changes_group_id_group_domain_fkey,
+    DROP CONSTRAINT pending_changes_child_id_child_domain_fkey,
+    ADD FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE,
+    ADD FOREIGN KEY (child_id, child_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+CREATE TABLE "group_aliases" (
+    old_id       SLUG        NOT NULL,
+    old_domain   DOMAIN      NOT NULL,
+    group_id     SLUG        NOT NULL,
+    group_domain DOMAIN      NOT NULL,
+    renamed_by   USERNAME    NOT NULL,
+    renamed_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (old_id, old_domain),
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX ON "group_aliases" (group_id, group_domain);
+
+-- (so that old links keep working for a group restored after its deletion)
+CREATE TRIGGER archive_deleted_group_alias BEFORE DELETE ON "group_aliases"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
@@ -0,0 +1,20 @@
+-- Links to a group's external resources (e.g., drive folders, wikis, meeting
+-- notes), shown on its page in the order given by "position" (ascending).
+
+CREATE TABLE "group_links" (
+    id           UUID    PRIMARY KEY DEFAULT gen_random_uuid(),
+    group_id     SLUG    NOT NULL,
+    group_domain DOMAIN  NOT NULL,
+    name         TEXT    NOT NULL CHECK (name <> ''),
+    url          TEXT    NOT NULL CHECK (url ~ '^https?://'),
+    position     INTEGER NOT NULL,
+
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX "group_links_group_idx" ON "group_links" (group_id, group_domain, position);
+
+-- (so that they're restored as well when undoing a group's deletion)
+CREATE TRIGGER archive_deleted_group_link BEFORE DELETE ON "group_links"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
@@ -0,0 +1,4 @@
+DELETE FROM "tags"
+WHERE system_id = 'hive'
+    AND tag_id = 'public';
+-- ^ this cascades to tag_assignments
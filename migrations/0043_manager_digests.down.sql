@@ -0,0 +1,2 @@
+DROP TABLE "digest_runs";
+DROP TABLE "digest_opt_outs";
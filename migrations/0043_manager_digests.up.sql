@@ -0,0 +1,16 @@
+-- Weekly digests summarize recent changes in the groups each manager is
+-- responsible for. Every manager receives them by default, so only those who
+-- have opted out are recorded.
+
+CREATE TABLE "digest_opt_outs" (
+    username     USERNAME    PRIMARY KEY,
+    opted_out_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One row per round of digests, such that a restart doesn't cause them to be
+-- sent again (and so that each round covers everything since the last one).
+
+CREATE TABLE "digest_runs" (
+    stamp        TIMESTAMPTZ PRIMARY KEY DEFAULT NOW(),
+    n_recipients INTEGER
+);
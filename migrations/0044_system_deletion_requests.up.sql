@@ -0,0 +1,12 @@
+-- Systems in active use (with permission assignments, or API tokens used
+-- recently) are not deleted right away: the first request is only recorded
+-- here, and the deletion only goes through once a second authorized person
+-- confirms it.
+
+CREATE TABLE "system_deletion_requests" (
+    system_id    SLUG        PRIMARY KEY,
+    requested_by USERNAME    NOT NULL,
+    requested_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    FOREIGN KEY (system_id) REFERENCES "systems" (id) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+DROP TRIGGER archive_deleted_api_token ON "api_tokens";
+
+DROP INDEX "deletions_deleted_at_idx";
+
+DELETE FROM "permissions"
+WHERE system_id = 'hive'
+    AND perm_id = 'restore-deletions';
+-- ^ this cascades to permission_assignments
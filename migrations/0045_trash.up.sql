@@ -0,0 +1,14 @@
+-- Deletions are now kept for a while longer than the actor's undo window, so
+-- that anything deleted recently (by anyone) can still be restored from the
+-- trash by those with the permission below. API tokens can be restored too.
+
+INSERT INTO "permissions" (system_id, perm_id, has_scope, description) VALUES
+    ('hive', 'restore-deletions', FALSE, 'Browse and restore anything deleted recently, by anyone');
+
+INSERT INTO "permission_assignments" (system_id, perm_id, scope, group_id, group_domain) VALUES
+    ('hive', 'restore-deletions', NULL, 'root', 'hive.internal');
+
+CREATE INDEX "deletions_deleted_at_idx" ON "deletions" (deleted_at);
+
+CREATE TRIGGER archive_deleted_api_token BEFORE DELETE ON "api_tokens"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
@@ -0,0 +1,7 @@
+DROP TRIGGER enqueue_webhook_deliveries ON "audit_logs";
+DROP FUNCTION enqueue_webhook_deliveries;
+
+DROP TABLE "webhook_deliveries";
+DROP TABLE "webhooks";
+
+DROP TYPE "webhook_event";
@@ -0,0 +1,82 @@
+-- Systems can subscribe to changes instead of polling for them. Every audit
+-- log entry of a subscribed kind is queued as a delivery for each matching
+-- webhook, within the same transaction as the change itself, and deliveries
+-- are then sent out in the background (keeping their outcome as history).
+
+CREATE TYPE "webhook_event" AS ENUM (
+    'membership_changed',
+    'permission_assigned',
+    'tag_assigned'
+);
+
+CREATE TABLE "webhooks" (
+    id         UUID            PRIMARY KEY DEFAULT gen_random_uuid(),
+    system_id  SLUG            NOT NULL,
+    url        TEXT            NOT NULL CHECK (url ~ '^https?://'),
+    secret     UUID            NOT NULL DEFAULT gen_random_uuid(),
+    events     WEBHOOK_EVENT[] NOT NULL CHECK (cardinality(events) > 0),
+    created_by USERNAME        NOT NULL,
+    created_at TIMESTAMPTZ     NOT NULL DEFAULT NOW(),
+
+    FOREIGN KEY (system_id) REFERENCES "systems" (id) ON DELETE CASCADE
+);
+
+CREATE INDEX "webhooks_system_id_idx" ON "webhooks" (system_id);
+
+CREATE TABLE "webhook_deliveries" (
+    id           UUID          PRIMARY KEY DEFAULT gen_random_uuid(),
+    webhook_id   UUID          NOT NULL,
+    event        WEBHOOK_EVENT NOT NULL,
+    payload      JSONB         NOT NULL,
+    created_at   TIMESTAMPTZ   NOT NULL DEFAULT NOW(),
+    attempted_at TIMESTAMPTZ,             -- NULL => still pending
+    status_code  INTEGER,                 -- NULL => no response at all
+    error        TEXT,
+    replay_of    UUID,
+
+    FOREIGN KEY (webhook_id) REFERENCES "webhooks" (id) ON DELETE CASCADE,
+    FOREIGN KEY (replay_of) REFERENCES "webhook_deliveries" (id) ON DELETE SET NULL
+);
+
+CREATE INDEX "webhook_deliveries_webhook_id_idx" ON "webhook_deliveries" (webhook_id, created_at);
+CREATE INDEX "webhook_deliveries_pending_idx" ON "webhook_deliveries" (created_at)
+    WHERE attempted_at IS NULL;
+
+CREATE FUNCTION enqueue_webhook_deliveries() RETURNS TRIGGER AS $$
+DECLARE
+    subscribed_event WEBHOOK_EVENT;
+    target_system_id TEXT; -- NULL => relevant to every system
+BEGIN
+    IF NEW.target_kind = 'membership' THEN
+        subscribed_event := 'membership_changed';
+    ELSIF NEW.target_kind = 'permission_assignment' AND NEW.action_kind = 'create' THEN
+        subscribed_event := 'permission_assigned';
+        target_system_id := split_part(substr(NEW.target_id, 2), ':', 1); -- $system:perm
+    ELSIF NEW.target_kind = 'tag_assignment' AND NEW.action_kind = 'create' THEN
+        subscribed_event := 'tag_assigned';
+        target_system_id := split_part(substr(NEW.target_id, 2), ':', 1); -- #system:tag
+    ELSE
+        RETURN NULL;
+    END IF;
+
+    INSERT INTO webhook_deliveries (webhook_id, event, payload)
+    SELECT wh.id,
+        subscribed_event,
+        jsonb_build_object(
+            'event', subscribed_event,
+            'action', NEW.action_kind,
+            'target', NEW.target_id,
+            'actor', NEW.actor,
+            'stamp', NEW.stamp,
+            'details', NEW.details
+        )
+    FROM webhooks wh
+    WHERE subscribed_event = ANY(wh.events)
+        AND (target_system_id IS NULL OR wh.system_id = target_system_id);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER enqueue_webhook_deliveries AFTER INSERT ON "audit_logs"
+    FOR EACH ROW EXECUTE FUNCTION enqueue_webhook_deliveries();
@@ -0,0 +1 @@
+DROP TABLE "member_list_shares";
@@ -0,0 +1,23 @@
+-- Managers can share a read-only snapshot of their group's member list with
+-- external parties (e.g., a venue needing a name list) through a link, which
+-- is signed and only valid until it expires (or is revoked by deleting it).
+-- The snapshot is taken on creation, so later changes are never revealed.
+
+CREATE TABLE "member_list_shares" (
+    id           UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    group_id     SLUG        NOT NULL,
+    group_domain DOMAIN      NOT NULL,
+    members      JSONB       NOT NULL,
+    created_by   USERNAME    NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at   TIMESTAMPTZ NOT NULL,
+
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK (expires_at > created_at)
+);
+
+CREATE INDEX "member_list_shares_group_idx" ON "member_list_shares" (group_id, group_domain);
+
+CREATE TRIGGER archive_deleted_member_list_share BEFORE DELETE ON "member_list_shares"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
@@ -0,0 +1,7 @@
+-- Groups can designate one or more of their direct members as official
+-- contact persons (e.g., whoever should be reached about the group from the
+-- outside), which is independent from the manager flag, since the formal
+-- contact isn't always the person administering membership.
+
+ALTER TABLE "direct_memberships"
+    ADD COLUMN contact BOOL NOT NULL DEFAULT FALSE;
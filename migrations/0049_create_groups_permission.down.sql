@@ -0,0 +1,4 @@
+DELETE FROM "permissions"
+WHERE system_id = 'hive'
+    AND perm_id = 'create-groups';
+-- ^ this cascades to permission_assignments
@@ -0,0 +1,6 @@
+-- Allows creating new groups in a domain (scope is `*` or `@<domain>`) without
+-- being able to manage every group that already exists there, e.g. for
+-- whoever administers a domain on behalf of some committee.
+
+INSERT INTO "permissions" (system_id, perm_id, has_scope, description) VALUES
+    ('hive', 'create-groups', TRUE, 'Create new groups in @domain');
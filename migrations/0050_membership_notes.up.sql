@@ -0,0 +1,5 @@
+-- Direct memberships can carry a free-text note on why someone is in a group
+-- (e.g., "elected at SM 2024-05-12"), since end dates alone don't say much.
+
+ALTER TABLE "direct_memberships"
+    ADD COLUMN note TEXT CHECK (length(note) <= 500);
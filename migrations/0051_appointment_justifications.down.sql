@@ -0,0 +1,2 @@
+ALTER TABLE "direct_memberships"
+    DROP COLUMN justification;
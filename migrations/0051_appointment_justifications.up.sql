@@ -0,0 +1,7 @@
+-- Memberships extending beyond the default appointment bounds (through
+-- $hive:long-term-appointment) must be justified when they are created or
+-- extended, so that unusual appointments document themselves for later
+-- reviews. Pre-existing ones are left without a justification (NULL).
+
+ALTER TABLE "direct_memberships"
+    ADD COLUMN justification TEXT CHECK (length(justification) <= 500);
@@ -0,0 +1,3 @@
+ALTER TABLE "groups"
+    DROP COLUMN member_cap,
+    DROP COLUMN member_cap_enforced;
@@ -0,0 +1,8 @@
+-- Groups can optionally be capped to a maximum number of (direct) members,
+-- e.g., the size of a board, to catch mistakes like importing an entire
+-- cohort into a small committee. By default, exceeding the cap only warns;
+-- enforced caps instead reject new memberships beyond it.
+
+ALTER TABLE "groups"
+    ADD COLUMN member_cap INT CHECK (member_cap > 0),
+    ADD COLUMN member_cap_enforced BOOL NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+DROP FUNCTION all_members_of(group_id SLUG, group_domain DOMAIN, at TIMESTAMP);
+
+ALTER TABLE "direct_memberships"
+    DROP COLUMN from_time,
+    DROP COLUMN until_time;
@@ -0,0 +1,59 @@
+-- Memberships can optionally start and/or end at a specific time of day
+-- (e.g., effective from 12:00 on handover day), for domains that need it.
+-- NULL keeps the date-only behavior: from the start of "from" until the end
+-- of "until", both in the deployment's timezone.
+
+-- Date-based lookups (including permission checks) still consider someone a
+-- member for the whole day; only the timestamp variant of `all_members_of`
+-- below, used when listing current members (e.g., for integrations), takes
+-- the time of day into account.
+
+ALTER TABLE "direct_memberships"
+    ADD COLUMN from_time TIME,
+    ADD COLUMN until_time TIME,
+    ADD CHECK ("from" < "until" OR from_time IS NULL OR until_time IS NULL OR from_time < until_time);
+
+CREATE FUNCTION all_members_of(group_id SLUG, group_domain DOMAIN, at TIMESTAMP)
+RETURNS TABLE (username USERNAME, manager BOOL, "from" DATE, "until" DATE, path GROUP_REF[])
+AS $$
+    -- direct members
+    SELECT
+        dm.username,
+        dm.manager,
+        dm."from",
+        dm."until",
+        ARRAY[(dm.group_id, dm.group_domain)::GROUP_REF] AS path
+    FROM direct_memberships dm
+    WHERE dm.group_id = all_members_of.group_id
+        AND dm.group_domain = all_members_of.group_domain
+        AND all_members_of.at >= dm."from" + COALESCE(dm.from_time, '00:00')
+        AND all_members_of.at < dm."until" + COALESCE(dm.until_time, '24:00')
+
+    UNION -- removes duplicates (vs. UNION ALL)
+
+    -- indirect members
+    SELECT
+        dm.username,
+        sg.manager,
+        dm."from",
+        dm."until",
+        sg.path || (all_members_of.group_id, all_members_of.group_domain)::GROUP_REF AS path
+    FROM all_subgroups_of(group_id, group_domain) sg
+    JOIN direct_memberships dm
+        ON dm.group_id = sg.child_id
+        AND dm.group_domain = sg.child_domain
+        AND all_members_of.at >= dm."from" + COALESCE(dm.from_time, '00:00')
+        AND all_members_of.at < dm."until" + COALESCE(dm.until_time, '24:00')
+    WHERE NOT EXISTS (
+        -- the last element of sg.path is where the user is a direct member,
+        -- so it's not subject to exclusions; all others (plus the group
+        -- itself) are only reached indirectly
+        SELECT 1
+        FROM membership_exclusions me
+        WHERE me.username = dm.username
+            AND (
+                (me.group_id = all_members_of.group_id AND me.group_domain = all_members_of.group_domain)
+                OR (me.group_id, me.group_domain)::GROUP_REF = ANY(trim_array(sg.path, 1))
+            )
+    )
+$$ LANGUAGE SQL;
@@ -0,0 +1,7 @@
+-- Groups can suggest a default end date for new memberships, which prefills
+-- the add-member form so that a committee's members end consistently. It is
+-- stored as written (e.g., `6m`, `1y`, `end-of-year`) and interpreted by Hive
+-- relative to each membership's start date.
+
+ALTER TABLE "groups"
+    ADD COLUMN default_membership_duration TEXT CHECK (length(default_membership_duration) <= 32);
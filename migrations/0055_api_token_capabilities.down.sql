@@ -0,0 +1,5 @@
+ALTER TABLE "api_tokens"
+    DROP CONSTRAINT single_area,
+    DROP COLUMN read_only,
+    DROP COLUMN groups_only,
+    DROP COLUMN permissions_only;
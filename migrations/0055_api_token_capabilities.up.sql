@@ -0,0 +1,10 @@
+-- API tokens can be structurally limited to reading (no changes through the
+-- API at all) and/or to a single area (groups or permissions), regardless of
+-- which permissions are assigned to them; e.g., for tokens embedded in
+-- low-trust services.
+
+ALTER TABLE "api_tokens"
+    ADD COLUMN read_only        BOOL NOT NULL DEFAULT FALSE,
+    ADD COLUMN groups_only      BOOL NOT NULL DEFAULT FALSE,
+    ADD COLUMN permissions_only BOOL NOT NULL DEFAULT FALSE,
+    ADD CONSTRAINT single_area CHECK (NOT (groups_only AND permissions_only));
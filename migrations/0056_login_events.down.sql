@@ -0,0 +1,3 @@
+DROP TABLE "login_events";
+
+DROP TYPE "login_alert";
@@ -0,0 +1,28 @@
+-- Every attempt to log in (i.e., every OIDC callback) is recorded, so that
+-- users can review where their account was used and administrators can be
+-- alerted about anomalies. Failed attempts usually can't be attributed to any
+-- user, since the identity provider never told us who it was.
+
+CREATE TYPE "login_alert" AS ENUM (
+    'repeated_failures',
+    'dormant_admin'
+);
+
+CREATE TABLE "login_events" (
+    id         UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    username   USERNAME,               -- NULL => unknown (failed attempt)
+    success    BOOL        NOT NULL,
+    ip         TEXT,
+    user_agent TEXT,
+    error      TEXT,                   -- why the attempt failed
+    alert      LOGIN_ALERT,            -- raised because of this event, if any
+    stamp      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    CHECK (success = (error IS NULL)),
+    CHECK (username IS NOT NULL OR NOT success)
+);
+
+CREATE INDEX "login_events_username_idx" ON "login_events" (username, stamp)
+    WHERE username IS NOT NULL;
+CREATE INDEX "login_events_failures_idx" ON "login_events" (ip, stamp)
+    WHERE NOT success;
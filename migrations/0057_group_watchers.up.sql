@@ -0,0 +1,19 @@
+-- Users who can view a group without being one of its managers (e.g., auditors
+-- or interested board members) can watch it, which includes it in their weekly
+-- digest alongside the groups they manage.
+
+CREATE TABLE "group_watchers" (
+    username     USERNAME    NOT NULL,
+    group_id     SLUG        NOT NULL,
+    group_domain DOMAIN      NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (username, group_id, group_domain),
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX "group_watchers_group_idx" ON "group_watchers" (group_id, group_domain);
+
+CREATE TRIGGER archive_deleted_group_watcher BEFORE DELETE ON "group_watchers"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
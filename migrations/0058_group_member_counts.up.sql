@@ -0,0 +1,21 @@
+-- Daily snapshots of how many members each group has, so that changes in size
+-- can be followed across years (computing them retroactively from memberships
+-- would be possible, but expensive, and wrong after anything is deleted).
+
+CREATE TABLE "group_member_counts" (
+    group_id     SLUG    NOT NULL,
+    group_domain DOMAIN  NOT NULL,
+    day          DATE    NOT NULL,
+    n_direct     INTEGER NOT NULL CHECK (n_direct >= 0),
+    n_total      INTEGER NOT NULL CHECK (n_total >= n_direct),
+
+    PRIMARY KEY (group_id, group_domain, day),
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX "group_member_counts_day_idx" ON "group_member_counts" (day);
+
+-- (a restored group keeps its history, since it can't be recomputed)
+CREATE TRIGGER archive_deleted_group_member_count BEFORE DELETE ON "group_member_counts"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
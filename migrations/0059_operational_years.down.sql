@@ -0,0 +1,7 @@
+ALTER TABLE "direct_memberships"
+    DROP COLUMN operational_year_id;
+
+DROP TABLE "operational_years";
+
+-- Postgres doesn't support removing enum values, so we just keep it,
+-- which should be fine since the UP migration only adds IF NOT EXISTS
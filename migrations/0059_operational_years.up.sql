@@ -0,0 +1,24 @@
+-- Operational years ("verksamhetsår") are how the organization actually thinks
+-- about time: most mandates last for one of them, and they rarely coincide
+-- with calendar years. Both bounds are inclusive, and years cannot overlap.
+
+-- Memberships can reference the year they were appointed for, which keeps
+-- their dates in sync if the year's bounds are later adjusted (e.g., when the
+-- general meeting is postponed); they still store their own dates regardless.
+
+CREATE TABLE "operational_years" (
+    id      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name    TEXT NOT NULL UNIQUE CHECK (length(name) <= 32),
+    "start" DATE NOT NULL,
+    "end"   DATE NOT NULL,
+
+    CHECK ("start" < "end"),
+    EXCLUDE USING gist (daterange("start", "end", '[]') WITH &&)
+);
+
+ALTER TABLE "direct_memberships"
+    ADD COLUMN operational_year_id UUID REFERENCES "operational_years" (id) ON DELETE SET NULL;
+
+CREATE INDEX "direct_memberships_operational_year_idx" ON "direct_memberships" (operational_year_id);
+
+ALTER TYPE "target_kind" ADD VALUE IF NOT EXISTS 'operational_year';
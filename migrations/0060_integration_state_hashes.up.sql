@@ -0,0 +1,14 @@
+-- Hashes of the state that integrations last successfully pushed for each
+-- entity they mirror (e.g., a Google group's settings and members), so that
+-- entities that haven't changed since can be skipped in later runs. Entity
+-- keys are up to each integration, since they needn't be Hive groups at all.
+
+CREATE TABLE "integration_state_hashes" (
+    integration_id SLUG        NOT NULL,
+    entity         TEXT        NOT NULL,
+    hash           TEXT        NOT NULL,
+    pushed_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (integration_id, entity),
+    FOREIGN KEY (integration_id) REFERENCES "systems" (id) ON DELETE CASCADE
+);
@@ -0,0 +1,6 @@
+-- Tags declared in an integration's manifest are marked as managed whenever it
+-- is set up on startup (and unmarked if it no longer declares them), so that
+-- they can be told apart from tags that are safe to delete.
+
+ALTER TABLE "tags"
+    ADD COLUMN managed BOOL NOT NULL DEFAULT FALSE;
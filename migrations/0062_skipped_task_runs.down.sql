@@ -0,0 +1,5 @@
+DELETE FROM "integration_task_runs"
+WHERE skipped;
+
+ALTER TABLE "integration_task_runs"
+    DROP COLUMN skipped;
@@ -0,0 +1,8 @@
+-- Runs are now also guarded by an advisory lock held for as long as they last,
+-- so a run that starts while another one of the same task is still ongoing
+-- (e.g., a manual run overlapping the scheduled one) is skipped; that is still
+-- recorded as an (instantly finished) run, such that it shows up in the run
+-- history instead of only in the server logs.
+
+ALTER TABLE "integration_task_runs"
+    ADD COLUMN skipped BOOL NOT NULL DEFAULT FALSE;
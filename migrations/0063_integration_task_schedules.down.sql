@@ -0,0 +1 @@
+DROP TABLE "integration_task_schedules";
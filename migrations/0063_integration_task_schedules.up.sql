@@ -0,0 +1,13 @@
+-- Deployment-specific overrides of the cron schedules that integration tasks
+-- declare in their manifests (e.g., syncing more often during onboarding week).
+-- They are validated when set, and again when tasks are scheduled on startup,
+-- in which case an invalid one is ignored in favour of the manifest's.
+
+CREATE TABLE "integration_task_schedules" (
+    integration_id SLUG NOT NULL,
+    task_id        SLUG NOT NULL,
+    schedule       TEXT NOT NULL,
+
+    PRIMARY KEY (integration_id, task_id),
+    FOREIGN KEY (integration_id) REFERENCES "systems" (id) ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+DELETE FROM "permissions"
+WHERE system_id = 'hive'
+    AND perm_id = 'api-view-integration-runs';
+-- ^ this cascades to permission_assignments
@@ -0,0 +1,5 @@
+-- Read-only counterpart to `api-run-integrations`, e.g. for monitoring systems
+-- that alert on failed runs without being able to trigger any themselves.
+
+INSERT INTO "permissions" (system_id, perm_id, has_scope, description) VALUES
+    ('hive', 'api-view-integration-runs', FALSE, 'View integration task runs and their logs via Hive''s API');
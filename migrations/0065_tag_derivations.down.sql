@@ -0,0 +1,33 @@
+CREATE OR REPLACE VIEW "all_tag_assignments"
+    (id, system_id, tag_id, content, username, group_id, group_domain) AS
+    SELECT
+        CASE
+            WHEN th.descendant_id = th.ancestor_id
+                AND th.descendant_system_id = th.ancestor_system_id
+            THEN ta.id
+            ELSE NULL -- if indirect assignment, id is NULL
+        END AS id,
+
+        th.ancestor_system_id AS system_id,
+        th.ancestor_id        AS tag_id,
+
+        CASE
+            WHEN th.descendant_id = th.ancestor_id
+                AND th.descendant_system_id = th.ancestor_system_id
+            THEN ta.content
+            ELSE NULL -- if indirect assignment, content is NULL
+        END AS content,
+
+        ta.username,
+        ta.group_id,
+        ta.group_domain
+    FROM tag_assignments ta
+    JOIN tag_ancestry th
+        ON ta.tag_id = th.descendant_id
+            AND ta.system_id = th.descendant_system_id;
+
+DROP VIEW "derived_tag_assignments";
+
+DROP TRIGGER archive_deleted_tag_derivation ON "tag_derivations";
+
+DROP TABLE "tag_derivations";
@@ -0,0 +1,82 @@
+-- Tags can be declared as derived from a permission, so that every group that
+-- is assigned it (directly, with the given scope or a wildcard one, if any) is
+-- considered tagged too, without having to keep both in sync by hand. Derived
+-- assignments only exist in `all_tag_assignments` (like indirect ones through
+-- subtags, without an ID or content), so they are never out of date. Deriving
+-- permissions from tags instead is deliberately not supported, since tags can
+-- be much easier to come by than permissions.
+
+CREATE TABLE "tag_derivations" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+
+    system_id      SLUG NOT NULL,
+    tag_id         SLUG NOT NULL,
+    perm_system_id SLUG NOT NULL,
+    perm_id        SLUG NOT NULL,
+    scope          TEXT          CHECK (scope <> ''),
+
+    FOREIGN KEY (system_id, tag_id)       REFERENCES "tags"        (system_id, tag_id)  ON DELETE CASCADE,
+    FOREIGN KEY (perm_system_id, perm_id) REFERENCES "permissions" (system_id, perm_id) ON DELETE CASCADE,
+    CONSTRAINT no_duplicate_tag_derivations
+        UNIQUE NULLS NOT DISTINCT (system_id, tag_id, perm_system_id, perm_id, scope)
+);
+
+COMMENT ON COLUMN "tag_derivations".scope IS 'NULL means any scope';
+
+-- (so that they're restored as well when undoing a tag's or permission's deletion)
+CREATE TRIGGER archive_deleted_tag_derivation BEFORE DELETE ON "tag_derivations"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
+
+
+CREATE VIEW "derived_tag_assignments" (system_id, tag_id, group_id, group_domain) AS
+    SELECT DISTINCT td.system_id, td.tag_id, pa.group_id, pa.group_domain
+    FROM tag_derivations td
+    JOIN permission_assignments pa
+        ON pa.system_id = td.perm_system_id
+            AND pa.perm_id = td.perm_id
+            AND (td.scope IS NULL OR pa.scope = td.scope OR pa.scope = '*')
+    WHERE pa.group_id IS NOT NULL
+        AND pa.group_domain IS NOT NULL;
+
+
+-- same as before, but with derived assignments as an additional (indirect)
+-- source, which are then also propagated through subtags
+CREATE OR REPLACE VIEW "all_tag_assignments"
+    (id, system_id, tag_id, content, username, group_id, group_domain) AS
+    WITH sources AS (
+        SELECT ta.id, ta.system_id, ta.tag_id, ta.content, ta.username, ta.group_id, ta.group_domain
+        FROM tag_assignments ta
+
+        UNION ALL
+
+        -- (casts so that column types stay exactly as they were)
+        SELECT
+            NULL::UUID, dta.system_id, dta.tag_id, NULL::TEXT, NULL::USERNAME,
+            dta.group_id, dta.group_domain
+        FROM derived_tag_assignments dta
+    )
+    SELECT
+        CASE
+            WHEN th.descendant_id = th.ancestor_id
+                AND th.descendant_system_id = th.ancestor_system_id
+            THEN ta.id
+            ELSE NULL -- if indirect assignment, id is NULL
+        END AS id,
+
+        th.ancestor_system_id AS system_id,
+        th.ancestor_id        AS tag_id,
+
+        CASE
+            WHEN th.descendant_id = th.ancestor_id
+                AND th.descendant_system_id = th.ancestor_system_id
+            THEN ta.content
+            ELSE NULL -- if indirect assignment, content is NULL
+        END AS content,
+
+        ta.username,
+        ta.group_id,
+        ta.group_domain
+    FROM sources ta
+    JOIN tag_ancestry th
+        ON ta.tag_id = th.descendant_id
+            AND ta.system_id = th.descendant_system_id;
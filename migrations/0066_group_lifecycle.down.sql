@@ -0,0 +1,4 @@
+ALTER TABLE "groups"
+    DROP COLUMN state;
+
+DROP TYPE "group_state";
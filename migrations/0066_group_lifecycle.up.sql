@@ -0,0 +1,10 @@
+-- Where each group is in its lifecycle, instead of marking groups that are no
+-- longer in use by renaming them (e.g., with an "(inactive)" suffix). Dormant
+-- and archived groups are left out of default listings, but otherwise behave
+-- exactly like any other group (e.g., they can still be looked up by key).
+-- Which transitions are allowed is only enforced by the application.
+
+CREATE TYPE "group_state" AS ENUM ('proposed', 'active', 'dormant', 'archived');
+
+ALTER TABLE "groups"
+    ADD COLUMN state GROUP_STATE NOT NULL DEFAULT 'active';
@@ -0,0 +1,3 @@
+DROP TABLE "membership_email_opt_outs";
+DROP TABLE "membership_emails";
+DROP TYPE "membership_email_kind";
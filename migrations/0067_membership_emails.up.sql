@@ -0,0 +1,35 @@
+-- Users are emailed whenever one of their direct memberships is added, edited
+-- or removed by someone else, so that access never appears or vanishes by
+-- surprise. Emails are queued within the same transaction as the change itself
+-- (such that nothing is sent for changes that are rolled back), and then sent
+-- out in the background. Everyone receives them by default, so only those who
+-- have opted out are recorded.
+
+CREATE TYPE "membership_email_kind" AS ENUM ('added', 'edited', 'removed');
+
+CREATE TABLE "membership_emails" (
+    id           UUID                  PRIMARY KEY DEFAULT gen_random_uuid(),
+    kind         MEMBERSHIP_EMAIL_KIND NOT NULL,
+    username     USERNAME              NOT NULL,
+    group_id     SLUG                  NOT NULL,
+    group_domain DOMAIN                NOT NULL,
+    "from"       DATE                  NOT NULL,
+    "until"      DATE                  NOT NULL,
+    manager      BOOLEAN               NOT NULL,
+    actor        USERNAME              NOT NULL,
+    created_at   TIMESTAMPTZ           NOT NULL DEFAULT NOW(),
+
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX "membership_emails_created_at_idx" ON "membership_emails" (created_at);
+
+-- Queued emails are intentionally not archived when their group is deleted:
+-- restoring it (possibly weeks later) should not send out stale notifications
+-- about changes that happened before the deletion.
+
+CREATE TABLE "membership_email_opt_outs" (
+    username     USERNAME    PRIMARY KEY,
+    opted_out_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
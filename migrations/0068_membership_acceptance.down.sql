@@ -0,0 +1,4 @@
+DROP TABLE "pending_memberships";
+
+ALTER TABLE "groups"
+    DROP COLUMN requires_acceptance;
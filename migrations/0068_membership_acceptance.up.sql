@@ -0,0 +1,36 @@
+-- Groups granting sensitive access can require that new members accept their
+-- membership before it takes effect. Until then, it is only kept here (rather
+-- than as a flagged direct membership), so that nothing that considers
+-- memberships (permissions, integrations, member counts...) has to know about
+-- it. Once accepted, it becomes a regular direct membership. Users adding
+-- themselves don't need to accept anything.
+
+ALTER TABLE "groups"
+    ADD COLUMN requires_acceptance BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE "pending_memberships" (
+    id                  UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    username            USERNAME    NOT NULL,
+    group_id            SLUG        NOT NULL,
+    group_domain        DOMAIN      NOT NULL,
+    "from"              DATE        NOT NULL,
+    "until"             DATE        NOT NULL,
+    from_time           TIME,
+    until_time          TIME,
+    manager             BOOLEAN     NOT NULL,
+    note                TEXT,
+    justification       TEXT,
+    operational_year_id UUID        REFERENCES "operational_years" (id) ON DELETE SET NULL,
+    added_by            USERNAME    NOT NULL,
+    added_at            TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK ("from" <= "until")
+);
+
+CREATE INDEX "pending_memberships_username_idx" ON "pending_memberships" (username);
+CREATE INDEX "pending_memberships_group_idx" ON "pending_memberships" (group_id, group_domain);
+
+CREATE TRIGGER archive_deleted_pending_membership BEFORE DELETE ON "pending_memberships"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
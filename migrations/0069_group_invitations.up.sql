@@ -0,0 +1,32 @@
+-- Managers can hand out reusable links that let any logged-in user join their
+-- group directly (e.g., volunteers signing up at an event), without having to
+-- collect usernames first. Everyone joining through the same link gets the
+-- same preset period (from whenever they join, if it already started) and is
+-- never made a manager. Links can optionally be limited to a number of uses
+-- and/or expire at some point; revoking one deletes it. Knowing the (random)
+-- secret is all it takes to join, so it is only ever shown to managers.
+
+CREATE TABLE "group_invitations" (
+    id           UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    secret       UUID        NOT NULL UNIQUE DEFAULT gen_random_uuid(),
+    group_id     SLUG        NOT NULL,
+    group_domain DOMAIN      NOT NULL,
+    "from"       DATE        NOT NULL,
+    "until"      DATE        NOT NULL,
+    max_uses     INTEGER     CHECK (max_uses > 0),
+    uses         INTEGER     NOT NULL DEFAULT 0,
+    expires_at   TIMESTAMPTZ,
+    created_by   USERNAME    NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK ("from" <= "until"),
+    CHECK (uses >= 0 AND (max_uses IS NULL OR uses <= max_uses)),
+    CHECK (expires_at > created_at)
+);
+
+CREATE INDEX "group_invitations_group_idx" ON "group_invitations" (group_id, group_domain);
+
+CREATE TRIGGER archive_deleted_group_invitation BEFORE DELETE ON "group_invitations"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
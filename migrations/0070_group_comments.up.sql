@@ -0,0 +1,24 @@
+-- Internal notes on a group for those who manage it (e.g., "waiting for board
+-- decision before adding X"), never shown to regular members. Comments can
+-- reply to a top-level comment of the same group, forming threads that are
+-- one level deep. They cannot be edited and can only be deleted by their own
+-- author, so that who wrote what (and when) is always accurate.
+
+CREATE TABLE "group_comments" (
+    id           UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    group_id     SLUG        NOT NULL,
+    group_domain DOMAIN      NOT NULL,
+    reply_to     UUID        REFERENCES "group_comments" (id) ON DELETE CASCADE,
+    author       USERNAME    NOT NULL,
+    body         TEXT        NOT NULL CHECK (body <> ''),
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    FOREIGN KEY (group_id, group_domain) REFERENCES "groups" (id, domain)
+        ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK (reply_to <> id)
+);
+
+CREATE INDEX "group_comments_group_idx" ON "group_comments" (group_id, group_domain, created_at);
+
+CREATE TRIGGER archive_deleted_group_comment BEFORE DELETE ON "group_comments"
+    FOR EACH ROW EXECUTE FUNCTION archive_deleted_row();
@@ -0,0 +1,12 @@
+-- Signed requests used to be keyed by the stored (hashed) secret itself. The
+-- signing key is now derived from the raw secret as
+-- HMAC-SHA256(secret, "hive-signing"), and can therefore only be computed when
+-- the token is created. Existing tokens are left without one and must be
+-- regenerated before they can require signing.
+--
+-- Note that the signing key is stored as-is, so anyone able to read this table
+-- can still forge signed requests; signing only keeps the secret itself from
+-- being sent along with (and leaked through) requests.
+
+ALTER TABLE "api_tokens"
+    ADD COLUMN signing_key TEXT;
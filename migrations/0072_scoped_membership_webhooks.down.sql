@@ -0,0 +1,35 @@
+CREATE OR REPLACE FUNCTION enqueue_webhook_deliveries() RETURNS TRIGGER AS $$
+DECLARE
+    subscribed_event WEBHOOK_EVENT;
+    target_system_id TEXT; -- NULL => relevant to every system
+BEGIN
+    IF NEW.target_kind = 'membership' THEN
+        subscribed_event := 'membership_changed';
+    ELSIF NEW.target_kind = 'permission_assignment' AND NEW.action_kind = 'create' THEN
+        subscribed_event := 'permission_assigned';
+        target_system_id := split_part(substr(NEW.target_id, 2), ':', 1); -- $system:perm
+    ELSIF NEW.target_kind = 'tag_assignment' AND NEW.action_kind = 'create' THEN
+        subscribed_event := 'tag_assigned';
+        target_system_id := split_part(substr(NEW.target_id, 2), ':', 1); -- #system:tag
+    ELSE
+        RETURN NULL;
+    END IF;
+
+    INSERT INTO webhook_deliveries (webhook_id, event, payload)
+    SELECT wh.id,
+        subscribed_event,
+        jsonb_build_object(
+            'event', subscribed_event,
+            'action', NEW.action_kind,
+            'target', NEW.target_id,
+            'actor', NEW.actor,
+            'stamp', NEW.stamp,
+            'details', NEW.details
+        )
+    FROM webhooks wh
+    WHERE subscribed_event = ANY(wh.events)
+        AND (target_system_id IS NULL OR wh.system_id = target_system_id);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
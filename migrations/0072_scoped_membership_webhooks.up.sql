@@ -0,0 +1,55 @@
+-- Membership changes used to be queued for every subscribed system, which
+-- leaked changes in groups that a system otherwise has no access to (e.g., via
+-- its API tokens). They are now only queued for systems with any of their tags
+-- assigned to the group (directly or indirectly), like the API's
+-- `tagged_group_members` requires.
+
+CREATE OR REPLACE FUNCTION enqueue_webhook_deliveries() RETURNS TRIGGER AS $$
+DECLARE
+    subscribed_event WEBHOOK_EVENT;
+    target_system_id TEXT;    -- NULL => not specific to one system
+    target_group_id TEXT;     -- NULL => not about a group's members
+    target_group_domain TEXT;
+BEGIN
+    IF NEW.target_kind = 'membership' THEN
+        subscribed_event := 'membership_changed';
+        target_group_id := split_part(NEW.target_id, '@', 1); -- id@domain
+        target_group_domain := split_part(NEW.target_id, '@', 2);
+    ELSIF NEW.target_kind = 'permission_assignment' AND NEW.action_kind = 'create' THEN
+        subscribed_event := 'permission_assigned';
+        target_system_id := split_part(substr(NEW.target_id, 2), ':', 1); -- $system:perm
+    ELSIF NEW.target_kind = 'tag_assignment' AND NEW.action_kind = 'create' THEN
+        subscribed_event := 'tag_assigned';
+        target_system_id := split_part(substr(NEW.target_id, 2), ':', 1); -- #system:tag
+    ELSE
+        RETURN NULL;
+    END IF;
+
+    INSERT INTO webhook_deliveries (webhook_id, event, payload)
+    SELECT wh.id,
+        subscribed_event,
+        jsonb_build_object(
+            'event', subscribed_event,
+            'action', NEW.action_kind,
+            'target', NEW.target_id,
+            'actor', NEW.actor,
+            'stamp', NEW.stamp,
+            'details', NEW.details
+        )
+    FROM webhooks wh
+    WHERE subscribed_event = ANY(wh.events)
+        AND (target_system_id IS NULL OR wh.system_id = target_system_id)
+        AND (
+            target_group_id IS NULL
+            OR EXISTS (
+                SELECT 1
+                FROM all_tag_assignments ta
+                WHERE ta.group_id = target_group_id
+                    AND ta.group_domain = target_group_domain
+                    AND ta.system_id = wh.system_id
+            )
+        );
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
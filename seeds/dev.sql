@@ -0,0 +1,99 @@
+-- Example data for local development, so that new contributors can explore
+-- Hive without hand-crafting rows. Applied on startup with `--seed-dev-data`,
+-- but only to a fresh database (i.e., no groups outside hive.internal).
+--
+-- Nobody is added to root@hive.internal, so whoever logs in first is still
+-- bootstrapped as an administrator as usual. Dates are relative to today so
+-- that the data stays "current" whenever it is seeded.
+
+INSERT INTO "systems" (id, description) VALUES
+    ('calypso', 'News and event publishing'),
+    ('cashflow', 'Expense reporting and attestation');
+
+INSERT INTO "permissions" (system_id, perm_id, has_scope, description) VALUES
+    ('calypso', 'post', FALSE, 'Publish news and events'),
+    ('calypso', 'admin', FALSE, 'Edit and remove anyone''s posts'),
+    ('cashflow', 'attest', TRUE, 'Attest expenses for a given cost centre (or *)'),
+    ('cashflow', 'admin', FALSE, 'Manage cost centres and budgets');
+
+INSERT INTO "tags" (system_id, tag_id, supports_users, supports_groups, has_content, description) VALUES
+    ('calypso', 'author', TRUE, TRUE, FALSE, 'Shown as a featured author'),
+    ('cashflow', 'cost-centre', FALSE, TRUE, TRUE, 'Cost centre that the group''s expenses are booked on'),
+    ('cashflow', 'board', FALSE, TRUE, FALSE, 'Part of the board (attests for every cost centre)');
+
+INSERT INTO "groups" (id, domain, name_sv, name_en, description_sv, description_en) VALUES
+    ('styrelsen', 'datasektionen.se', 'Styrelsen', 'The Board',
+     'Sektionens styrelse', 'The chapter''s board of directors'),
+    ('ordf', 'datasektionen.se', 'Ordförande', 'Chair',
+     'Sektionens ordförande', 'The chapter''s chair'),
+    ('vordf', 'datasektionen.se', 'Vice ordförande', 'Vice Chair',
+     'Sektionens vice ordförande', 'The chapter''s vice chair'),
+    ('kassor', 'datasektionen.se', 'Kassör', 'Treasurer',
+     'Ansvarar för sektionens ekonomi', 'Responsible for the chapter''s finances'),
+    ('namnder', 'datasektionen.se', 'Nämnder', 'Committees',
+     'Alla sektionens nämnder', 'All of the chapter''s committees'),
+    ('d-sys', 'datasektionen.se', 'Systemansvariga', 'Systems Group',
+     'Utvecklar och driftar sektionens system', 'Develops and operates the chapter''s systems'),
+    ('ior', 'datasektionen.se', 'Informationsorganet', 'Information Committee',
+     'Sköter sektionens kommunikation', 'Handles the chapter''s communication'),
+    ('mottagningen', 'datasektionen.se', 'Mottagningen', 'Reception',
+     'Välkomnar nya studenter', 'Welcomes new students'),
+    ('metaspexet', 'metaspexet.se', 'Metaspexet', 'Metaspexet',
+     'Sektionens spex', 'The chapter''s spex'),
+    ('producenter', 'metaspexet.se', 'Producenter', 'Producers',
+     'Leder årets uppsättning', 'Lead this year''s production');
+
+INSERT INTO "subgroups" (parent_id, parent_domain, child_id, child_domain, manager) VALUES
+    ('styrelsen', 'datasektionen.se', 'ordf', 'datasektionen.se', TRUE),
+    ('styrelsen', 'datasektionen.se', 'vordf', 'datasektionen.se', FALSE),
+    ('styrelsen', 'datasektionen.se', 'kassor', 'datasektionen.se', FALSE),
+    ('namnder', 'datasektionen.se', 'd-sys', 'datasektionen.se', FALSE),
+    ('namnder', 'datasektionen.se', 'ior', 'datasektionen.se', FALSE),
+    ('namnder', 'datasektionen.se', 'mottagningen', 'datasektionen.se', FALSE),
+    ('metaspexet', 'metaspexet.se', 'producenter', 'metaspexet.se', TRUE);
+
+INSERT INTO "direct_memberships" (username, group_id, group_domain, "from", "until", manager) VALUES
+    ('alicea', 'ordf', 'datasektionen.se', CURRENT_DATE - 180, CURRENT_DATE + 185, FALSE),
+    ('bobb', 'vordf', 'datasektionen.se', CURRENT_DATE - 180, CURRENT_DATE + 185, FALSE),
+    ('carolc', 'kassor', 'datasektionen.se', CURRENT_DATE - 180, CURRENT_DATE + 185, FALSE),
+    ('davidd', 'd-sys', 'datasektionen.se', CURRENT_DATE - 300, CURRENT_DATE + 65, TRUE),
+    ('evae', 'd-sys', 'datasektionen.se', CURRENT_DATE - 90, CURRENT_DATE + 275, FALSE),
+    ('frankf', 'd-sys', 'datasektionen.se', CURRENT_DATE - 500, CURRENT_DATE - 135, FALSE),
+    ('graceg', 'ior', 'datasektionen.se', CURRENT_DATE - 30, CURRENT_DATE + 335, TRUE),
+    ('heidih', 'mottagningen', 'datasektionen.se', CURRENT_DATE + 14, CURRENT_DATE + 379, FALSE),
+    ('evae', 'producenter', 'metaspexet.se', CURRENT_DATE - 60, CURRENT_DATE + 120, FALSE),
+    ('ivani', 'metaspexet', 'metaspexet.se', CURRENT_DATE - 60, CURRENT_DATE + 120, FALSE);
+
+INSERT INTO "permission_assignments" (system_id, perm_id, scope, group_id, group_domain) VALUES
+    ('hive', 'manage-groups', '@datasektionen.se', 'd-sys', 'datasektionen.se'),
+    ('hive', 'manage-members', '@metaspexet.se', 'producenter', 'metaspexet.se'),
+    ('calypso', 'post', NULL, 'namnder', 'datasektionen.se'),
+    ('calypso', 'post', NULL, 'styrelsen', 'datasektionen.se'),
+    ('calypso', 'admin', NULL, 'ior', 'datasektionen.se'),
+    ('cashflow', 'attest', '*', 'ordf', 'datasektionen.se'),
+    ('cashflow', 'attest', '*', 'kassor', 'datasektionen.se'),
+    ('cashflow', 'attest', 'mottagningen', 'mottagningen', 'datasektionen.se'),
+    ('cashflow', 'admin', NULL, 'kassor', 'datasektionen.se');
+
+INSERT INTO "tag_assignments" (system_id, tag_id, content, username, group_id, group_domain) VALUES
+    ('calypso', 'author', NULL, 'graceg', NULL, NULL),
+    ('calypso', 'author', NULL, NULL, 'ior', 'datasektionen.se'),
+    ('cashflow', 'cost-centre', 'dsys', NULL, 'd-sys', 'datasektionen.se'),
+    ('cashflow', 'cost-centre', 'mottagningen', NULL, 'mottagningen', 'datasektionen.se'),
+    ('cashflow', 'cost-centre', 'metaspexet', NULL, 'metaspexet', 'metaspexet.se'),
+    ('cashflow', 'board', NULL, NULL, 'styrelsen', 'datasektionen.se');
+
+-- the secret is well-known (see `DEV_API_TOKEN_SECRET` in src/seed.rs) and
+-- stored hashed, exactly like Rust-side `api_tokens::hash_secret` does
+WITH token AS (
+    INSERT INTO "api_tokens" (secret, system_id, description)
+    VALUES (
+        encode(sha256(decode(replace('deadbeef-0000-4000-8000-000000000000', '-', ''), 'hex')), 'hex'),
+        'calypso',
+        'Local development token'
+    )
+    RETURNING id
+)
+INSERT INTO "permission_assignments" (system_id, perm_id, scope, api_token_id)
+SELECT 'hive', perm_id, NULL, token.id
+FROM token, (VALUES ('api-check-permissions'), ('api-list-tagged')) AS perms (perm_id);
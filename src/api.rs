@@ -1,4 +1,5 @@
 pub use catchers::catchers;
+use serde::Serialize;
 
 use crate::perms::HivePermission;
 
@@ -6,6 +7,7 @@ mod catchers;
 pub mod v0;
 pub mod v1;
 
+#[derive(Serialize)]
 pub struct ApiVersionInfo<'a> {
     pub n: u8,
     pub annotation: Option<(&'a str, &'a str)>, // en, sv
@@ -32,6 +34,45 @@ pub const API_VERSIONS: &[ApiVersionInfo<'static>] = &[
 pub enum HiveApiPermission {
     CheckPermissions,
     ListTagged,
+    ApplyManifest,
+    ListGroups,
+    ManageMembers,
+    RunIntegrations,
+    ViewIntegrationRuns,
+}
+
+impl HiveApiPermission {
+    // whether anything can be changed through it (see read-only tokens)
+    pub fn is_write(&self) -> bool {
+        match self {
+            Self::ApplyManifest | Self::ManageMembers | Self::RunIntegrations => true,
+            Self::CheckPermissions
+            | Self::ListTagged
+            | Self::ListGroups
+            | Self::ViewIntegrationRuns => false,
+        }
+    }
+
+    pub fn concerns_groups(&self) -> bool {
+        match self {
+            Self::ListTagged | Self::ListGroups | Self::ManageMembers => true,
+            Self::CheckPermissions
+            | Self::ApplyManifest
+            | Self::RunIntegrations
+            | Self::ViewIntegrationRuns => false,
+        }
+    }
+
+    pub fn concerns_permissions(&self) -> bool {
+        match self {
+            Self::CheckPermissions | Self::ApplyManifest => true,
+            Self::ListTagged
+            | Self::ListGroups
+            | Self::ManageMembers
+            | Self::RunIntegrations
+            | Self::ViewIntegrationRuns => false,
+        }
+    }
 }
 
 impl From<HiveApiPermission> for HivePermission {
@@ -39,6 +80,11 @@ impl From<HiveApiPermission> for HivePermission {
         match perm {
             HiveApiPermission::CheckPermissions => HivePermission::ApiCheckPermissions,
             HiveApiPermission::ListTagged => HivePermission::ApiListTagged,
+            HiveApiPermission::ApplyManifest => HivePermission::ApiApplyManifest,
+            HiveApiPermission::ListGroups => HivePermission::ApiListGroups,
+            HiveApiPermission::ManageMembers => HivePermission::ApiManageMembers,
+            HiveApiPermission::RunIntegrations => HivePermission::ApiRunIntegrations,
+            HiveApiPermission::ViewIntegrationRuns => HivePermission::ApiViewIntegrationRuns,
         }
     }
 }
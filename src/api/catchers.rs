@@ -1,6 +1,8 @@
-use rocket::catchers;
+use rocket::{Request, catchers, http::Status};
 use serde_json::json;
 
+use crate::dto::errors::{ERROR_ENVELOPE_VERSION, ErrorCode};
+
 pub fn catchers() -> Vec<rocket::Catcher> {
     catchers![not_found, unauthorized, forbidden, unknown]
 }
@@ -10,6 +12,8 @@ fn not_found() -> serde_json::Value {
     // same format as AppErrorDto when serialized
     json!({
         "error": true,
+        "version": ERROR_ENVELOPE_VERSION,
+        "code": "not_found",
         "info": {
             "key": "api.path.unknown"
         }
@@ -21,6 +25,8 @@ fn unauthorized() -> serde_json::Value {
     // same format as AppErrorDto when serialized
     json!({
         "error": true,
+        "version": ERROR_ENVELOPE_VERSION,
+        "code": "unauthenticated",
         "info": {
             "key": "api.unauthorized"
         }
@@ -32,6 +38,8 @@ fn forbidden() -> serde_json::Value {
     // same format as AppErrorDto when serialized
     json!({
         "error": true,
+        "version": ERROR_ENVELOPE_VERSION,
+        "code": "forbidden",
         "info": {
             "key": "api.forbidden"
         }
@@ -39,10 +47,12 @@ fn forbidden() -> serde_json::Value {
 }
 
 #[rocket::catch(default)]
-fn unknown() -> serde_json::Value {
+fn unknown(status: Status, _: &Request) -> serde_json::Value {
     // same format as AppErrorDto when serialized
     json!({
         "error": true,
+        "version": ERROR_ENVELOPE_VERSION,
+        "code": ErrorCode::from(status),
         "info": {
             "key": "api.error"
         }
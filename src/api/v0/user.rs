@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 
 use rocket::{State, serde::json::Json};
-use sqlx::PgPool;
 
 use super::PermKey;
-use crate::{errors::AppResult, routing::RouteTree, services::permissions};
+use crate::{
+    errors::AppResult,
+    routing::RouteTree,
+    services::{ReadReplica, permissions},
+};
 
 pub fn routes() -> RouteTree {
     rocket::routes![
@@ -18,8 +21,11 @@ pub fn routes() -> RouteTree {
 type SystemPermissionsMap = HashMap<String, Vec<String>>;
 
 #[rocket::get("/user/<username>")]
-async fn user_systems(username: &str, db: &State<PgPool>) -> AppResult<Json<SystemPermissionsMap>> {
-    let assignments = permissions::list_all_assignments_for_user(username, db.inner()).await?;
+async fn user_systems(
+    username: &str,
+    replica: &State<ReadReplica>,
+) -> AppResult<Json<SystemPermissionsMap>> {
+    let assignments = permissions::list_all_assignments_for_user(username, replica.pool()).await?;
 
     let mut map: SystemPermissionsMap = HashMap::new();
 
@@ -39,16 +45,17 @@ async fn user_systems(username: &str, db: &State<PgPool>) -> AppResult<Json<Syst
 async fn user_permissions_for_system(
     username: &str,
     system_id: &str,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<Vec<String>>> {
-    let perms = permissions::list_all_assignments_for_user_system(username, system_id, db.inner())
-        .await?
-        .into_iter()
-        .map(|assignment| match assignment.scope {
-            Some(scope) => format!("{}:{}", assignment.perm_id, scope),
-            None => assignment.perm_id,
-        })
-        .collect();
+    let perms =
+        permissions::list_all_assignments_for_user_system(username, system_id, replica.pool())
+            .await?
+            .into_iter()
+            .map(|assignment| match assignment.scope {
+                Some(scope) => format!("{}:{}", assignment.perm_id, scope),
+                None => assignment.perm_id,
+            })
+            .collect();
 
     Ok(Json(perms))
 }
@@ -58,14 +65,14 @@ async fn user_has_permission(
     username: &str,
     system_id: &str,
     perm_key: PermKey<'_>,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<bool>> {
     let has_permission = permissions::user_has_permission(
         username,
         system_id,
         perm_key.perm_id,
         perm_key.scope,
-        db.inner(),
+        replica.pool(),
     )
     .await?;
 
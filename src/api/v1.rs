@@ -3,6 +3,11 @@ use serde::Serialize;
 use super::with_api_docs;
 use crate::{models::BasePermissionAssignment, routing::RouteTree};
 
+mod groups;
+#[cfg(feature = "integrations")]
+mod integrations;
+mod manifest;
+mod system;
 mod tagged;
 mod token;
 mod user;
@@ -10,7 +15,16 @@ mod user;
 pub fn tree() -> RouteTree {
     with_api_docs!(
         "v1",
-        RouteTree::Branch(vec![tagged::routes(), token::routes(), user::routes()])
+        RouteTree::Branch(vec![
+            groups::routes(),
+            #[cfg(feature = "integrations")]
+            integrations::routes(),
+            manifest::routes(),
+            system::routes(),
+            tagged::routes(),
+            token::routes(),
+            user::routes()
+        ])
     )
 }
 
@@ -0,0 +1,347 @@
+use chrono::{NaiveDate, NaiveTime};
+use log::*;
+use rocket::{
+    State,
+    http::{Status, uri::Host},
+    serde::json::Json,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    api::HiveApiPermission,
+    dto::groups::AddMemberDto,
+    errors::{AppError, AppResult},
+    guards::{api::consumer::ApiConsumer, scheme::RequestScheme},
+    mailer::Mailer,
+    models::{Group, GroupMember, GroupState, PendingMembership},
+    resolver::UserEmailDomain,
+    routing::RouteTree,
+    services::{
+        ReadReplica,
+        groups::{self, AuthorityInGroup},
+        self_preservation,
+    },
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![
+        list_groups,
+        group_memberships,
+        group_authority,
+        add_group_membership,
+        remove_group_membership,
+    ]
+    .into()
+}
+
+#[derive(Serialize)]
+struct ExportedGroup {
+    id: String,
+    domain: String,
+    name_sv: String,
+    name_en: String,
+    description_sv: String,
+    description_en: String,
+    state: GroupState,
+}
+
+impl From<Group> for ExportedGroup {
+    fn from(group: Group) -> Self {
+        Self {
+            id: group.id,
+            domain: group.domain,
+            name_sv: group.name_sv,
+            name_en: group.name_en,
+            description_sv: group.description_sv,
+            description_en: group.description_en,
+            state: group.state,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DirectMembership {
+    id: Option<Uuid>,
+    username: String,
+    from: NaiveDate,
+    until: NaiveDate,
+    from_time: Option<NaiveTime>,
+    until_time: Option<NaiveTime>,
+    manager: bool,
+    contact: bool,
+    note: Option<String>,
+}
+
+impl From<GroupMember> for DirectMembership {
+    fn from(member: GroupMember) -> Self {
+        Self {
+            id: member.id,
+            username: member.username,
+            from: member.from,
+            until: member.until,
+            from_time: member.from_time,
+            until_time: member.until_time,
+            manager: member.manager,
+            contact: member.contact,
+            note: member.note,
+        }
+    }
+}
+
+// (without an ID, since it only becomes a direct membership once accepted)
+impl From<PendingMembership> for DirectMembership {
+    fn from(pending: PendingMembership) -> Self {
+        Self {
+            id: None,
+            username: pending.username,
+            from: pending.from,
+            until: pending.until,
+            from_time: pending.from_time,
+            until_time: pending.until_time,
+            manager: pending.manager,
+            contact: false,
+            note: pending.note,
+        }
+    }
+}
+
+// groups the token is not allowed to see are reported as not existing, to
+// prevent enumeration; old keys of renamed groups resolve to the group itself
+async fn require_visible(
+    group_id: &str,
+    group_domain: &str,
+    consumer: &ApiConsumer,
+    db: &PgPool,
+) -> AppResult<Group> {
+    let not_found = || AppError::NoSuchGroup(group_id.to_owned(), group_domain.to_owned());
+
+    let group: Group = match groups::details::get_one(group_id, group_domain, db).await? {
+        Some(group) => group,
+        None => {
+            let (id, domain) = groups::details::resolve_alias(group_id, group_domain, db)
+                .await?
+                .ok_or_else(not_found)?;
+
+            groups::details::require_one(&id, &domain, db).await?
+        }
+    };
+
+    let visibility = consumer.group_visibility(db).await?;
+    if !visibility.allows(&group.id, &group.domain) {
+        return Err(not_found());
+    }
+
+    Ok(group)
+}
+
+#[rocket::get("/groups")]
+async fn list_groups(
+    consumer: ApiConsumer,
+    replica: &State<ReadReplica>,
+) -> AppResult<Json<Vec<ExportedGroup>>> {
+    consumer
+        .require(HiveApiPermission::ListGroups, replica.pool())
+        .await?;
+
+    let visibility = consumer.group_visibility(replica.pool()).await?;
+
+    let groups = groups::list::list_all(replica.pool())
+        .await?
+        .into_iter()
+        .filter(|group| visibility.allows(&group.id, &group.domain))
+        .map(Into::into)
+        .collect();
+
+    Ok(Json(groups))
+}
+
+#[rocket::get("/group/<group_domain>/<group_id>/memberships?<future>")]
+async fn group_memberships(
+    group_id: &str,
+    group_domain: &str,
+    future: Option<bool>,
+    consumer: ApiConsumer,
+    replica: &State<ReadReplica>,
+) -> AppResult<Json<Vec<DirectMembership>>> {
+    consumer
+        .require(HiveApiPermission::ListGroups, replica.pool())
+        .await?;
+
+    let group = require_visible(group_id, group_domain, &consumer, replica.pool()).await?;
+    let (group_id, group_domain) = (group.id.as_str(), group.domain.as_str());
+
+    // future memberships are included by default, since this is also how
+    // their IDs can be found (e.g., to remove them before they start)
+    let members = groups::members::get_direct_members(
+        group_id,
+        group_domain,
+        future.unwrap_or(true),
+        None::<chrono::Days>,
+        replica.pool(),
+        None,
+    )
+    .await?
+    .into_iter()
+    .map(Into::into)
+    .collect();
+
+    Ok(Json(members))
+}
+
+// what the user would be allowed to do with the group in Hive (as a member,
+// manager or through Hive permissions), so that other systems can reuse the
+// same notion of e.g. "manager of this group"
+#[rocket::get("/group/<group_domain>/<group_id>/authority?<user>")]
+async fn group_authority(
+    group_id: &str,
+    group_domain: &str,
+    user: &str,
+    consumer: ApiConsumer,
+    replica: &State<ReadReplica>,
+) -> AppResult<Json<AuthorityInGroup>> {
+    consumer
+        .require(HiveApiPermission::ListGroups, replica.pool())
+        .await?;
+
+    let group = require_visible(group_id, group_domain, &consumer, replica.pool()).await?;
+
+    let authority =
+        groups::details::get_authority_of(user, &group.id, &group.domain, replica.pool()).await?;
+
+    Ok(Json(authority))
+}
+
+// form fields are passed in the query string (rather than the body) so that
+// request signatures cover them without any extra work; in groups that require
+// acceptance, the membership is only proposed to the user (202 Accepted)
+#[rocket::post("/group/<group_domain>/<group_id>/memberships?<member..>")]
+#[allow(clippy::too_many_arguments)]
+async fn add_group_membership(
+    group_id: &str,
+    group_domain: &str,
+    member: AddMemberDto<'_>,
+    consumer: ApiConsumer,
+    db: &State<PgPool>,
+    mailer: &State<Option<Mailer>>,
+    email_domain: &State<UserEmailDomain>,
+    scheme: RequestScheme,
+    host: &Host<'_>,
+) -> AppResult<(Status, Json<DirectMembership>)> {
+    consumer
+        .require(HiveApiPermission::ManageMembers, db.inner())
+        .await?;
+
+    let group = require_visible(group_id, group_domain, &consumer, db.inner()).await?;
+    let (group_id, group_domain) = (group.id.as_str(), group.domain.as_str());
+
+    // would allow tokens to grant arbitrary $hive permissions
+    require_unaffecting_hive(group_id, group_domain, db.inner()).await?;
+
+    // tokens can't hold $hive:long-term-appointment, so anything beyond the
+    // default bounds must be done by a person via the web interface
+    let required = groups::members::required_appointment_permission(
+        &member.until.0,
+        group_id,
+        group_domain,
+        db.inner(),
+    )
+    .await?;
+    if required.is_some() {
+        return Err(AppError::AppointmentTooLong(member.username.to_string()));
+    }
+
+    if group.requires_acceptance {
+        let pending = groups::acceptances::propose(
+            group_id,
+            group_domain,
+            &member,
+            db.inner(),
+            crate::HIVE_API_ACTOR,
+        )
+        .await?;
+
+        if let Some(mailer) = mailer.inner() {
+            // it can still be found on their profile page if this fails
+            let base_url = format!("{scheme}://{host}");
+            if let Err(e) = groups::acceptances::send_email(
+                &pending,
+                &base_url,
+                mailer,
+                email_domain,
+                db.inner(),
+            )
+            .await
+            {
+                warn!(
+                    "Failed to email {} about their pending membership in {}: {e}",
+                    pending.username,
+                    pending.group_key()
+                );
+            }
+        }
+
+        return Ok((Status::Accepted, Json(pending.into())));
+    }
+
+    let added = groups::members::add_member(
+        group_id,
+        group_domain,
+        &member,
+        db.inner(),
+        None,
+        crate::HIVE_API_ACTOR,
+    )
+    .await?;
+
+    Ok((Status::Created, Json(added.into())))
+}
+
+// checked on the resolved group (i.e., after following aliases), since groups
+// outside hive.internal can also hold $hive permissions, directly or by being
+// nested under a group that does
+async fn require_unaffecting_hive(
+    group_id: &str,
+    group_domain: &str,
+    db: &PgPool,
+) -> AppResult<()> {
+    if group_domain == crate::HIVE_INTERNAL_DOMAIN
+        || self_preservation::affects_hive(group_id, group_domain, db).await?
+    {
+        Err(AppError::SelfPreservation)
+    } else {
+        Ok(())
+    }
+}
+
+#[rocket::delete("/group/<group_domain>/<group_id>/membership/<membership_id>")]
+async fn remove_group_membership(
+    group_id: &str,
+    group_domain: &str,
+    membership_id: Uuid,
+    consumer: ApiConsumer,
+    db: &State<PgPool>,
+) -> AppResult<Status> {
+    consumer
+        .require(HiveApiPermission::ManageMembers, db.inner())
+        .await?;
+
+    let group = require_visible(group_id, group_domain, &consumer, db.inner()).await?;
+    let (group_id, group_domain) = (group.id.as_str(), group.domain.as_str());
+
+    // would allow tokens to revoke arbitrary $hive permissions
+    require_unaffecting_hive(group_id, group_domain, db.inner()).await?;
+
+    groups::members::remove_member(
+        &membership_id,
+        group_id,
+        group_domain,
+        db.inner(),
+        crate::HIVE_API_ACTOR,
+    )
+    .await?
+    .ok_or_else(|| AppError::NoSuchMembership(membership_id.to_string()))?;
+
+    Ok(Status::NoContent)
+}
@@ -0,0 +1,136 @@
+use chrono::{DateTime, Local};
+use rocket::{State, http::Status, serde::json::Json};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    api::HiveApiPermission,
+    errors::{AppError, AppResult},
+    guards::api::consumer::ApiConsumer,
+    models::{IntegrationTaskLogEntry, IntegrationTaskRun},
+    routing::RouteTree,
+    services::{ReadReplica, integrations},
+};
+
+// how many of the most recent runs are listed, unless otherwise requested
+const DEFAULT_TASK_RUNS_LIMIT: i64 = 50;
+const MAX_TASK_RUNS_LIMIT: i64 = 500;
+
+pub fn routes() -> RouteTree {
+    rocket::routes![run_integration_task, list_task_runs, task_run_details].into()
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TaskRunStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Skipped, // because another run of the same task was still ongoing
+}
+
+#[derive(Serialize)]
+struct TaskRun {
+    id: Uuid,
+    task_id: String,
+    status: TaskRunStatus,
+    started_at: DateTime<Local>,
+    ended_at: Option<DateTime<Local>>,
+}
+
+impl From<IntegrationTaskRun> for TaskRun {
+    fn from(run: IntegrationTaskRun) -> Self {
+        let status = match (run.skipped, run.succeeded) {
+            (true, _) => TaskRunStatus::Skipped,
+            (false, Some(true)) => TaskRunStatus::Succeeded,
+            (false, Some(false)) => TaskRunStatus::Failed,
+            (false, None) => TaskRunStatus::Running,
+        };
+
+        Self {
+            id: run.run_id,
+            task_id: run.task_id,
+            status,
+            started_at: run.start_stamp,
+            ended_at: run.end_stamp,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TaskRunDetails {
+    #[serde(flatten)]
+    run: TaskRun,
+    logs: Vec<IntegrationTaskLogEntry>,
+}
+
+#[rocket::post("/integration/<integration_id>/task/<task_id>/run")]
+async fn run_integration_task(
+    integration_id: &str,
+    task_id: &str,
+    consumer: ApiConsumer,
+    db: &State<PgPool>,
+) -> AppResult<Status> {
+    consumer
+        .require(HiveApiPermission::RunIntegrations, db.inner())
+        .await?;
+
+    // the run itself happens in the background, since tasks can take a while;
+    // its outcome can be seen in the system's task run logs
+    if crate::integrations::trigger_task_run(integration_id, task_id, db.inner().clone()) {
+        Ok(Status::Accepted)
+    } else {
+        Err(AppError::NoSuchSystem(integration_id.to_owned()))
+    }
+}
+
+// most recent first
+#[rocket::get("/integration/<integration_id>/runs?<limit>")]
+async fn list_task_runs(
+    integration_id: &str,
+    limit: Option<i64>,
+    consumer: ApiConsumer,
+    replica: &State<ReadReplica>,
+) -> AppResult<Json<Vec<TaskRun>>> {
+    consumer
+        .require(HiveApiPermission::ViewIntegrationRuns, replica.pool())
+        .await?;
+
+    if !crate::integrations::integration_exists(integration_id) {
+        return Err(AppError::NoSuchSystem(integration_id.to_owned()));
+    }
+
+    let limit = limit
+        .unwrap_or(DEFAULT_TASK_RUNS_LIMIT)
+        .clamp(1, MAX_TASK_RUNS_LIMIT);
+
+    let runs = integrations::list_recent_task_runs(integration_id, limit, replica.pool())
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok(Json(runs))
+}
+
+#[rocket::get("/integration/<integration_id>/run/<run_id>")]
+async fn task_run_details(
+    integration_id: &str,
+    run_id: Uuid,
+    consumer: ApiConsumer,
+    replica: &State<ReadReplica>,
+) -> AppResult<Json<TaskRunDetails>> {
+    consumer
+        .require(HiveApiPermission::ViewIntegrationRuns, replica.pool())
+        .await?;
+
+    let run = integrations::require_task_run(integration_id, &run_id, replica.pool()).await?;
+
+    let logs = integrations::get_task_run_logs(integration_id, &run_id, replica.pool()).await?;
+
+    Ok(Json(TaskRunDetails {
+        run: run.into(),
+        logs,
+    }))
+}
@@ -0,0 +1,68 @@
+use rocket::{State, data::Limits, serde::json::Json};
+use sha2::Digest;
+use sqlx::PgPool;
+
+use crate::{
+    api::HiveApiPermission,
+    dto::systems::SystemManifestDto,
+    errors::{AppError, AppResult},
+    guards::api::{consumer::ApiConsumer, token::SignedRequest},
+    models::ManifestChanges,
+    routing::RouteTree,
+    services::systems,
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![apply_system_manifest].into()
+}
+
+#[rocket::put("/system/<id>/manifest?<dry_run>", data = "<data>")]
+async fn apply_system_manifest(
+    id: &str,
+    dry_run: bool,
+    data: rocket::Data<'_>,
+    limits: &Limits,
+    consumer: ApiConsumer,
+    signed: Option<SignedRequest<'_>>,
+    db: &State<PgPool>,
+) -> AppResult<Json<ManifestChanges>> {
+    consumer
+        .require(HiveApiPermission::ApplyManifest, db.inner())
+        .await?;
+
+    if id != consumer.system_id {
+        // other systems can only be targeted via impersonation
+        return Err(AppError::NotAllowed(
+            HiveApiPermission::ApplyManifest.into(),
+        ));
+    }
+
+    // read manually (instead of via Json) since signatures must cover it
+    let body = data
+        .open(limits.get("json").unwrap_or(Limits::JSON))
+        .into_string()
+        .await
+        .map_err(|e| AppError::InvalidSystemManifest(e.to_string()))?;
+
+    if !body.is_complete() {
+        let reason = "request body is too large".to_owned();
+        return Err(AppError::InvalidSystemManifest(reason));
+    }
+
+    if let Some(signed) = signed {
+        let digest = hex::encode(sha2::Sha256::digest(body.value.as_bytes()));
+
+        if digest != signed.body_digest {
+            let reason = "request body does not match signed content digest".to_owned();
+            return Err(AppError::InvalidSystemManifest(reason));
+        }
+    }
+
+    let manifest: SystemManifestDto = serde_json::from_str(&body.value)
+        .map_err(|e| AppError::InvalidSystemManifest(e.to_string()))?;
+
+    let changes =
+        systems::apply_manifest(id, &manifest, dry_run, consumer.api_token_id, db.inner()).await?;
+
+    Ok(Json(changes))
+}
@@ -0,0 +1,92 @@
+use rocket::{State, serde::json::Json};
+use serde::Serialize;
+
+use crate::{
+    api::HiveApiPermission,
+    errors::{AppError, AppResult},
+    guards::api::consumer::ApiConsumer,
+    models::Permission,
+    routing::RouteTree,
+    services::{ReadReplica, permissions, systems},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![system_details, system_permissions].into()
+}
+
+#[derive(Serialize)]
+struct SystemDetails {
+    id: String,
+    description: String,
+}
+
+#[derive(Serialize)]
+struct SystemPermission {
+    id: String,
+    description: String,
+    has_scope: bool,
+}
+
+impl From<Permission> for SystemPermission {
+    fn from(permission: Permission) -> Self {
+        Self {
+            id: permission.perm_id,
+            description: permission.description,
+            has_scope: permission.has_scope,
+        }
+    }
+}
+
+async fn require_own_system(
+    id: &str,
+    consumer: &ApiConsumer,
+    replica: &ReadReplica,
+) -> AppResult<()> {
+    consumer
+        .require(HiveApiPermission::CheckPermissions, replica.pool())
+        .await?;
+
+    if id != consumer.system_id {
+        // other systems can only be targeted via impersonation
+        return Err(AppError::NotAllowed(
+            HiveApiPermission::CheckPermissions.into(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[rocket::get("/system/<id>")]
+async fn system_details(
+    id: &str,
+    consumer: ApiConsumer,
+    replica: &State<ReadReplica>,
+) -> AppResult<Json<SystemDetails>> {
+    require_own_system(id, &consumer, replica).await?;
+
+    let system = systems::get_one(id, replica.pool())
+        .await?
+        .ok_or_else(|| AppError::NoSuchSystem(id.to_owned()))?;
+
+    Ok(Json(SystemDetails {
+        id: system.id,
+        description: system.description,
+    }))
+}
+
+#[rocket::get("/system/<id>/permissions")]
+async fn system_permissions(
+    id: &str,
+    consumer: ApiConsumer,
+    replica: &State<ReadReplica>,
+) -> AppResult<Json<Vec<SystemPermission>>> {
+    require_own_system(id, &consumer, replica).await?;
+
+    let permissions = permissions::list_for_system(id, replica.pool())
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok(Json(permissions))
+}
@@ -1,21 +1,26 @@
 use std::collections::BTreeSet;
 
-use rocket::{State, serde::json::Json};
+use rocket::{
+    State,
+    form::{self, error::ErrorKind},
+    serde::json::Json,
+};
 use serde::Serialize;
-use sqlx::PgPool;
 
 use crate::{
     api::HiveApiPermission,
+    dto::{datetime::BrowserDateDto, tags::TaggedFilterDto},
     errors::{AppError, AppResult},
     guards::{api::consumer::ApiConsumer, lang::Language},
-    models::AffiliatedTagAssignment,
+    models::{AffiliatedTagAssignment, TaggedEntity},
     perms::HivePermission,
     routing::RouteTree,
-    services::{groups, tags},
+    services::{ReadReplica, groups, tags},
 };
 
 pub fn routes() -> RouteTree {
     rocket::routes![
+        tagged,
         tagged_groups,
         tagged_users,
         tagged_user_memberships,
@@ -24,6 +29,43 @@ pub fn routes() -> RouteTree {
     .into()
 }
 
+const DEFAULT_TAGGED_LIMIT: u32 = 100;
+const MAX_TAGGED_LIMIT: u32 = 1000;
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TaggedEntry {
+    Group {
+        group_domain: String,
+        group_id: String,
+        tag_content: Option<String>,
+        direct: bool,
+    },
+    User {
+        username: String,
+        tag_content: Option<String>,
+        direct: bool,
+    },
+}
+
+impl From<TaggedEntity> for TaggedEntry {
+    fn from(entity: TaggedEntity) -> Self {
+        match entity.username {
+            Some(username) => Self::User {
+                username,
+                tag_content: entity.content,
+                direct: entity.direct,
+            },
+            None => Self::Group {
+                group_domain: entity.group_domain.unwrap_or_default(),
+                group_id: entity.group_id.unwrap_or_default(),
+                tag_content: entity.content,
+                direct: entity.direct,
+            },
+        }
+    }
+}
+
 #[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
 struct TaggedGroup {
     group_name: String,
@@ -61,32 +103,67 @@ impl From<AffiliatedTagAssignment> for TaggedUser {
     }
 }
 
+#[rocket::get("/tagged/<tag_id>?<filter..>")]
+async fn tagged(
+    tag_id: &str,
+    filter: TaggedFilterDto<'_>,
+    consumer: ApiConsumer,
+    replica: &State<ReadReplica>,
+) -> AppResult<Json<Vec<TaggedEntry>>> {
+    consumer
+        .require(HiveApiPermission::ListTagged, replica.pool())
+        .await?;
+
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_TAGGED_LIMIT)
+        .clamp(1, MAX_TAGGED_LIMIT);
+    let visibility = consumer.group_visibility(replica.pool()).await?;
+
+    let entries = tags::list_tagged_paged(
+        &consumer.system_id,
+        tag_id,
+        &filter,
+        &visibility,
+        limit,
+        replica.pool(),
+    )
+    .await?
+    .into_iter()
+    .map(Into::into)
+    .collect();
+
+    Ok(Json(entries))
+}
+
 #[rocket::get("/tagged/<tag_id>/groups?<lang>&<description>")]
 async fn tagged_groups(
     tag_id: &str,
     lang: Option<Language>,
     description: Option<bool>,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<BTreeSet<TaggedGroup>>> {
     consumer
-        .require(HiveApiPermission::ListTagged, db.inner())
+        .require(HiveApiPermission::ListTagged, replica.pool())
         .await?;
 
     let lang = lang.unwrap_or(Language::Swedish);
+    let visibility = consumer.group_visibility(replica.pool()).await?;
 
     let assignments = tags::list_group_assignments(
         &consumer.system_id,
         tag_id,
         Some(&lang),
         None,
-        db.inner(),
+        replica.pool(),
         None,
         description.unwrap_or_default(),
     )
     .await?
     .into_iter()
-    .map(Into::into)
+    .map(TaggedGroup::from)
+    .filter(|group| visibility.allows(&group.group_id, &group.group_domain))
     .collect(); // BTreeSet orders and removes duplicates
 
     Ok(Json(assignments))
@@ -96,14 +173,14 @@ async fn tagged_groups(
 async fn tagged_users(
     tag_id: &str,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<BTreeSet<TaggedUser>>> {
     consumer
-        .require(HiveApiPermission::ListTagged, db.inner())
+        .require(HiveApiPermission::ListTagged, replica.pool())
         .await?;
 
     let assignments =
-        tags::list_user_assignments(&consumer.system_id, tag_id, db.inner(), None, None)
+        tags::list_user_assignments(&consumer.system_id, tag_id, replica.pool(), None, None)
             .await?
             .into_iter()
             .map(Into::into)
@@ -119,54 +196,77 @@ async fn tagged_user_memberships(
     lang: Option<Language>,
     description: Option<bool>,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<BTreeSet<TaggedGroup>>> {
     consumer
-        .require(HiveApiPermission::ListTagged, db.inner())
+        .require(HiveApiPermission::ListTagged, replica.pool())
         .await?;
 
     let lang = lang.unwrap_or(Language::Swedish);
+    let visibility = consumer.group_visibility(replica.pool()).await?;
 
     let assignments = tags::list_group_assignments(
         &consumer.system_id,
         tag_id,
         Some(&lang),
         Some(username),
-        db.inner(),
+        replica.pool(),
         None,
         description.unwrap_or_default(),
     )
     .await?
     .into_iter()
-    .map(Into::into)
+    .map(TaggedGroup::from)
+    .filter(|group| visibility.allows(&group.group_id, &group.group_domain))
     .collect(); // BTreeSet orders and removes duplicates
 
     Ok(Json(assignments))
 }
 
-#[rocket::get("/group/<group_domain>/<group_id>/members")]
+#[rocket::get("/group/<group_domain>/<group_id>/members?<at>")]
 async fn tagged_group_members(
     group_id: &str,
     group_domain: &str,
+    at: form::Result<'_, BrowserDateDto>,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<BTreeSet<String>>> {
     consumer
-        .require(HiveApiPermission::ListTagged, db.inner())
+        .require(HiveApiPermission::ListTagged, replica.pool())
         .await?;
 
-    let tagged_for_system =
-        groups::tags::is_tagged_for_system(group_id, group_domain, &consumer.system_id, db.inner())
-            .await?;
-    if !tagged_for_system {
+    // old keys of renamed groups keep working
+    let current = groups::details::resolve_alias(group_id, group_domain, replica.pool()).await?;
+    let (group_id, group_domain) = match &current {
+        Some((id, domain)) => (id.as_str(), domain.as_str()),
+        None => (group_id, group_domain),
+    };
+
+    let tagged_for_system = groups::tags::is_tagged_for_system(
+        group_id,
+        group_domain,
+        &consumer.system_id,
+        replica.pool(),
+    )
+    .await?;
+    let visibility = consumer.group_visibility(replica.pool()).await?;
+    if !tagged_for_system || !visibility.allows(group_id, group_domain) {
         return Err(AppError::NotAllowed(HivePermission::ApiListTagged));
     }
 
-    let members = groups::members::get_all_members(group_id, group_domain, db.inner(), None)
-        .await?
-        .into_iter()
-        .map(|member| member.username)
-        .collect(); // BTreeSet orders and removes duplicates
+    // a malformed date must not silently fall back to today
+    let at = match at {
+        Ok(at) => Some(at.0),
+        Err(errors) if errors.iter().all(|e| matches!(e.kind, ErrorKind::Missing)) => None,
+        Err(_) => return Err(AppError::InvalidQueryParameter("at".to_owned())),
+    };
+
+    let members =
+        groups::members::get_all_members(group_id, group_domain, at, replica.pool(), None)
+            .await?
+            .into_iter()
+            .map(|member| member.username)
+            .collect(); // BTreeSet orders and removes duplicates
 
     Ok(Json(members))
 }
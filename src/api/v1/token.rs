@@ -1,13 +1,15 @@
 use std::collections::BTreeSet;
 
 use rocket::{State, serde::json::Json};
-use sqlx::PgPool;
 use uuid::Uuid;
 
 use super::SystemPermissionAssignment;
 use crate::{
-    api::HiveApiPermission, errors::AppResult, guards::api::consumer::ApiConsumer,
-    routing::RouteTree, services::permissions,
+    api::HiveApiPermission,
+    errors::AppResult,
+    guards::api::consumer::ApiConsumer,
+    routing::RouteTree,
+    services::{ReadReplica, permissions},
 };
 
 pub fn routes() -> RouteTree {
@@ -24,18 +26,21 @@ pub fn routes() -> RouteTree {
 async fn token_permissions(
     secret: Uuid,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<BTreeSet<SystemPermissionAssignment>>> {
     consumer
-        .require(HiveApiPermission::CheckPermissions, db.inner())
+        .require(HiveApiPermission::CheckPermissions, replica.pool())
         .await?;
 
-    let perms =
-        permissions::list_all_assignments_for_token_system(secret, &consumer.system_id, db.inner())
-            .await?
-            .into_iter()
-            .map(Into::into)
-            .collect(); // BTreeSet orders and removes duplicates
+    let perms = permissions::list_all_assignments_for_token_system(
+        secret,
+        &consumer.system_id,
+        replica.pool(),
+    )
+    .await?
+    .into_iter()
+    .map(Into::into)
+    .collect(); // BTreeSet orders and removes duplicates
 
     Ok(Json(perms))
 }
@@ -45,17 +50,17 @@ async fn token_permission_scopes(
     secret: Uuid,
     perm_id: &str,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<BTreeSet<String>>> {
     consumer
-        .require(HiveApiPermission::CheckPermissions, db.inner())
+        .require(HiveApiPermission::CheckPermissions, replica.pool())
         .await?;
 
     let scopes = permissions::list_all_scopes_for_token_permission(
         secret,
         perm_id,
         &consumer.system_id,
-        db.inner(),
+        replica.pool(),
     )
     .await?;
 
@@ -68,15 +73,20 @@ async fn token_has_permission(
     secret: Uuid,
     perm_id: &str,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<bool>> {
     consumer
-        .require(HiveApiPermission::CheckPermissions, db.inner())
+        .require(HiveApiPermission::CheckPermissions, replica.pool())
         .await?;
 
-    let has_permission =
-        permissions::token_has_permission(secret, &consumer.system_id, perm_id, None, db.inner())
-            .await?;
+    let has_permission = permissions::token_has_permission(
+        secret,
+        &consumer.system_id,
+        perm_id,
+        None,
+        replica.pool(),
+    )
+    .await?;
 
     Ok(Json(has_permission))
 }
@@ -87,10 +97,10 @@ async fn token_has_permission_scoped(
     perm_id: &str,
     scope: &str,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<bool>> {
     consumer
-        .require(HiveApiPermission::CheckPermissions, db.inner())
+        .require(HiveApiPermission::CheckPermissions, replica.pool())
         .await?;
 
     let has_permission = permissions::token_has_permission(
@@ -98,7 +108,7 @@ async fn token_has_permission_scoped(
         &consumer.system_id,
         perm_id,
         Some(scope),
-        db.inner(),
+        replica.pool(),
     )
     .await?;
 
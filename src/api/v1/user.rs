@@ -1,12 +1,14 @@
 use std::collections::BTreeSet;
 
 use rocket::{State, serde::json::Json};
-use sqlx::PgPool;
 
 use super::SystemPermissionAssignment;
 use crate::{
-    api::HiveApiPermission, errors::AppResult, guards::api::consumer::ApiConsumer,
-    routing::RouteTree, services::permissions,
+    api::HiveApiPermission,
+    errors::AppResult,
+    guards::api::consumer::ApiConsumer,
+    routing::RouteTree,
+    services::{ReadReplica, permissions},
 };
 
 pub fn routes() -> RouteTree {
@@ -23,16 +25,16 @@ pub fn routes() -> RouteTree {
 async fn user_permissions(
     username: &str,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<BTreeSet<SystemPermissionAssignment>>> {
     consumer
-        .require(HiveApiPermission::CheckPermissions, db.inner())
+        .require(HiveApiPermission::CheckPermissions, replica.pool())
         .await?;
 
     let perms = permissions::list_all_assignments_for_user_system(
         username,
         &consumer.system_id,
-        db.inner(),
+        replica.pool(),
     )
     .await?
     .into_iter()
@@ -47,17 +49,17 @@ async fn user_permission_scopes(
     username: &str,
     perm_id: &str,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<BTreeSet<String>>> {
     consumer
-        .require(HiveApiPermission::CheckPermissions, db.inner())
+        .require(HiveApiPermission::CheckPermissions, replica.pool())
         .await?;
 
     let scopes = permissions::list_all_scopes_for_user_permission(
         username,
         perm_id,
         &consumer.system_id,
-        db.inner(),
+        replica.pool(),
     )
     .await?;
 
@@ -70,15 +72,20 @@ async fn user_has_permission(
     username: &str,
     perm_id: &str,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<bool>> {
     consumer
-        .require(HiveApiPermission::CheckPermissions, db.inner())
+        .require(HiveApiPermission::CheckPermissions, replica.pool())
         .await?;
 
-    let has_permission =
-        permissions::user_has_permission(username, &consumer.system_id, perm_id, None, db.inner())
-            .await?;
+    let has_permission = permissions::user_has_permission(
+        username,
+        &consumer.system_id,
+        perm_id,
+        None,
+        replica.pool(),
+    )
+    .await?;
 
     Ok(Json(has_permission))
 }
@@ -89,10 +96,10 @@ async fn user_has_permission_scoped(
     perm_id: &str,
     scope: &str,
     consumer: ApiConsumer,
-    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
 ) -> AppResult<Json<bool>> {
     consumer
-        .require(HiveApiPermission::CheckPermissions, db.inner())
+        .require(HiveApiPermission::CheckPermissions, replica.pool())
         .await?;
 
     let has_permission = permissions::user_has_permission(
@@ -100,7 +107,7 @@ async fn user_has_permission_scoped(
         &consumer.system_id,
         perm_id,
         Some(scope),
-        db.inner(),
+        replica.pool(),
     )
     .await?;
 
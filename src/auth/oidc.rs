@@ -96,6 +96,34 @@ impl OidcClient {
         })
     }
 
+    // without discovery (i.e., network access), for tests that never go through
+    // the login flow but still need a client to be managed
+    #[cfg(test)]
+    pub fn offline() -> Self {
+        use openidconnect::{
+            AuthUrl, EmptyAdditionalProviderMetadata, JsonWebKeySetUrl, ResponseTypes,
+            core::{CoreJwsSigningAlgorithm, CoreSubjectIdentifierType},
+        };
+
+        let provider_metadata = CoreProviderMetadata::new(
+            IssuerUrl::new("https://sso.invalid".to_owned()).unwrap(),
+            AuthUrl::new("https://sso.invalid/authorize".to_owned()).unwrap(),
+            JsonWebKeySetUrl::new("https://sso.invalid/jwks".to_owned()).unwrap(),
+            vec![ResponseTypes::new(vec![CoreResponseType::Code])],
+            vec![CoreSubjectIdentifierType::Public],
+            vec![CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256],
+            EmptyAdditionalProviderMetadata {},
+        );
+
+        let client_id = ClientId::new("hive".to_owned());
+        let client = CoreClient::from_provider_metadata(provider_metadata, client_id, None);
+
+        Self {
+            client,
+            http_client: openidconnect::reqwest::Client::new(),
+        }
+    }
+
     pub(super) async fn begin_authentication<'n>(
         &self,
         redirect_url: String,
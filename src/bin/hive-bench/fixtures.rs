@@ -0,0 +1,268 @@
+// the synthetic organization shared by `hive-bench` and the criterion benches
+// (see `benches/`), along with the scenarios that are timed against it
+//
+// everything seeded lives in its own domain/system (see constants below), so it
+// can be removed again afterwards with `clean`
+
+use chrono::{Days, NaiveDate};
+use sqlx::PgPool;
+
+use crate::{clock, queries};
+
+pub const BENCH_DOMAIN: &str = "bench.test";
+pub const BENCH_SYSTEM: &str = "bench";
+pub const BENCH_PERM: &str = "access";
+pub const USERNAME_PREFIX: &str = "bench";
+
+// deterministic, so that different runs (and machines) use the same inputs
+pub struct Lcg(pub u64);
+
+impl Lcg {
+    pub fn next(&mut self, bound: u32) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+
+        ((self.0 >> 33) % bound as u64) as u32
+    }
+}
+
+pub async fn clean(db: &PgPool) -> Result<(), sqlx::Error> {
+    let mut txn = db.begin().await?;
+
+    // memberships, subgroups and assignments are removed via ON DELETE CASCADE
+    sqlx::query("DELETE FROM groups WHERE domain = $1")
+        .bind(BENCH_DOMAIN)
+        .execute(&mut *txn)
+        .await?;
+
+    sqlx::query("DELETE FROM systems WHERE id = $1")
+        .bind(BENCH_SYSTEM)
+        .execute(&mut *txn)
+        .await?;
+
+    txn.commit().await?;
+
+    println!("Removed all benchmark data");
+
+    Ok(())
+}
+
+pub async fn seed(
+    users: u32,
+    groups: u32,
+    fanout: u32,
+    memberships: u32,
+    db: &PgPool,
+) -> Result<(), sqlx::Error> {
+    clean(db).await?;
+
+    let groups = groups.max(1);
+    let fanout = fanout.max(1);
+
+    let today = clock::today();
+    let mut rng = Lcg(0x5eed);
+
+    let mut txn = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO groups (id, domain, name_sv, name_en, description_sv, description_en)
+        SELECT 'g' || n, $1, 'Grupp ' || n, 'Group ' || n, 'Genererad', 'Generated'
+        FROM GENERATE_SERIES(0, $2 - 1) n",
+    )
+    .bind(BENCH_DOMAIN)
+    .bind(groups as i32)
+    .execute(&mut *txn)
+    .await?;
+
+    // memberships are inserted before subgroups so that the closure triggers
+    // only have to deal with flat groups; it's rebuilt once at the end instead
+    let mut usernames = Vec::new();
+    let mut group_ids = Vec::new();
+    let mut froms = Vec::new();
+    let mut untils = Vec::new();
+    for user in 0..users {
+        for _ in 0..memberships {
+            // most memberships are current, but roughly 1 in 10 is already
+            // over and another 1 in 10 has not started yet
+            let from = match rng.next(10) {
+                0 => shift(today, -(400 + rng.next(365) as i64)),
+                1 => shift(today, 1 + rng.next(90) as i64),
+                _ => shift(today, -(rng.next(365) as i64)),
+            };
+
+            usernames.push(format!("{USERNAME_PREFIX}{user}"));
+            group_ids.push(format!("g{}", rng.next(groups)));
+            froms.push(from);
+            untils.push(shift(from, 365));
+        }
+    }
+
+    sqlx::query(
+        "INSERT INTO direct_memberships (username, group_id, group_domain, \"from\", \"until\")
+        SELECT m.username, m.group_id, $1, m.\"from\", m.\"until\"
+        FROM UNNEST($2::text[], $3::text[], $4::date[], $5::date[])
+            AS m (username, group_id, \"from\", \"until\")",
+    )
+    .bind(BENCH_DOMAIN)
+    .bind(&usernames)
+    .bind(&group_ids)
+    .bind(&froms)
+    .bind(&untils)
+    .execute(&mut *txn)
+    .await?;
+
+    // a tree rooted at g0 (depth ~ log_fanout(groups)), plus some extra edges
+    // so that groups can be reached through multiple paths; parents always
+    // have lower numbers than children, so there are no cycles
+    sqlx::query(
+        "INSERT INTO subgroups (parent_id, parent_domain, child_id, child_domain, manager)
+        SELECT 'g' || ((n - 1) / $2), $1, 'g' || n, $1, n % 50 = 0
+        FROM GENERATE_SERIES(1, $3 - 1) n
+        UNION
+        SELECT 'g' || (n / 7), $1, 'g' || n, $1, FALSE
+        FROM GENERATE_SERIES(1, $3 - 1) n
+        WHERE n % 10 = 0 AND n / 7 <> (n - 1) / $2",
+    )
+    .bind(BENCH_DOMAIN)
+    .bind(fanout as i32)
+    .bind(groups as i32)
+    .execute(&mut *txn)
+    .await?;
+
+    sqlx::query("INSERT INTO systems (id, description) VALUES ($1, 'Benchmark data')")
+        .bind(BENCH_SYSTEM)
+        .execute(&mut *txn)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO permissions (system_id, perm_id, has_scope, description)
+        VALUES ($1, $2, TRUE, 'Benchmark permission')",
+    )
+    .bind(BENCH_SYSTEM)
+    .bind(BENCH_PERM)
+    .execute(&mut *txn)
+    .await?;
+
+    // every 5th group gets a scoped assignment, every 100th a wildcard
+    sqlx::query(
+        "INSERT INTO permission_assignments (system_id, perm_id, scope, group_id, group_domain)
+        SELECT $2, $3, CASE WHEN n % 100 = 0 THEN '*' ELSE 's' || (n % 37) END, 'g' || n, $1
+        FROM GENERATE_SERIES(0, $4 - 1, 5) n",
+    )
+    .bind(BENCH_DOMAIN)
+    .bind(BENCH_SYSTEM)
+    .bind(BENCH_PERM)
+    .bind(groups as i32)
+    .execute(&mut *txn)
+    .await?;
+
+    sqlx::query("SELECT rebuild_membership_closure()")
+        .execute(&mut *txn)
+        .await?;
+
+    txn.commit().await?;
+
+    sqlx::query("ANALYZE").execute(db).await?;
+
+    println!(
+        "Seeded {users} users, {groups} groups and {} memberships",
+        usernames.len()
+    );
+
+    Ok(())
+}
+
+fn shift(date: NaiveDate, days: i64) -> NaiveDate {
+    if days < 0 {
+        date - Days::new(days.unsigned_abs())
+    } else {
+        date + Days::new(days as u64)
+    }
+}
+
+// (both `hive-bench` and the benches include the modules below by path, since
+// Hive isn't a library crate)
+#[derive(Clone, Copy)]
+pub enum Scenario {
+    PermissionCheck,      // `services::permissions::find_user_matches`
+    EvaluatorAssignments, // `perms::get_assignments`
+    AllGroupsOf,          // also the fallback while the closure is invalid
+    AllMembersOf,         // heavier, so run on a single (top-level) group only
+}
+
+impl Scenario {
+    pub const ALL: [Self; 4] = [
+        Self::PermissionCheck,
+        Self::EvaluatorAssignments,
+        Self::AllGroupsOf,
+        Self::AllMembersOf,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PermissionCheck => "permission-check",
+            Self::EvaluatorAssignments => "evaluator-assignments",
+            Self::AllGroupsOf => "all-groups-of",
+            Self::AllMembersOf => "all-members-of",
+        }
+    }
+
+    // the benchmark data has no external domains, so none are excluded
+    pub async fn run(self, username: &str, scope: &str, db: &PgPool) -> Result<(), sqlx::Error> {
+        let today = clock::today();
+        let external_domains: &[String] = &[];
+
+        match self {
+            Self::PermissionCheck => {
+                sqlx::query(queries::USER_MATCHES)
+                    .bind(username)
+                    .bind(today)
+                    .bind(BENCH_SYSTEM)
+                    .bind(BENCH_PERM)
+                    .bind(scope)
+                    .bind(external_domains)
+                    .fetch_all(db)
+                    .await?;
+            }
+            Self::EvaluatorAssignments => {
+                sqlx::query(queries::REACHING_ASSIGNMENTS)
+                    .bind(username)
+                    .bind(today)
+                    .bind(BENCH_SYSTEM)
+                    .bind(BENCH_PERM)
+                    .bind(external_domains)
+                    .fetch_all(db)
+                    .await?;
+            }
+            Self::AllGroupsOf => {
+                sqlx::query("SELECT * FROM all_groups_of($1, $2)")
+                    .bind(username)
+                    .bind(today)
+                    .fetch_all(db)
+                    .await?;
+            }
+            Self::AllMembersOf => {
+                sqlx::query("SELECT COUNT(*) FROM all_members_of($1, $2, $3)")
+                    .bind("g0")
+                    .bind(BENCH_DOMAIN)
+                    .bind(today)
+                    .fetch_one(db)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// i.e., how many distinct users were seeded (0 if `seed` hasn't been run)
+pub async fn count_users(db: &PgPool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT COUNT(DISTINCT username) FROM direct_memberships WHERE group_domain = $1",
+    )
+    .bind(BENCH_DOMAIN)
+    .fetch_one(db)
+    .await
+}
@@ -0,0 +1,244 @@
+// load-testing harness for permission evaluation, which seeds a large synthetic
+// organization into a (non-production!) database and then times the queries
+// behind permission checks and membership calculations, so that performance
+// regressions can be caught before deployment
+//
+// everything seeded can be removed again afterwards with the `clean` subcommand
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::PathBuf,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use clap::{Parser, Subcommand};
+use fixtures::{Lcg, Scenario, USERNAME_PREFIX};
+use sqlx::PgPool;
+
+// shared with Hive itself, so that exactly the same queries are timed
+#[path = "../../clock.rs"]
+#[allow(dead_code)]
+mod clock;
+mod fixtures;
+#[path = "../../perms/queries.rs"]
+mod queries;
+
+const DB_URL_VAR: &str = "HIVE_DB_URL";
+const TIMEZONE_VAR: &str = "HIVE_TIMEZONE";
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct CliArgs {
+    /// Database to use; never point this at production [default: $HIVE_DB_URL]
+    #[arg(short, long)]
+    db_url: Option<String>,
+
+    /// Timezone for membership periods, as configured for Hive [default: $HIVE_TIMEZONE, or local]
+    #[arg(long)]
+    timezone: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate a synthetic organization (replacing any previous one)
+    Seed {
+        /// Number of distinct users
+        #[arg(long, default_value_t = 10_000)]
+        users: u32,
+
+        /// Number of groups
+        #[arg(long, default_value_t = 2_000)]
+        groups: u32,
+
+        /// Number of subgroups per group (lower means deeper nesting)
+        #[arg(long, default_value_t = 3)]
+        fanout: u32,
+
+        /// Number of direct memberships per user
+        #[arg(long, default_value_t = 3)]
+        memberships: u32,
+    },
+
+    /// Time permission checks and membership calculations
+    Run {
+        /// Number of times each scenario is run
+        #[arg(short, long, default_value_t = 500)]
+        iterations: u32,
+
+        /// Save median timings to this file, for later comparison
+        #[arg(long)]
+        save: Option<PathBuf>,
+
+        /// Compare median timings against a previously saved file, failing
+        /// if any scenario got slower by more than the tolerance
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+
+        /// Allowed slowdown relative to the baseline, in percent
+        #[arg(long, default_value_t = 20)]
+        tolerance: u32,
+    },
+
+    /// Remove everything generated by `seed`
+    Clean,
+}
+
+#[rocket::main]
+async fn main() -> ExitCode {
+    let args = CliArgs::parse();
+
+    let Some(db_url) = args.db_url.or_else(|| env::var(DB_URL_VAR).ok()) else {
+        eprintln!("error: no database specified (use --db-url or ${DB_URL_VAR})");
+        return ExitCode::from(2);
+    };
+
+    clock::init(args.timezone.or_else(|| env::var(TIMEZONE_VAR).ok()));
+
+    let db = match clock::pool_options()
+        .max_connections(1)
+        .connect(&db_url)
+        .await
+    {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("error: failed to connect to database: {e}");
+            return ExitCode::from(2);
+        }
+    };
+
+    // assumes the database is already fully migrated (e.g., Hive ran once)
+    let result = match args.command {
+        Command::Seed {
+            users,
+            groups,
+            fanout,
+            memberships,
+        } => fixtures::seed(users, groups, fanout, memberships, &db)
+            .await
+            .map(|_| true)
+            .map_err(|e| e.to_string()),
+        Command::Run {
+            iterations,
+            save,
+            baseline,
+            tolerance,
+        } => run(iterations, save, baseline, tolerance, &db).await,
+        Command::Clean => fixtures::clean(&db)
+            .await
+            .map(|_| true)
+            .map_err(|e| e.to_string()),
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+struct Timings(Vec<Duration>);
+
+impl Timings {
+    fn percentile(&self, p: usize) -> Duration {
+        self.0[(self.0.len() - 1) * p / 100]
+    }
+
+    fn mean(&self) -> Duration {
+        self.0.iter().sum::<Duration>() / self.0.len() as u32
+    }
+}
+
+async fn run(
+    iterations: u32,
+    save: Option<PathBuf>,
+    baseline: Option<PathBuf>,
+    tolerance: u32,
+    db: &PgPool,
+) -> Result<bool, String> {
+    let n_users = fixtures::count_users(db).await.map_err(|e| e.to_string())?;
+
+    if n_users == 0 {
+        return Err("no benchmark data found (run `seed` first)".to_owned());
+    }
+
+    let iterations = iterations.max(1);
+
+    let mut results = BTreeMap::new();
+
+    for scenario in Scenario::ALL {
+        // same inputs for every scenario, so they're comparable
+        let mut rng = Lcg(0xbe7c4);
+        let mut timings = Vec::with_capacity(iterations as usize);
+
+        for _ in 0..iterations {
+            let username = format!("{USERNAME_PREFIX}{}", rng.next(n_users as u32));
+            let scope = format!("s{}", rng.next(37));
+
+            let start = Instant::now();
+            scenario
+                .run(&username, &scope, db)
+                .await
+                .map_err(|e| format!("{}: {e}", scenario.name()))?;
+            timings.push(start.elapsed());
+        }
+
+        results.insert(scenario.name().to_owned(), Timings(timings));
+    }
+
+    println!(
+        "{:<28}{:>12}{:>12}{:>12}{:>12}",
+        "scenario", "mean", "p50", "p95", "p99"
+    );
+
+    let mut medians = BTreeMap::new();
+    for (name, timings) in &mut results {
+        timings.0.sort_unstable();
+
+        println!(
+            "{:<28}{:>12.2?}{:>12.2?}{:>12.2?}{:>12.2?}",
+            name,
+            timings.mean(),
+            timings.percentile(50),
+            timings.percentile(95),
+            timings.percentile(99),
+        );
+
+        medians.insert(name.clone(), timings.percentile(50).as_micros() as u64);
+    }
+
+    if let Some(path) = save {
+        let json = serde_json::to_string_pretty(&medians).map_err(|e| e.to_string())?;
+        fs::write(&path, json).map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+
+    let Some(path) = baseline else {
+        return Ok(true);
+    };
+
+    let contents = fs::read(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let baseline: BTreeMap<String, u64> =
+        serde_json::from_slice(&contents).map_err(|e| format!("{}: {e}", path.display()))?;
+
+    let mut ok = true;
+    for (name, median) in medians {
+        let Some(previous) = baseline.get(&name) else {
+            continue; // new scenario
+        };
+
+        let limit = previous * (100 + tolerance as u64) / 100;
+        if median > limit {
+            eprintln!("regression: {name} took {median}µs (baseline: {previous}µs)");
+            ok = false;
+        }
+    }
+
+    Ok(ok)
+}
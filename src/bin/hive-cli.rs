@@ -0,0 +1,385 @@
+// command-line tool for common administrative tasks, which talks to a running
+// Hive instance through its API (v1) rather than to the database directly, so
+// it can be used from anywhere with an appropriate API token
+
+use std::{env, process::ExitCode, str::FromStr};
+
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use reqwest::{Client, Method, RequestBuilder, Url};
+use serde_json::Value;
+
+// so that "today" is the same day as for the instance being talked to
+#[path = "../clock.rs"]
+#[allow(dead_code)]
+mod clock;
+
+const URL_VAR: &str = "HIVE_API_URL";
+const TOKEN_VAR: &str = "HIVE_API_TOKEN";
+const TIMEZONE_VAR: &str = "HIVE_TIMEZONE";
+const IMPERSONATION_HEADER: &str = "X-Hive-Impersonate-System";
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct CliArgs {
+    /// Base URL of the Hive instance to talk to [default: $HIVE_API_URL]
+    #[arg(short, long)]
+    url: Option<String>,
+
+    /// Secret of the API token to authenticate with [default: $HIVE_API_TOKEN]
+    #[arg(short, long)]
+    token: Option<String>,
+
+    /// Act as another system (token needs $hive:api-impersonate-system)
+    #[arg(short, long)]
+    system: Option<String>,
+
+    /// Timezone for membership periods, as configured for Hive [default: $HIVE_TIMEZONE, or local]
+    #[arg(long)]
+    timezone: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage direct members of a group
+    #[command(subcommand)]
+    Members(MembersCommand),
+
+    /// Check if a user has a permission (exits with 0 if so, 1 if not)
+    Check {
+        /// Username of the user to check
+        username: String,
+
+        /// ID of the permission (in the token's or impersonated system)
+        perm_id: String,
+
+        /// Specific scope to check for, if the permission has any
+        #[arg(long)]
+        scope: Option<String>,
+    },
+
+    /// Manage integrations
+    #[command(subcommand)]
+    Integrations(IntegrationsCommand),
+
+    /// Manage groups
+    #[command(subcommand)]
+    Groups(GroupsCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum MembersCommand {
+    /// List current and future direct members of a group
+    List {
+        /// Group to list, as id@domain
+        group: GroupKey,
+    },
+
+    /// Add a direct member to a group
+    Add {
+        /// Group to add to, as id@domain
+        group: GroupKey,
+
+        /// Username of the user to add
+        username: String,
+
+        /// First day of the membership [default: today]
+        #[arg(long)]
+        from: Option<NaiveDate>,
+
+        /// Last day of the membership
+        #[arg(long)]
+        until: NaiveDate,
+
+        /// Whether the user should be a manager of the group
+        #[arg(long)]
+        manager: bool,
+    },
+
+    /// Remove all direct memberships of a user from a group
+    Remove {
+        /// Group to remove from, as id@domain
+        group: GroupKey,
+
+        /// Username of the user to remove
+        username: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum IntegrationsCommand {
+    /// Run an integration task right away (in the background)
+    Run {
+        /// ID of the integration
+        integration_id: String,
+
+        /// ID of the task to run
+        task_id: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum GroupsCommand {
+    /// Print all groups as JSON
+    Export {
+        /// Also include each group's direct memberships
+        #[arg(long)]
+        members: bool,
+
+        /// Also include memberships that haven't started yet
+        #[arg(long, requires = "members")]
+        include_future: bool,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct GroupKey {
+    id: String,
+    domain: String,
+}
+
+impl FromStr for GroupKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('@') {
+            Some((id, domain)) if !id.is_empty() && !domain.is_empty() => Ok(Self {
+                id: id.to_owned(),
+                domain: domain.to_owned(),
+            }),
+            _ => Err("expected group in the format id@domain".to_owned()),
+        }
+    }
+}
+
+struct ApiClient {
+    client: Client,
+    base_url: Url,
+    token: String,
+    system: Option<String>,
+}
+
+impl ApiClient {
+    fn request(&self, method: Method, segments: &[&str]) -> RequestBuilder {
+        // segments are percent-encoded as needed, unlike with format!
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("URL was checked to be a base")
+            .pop_if_empty()
+            .extend(["api", "v1"])
+            .extend(segments);
+
+        let mut request = self.client.request(method, url).bearer_auth(&self.token);
+
+        if let Some(system) = &self.system {
+            request = request.header(IMPERSONATION_HEADER, system);
+        }
+
+        request
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Value, String> {
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+
+        let body = response.bytes().await.map_err(|e| e.to_string())?;
+        let body = if body.is_empty() {
+            Value::Null // e.g., 202 Accepted or 204 No Content
+        } else {
+            serde_json::from_slice(&body).map_err(|e| format!("{status}: {e}"))?
+        };
+
+        if status.is_success() {
+            Ok(body)
+        } else {
+            // see AppErrorDto for the format
+            let key = body["info"]["key"].as_str().unwrap_or("unknown");
+            match body["info"].get("details") {
+                Some(details) => Err(format!("{status}: {key} {details}")),
+                None => Err(format!("{status}: {key}")),
+            }
+        }
+    }
+
+    async fn memberships(&self, group: &GroupKey, future: bool) -> Result<Vec<Value>, String> {
+        let request = self
+            .request(
+                Method::GET,
+                &["group", &group.domain, &group.id, "memberships"],
+            )
+            .query(&[("future", if future { "true" } else { "false" })]);
+
+        match self.send(request).await? {
+            Value::Array(memberships) => Ok(memberships),
+            other => Err(format!("unexpected response: {other}")),
+        }
+    }
+}
+
+#[rocket::main]
+async fn main() -> ExitCode {
+    let args = CliArgs::parse();
+
+    let Some(base_url) = args.url.or_else(|| env::var(URL_VAR).ok()) else {
+        eprintln!("error: no URL specified (use --url or ${URL_VAR})");
+        return ExitCode::from(2);
+    };
+
+    let Some(token) = args.token.or_else(|| env::var(TOKEN_VAR).ok()) else {
+        eprintln!("error: no API token specified (use --token or ${TOKEN_VAR})");
+        return ExitCode::from(2);
+    };
+
+    clock::init(args.timezone.or_else(|| env::var(TIMEZONE_VAR).ok()));
+
+    let base_url = match Url::parse(&base_url) {
+        Ok(url) if !url.cannot_be_a_base() => url,
+        _ => {
+            eprintln!("error: invalid URL: {base_url}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let api = ApiClient {
+        client: Client::new(),
+        base_url,
+        token,
+        system: args.system,
+    };
+
+    match run(args.command, &api).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+// Ok(false) only means a negative answer (e.g., to a permission check)
+async fn run(command: Command, api: &ApiClient) -> Result<bool, String> {
+    match command {
+        Command::Members(MembersCommand::List { group }) => {
+            for membership in api.memberships(&group, true).await? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    membership["username"].as_str().unwrap_or_default(),
+                    membership["from"].as_str().unwrap_or_default(),
+                    membership["until"].as_str().unwrap_or_default(),
+                    if membership["manager"] == true {
+                        "manager"
+                    } else {
+                        "member"
+                    },
+                    membership["id"].as_str().unwrap_or_default(),
+                );
+            }
+        }
+        Command::Members(MembersCommand::Add {
+            group,
+            username,
+            from,
+            until,
+            manager,
+        }) => {
+            let from = from.unwrap_or_else(clock::today).to_string();
+            let until = until.to_string();
+
+            let request = api
+                .request(
+                    Method::POST,
+                    &["group", &group.domain, &group.id, "memberships"],
+                )
+                .query(&[
+                    ("username", username.as_str()),
+                    ("from", from.as_str()),
+                    ("until", until.as_str()),
+                    ("manager", if manager { "true" } else { "false" }),
+                ]);
+
+            let added = api.send(request).await?;
+            println!("Added membership {}", added["id"].as_str().unwrap_or("?"));
+        }
+        Command::Members(MembersCommand::Remove { group, username }) => {
+            let ids: Vec<_> = api
+                .memberships(&group, true)
+                .await?
+                .into_iter()
+                .filter(|membership| membership["username"] == username.as_str())
+                .filter_map(|membership| membership["id"].as_str().map(ToOwned::to_owned))
+                .collect();
+
+            if ids.is_empty() {
+                return Err(format!("{username} is not a direct member of the group"));
+            }
+
+            for id in ids {
+                let request = api.request(
+                    Method::DELETE,
+                    &["group", &group.domain, &group.id, "membership", &id],
+                );
+
+                api.send(request).await?;
+                println!("Removed membership {id}");
+            }
+        }
+        Command::Check {
+            username,
+            perm_id,
+            scope,
+        } => {
+            let mut segments = vec!["user", username.as_str(), "permission", perm_id.as_str()];
+            if let Some(scope) = &scope {
+                segments.extend(["scope", scope.as_str()]);
+            }
+
+            let request = api.request(Method::GET, &segments);
+
+            let has_permission = api.send(request).await? == true;
+            println!("{has_permission}");
+
+            return Ok(has_permission);
+        }
+        Command::Integrations(IntegrationsCommand::Run {
+            integration_id,
+            task_id,
+        }) => {
+            let request = api.request(
+                Method::POST,
+                &["integration", &integration_id, "task", &task_id, "run"],
+            );
+
+            api.send(request).await?;
+            println!("Triggered run of task {task_id} (integration {integration_id})");
+        }
+        Command::Groups(GroupsCommand::Export {
+            members,
+            include_future,
+        }) => {
+            let mut groups = match api.send(api.request(Method::GET, &["groups"])).await? {
+                Value::Array(groups) => groups,
+                other => return Err(format!("unexpected response: {other}")),
+            };
+
+            if members {
+                for group in &mut groups {
+                    let key = GroupKey {
+                        id: group["id"].as_str().unwrap_or_default().to_owned(),
+                        domain: group["domain"].as_str().unwrap_or_default().to_owned(),
+                    };
+
+                    group["members"] = Value::Array(api.memberships(&key, include_future).await?);
+                }
+            }
+
+            let json = serde_json::to_string_pretty(&groups).map_err(|e| e.to_string())?;
+            println!("{json}");
+        }
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,88 @@
+use std::sync::OnceLock;
+
+use chrono::{Local, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use log::*;
+use sqlx::postgres::PgPoolOptions;
+
+// IANA name of the timezone (e.g., Europe/Stockholm) in which membership
+// periods are interpreted, i.e., when one day ends and the next begins. If
+// unset, the server's local timezone is used (which might differ between the
+// app server, the database and integrations running elsewhere). Global rather
+// than managed state since "today" is needed almost everywhere.
+static TIMEZONE: OnceLock<Tz> = OnceLock::new();
+
+// group domains whose memberships can start and end at a specific time of day
+// (e.g., from 12:00 on handover day), rather than only whole days
+static TIME_PRECISION_DOMAINS: OnceLock<Vec<String>> = OnceLock::new();
+
+// must be called on startup, before anything computes dates
+pub fn init(timezone: Option<String>) {
+    let Some(timezone) = timezone else {
+        return;
+    };
+
+    let Ok(timezone) = timezone.parse::<Tz>() else {
+        panic!("Fatal error: timezone `{timezone}` is not a known IANA timezone")
+    };
+
+    info!("Using timezone {timezone} for membership boundaries");
+
+    if TIMEZONE.set(timezone).is_err() {
+        warn!("Timezone was already initialized; ignoring");
+    }
+}
+
+pub fn init_time_precision_domains(domains: Vec<String>) {
+    if TIME_PRECISION_DOMAINS.set(domains).is_err() {
+        warn!("Time precision domains were already initialized; ignoring");
+    }
+}
+
+pub fn has_time_precision(domain: &str) -> bool {
+    TIME_PRECISION_DOMAINS
+        .get()
+        .is_some_and(|domains| domains.iter().any(|d| d == domain))
+}
+
+pub fn timezone() -> Option<&'static str> {
+    TIMEZONE.get().map(|tz| tz.name())
+}
+
+// `None` if the server's local timezone should be used instead
+pub fn tz() -> Option<Tz> {
+    TIMEZONE.get().copied()
+}
+
+// the day against which membership periods (`from`/`until`) are compared
+pub fn today() -> NaiveDate {
+    now().date()
+}
+
+// for memberships with a time of day (see `has_time_precision`)
+pub fn now() -> NaiveDateTime {
+    match TIMEZONE.get() {
+        Some(tz) => Utc::now().with_timezone(tz).naive_local(),
+        None => Local::now().naive_local(),
+    }
+}
+
+// so that, e.g., CURRENT_DATE in SQL agrees with `today()`
+pub fn pool_options() -> PgPoolOptions {
+    let options = PgPoolOptions::new();
+
+    let Some(timezone) = timezone() else {
+        return options;
+    };
+
+    options.after_connect(move |conn, _| {
+        Box::pin(async move {
+            sqlx::query("SELECT set_config('TimeZone', $1, false)")
+                .bind(timezone)
+                .execute(conn)
+                .await?;
+
+            Ok(())
+        })
+    })
+}
@@ -1,4 +1,4 @@
-use std::{net::IpAddr, path::PathBuf};
+use std::{collections::HashMap, net::IpAddr, path::PathBuf};
 
 use clap::Parser;
 use figment::{
@@ -7,7 +7,10 @@ use figment::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::{auth::oidc::OidcConfig, logging::Verbosity};
+use crate::{
+    auth::oidc::OidcConfig, logging::Verbosity, mailer::Mailer, notifications::ChannelConfig,
+    secrets::VaultConfig, services::public_statistics::PublicMetric,
+};
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
@@ -26,6 +29,73 @@ pub struct Config {
     #[serde(default)]
     pub identity_resolver_endpoint: Option<String>,
 
+    #[serde(default)]
+    pub identity_search_endpoint: Option<String>,
+
+    #[serde(default = "defaults::user_email_domain")]
+    pub user_email_domain: String,
+
+    #[serde(default)]
+    pub maintenance: bool,
+
+    #[serde(default)]
+    pub seed_dev_data: bool,
+
+    #[serde(default)]
+    pub db_replica_url: Option<String>,
+
+    #[serde(default)]
+    pub protected_domains: Vec<String>,
+
+    #[serde(default)]
+    pub external_domains: Vec<String>,
+
+    #[serde(default)]
+    pub auto_link_domains: Vec<String>,
+
+    #[serde(default)]
+    pub timezone: Option<String>,
+
+    #[serde(default)]
+    pub time_precision_domains: Vec<String>,
+
+    #[serde(default)]
+    pub public_directory: bool,
+
+    #[serde(default)]
+    pub public_statistics: Vec<PublicMetric>,
+
+    #[serde(default)]
+    pub manager_digests: bool,
+
+    #[serde(default)]
+    pub membership_emails: bool,
+
+    #[serde(default)]
+    pub mailer_endpoint: Option<String>,
+
+    #[serde(default)]
+    pub mailer_api_key: Option<String>,
+
+    #[serde(default)]
+    pub mailer_sender: Option<String>,
+
+    // only configurable through `hive.toml` (or `HIVE_NOTIFICATION_CHANNELS`),
+    // since each channel is a table of its own; see `notifications`
+    #[serde(default)]
+    pub notification_channels: Vec<ChannelConfig>,
+
+    // also only through `hive.toml`, as an `[integration_task_timeouts]` table
+    // of seconds keyed like `"gworkspace/sync-to-directory"`
+    #[serde(default)]
+    pub integration_task_timeouts: HashMap<String, u64>,
+
+    #[serde(default)]
+    pub secrets_vault_addr: Option<String>,
+
+    #[serde(default)]
+    pub secrets_vault_token: Option<String>,
+
     // no default! must be specified in some way
     pub db_url: String,
     pub secret_key: String,
@@ -78,6 +148,29 @@ impl Config {
         }
     }
 
+    pub fn get_mailer(&self) -> Option<Mailer> {
+        let endpoint = self.mailer_endpoint.as_ref()?;
+
+        let (Some(api_key), Some(sender)) = (&self.mailer_api_key, &self.mailer_sender) else {
+            panic!("Fatal error: mailer endpoint is set, but API key and/or sender are not")
+        };
+
+        Some(Mailer::new(endpoint, api_key, sender))
+    }
+
+    pub fn get_vault_config(&self) -> Option<VaultConfig> {
+        let addr = self.secrets_vault_addr.as_ref()?;
+
+        let Some(token) = &self.secrets_vault_token else {
+            panic!("Fatal error: Vault address is set, but token is not")
+        };
+
+        Some(VaultConfig {
+            addr: addr.clone(),
+            token: token.clone(),
+        })
+    }
+
     pub fn get_oidc_config(&self) -> OidcConfig {
         OidcConfig {
             issuer_url: self.oidc_issuer_url.clone(),
@@ -112,6 +205,11 @@ pub struct CliArgs {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub db_url: Option<String>,
 
+    /// Read-only PostgreSQL replica connection string, for heavy reads [optional]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub db_replica_url: Option<String>,
+
     /// Random 64-byte hex string (length 128) to use as secret key [no default]
     #[arg(short = 'k', long)]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -137,6 +235,16 @@ pub struct CliArgs {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub identity_resolver_endpoint: Option<String>,
 
+    /// HTTP URL to query when searching for users by name or username [optional]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity_search_endpoint: Option<String>,
+
+    /// Domain at which every username is also an email address [default: kth.se]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_email_domain: Option<String>,
+
     /// How much information to show and log [default: normal]
     #[arg(short, long)]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -146,6 +254,86 @@ pub struct CliArgs {
     #[arg(short = 'f', long)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub log_file: Option<PathBuf>,
+
+    /// Start in read-only maintenance mode [default: false]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<bool>,
+
+    /// Populate a fresh database with example data for development [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed_dev_data: Option<bool>,
+
+    /// Group domains whose structural changes require a second approval [default: none]
+    #[arg(long, value_delimiter = ',')]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protected_domains: Option<Vec<String>>,
+
+    /// Group domains for external users, whose groups never confer permissions [default: none]
+    #[arg(long, value_delimiter = ',')]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_domains: Option<Vec<String>>,
+
+    /// Group domains whose new groups are linked under their suggested parent by default [default: none]
+    #[arg(long, value_delimiter = ',')]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_link_domains: Option<Vec<String>>,
+
+    /// IANA timezone in which membership periods start and end (and integration tasks are scheduled), e.g. Europe/Stockholm [default: server's local]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+
+    /// Group domains whose memberships can start and end at a time of day, in member listings only (permissions still apply per whole day) [default: none]
+    #[arg(long, value_delimiter = ',')]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_precision_domains: Option<Vec<String>>,
+
+    /// Publicly list groups tagged #hive:public at /public/groups [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_directory: Option<bool>,
+
+    /// Aggregate metrics published at /public/statistics.json, e.g. active_volunteers [default: none]
+    #[arg(long, value_delimiter = ',')]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_statistics: Option<Vec<PublicMetric>>,
+
+    /// Email group managers a weekly digest of changes (requires mailer) [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manager_digests: Option<bool>,
+
+    /// Email users whenever others change their memberships (requires mailer) [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub membership_emails: Option<bool>,
+
+    /// HTTP endpoint of the mailing service used to send emails [optional]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mailer_endpoint: Option<String>,
+
+    /// API key to authenticate with the mailing service [optional]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mailer_api_key: Option<String>,
+
+    /// Email address from which emails are sent [optional]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mailer_sender: Option<String>,
+
+    /// HashiCorp Vault address, for `vault://` secret references [optional]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secrets_vault_addr: Option<String>,
+
+    /// Token to authenticate with HashiCorp Vault [optional]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secrets_vault_token: Option<String>,
 }
 
 // unfortunately #[serde(default = "path")] only allows specifying
@@ -174,4 +362,8 @@ mod defaults {
     pub fn log_file() -> PathBuf {
         PathBuf::from("/tmp/hive.log")
     }
+
+    pub fn user_email_domain() -> String {
+        "kth.se".to_owned()
+    }
 }
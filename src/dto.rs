@@ -11,10 +11,14 @@ pub mod api_tokens;
 pub mod datetime;
 pub mod errors;
 pub mod groups;
+pub mod imports;
 pub mod logs;
+pub mod maintenance;
+pub mod operational_years;
 pub mod permissions;
 pub mod systems;
 pub mod tags;
+pub mod users;
 
 #[derive(sqlx::Type, Serialize, Clone, Copy)]
 #[sqlx(transparent)]
@@ -28,6 +32,12 @@ impl<'v> FromFormField<'v> for TrimmedStr<'v> {
     }
 }
 
+impl<'v> From<&'v str> for TrimmedStr<'v> {
+    fn from(s: &'v str) -> Self {
+        Self(s.trim())
+    }
+}
+
 impl<'v> Deref for TrimmedStr<'v> {
     type Target = &'v str;
 
@@ -80,7 +90,7 @@ impl PartialEq<TrimmedStr<'_>> for String {
     }
 }
 
-#[derive(sqlx::Type, Serialize, Clone, Copy, Debug)]
+#[derive(sqlx::Type, Serialize, Clone, Copy, PartialEq, Debug)]
 #[sqlx(transparent)]
 #[serde(transparent)]
 pub struct OptionalStr<'v>(Option<&'v str>);
@@ -109,6 +119,12 @@ impl<'v> Deref for OptionalStr<'v> {
     }
 }
 
+impl<'v> From<Option<&'v str>> for OptionalStr<'v> {
+    fn from(s: Option<&'v str>) -> Self {
+        Self(s.map(str::trim).filter(|s| !s.is_empty()))
+    }
+}
+
 impl<'v> From<&OptionalStr<'v>> for Option<&'v str> {
     fn from(t: &OptionalStr<'v>) -> Self {
         **t
@@ -121,6 +137,12 @@ impl From<OptionalStr<'_>> for serde_json::Value {
     }
 }
 
+impl PartialEq<OptionalStr<'_>> for Option<String> {
+    fn eq(&self, other: &OptionalStr) -> bool {
+        self.as_deref() == **other
+    }
+}
+
 fn valid_slug<'v, T: Into<&'v str>>(s: T) -> form::Result<'v, ()> {
     let re = Regex::new("^[a-z0-9]+(-[a-z0-9]+)*$").unwrap();
 
@@ -151,6 +173,16 @@ fn valid_username<'v, T: Into<&'v str>>(s: T) -> form::Result<'v, ()> {
     }
 }
 
+fn valid_http_url<'v, T: Into<&'v str>>(s: T) -> form::Result<'v, ()> {
+    let re = Regex::new("^https?://[^\\s/$.?#].[^\\s]*$").unwrap();
+
+    if re.is_match(s.into()) {
+        Ok(())
+    } else {
+        Err(form::Error::validation("invalid http(s) url").into())
+    }
+}
+
 fn option_len<'v, V, L, R>(opt: &Option<V>, range: R) -> form::Result<'v, ()>
 where
     V: form::validate::Len<L>,
@@ -1,7 +1,7 @@
 use chrono::Local;
-use rocket::FromForm;
+use rocket::{FromForm, form};
 
-use super::{TrimmedStr, datetime::BrowserDateTimeDto};
+use super::{OptionalStr, TrimmedStr, datetime::BrowserDateTimeDto};
 
 #[derive(FromForm)]
 pub struct CreateApiTokenDto<'v> {
@@ -9,4 +9,35 @@ pub struct CreateApiTokenDto<'v> {
     pub description: TrimmedStr<'v>,
     #[field(validate = with(|o| o.as_ref().map(|e| e.0 >= Local::now()).unwrap_or(true), "invalid past expiration"))]
     pub expiration: Option<BrowserDateTimeDto>,
+    // both are space-separated; token only "sees" matching groups if present
+    #[field(validate = space_separated(super::valid_domain::<&str>))]
+    pub group_domains: OptionalStr<'v>,
+    #[field(validate = space_separated(super::valid_slug::<&str>))]
+    pub group_tags: OptionalStr<'v>,
+    pub requires_signing: bool,
+    pub read_only: bool,
+    pub groups_only: bool,
+    #[field(validate = with(|p| !(*p && self.groups_only), "cannot be limited to both groups and permissions"))]
+    pub permissions_only: bool,
+}
+
+impl<'v> CreateApiTokenDto<'v> {
+    pub fn restricted_group_domains(&self) -> Vec<&'v str> {
+        split_space_separated(self.group_domains)
+    }
+
+    pub fn restricted_group_tags(&self) -> Vec<&'v str> {
+        split_space_separated(self.group_tags)
+    }
+}
+
+fn split_space_separated(s: OptionalStr<'_>) -> Vec<&str> {
+    (*s).into_iter().flat_map(str::split_whitespace).collect()
+}
+
+fn space_separated<'v, F>(s: &OptionalStr<'v>, check: F) -> form::Result<'v, ()>
+where
+    F: Fn(&'v str) -> form::Result<'v, ()>,
+{
+    split_space_separated(*s).into_iter().try_for_each(check)
 }
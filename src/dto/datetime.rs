@@ -1,6 +1,6 @@
 use std::fmt;
 
-use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime};
 use rocket::form;
 use serde::Serialize;
 
@@ -12,6 +12,7 @@ use serde::Serialize;
 // with absolutely no room for variation, per MDN
 const BROWSER_DATE_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
 const BROWSER_DATE_FORMAT: &str = "%Y-%m-%d";
+const BROWSER_TIME_FORMAT: &str = "%H:%M";
 
 #[derive(sqlx::Type, Serialize, Clone, Debug)]
 #[sqlx(transparent)]
@@ -75,3 +76,31 @@ impl From<BrowserDateDto> for serde_json::Value {
         serde_json::Value::from(value.to_string())
     }
 }
+
+#[derive(sqlx::Type, Serialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[sqlx(transparent)]
+#[serde(transparent)]
+pub struct BrowserTimeDto(pub NaiveTime);
+
+impl fmt::Display for BrowserTimeDto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.format(BROWSER_TIME_FORMAT))
+    }
+}
+
+#[rocket::async_trait]
+impl<'f> form::FromFormField<'f> for BrowserTimeDto {
+    fn from_value(field: form::ValueField<'f>) -> form::Result<'f, Self> {
+        if let Ok(naive) = NaiveTime::parse_from_str(field.value, BROWSER_TIME_FORMAT) {
+            Ok(Self(naive))
+        } else {
+            Err(form::Error::validation("invalid time format").into())
+        }
+    }
+}
+
+impl From<BrowserTimeDto> for serde_json::Value {
+    fn from(value: BrowserTimeDto) -> Self {
+        serde_json::Value::from(value.to_string())
+    }
+}
@@ -1,7 +1,11 @@
+use rocket::http::Status;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{errors::AppError, guards::lang::Language, services::groups::AuthorityInGroup};
+use crate::{
+    errors::AppError, guards::lang::Language, models::GroupState,
+    services::groups::AuthorityInGroup,
+};
 
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "key", content = "context")]
@@ -12,9 +16,17 @@ enum InnerAppErrorDto {
     PipelineError, // anything related to handling requests/responses (500)
     #[serde(rename = "self-preservation")]
     SelfPreservation,
+    #[serde(rename = "maintenance")]
+    MaintenanceMode,
+    #[serde(rename = "import.invalid")]
+    InvalidLegacyDump { reason: String },
+    #[serde(rename = "query.invalid")]
+    InvalidQueryParameter { name: String },
 
     #[serde(rename = "forbidden")]
     NotAllowed,
+    #[serde(rename = "admin.forbidden")]
+    AdministratorsOnly,
     #[serde(rename = "group.forbidden")]
     InsufficientAuthorityInGroup { min: AuthorityInGroup },
     #[serde(rename = "auth.login.flow.expired")]
@@ -24,11 +36,15 @@ enum InnerAppErrorDto {
     NoSuchSystem { id: String },
     #[serde(rename = "system.id.duplicate")]
     DuplicateSystemId { id: String },
+    #[serde(rename = "system.manifest.invalid")]
+    InvalidSystemManifest { reason: String },
 
     #[serde(rename = "api-token.unknown")]
     NoSuchApiToken { id: Uuid },
     #[serde(rename = "api-token.description.ambiguous-in-system")]
     AmbiguousApiToken { description: String },
+    #[serde(rename = "api-token.signing.unavailable")]
+    UnsignableApiToken { id: Uuid },
 
     #[serde(rename = "permission.unknown")]
     NoSuchPermission { system_id: String, perm_id: String },
@@ -44,6 +60,12 @@ enum InnerAppErrorDto {
     MissingPermissionScope { system_id: String, perm_id: String },
     #[serde(rename = "permission.assignment.scope.extraneous")]
     ExtraneousPermissionScope { system_id: String, perm_id: String },
+    #[serde(rename = "permission.assignment.scope.invalid")]
+    InvalidPermissionScope {
+        system_id: String,
+        perm_id: String,
+        scope: String,
+    },
 
     #[serde(rename = "tag.unknown")]
     NoSuchTag { system_id: String, tag_id: String },
@@ -91,6 +113,98 @@ enum InnerAppErrorDto {
 
     #[serde(rename = "membership.unknown")]
     NoSuchMembership { id: String },
+    #[serde(rename = "membership.appointment.too-long")]
+    AppointmentTooLong { username: String },
+
+    #[serde(rename = "group.transfer.unknown")]
+    NoPendingTransfer,
+    #[serde(rename = "group.transfer.not-recipient")]
+    NotTransferRecipient,
+
+    #[serde(rename = "deletion.unknown")]
+    NoSuchDeletion { id: Uuid },
+    #[serde(rename = "deletion.undo.conflict")]
+    UndoConflict,
+
+    #[serde(rename = "change.unknown")]
+    NoSuchPendingChange { id: Uuid },
+    #[serde(rename = "change.approve.self")]
+    SelfApproval,
+    #[serde(rename = "change.unapprovable")]
+    UnapprovableProtectedChange { domain: String },
+
+    #[serde(rename = "tag.verification.invalid")]
+    InvalidVerificationLink,
+
+    #[serde(rename = "group.link.unknown")]
+    NoSuchGroupLink { id: Uuid },
+
+    #[serde(rename = "group.external.permission")]
+    ExternalDomainPermission { domain: String },
+    #[serde(rename = "group.external.subgroup")]
+    ExternalSubgroup {
+        child_id: String,
+        child_domain: String,
+    },
+
+    #[serde(rename = "system.delete.unconfirmed")]
+    DeletionNotConfirmed { id: String },
+    #[serde(rename = "system.delete.self-confirmed")]
+    SelfConfirmedDeletion,
+
+    #[serde(rename = "webhook.unknown")]
+    NoSuchWebhook { id: Uuid },
+    #[serde(rename = "webhook.delivery.unknown")]
+    NoSuchWebhookDelivery { id: Uuid },
+
+    #[serde(rename = "group.share.unknown")]
+    NoSuchMemberListShare { id: Uuid },
+
+    #[serde(rename = "membership.appointment.missing-justification")]
+    MissingAppointmentJustification { username: String },
+
+    #[serde(rename = "group.member-cap.exceeded")]
+    MemberCapExceeded { cap: i32 },
+
+    #[serde(rename = "operational-year.unknown")]
+    NoSuchOperationalYear { id: Uuid },
+    #[serde(rename = "operational-year.name.duplicate")]
+    DuplicateOperationalYearName { name: String },
+    #[serde(rename = "operational-year.overlapping")]
+    OverlappingOperationalYear { name: String },
+    #[serde(rename = "membership.operational-year.outside")]
+    MembershipOutsideOperationalYear { name: String },
+
+    #[serde(rename = "self-preservation.last-holder")]
+    LastHivePermissionHolder { permission: String },
+
+    #[serde(rename = "integration.task.unknown")]
+    NoSuchIntegrationTask { integration: String, task: String },
+    #[serde(rename = "integration.task.schedule.invalid")]
+    InvalidTaskSchedule { schedule: String },
+    #[serde(rename = "integration.run.unknown")]
+    NoSuchTaskRun { id: Uuid },
+
+    #[serde(rename = "tag.derivation.invalid")]
+    InvalidTagDerivation { system_id: String, tag_id: String },
+    #[serde(rename = "tag.derivation.duplicate")]
+    DuplicateTagDerivation { permission: String },
+    #[serde(rename = "tag.derivation.unknown")]
+    NoSuchTagDerivation { id: Uuid },
+
+    #[serde(rename = "group.state.transition.invalid")]
+    InvalidGroupStateTransition { from: GroupState, to: GroupState },
+
+    #[serde(rename = "membership.pending.unknown")]
+    NoSuchPendingMembership { id: Uuid },
+
+    #[serde(rename = "group.invitation.unknown")]
+    NoSuchGroupInvitation { id: Uuid },
+    #[serde(rename = "group.invitation.unusable")]
+    GroupInvitationUnusable,
+
+    #[serde(rename = "group.comment.unknown")]
+    NoSuchGroupComment { id: Uuid },
 }
 
 impl From<AppError> for InnerAppErrorDto {
@@ -102,7 +216,9 @@ impl From<AppError> for InnerAppErrorDto {
             AppError::OidcAuthenticationError(..) => Self::PipelineError,
             AppError::StateSerializationError(..) => Self::PipelineError,
             AppError::StateDeserializationError(..) => Self::PipelineError,
+            AppError::ResponseSerializationError(..) => Self::PipelineError,
             AppError::IdentityResolutionError(..) => Self::PipelineError,
+            AppError::MailerError(..) => Self::PipelineError,
             AppError::ErrorDecodeFailure => Self::PipelineError,
             AppError::NotAllowed(..) => Self::NotAllowed,
             AppError::InsufficientAuthorityInGroup(min) => {
@@ -110,10 +226,16 @@ impl From<AppError> for InnerAppErrorDto {
             }
             AppError::AuthenticationFlowExpired => Self::AuthenticationFlowExpired,
             AppError::SelfPreservation => Self::SelfPreservation,
+            AppError::AdministratorsOnly => Self::AdministratorsOnly,
+            AppError::MaintenanceMode => Self::MaintenanceMode,
+            AppError::InvalidLegacyDump(reason) => Self::InvalidLegacyDump { reason },
+            AppError::InvalidQueryParameter(name) => Self::InvalidQueryParameter { name },
             AppError::NoSuchSystem(id) => Self::NoSuchSystem { id },
             AppError::DuplicateSystemId(id) => Self::DuplicateSystemId { id },
+            AppError::InvalidSystemManifest(reason) => Self::InvalidSystemManifest { reason },
             AppError::NoSuchApiToken(id) => Self::NoSuchApiToken { id },
             AppError::AmbiguousApiToken(description) => Self::AmbiguousApiToken { description },
+            AppError::UnsignableApiToken(id) => Self::UnsignableApiToken { id },
             AppError::NoSuchPermission(system_id, perm_id) => {
                 Self::NoSuchPermission { system_id, perm_id }
             }
@@ -131,6 +253,13 @@ impl From<AppError> for InnerAppErrorDto {
             AppError::ExtraneousPermissionScope(system_id, perm_id) => {
                 Self::ExtraneousPermissionScope { system_id, perm_id }
             }
+            AppError::InvalidPermissionScope(system_id, perm_id, scope) => {
+                Self::InvalidPermissionScope {
+                    system_id,
+                    perm_id,
+                    scope,
+                }
+            }
             AppError::NoSuchTag(system_id, tag_id) => Self::NoSuchTag { system_id, tag_id },
             AppError::DuplicateTagId(id) => Self::DuplicateTagId { id },
             AppError::DuplicateTagAssignment(system_id, tag_id, content) => {
@@ -170,6 +299,71 @@ impl From<AppError> for InnerAppErrorDto {
             AppError::RedundantMembership(username) => Self::RedundantMembership { username },
 
             AppError::NoSuchMembership(id) => Self::NoSuchMembership { id },
+            AppError::AppointmentTooLong(username) => Self::AppointmentTooLong { username },
+
+            AppError::NoPendingTransfer => Self::NoPendingTransfer,
+            AppError::NotTransferRecipient => Self::NotTransferRecipient,
+
+            AppError::NoSuchDeletion(id) => Self::NoSuchDeletion { id },
+            AppError::UndoConflict => Self::UndoConflict,
+
+            AppError::NoSuchPendingChange(id) => Self::NoSuchPendingChange { id },
+            AppError::SelfApproval => Self::SelfApproval,
+            AppError::UnapprovableProtectedChange(domain) => {
+                Self::UnapprovableProtectedChange { domain }
+            }
+
+            AppError::InvalidVerificationLink => Self::InvalidVerificationLink,
+
+            AppError::NoSuchGroupLink(id) => Self::NoSuchGroupLink { id },
+
+            AppError::ExternalDomainPermission(domain) => Self::ExternalDomainPermission { domain },
+            AppError::ExternalSubgroup(child_id, child_domain) => Self::ExternalSubgroup {
+                child_id,
+                child_domain,
+            },
+
+            AppError::DeletionNotConfirmed(id) => Self::DeletionNotConfirmed { id },
+            AppError::SelfConfirmedDeletion => Self::SelfConfirmedDeletion,
+
+            AppError::NoSuchWebhook(id) => Self::NoSuchWebhook { id },
+            AppError::NoSuchWebhookDelivery(id) => Self::NoSuchWebhookDelivery { id },
+
+            AppError::NoSuchMemberListShare(id) => Self::NoSuchMemberListShare { id },
+            AppError::MissingAppointmentJustification(username) => {
+                Self::MissingAppointmentJustification { username }
+            }
+            AppError::MemberCapExceeded(cap) => Self::MemberCapExceeded { cap },
+            AppError::NoSuchOperationalYear(id) => Self::NoSuchOperationalYear { id },
+            AppError::DuplicateOperationalYearName(name) => {
+                Self::DuplicateOperationalYearName { name }
+            }
+            AppError::OverlappingOperationalYear(name) => Self::OverlappingOperationalYear { name },
+            AppError::MembershipOutsideOperationalYear(name) => {
+                Self::MembershipOutsideOperationalYear { name }
+            }
+            AppError::LastHivePermissionHolder(permission) => {
+                Self::LastHivePermissionHolder { permission }
+            }
+            AppError::NoSuchIntegrationTask(integration, task) => {
+                Self::NoSuchIntegrationTask { integration, task }
+            }
+            AppError::InvalidTaskSchedule(schedule) => Self::InvalidTaskSchedule { schedule },
+            AppError::NoSuchTaskRun(id) => Self::NoSuchTaskRun { id },
+            AppError::InvalidTagDerivation(system_id, tag_id) => {
+                Self::InvalidTagDerivation { system_id, tag_id }
+            }
+            AppError::DuplicateTagDerivation(permission) => {
+                Self::DuplicateTagDerivation { permission }
+            }
+            AppError::NoSuchTagDerivation(id) => Self::NoSuchTagDerivation { id },
+            AppError::InvalidGroupStateTransition(from, to) => {
+                Self::InvalidGroupStateTransition { from, to }
+            }
+            AppError::NoSuchPendingMembership(id) => Self::NoSuchPendingMembership { id },
+            AppError::NoSuchGroupInvitation(id) => Self::NoSuchGroupInvitation { id },
+            AppError::GroupInvitationUnusable => Self::GroupInvitationUnusable,
+            AppError::NoSuchGroupComment(id) => Self::NoSuchGroupComment { id },
         }
     }
 }
@@ -190,6 +384,16 @@ impl InnerAppErrorDto {
             (Self::SelfPreservation, Language::Swedish) => "Självbevarelsedriftsfel",
             (Self::NotAllowed, Language::English) => "Not Allowed",
             (Self::NotAllowed, Language::Swedish) => "Inte tillåtet",
+            (Self::AdministratorsOnly, Language::English) => "Administrators Only",
+            (Self::AdministratorsOnly, Language::Swedish) => "Endast för administratörer",
+            (Self::MaintenanceMode, Language::English) => "Undergoing Maintenance",
+            (Self::MaintenanceMode, Language::Swedish) => "Underhåll pågår",
+            (Self::InvalidLegacyDump { .. }, Language::English) => "Invalid Legacy Dump",
+            (Self::InvalidLegacyDump { .. }, Language::Swedish) => {
+                "Ogiltig export från gammalt system"
+            }
+            (Self::InvalidQueryParameter { .. }, Language::English) => "Invalid Query Parameter",
+            (Self::InvalidQueryParameter { .. }, Language::Swedish) => "Ogiltig frågeparameter",
             (Self::InsufficientAuthorityInGroup { .. }, Language::English) => {
                 "Insufficient Authority in Group"
             }
@@ -204,12 +408,16 @@ impl InnerAppErrorDto {
             (Self::NoSuchSystem { .. }, Language::Swedish) => "Okänt system",
             (Self::DuplicateSystemId { .. }, Language::English) => "Duplicate System ID",
             (Self::DuplicateSystemId { .. }, Language::Swedish) => "Duplicerat system-ID",
+            (Self::InvalidSystemManifest { .. }, Language::English) => "Invalid System Manifest",
+            (Self::InvalidSystemManifest { .. }, Language::Swedish) => "Ogiltigt systemmanifest",
             (Self::NoSuchApiToken { .. }, Language::English) => "Unknown API Token",
             (Self::NoSuchApiToken { .. }, Language::Swedish) => "Okänt API-token",
             (Self::AmbiguousApiToken { .. }, Language::English) => {
                 "Ambiguous API Token Description"
             }
             (Self::AmbiguousApiToken { .. }, Language::Swedish) => "Tvetydig API-token beskrivning",
+            (Self::UnsignableApiToken { .. }, Language::English) => "API Token Cannot Be Signed",
+            (Self::UnsignableApiToken { .. }, Language::Swedish) => "API-token kan inte signeras",
             (Self::NoSuchPermission { .. }, Language::English) => "Unknown Permission",
             (Self::NoSuchPermission { .. }, Language::Swedish) => "Okänt behörighet",
             (Self::DuplicatePermissionId { .. }, Language::English) => "Duplicate Permission ID",
@@ -228,6 +436,8 @@ impl InnerAppErrorDto {
             (Self::ExtraneousPermissionScope { .. }, Language::Swedish) => {
                 "Vederlagsfri behörighetsgräns"
             }
+            (Self::InvalidPermissionScope { .. }, Language::English) => "Invalid Permission Scope",
+            (Self::InvalidPermissionScope { .. }, Language::Swedish) => "Ogiltig behörighetsgräns",
             (Self::NoSuchTag { .. }, Language::English) => "Unknown Tag",
             (Self::NoSuchTag { .. }, Language::Swedish) => "Okänt tagg",
             (Self::DuplicateTagId { .. }, Language::English) => "Duplicate Tag ID",
@@ -262,6 +472,98 @@ impl InnerAppErrorDto {
             (Self::RedundantMembership { .. }, Language::Swedish) => "Överflödigt medlemskap",
             (Self::NoSuchMembership { .. }, Language::English) => "Unknown Membership",
             (Self::NoSuchMembership { .. }, Language::Swedish) => "Okänt medlemskap",
+            (Self::AppointmentTooLong { .. }, Language::English) => "Appointment Too Long",
+            (Self::AppointmentTooLong { .. }, Language::Swedish) => "För långt förordnande",
+            (Self::NoPendingTransfer, Language::English) => "No Pending Transfer",
+            (Self::NoPendingTransfer, Language::Swedish) => "Ingen väntande överlämning",
+            (Self::NotTransferRecipient, Language::English) => "Not Transfer Recipient",
+            (Self::NotTransferRecipient, Language::Swedish) => "Inte mottagare av överlämning",
+            (Self::NoSuchDeletion { .. }, Language::English) => "Unknown Deletion",
+            (Self::NoSuchDeletion { .. }, Language::Swedish) => "Okänd radering",
+            (Self::UndoConflict, Language::English) => "Undo Conflict",
+            (Self::UndoConflict, Language::Swedish) => "Ångrakonflikt",
+            (Self::NoSuchPendingChange { .. }, Language::English) => "Unknown Pending Change",
+            (Self::NoSuchPendingChange { .. }, Language::Swedish) => "Okänd väntande ändring",
+            (Self::SelfApproval, Language::English) => "Self-Approval",
+            (Self::SelfApproval, Language::Swedish) => "Självgodkännande",
+            (Self::UnapprovableProtectedChange { .. }, Language::English) => "Protected Domain",
+            (Self::UnapprovableProtectedChange { .. }, Language::Swedish) => "Skyddad domän",
+            (Self::InvalidVerificationLink, Language::English) => "Invalid Verification Link",
+            (Self::InvalidVerificationLink, Language::Swedish) => "Ogiltig verifieringslänk",
+            (Self::NoSuchGroupLink { .. }, Language::English) => "Unknown Group Link",
+            (Self::NoSuchGroupLink { .. }, Language::Swedish) => "Okänd grupplänk",
+            (Self::ExternalDomainPermission { .. }, Language::English) => "External Domain",
+            (Self::ExternalDomainPermission { .. }, Language::Swedish) => "Extern domän",
+            (Self::ExternalSubgroup { .. }, Language::English) => "External Subgroup",
+            (Self::ExternalSubgroup { .. }, Language::Swedish) => "Extern undergrupp",
+            (Self::DeletionNotConfirmed { .. }, Language::English) => "Deletion Not Confirmed",
+            (Self::DeletionNotConfirmed { .. }, Language::Swedish) => "Radering ej bekräftad",
+            (Self::SelfConfirmedDeletion, Language::English) => "Self-Confirmed Deletion",
+            (Self::SelfConfirmedDeletion, Language::Swedish) => "Självbekräftad radering",
+            (Self::NoSuchWebhook { .. }, Language::English) => "Unknown Webhook",
+            (Self::NoSuchWebhook { .. }, Language::Swedish) => "Okänd webhook",
+            (Self::NoSuchWebhookDelivery { .. }, Language::English) => "Unknown Webhook Delivery",
+            (Self::NoSuchWebhookDelivery { .. }, Language::Swedish) => "Okänd webhookleverans",
+            (Self::NoSuchMemberListShare { .. }, Language::English) => "Unknown Share Link",
+            (Self::NoSuchMemberListShare { .. }, Language::Swedish) => "Okänd delningslänk",
+            (Self::MissingAppointmentJustification { .. }, Language::English) => {
+                "Missing Justification"
+            }
+            (Self::MissingAppointmentJustification { .. }, Language::Swedish) => {
+                "Motivering saknas"
+            }
+            (Self::MemberCapExceeded { .. }, Language::English) => "Member Cap Exceeded",
+            (Self::MemberCapExceeded { .. }, Language::Swedish) => "Medlemstaket överskrids",
+            (Self::NoSuchOperationalYear { .. }, Language::English) => "Unknown Operational Year",
+            (Self::NoSuchOperationalYear { .. }, Language::Swedish) => "Okänt verksamhetsår",
+            (Self::DuplicateOperationalYearName { .. }, Language::English) => {
+                "Duplicate Operational Year"
+            }
+            (Self::DuplicateOperationalYearName { .. }, Language::Swedish) => {
+                "Dubblerat verksamhetsår"
+            }
+            (Self::OverlappingOperationalYear { .. }, Language::English) => {
+                "Overlapping Operational Years"
+            }
+            (Self::OverlappingOperationalYear { .. }, Language::Swedish) => {
+                "Överlappande verksamhetsår"
+            }
+            (Self::MembershipOutsideOperationalYear { .. }, Language::English) => {
+                "Outside Operational Year"
+            }
+            (Self::MembershipOutsideOperationalYear { .. }, Language::Swedish) => {
+                "Utanför verksamhetsåret"
+            }
+            (Self::LastHivePermissionHolder { .. }, Language::English) => "Self-Preservation Fault",
+            (Self::LastHivePermissionHolder { .. }, Language::Swedish) => "Självbevarelsedriftsfel",
+            (Self::NoSuchIntegrationTask { .. }, Language::English) => "Unknown Task",
+            (Self::NoSuchIntegrationTask { .. }, Language::Swedish) => "Okänd uppgift",
+            (Self::InvalidTaskSchedule { .. }, Language::English) => "Invalid Schedule",
+            (Self::InvalidTaskSchedule { .. }, Language::Swedish) => "Ogiltigt schema",
+            (Self::NoSuchTaskRun { .. }, Language::English) => "Unknown Task Run",
+            (Self::NoSuchTaskRun { .. }, Language::Swedish) => "Okänd uppgiftskörning",
+            (Self::InvalidTagDerivation { .. }, Language::English) => "Invalid Tag Derivation",
+            (Self::InvalidTagDerivation { .. }, Language::Swedish) => "Ogiltig tagghärledning",
+            (Self::DuplicateTagDerivation { .. }, Language::English) => "Duplicate Tag Derivation",
+            (Self::DuplicateTagDerivation { .. }, Language::Swedish) => "Duplicerad tagghärledning",
+            (Self::NoSuchTagDerivation { .. }, Language::English) => "Unknown Tag Derivation",
+            (Self::NoSuchTagDerivation { .. }, Language::Swedish) => "Okänd tagghärledning",
+            (Self::InvalidGroupStateTransition { .. }, Language::English) => "Invalid State Change",
+            (Self::InvalidGroupStateTransition { .. }, Language::Swedish) => {
+                "Ogiltigt tillståndsbyte"
+            }
+            (Self::NoSuchPendingMembership { .. }, Language::English) => {
+                "Unknown Pending Membership"
+            }
+            (Self::NoSuchPendingMembership { .. }, Language::Swedish) => {
+                "Okänt väntande medlemskap"
+            }
+            (Self::NoSuchGroupInvitation { .. }, Language::English) => "Unknown Invitation Link",
+            (Self::NoSuchGroupInvitation { .. }, Language::Swedish) => "Okänd inbjudningslänk",
+            (Self::GroupInvitationUnusable, Language::English) => "Invalid Invitation Link",
+            (Self::GroupInvitationUnusable, Language::Swedish) => "Ogiltig inbjudningslänk",
+            (Self::NoSuchGroupComment { .. }, Language::English) => "Unknown Comment",
+            (Self::NoSuchGroupComment { .. }, Language::Swedish) => "Okänd kommentar",
         }
     }
 
@@ -301,6 +603,38 @@ impl InnerAppErrorDto {
             (Self::NotAllowed, Language::Swedish) => {
                 "Du har inte de nödvändiga behörigheterna för att utföra denna åtgärd.".to_owned()
             }
+            (Self::AdministratorsOnly, Language::English) => {
+                "Only administrators (i.e., members of the root group) can perform this action."
+                    .to_owned()
+            }
+            (Self::AdministratorsOnly, Language::Swedish) => {
+                "Endast administratörer (d.v.s. medlemmar i rotgruppen) kan utföra denna åtgärd."
+                    .to_owned()
+            }
+            (Self::MaintenanceMode, Language::English) => {
+                "Hive is currently undergoing maintenance, so no changes can be made. Everything \
+                 can still be viewed as usual; please try again later."
+                    .to_owned()
+            }
+            (Self::MaintenanceMode, Language::Swedish) => {
+                "Hive genomgår för tillfället underhåll, så inga ändringar kan göras. Allt kan \
+                 fortfarande visas som vanligt; försök igen senare."
+                    .to_owned()
+            }
+            (Self::InvalidLegacyDump { reason }, Language::English) => {
+                format!("The legacy dump could not be imported: {reason}. No changes were made.")
+            }
+            (Self::InvalidLegacyDump { reason }, Language::Swedish) => {
+                format!(
+                    "Exporten från det gamla systemet kunde inte importeras: {reason}. Inga                      ändringar har gjorts."
+                )
+            }
+            (Self::InvalidQueryParameter { name }, Language::English) => {
+                format!("The value of query parameter \"{name}\" is invalid.")
+            }
+            (Self::InvalidQueryParameter { name }, Language::Swedish) => {
+                format!("Värdet av frågeparametern \"{name}\" är ogiltigt.")
+            }
             (Self::InsufficientAuthorityInGroup { min }, Language::English) => format!(
                 "You lack the necessary authority in the relevant group to perform this action. \
                  {} is required for access to be granted.",
@@ -343,6 +677,14 @@ impl InnerAppErrorDto {
             (Self::DuplicateSystemId { id }, Language::Swedish) => {
                 format!("ID \"{id}\" används redan av ett annat system.")
             }
+            (Self::InvalidSystemManifest { reason }, Language::English) => {
+                format!("The system manifest could not be applied: {reason}. No changes were made.")
+            }
+            (Self::InvalidSystemManifest { reason }, Language::Swedish) => {
+                format!(
+                    "Systemmanifestet kunde inte tillämpas: {reason}. Inga ändringar har gjorts."
+                )
+            }
             (Self::NoSuchApiToken { id }, Language::English) => {
                 format!("Could not find any API token with ID \"{id}\".")
             }
@@ -359,6 +701,14 @@ impl InnerAppErrorDto {
                 "Beskrivning \"{description}\" är tvetydig eftersom den redan används av ett \
                  annat API-token för samma system."
             ),
+            (Self::UnsignableApiToken { id }, Language::English) => format!(
+                "API token \"{id}\" was created before signed requests were made more secure, \
+                 and cannot require them. Create a new API token instead."
+            ),
+            (Self::UnsignableApiToken { id }, Language::Swedish) => format!(
+                "API-token \"{id}\" skapades innan signerade anrop gjordes säkrare, och kan inte \
+                 kräva dem. Skapa ett nytt API-token istället."
+            ),
             (Self::NoSuchPermission { system_id, perm_id }, Language::English) => {
                 format!("Could not find any permission with key \"${system_id}:{perm_id}\".")
             }
@@ -431,6 +781,32 @@ impl InnerAppErrorDto {
                      till en konkret gräns vid tilldelning."
                 )
             }
+            (
+                Self::InvalidPermissionScope {
+                    system_id,
+                    perm_id,
+                    scope,
+                },
+                Language::English,
+            ) => {
+                format!(
+                    "Scope \"{scope}\" is not valid for permission with key \
+                     \"${system_id}:{perm_id}\", since it would never match anything."
+                )
+            }
+            (
+                Self::InvalidPermissionScope {
+                    system_id,
+                    perm_id,
+                    scope,
+                },
+                Language::Swedish,
+            ) => {
+                format!(
+                    "Gränsen \"{scope}\" är inte giltig för behörighet med nyckel \
+                     \"${system_id}:{perm_id}\", eftersom den aldrig skulle matcha något."
+                )
+            }
             (Self::NoSuchTag { system_id, tag_id }, Language::English) => {
                 format!("Could not find any tag with key \"#{system_id}:{tag_id}\".")
             }
@@ -634,13 +1010,366 @@ impl InnerAppErrorDto {
             (Self::NoSuchMembership { id }, Language::Swedish) => {
                 format!("Kunde inte hitta något gruppmedlemskap med nyckel \"{id}\".")
             }
+            (Self::AppointmentTooLong { username }, Language::English) => {
+                format!(
+                    "The membership of user \"{username}\" cannot be extended this far into the \
+                     future without special permission."
+                )
+            }
+            (Self::AppointmentTooLong { username }, Language::Swedish) => {
+                format!(
+                    "Medlemskapet för användaren \"{username}\" kan inte förlängas så långt in i \
+                     framtiden utan särskild behörighet."
+                )
+            }
+            (Self::NoPendingTransfer, Language::English) => {
+                "This group has no pending transfer of responsibility, so there is nothing to \
+                 respond to. It might have already been accepted, declined or cancelled."
+                    .to_owned()
+            }
+            (Self::NoPendingTransfer, Language::Swedish) => {
+                "Denna grupp har ingen väntande överlämning av ansvar, så det finns inget att \
+                 svara på. Den kan redan ha accepterats, avböjts eller avbrutits."
+                    .to_owned()
+            }
+            (Self::NotTransferRecipient, Language::English) => {
+                "Only the receiving party (the user in question, or a manager of the receiving \
+                 group) can accept or decline this transfer of responsibility."
+                    .to_owned()
+            }
+            (Self::NotTransferRecipient, Language::Swedish) => {
+                "Endast den mottagande parten (användaren i fråga, eller en ansvarig för den \
+                 mottagande gruppen) kan acceptera eller avböja denna överlämning av ansvar."
+                    .to_owned()
+            }
+            (Self::NoSuchDeletion { id }, Language::English) => format!(
+                "Could not find any deletion with ID \"{id}\" that you can undo. Note that \
+                 deletions can only be undone by whoever performed them, and only for a limited \
+                 time."
+            ),
+            (Self::NoSuchDeletion { id }, Language::Swedish) => format!(
+                "Kunde inte hitta någon radering med ID \"{id}\" som du kan ångra. Observera \
+                 att raderingar endast kan ångras av den som utförde dem, och endast under en \
+                 begränsad tid."
+            ),
+            (Self::UndoConflict, Language::English) => {
+                "This deletion cannot be undone because something else has since been created \
+                 in its place, or something it depended on has since been deleted."
+                    .to_owned()
+            }
+            (Self::UndoConflict, Language::Swedish) => {
+                "Denna radering kan inte ångras eftersom något annat har skapats i dess ställe \
+                 sedan dess, eller något den berodde på har raderats sedan dess."
+                    .to_owned()
+            }
+            (Self::NoSuchPendingChange { id }, Language::English) => format!(
+                "Could not find any pending change with ID \"{id}\". It might have already been \
+                 approved, rejected or withdrawn."
+            ),
+            (Self::NoSuchPendingChange { id }, Language::Swedish) => format!(
+                "Kunde inte hitta någon väntande ändring med ID \"{id}\". Den kan redan ha \
+                 godkänts, avslagits eller dragits tillbaka."
+            ),
+            (Self::SelfApproval, Language::English) => {
+                "Changes in protected domains must be approved by a second authorized person, \
+                 so you cannot approve a change that you proposed yourself."
+                    .to_owned()
+            }
+            (Self::SelfApproval, Language::Swedish) => {
+                "Ändringar i skyddade domäner måste godkännas av en andra behörig person, så du \
+                 kan inte godkänna en ändring som du själv har föreslagit."
+                    .to_owned()
+            }
+            (Self::UnapprovableProtectedChange { domain }, Language::English) => format!(
+                "Changes to groups in domain \"{domain}\" must be approved by a second \
+                 authorized person, but this kind of change cannot be proposed for approval. \
+                 Add the subgroup or assign the permission as usual instead, which proposes it."
+            ),
+            (Self::UnapprovableProtectedChange { domain }, Language::Swedish) => format!(
+                "Ändringar av grupper i domänen \"{domain}\" måste godkännas av en andra behörig \
+                 person, men denna typ av ändring kan inte föreslås för godkännande. Lägg till \
+                 undergruppen eller tilldela behörigheten som vanligt i stället, vilket föreslår \
+                 den."
+            ),
+            (Self::InvalidVerificationLink, Language::English) => {
+                "This verification link is invalid or has expired. You can request a new one \
+                 from your user settings."
+                    .to_owned()
+            }
+            (Self::InvalidVerificationLink, Language::Swedish) => {
+                "Denna verifieringslänk är ogiltig eller har gått ut. Du kan begära en ny från \
+                 dina användarinställningar."
+                    .to_owned()
+            }
+            (Self::NoSuchGroupLink { id }, Language::English) => format!(
+                "Could not find any link with ID \"{id}\" in this group. It might have already \
+                 been removed."
+            ),
+            (Self::NoSuchGroupLink { id }, Language::Swedish) => format!(
+                "Kunde inte hitta någon länk med ID \"{id}\" i denna grupp. Den kan redan ha \
+                 tagits bort."
+            ),
+            (Self::ExternalDomainPermission { domain }, Language::English) => format!(
+                "Groups in domain \"{domain}\" are for external users (such as alumni or \
+                 collaborators), so they cannot be assigned any permissions."
+            ),
+            (Self::ExternalDomainPermission { domain }, Language::Swedish) => format!(
+                "Grupper i domänen \"{domain}\" är till för externa användare (såsom alumner \
+                 eller samarbetspartner), så de kan inte tilldelas några behörigheter."
+            ),
+            (
+                Self::ExternalSubgroup {
+                    child_id,
+                    child_domain,
+                },
+                Language::English,
+            ) => format!(
+                "The group with key \"{child_id}@{child_domain}\" is for external users (such \
+                 as alumni or collaborators), so it can only be a subgroup of other groups in \
+                 external domains."
+            ),
+            (
+                Self::ExternalSubgroup {
+                    child_id,
+                    child_domain,
+                },
+                Language::Swedish,
+            ) => format!(
+                "Gruppen med nyckel \"{child_id}@{child_domain}\" är till för externa \
+                 användare (såsom alumner eller samarbetspartner), så den kan bara vara en \
+                 undergrupp till andra grupper i externa domäner."
+            ),
+            (Self::DeletionNotConfirmed { id }, Language::English) => format!(
+                "System \"{id}\" is in active use, so you must type its ID to confirm that you \
+                 really want to delete it."
+            ),
+            (Self::DeletionNotConfirmed { id }, Language::Swedish) => format!(
+                "Systemet \"{id}\" används aktivt, så du måste skriva in dess ID för att \
+                 bekräfta att du verkligen vill radera det."
+            ),
+            (Self::SelfConfirmedDeletion, Language::English) => {
+                "Systems in active use can only be deleted once a second authorized person \
+                 confirms it, so you cannot confirm a deletion that you requested yourself."
+                    .to_owned()
+            }
+            (Self::SelfConfirmedDeletion, Language::Swedish) => {
+                "System som används aktivt kan bara raderas när en andra behörig person \
+                 bekräftar det, så du kan inte bekräfta en radering som du själv har begärt."
+                    .to_owned()
+            }
+            (Self::NoSuchWebhook { id }, Language::English) => format!(
+                "Could not find any webhook with ID \"{id}\" in this system. It might have \
+                 already been removed."
+            ),
+            (Self::NoSuchWebhook { id }, Language::Swedish) => format!(
+                "Kunde inte hitta någon webhook med ID \"{id}\" i detta system. Den kan redan \
+                 ha tagits bort."
+            ),
+            (Self::NoSuchWebhookDelivery { id }, Language::English) => format!(
+                "Could not find any delivery with ID \"{id}\" for this webhook that has already \
+                 been attempted. Deliveries that are still pending cannot be replayed."
+            ),
+            (Self::NoSuchWebhookDelivery { id }, Language::Swedish) => format!(
+                "Kunde inte hitta någon redan försökt leverans med ID \"{id}\" för denna \
+                 webhook. Leveranser som fortfarande väntar kan inte skickas om."
+            ),
+            (Self::NoSuchMemberListShare { id }, Language::English) => format!(
+                "Could not find any shared member list with ID \"{id}\" in this group. It might \
+                 have already been revoked."
+            ),
+            (Self::NoSuchMemberListShare { id }, Language::Swedish) => format!(
+                "Kunde inte hitta någon delad medlemslista med ID \"{id}\" i denna grupp. Den \
+                 kan redan ha återkallats."
+            ),
+            (Self::MissingAppointmentJustification { username }, Language::English) => {
+                format!(
+                    "The membership of user \"{username}\" extends beyond the default \
+                     appointment bounds, so it needs a justification first."
+                )
+            }
+            (Self::MissingAppointmentJustification { username }, Language::Swedish) => {
+                format!(
+                    "Medlemskapet för användaren \"{username}\" sträcker sig längre än \
+                     standardgränsen för förordnanden, så det behöver först en motivering."
+                )
+            }
+            (Self::MemberCapExceeded { cap }, Language::English) => format!(
+                "This group may have at most {cap} members at a time. Remove someone \
+                 or raise the cap before adding more members."
+            ),
+            (Self::MemberCapExceeded { cap }, Language::Swedish) => format!(
+                "Den här gruppen får ha högst {cap} medlemmar åt gången. Ta bort någon \
+                 eller höj taket innan fler medlemmar läggs till."
+            ),
+            (Self::NoSuchOperationalYear { id }, Language::English) => format!(
+                "Could not find any operational year with ID \"{id}\". It might have already \
+                 been deleted."
+            ),
+            (Self::NoSuchOperationalYear { id }, Language::Swedish) => format!(
+                "Kunde inte hitta något verksamhetsår med ID \"{id}\". Det kan redan ha \
+                 tagits bort."
+            ),
+            (Self::DuplicateOperationalYearName { name }, Language::English) => {
+                format!("An operational year named \"{name}\" already exists.")
+            }
+            (Self::DuplicateOperationalYearName { name }, Language::Swedish) => {
+                format!("Ett verksamhetsår med namnet \"{name}\" finns redan.")
+            }
+            (Self::OverlappingOperationalYear { name }, Language::English) => format!(
+                "These dates overlap with operational year \"{name}\". Operational years \
+                 cannot overlap, since any given day must belong to at most one of them."
+            ),
+            (Self::OverlappingOperationalYear { name }, Language::Swedish) => format!(
+                "Dessa datum överlappar med verksamhetsåret \"{name}\". Verksamhetsår kan \
+                 inte överlappa, eftersom varje dag får tillhöra högst ett av dem."
+            ),
+            (Self::MembershipOutsideOperationalYear { name }, Language::English) => format!(
+                "The membership's dates are not within operational year \"{name}\". Either \
+                 adjust the dates or add the membership without referencing the year."
+            ),
+            (Self::MembershipOutsideOperationalYear { name }, Language::Swedish) => format!(
+                "Medlemskapets datum ligger inte inom verksamhetsåret \"{name}\". Justera \
+                 antingen datumen eller lägg till medlemskapet utan att hänvisa till året."
+            ),
+            (Self::LastHivePermissionHolder { permission }, Language::English) => format!(
+                "Your action was automatically disallowed because it would leave nobody with \
+                 permission \"{permission}\", which could then never be granted again. Make \
+                 sure someone else holds it first."
+            ),
+            (Self::LastHivePermissionHolder { permission }, Language::Swedish) => format!(
+                "Din åtgärd avvisades automatiskt eftersom ingen skulle ha kvar behörigheten \
+                 \"{permission}\", som då aldrig skulle kunna tilldelas igen. Se först till \
+                 att någon annan har den."
+            ),
+            (Self::NoSuchIntegrationTask { integration, task }, Language::English) => {
+                format!("Integration \"{integration}\" has no task \"{task}\".")
+            }
+            (Self::NoSuchIntegrationTask { integration, task }, Language::Swedish) => {
+                format!("Integrationen \"{integration}\" har ingen uppgift \"{task}\".")
+            }
+            (Self::InvalidTaskSchedule { schedule }, Language::English) => format!(
+                "\"{schedule}\" is not a valid schedule. Use a cron expression with 6 fields \
+                 (starting with seconds), like \"0 0 * * * *\" for every hour."
+            ),
+            (Self::InvalidTaskSchedule { schedule }, Language::Swedish) => format!(
+                "\"{schedule}\" är inte ett giltigt schema. Använd ett cron-uttryck med 6 fält \
+                 (som börjar med sekunder), t.ex. \"0 0 * * * *\" för varje timme."
+            ),
+            (Self::NoSuchTaskRun { id }, Language::English) => {
+                format!("Could not find any task run with ID \"{id}\" for this integration.")
+            }
+            (Self::NoSuchTaskRun { id }, Language::Swedish) => format!(
+                "Kunde inte hitta någon uppgiftskörning med ID \"{id}\" för denna \
+                 integration."
+            ),
+            (Self::InvalidTagDerivation { system_id, tag_id }, Language::English) => format!(
+                "Tag \"#{system_id}:{tag_id}\" cannot be derived from a permission. Only tags \
+                 that support groups and have no content can be."
+            ),
+            (Self::InvalidTagDerivation { system_id, tag_id }, Language::Swedish) => format!(
+                "Taggen \"#{system_id}:{tag_id}\" kan inte härledas från en behörighet. Endast \
+                 taggar som stöder grupper och saknar innehåll kan det."
+            ),
+            (Self::DuplicateTagDerivation { permission }, Language::English) => {
+                format!("This tag is already derived from permission \"{permission}\".")
+            }
+            (Self::DuplicateTagDerivation { permission }, Language::Swedish) => {
+                format!("Denna tagg härleds redan från behörigheten \"{permission}\".")
+            }
+            (Self::NoSuchTagDerivation { id }, Language::English) => {
+                format!("Could not find any derivation with ID \"{id}\" for this tag.")
+            }
+            (Self::NoSuchTagDerivation { id }, Language::Swedish) => {
+                format!("Kunde inte hitta någon härledning med ID \"{id}\" för denna tagg.")
+            }
+            (Self::InvalidGroupStateTransition { from, to }, Language::English) => {
+                format!("A group cannot go directly from being {from} to being {to}.")
+            }
+            (Self::InvalidGroupStateTransition { from, to }, Language::Swedish) => format!(
+                "En grupp kan inte gå direkt från tillståndet {from} till tillståndet {to}."
+            ),
+            (Self::NoSuchPendingMembership { id }, Language::English) => {
+                format!("Could not find any membership with ID \"{id}\" awaiting acceptance.")
+            }
+            (Self::NoSuchPendingMembership { id }, Language::Swedish) => {
+                format!(
+                    "Kunde inte hitta något medlemskap med ID \"{id}\" som väntar på godkännande."
+                )
+            }
+            (Self::NoSuchGroupInvitation { id }, Language::English) => {
+                format!("Could not find any invitation link with ID \"{id}\" for this group.")
+            }
+            (Self::NoSuchGroupInvitation { id }, Language::Swedish) => {
+                format!("Kunde inte hitta någon inbjudningslänk med ID \"{id}\" för denna grupp.")
+            }
+            (Self::GroupInvitationUnusable, Language::English) => {
+                "This invitation link can no longer be used to join the group. It might have \
+                 expired, been used up or been revoked by the group's managers."
+                    .to_owned()
+            }
+            (Self::GroupInvitationUnusable, Language::Swedish) => {
+                "Denna inbjudningslänk kan inte längre användas för att gå med i gruppen. Den kan \
+                 ha gått ut, förbrukats eller återkallats av gruppens ansvariga."
+                    .to_owned()
+            }
+            (Self::NoSuchGroupComment { id }, Language::English) => format!(
+                "Could not find any comment with ID \"{id}\" by you in this group. It might have \
+                 already been deleted, and only its author can delete a comment."
+            ),
+            (Self::NoSuchGroupComment { id }, Language::Swedish) => format!(
+                "Kunde inte hitta någon kommentar med ID \"{id}\" av dig i denna grupp. Den kan \
+                 redan ha tagits bort, och endast dess författare kan ta bort en kommentar."
+            ),
+        }
+    }
+}
+
+// bumped whenever the envelope itself changes shape (not when keys are added);
+// version 1 had neither `version`, `code` nor `retry_after`
+pub const ERROR_ENVELOPE_VERSION: u32 = 2;
+
+// coarse, stable classification of errors, for clients to branch on without
+// having to know every specific key (which are more likely to be added to)
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidRequest,
+    Unauthenticated,
+    Forbidden,
+    NotFound,
+    Conflict,
+    Gone,
+    Unavailable,
+    #[default]
+    Internal,
+}
+
+impl From<Status> for ErrorCode {
+    fn from(status: Status) -> Self {
+        match status.code {
+            401 => Self::Unauthenticated,
+            403 | 451 => Self::Forbidden,
+            404 => Self::NotFound,
+            409 => Self::Conflict,
+            410 => Self::Gone,
+            503 => Self::Unavailable,
+            400..500 => Self::InvalidRequest,
+            _ => Self::Internal,
         }
     }
 }
 
+// (missing fields are defaulted when deserializing, since that is also used to
+// render error pages from responses generated elsewhere, e.g. by catchers)
 #[derive(Serialize, Deserialize)]
 pub struct AppErrorDto {
     error: bool,
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    code: ErrorCode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retry_after: Option<u32>, // seconds
     info: InnerAppErrorDto,
 }
 
@@ -648,6 +1377,9 @@ impl From<AppError> for AppErrorDto {
     fn from(err: AppError) -> Self {
         Self {
             error: true,
+            version: ERROR_ENVELOPE_VERSION,
+            code: err.status().into(),
+            retry_after: err.retry_after(),
             info: err.into(),
         }
     }
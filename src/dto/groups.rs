@@ -2,8 +2,13 @@ use rocket::{
     FromForm,
     form::{self, FromFormField},
 };
+use uuid::Uuid;
 
-use super::{TrimmedStr, datetime::BrowserDateDto};
+use super::{
+    OptionalStr, TrimmedStr,
+    datetime::{BrowserDateDto, BrowserTimeDto},
+};
+use crate::models::{GroupState, MembershipDuration};
 
 #[derive(FromForm)]
 pub struct CreateGroupDto<'v> {
@@ -19,6 +24,8 @@ pub struct CreateGroupDto<'v> {
     pub description_sv: TrimmedStr<'v>,
     #[field(validate = len(10..))]
     pub description_en: TrimmedStr<'v>,
+    pub parents: Vec<GroupRefDto<'v>>, // to be linked as (non-manager) subgroup of
+    pub proposed: bool,                // otherwise, it's active right away
 }
 
 #[derive(FromForm)]
@@ -31,6 +38,28 @@ pub struct EditGroupDto<'v> {
     pub description_sv: TrimmedStr<'v>,
     #[field(validate = len(10..))]
     pub description_en: TrimmedStr<'v>,
+    #[field(validate = with(|cap| cap.is_none_or(|cap| cap > 0), "invalid member cap"))]
+    pub member_cap: Option<i32>, // empty => no cap
+    pub member_cap_enforced: bool,
+    #[field(validate = with(
+        |d| d.is_none_or(|d| d.parse::<MembershipDuration>().is_ok()),
+        "invalid membership duration"
+    ))]
+    pub default_membership_duration: OptionalStr<'v>,
+    pub requires_acceptance: bool,
+}
+
+#[derive(FromForm)]
+pub struct SetGroupStateDto {
+    pub state: GroupState,
+}
+
+#[derive(FromForm)]
+pub struct RenameGroupDto<'v> {
+    #[field(validate = super::valid_slug())]
+    pub id: TrimmedStr<'v>,
+    #[field(validate = super::valid_domain())]
+    pub domain: TrimmedStr<'v>,
 }
 
 #[derive(FromForm)]
@@ -68,12 +97,113 @@ pub struct AddMemberDto<'v> {
     pub from: BrowserDateDto,
     #[field(validate = with(|until| until >= &self.from, "invalid until before from"))]
     pub until: BrowserDateDto,
+    // only kept for domains with time precision (see `clock`)
+    pub from_time: Option<BrowserTimeDto>,
+    #[field(validate = with(
+        |t| self.from < self.until || self.from_time.zip(*t).is_none_or(|(from, until)| from < until),
+        "invalid until time before from time"
+    ))]
+    pub until_time: Option<BrowserTimeDto>,
     pub manager: bool,
+    #[field(validate = with(|note| note.is_none_or(|note| note.len() <= 500), "note too long"))]
+    pub note: OptionalStr<'v>,
+    // required beyond the default appointment bounds (see members service)
+    #[field(validate = with(|j| j.is_none_or(|j| j.len() <= 500), "justification too long"))]
+    pub justification: OptionalStr<'v>,
+    // the dates must then lie within it (see `services::operational_years`)
+    pub operational_year: Option<Uuid>,
 }
 
 #[derive(FromForm)]
-pub struct EditMemberDto {
+pub struct AddGroupLinkDto<'v> {
+    #[field(validate = len(1..=64))]
+    pub name: TrimmedStr<'v>,
+    #[field(validate = super::valid_http_url())]
+    pub url: TrimmedStr<'v>,
+}
+
+#[derive(FromForm)]
+pub struct ShareMemberListDto {
+    #[field(validate = range(1..=30))] // meant for one-off purposes, like an event
+    pub days: u32,
+}
+
+#[derive(FromForm)]
+pub struct AddGroupCommentDto<'v> {
+    #[field(validate = len(1..=2000))]
+    pub body: TrimmedStr<'v>,
+    pub reply_to: Option<Uuid>, // top-level comment in the same group
+}
+
+#[derive(FromForm)]
+pub struct CreateInvitationDto {
     pub from: BrowserDateDto,
     #[field(validate = with(|until| until >= &self.from, "invalid until before from"))]
     pub until: BrowserDateDto,
+    #[field(validate = with(|n| n.is_none_or(|n| n > 0), "invalid max uses"))]
+    pub max_uses: Option<i32>, // empty => unlimited
+    #[field(validate = with(|d| d.is_none_or(|d| (1..=30).contains(&d)), "invalid days"))]
+    pub days: Option<u32>, // until expiry; empty => until the membership ends
+}
+
+#[derive(FromForm)]
+pub struct ProposeTransferDto<'v> {
+    pub recipient: TransferRecipientDto<'v>,
+    #[field(validate = with(
+        |until| until.is_some()
+            || matches!(self.recipient, Some(TransferRecipientDto::Group(..))),
+        "missing until for user recipient"
+    ))]
+    pub until: Option<BrowserDateDto>,
+}
+
+// either a username or a group key (id@domain)
+pub enum TransferRecipientDto<'v> {
+    User(&'v str),
+    Group(GroupRefDto<'v>),
+}
+
+impl<'v> FromFormField<'v> for TransferRecipientDto<'v> {
+    fn from_value(field: form::ValueField<'v>) -> form::Result<'v, Self> {
+        if field.value.contains('@') {
+            return GroupRefDto::from_value(field).map(Self::Group);
+        }
+
+        let username = field.value.trim();
+        super::valid_username(username)?;
+
+        Ok(Self::User(username))
+    }
+}
+
+#[derive(FromForm)]
+pub struct AddExclusionDto<'v> {
+    #[field(validate = super::valid_username())]
+    pub username: TrimmedStr<'v>,
+}
+
+#[derive(FromForm)]
+pub struct EditMemberDto<'v> {
+    pub from: BrowserDateDto,
+    #[field(validate = with(|until| until >= &self.from, "invalid until before from"))]
+    pub until: BrowserDateDto,
+    // only kept for domains with time precision (see `clock`)
+    pub from_time: Option<BrowserTimeDto>,
+    #[field(validate = with(
+        |t| self.from < self.until || self.from_time.zip(*t).is_none_or(|(from, until)| from < until),
+        "invalid until time before from time"
+    ))]
+    pub until_time: Option<BrowserTimeDto>,
+    #[field(validate = with(|note| note.is_none_or(|note| note.len() <= 500), "note too long"))]
+    pub note: OptionalStr<'v>,
+    // required beyond the default appointment bounds (see members service)
+    #[field(validate = with(|j| j.is_none_or(|j| j.len() <= 500), "justification too long"))]
+    pub justification: OptionalStr<'v>,
+}
+
+#[derive(FromForm)]
+pub struct BulkMembersDto {
+    #[field(validate = len(1..))]
+    pub selected: Vec<Uuid>,
+    pub at: Option<BrowserDateDto>, // to re-render the same view afterwards
 }
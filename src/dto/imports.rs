@@ -0,0 +1,200 @@
+use chrono::NaiveDate;
+use rocket::{FromForm, FromFormField, fs::TempFile};
+use serde::Deserialize;
+
+#[derive(FromForm)]
+pub struct LegacyImportDto<'v> {
+    pub dump: TempFile<'v>,
+    pub format: LegacyDumpFormat,
+    pub dry_run: bool,
+}
+
+#[derive(FromFormField, Clone, Copy)]
+pub enum LegacyDumpFormat {
+    Json,
+    Csv,
+}
+
+// structure exported from the legacy systems (pls for groups and permissions,
+// dfunkt for mandates); groups are referred to by `id@domain` everywhere
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LegacyDumpDto {
+    #[serde(default)]
+    pub groups: Vec<LegacyGroupDto>,
+    #[serde(default)]
+    pub subgroups: Vec<LegacySubgroupDto>,
+    #[serde(default)]
+    pub memberships: Vec<LegacyMembershipDto>,
+    #[serde(default)]
+    pub permissions: Vec<LegacyPermissionDto>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LegacyGroupDto {
+    pub id: String,
+    pub domain: String,
+    pub name_sv: String,
+    pub name_en: String,
+    pub description_sv: String,
+    pub description_en: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LegacySubgroupDto {
+    pub parent: String,
+    pub child: String,
+    #[serde(default)]
+    pub manager: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LegacyMembershipDto {
+    pub group: String,
+    pub username: String,
+    pub from: NaiveDate,
+    pub until: NaiveDate,
+    #[serde(default)]
+    pub manager: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LegacyPermissionDto {
+    pub group: String,
+    pub system_id: String,
+    pub perm_id: String,
+    pub scope: Option<String>,
+}
+
+impl LegacyDumpDto {
+    pub fn parse(content: &str, format: LegacyDumpFormat) -> Result<Self, String> {
+        match format {
+            LegacyDumpFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+            LegacyDumpFormat::Csv => Self::from_csv(content),
+        }
+    }
+
+    // one record per line, with the kind of record in the first column:
+    //   group,<id>,<domain>,<name_sv>,<name_en>,<description_sv>,<description_en>
+    //   subgroup,<parent>,<child>[,manager]
+    //   membership,<group>,<username>,<from>,<until>[,manager]
+    //   permission,<group>,<system_id>,<perm_id>[,<scope>]
+    // (empty lines and lines starting with `#` are ignored)
+    fn from_csv(content: &str) -> Result<Self, String> {
+        let mut dump = Self::default();
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = split_csv_line(line).map_err(|e| format!("line {}: {e}", i + 1))?;
+            let fields: Vec<_> = fields.iter().map(String::as_str).collect();
+
+            let manager = |flag: Option<&&str>| match flag {
+                None | Some(&"") => Ok(false),
+                Some(&"manager") => Ok(true),
+                Some(other) => Err(format!("line {}: unknown flag `{other}`", i + 1)),
+            };
+            let date = |s: &str| {
+                NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                    .map_err(|e| format!("line {}: invalid date `{s}`: {e}", i + 1))
+            };
+
+            match fields.as_slice() {
+                [
+                    "group",
+                    id,
+                    domain,
+                    name_sv,
+                    name_en,
+                    description_sv,
+                    description_en,
+                ] => {
+                    dump.groups.push(LegacyGroupDto {
+                        id: id.to_string(),
+                        domain: domain.to_string(),
+                        name_sv: name_sv.to_string(),
+                        name_en: name_en.to_string(),
+                        description_sv: description_sv.to_string(),
+                        description_en: description_en.to_string(),
+                    });
+                }
+                ["subgroup", parent, child, rest @ ..] if rest.len() <= 1 => {
+                    dump.subgroups.push(LegacySubgroupDto {
+                        parent: parent.to_string(),
+                        child: child.to_string(),
+                        manager: manager(rest.first())?,
+                    });
+                }
+                ["membership", group, username, from, until, rest @ ..] if rest.len() <= 1 => {
+                    dump.memberships.push(LegacyMembershipDto {
+                        group: group.to_string(),
+                        username: username.to_string(),
+                        from: date(from)?,
+                        until: date(until)?,
+                        manager: manager(rest.first())?,
+                    });
+                }
+                ["permission", group, system_id, perm_id, rest @ ..] if rest.len() <= 1 => {
+                    dump.permissions.push(LegacyPermissionDto {
+                        group: group.to_string(),
+                        system_id: system_id.to_string(),
+                        perm_id: perm_id.to_string(),
+                        scope: rest
+                            .first()
+                            .filter(|s| !s.is_empty())
+                            .map(|s| s.to_string()),
+                    });
+                }
+                [kind, ..] => {
+                    return Err(format!(
+                        "line {}: unknown record kind `{kind}` or wrong number of columns",
+                        i + 1
+                    ));
+                }
+                [] => unreachable!("split always yields at least one field"),
+            }
+        }
+
+        Ok(dump)
+    }
+}
+
+// minimal RFC 4180: fields may be quoted (with `""` as an escaped quote) so
+// that they can contain commas, but can't span multiple lines
+fn split_csv_line(line: &str) -> Result<Vec<String>, &'static str> {
+    let mut fields = vec![];
+    let mut current = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', false) if current.is_empty() => quoted = true,
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                current.push('"');
+            }
+            ('"', true) => match chars.peek() {
+                None | Some(',') => quoted = false,
+                _ => return Err("unexpected character after closing quote"),
+            },
+            (',', false) => fields.push(std::mem::take(&mut current).trim().to_owned()),
+            (c, _) => current.push(c),
+        }
+    }
+
+    if quoted {
+        return Err("unterminated quoted field");
+    }
+
+    fields.push(current.trim().to_owned());
+
+    Ok(fields)
+}
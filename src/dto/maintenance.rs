@@ -0,0 +1,13 @@
+use rocket::FromForm;
+use uuid::Uuid;
+
+use super::{groups::GroupRefDto, permissions::PermissionKey, tags::TagKey};
+
+// whatever was checked on the orphans page; each list may be empty
+#[derive(FromForm)]
+pub struct CleanupOrphansDto<'v> {
+    pub permissions: Vec<PermissionKey<'v>>,
+    pub tags: Vec<TagKey<'v>>,
+    pub groups: Vec<GroupRefDto<'v>>,
+    pub api_tokens: Vec<Uuid>,
+}
@@ -0,0 +1,13 @@
+use rocket::FromForm;
+
+use super::{TrimmedStr, datetime::BrowserDateDto};
+
+// for both creating and editing (everything can be changed)
+#[derive(FromForm)]
+pub struct OperationalYearDto<'v> {
+    #[field(validate = len(1..=32))]
+    pub name: TrimmedStr<'v>,
+    pub start: BrowserDateDto,
+    #[field(validate = with(|end| end > &self.start, "invalid end before start"))]
+    pub end: BrowserDateDto,
+}
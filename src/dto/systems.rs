@@ -1,6 +1,10 @@
+use std::collections::HashSet;
+
 use rocket::FromForm;
+use serde::Deserialize;
 
 use super::TrimmedStr;
+use crate::models::WebhookEvent;
 
 #[derive(FromForm)]
 pub struct CreateSystemDto<'v> {
@@ -15,3 +19,86 @@ pub struct EditSystemDto<'v> {
     #[field(validate = len(3..))]
     pub description: TrimmedStr<'v>,
 }
+
+#[derive(FromForm)]
+pub struct CreateWebhookDto<'v> {
+    #[field(validate = super::valid_http_url())]
+    pub url: TrimmedStr<'v>,
+    #[field(validate = len(1..))]
+    pub events: Vec<WebhookEvent>,
+}
+
+// empty goes back to the schedule in the task's manifest
+#[derive(FromForm)]
+pub struct TaskScheduleDto<'v> {
+    pub schedule: TrimmedStr<'v>,
+}
+
+// omitted sections are left untouched, while present ones are considered
+// exhaustive (i.e., anything not listed is deleted)
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SystemManifestDto {
+    pub permissions: Option<Vec<ManifestPermissionDto>>,
+    pub tags: Option<Vec<ManifestTagDto>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestPermissionDto {
+    pub id: String,
+    pub description: String,
+    #[serde(default)]
+    pub has_scope: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestTagDto {
+    pub id: String,
+    pub description: String,
+    #[serde(default)]
+    pub supports_groups: bool,
+    #[serde(default)]
+    pub supports_users: bool,
+    #[serde(default)]
+    pub has_content: bool,
+}
+
+impl SystemManifestDto {
+    // same rules as when creating via forms
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seen = HashSet::new();
+        for perm in self.permissions.iter().flatten() {
+            validate_entry("permission", &perm.id, &perm.description, &mut seen)?;
+        }
+
+        let mut seen = HashSet::new();
+        for tag in self.tags.iter().flatten() {
+            validate_entry("tag", &tag.id, &tag.description, &mut seen)?;
+
+            if !tag.supports_groups && !tag.supports_users {
+                return Err(format!("tag `{}` must support something", tag.id));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_entry<'a>(
+    kind: &str,
+    id: &'a str,
+    description: &str,
+    seen: &mut HashSet<&'a str>,
+) -> Result<(), String> {
+    if super::valid_slug(id).is_err() {
+        Err(format!("{kind} ID `{id}` is not a valid slug"))
+    } else if description.trim().len() < 3 {
+        Err(format!("{kind} `{id}` has a description that is too short"))
+    } else if !seen.insert(id) {
+        Err(format!("{kind} `{id}` is listed more than once"))
+    } else {
+        Ok(())
+    }
+}
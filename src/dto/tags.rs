@@ -3,7 +3,7 @@ use rocket::{
     form::{self, FromFormField},
 };
 
-use super::{TrimmedStr, groups::GroupRefDto};
+use super::{TrimmedStr, groups::GroupRefDto, permissions::PermissionKey};
 
 #[derive(FromForm)]
 pub struct CreateTagDto<'v> {
@@ -40,18 +40,59 @@ pub struct AssignTagToUserDto<'v> {
     pub content: Option<TrimmedStr<'v>>,
 }
 
+#[derive(FromForm)]
+pub struct EditTagAssignmentDto<'v> {
+    #[field(validate = len(1..))]
+    pub content: TrimmedStr<'v>,
+}
+
 #[derive(FromForm)]
 pub struct BulkTagGroupsDto<'v> {
     pub tag: TagKey<'v>,
+    #[field(validate = super::option_len(1..))]
+    pub content: Option<TrimmedStr<'v>>, // when unassigning, None => any
     #[field(validate = len(1..))]
     pub selected: Vec<GroupRefDto<'v>>,
 }
 
+#[derive(FromForm)]
+pub struct DelegateTagDto<'v> {
+    #[field(validate = super::valid_slug())]
+    pub tag: TrimmedStr<'v>,
+    pub group: GroupRefDto<'v>,
+}
+
 #[derive(FromForm)]
 pub struct CreateSubtagDto<'v> {
     pub subtag: TagKey<'v>,
 }
 
+#[derive(FromForm)]
+pub struct CreateTagDerivationDto<'v> {
+    pub perm: PermissionKey<'v>,
+    #[field(validate = super::option_len(1..))]
+    pub scope: Option<TrimmedStr<'v>>, // None => any
+}
+
+#[derive(FromFormField, Clone, Copy, PartialEq, Eq)]
+pub enum TaggedEntityKind {
+    Group,
+    User,
+}
+
+#[derive(FromForm)]
+pub struct TaggedFilterDto<'v> {
+    #[field(validate = super::option_len(1..))]
+    pub content: Option<&'v str>,
+    #[field(name = "type")]
+    pub kind: Option<TaggedEntityKind>, // None => both
+    #[field(default = true)]
+    pub transitive: bool, // whether to resolve subtags
+    #[field(default = 0)]
+    pub offset: u32,
+    pub limit: Option<u32>,
+}
+
 pub struct TagKey<'v> {
     pub system_id: &'v str,
     pub tag_id: &'v str,
@@ -0,0 +1,11 @@
+use rocket::FromForm;
+
+#[derive(FromForm)]
+pub struct DigestSettingsDto {
+    pub subscribed: bool, // (unchecked boxes aren't submitted, i.e. false)
+}
+
+#[derive(FromForm)]
+pub struct MembershipEmailSettingsDto {
+    pub subscribed: bool, // (idem)
+}
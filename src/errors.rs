@@ -15,7 +15,8 @@ use uuid::Uuid;
 use crate::{
     auth::oidc::OidcAuthenticationError,
     dto::errors::AppErrorDto,
-    guards::{context::PageContext, headers::HxRequest},
+    guards::{context::PageContext, format::ResponseFormat, headers::HxRequest},
+    models::GroupState,
     perms::HivePermission,
     services::groups::AuthorityInGroup,
 };
@@ -36,8 +37,12 @@ pub enum AppError {
     StateSerializationError(#[source] serde_json::Error),
     #[error("failed to deserialize internal state from secure storage: {0}")]
     StateDeserializationError(#[source] serde_json::Error), // not from client-controlled
+    #[error("failed to serialize response as JSON: {0}")]
+    ResponseSerializationError(#[source] serde_json::Error),
     #[error("failed to translate usernames to display names via the set endpoint: {0}")]
     IdentityResolutionError(#[source] reqwest::Error),
+    #[error("failed to send email via the mailing service: {0}")]
+    MailerError(#[source] reqwest::Error),
     #[error("failed to decode error while generating error page from JSON")]
     ErrorDecodeFailure,
 
@@ -49,16 +54,28 @@ pub enum AppError {
     AuthenticationFlowExpired,
     #[error("action disallowed because it compromises system integrity")]
     SelfPreservation,
+    #[error("action is restricted to administrators (root group members)")]
+    AdministratorsOnly,
+    #[error("state cannot be changed while in read-only maintenance mode")]
+    MaintenanceMode,
+    #[error("legacy dump cannot be imported: {0}")]
+    InvalidLegacyDump(String),
+    #[error("invalid value for query parameter `{0}`")]
+    InvalidQueryParameter(String),
 
     #[error("could not find system with ID `{0}`")]
     NoSuchSystem(String),
     #[error("ID `{0}` is already in use by another system")]
     DuplicateSystemId(String),
+    #[error("system manifest cannot be applied: {0}")]
+    InvalidSystemManifest(String),
 
     #[error("could not find API token with ID `{0}`")]
     NoSuchApiToken(Uuid),
     #[error("description `{0}` is already in use by another API token for this system")]
     AmbiguousApiToken(String),
+    #[error("API token `{0}` predates signing keys and cannot require signed requests")]
+    UnsignableApiToken(Uuid),
 
     #[error("could not find permission with key `${0}:{1}`")]
     NoSuchPermission(String, String),
@@ -70,6 +87,8 @@ pub enum AppError {
     MissingPermissionScope(String, String),
     #[error("permission with key `${0}:{1}` does not accept a scope on assignment")]
     ExtraneousPermissionScope(String, String),
+    #[error("scope `{2}` is not valid for permission with key `${0}:{1}`")]
+    InvalidPermissionScope(String, String, String),
 
     #[error("could not find tag with key `#{0}:{1}`")]
     NoSuchTag(String, String),
@@ -101,6 +120,97 @@ pub enum AppError {
 
     #[error("could not find any group membership with id `{0}`")]
     NoSuchMembership(String),
+    #[error("membership of user `{0}` would exceed the allowed appointment bounds")]
+    AppointmentTooLong(String),
+
+    #[error("group has no pending ownership transfer")]
+    NoPendingTransfer,
+    #[error("only the receiving party can respond to an ownership transfer")]
+    NotTransferRecipient,
+
+    #[error("could not find any undoable deletion with id `{0}`")]
+    NoSuchDeletion(Uuid),
+    #[error("deletion cannot be undone because it conflicts with newer changes")]
+    UndoConflict,
+
+    #[error("could not find any pending change with id `{0}`")]
+    NoSuchPendingChange(Uuid),
+    #[error("pending changes must be approved by someone other than their proposer")]
+    SelfApproval,
+    #[error("change in protected domain `{0}` cannot be proposed for approval")]
+    UnapprovableProtectedChange(String),
+
+    #[error("verification link is invalid or has expired")]
+    InvalidVerificationLink,
+
+    #[error("could not find any link with id `{0}` in this group")]
+    NoSuchGroupLink(Uuid),
+
+    #[error("groups in external domain `{0}` cannot confer permissions")]
+    ExternalDomainPermission(String),
+    #[error(
+        "group with key `{0}@{1}` is external, so it can only be a subgroup of external groups"
+    )]
+    ExternalSubgroup(String, String),
+
+    #[error("deletion of system `{0}` must be confirmed by typing its ID")]
+    DeletionNotConfirmed(String),
+    #[error("deletion must be confirmed by someone other than who requested it")]
+    SelfConfirmedDeletion,
+
+    #[error("could not find any webhook with id `{0}` in this system")]
+    NoSuchWebhook(Uuid),
+    #[error("could not find any attempted delivery with id `{0}` for this webhook")]
+    NoSuchWebhookDelivery(Uuid),
+
+    #[error("could not find any shared member list with id `{0}` in this group")]
+    NoSuchMemberListShare(Uuid),
+
+    #[error("long-term appointment of `{0}` lacks a justification")]
+    MissingAppointmentJustification(String),
+
+    #[error("group member cap of {0} would be exceeded")]
+    MemberCapExceeded(i32),
+
+    #[error("could not find any operational year with id `{0}`")]
+    NoSuchOperationalYear(Uuid),
+    #[error("operational year with name `{0}` already exists")]
+    DuplicateOperationalYearName(String),
+    #[error("operational year would overlap with `{0}`")]
+    OverlappingOperationalYear(String),
+    #[error("membership dates are outside of operational year `{0}`")]
+    MembershipOutsideOperationalYear(String),
+
+    #[error("nobody would be left with permission `{0}`")]
+    LastHivePermissionHolder(String),
+
+    #[error("unknown task `{1}` of integration `{0}`")]
+    NoSuchIntegrationTask(String, String),
+    #[error("invalid task schedule `{0}`")]
+    InvalidTaskSchedule(String),
+    #[error("unknown task run `{0}`")]
+    NoSuchTaskRun(Uuid),
+
+    #[error("tag `#{0}:{1}` cannot be derived from permissions")]
+    InvalidTagDerivation(String, String),
+    #[error("tag is already derived from permission `{0}`")]
+    DuplicateTagDerivation(String),
+    #[error("unknown tag derivation `{0}`")]
+    NoSuchTagDerivation(Uuid),
+
+    #[error("group cannot go from being {0} to being {1}")]
+    InvalidGroupStateTransition(GroupState, GroupState),
+
+    #[error("unknown pending membership `{0}`")]
+    NoSuchPendingMembership(Uuid),
+
+    #[error("unknown group invitation `{0}`")]
+    NoSuchGroupInvitation(Uuid),
+    #[error("group invitation has expired, been used up or been revoked")]
+    GroupInvitationUnusable,
+
+    #[error("could not find any comment with id `{0}` in this group")]
+    NoSuchGroupComment(Uuid),
 }
 
 impl AppError {
@@ -118,7 +228,7 @@ impl AppError {
         Self::from(err)
     }
 
-    fn status(&self) -> Status {
+    pub(crate) fn status(&self) -> Status {
         match self {
             AppError::DbError(..) => Status::InternalServerError,
             AppError::QueryBuildError(..) => Status::InternalServerError,
@@ -126,21 +236,30 @@ impl AppError {
             AppError::OidcAuthenticationError(..) => Status::InternalServerError,
             AppError::StateSerializationError(..) => Status::InternalServerError,
             AppError::StateDeserializationError(..) => Status::InternalServerError,
+            AppError::ResponseSerializationError(..) => Status::InternalServerError,
             AppError::IdentityResolutionError(..) => Status::InternalServerError,
+            AppError::MailerError(..) => Status::InternalServerError,
             AppError::ErrorDecodeFailure => Status::InternalServerError,
             AppError::NotAllowed(..) => Status::Forbidden,
             AppError::InsufficientAuthorityInGroup(..) => Status::Forbidden,
             AppError::AuthenticationFlowExpired => Status::Gone,
             AppError::SelfPreservation => Status::UnavailableForLegalReasons,
+            AppError::AdministratorsOnly => Status::Forbidden,
+            AppError::MaintenanceMode => Status::ServiceUnavailable,
+            AppError::InvalidLegacyDump(..) => Status::BadRequest,
+            AppError::InvalidQueryParameter(..) => Status::BadRequest,
             AppError::NoSuchSystem(..) => Status::NotFound,
             AppError::DuplicateSystemId(..) => Status::Conflict,
+            AppError::InvalidSystemManifest(..) => Status::BadRequest,
             AppError::NoSuchApiToken(..) => Status::NotFound,
             AppError::AmbiguousApiToken(..) => Status::Conflict,
+            AppError::UnsignableApiToken(..) => Status::Conflict,
             AppError::NoSuchPermission(..) => Status::NotFound,
             AppError::DuplicatePermissionId(..) => Status::Conflict,
             AppError::DuplicatePermissionAssignment(..) => Status::Conflict,
             AppError::MissingPermissionScope(..) => Status::BadRequest,
             AppError::ExtraneousPermissionScope(..) => Status::BadRequest,
+            AppError::InvalidPermissionScope(..) => Status::BadRequest,
             AppError::NoSuchTag(..) => Status::NotFound,
             AppError::DuplicateTagId(..) => Status::Conflict,
             AppError::DuplicateTagAssignment(..) => Status::Conflict,
@@ -155,23 +274,83 @@ impl AppError {
             AppError::DuplicateSubgroup(..) => Status::Conflict,
             AppError::RedundantMembership(..) => Status::Conflict,
             AppError::NoSuchMembership(..) => Status::NotFound,
+            AppError::AppointmentTooLong(..) => Status::Forbidden,
+            AppError::NoPendingTransfer => Status::NotFound,
+            AppError::NotTransferRecipient => Status::Forbidden,
+            AppError::NoSuchDeletion(..) => Status::NotFound,
+            AppError::UndoConflict => Status::Conflict,
+            AppError::NoSuchPendingChange(..) => Status::NotFound,
+            AppError::SelfApproval => Status::Forbidden,
+            AppError::UnapprovableProtectedChange(..) => Status::Forbidden,
+            AppError::InvalidVerificationLink => Status::NotFound,
+            AppError::NoSuchGroupLink(..) => Status::NotFound,
+            AppError::ExternalDomainPermission(..) => Status::Forbidden,
+            AppError::ExternalSubgroup(..) => Status::Forbidden,
+            AppError::DeletionNotConfirmed(..) => Status::BadRequest,
+            AppError::SelfConfirmedDeletion => Status::Forbidden,
+            AppError::NoSuchWebhook(..) => Status::NotFound,
+            AppError::NoSuchWebhookDelivery(..) => Status::NotFound,
+            AppError::NoSuchMemberListShare(..) => Status::NotFound,
+            AppError::MissingAppointmentJustification(..) => Status::BadRequest,
+            AppError::MemberCapExceeded(..) => Status::Conflict,
+            AppError::NoSuchOperationalYear(..) => Status::NotFound,
+            AppError::DuplicateOperationalYearName(..) => Status::Conflict,
+            AppError::OverlappingOperationalYear(..) => Status::Conflict,
+            AppError::MembershipOutsideOperationalYear(..) => Status::BadRequest,
+            AppError::LastHivePermissionHolder(..) => Status::UnavailableForLegalReasons,
+            AppError::NoSuchIntegrationTask(..) => Status::NotFound,
+            AppError::InvalidTaskSchedule(..) => Status::BadRequest,
+            AppError::NoSuchTaskRun(..) => Status::NotFound,
+            AppError::InvalidTagDerivation(..) => Status::BadRequest,
+            AppError::DuplicateTagDerivation(..) => Status::Conflict,
+            AppError::NoSuchTagDerivation(..) => Status::NotFound,
+            AppError::InvalidGroupStateTransition(..) => Status::BadRequest,
+            AppError::NoSuchPendingMembership(..) => Status::NotFound,
+            AppError::NoSuchGroupInvitation(..) => Status::NotFound,
+            AppError::GroupInvitationUnusable => Status::Gone,
+            AppError::NoSuchGroupComment(..) => Status::NotFound,
+        }
+    }
+
+    // hint for clients about when retrying the exact same request might
+    // succeed, in seconds (None if it never will, or it's unknown)
+    pub(crate) fn retry_after(&self) -> Option<u32> {
+        match self {
+            AppError::MaintenanceMode => Some(MAINTENANCE_RETRY_AFTER),
+            AppError::DbError(sqlx::Error::PoolTimedOut | sqlx::Error::Io(..)) => {
+                Some(TRANSIENT_RETRY_AFTER)
+            }
+            _ => None,
         }
     }
 }
 
+// maintenance typically lasts a while (e.g., during migrations or restores)
+const MAINTENANCE_RETRY_AFTER: u32 = 5 * 60;
+
+// connection issues with the database tend to resolve themselves quickly
+const TRANSIENT_RETRY_AFTER: u32 = 5;
+
 impl<'r> Responder<'r, 'static> for AppError {
     fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
         let status = self.status();
-        if status.code >= 500 {
+        if status.code >= 500 && !matches!(self, AppError::MaintenanceMode) {
             // debug prints enum variant name, display shows thiserror message
             error!("While handling [{req}], encountered {self:?}: {self}");
         } else {
             debug!("While handling [{req}], encountered {self:?}: {self}")
         }
 
+        let retry_after = self.retry_after();
         let base = Json(AppErrorDto::from(self)).respond_to(req)?;
 
-        Ok(Response::build_from(base).status(status).finalize())
+        let mut response = Response::build_from(base);
+        response.status(status);
+        if let Some(seconds) = retry_after {
+            response.raw_header("Retry-After", seconds.to_string());
+        }
+
+        Ok(response.finalize())
     }
 }
 
@@ -232,6 +411,11 @@ impl Fairing for ErrorPageGenerator {
             return;
         }
 
+        if ResponseFormat::of(req).is_json() {
+            // nothing to do; client asked for JSON (see `web::render`)
+            return;
+        }
+
         if res.content_type().map(|t| t.is_html()).unwrap_or(false) {
             // this is not JSON! probably an error that has already been made
             // into HTML by a catcher
@@ -1,6 +1,7 @@
 pub mod api;
 pub mod context;
 pub mod cors;
+pub mod format;
 pub mod headers;
 pub mod lang;
 pub mod nav;
@@ -1,2 +1,5 @@
 pub mod consumer;
 pub mod token;
+
+#[cfg(test)]
+mod tests;
@@ -1,26 +1,59 @@
-use chrono::Local;
 use rocket::{
     Request, State,
     http::Status,
     request::{FromRequest, Outcome},
 };
-use sqlx::{PgPool, prelude::FromRow};
+use sqlx::PgPool;
 use uuid::Uuid;
 
-use super::token::BearerToken;
+use super::token::{BearerToken, InvalidSignedRequest, SignedRequest};
 use crate::{
     api::HiveApiPermission,
     errors::{AppError, AppResult},
+    models::ApiToken,
     perms::HivePermission,
-    services::api_tokens,
+    services::api_tokens::{self, GroupVisibility},
 };
 
 const IMPERSONATION_HEADER: &str = "X-Hive-Impersonate-System";
 
-#[derive(FromRow)]
 pub struct ApiConsumer {
     pub api_token_id: Uuid,
     pub system_id: String,
+    pub capabilities: ApiTokenCapabilities,
+}
+
+// structural limits on what a token can do, enforced before (and regardless
+// of) whichever permissions are assigned to it
+#[derive(Clone, Copy)]
+pub struct ApiTokenCapabilities {
+    pub read_only: bool,
+    pub groups_only: bool,
+    pub permissions_only: bool,
+}
+
+impl ApiTokenCapabilities {
+    pub fn allow(&self, perm: &HiveApiPermission) -> bool {
+        if self.read_only && perm.is_write() {
+            return false;
+        }
+
+        if self.groups_only && !perm.concerns_groups() {
+            return false;
+        }
+
+        !self.permissions_only || perm.concerns_permissions()
+    }
+}
+
+impl From<&ApiToken> for ApiTokenCapabilities {
+    fn from(token: &ApiToken) -> Self {
+        Self {
+            read_only: token.read_only,
+            groups_only: token.groups_only,
+            permissions_only: token.permissions_only,
+        }
+    }
 }
 
 impl ApiConsumer {
@@ -28,6 +61,10 @@ impl ApiConsumer {
     where
         X: sqlx::Executor<'x, Database = sqlx::Postgres>,
     {
+        if !self.capabilities.allow(&min) {
+            return Ok(false);
+        }
+
         // (ignores scope since all current permissions don't have any scope)
         let satisfies = sqlx::query_scalar(
             "SELECT COUNT(*) > 0
@@ -56,6 +93,14 @@ impl ApiConsumer {
         }
     }
 
+    // tokens might be restricted to only "see" certain groups through the API
+    pub async fn group_visibility<'x, X>(&self, db: X) -> AppResult<GroupVisibility>
+    where
+        X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+    {
+        api_tokens::get_group_visibility(self.api_token_id, db).await
+    }
+
     pub async fn try_impersonate<'x, X>(
         self,
         other_system_id: &str,
@@ -84,6 +129,7 @@ impl ApiConsumer {
             Some(Self {
                 api_token_id: self.api_token_id,
                 system_id: other_system_id.to_owned(),
+                capabilities: self.capabilities, // still the same token
             })
         } else {
             None
@@ -98,6 +144,8 @@ pub enum InvalidApiConsumer {
     MissingBearerToken,
     MalformedUuid,
     UnknownApiToken,
+    InvalidSignature,
+    SignatureRequired,
     UnauthorizedImpersonation,
 }
 
@@ -106,49 +154,81 @@ impl<'r> FromRequest<'r> for ApiConsumer {
     type Error = InvalidApiConsumer;
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        if let Some(bearer) = req.guard::<BearerToken>().await.succeeded() {
-            if let Ok(secret) = Uuid::try_parse(bearer.0) {
-                let hash = api_tokens::hash_secret(secret);
-                let now = Local::now();
-
-                let pool = req.guard::<&State<PgPool>>().await.unwrap();
-
-                let result: Result<ApiConsumer, _> = sqlx::query_as(
-                    "UPDATE api_tokens
-                    SET last_used_at = $1
-                    WHERE secret = $2
-                        AND (expires_at IS NULL OR expires_at >= $1)
-                    RETURNING id AS api_token_id, system_id",
-                )
-                .bind(now)
-                .bind(hash)
-                .fetch_one(pool.inner())
-                .await;
-
-                if let Ok(consumer) = result {
-                    if let Some(other_system_id) = req.headers().get_one(IMPERSONATION_HEADER) {
-                        if let Ok(Some(impersonated)) = consumer
-                            .try_impersonate(other_system_id, pool.inner())
-                            .await
-                        {
-                            Outcome::Success(impersonated)
-                        } else {
-                            Outcome::Error((
-                                Status::Forbidden,
-                                InvalidApiConsumer::UnauthorizedImpersonation,
-                            ))
+        let pool = req.guard::<&State<PgPool>>().await.unwrap();
+
+        let token = match req.guard::<SignedRequest>().await {
+            Outcome::Success(signed) => {
+                if let Ok(Some(token)) =
+                    api_tokens::authenticate_signed(&signed, pool.inner()).await
+                {
+                    token
+                } else {
+                    return Outcome::Error((
+                        Status::Unauthorized,
+                        InvalidApiConsumer::InvalidSignature,
+                    ));
+                }
+            }
+            Outcome::Error((_, InvalidSignedRequest::Headers)) => {
+                // not signed; fall back to plain bearer token instead
+                if let Some(bearer) = req.guard::<BearerToken>().await.succeeded() {
+                    if let Ok(secret) = Uuid::try_parse(bearer.0) {
+                        match api_tokens::authenticate(secret, pool.inner()).await {
+                            Ok(Some(token)) if token.requires_signing => {
+                                return Outcome::Error((
+                                    Status::Unauthorized,
+                                    InvalidApiConsumer::SignatureRequired,
+                                ));
+                            }
+                            Ok(Some(token)) => token,
+                            _ => {
+                                return Outcome::Error((
+                                    Status::Unauthorized,
+                                    InvalidApiConsumer::UnknownApiToken,
+                                ));
+                            }
                         }
                     } else {
-                        Outcome::Success(consumer)
+                        return Outcome::Error((
+                            Status::Unauthorized,
+                            InvalidApiConsumer::MalformedUuid,
+                        ));
                     }
                 } else {
-                    Outcome::Error((Status::Unauthorized, InvalidApiConsumer::UnknownApiToken))
+                    return Outcome::Error((
+                        Status::Unauthorized,
+                        InvalidApiConsumer::MissingBearerToken,
+                    ));
                 }
+            }
+            _ => {
+                return Outcome::Error((
+                    Status::Unauthorized,
+                    InvalidApiConsumer::InvalidSignature,
+                ));
+            }
+        };
+
+        let consumer = ApiConsumer {
+            api_token_id: token.id,
+            capabilities: ApiTokenCapabilities::from(&token),
+            system_id: token.system_id,
+        };
+
+        if let Some(other_system_id) = req.headers().get_one(IMPERSONATION_HEADER) {
+            if let Ok(Some(impersonated)) = consumer
+                .try_impersonate(other_system_id, pool.inner())
+                .await
+            {
+                Outcome::Success(impersonated)
             } else {
-                Outcome::Error((Status::Unauthorized, InvalidApiConsumer::MalformedUuid))
+                Outcome::Error((
+                    Status::Forbidden,
+                    InvalidApiConsumer::UnauthorizedImpersonation,
+                ))
             }
         } else {
-            Outcome::Error((Status::Unauthorized, InvalidApiConsumer::MissingBearerToken))
+            Outcome::Success(consumer)
         }
     }
 }
@@ -0,0 +1,169 @@
+// signed API requests (see `SignedRequest` and `api_tokens::authenticate_signed`),
+// sent the way clients would, to a route that also checks the content digest
+
+use chrono::Local;
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::asynchronous::{Client, LocalResponse},
+};
+use serde_json::json;
+use sha2::Digest;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    auth::oidc::OidcClient,
+    config::Config,
+    services::{ReadReplica, api_tokens},
+};
+
+const MANIFEST_URI: &str = "/api/v1/system/calypso/manifest?dry_run=true";
+const MANIFEST: &str = r#"{"permissions":[]}"#;
+
+struct SigningToken {
+    id: Uuid,
+    signing_key: Vec<u8>,
+}
+
+async fn client(db: PgPool) -> Client {
+    let config: Config = serde_json::from_value(json!({
+        "db_url": "",
+        "secret_key": "ab".repeat(64),
+        "oidc_issuer_url": "",
+        "oidc_client_id": "",
+        "oidc_client_secret": "",
+    }))
+    .unwrap();
+
+    let replica = ReadReplica::new(None, &db);
+    let rocket = crate::build(&config, db, replica, OidcClient::offline(), None);
+
+    Client::untracked(rocket).await.unwrap()
+}
+
+async fn create_signing_token(db: &PgPool) -> SigningToken {
+    let secret = Uuid::new_v4();
+    // derived from the secret exactly like clients do
+    let signing_key = api_tokens::hmac_sha256(secret.as_bytes(), b"hive-signing");
+
+    let id = sqlx::query_scalar(
+        "INSERT INTO api_tokens (secret, signing_key, system_id, description, requires_signing)
+        VALUES ($1, $2, 'calypso', 'Signing test token', TRUE)
+        RETURNING id",
+    )
+    .bind(api_tokens::hash_secret(secret))
+    .bind(hex::encode(&signing_key))
+    .fetch_one(db)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        "INSERT INTO permission_assignments (system_id, perm_id, api_token_id)
+        VALUES ('hive', 'api-apply-manifest', $1)",
+    )
+    .bind(id)
+    .execute(db)
+    .await
+    .unwrap();
+
+    SigningToken { id, signing_key }
+}
+
+fn digest(body: &str) -> String {
+    hex::encode(sha2::Sha256::digest(body.as_bytes()))
+}
+
+fn sign(token: &SigningToken, timestamp: i64, body_digest: &str) -> String {
+    let message = format!("{timestamp}\nPUT\n{MANIFEST_URI}\n{body_digest}");
+
+    hex::encode(api_tokens::hmac_sha256(
+        &token.signing_key,
+        message.as_bytes(),
+    ))
+}
+
+async fn send<'c>(
+    client: &'c Client,
+    token: &SigningToken,
+    timestamp: i64,
+    body_digest: &str,
+    signature: &str,
+) -> LocalResponse<'c> {
+    client
+        .put(MANIFEST_URI)
+        .header(ContentType::JSON)
+        .header(Header::new("X-Hive-Token-Id", token.id.to_string()))
+        .header(Header::new("X-Hive-Timestamp", timestamp.to_string()))
+        .header(Header::new("X-Hive-Content-SHA256", body_digest.to_owned()))
+        .header(Header::new("X-Hive-Signature", signature.to_owned()))
+        .body(MANIFEST)
+        .dispatch()
+        .await
+}
+
+#[sqlx::test(fixtures(path = "../../../seeds", scripts("dev")))]
+async fn valid_signatures_are_accepted(db: PgPool) {
+    let token = create_signing_token(&db).await;
+    let client = client(db).await;
+
+    let now = Local::now().timestamp();
+    let signature = sign(&token, now, &digest(MANIFEST));
+
+    let response = send(&client, &token, now, &digest(MANIFEST), &signature).await;
+
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[sqlx::test(fixtures(path = "../../../seeds", scripts("dev")))]
+async fn tampered_signatures_are_rejected(db: PgPool) {
+    let token = create_signing_token(&db).await;
+    let client = client(db).await;
+
+    let now = Local::now().timestamp();
+    let mut signature = sign(&token, now, &digest(MANIFEST));
+    let flipped = if signature.ends_with('0') { "1" } else { "0" };
+    signature.replace_range(signature.len() - 1.., flipped);
+
+    let response = send(&client, &token, now, &digest(MANIFEST), &signature).await;
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    // validly signed, but claiming a different time than what was signed
+    let signature = sign(&token, now, &digest(MANIFEST));
+
+    let response = send(&client, &token, now + 1, &digest(MANIFEST), &signature).await;
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[sqlx::test(fixtures(path = "../../../seeds", scripts("dev")))]
+async fn stale_signatures_are_rejected(db: PgPool) {
+    let token = create_signing_token(&db).await;
+    let client = client(db).await;
+
+    // (well beyond the allowed clock skew)
+    let stale = Local::now().timestamp() - 60 * 60;
+    let signature = sign(&token, stale, &digest(MANIFEST));
+
+    let response = send(&client, &token, stale, &digest(MANIFEST), &signature).await;
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[sqlx::test(fixtures(path = "../../../seeds", scripts("dev")))]
+async fn wrong_content_digests_are_rejected(db: PgPool) {
+    let token = create_signing_token(&db).await;
+    let client = client(db).await;
+
+    let now = Local::now().timestamp();
+
+    // the signature covers a digest other than the declared one
+    let signature = sign(&token, now, &digest("{}"));
+
+    let response = send(&client, &token, now, &digest(MANIFEST), &signature).await;
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    // validly signed, but the body doesn't match the declared digest
+    let signature = sign(&token, now, &digest("{}"));
+
+    let response = send(&client, &token, now, &digest("{}"), &signature).await;
+    assert_eq!(response.status(), Status::BadRequest);
+}
@@ -3,6 +3,8 @@ use rocket::{
     http::Status,
     request::{FromRequest, Outcome},
 };
+use sha2::Digest;
+use uuid::Uuid;
 
 pub struct BearerToken<'t>(pub &'t str);
 
@@ -28,3 +30,72 @@ impl<'r> FromRequest<'r> for BearerToken<'r> {
         }
     }
 }
+
+const TOKEN_ID_HEADER: &str = "X-Hive-Token-Id";
+const TIMESTAMP_HEADER: &str = "X-Hive-Timestamp";
+const SIGNATURE_HEADER: &str = "X-Hive-Signature";
+const CONTENT_DIGEST_HEADER: &str = "X-Hive-Content-SHA256";
+
+// alternative to BearerToken where the secret itself is never sent, but
+// rather used to sign the request (see `api_tokens::authenticate_signed`)
+pub struct SignedRequest<'t> {
+    pub token_id: Uuid,
+    pub timestamp: i64, // seconds since epoch
+    pub signature: &'t str,
+    pub body_digest: String, // as claimed; must be checked once the body is read
+    pub message: String,     // what should have been signed
+}
+
+#[derive(Debug)]
+pub enum InvalidSignedRequest {
+    Headers,
+    Malformed,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SignedRequest<'r> {
+    type Error = InvalidSignedRequest;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = req.headers();
+
+        match (
+            headers.get_one(TOKEN_ID_HEADER),
+            headers.get_one(TIMESTAMP_HEADER),
+            headers.get_one(SIGNATURE_HEADER),
+        ) {
+            (Some(token_id), Some(timestamp), Some(signature)) => {
+                if let (Ok(token_id), Ok(timestamp)) =
+                    (Uuid::try_parse(token_id), timestamp.parse())
+                {
+                    // guards cannot read the body, so requests that have one
+                    // must declare its digest, to be verified by the route
+                    let body_digest = match headers.get_one(CONTENT_DIGEST_HEADER) {
+                        Some(digest) => digest.to_owned(),
+                        None => hex::encode(sha2::Sha256::digest(b"")),
+                    };
+
+                    let message = format!(
+                        "{timestamp}\n{}\n{}\n{body_digest}",
+                        req.method(),
+                        req.uri()
+                    );
+
+                    Outcome::Success(Self {
+                        token_id,
+                        timestamp,
+                        signature,
+                        body_digest,
+                        message,
+                    })
+                } else {
+                    Outcome::Error((Status::Unauthorized, InvalidSignedRequest::Malformed))
+                }
+            }
+            (None, None, None) => {
+                Outcome::Error((Status::Unauthorized, InvalidSignedRequest::Headers))
+            }
+            _ => Outcome::Error((Status::Unauthorized, InvalidSignedRequest::Malformed)),
+        }
+    }
+}
@@ -1,16 +1,32 @@
 use std::{borrow::Cow, fmt};
 
+use chrono::NaiveDate;
 use rocket::{
-    Request,
-    request::{FromRequest, Outcome},
+    Request, State,
+    request::{FlashMessage, FromRequest, Outcome},
 };
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
 
-use super::{Infallible, lang::Language, nav::Nav, user::User};
+use super::{Infallible, format::ResponseFormat, lang::Language, nav::Nav, user::User};
+use crate::services::groups;
 
+// flash message kind whose message is the ID of a deletion that can be undone
+pub const UNDO_FLASH_KIND: &str = "undo";
+
+#[derive(Serialize)]
 pub struct PageContext {
     pub lang: Language,
     pub user: Option<User>,
+    #[serde(skip)]
     pub nav: Nav,
+    pub admin: bool,                    // i.e., root member
+    pub root_expiry: Option<NaiveDate>, // only for administrators
+    pub undo: Option<Uuid>,             // deletion that was just performed
+    pub maintenance: bool,              // read-only mode
+    #[serde(skip)]
+    pub format: ResponseFormat,
 }
 
 // Convenience aliases to prevent having to ctx.lang.t
@@ -33,6 +49,62 @@ impl<'r> FromRequest<'r> for PageContext {
         let user = req.guard::<User>().await.succeeded();
         let nav = req.guard::<Nav>().await.unwrap();
 
-        Outcome::Success(Self { lang, user, nav })
+        let admin = match &user {
+            Some(user) => is_admin(req, user).await,
+            None => false,
+        };
+
+        let root_expiry = if admin {
+            get_root_expiry(req).await
+        } else {
+            None
+        };
+
+        let undo = req
+            .guard::<Option<FlashMessage<'_>>>()
+            .await
+            .unwrap()
+            .filter(|flash| flash.kind() == UNDO_FLASH_KIND)
+            .and_then(|flash| flash.message().parse().ok());
+
+        Outcome::Success(Self {
+            lang,
+            user,
+            nav,
+            admin,
+            root_expiry,
+            undo,
+            maintenance: crate::routing::maintenance::is_active(),
+            format: ResponseFormat::of(req),
+        })
     }
 }
+
+// failures are ignored since this is merely used to show administrative
+// information and controls, and shouldn't prevent pages from loading (routes
+// that depend on it must check again by themselves)
+async fn is_admin(req: &Request<'_>, user: &User) -> bool {
+    let Some(db) = req.guard::<&State<PgPool>>().await.succeeded() else {
+        return false;
+    };
+
+    groups::details::get_role_in_group(
+        user.username(),
+        crate::HIVE_ROOT_GROUP_ID,
+        crate::HIVE_INTERNAL_DOMAIN,
+        db.inner(),
+    )
+    .await
+    .ok()
+    .flatten()
+    .is_some()
+}
+
+// only administrators (i.e., root members) can do anything about it, so there's
+// no point in bothering anyone else; failures are ignored since this is merely
+// informative and shouldn't prevent pages from loading
+async fn get_root_expiry(req: &Request<'_>) -> Option<NaiveDate> {
+    let db = req.guard::<&State<PgPool>>().await.succeeded()?;
+
+    groups::members::get_root_expiry(db.inner()).await.ok()?
+}
@@ -0,0 +1,37 @@
+use rocket::{
+    Request,
+    request::{FromRequest, Outcome},
+};
+
+use super::Infallible;
+
+// how the client wants web responses to be represented: HTML unless it
+// explicitly prefers JSON (`Accept: application/json`), in which case the same
+// data that would otherwise be fed to the template is serialized instead
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ResponseFormat {
+    Html,
+    Json,
+}
+
+impl ResponseFormat {
+    pub fn of(req: &Request<'_>) -> Self {
+        match req.accept() {
+            Some(accept) if accept.preferred().media_type().is_json() => Self::Json,
+            _ => Self::Html,
+        }
+    }
+
+    pub fn is_json(&self) -> bool {
+        *self == Self::Json
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ResponseFormat {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Self::of(req))
+    }
+}
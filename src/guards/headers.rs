@@ -8,12 +8,13 @@ use rocket::{
 // `Header<const NAME: &str>` because &str is a
 // forbidden const type; instead, we use an index
 // to this array
-const HEADER_NAMES: &[&str] = &["Accept-Language", "HX-Request"];
+const HEADER_NAMES: &[&str] = &["Accept-Language", "HX-Request", "User-Agent"];
 
 pub struct Header<'r, const N: usize>(&'r str);
 
 pub type AcceptLanguage<'r> = Header<'r, 0>;
 pub type HxRequest<'r> = Header<'r, 1>;
+pub type UserAgent<'r> = Header<'r, 2>;
 
 #[derive(Debug)]
 pub struct MissingHeader;
@@ -5,6 +5,7 @@ use rocket::{
     http::CookieJar,
     request::{FromRequest, Outcome},
 };
+use serde::Serialize;
 
 use super::{Infallible, headers::AcceptLanguage};
 
@@ -28,6 +29,12 @@ impl fmt::Display for Language {
     }
 }
 
+impl Serialize for Language {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl Language {
     fn from_tag(tag: &str) -> Option<Self> {
         let tag = tag.to_lowercase();
@@ -41,7 +48,7 @@ impl Language {
         }
     }
 
-    fn i18n_locale(&self) -> &str {
+    pub fn i18n_locale(&self) -> &str {
         match self {
             Self::Swedish => "sv",
             Self::English => "en",
@@ -8,6 +8,7 @@ use super::perms::PermsEvaluator;
 use crate::{
     errors::AppError,
     perms::{HivePermission, SystemsScope},
+    services::changes::ProtectedDomains,
 };
 
 // pub type Nav = Vec<NavLink> not allowed because of orphan rule;
@@ -57,11 +58,26 @@ impl<'r> FromRequest<'r> for Nav {
                 Err(err) => return err.into(),
             }
 
+            // changes can only be pending if some domain is protected
+            if req
+                .rocket()
+                .state::<ProtectedDomains>()
+                .is_some_and(|protected| !protected.is_empty())
+            {
+                links.push(NavLink::new("changes", "/changes", &path));
+            }
+
             match perms.satisfies(HivePermission::ViewLogs).await {
                 Ok(true) => links.push(NavLink::new("logs", "/logs", &path)),
                 Ok(false) => {}
                 Err(err) => return err.into(),
             }
+
+            match perms.satisfies(HivePermission::RestoreDeletions).await {
+                Ok(true) => links.push(NavLink::new("trash", "/trash", &path)),
+                Ok(false) => {}
+                Err(err) => return err.into(),
+            }
         }
 
         Outcome::Success(Self { links })
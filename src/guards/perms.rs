@@ -11,9 +11,9 @@ use sqlx::PgPool;
 
 use super::{Infallible, user::User};
 use crate::{
-    HIVE_SYSTEM_ID,
     errors::{AppError, AppResult},
-    perms::{self, HivePermission},
+    perms::HivePermission,
+    services::perms_cache,
 };
 
 pub struct PermsEvaluator {
@@ -81,17 +81,9 @@ impl PermsEvaluator {
         cache: &mut MutexGuard<'_, HivePermissionsCache>,
         key: &'static str,
     ) -> AppResult<Vec<HivePermission>> {
-        let perms = perms::get_assignments(self.user.username(), HIVE_SYSTEM_ID, key, &self.db)
-            .await?
-            .into_iter()
-            .map(HivePermission::try_from)
-            .inspect(|r| {
-                if let Err(err) = r {
-                    warn!("Got invalid Hive permission: {err:?}");
-                }
-            })
-            .filter_map(Result::ok)
-            .collect::<Vec<_>>();
+        // (the application-wide cache is shared across requests, but still
+        // needs a lock and key allocations, so this one is kept on top of it)
+        let perms = perms_cache::get_hive_permissions(self.user.username(), key, &self.db).await?;
 
         cache.insert(key, perms.clone());
 
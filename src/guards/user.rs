@@ -1,10 +1,12 @@
 use std::sync::Arc;
 
+use chrono::{DateTime, Local};
 use rocket::{
     Request,
     http::Status,
     request::{FromRequest, Outcome},
 };
+use serde::{Serialize, ser::SerializeStruct};
 
 use super::Infallible;
 use crate::auth;
@@ -19,6 +21,20 @@ impl User {
     pub fn display_name(&self) -> &str {
         &self.0.display_name
     }
+
+    pub fn session_expiration(&self) -> DateTime<Local> {
+        self.0.expiration
+    }
+}
+
+// (only what is also shown in the page header, never the session itself)
+impl Serialize for User {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut user = serializer.serialize_struct("User", 2)?;
+        user.serialize_field("username", self.username())?;
+        user.serialize_field("display_name", self.display_name())?;
+        user.end()
+    }
 }
 
 #[rocket::async_trait]
@@ -1,23 +1,58 @@
-use std::{collections::HashMap, future::Future, pin::Pin, sync::LazyLock};
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{LazyLock, OnceLock},
+    time::Duration,
+};
 
-use chrono::Local;
+use chrono::{Local, TimeDelta};
 use log::*;
+use rocket::tokio::{
+    sync::{Mutex, watch},
+    time,
+};
+use serde::Serialize;
+use sha2::Digest;
 use sqlx::{PgPool, error::DatabaseError};
 use tokio_cron_scheduler::{Job, JobScheduler, JobSchedulerError};
+use uuid::Uuid;
 
 use crate::{
+    clock,
     errors::AppResult,
+    mailer::Mailer,
     models::{IntegrationTaskLogEntry, IntegrationTaskLogEntryKind, IntegrationTaskRun},
+    secrets,
 };
 
 #[cfg(feature = "integration-gworkspace")]
 mod gworkspace;
+#[cfg(feature = "integration-health-checks")]
+mod health_checks;
+
+// set once tasks are scheduled, so that their schedules can be changed later
+static SCHEDULED_JOBS: OnceLock<ScheduledJobs> = OnceLock::new();
+
+// how long a task that was cancelled (after exceeding its timeout) gets to stop
+// on its own, before it is aborted wherever it happens to be
+const CANCELLATION_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+// per-deployment task timeouts (in seconds) overriding those declared in the
+// manifests, keyed like `gworkspace/sync-to-directory`
+static TASK_TIMEOUT_OVERRIDES: OnceLock<HashMap<String, u64>> = OnceLock::new();
+
+// Hive's own mailer (if configured), for tasks that send emails themselves
+// (e.g., reports) instead of each integration configuring its own
+static MAILER: OnceLock<Mailer> = OnceLock::new();
 
 // can't use const because it wouldn't support async fn pointers for tasks
 pub static MANIFESTS: LazyLock<Vec<&Manifest>> = LazyLock::new(|| {
     vec![
         #[cfg(feature = "integration-gworkspace")]
         &*gworkspace::MANIFEST,
+        #[cfg(feature = "integration-health-checks")]
+        &*health_checks::MANIFEST,
     ]
 });
 
@@ -60,6 +95,9 @@ pub struct Tag {
                              * `user.settings.field.integration_`
                              * + `{INT_ID}_{TAG_ID}.`
                              * + `label`, `placeholder`, and `tip` */
+    pub verify_email: bool, /* whether self-set content is an email address that
+                             * must be confirmed via a link before it is used
+                             * (only if a mailer is configured) */
 }
 
 // Rust really is very clunky sometimes...
@@ -73,27 +111,65 @@ type AppResultFuture<'a, T> = Pin<Box<dyn Future<Output = AppResult<T>> + Send +
 pub struct Task {
     pub id: &'static str,
     pub schedule: &'static str,
+    pub timeout: Duration, // after which the run is cancelled and marked failed
     pub(self) func: fn(&mut TaskRunMonitor, SettingsValues, PgPool) -> AppResultFuture<'_, ()>,
 }
 
 type SettingsValues = HashMap<String, serde_json::Value>;
 
+// cancellation is cooperative: tasks should check it between steps (e.g., once
+// per synchronized group) and stop early once it is set
+#[derive(Clone)]
+struct CancellationToken(watch::Receiver<bool>);
+
+impl CancellationToken {
+    fn new() -> (watch::Sender<bool>, Self) {
+        let (tx, rx) = watch::channel(false);
+
+        (tx, Self(rx))
+    }
+
+    fn is_cancelled(&self) -> bool {
+        *self.0.borrow()
+    }
+}
+
+struct ScheduledJobs {
+    scheduler: JobScheduler,
+    job_ids: Mutex<HashMap<(&'static str, &'static str), Uuid>>,
+    db: PgPool,
+}
+
 struct TaskRunMonitor {
     succeeded: bool,
     logs: Vec<IntegrationTaskLogEntry>,
+    cancellation: CancellationToken,
 }
 
 impl TaskRunMonitor {
-    fn new() -> Self {
+    fn new(cancellation: CancellationToken) -> Self {
         Self {
             succeeded: false,
             logs: Vec::with_capacity(128),
+            cancellation,
         }
     }
 
     fn succeeded(&mut self) {
         self.succeeded = true;
     }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    // e.g., to tell whether some step went through without any errors
+    fn n_errors(&self) -> usize {
+        self.logs
+            .iter()
+            .filter(|entry| matches!(entry.kind, IntegrationTaskLogEntryKind::Error))
+            .count()
+    }
 }
 
 macro_rules! impl_log_entry {
@@ -116,39 +192,81 @@ impl_log_entry!(error, IntegrationTaskLogEntryKind::Error);
 impl_log_entry!(warn, IntegrationTaskLogEntryKind::Warning);
 impl_log_entry!(info, IntegrationTaskLogEntryKind::Info);
 
+pub fn init_task_timeouts(overrides: HashMap<String, u64>) {
+    if TASK_TIMEOUT_OVERRIDES.set(overrides).is_err() {
+        warn!("Integration task timeouts were already initialized; ignoring");
+    }
+}
+
+pub fn init_mailer(mailer: Option<Mailer>) {
+    let Some(mailer) = mailer else {
+        return;
+    };
+
+    if MAILER.set(mailer).is_err() {
+        warn!("Integration mailer was already initialized; ignoring");
+    }
+}
+
+#[cfg_attr(not(feature = "integration-health-checks"), allow(dead_code))]
+fn mailer() -> Option<&'static Mailer> {
+    MAILER.get()
+}
+
+// e.g., to show how a task is actually configured
+pub fn task_timeout(manifest: &Manifest, task: &Task) -> Duration {
+    TASK_TIMEOUT_OVERRIDES
+        .get()
+        .and_then(|overrides| overrides.get(&format!("{}/{}", manifest.id, task.id)))
+        .map_or(task.timeout, |secs| Duration::from_secs(*secs))
+}
+
 pub async fn schedule_tasks(db: PgPool) -> Result<(), JobSchedulerError> {
     let scheduler = JobScheduler::new().await?;
 
-    for manifest in &*MANIFESTS {
-        debug!("Setting up integration {} from manifest", manifest.id);
+    reconcile_manifests(&db).await;
+
+    let overrides: HashMap<(String, String), String> =
+        sqlx::query_as::<_, (String, String, String)>(
+            "SELECT integration_id, task_id, schedule
+            FROM integration_task_schedules",
+        )
+        .fetch_all(&db)
+        .await
+        .expect("Failed to list integration task schedule overrides")
+        .into_iter()
+        .map(|(integration_id, task_id, schedule)| ((integration_id, task_id), schedule))
+        .collect();
 
-        setup_integration(manifest, &db).await;
+    let mut job_ids = HashMap::new();
 
+    for manifest in &*MANIFESTS {
         debug!("Registering jobs for integration {}", manifest.id);
 
         for task in manifest.tasks {
-            let db = db.clone(); // cheap, just an Arc
-            let job = Job::new_async_tz(task.schedule, Local, move |uuid, _| {
-                let db = db.clone();
-
-                Box::pin(async move {
-                    debug!(
-                        "Executing job {} for task {} (integration {})",
-                        uuid, task.id, manifest.id
+            let schedule = match overrides.get(&(manifest.id.to_owned(), task.id.to_owned())) {
+                Some(schedule) if is_valid_schedule(schedule) => {
+                    info!(
+                        "Task {} (integration {}) is scheduled as `{schedule}` instead of `{}`",
+                        task.id, manifest.id, task.schedule
                     );
-
-                    dispatch_task_run(manifest.id, task, &db)
-                        .await
-                        .expect("Task run failed");
-
-                    debug!(
-                        "Finished executing job {} for task {} (integration {})",
-                        uuid, task.id, manifest.id
+                    schedule.as_str()
+                }
+                Some(schedule) => {
+                    warn!(
+                        "Ignoring invalid schedule `{schedule}` for task {} (integration {})",
+                        task.id, manifest.id
                     );
-                })
-            })?;
+                    task.schedule
+                }
+                None => task.schedule,
+            };
 
-            scheduler.add(job).await?;
+            let job_id = scheduler
+                .add(make_job(manifest, task, schedule, &db)?)
+                .await?;
+
+            job_ids.insert((manifest.id, task.id), job_id);
         }
     }
 
@@ -156,11 +274,172 @@ pub async fn schedule_tasks(db: PgPool) -> Result<(), JobSchedulerError> {
 
     scheduler.start().await?;
 
+    let scheduled = ScheduledJobs {
+        scheduler,
+        job_ids: Mutex::new(job_ids),
+        db,
+    };
+
+    if SCHEDULED_JOBS.set(scheduled).is_err() {
+        warn!("Integration tasks were already scheduled; ignoring");
+    }
+
     info!("All integration jobs scheduled!");
 
     Ok(())
 }
 
+fn make_job(
+    manifest: &'static Manifest,
+    task: &'static Task,
+    schedule: &str,
+    db: &PgPool,
+) -> Result<Job, JobSchedulerError> {
+    let db = db.clone(); // cheap, just an Arc
+
+    new_job(schedule, move |uuid, _| {
+        let db = db.clone();
+
+        Box::pin(async move {
+            if crate::routing::maintenance::is_active() {
+                info!(
+                    "Skipping job {} for task {} (integration {}) due to maintenance mode",
+                    uuid, task.id, manifest.id
+                );
+                return;
+            }
+
+            debug!(
+                "Executing job {} for task {} (integration {})",
+                uuid, task.id, manifest.id
+            );
+
+            dispatch_task_run(manifest, task, &db)
+                .await
+                .expect("Task run failed");
+
+            debug!(
+                "Finished executing job {} for task {} (integration {})",
+                uuid, task.id, manifest.id
+            );
+        })
+    })
+}
+
+// schedules are interpreted in the same timezone as membership periods, so
+// that, e.g., a nightly sync runs after memberships have changed for the day
+fn new_job<T>(schedule: &str, run: T) -> Result<Job, JobSchedulerError>
+where
+    T: FnMut(Uuid, JobScheduler) -> Pin<Box<dyn Future<Output = ()> + Send>>
+        + Send
+        + Sync
+        + 'static,
+{
+    match clock::tz() {
+        Some(tz) => Job::new_async_tz(schedule, tz, run),
+        None => Job::new_async_tz(schedule, Local, run),
+    }
+}
+
+// (6-field) cron expressions, as understood by the scheduler itself
+pub fn is_valid_schedule(schedule: &str) -> bool {
+    new_job(schedule, |_, _| Box::pin(async {})).is_ok()
+}
+
+// replaces the task's job so that a changed schedule (or `None`, for the one in
+// its manifest) takes effect right away; the schedule must already be valid
+pub async fn reschedule_task(integration_id: &str, task_id: &str, schedule: Option<&str>) {
+    let Some(scheduled) = SCHEDULED_JOBS.get() else {
+        // (will be picked up once tasks are scheduled)
+        return;
+    };
+
+    let Some((manifest, task)) = find_task(integration_id, task_id) else {
+        return;
+    };
+
+    let schedule = schedule.unwrap_or(task.schedule);
+
+    let result = async {
+        let job = make_job(manifest, task, schedule, &scheduled.db)?;
+        let job_id = scheduled.scheduler.add(job).await?;
+
+        let old = scheduled
+            .job_ids
+            .lock()
+            .await
+            .insert((manifest.id, task.id), job_id);
+
+        if let Some(old) = old {
+            scheduled.scheduler.remove(&old).await?;
+        }
+
+        Ok::<_, JobSchedulerError>(())
+    };
+
+    match result.await {
+        Ok(()) => info!(
+            "Rescheduled task {} (integration {}) as `{schedule}`",
+            task.id, manifest.id
+        ),
+        Err(e) => error!(
+            "Failed to reschedule task {} (integration {}) as `{schedule}`: {e}",
+            task.id, manifest.id
+        ),
+    }
+}
+
+fn find_task(integration_id: &str, task_id: &str) -> Option<(&'static Manifest, &'static Task)> {
+    let manifest = MANIFESTS.iter().find(|m| m.id == integration_id).copied()?;
+    let task = manifest.tasks.iter().find(|t| t.id == task_id)?;
+
+    Some((manifest, task))
+}
+
+// makes the database match the compiled-in manifests on every startup, so that
+// enabling an integration's feature needs no manual setup; leftovers that
+// might still hold configuration are only ever warned about, not deleted
+async fn reconcile_manifests(db: &PgPool) {
+    for manifest in &*MANIFESTS {
+        debug!("Setting up integration {} from manifest", manifest.id);
+
+        setup_integration(manifest, db).await;
+        reconcile_settings(manifest, db).await;
+    }
+
+    // e.g., integrations that have since been disabled at compile time
+    let integration_ids: Vec<_> = MANIFESTS.iter().map(|manifest| manifest.id).collect();
+
+    sqlx::query(
+        "UPDATE tags
+        SET managed = FALSE
+        WHERE managed
+            AND NOT (system_id = ANY($1))",
+    )
+    .bind(&integration_ids)
+    .execute(db)
+    .await
+    .expect("Failed to unmark tags of unknown integrations");
+
+    let orphaned: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT integration_id
+        FROM integration_settings
+        WHERE NOT (integration_id = ANY($1))
+        ORDER BY integration_id",
+    )
+    .bind(&integration_ids)
+    .fetch_all(db)
+    .await
+    .expect("Failed to list settings of unknown integrations");
+
+    for integration_id in orphaned {
+        warn!(
+            "Found settings for integration {integration_id}, which is unknown (removed or not \
+             enabled at compile time?); they are kept, but unused"
+        );
+    }
+}
+
 async fn setup_integration(manifest: &Manifest, db: &PgPool) {
     sqlx::query(
         "INSERT INTO systems (id, description)
@@ -179,13 +458,15 @@ async fn setup_integration(manifest: &Manifest, db: &PgPool) {
     for tag in manifest.tags {
         sqlx::query(
             "INSERT INTO tags
-                (system_id, tag_id, description, supports_groups, supports_users, has_content)
-            VALUES ($1, $2, $3, $4, $5, $6)
+                (system_id, tag_id, description, supports_groups, supports_users, has_content,
+                    managed)
+            VALUES ($1, $2, $3, $4, $5, $6, TRUE)
             ON CONFLICT (system_id, tag_id) DO UPDATE SET
                 description = EXCLUDED.description,
                 supports_groups = EXCLUDED.supports_groups,
                 supports_users = EXCLUDED.supports_users,
-                has_content = EXCLUDED.has_content",
+                has_content = EXCLUDED.has_content,
+                managed = TRUE",
         )
         .bind(manifest.id)
         .bind(tag.id)
@@ -197,16 +478,178 @@ async fn setup_integration(manifest: &Manifest, db: &PgPool) {
         .await
         .expect("Failed to create tag for integration");
     }
+
+    // tags it no longer declares are left alone (they might still be assigned),
+    // but can now be deleted like any other
+    let tag_ids: Vec<_> = manifest.tags.iter().map(|tag| tag.id).collect();
+
+    sqlx::query(
+        "UPDATE tags
+        SET managed = FALSE
+        WHERE system_id = $1
+            AND managed
+            AND NOT (tag_id = ANY($2))",
+    )
+    .bind(manifest.id)
+    .bind(tag_ids)
+    .execute(db)
+    .await
+    .expect("Failed to unmark tags no longer declared by integration");
 }
 
-async fn dispatch_task_run(integration_id: &str, task: &Task, db: &PgPool) -> AppResult<()> {
+// settings with an obviously safe value are initialized with it (booleans as
+// off, selects as their first option, which must thus be the most cautious),
+// while any others are left for administrators to fill in
+async fn reconcile_settings(manifest: &Manifest, db: &PgPool) {
+    for setting in manifest.settings {
+        let initial = match setting.r#type {
+            SettingType::Boolean => serde_json::Value::Bool(false),
+            SettingType::Select(options) => match options.first() {
+                Some(option) => serde_json::Value::String(option.value.to_owned()),
+                None => continue,
+            },
+            SettingType::ShortText | SettingType::LongText => continue,
+        };
+
+        let initialized = sqlx::query(
+            "INSERT INTO integration_settings (integration_id, setting_id, setting_value)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (integration_id, setting_id) DO NOTHING",
+        )
+        .bind(manifest.id)
+        .bind(setting.id)
+        .bind(&initial)
+        .execute(db)
+        .await
+        .expect("Failed to initialize integration setting")
+        .rows_affected()
+            > 0;
+
+        if initialized {
+            info!(
+                "Initialized setting {} of integration {} to {initial}",
+                setting.id, manifest.id
+            );
+        }
+    }
+
+    let stored: Vec<(String, serde_json::Value)> = sqlx::query_as(
+        "SELECT setting_id, setting_value
+        FROM integration_settings
+        WHERE integration_id = $1
+        ORDER BY setting_id",
+    )
+    .bind(manifest.id)
+    .fetch_all(db)
+    .await
+    .expect("Failed to list integration settings");
+
+    for (setting_id, value) in &stored {
+        let Some(setting) = manifest.settings.iter().find(|s| s.id == setting_id) else {
+            warn!(
+                "Integration {} has a value for setting {setting_id}, which it no longer \
+                 declares; it is kept, but unused",
+                manifest.id
+            );
+            continue;
+        };
+
+        match setting.r#type {
+            SettingType::Select(options) if !options.iter().any(|o| *value == o.value) => warn!(
+                "Integration {} has an invalid value for setting {setting_id}: {value}",
+                manifest.id
+            ),
+            _ => {}
+        }
+    }
+
+    for setting in manifest.settings {
+        if !stored
+            .iter()
+            .any(|(setting_id, _)| setting_id == setting.id)
+        {
+            info!(
+                "Integration {} has no value for setting {} yet",
+                manifest.id, setting.id
+            );
+        }
+    }
+}
+
+// runs are guarded by a session-level advisory lock on a dedicated connection,
+// which (unlike an unfinished run's row) can't outlive a crashed instance; a
+// run that can't take it is skipped, but still recorded in the run history
+async fn dispatch_task_run(manifest: &Manifest, task: &Task, db: &PgPool) -> AppResult<()> {
+    let lock_key = format!("integration-task:{}:{}", manifest.id, task.id);
+
+    let mut lock_conn = db.acquire().await?;
+
+    let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock(hashtextextended($1, 0))")
+        .bind(&lock_key)
+        .fetch_one(&mut *lock_conn)
+        .await?;
+
+    if !locked {
+        warn!(
+            "Skipping run for task {} (integration {}) because another one is still ongoing",
+            task.id, manifest.id
+        );
+
+        return record_skipped_task_run(manifest, task, db).await;
+    }
+
+    let result = run_task(manifest, task, db).await;
+
+    let unlocked: Result<bool, _> =
+        sqlx::query_scalar("SELECT pg_advisory_unlock(hashtextextended($1, 0))")
+            .bind(&lock_key)
+            .fetch_one(&mut *lock_conn)
+            .await;
+
+    if !matches!(unlocked, Ok(true)) {
+        // closing the connection releases the lock all the same, rather than
+        // returning it to the pool while still holding it
+        warn!("Failed to release lock {lock_key}; closing its connection instead");
+
+        if let Err(e) = lock_conn.close().await {
+            error!("Failed to close connection holding lock {lock_key}: {e}");
+        }
+    }
+
+    result
+}
+
+async fn run_task(manifest: &Manifest, task: &Task, db: &PgPool) -> AppResult<()> {
+    // while the lock is held, any unfinished run can only have been interrupted
+    // (e.g., by a crash or restart), and would otherwise block this one forever
+    let interrupted: Vec<Uuid> = sqlx::query_scalar(
+        "UPDATE integration_task_runs
+        SET end_stamp = NOW(), succeeded = FALSE
+        WHERE integration_id = $1
+            AND task_id = $2
+            AND end_stamp IS NULL
+        RETURNING run_id",
+    )
+    .bind(manifest.id)
+    .bind(task.id)
+    .fetch_all(db)
+    .await?;
+
+    for run_id in interrupted {
+        warn!(
+            "Marked run {run_id} for task {} (integration {}) as failed, since it was \
+             interrupted before finishing",
+            task.id, manifest.id
+        );
+    }
+
     let run: IntegrationTaskRun = sqlx::query_as(
         "INSERT INTO integration_task_runs
             (integration_id, task_id)
         VALUES ($1, $2)
         RETURNING *",
     )
-    .bind(integration_id)
+    .bind(manifest.id)
     .bind(task.id)
     .fetch_one(db)
     .await
@@ -221,20 +664,63 @@ async fn dispatch_task_run(integration_id: &str, task: &Task, db: &PgPool) -> Ap
 
     debug!("Assigned run ID {}", run.run_id);
 
-    let settings: HashMap<String, serde_json::Value> = sqlx::query_as(
+    let mut settings: HashMap<String, serde_json::Value> = sqlx::query_as(
         "SELECT setting_id, setting_value
         FROM integration_settings
         WHERE integration_id = $1",
     )
-    .bind(integration_id)
+    .bind(manifest.id)
     .fetch_all(db)
     .await?
     .into_iter()
     .collect();
 
-    let mut mon = TaskRunMonitor::new();
+    let (cancel, cancellation) = CancellationToken::new();
+    let mut mon = TaskRunMonitor::new(cancellation);
 
-    let result = (task.func)(&mut mon, settings, db.clone()).await;
+    resolve_secret_settings(manifest, &mut settings, &mut mon).await;
+
+    let timeout = task_timeout(manifest, task);
+
+    // `None` if it had to be aborted
+    let (result, timed_out) = {
+        let mut future = (task.func)(&mut mon, settings, db.clone());
+
+        match time::timeout(timeout, &mut future).await {
+            Ok(result) => (Some(result), false),
+            Err(_) => {
+                cancel.send_replace(true);
+
+                (
+                    time::timeout(CANCELLATION_GRACE_PERIOD, future).await.ok(),
+                    true,
+                )
+            }
+        }
+    };
+
+    if timed_out {
+        warn!(
+            "Run {} for task {} (integration {}) timed out after {timeout:?}",
+            run.run_id, task.id, manifest.id
+        );
+
+        mon.succeeded = false;
+        mon.error(format!(
+            "Run was cancelled because it exceeded its timeout of {} seconds",
+            timeout.as_secs()
+        ));
+
+        if result.is_none() {
+            mon.error(format!(
+                "Task did not stop within {} seconds of being cancelled, so it was aborted",
+                CANCELLATION_GRACE_PERIOD.as_secs()
+            ));
+        }
+    }
+
+    // (an aborted run is only recorded as failed, like any that timed out)
+    let result = result.unwrap_or(Ok(()));
 
     let mut txn = db.begin().await?;
 
@@ -273,6 +759,173 @@ async fn dispatch_task_run(integration_id: &str, task: &Task, db: &PgPool) -> Ap
     result
 }
 
+async fn record_skipped_task_run(manifest: &Manifest, task: &Task, db: &PgPool) -> AppResult<()> {
+    let mut txn = db.begin().await?;
+
+    let run_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO integration_task_runs
+            (integration_id, task_id, end_stamp, skipped)
+        VALUES ($1, $2, NOW(), TRUE)
+        RETURNING run_id",
+    )
+    .bind(manifest.id)
+    .bind(task.id)
+    .fetch_one(&mut *txn)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO integration_task_logs (run_id, kind, message)
+        VALUES ($1, $2, $3)",
+    )
+    .bind(run_id)
+    .bind(IntegrationTaskLogEntryKind::Warning)
+    .bind("Skipped because another run of this task is still ongoing")
+    .execute(&mut *txn)
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+// secret settings may be stored as references to an external secrets manager
+// (see `crate::secrets`), which are fetched anew for every run so that rotated
+// secrets are picked up; failures leave the setting unset, such that the task
+// reports it as missing instead of receiving the reference itself
+async fn resolve_secret_settings(
+    manifest: &Manifest,
+    settings: &mut SettingsValues,
+    mon: &mut TaskRunMonitor,
+) {
+    for setting in manifest.settings.iter().filter(|s| s.secret) {
+        let Some(serde_json::Value::String(value)) = settings.get(setting.id) else {
+            continue;
+        };
+
+        if !secrets::is_reference(value) {
+            continue;
+        }
+
+        match secrets::resolve(value).await {
+            Ok(secret) => {
+                let secret = serde_json::Value::String(secret.into_owned());
+                settings.insert(setting.id.to_owned(), secret);
+            }
+            Err(e) => {
+                mon.error(format!(
+                    "Failed to fetch secret setting `{}`: {e}",
+                    setting.id
+                ));
+                settings.remove(setting.id);
+            }
+        }
+    }
+}
+
+// Integrations can remember what they last pushed for each entity they mirror
+// (see `integration_state_hashes`), to skip any whose state in Hive hasn't
+// changed since. Hashes expire after a while regardless, so that changes made
+// directly in the external system (or to how state is pushed) are still
+// corrected eventually.
+const STATE_HASH_MAX_AGE: TimeDelta = TimeDelta::days(1);
+
+fn state_hash(state: &impl Serialize) -> String {
+    let json = serde_json::to_vec(state).expect("state should be serializable");
+
+    hex::encode(sha2::Sha256::digest(json))
+}
+
+async fn is_state_unchanged(
+    integration_id: &str,
+    entity: &str,
+    hash: &str,
+    db: &PgPool,
+) -> AppResult<bool> {
+    let unchanged = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0
+        FROM integration_state_hashes
+        WHERE integration_id = $1
+            AND entity = $2
+            AND hash = $3
+            AND pushed_at > $4",
+    )
+    .bind(integration_id)
+    .bind(entity)
+    .bind(hash)
+    .bind(Local::now() - STATE_HASH_MAX_AGE)
+    .fetch_one(db)
+    .await?;
+
+    Ok(unchanged)
+}
+
+// should only be called once the state was pushed without any errors
+async fn record_state_hash(
+    integration_id: &str,
+    entity: &str,
+    hash: &str,
+    db: &PgPool,
+) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO integration_state_hashes (integration_id, entity, hash)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (integration_id, entity) DO UPDATE SET
+            hash = EXCLUDED.hash,
+            pushed_at = NOW()",
+    )
+    .bind(integration_id)
+    .bind(entity)
+    .bind(hash)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// expired hashes are useless, and entities that are no longer mirrored would
+// otherwise keep theirs forever
+async fn purge_stale_state_hashes(integration_id: &str, db: &PgPool) -> AppResult<()> {
+    sqlx::query(
+        "DELETE FROM integration_state_hashes
+        WHERE integration_id = $1
+            AND pushed_at <= $2",
+    )
+    .bind(integration_id)
+    .bind(Local::now() - STATE_HASH_MAX_AGE)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// runs a task right away (in the background), outside of its usual schedule;
+// returns false if there is no such task
+pub fn trigger_task_run(integration_id: &str, task_id: &str, db: PgPool) -> bool {
+    let Some((manifest, task)) = find_task(integration_id, task_id) else {
+        return false;
+    };
+
+    rocket::tokio::spawn(async move {
+        debug!(
+            "Executing triggered run for task {} (integration {})",
+            task.id, manifest.id
+        );
+
+        if let Err(e) = dispatch_task_run(manifest, task, &db).await {
+            error!(
+                "Triggered run for task {} (integration {}) failed: {e}",
+                task.id, manifest.id
+            );
+        }
+    });
+
+    true
+}
+
+pub fn task_exists(integration_id: &str, task_id: &str) -> bool {
+    find_task(integration_id, task_id).is_some()
+}
+
 pub fn integration_exists(id: &str) -> bool {
     for manifest in &*MANIFESTS {
         if manifest.id == id {
@@ -289,7 +942,10 @@ macro_rules! require_list_setting {
     };
     ($settings:expr, $key:literal, $contained:expr) => {
         if let Some(serde_json::Value::String(s)) = $settings.get($key) {
-            s.split(',').filter(|e| e.contains($contained)).collect()
+            s.split(',')
+                .map(str::trim)
+                .filter(|e| e.contains($contained))
+                .collect()
         } else {
             Vec::new()
         }
@@ -1,15 +1,27 @@
-use std::{collections::HashSet, iter, sync::LazyLock};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+    time::Duration,
+};
 
-use serde::Deserialize;
+use rocket::futures::{StreamExt, stream};
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
 use crate::{
-    errors::AppResult, integrations::gworkspace::google::DirectoryApiClient, models,
+    clock, errors::AppResult, integrations::gworkspace::google::DirectoryApiClient, models,
     services::groups,
 };
 
 mod google;
 
+// requests to the Directory API that may be in flight at once, per group;
+// well within its quota, but enough that syncing large groups is quick
+const MEMBER_CHANGE_CONCURRENCY: usize = 10;
+
+// a full sync of a large directory can take a while
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
 // can't use const because it wouldn't support async fn pointers for tasks
 pub static MANIFEST: LazyLock<super::Manifest> = LazyLock::new(|| {
     super::Manifest {
@@ -39,8 +51,9 @@ pub static MANIFEST: LazyLock<super::Manifest> = LazyLock::new(|| {
             super::Setting {
                 id: "primary-domain",
                 secret: false,
-                name: "Primary Domain",
-                description: "Where user accounts will be looked up & created",
+                name: "Primary Domains",
+                description: "Comma-separated list of Workspace domains where user accounts \
+                              will be looked up (in order) & created (in the first)",
                 r#type: super::SettingType::ShortText,
             },
             super::Setting {
@@ -76,7 +89,8 @@ pub static MANIFEST: LazyLock<super::Manifest> = LazyLock::new(|| {
                 id: "alternative-domains",
                 secret: false,
                 name: "Alternative Domains",
-                description: "Comma-separated list of secondary domains where to lookup users",
+                description: "Comma-separated list of secondary domains (or aliases) where to \
+                              lookup users, after the primary ones",
                 r#type: super::SettingType::ShortText,
             },
         ],
@@ -88,6 +102,7 @@ pub static MANIFEST: LazyLock<super::Manifest> = LazyLock::new(|| {
                 supports_groups: true,
                 supports_users: true,
                 self_service: false,
+                verify_email: false,
             },
             super::Tag {
                 id: "allow-external",
@@ -96,14 +111,37 @@ pub static MANIFEST: LazyLock<super::Manifest> = LazyLock::new(|| {
                 supports_groups: true,
                 supports_users: false,
                 self_service: false,
+                verify_email: false,
             },
             super::Tag {
                 id: "grace-period",
-                description: "Keep old members until a month past their membership end date",
+                description: "Keep old members for a while past their membership end date \
+                              (e.g., `P2M` or `45d`; defaults to a month if invalid)",
+                has_content: true,
+                supports_groups: true,
+                supports_users: false,
+                self_service: false,
+                verify_email: false,
+            },
+            super::Tag {
+                id: "sync-managers",
+                description: "Give the group's current Hive managers the MANAGER role in its \
+                              Google group (instead of everyone being a MEMBER)",
                 has_content: false,
                 supports_groups: true,
                 supports_users: false,
                 self_service: false,
+                verify_email: false,
+            },
+            super::Tag {
+                id: "setting",
+                description: "Override one of the Google group's default settings, as \
+                              `key=VALUE` (e.g., `who_can_post_message=ALL_MEMBERS_CAN_POST`)",
+                has_content: true,
+                supports_groups: true,
+                supports_users: false,
+                self_service: false,
+                verify_email: false,
             },
             super::Tag {
                 id: "sensitive",
@@ -113,6 +151,7 @@ pub static MANIFEST: LazyLock<super::Manifest> = LazyLock::new(|| {
                 supports_groups: true,
                 supports_users: false,
                 self_service: false,
+                verify_email: false,
             },
             super::Tag {
                 id: "extra-member",
@@ -121,6 +160,7 @@ pub static MANIFEST: LazyLock<super::Manifest> = LazyLock::new(|| {
                 supports_groups: true,
                 supports_users: false,
                 self_service: false,
+                verify_email: false,
             },
             super::Tag {
                 id: "extra-subgroup",
@@ -129,6 +169,7 @@ pub static MANIFEST: LazyLock<super::Manifest> = LazyLock::new(|| {
                 supports_groups: true,
                 supports_users: false,
                 self_service: false,
+                verify_email: false,
             },
             super::Tag {
                 id: "embed-members",
@@ -143,6 +184,7 @@ pub static MANIFEST: LazyLock<super::Manifest> = LazyLock::new(|| {
                 supports_groups: true,
                 supports_users: false,
                 self_service: false,
+                verify_email: false,
             },
             super::Tag {
                 id: "personal-email",
@@ -151,17 +193,19 @@ pub static MANIFEST: LazyLock<super::Manifest> = LazyLock::new(|| {
                 supports_groups: false,
                 supports_users: true,
                 self_service: true,
+                verify_email: true,
             },
         ],
         tasks: &[super::Task {
             id: "sync-to-directory",
             schedule: "0 0 * * * *", // every hour
+            timeout: SYNC_TIMEOUT,
             func: |mon, settings, db| Box::pin(sync_to_directory(mon, settings, db)),
         }],
     }
 });
 
-#[derive(Deserialize, Clone, Copy)]
+#[derive(Deserialize, Serialize, Clone, Copy)]
 #[serde(rename_all = "kebab-case")]
 enum Mode {
     DryRun,     // no actions are taken
@@ -214,8 +258,29 @@ async fn sync_to_directory(
 ) -> AppResult<()> {
     let mode: Mode = super::require_serde_setting!(mon, settings, "mode");
 
-    let primary_domain = super::require_string_setting!(mon, settings, "primary-domain", '.');
-    let alternative_domains = super::require_list_setting!(settings, "alternative-domains", '.');
+    let primary_domains: Vec<&str> = super::require_list_setting!(settings, "primary-domain", '.');
+    let alternative_domains: Vec<&str> =
+        super::require_list_setting!(settings, "alternative-domains", '.');
+
+    if primary_domains.is_empty() {
+        mon.error("Setting value `primary-domain` is not set correctly");
+
+        return Ok(());
+    }
+
+    // every domain where user accounts are looked up, in order (since users
+    // might have an account in any one of them, e.g. if there are several
+    // Workspace domains); the first match wins
+    let mut user_domains = primary_domains;
+    for domain in alternative_domains {
+        if !user_domains.contains(&domain) {
+            user_domains.push(domain);
+        }
+    }
+
+    // username -> Workspace email (if any), so that each user is only looked up
+    // (in possibly every domain) once per run, however many groups they are in
+    let mut workspace_emails = HashMap::new();
 
     let service_account_email =
         super::require_string_setting!(mon, settings, "service-account-email", '@');
@@ -258,17 +323,59 @@ async fn sync_to_directory(
     let mut whitelist = super::require_list_setting!(settings, "group-whitelist", '@');
     whitelist.sort_unstable();
 
+    // old keys of groups that have since been renamed, so that the mirrored
+    // Google groups can be renamed as well (instead of deleted and recreated)
+    let aliases: HashMap<String, String> = sqlx::query_as(
+        "SELECT old_id || '@' || old_domain, group_id || '@' || group_domain
+        FROM group_aliases",
+    )
+    .fetch_all(&db)
+    .await?
+    .into_iter()
+    .collect();
+
     // doing this before sync'ing groups to avoid listing newly-created;
     // means that we don't need to process groups that obviously should remain
-    let listed = fallible!(mon, client.list_groups().await);
+    let mut listed = fallible!(mon, client.list_groups().await);
 
-    for existing in &listed {
+    for existing in &mut listed {
         let (id, domain) = existing.email.split_once('@').expect("valid email");
 
         if groups
             .binary_search_by_key(&(domain, id), |g| (g.domain.as_str(), g.id.as_str()))
             .is_err()
         {
+            if let Some(new_key) = aliases.get(&existing.email.to_lowercase()) {
+                let (new_id, new_domain) = new_key.split_once('@').expect("valid key");
+
+                if groups
+                    .binary_search_by_key(&(new_domain, new_id), |g| {
+                        (g.domain.as_str(), g.id.as_str())
+                    })
+                    .is_ok()
+                {
+                    mon.info(format!(
+                        "Renaming group <{}> to <{new_key}>",
+                        existing.email
+                    ));
+
+                    if mode.should_update() {
+                        let patch = google::GroupPatch {
+                            email: Some(new_key),
+                            name: None,
+                            description: None,
+                        };
+
+                        fallible!(mon, client.patch_group(&existing.email, &patch).await);
+                    }
+
+                    // either way, it shouldn't be created again below
+                    existing.email = new_key.clone();
+
+                    continue;
+                }
+            }
+
             if whitelist.binary_search(&existing.email.as_str()).is_ok() {
                 mon.info(format!(
                     "Not deleting whitelisted group `{}`",
@@ -301,9 +408,22 @@ async fn sync_to_directory(
         .collect();
     existing_emails.sort_unstable(); // to allow binary search
 
+    super::purge_stale_state_hashes("gworkspace", &db).await?;
+
+    let mut n_skipped = 0;
+
     for group in &groups {
         let key = format!("{}@{}", group.id, group.domain);
 
+        if mon.is_cancelled() {
+            // (any groups already pushed have had their state hashes recorded)
+            mon.warn(format!(
+                "Stopping before group `{key}` since the run was cancelled"
+            ));
+
+            return Ok(());
+        }
+
         mon.info(format!("Synchronizing group `{key}`"));
 
         let allow_external = groups::tags::is_tagged_with(
@@ -319,12 +439,7 @@ async fn sync_to_directory(
             mon.info(format!("Group {key} allows external members"));
         }
 
-        if existing_emails.binary_search(&key).is_err() {
-            // this group wasn't in the listing, so we need to create it
-            create_group(&key, group, &client, mode, mon).await?;
-        }
-
-        sync_group_settings(&key, group, &client, mode, mon).await?;
+        let exists = existing_emails.binary_search(&key).is_ok();
 
         let subgroup_emails_owned: Vec<_> =
             groups::members::get_direct_subgroups(&group.id, &group.domain, &db)
@@ -353,13 +468,18 @@ async fn sync_to_directory(
             .map(String::as_str)
             .collect();
 
-        let has_grace_period = groups::tags::is_tagged_with(
-            &group.id,
-            &group.domain,
-            "gworkspace",
-            "grace-period",
-            &db,
+        let grace_periods: Vec<String> = sqlx::query_scalar(
+            "SELECT content
+            FROM all_tag_assignments
+            WHERE system_id = 'gworkspace'
+                AND tag_id = 'grace-period'
+                AND group_id = $1
+                AND group_domain = $2
+                AND content IS NOT NULL",
         )
+        .bind(&group.id)
+        .bind(&group.domain)
+        .fetch_all(&db)
         .await?;
 
         // Mainly for roles that handle confidential information
@@ -367,11 +487,23 @@ async fn sync_to_directory(
             groups::tags::is_tagged_with(&group.id, &group.domain, "gworkspace", "sensitive", &db)
                 .await?;
 
-        let grace_period = if has_grace_period && !is_sensitive {
-            // 2025-03-01 becomes 2025-02-01, etc.
-            Some(chrono::Months::new(1))
-        } else {
+        let grace_period = if is_sensitive {
             None
+        } else {
+            // if tagged more than once, the most lenient period wins
+            let today = clock::today();
+            grace_periods
+                .iter()
+                .map(|content| {
+                    content.parse().unwrap_or_else(|_| {
+                        mon.warn(format!(
+                            "Invalid grace period `{content}` for group `{key}`; defaulting to \
+                             one month"
+                        ));
+                        GracePeriod::default()
+                    })
+                })
+                .min_by_key(|period| today - *period)
         };
 
         let mut direct_members_owned = groups::members::get_direct_members(
@@ -384,6 +516,31 @@ async fn sync_to_directory(
         )
         .await?;
 
+        let sync_managers = groups::tags::is_tagged_with(
+            &group.id,
+            &group.domain,
+            "gworkspace",
+            "sync-managers",
+            &db,
+        )
+        .await?;
+
+        // only the group's own (current, not just in grace period) managers,
+        // and never any embedded members
+        let mut managers: Vec<String> = if sync_managers {
+            let today = clock::today();
+
+            direct_members_owned
+                .iter()
+                .filter(|member| member.manager && member.until >= today)
+                .map(|member| member.username.clone())
+                .collect()
+        } else {
+            vec![]
+        };
+        managers.sort_unstable();
+        managers.dedup();
+
         let embeddings: Vec<String> = sqlx::query_scalar(
             "SELECT LOWER(content)
                     FROM all_tag_assignments
@@ -400,19 +557,116 @@ async fn sync_to_directory(
 
         for embedding in embeddings {
             if let Some((id, domain)) = embedding.split_once('@') {
-                let embedded = groups::members::get_all_members(id, domain, &db, None).await?;
+                let embedded =
+                    groups::members::get_all_members(id, domain, None, &db, None).await?;
 
                 direct_members_owned.extend(embedded)
             }
         }
 
+        let extra_members: Vec<String> = sqlx::query_scalar(
+            "SELECT LOWER(content)
+            FROM all_tag_assignments
+            WHERE system_id = 'gworkspace'
+                AND tag_id = 'extra-member'
+                AND group_id = $1
+                AND group_domain = $2
+                AND content LIKE '%@%.%'",
+        )
+        .bind(&group.id)
+        .bind(&group.domain)
+        .fetch_all(&db)
+        .await?;
+
+        let mut usernames: Vec<_> = direct_members_owned
+            .iter()
+            .map(|member| member.username.as_str())
+            .collect();
+        usernames.sort_unstable();
+        usernames.dedup();
+
+        // (only used if members have no Workspace account, which is what
+        // `get_user_email` checks first, but they would still be pushed)
+        let personal_emails: Vec<(String, String)> = if allow_external {
+            sqlx::query_as(
+                "SELECT username, content
+                FROM tag_assignments
+                WHERE system_id = 'gworkspace'
+                    AND tag_id = 'personal-email'
+                    AND username = ANY($1)
+                    AND content LIKE '%@%.%'
+                    AND verified
+                ORDER BY username, id",
+            )
+            .bind(&usernames)
+            .fetch_all(&db)
+            .await?
+        } else {
+            vec![]
+        };
+
+        // (only validated when applied, in `sync_group_settings`)
+        let mut setting_overrides: Vec<String> = sqlx::query_scalar(
+            "SELECT content
+            FROM all_tag_assignments
+            WHERE system_id = 'gworkspace'
+                AND tag_id = 'setting'
+                AND group_id = $1
+                AND group_domain = $2
+                AND content IS NOT NULL",
+        )
+        .bind(&group.id)
+        .bind(&group.domain)
+        .fetch_all(&db)
+        .await?;
+        setting_overrides.sort_unstable();
+        setting_overrides.dedup();
+
+        let mut subgroups = subgroup_emails.clone();
+        subgroups.sort_unstable();
+
+        let mut extra_member_emails: Vec<_> = extra_members.iter().map(String::as_str).collect();
+        extra_member_emails.sort_unstable();
+
+        let hash = super::state_hash(&GroupState {
+            mode,
+            user_domains: &user_domains,
+            group,
+            allow_external,
+            subgroups,
+            usernames,
+            extra_members: extra_member_emails,
+            personal_emails,
+            managers: &managers,
+            setting_overrides: &setting_overrides,
+        });
+
+        if exists && super::is_state_unchanged("gworkspace", &key, &hash, &db).await? {
+            mon.info(format!(
+                "Group `{key}` is unchanged since it was last synchronized; skipping"
+            ));
+            n_skipped += 1;
+
+            continue;
+        }
+
+        let n_errors = mon.n_errors();
+
+        if !exists {
+            // this group wasn't in the listing, so we need to create it
+            create_group(&key, group, &client, mode, mon).await?;
+        }
+
+        sync_group_settings(&key, group, &setting_overrides, &client, mode, mon).await?;
+
         let mut direct_members = HashSet::new();
+        let mut manager_emails = HashSet::new();
 
         for member in direct_members_owned {
             let with_email = get_user_email(
                 &member.username,
-                primary_domain,
-                &alternative_domains,
+                &user_domains,
+                &mut workspace_emails,
                 allow_external,
                 &client,
                 &db,
@@ -421,6 +675,10 @@ async fn sync_to_directory(
             .await?;
 
             if let Some(with_email) = with_email {
+                if managers.binary_search(&member.username).is_ok() {
+                    manager_emails.insert(with_email.email.clone());
+                }
+
                 direct_members.insert(with_email);
             } else {
                 mon.warn(format!(
@@ -430,29 +688,33 @@ async fn sync_to_directory(
             }
         }
 
-        let extra_members: Vec<UserWithEmail> = sqlx::query_scalar(
-            "SELECT LOWER(content)
-            FROM all_tag_assignments
-            WHERE system_id = 'gworkspace'
-                AND tag_id = 'extra-member'
-                AND group_id = $1
-                AND group_domain = $2
-                AND content LIKE '%@%.%'",
+        direct_members.extend(
+            extra_members
+                .into_iter()
+                .filter_map(UserWithEmail::new_extra),
+        );
+
+        sync_group_members(
+            &key,
+            &subgroup_emails,
+            &direct_members,
+            &manager_emails,
+            &client,
+            mode,
+            mon,
         )
-        .bind(&group.id)
-        .bind(&group.domain)
-        .fetch_all(&db)
-        .await?
-        .into_iter()
-        .filter_map(UserWithEmail::new_extra)
-        .collect();
-
-        direct_members.extend(extra_members);
+        .await?;
 
-        sync_group_members(&key, &subgroup_emails, &direct_members, &client, mode, mon).await?;
+        // anything less than a complete push must be retried next time
+        if mode.should_update() && mon.n_errors() == n_errors {
+            super::record_state_hash("gworkspace", &key, &hash, &db).await?;
+        }
     }
 
-    mon.info(format!("Synchronized {} groups!", groups.len()));
+    mon.info(format!(
+        "Synchronized {} groups! ({n_skipped} were unchanged)",
+        groups.len() - n_skipped
+    ));
 
     mon.succeeded();
 
@@ -492,6 +754,7 @@ async fn create_group(
 async fn sync_group_settings(
     key: &str,
     group: &models::Group,
+    overrides: &[String],
     client: &DirectoryApiClient,
     mode: Mode,
     mon: &mut super::TaskRunMonitor,
@@ -509,7 +772,7 @@ async fn sync_group_settings(
     let mut alt_description = group.description_en.clone();
     alt_description.truncate(4096);
 
-    let target = google::GroupSettings {
+    let mut target = google::GroupSettings {
         name: group.name_sv.clone(),
         description: truncated_description,
         who_can_view_group: google::GroupVisibility::AllMembersCanView,
@@ -532,6 +795,14 @@ async fn sync_group_settings(
         default_sender: google::GroupDefaultSender::DefaultSelf,
     };
 
+    for setting in overrides {
+        if let Err(reason) = override_group_setting(&mut target, setting) {
+            mon.warn(format!(
+                "Ignoring invalid setting override `{setting}` for group `{key}` ({reason})"
+            ));
+        }
+    }
+
     let Some(patch) =
         google::GroupSettingsPatch::new(&current, &target, &group.name_en, &alt_description)
     else {
@@ -552,6 +823,7 @@ async fn sync_group_members(
     key: &str,
     subgroup_emails: &[&str],
     direct_members: &HashSet<UserWithEmail>,
+    manager_emails: &HashSet<String>, // (if empty, everyone is just a member)
     client: &DirectoryApiClient,
     mode: Mode,
     mon: &mut super::TaskRunMonitor,
@@ -560,6 +832,10 @@ async fn sync_group_members(
 
     let mut current = fallible!(mon, client.list_group_members(key).await);
 
+    // only changes that should actually be made given the mode (but all are
+    // reported either way)
+    let mut changes = vec![];
+
     for entry in &mut current {
         entry.email = entry.email.to_lowercase();
 
@@ -575,7 +851,7 @@ async fn sync_group_members(
             ));
 
             if mode.should_delete() {
-                fallible!(mon, client.remove_group_member(key, &entry.email).await);
+                changes.push(MemberChange::Remove(entry.email.clone()));
             }
         }
     }
@@ -590,77 +866,185 @@ async fn sync_group_members(
             mon.info(format!("Adding subgroup `{subgroup}` to group `{key}`"));
 
             if mode.should_insert() {
-                let member = google::GroupMember {
+                changes.push(MemberChange::Add(google::GroupMember {
                     email: subgroup.to_string(),
                     role: google::GroupMemberRole::Member,
                     r#type: google::GroupMemberType::Group,
                     delivery_settings: Some(google::GroupMemberDeliverySettings::AllMail),
-                };
-
-                fallible!(mon, client.add_group_member(key, &member).await);
+                }));
             }
         }
     }
 
     for direct_member in direct_members {
         let username = direct_member.username.as_str();
+        let manager = manager_emails.contains(&direct_member.email);
 
         if let Some(existing_member) = current.iter().find(|m| m.email == direct_member.email) {
-            if existing_member.role != google::GroupMemberRole::Member {
-                mon.info(format!("Demoting `{username}` to MEMBER in group `{key}`"));
-
-                if mode.should_update() {
-                    let patch = google::GroupMemberPatch {
-                        role: google::GroupMemberRole::Member,
-                    };
-
-                    fallible!(
-                        mon,
-                        client
-                            .patch_group_member(key, &direct_member.email, &patch)
-                            .await
-                    );
+            match existing_member.role {
+                google::GroupMemberRole::Manager if manager => {}
+                google::GroupMemberRole::Member if !manager => {}
+                _ if manager => {
+                    mon.info(format!(
+                        "Promoting `{username}` to MANAGER in group `{key}`"
+                    ));
+
+                    if mode.should_update() {
+                        changes.push(MemberChange::Promote(direct_member.email.clone()));
+                    }
+                }
+                _ => {
+                    mon.info(format!("Demoting `{username}` to MEMBER in group `{key}`"));
+
+                    if mode.should_update() {
+                        changes.push(MemberChange::Demote(direct_member.email.clone()));
+                    }
                 }
             }
         } else {
             mon.info(format!("Adding member `{username}` to group `{key}`"));
 
             if mode.should_insert() {
-                let member = google::GroupMember {
+                changes.push(MemberChange::Add(google::GroupMember {
                     email: direct_member.email.clone(),
-                    role: google::GroupMemberRole::Member,
+                    role: if manager {
+                        google::GroupMemberRole::Manager
+                    } else {
+                        google::GroupMemberRole::Member
+                    },
                     r#type: google::GroupMemberType::User,
                     delivery_settings: Some(google::GroupMemberDeliverySettings::AllMail),
-                };
-
-                fallible!(mon, client.add_group_member(key, &member).await);
+                }));
             }
         }
     }
 
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let total = changes.len();
+
+    // one request per member is unavoidable, but they needn't wait for each
+    // other; a failure only affects its own member, so the rest still go ahead
+    let outcomes: Vec<_> = stream::iter(changes)
+        .map(|change| async move {
+            let result = change.apply(key, client).await;
+
+            (change, result)
+        })
+        .buffer_unordered(MEMBER_CHANGE_CONCURRENCY)
+        .collect()
+        .await;
+
+    let mut failed = 0;
+    for (change, result) in outcomes {
+        if let Err(e) = result {
+            failed += 1;
+
+            mon.error(format!(
+                "Failed to {} `{}` in group `{key}`: {e}",
+                change.verb(),
+                change.email()
+            ));
+        }
+    }
+
+    if failed == 0 {
+        mon.info(format!(
+            "Applied all {total} member changes to group `{key}`"
+        ));
+    } else {
+        mon.warn(format!(
+            "Applied {} of {total} member changes to group `{key}` ({failed} failed)",
+            total - failed
+        ));
+    }
+
     Ok(())
 }
 
+enum MemberChange {
+    Add(google::GroupMember),
+    Remove(String),
+    Promote(String), // to manager
+    Demote(String),  // to (plain) member
+}
+
+impl MemberChange {
+    fn email(&self) -> &str {
+        match self {
+            Self::Add(member) => &member.email,
+            Self::Remove(email) | Self::Promote(email) | Self::Demote(email) => email,
+        }
+    }
+
+    fn verb(&self) -> &'static str {
+        match self {
+            Self::Add(..) => "add",
+            Self::Remove(..) => "remove",
+            Self::Promote(..) => "promote",
+            Self::Demote(..) => "demote",
+        }
+    }
+
+    async fn apply(&self, key: &str, client: &DirectoryApiClient) -> Result<(), &'static str> {
+        match self {
+            Self::Add(member) => client.add_group_member(key, member).await.map(|_| ()),
+            Self::Remove(email) => client.remove_group_member(key, email).await.map(|_| ()),
+            Self::Promote(email) | Self::Demote(email) => {
+                let role = match self {
+                    Self::Promote(..) => google::GroupMemberRole::Manager,
+                    _ => google::GroupMemberRole::Member,
+                };
+                let patch = google::GroupMemberPatch { role };
+
+                client
+                    .patch_group_member(key, email, &patch)
+                    .await
+                    .map(|_| ())
+            }
+        }
+    }
+}
+
 async fn get_user_email(
     username: &str,
-    primary_domain: &str,
-    alternative_domains: &[&str],
+    user_domains: &[&str],
+    workspace_emails: &mut HashMap<String, Option<String>>,
     allow_external: bool,
     client: &DirectoryApiClient,
     db: &PgPool,
     mon: &mut super::TaskRunMonitor,
 ) -> AppResult<Option<UserWithEmail>> {
-    // look for a user account in each domain, in order
-    for domain in iter::once(&primary_domain).chain(alternative_domains) {
-        let lookup = format!("{username}@{domain}");
-
-        if let Some(user) = fallible!(mon, client.get_user(&lookup).await, None) {
-            // user exists in domain!
-            return Ok(Some(UserWithEmail {
-                username: username.to_owned(),
-                email: user.primary_email.to_lowercase(),
-            }));
+    let workspace_email = match workspace_emails.get(username) {
+        Some(cached) => cached.clone(),
+        None => {
+            let mut found = None;
+
+            // look for a user account in each domain, in order
+            for domain in user_domains {
+                let lookup = format!("{username}@{domain}");
+
+                // (failures aren't cached, so that they're retried elsewhere)
+                if let Some(user) = fallible!(mon, client.get_user(&lookup).await, None) {
+                    // user exists in domain!
+                    found = Some(user.primary_email.to_lowercase());
+                    break;
+                }
+            }
+
+            workspace_emails.insert(username.to_owned(), found.clone());
+
+            found
         }
+    };
+
+    if let Some(email) = workspace_email {
+        return Ok(Some(UserWithEmail {
+            username: username.to_owned(),
+            email,
+        }));
     }
 
     // nothing was found in any domain
@@ -673,13 +1057,16 @@ async fn get_user_email(
         return Ok(None);
     }
 
+    // only direct assignments have content anyway; unverified addresses might
+    // contain typos, so they are ignored until confirmed by the user
     let personal: Option<String> = sqlx::query_scalar(
         "SELECT content
-        FROM all_tag_assignments
+        FROM tag_assignments
         WHERE system_id = 'gworkspace'
             AND tag_id = 'personal-email'
             AND username = $1
             AND content LIKE '%@%.%'
+            AND verified
         ORDER BY id
         LIMIT 1",
     )
@@ -701,6 +1088,71 @@ async fn get_user_email(
     }
 }
 
+// everything in Hive that determines what a mirrored group should look like
+// (but not whether members have Workspace accounts, which hardly ever changes
+// and is left to the state hashes' expiry)
+// `setting` is `key=VALUE`, where the key is the field's name and the value is
+// whatever the Group Settings API itself would accept for it
+fn override_group_setting(target: &mut google::GroupSettings, setting: &str) -> Result<(), String> {
+    fn parse<T: serde::de::DeserializeOwned>(value: &str) -> Result<T, String> {
+        serde_json::from_value(serde_json::Value::String(value.to_owned()))
+            .map_err(|_| format!("unsupported value `{value}`"))
+    }
+
+    let Some((field, value)) = setting.split_once('=') else {
+        return Err("expected `key=VALUE`".to_owned());
+    };
+
+    let field = field.trim().to_lowercase().replace('-', "_");
+    let value = value.trim();
+    let boolean = || parse::<google::PoorMansBoolean>(&value.to_lowercase());
+
+    // name and description always come from Hive
+    match field.as_str() {
+        "who_can_view_group" => target.who_can_view_group = parse(value)?,
+        "who_can_view_membership" => target.who_can_view_membership = parse(value)?,
+        "who_can_discover_group" => target.who_can_discover_group = parse(value)?,
+        "who_can_join" => target.who_can_join = parse(value)?,
+        "who_can_leave_group" => target.who_can_leave_group = parse(value)?,
+        "who_can_contact_owner" => target.who_can_contact_owner = parse(value)?,
+        "who_can_post" | "who_can_post_message" => target.who_can_post_message = parse(value)?,
+        "who_can_moderate_members" => target.who_can_moderate_members = parse(value)?,
+        "who_can_moderate_content" => target.who_can_moderate_content = parse(value)?,
+        "who_can_assist_content" => target.who_can_assist_content = parse(value)?,
+        "allow_web_posting" => target.allow_web_posting = boolean()?,
+        "allow_external_members" => {
+            target.allow_external_members = boolean()?;
+        }
+        "is_archived" => target.is_archived = boolean()?,
+        "members_can_post_as_the_group" => {
+            target.members_can_post_as_the_group = boolean()?;
+        }
+        "enable_collaborative_inbox" => {
+            target.enable_collaborative_inbox = boolean()?;
+        }
+        "message_moderation_level" => target.message_moderation_level = parse(value)?,
+        "spam_moderation_level" => target.spam_moderation_level = parse(value)?,
+        "default_sender" => target.default_sender = parse(value)?,
+        _ => return Err(format!("unknown or unsupported setting `{field}`")),
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct GroupState<'a> {
+    mode: Mode, // e.g., a no-deletion push is incomplete for a full one
+    user_domains: &'a [&'a str],
+    group: &'a models::Group,
+    allow_external: bool,
+    subgroups: Vec<&'a str>,
+    usernames: Vec<&'a str>,
+    extra_members: Vec<&'a str>,
+    personal_emails: Vec<(String, String)>,
+    managers: &'a [String],
+    setting_overrides: &'a [String],
+}
+
 #[derive(Hash, PartialEq, Eq)]
 struct UserWithEmail {
     username: String,
@@ -719,3 +1171,64 @@ impl UserWithEmail {
         }
     }
 }
+
+// how long past their end date former members should be kept, as specified in
+// `grace-period` tag contents; both ISO 8601-like (`P2M`, `P1M15D`) and short
+// (`45d`, `2w`) notations are accepted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct GracePeriod {
+    months: u32,
+    days: u64,
+}
+
+impl Default for GracePeriod {
+    fn default() -> Self {
+        // 2025-03-01 becomes 2025-02-01, etc.
+        Self { months: 1, days: 0 }
+    }
+}
+
+impl std::str::FromStr for GracePeriod {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_prefix(['P', 'p']).unwrap_or(s);
+
+        if s.is_empty() {
+            return Err(());
+        }
+
+        let mut period = Self { months: 0, days: 0 };
+        let mut rest = s;
+
+        while !rest.is_empty() {
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or(())?;
+            let n: u32 = rest[..digits].parse().map_err(|_| ())?;
+
+            let mut chars = rest[digits..].chars();
+            match chars.next().ok_or(())?.to_ascii_lowercase() {
+                'y' => period.months = period.months.saturating_add(n.saturating_mul(12)),
+                'm' => period.months = period.months.saturating_add(n),
+                'w' => period.days = period.days.saturating_add(u64::from(n) * 7),
+                'd' => period.days = period.days.saturating_add(u64::from(n)),
+                _ => return Err(()),
+            }
+
+            rest = chars.as_str();
+        }
+
+        Ok(period)
+    }
+}
+
+impl std::ops::Sub<GracePeriod> for chrono::NaiveDate {
+    type Output = Self;
+
+    fn sub(self, period: GracePeriod) -> Self {
+        // absurdly long periods just mean that nobody is ever removed
+        self.checked_sub_months(chrono::Months::new(period.months))
+            .and_then(|date| date.checked_sub_days(chrono::Days::new(period.days)))
+            .unwrap_or(Self::MIN)
+    }
+}
@@ -289,6 +289,20 @@ impl DirectoryApiClient {
         .await
     }
 
+    pub async fn patch_group(
+        &self,
+        key: &str,
+        patch: &GroupPatch<'_>,
+    ) -> Result<Option<SimpleGroup>, &'static str> {
+        self.exec_request(
+            reqwest::Method::PATCH,
+            &format!("https://admin.googleapis.com/admin/directory/v1/groups/{key}"),
+            Some(patch),
+            "Failed to patch group",
+        )
+        .await
+    }
+
     pub async fn list_group_members(&self, key: &str) -> Result<Vec<GroupMember>, &'static str> {
         let params = HashMap::from([("includeDerivedMembership", "false".to_owned())]);
 
@@ -411,6 +425,8 @@ pub struct NewGroup {
 
 #[derive(Debug, Serialize)]
 pub struct GroupPatch<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<&'a str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<&'a str>,
     #[serde(skip_serializing_if = "Option::is_none")]
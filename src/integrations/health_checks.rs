@@ -0,0 +1,449 @@
+use std::{sync::LazyLock, time::Duration};
+
+use sqlx::PgPool;
+
+use crate::{
+    clock,
+    errors::AppResult,
+    mailer::Mailer,
+    notifications::{self, Notification, NotificationEvent},
+    services::groups,
+};
+
+// (must be consts to be promoted to 'static within the manifest below)
+const CONSISTENCY_CHECK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const ROOT_EXPIRY_WARNING_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+// can't use const because it wouldn't support async fn pointers for tasks
+pub static MANIFEST: LazyLock<super::Manifest> = LazyLock::new(|| super::Manifest {
+    id: "health-checks",
+    description: "Periodic checks for anomalies and inconsistencies in Hive's data",
+    settings: &[
+        super::Setting {
+            id: "report-recipients",
+            secret: false,
+            name: "Report Recipients",
+            description: "Comma-separated list of email addresses to notify about anomalies \
+                          (optional)",
+            r#type: super::SettingType::ShortText,
+        },
+        super::Setting {
+            id: "admin-email-domain",
+            secret: false,
+            name: "Administrator Email Domain",
+            description: "Domain appended to usernames to email administrators directly \
+                          when Hive is about to lose all of them, e.g., kth.se (optional)",
+            r#type: super::SettingType::ShortText,
+        },
+    ],
+    tags: &[],
+    tasks: &[
+        super::Task {
+            id: "consistency-check",
+            schedule: "0 0 3 * * *", // every night at 03:00
+            timeout: CONSISTENCY_CHECK_TIMEOUT,
+            func: |mon, settings, db| Box::pin(check_consistency(mon, settings, db)),
+        },
+        super::Task {
+            id: "root-expiry-warning",
+            schedule: "0 0 9 * * *", // every day at 09:00
+            timeout: ROOT_EXPIRY_WARNING_TIMEOUT,
+            func: |mon, settings, db| Box::pin(warn_root_expiry(mon, settings, db)),
+        },
+    ],
+});
+
+async fn check_consistency(
+    mon: &mut super::TaskRunMonitor,
+    settings: super::SettingsValues,
+    db: PgPool,
+) -> AppResult<()> {
+    let mut anomalies = Vec::new();
+
+    check_membership_bounds(&mut anomalies, &db).await?;
+    check_orphaned_assignments(&mut anomalies, &db).await?;
+    check_permission_scopes(&mut anomalies, &db).await?;
+    check_managerless_groups(&mut anomalies, &db).await?;
+    check_root_members(&mut anomalies, &db).await?;
+    check_cycles(&mut anomalies, &db).await?;
+
+    for anomaly in &anomalies {
+        mon.warn(anomaly);
+    }
+
+    if anomalies.is_empty() {
+        mon.info("No anomalies found, everything looks consistent!");
+    } else {
+        mon.info(format!("Found {} anomalies", anomalies.len()));
+
+        send_report(mon, &settings, &anomalies).await;
+    }
+
+    mon.succeeded();
+
+    Ok(())
+}
+
+async fn check_membership_bounds(anomalies: &mut Vec<String>, db: &PgPool) -> AppResult<()> {
+    // should be impossible due to a CHECK constraint, but that's the point
+    let invalid: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT username, group_id, group_domain
+        FROM direct_memberships
+        WHERE until < \"from\"",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for (username, group_id, group_domain) in invalid {
+        anomalies.push(format!(
+            "Membership of `{username}` in `{group_id}@{group_domain}` ends before it starts"
+        ));
+    }
+
+    Ok(())
+}
+
+async fn check_orphaned_assignments(anomalies: &mut Vec<String>, db: &PgPool) -> AppResult<()> {
+    // foreign keys should cascade deletions, but better safe than sorry
+    let orphaned_tags: Vec<(String, String)> = sqlx::query_as(
+        "SELECT ta.system_id, ta.tag_id
+        FROM tag_assignments ta
+        WHERE NOT EXISTS (
+                SELECT 1
+                FROM tags t
+                WHERE t.system_id = ta.system_id
+                    AND t.tag_id = ta.tag_id
+            )
+            OR (
+                ta.group_id IS NOT NULL
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM groups g
+                    WHERE g.id = ta.group_id
+                        AND g.domain = ta.group_domain
+                )
+            )",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for (system_id, tag_id) in orphaned_tags {
+        anomalies.push(format!(
+            "Assignment of tag `#{system_id}:{tag_id}` points to a deleted entity"
+        ));
+    }
+
+    let orphaned_perms: Vec<(String, String)> = sqlx::query_as(
+        "SELECT pa.system_id, pa.perm_id
+        FROM permission_assignments pa
+        WHERE NOT EXISTS (
+                SELECT 1
+                FROM permissions p
+                WHERE p.system_id = pa.system_id
+                    AND p.perm_id = pa.perm_id
+            )
+            OR (
+                pa.group_id IS NOT NULL
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM groups g
+                    WHERE g.id = pa.group_id
+                        AND g.domain = pa.group_domain
+                )
+            )
+            OR (
+                pa.api_token_id IS NOT NULL
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM api_tokens at
+                    WHERE at.id = pa.api_token_id
+                )
+            )",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for (system_id, perm_id) in orphaned_perms {
+        anomalies.push(format!(
+            "Assignment of permission `${system_id}:{perm_id}` points to a deleted entity"
+        ));
+    }
+
+    Ok(())
+}
+
+async fn check_permission_scopes(anomalies: &mut Vec<String>, db: &PgPool) -> AppResult<()> {
+    // e.g., if a permission's `has_scope` was changed after being assigned
+    let mismatched: Vec<(String, String, bool)> = sqlx::query_as(
+        "SELECT DISTINCT pa.system_id, pa.perm_id, p.has_scope
+        FROM permission_assignments pa
+        JOIN permissions p
+            ON p.system_id = pa.system_id
+            AND p.perm_id = pa.perm_id
+        WHERE p.has_scope <> (pa.scope IS NOT NULL)",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for (system_id, perm_id, has_scope) in mismatched {
+        if has_scope {
+            anomalies.push(format!(
+                "Permission `${system_id}:{perm_id}` is scoped, but assigned without a scope"
+            ));
+        } else {
+            anomalies.push(format!(
+                "Permission `${system_id}:{perm_id}` is not scoped, but assigned with a scope"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+async fn check_managerless_groups(anomalies: &mut Vec<String>, db: &PgPool) -> AppResult<()> {
+    let today = clock::today();
+
+    let managerless: Vec<(String, String)> = sqlx::query_as(
+        "SELECT g.id, g.domain
+        FROM groups g
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM all_members_of(g.id, g.domain, $1) am
+            WHERE am.manager
+        )
+        ORDER BY g.domain, g.id",
+    )
+    .bind(today)
+    .fetch_all(db)
+    .await?;
+
+    for (id, domain) in managerless {
+        anomalies.push(format!("Group `{id}@{domain}` has no managers"));
+    }
+
+    Ok(())
+}
+
+async fn check_root_members(anomalies: &mut Vec<String>, db: &PgPool) -> AppResult<()> {
+    if let Some(expiry) = groups::members::get_root_expiry(db).await? {
+        anomalies.push(format!(
+            "Group `{}@{}` will have no members left after {expiry}",
+            crate::HIVE_ROOT_GROUP_ID,
+            crate::HIVE_INTERNAL_DOMAIN
+        ));
+    }
+
+    Ok(())
+}
+
+async fn check_cycles(anomalies: &mut Vec<String>, db: &PgPool) -> AppResult<()> {
+    // recursive functions stop at cycles, so they can't cause infinite loops,
+    // but they should still never exist
+    let group_cycles: Vec<(String, String)> = sqlx::query_as(
+        "SELECT DISTINCT sg.parent_id, sg.parent_domain
+        FROM subgroups sg
+        WHERE EXISTS (
+            SELECT 1
+            FROM all_subgroups_of(sg.parent_id, sg.parent_domain) asg
+            WHERE asg.child_id = sg.parent_id
+                AND asg.child_domain = sg.parent_domain
+        )",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for (id, domain) in group_cycles {
+        anomalies.push(format!(
+            "Group `{id}@{domain}` is (indirectly) its own subgroup"
+        ));
+    }
+
+    let tag_cycles: Vec<(String, String)> = sqlx::query_as(
+        "SELECT DISTINCT st.parent_system_id, st.parent_id
+        FROM subtags st
+        JOIN tag_ancestry ta
+            ON ta.descendant_id = st.parent_id
+            AND ta.descendant_system_id = st.parent_system_id
+            AND ta.ancestor_id = st.child_id
+            AND ta.ancestor_system_id = st.child_system_id",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for (system_id, tag_id) in tag_cycles {
+        anomalies.push(format!(
+            "Tag `#{system_id}:{tag_id}` is (indirectly) its own subtag"
+        ));
+    }
+
+    Ok(())
+}
+
+async fn warn_root_expiry(
+    mon: &mut super::TaskRunMonitor,
+    settings: super::SettingsValues,
+    db: PgPool,
+) -> AppResult<()> {
+    // the nightly report also mentions this, but it can easily get lost among
+    // other anomalies, and its recipients might not be administrators at all
+    let Some(expiry) = groups::members::get_root_expiry(&db).await? else {
+        mon.info("Hive will still have administrators within the horizon");
+        mon.succeeded();
+        return Ok(());
+    };
+
+    mon.warn(format!(
+        "Hive will have no administrators left after {expiry}"
+    ));
+
+    let content = format!(
+        "All memberships in `{}@{}` will have expired after {expiry}, at which point nobody \
+         will be able to administrate Hive anymore.\n\nPlease extend at least one membership \
+         (or add a new member) before then.",
+        crate::HIVE_ROOT_GROUP_ID,
+        crate::HIVE_INTERNAL_DOMAIN
+    );
+
+    let notification = Notification {
+        event: NotificationEvent::RootExpiry,
+        subject: "Administrators about to expire".to_owned(),
+        content,
+    };
+
+    notify(mon, &notification).await;
+
+    let admin_email_domain = settings
+        .get("admin-email-domain")
+        .and_then(serde_json::Value::as_str)
+        .filter(|domain| !domain.is_empty());
+
+    let report_recipients: Vec<&str> =
+        super::require_list_setting!(settings, "report-recipients", '@');
+    let mut recipients: Vec<String> = report_recipients
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect();
+
+    if let Some(domain) = admin_email_domain {
+        let admins = groups::members::get_all_members(
+            crate::HIVE_ROOT_GROUP_ID,
+            crate::HIVE_INTERNAL_DOMAIN,
+            None,
+            &db,
+            None,
+        )
+        .await?;
+
+        recipients.extend(
+            admins
+                .into_iter()
+                .map(|admin| format!("{}@{domain}", admin.username)),
+        );
+    }
+
+    if recipients.is_empty() {
+        if !notifications::is_subscribed(NotificationEvent::RootExpiry) {
+            mon.warn(
+                "Nobody to notify; set report recipients, the administrator email domain or a \
+                 notification channel",
+            );
+        }
+        mon.succeeded();
+        return Ok(());
+    }
+
+    let Some(mailer) = require_mailer(mon) else {
+        return Ok(());
+    };
+
+    let recipients: Vec<&str> = recipients.iter().map(String::as_str).collect();
+
+    match mailer
+        .send(
+            &recipients,
+            "[Hive] Administrators about to expire",
+            &notification.content,
+        )
+        .await
+    {
+        Ok(()) => {
+            mon.info(format!("Sent warning to {} recipients", recipients.len()));
+            mon.succeeded();
+        }
+        Err(e) => mon.error(format!("Failed to send warning: {e}")),
+    }
+
+    Ok(())
+}
+
+async fn send_report(
+    mon: &mut super::TaskRunMonitor,
+    settings: &super::SettingsValues,
+    anomalies: &[String],
+) {
+    let content = format!(
+        "The nightly consistency check found the following anomalies:\n\n{}\n\nSee the task run \
+         logs for more details.",
+        anomalies
+            .iter()
+            .map(|anomaly| format!("- {anomaly}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+
+    let notification = Notification {
+        event: NotificationEvent::ConsistencyReport,
+        subject: "Consistency check report".to_owned(),
+        content,
+    };
+
+    notify(mon, &notification).await;
+
+    let recipients: Vec<&str> = super::require_list_setting!(settings, "report-recipients", '@');
+
+    if recipients.is_empty() {
+        // emailing is optional, the run logs are the report
+        return;
+    }
+
+    let Some(mailer) = require_mailer(mon) else {
+        return;
+    };
+
+    match mailer
+        .send(
+            &recipients,
+            "[Hive] Consistency check report",
+            &notification.content,
+        )
+        .await
+    {
+        Ok(()) => mon.info(format!("Sent report to {} recipients", recipients.len())),
+        Err(e) => mon.error(format!("Failed to send report: {e}")),
+    }
+}
+
+// in addition to any recipients configured for the integration itself
+async fn notify(mon: &mut super::TaskRunMonitor, notification: &Notification) {
+    let (sent, failures) = notifications::dispatch(notification).await;
+
+    if sent > 0 {
+        mon.info(format!("Sent notification to {sent} channels"));
+    }
+
+    for (kind, e) in failures {
+        mon.error(format!(
+            "Failed to send notification to {kind} channel: {e}"
+        ));
+    }
+}
+
+fn require_mailer(mon: &mut super::TaskRunMonitor) -> Option<&'static Mailer> {
+    let mailer = super::mailer();
+
+    if mailer.is_none() {
+        mon.error("Emails should be sent, but Hive's mailer is not configured");
+    }
+
+    mailer
+}
@@ -0,0 +1,65 @@
+use log::*;
+use serde::Serialize;
+
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const USER_AGENT: &str = "hive-mailer";
+
+// Sends plain emails through an HTTP mailing service, compatible with
+// Datasektionen's `spam` (POST with a JSON body including an API key)
+pub struct Mailer {
+    endpoint: String,
+    api_key: String,
+    sender: String,
+    client: reqwest::Client,
+}
+
+#[derive(Serialize)]
+struct MailRequest<'a> {
+    key: &'a str,
+    from: &'a str,
+    to: &'a [&'a str],
+    subject: &'a str,
+    content: &'a str,
+}
+
+impl Mailer {
+    pub fn new(endpoint: &str, api_key: &str, sender: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(USER_AGENT)
+            .build()
+            .expect("failed to build mailer reqwest client");
+
+        Self {
+            endpoint: endpoint.to_owned(),
+            api_key: api_key.to_owned(),
+            sender: sender.to_owned(),
+            client,
+        }
+    }
+
+    pub async fn send(&self, to: &[&str], subject: &str, content: &str) -> reqwest::Result<()> {
+        if to.is_empty() {
+            return Ok(());
+        }
+
+        let body = MailRequest {
+            key: &self.api_key,
+            from: &self.sender,
+            to,
+            subject,
+            content,
+        };
+
+        self.client
+            .post(&self.endpoint)
+            .json(&body)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)?;
+
+        debug!("Sent email \"{subject}\" to {} recipients", to.len());
+
+        Ok(())
+    }
+}
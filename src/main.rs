@@ -1,23 +1,35 @@
 use auth::oidc::OidcClient;
 use errors::ErrorPageGenerator;
 use log::*;
-use resolver::IdentityResolver;
-use rocket::fs::FileServer;
-use routing::cors::Cors;
+use resolver::{IdentityResolver, UserEmailDomain};
+use rocket::{Build, Rocket, fs::FileServer};
+use routing::{cors::Cors, maintenance::MaintenanceMode, shutdown::FlushBuffers};
+use services::{
+    ReadReplica,
+    changes::ProtectedDomains,
+    digests::ManagerDigests,
+    groups::{list::PublicDirectory, shares::ShareLinkSigner, suggestions::AutoLinkDomains},
+    public_statistics::PublicStatistics,
+};
 use sqlx::PgPool;
 
 mod api;
 mod auth;
+mod clock;
 mod config;
 mod dto;
 mod errors;
 mod guards;
 mod logging;
+mod mailer;
 mod models;
+mod notifications;
 mod perms;
 mod resolver;
 mod routing;
 mod sanitizers;
+mod secrets;
+mod seed;
 mod services;
 mod web;
 
@@ -27,6 +39,9 @@ mod integrations;
 const HIVE_SYSTEM_ID: &str = "hive";
 const HIVE_ROOT_GROUP_ID: &str = "root";
 const HIVE_INTERNAL_DOMAIN: &str = "hive.internal";
+// audit log actors must be usernames, so changes applied through the API are
+// attributed to this pseudo-user (with the responsible token in the details)
+const HIVE_API_ACTOR: &str = "api";
 
 rust_i18n::i18n!("./locales");
 
@@ -38,12 +53,47 @@ async fn rocket() -> _ {
 
     debug!("{config:?}");
 
-    let db = PgPool::connect(&config.db_url)
+    routing::maintenance::set_active(config.maintenance);
+
+    clock::init(config.timezone.clone());
+    clock::init_time_precision_domains(config.time_precision_domains.clone());
+
+    secrets::init(config.get_vault_config());
+
+    perms::init_external_domains(config.external_domains.clone());
+
+    notifications::init(config.notification_channels.clone(), || config.get_mailer());
+
+    let db_url = secrets::resolve(&config.db_url)
+        .await
+        .expect("Failed to fetch database URL from secrets manager");
+
+    let db = clock::pool_options()
+        .connect(&db_url)
         .await
         .expect("Failed to connect to the database");
 
     debug!("Initialized database connection pool");
 
+    let replica = match &config.db_replica_url {
+        Some(url) => {
+            let url = secrets::resolve(url)
+                .await
+                .expect("Failed to fetch database read replica URL from secrets manager");
+
+            let replica = clock::pool_options()
+                .connect(&url)
+                .await
+                .expect("Failed to connect to the database read replica");
+
+            debug!("Initialized database read replica connection pool");
+
+            Some(replica)
+        }
+        None => None,
+    };
+    let replica = ReadReplica::new(replica, &db);
+
     sqlx::migrate!("./migrations")
         .run(&db)
         .await
@@ -51,6 +101,12 @@ async fn rocket() -> _ {
 
     info!("Database migrations successfully applied");
 
+    if config.seed_dev_data {
+        seed::seed_dev_data(&db)
+            .await
+            .expect("Failed to seed development data");
+    }
+
     info!(
         "Available i18n locales: {:?}",
         rust_i18n::available_locales!()
@@ -60,10 +116,65 @@ async fn rocket() -> _ {
         .await
         .expect("Failed to initialize OIDC");
 
-    let resolver = IdentityResolver::new(config.identity_resolver_endpoint.clone());
+    let resolver = IdentityResolver::new(
+        config.identity_resolver_endpoint.clone(),
+        config.identity_search_endpoint.clone(),
+    );
+
+    if secrets::is_reference(&config.db_url) {
+        rocket::tokio::spawn(secrets::refresh_db_url_periodically(
+            config.db_url.clone(),
+            db.clone(),
+        ));
+    }
+
+    rocket::tokio::spawn(services::deletions::purge_periodically(db.clone()));
+    rocket::tokio::spawn(services::api_tokens::flush_usage_periodically(db.clone()));
+    rocket::tokio::spawn(services::permissions::flush_matches_periodically(
+        db.clone(),
+    ));
+    rocket::tokio::spawn(services::perms_cache::report_periodically());
+    rocket::tokio::spawn(services::groups::members::maintain_closure_periodically(
+        db.clone(),
+    ));
+    rocket::tokio::spawn(services::webhooks::deliver_periodically(db.clone()));
+    rocket::tokio::spawn(services::webhooks::purge_periodically(db.clone()));
+    rocket::tokio::spawn(services::logins::purge_periodically(db.clone()));
+    rocket::tokio::spawn(services::groups::statistics::snapshot_periodically(
+        db.clone(),
+    ));
+
+    if config.manager_digests {
+        let Some(mailer) = config.get_mailer() else {
+            panic!("Fatal error: manager digests are enabled, but the mailer is not configured")
+        };
+
+        rocket::tokio::spawn(services::digests::send_periodically(
+            db.clone(),
+            mailer,
+            UserEmailDomain::new(config.user_email_domain.clone()),
+        ));
+    }
+
+    if config.membership_emails {
+        let Some(mailer) = config.get_mailer() else {
+            panic!("Fatal error: membership emails are enabled, but the mailer is not configured")
+        };
+
+        services::membership_emails::enable();
+
+        rocket::tokio::spawn(services::membership_emails::send_periodically(
+            db.clone(),
+            mailer,
+            UserEmailDomain::new(config.user_email_domain.clone()),
+        ));
+    }
 
     #[cfg(feature = "integrations")]
     {
+        integrations::init_task_timeouts(config.integration_task_timeouts.clone());
+        integrations::init_mailer(config.get_mailer());
+
         let db = db.clone(); // cloning is cheap (Arc)
 
         rocket::tokio::spawn(async move {
@@ -73,12 +184,35 @@ async fn rocket() -> _ {
         });
     }
 
+    build(&config, db, replica, oidc_client, resolver)
+}
+
+// everything that is served, given already initialized state (i.e., without
+// any side effects, so that tests can build it against their own database)
+fn build(
+    config: &config::Config,
+    db: PgPool,
+    replica: ReadReplica,
+    oidc_client: OidcClient,
+    resolver: Option<IdentityResolver>,
+) -> Rocket<Build> {
     rocket::custom(config.get_rocket_config())
         .manage(db)
+        .manage(replica)
         .manage(oidc_client)
         .manage(resolver)
+        .manage(UserEmailDomain::new(config.user_email_domain.clone()))
+        .manage(ProtectedDomains::new(config.protected_domains.clone()))
+        .manage(AutoLinkDomains::new(config.auto_link_domains.clone()))
+        .manage(PublicDirectory::new(config.public_directory))
+        .manage(PublicStatistics::new(config.public_statistics.clone()))
+        .manage(ManagerDigests::new(config.manager_digests))
+        .manage(ShareLinkSigner::new(&config.secret_key))
+        .manage(config.get_mailer())
         .attach(ErrorPageGenerator)
         .attach(Cors)
+        .attach(MaintenanceMode)
+        .attach(FlushBuffers)
         .mount("/", &web::tree())
         .mount("/api/v0", &api::v0::tree())
         .mount("/api/v1", &api::v1::tree())
@@ -1,18 +1,23 @@
 use std::{fmt, hash};
 
-use chrono::{DateTime, Local, NaiveDate};
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, NaiveTime};
 use rocket::{Either, FromFormField, UriDisplayQuery};
-use sqlx::{types::JsonValue, FromRow};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    FromRow,
+    types::{Json, JsonValue},
+};
 use uuid::Uuid;
 
 use crate::{
+    clock,
     errors::AppResult,
     guards::{lang::Language, perms::PermsEvaluator},
-    perms::{HivePermission, SystemsScope},
+    perms::{HivePermission, SystemsScope, TagScope},
 };
 
 // these are only needed in other sqlx::Type composite type records
-#[derive(sqlx::Type, PartialEq, Clone)]
+#[derive(sqlx::Type, PartialEq, Clone, Serialize)]
 #[sqlx(type_name = "slug")]
 pub struct Slug(String);
 
@@ -22,7 +27,7 @@ impl fmt::Display for Slug {
     }
 }
 
-#[derive(sqlx::Type, PartialEq, Clone)]
+#[derive(sqlx::Type, PartialEq, Clone, Serialize)]
 #[sqlx(type_name = "domain")]
 pub struct Domain(String);
 
@@ -32,7 +37,7 @@ impl fmt::Display for Domain {
     }
 }
 
-#[derive(FromRow)]
+#[derive(FromRow, Serialize)]
 pub struct Group {
     pub id: String,
     pub domain: String,
@@ -40,6 +45,11 @@ pub struct Group {
     pub name_en: String,
     pub description_sv: String,
     pub description_en: String,
+    pub member_cap: Option<i32>, // max. distinct direct members at a time
+    pub member_cap_enforced: bool, // otherwise, exceeding the cap only warns
+    pub default_membership_duration: Option<String>, // see `MembershipDuration`
+    pub state: GroupState,
+    pub requires_acceptance: bool, // new members must accept before it counts
 }
 
 impl Group {
@@ -60,9 +70,141 @@ impl Group {
             Language::English => &self.description_en,
         }
     }
+
+    // suggested `until` for a new membership starting on `from`, if any
+    pub fn default_until(&self, from: NaiveDate) -> Option<NaiveDate> {
+        let duration: MembershipDuration =
+            self.default_membership_duration.as_ref()?.parse().ok()?;
+
+        Some(duration.until(from))
+    }
+}
+
+#[derive(sqlx::Type, FromFormField, PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "group_state", rename_all = "snake_case")]
+pub enum GroupState {
+    Proposed, // not yet in use (e.g., waiting for a decision to be made)
+    Active,
+    Dormant,  // not currently in use, but expected to be again
+    Archived, // no longer in use, only kept for the record
+}
+
+impl GroupState {
+    pub const fn key(&self) -> &'static str {
+        match self {
+            Self::Proposed => "proposed",
+            Self::Active => "active",
+            Self::Dormant => "dormant",
+            Self::Archived => "archived",
+        }
+    }
+
+    pub const fn icon(&self) -> &'static str {
+        match self {
+            Self::Proposed => "pending",
+            Self::Active => "check_circle",
+            Self::Dormant => "bedtime",
+            Self::Archived => "inventory_2",
+        }
+    }
+
+    // whether groups in this state are shown in listings by default
+    pub const fn is_listed(&self) -> bool {
+        matches!(self, Self::Proposed | Self::Active)
+    }
+
+    // groups can only be proposed when they are first created
+    pub const fn transitions(&self) -> &'static [Self] {
+        match self {
+            Self::Proposed => &[Self::Active, Self::Archived],
+            Self::Active => &[Self::Dormant, Self::Archived],
+            Self::Dormant => &[Self::Active, Self::Archived],
+            Self::Archived => &[Self::Active, Self::Dormant],
+        }
+    }
+
+    pub fn can_transition_to(&self, other: &Self) -> bool {
+        self.transitions().contains(other)
+    }
+}
+
+impl fmt::Display for GroupState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.key())
+    }
+}
+
+// how long new memberships in a group last by default; either a period in
+// short notation (`6m`, `1y`, `2w`, `45d`, or combined like `1m15d`), or
+// until the end of the (calendar or academic) year, i.e., 31/Dec or 30/Jun
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MembershipDuration {
+    Period { months: u32, days: u32 },
+    EndOfYear,
+    EndOfJune,
+}
+
+impl MembershipDuration {
+    // last day (inclusive) of a membership starting on `from`
+    pub fn until(&self, from: NaiveDate) -> NaiveDate {
+        let (year, month, day) = match self {
+            Self::Period { months, days } => {
+                let end = from
+                    .checked_add_months(Months::new(*months))
+                    .and_then(|end| end.checked_add_days(Days::new(u64::from(*days))))
+                    .unwrap_or(NaiveDate::MAX);
+
+                return end.pred_opt().unwrap_or(end).max(from);
+            }
+            Self::EndOfYear => (from.year(), 12, 31),
+            Self::EndOfJune if from.month() <= 6 => (from.year(), 6, 30),
+            Self::EndOfJune => (from.year() + 1, 6, 30),
+        };
+
+        NaiveDate::from_ymd_opt(year, month, day).unwrap_or(NaiveDate::MAX)
+    }
+}
+
+impl std::str::FromStr for MembershipDuration {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "end-of-year" => return Ok(Self::EndOfYear),
+            "end-of-june" => return Ok(Self::EndOfJune),
+            "" => return Err(()),
+            _ => {}
+        }
+
+        let (mut months, mut days) = (0u32, 0u32);
+        let mut rest = s.trim();
+
+        while !rest.is_empty() {
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or(())?;
+            let n: u32 = rest[..digits].parse().map_err(|_| ())?;
+
+            let mut chars = rest[digits..].chars();
+            match chars.next().ok_or(())?.to_ascii_lowercase() {
+                'y' => months = months.saturating_add(n.saturating_mul(12)),
+                'm' => months = months.saturating_add(n),
+                'w' => days = days.saturating_add(n.saturating_mul(7)),
+                'd' => days = days.saturating_add(n),
+                _ => return Err(()),
+            }
+
+            rest = chars.as_str();
+        }
+
+        if months == 0 && days == 0 {
+            return Err(());
+        }
+
+        Ok(Self::Period { months, days })
+    }
 }
 
-#[derive(sqlx::Type, PartialEq, Clone)]
+#[derive(sqlx::Type, PartialEq, Clone, Serialize)]
 #[sqlx(type_name = "group_ref")]
 pub struct GroupRef {
     pub group_id: Slug,
@@ -71,7 +213,7 @@ pub struct GroupRef {
 
 // for when loading the whole Group isn't needed
 // (e.g., just in an autocomplete listing with name and id@domain)
-#[derive(FromRow, Clone)]
+#[derive(FromRow, Clone, Serialize)]
 pub struct SimpleGroup {
     pub id: String,
     pub domain: String,
@@ -112,15 +254,25 @@ pub trait GroupModel: for<'r> sqlx::FromRow<'r, sqlx::postgres::PgRow> + Send +
 impl GroupModel for Group {}
 impl GroupModel for SimpleGroup {}
 
-#[derive(FromRow, Debug)]
+#[derive(FromRow, Debug, Serialize)]
 pub struct GroupMember {
     #[sqlx(default)]
     pub id: Option<Uuid>, // only exists for direct memberships
     pub username: String,
     pub from: NaiveDate,
     pub until: NaiveDate,
+    #[sqlx(default)]
+    pub from_time: Option<NaiveTime>, // None => start of day
+    #[sqlx(default)]
+    pub until_time: Option<NaiveTime>, // None => end of day
     pub manager: bool,
     #[sqlx(default)]
+    pub contact: bool, // official contact person for the group
+    #[sqlx(default)]
+    pub note: Option<String>, // e.g., why they are a member
+    #[sqlx(default)]
+    pub justification: Option<String>, // for long-term appointments
+    #[sqlx(default)]
     pub display_name: Option<String>, // None if not loaded yet
 }
 
@@ -130,32 +282,386 @@ impl GroupMember {
     }
 }
 
-#[derive(FromRow)]
+// a user carved out of a group they would otherwise inherit via a subgroup
+#[derive(FromRow, Serialize)]
+pub struct MembershipExclusion {
+    pub id: Uuid,
+    pub username: String,
+    #[sqlx(default)]
+    pub display_name: Option<String>, // None if not loaded yet
+}
+
+// a membership added by someone else to a group that requires acceptance;
+// it only becomes a direct membership once the user accepts it
+#[derive(FromRow, Serialize)]
+pub struct PendingMembership {
+    pub id: Uuid,
+    pub username: String,
+    pub group_id: String,
+    pub group_domain: String,
+    pub from: NaiveDate,
+    pub until: NaiveDate,
+    pub from_time: Option<NaiveTime>,
+    pub until_time: Option<NaiveTime>,
+    pub manager: bool,
+    pub note: Option<String>,
+    pub justification: Option<String>,
+    pub operational_year_id: Option<Uuid>,
+    pub added_by: String,
+    pub added_at: DateTime<Local>,
+    #[sqlx(default)]
+    pub display_name: Option<String>, // None if not loaded yet
+}
+
+impl PendingMembership {
+    pub fn group_key(&self) -> String {
+        format!("{}@{}", self.group_id, self.group_domain)
+    }
+}
+
+#[derive(FromRow, Serialize)]
 pub struct Subgroup {
     pub manager: bool,
     #[sqlx(flatten)]
     pub group: SimpleGroup,
 }
 
-#[derive(FromRow)]
+// a direct membership from the user's point of view
+#[derive(FromRow, Serialize)]
+pub struct UserMembership {
+    pub membership_id: Uuid,
+    pub from: NaiveDate,
+    pub until: NaiveDate,
+    pub manager: bool,
+    #[sqlx(flatten)]
+    pub group: SimpleGroup,
+}
+
+// external resource of a group (e.g., drive folder or wiki), shown on its page
+#[derive(FromRow, Serialize)]
+pub struct GroupLink {
+    pub id: Uuid,
+    pub group_id: String,
+    pub group_domain: String,
+    pub name: String,
+    pub url: String,
+    pub position: i32,
+}
+
+// read-only snapshot of a group's members, shared through a signed link
+#[derive(FromRow, Serialize)]
+pub struct MemberListShare {
+    pub id: Uuid,
+    pub group_id: String,
+    pub group_domain: String,
+    pub members: Json<Vec<SharedMember>>,
+    pub created_by: String,
+    pub created_at: DateTime<Local>,
+    pub expires_at: DateTime<Local>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SharedMember {
+    pub username: String,
+    pub display_name: Option<String>,
+    pub manager: bool,
+}
+
+// reusable link for any logged-in user to join a group as a regular member
+#[derive(FromRow, Serialize)]
+pub struct GroupInvitation {
+    pub id: Uuid,
+    pub secret: Uuid, // in the link itself
+    pub group_id: String,
+    pub group_domain: String,
+    pub from: NaiveDate, // or whenever they join, if later
+    pub until: NaiveDate,
+    pub max_uses: Option<i32>, // None => unlimited
+    pub uses: i32,
+    pub expires_at: Option<DateTime<Local>>, // None => until `until` passes
+    pub created_by: String,
+    pub created_at: DateTime<Local>,
+}
+
+impl GroupInvitation {
+    pub fn group_key(&self) -> String {
+        format!("{}@{}", self.group_id, self.group_domain)
+    }
+
+    pub fn link(&self) -> String {
+        format!("/join/{}", self.secret)
+    }
+
+    pub fn is_usable(&self, now: &DateTime<Local>) -> bool {
+        // (`until` is a day in the configured timezone, like memberships')
+        self.until >= clock::today()
+            && self.max_uses.is_none_or(|max| self.uses < max)
+            && self.expires_at.is_none_or(|expires_at| expires_at > *now)
+    }
+}
+
+// internal note on a group, only for those who can manage its members
+#[derive(FromRow, Serialize)]
+pub struct GroupComment {
+    pub id: Uuid,
+    pub group_id: String,
+    pub group_domain: String,
+    pub reply_to: Option<Uuid>, // None => starts a thread
+    pub author: String,
+    pub body: String,
+    pub created_at: DateTime<Local>,
+}
+
+// a top-level comment and its replies (oldest first)
+#[derive(Serialize)]
+pub struct GroupCommentThread {
+    pub comment: GroupComment,
+    pub replies: Vec<GroupComment>,
+}
+
+// a pending handover of a group's managers to a user or a managing subgroup
+#[derive(FromRow, Serialize)]
+pub struct OwnershipTransfer {
+    pub id: Uuid,
+    pub group_id: String,
+    pub group_domain: String,
+    pub to_username: Option<String>,
+    pub to_group_id: Option<String>, // None if to a user
+    pub to_group_domain: Option<String>,
+    pub until: Option<NaiveDate>, // only for users
+    pub proposed_by: String,
+    pub proposed_at: DateTime<Local>,
+}
+
+impl OwnershipTransfer {
+    pub fn group_key(&self) -> String {
+        format!("{}@{}", self.group_id, self.group_domain)
+    }
+
+    // username or group key
+    pub fn recipient(&self) -> String {
+        match (&self.to_username, &self.to_group_id, &self.to_group_domain) {
+            (Some(username), _, _) => username.clone(),
+            (None, Some(id), Some(domain)) => format!("{id}@{domain}"),
+            _ => "?".to_owned(), // prevented by DB constraint
+        }
+    }
+}
+
+#[derive(sqlx::Type, PartialEq, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "pending_change_kind", rename_all = "snake_case")]
+pub enum PendingChangeKind {
+    AddSubgroup,
+    AssignPermission,
+}
+
+// a structural change to a group in a protected domain, awaiting approval
+#[derive(FromRow, Serialize)]
+pub struct PendingChange {
+    pub id: Uuid,
+    pub kind: PendingChangeKind,
+    pub group_id: String, // parent group or assignee
+    pub group_domain: String,
+    pub child_id: Option<String>, // only for new subgroups
+    pub child_domain: Option<String>,
+    pub manager: Option<bool>,
+    pub system_id: Option<String>, // only for permission assignments
+    pub perm_id: Option<String>,
+    pub scope: Option<String>,
+    pub proposed_by: String,
+    pub proposed_at: DateTime<Local>,
+    #[sqlx(default)]
+    pub can_review: Option<bool>, // whether current user can approve/reject
+}
+
+impl PendingChange {
+    pub fn group_key(&self) -> String {
+        format!("{}@{}", self.group_id, self.group_domain)
+    }
+
+    // child group key or permission key
+    pub fn subject(&self) -> String {
+        match self.kind {
+            PendingChangeKind::AddSubgroup => format!(
+                "{}@{}",
+                self.child_id.as_deref().unwrap_or("?"),
+                self.child_domain.as_deref().unwrap_or("?")
+            ),
+            PendingChangeKind::AssignPermission => {
+                let system_id = self.system_id.as_deref().unwrap_or("?");
+                let perm_id = self.perm_id.as_deref().unwrap_or("?");
+
+                if let Some(scope) = &self.scope {
+                    format!("${system_id}:{perm_id}:{scope}")
+                } else {
+                    format!("${system_id}:{perm_id}")
+                }
+            }
+        }
+    }
+}
+
+#[derive(FromRow, Serialize)]
+pub struct CalendarFeed {
+    pub secret: Uuid,
+    pub owner: String,
+    pub group_id: Option<String>, // None for personal feeds
+    pub group_domain: Option<String>,
+}
+
+#[derive(FromRow, Serialize)]
 pub struct System {
     pub id: String,
     pub description: String,
 }
 
-#[derive(FromRow)]
+// first step of deleting a system in active use, awaiting confirmation
+#[derive(FromRow, Serialize)]
+pub struct SystemDeletionRequest {
+    pub system_id: String,
+    pub requested_by: String,
+    pub requested_at: DateTime<Local>,
+}
+
+#[derive(sqlx::Type, FromFormField, PartialEq, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "webhook_event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    #[field(value = "membership_changed")]
+    MembershipChanged,
+    #[field(value = "permission_assigned")]
+    PermissionAssigned,
+    #[field(value = "tag_assigned")]
+    TagAssigned,
+}
+
+impl WebhookEvent {
+    pub const ALL: [Self; 3] = [
+        Self::MembershipChanged,
+        Self::PermissionAssigned,
+        Self::TagAssigned,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::MembershipChanged => "membership_changed",
+            Self::PermissionAssigned => "permission_assigned",
+            Self::TagAssigned => "tag_assigned",
+        }
+    }
+}
+
+#[derive(FromRow, Serialize)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub system_id: String,
+    pub url: String,
+    pub secret: Uuid,
+    pub events: Vec<WebhookEvent>,
+    pub created_by: String,
+    pub created_at: DateTime<Local>,
+    #[sqlx(default)]
+    pub n_recent_failures: i64, // in the past week
+}
+
+#[derive(FromRow, Serialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: WebhookEvent,
+    pub payload: JsonValue,
+    pub created_at: DateTime<Local>,
+    pub attempted_at: Option<DateTime<Local>>,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub replay_of: Option<Uuid>,
+}
+
+impl WebhookDelivery {
+    pub fn is_pending(&self) -> bool {
+        self.attempted_at.is_none()
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.status_code
+            .is_some_and(|code| (200..300).contains(&code))
+    }
+}
+
+#[derive(FromRow, Serialize)]
+pub struct MemberCountSnapshot {
+    pub day: NaiveDate,
+    pub n_direct: i32,
+    pub n_total: i32,
+}
+
+// both bounds inclusive; see `services::operational_years`
+#[derive(FromRow, Serialize, Clone)]
+pub struct OperationalYear {
+    pub id: Uuid,
+    pub name: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl OperationalYear {
+    pub fn contains(&self, from: &NaiveDate, until: &NaiveDate) -> bool {
+        self.start <= *from && *until <= self.end
+    }
+}
+
+#[derive(sqlx::Type, PartialEq, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "login_alert", rename_all = "snake_case")]
+pub enum LoginAlert {
+    RepeatedFailures, // many failed attempts from the same IP address
+    DormantAdmin,     // administrator who hadn't logged in for a long time
+}
+
+#[derive(FromRow, Serialize)]
+pub struct LoginEvent {
+    pub id: Uuid,
+    pub username: Option<String>,
+    pub success: bool,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub error: Option<String>,
+    pub alert: Option<LoginAlert>,
+    pub stamp: DateTime<Local>,
+}
+
+#[derive(FromRow, Serialize)]
 pub struct ApiToken {
     pub id: Uuid,
     pub system_id: String,
     pub description: String,
     pub expires_at: Option<DateTime<Local>>,
     pub last_used_at: Option<DateTime<Local>>,
+    pub restricted_to_groups: bool,
+    pub requires_signing: bool,
+    pub read_only: bool,        // enforced regardless of assigned permissions
+    pub groups_only: bool,      // (idem)
+    pub permissions_only: bool, // (idem)
     #[sqlx(default)]
     #[sqlx(try_from = "i64")]
     pub n_perms: usize, // number of assigned permissions
 }
 
-#[derive(FromRow)]
+#[derive(FromRow, Serialize)]
+pub struct DailyApiUsage {
+    pub day: NaiveDate,
+    pub n_requests: i64,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct SystemUsageStats {
+    pub last_token_usage: Option<DateTime<Local>>,
+    pub n_tagged_groups: i64,
+    pub n_tagged_users: i64,
+}
+
+#[derive(FromRow, Serialize)]
 pub struct Permission {
     pub system_id: String,
     pub perm_id: String,
@@ -169,7 +675,7 @@ impl Permission {
     }
 }
 
-#[derive(FromRow)]
+#[derive(FromRow, Serialize)]
 pub struct PermissionAssignment {
     pub id: Uuid,
     pub system_id: String,
@@ -199,14 +705,64 @@ impl PermissionAssignment {
     }
 }
 
-#[derive(FromRow)]
+#[derive(FromRow, Serialize)]
 pub struct BasePermissionAssignment {
     pub system_id: String,
     pub perm_id: String,
     pub scope: Option<String>,
 }
 
-#[derive(FromRow)]
+// an assignment of some (system, permission) pair that reaches a user through
+// their memberships, as kept in the application-wide permissions cache
+#[derive(FromRow, Clone)]
+pub struct ReachingAssignment {
+    pub id: Uuid,
+    pub scope: Option<String>,
+}
+
+impl ReachingAssignment {
+    // must agree with `perms::queries::scope_matches!`, which is what
+    // `find_user_matches` in services::permissions uses
+    pub fn matches(&self, scope: Option<&str>) -> bool {
+        match (self.scope.as_deref(), scope) {
+            (Some("*"), _) => true,
+            (assigned, requested) if assigned == requested => true,
+            (Some(assigned), Some(requested)) => {
+                // hierarchical prefix wildcard, e.g. committee/*
+                assigned.len() > 2
+                    && assigned.ends_with("/*")
+                    && requested.starts_with(&assigned[..assigned.len() - 1])
+            }
+            _ => false,
+        }
+    }
+}
+
+// what assigning a permission to a group would entail, before actually doing it
+#[derive(FromRow, Serialize)]
+pub struct AssignmentPreview {
+    pub members: i64,        // current (direct or indirect) members of the group
+    pub new_holders: i64,    // members who don't already hold the permission
+    pub outside_domain: i64, // members only reached via groups in other domains
+}
+
+// user who currently holds a permission (with a given scope) through
+// membership in any of the groups it is assigned to
+#[derive(FromRow, Serialize)]
+pub struct PermissionHolder {
+    pub username: String,
+    pub scope: Option<String>,
+    pub groups: Vec<GroupRef>, // to which the permission is assigned
+}
+
+// one way in which a permission check passes (see the simulator)
+#[derive(FromRow, Serialize)]
+pub struct PermissionGrant {
+    pub scope: Option<String>,
+    pub path: Vec<GroupRef>, // assignee first; empty if assigned directly
+}
+
+#[derive(FromRow, Serialize)]
 pub struct AffiliatedPermissionAssignment {
     pub id: Uuid,
     pub system_id: String,
@@ -220,6 +776,8 @@ pub struct AffiliatedPermissionAssignment {
     #[sqlx(default)]
     pub label: Option<String>, // group name or token description
     #[sqlx(default)]
+    pub last_matched_at: Option<DateTime<Local>>,
+    #[sqlx(default)]
     pub can_manage: Option<bool>, // whether current user can e.g. unassign
 }
 
@@ -239,7 +797,47 @@ impl AffiliatedPermissionAssignment {
     }
 }
 
-#[derive(FromRow)]
+// keys of everything touched by applying a system manifest
+#[derive(Serialize, Default)]
+pub struct ManifestChanges {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+// one line of the mapping report produced when importing a legacy dump
+#[derive(Serialize)]
+pub struct ImportedEntry {
+    pub kind: ImportedEntryKind,
+    pub legacy: String, // how the entry was described in the dump
+    pub outcome: ImportOutcome,
+}
+
+#[derive(PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportedEntryKind {
+    Group,
+    Subgroup,
+    Membership,
+    Permission,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportOutcome {
+    Created(String),  // key of the corresponding entity in Hive
+    Existing(String), // (same, but it was already there)
+    Failed(String),   // reason
+}
+
+// for when loading the whole Tag isn't needed (e.g., in subtag hierarchies)
+#[derive(Clone, PartialEq, Serialize)]
+pub struct TagRef {
+    pub system_id: String,
+    pub tag_id: String,
+}
+
+#[derive(FromRow, Serialize)]
 pub struct Tag {
     pub system_id: String,
     pub tag_id: String,
@@ -247,6 +845,7 @@ pub struct Tag {
     pub supports_users: bool,
     pub has_content: bool,
     pub description: String,
+    pub managed: bool, // declared by an integration (see `integrations::Tag`)
     #[sqlx(default)]
     pub can_view: Option<bool>, // whether current user can open tag details
 }
@@ -259,6 +858,10 @@ impl Tag {
     pub async fn set_can_view(&mut self, perms: &PermsEvaluator) -> AppResult<()> {
         let can_view = perms
             .satisfies_any_of(&[
+                HivePermission::AssignTag(TagScope::Id(
+                    self.system_id.clone(),
+                    self.tag_id.clone(),
+                )),
                 HivePermission::AssignTags(SystemsScope::Id(self.system_id.clone())),
                 HivePermission::ManageTags(SystemsScope::Id(self.system_id.clone())),
             ])
@@ -270,14 +873,71 @@ impl Tag {
     }
 }
 
-#[derive(FromRow)]
+// a group's `$hive:assign-tag` assignment for one of a system's tags
+#[derive(FromRow, Serialize)]
+pub struct TagDelegation {
+    pub id: Uuid,
+    pub tag_id: String,
+    pub group_id: String,
+    pub group_domain: String,
+    #[sqlx(default)]
+    pub label: Option<String>, // group name
+}
+
+impl TagDelegation {
+    pub fn group_key(&self) -> String {
+        format!("{}@{}", self.group_id, self.group_domain)
+    }
+}
+
+// every group assigned the permission (with this scope, a wildcard, or any if
+// None) is considered tagged too, but only through `all_tag_assignments`
+#[derive(FromRow, Serialize)]
+pub struct TagDerivation {
+    pub id: Uuid,
+    pub perm_system_id: String,
+    pub perm_id: String,
+    pub scope: Option<String>,
+}
+
+impl TagDerivation {
+    pub fn perm_key(&self) -> String {
+        match &self.scope {
+            Some(scope) => format!("${}:{}:{scope}", self.perm_system_id, self.perm_id),
+            None => format!("${}:{}", self.perm_system_id, self.perm_id),
+        }
+    }
+}
+
+// tag assignment as seen through subtag relations, i.e., mirroring the
+// semantics of `all_tag_assignments` but keeping track of where it came from
+#[derive(FromRow, Serialize)]
+pub struct EffectiveTagAssignment {
+    pub username: Option<String>,
+    pub group_id: Option<String>,
+    pub group_domain: Option<String>,
+    pub content: Option<String>, // only for direct assignments
+    // tag that's actually assigned (itself or any of its descendant subtags)
+    pub via_system_id: String,
+    pub via_tag_id: String,
+    pub direct: bool,
+    pub derived: bool, // from a permission (see `TagDerivation`)
+}
+
+impl EffectiveTagAssignment {
+    pub fn via_key(&self) -> String {
+        format!("#{}:{}", self.via_system_id, self.via_tag_id)
+    }
+}
+
+#[derive(FromRow, Serialize)]
 pub struct TagMorphology {
     pub has_content: bool,
     pub supports_groups: bool,
     pub supports_users: bool,
 }
 
-#[derive(FromRow)]
+#[derive(FromRow, Serialize)]
 pub struct TagAssignment {
     pub id: Uuid,
     pub system_id: String,
@@ -307,7 +967,17 @@ impl TagAssignment {
     }
 }
 
-#[derive(FromRow)]
+// group or user carrying a tag (with some content), however many times
+#[derive(FromRow, Serialize)]
+pub struct TaggedEntity {
+    pub username: Option<String>,
+    pub group_id: Option<String>,
+    pub group_domain: Option<String>,
+    pub content: Option<String>,
+    pub direct: bool, // false if only implied by subtags
+}
+
+#[derive(FromRow, Serialize)]
 pub struct AffiliatedTagAssignment {
     pub id: Option<Uuid>, // None if not a direct assignment
     pub system_id: String,
@@ -340,7 +1010,19 @@ impl AffiliatedTagAssignment {
     }
 }
 
-#[derive(FromRow)]
+#[derive(FromRow, Serialize)]
+pub struct Deletion {
+    pub id: Uuid,
+    pub target_kind: TargetKind,
+    pub target_id: String,
+    pub actor: String,
+    pub deleted_at: DateTime<Local>,
+    #[sqlx(default)]
+    #[sqlx(try_from = "i64")]
+    pub n_rows: usize, // number of archived rows (including cascades)
+}
+
+#[derive(FromRow, Serialize)]
 pub struct AuditLog {
     pub action_kind: ActionKind,
     pub target_kind: TargetKind,
@@ -411,7 +1093,130 @@ fn object_to_change(new: &serde_json::Value, old: &serde_json::Value) -> Option<
     })
 }
 
-#[derive(sqlx::Type, UriDisplayQuery, FromFormField, PartialEq, Clone, Debug)]
+// audit log entry as shown in a group's activity timeline
+#[derive(Serialize)]
+pub struct GroupActivity {
+    pub kind: GroupActivityKind,
+    pub action_kind: ActionKind,
+    pub subject: Option<String>, // e.g. member username or permission key
+    pub actor: String,
+    pub actor_display_name: Option<String>, // None if not resolved
+    pub stamp: DateTime<Local>,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupActivityKind {
+    Details, // incl. renames and transfers
+    Link,
+    Member,
+    Subgroup,
+    Permission,
+    Tag,
+}
+
+impl From<AuditLog> for GroupActivity {
+    fn from(log: AuditLog) -> Self {
+        // creations only have `new`, deletions only have `old`
+        let new = log.details.get("new").filter(|new| !new.is_null());
+        let details = new.or_else(|| log.details.get("old"));
+        let field = |name: &str| {
+            details
+                .and_then(|details| details.get(name))
+                .and_then(JsonValue::as_str)
+        };
+
+        let (kind, subject) = match log.target_kind {
+            TargetKind::Membership if field("member_type") == Some("subgroup") => (
+                GroupActivityKind::Subgroup,
+                field("child_id")
+                    .zip(field("child_domain"))
+                    .map(|(id, domain)| format!("{id}@{domain}")),
+            ),
+            TargetKind::Membership => (
+                GroupActivityKind::Member,
+                field("username").map(str::to_owned),
+            ),
+            TargetKind::PermissionAssignment => (
+                GroupActivityKind::Permission,
+                Some(match field("scope") {
+                    Some(scope) => format!("{}:{scope}", log.target_id),
+                    None => log.target_id.clone(),
+                }),
+            ),
+            TargetKind::TagAssignment => (
+                GroupActivityKind::Tag,
+                Some(match field("content") {
+                    Some(content) => format!("{}:{content}", log.target_id),
+                    None => log.target_id.clone(),
+                }),
+            ),
+            _ => match details.and_then(|details| details.get("link")) {
+                Some(link) => (
+                    GroupActivityKind::Link,
+                    link.get("name")
+                        .and_then(JsonValue::as_str)
+                        .map(str::to_owned),
+                ),
+                None => (GroupActivityKind::Details, None),
+            },
+        };
+
+        // links are added/removed as updates to the group itself
+        let action_kind = match (kind, new.is_some()) {
+            (GroupActivityKind::Link, true) => ActionKind::Create,
+            (GroupActivityKind::Link, false) => ActionKind::Delete,
+            _ => log.action_kind,
+        };
+
+        Self {
+            kind,
+            action_kind,
+            subject,
+            actor: log.actor,
+            actor_display_name: None,
+            stamp: log.stamp,
+        }
+    }
+}
+
+impl GroupActivity {
+    // localization key for the timeline sentence, taking the subject (if any)
+    pub fn phrase_key(&self) -> &'static str {
+        match (self.kind, &self.action_kind) {
+            (GroupActivityKind::Details, ActionKind::Create) => "groups.activity.details.create",
+            (GroupActivityKind::Details, ActionKind::Delete) => "groups.activity.details.delete",
+            (GroupActivityKind::Details, _) => "groups.activity.details.update",
+            (GroupActivityKind::Link, ActionKind::Delete) => "groups.activity.link.delete",
+            (GroupActivityKind::Link, _) => "groups.activity.link.create",
+            (GroupActivityKind::Member, ActionKind::Create) => "groups.activity.member.create",
+            (GroupActivityKind::Member, ActionKind::Delete) => "groups.activity.member.delete",
+            (GroupActivityKind::Member, _) => "groups.activity.member.update",
+            (GroupActivityKind::Subgroup, ActionKind::Delete) => "groups.activity.subgroup.delete",
+            (GroupActivityKind::Subgroup, _) => "groups.activity.subgroup.create",
+            (GroupActivityKind::Permission, ActionKind::Delete) => {
+                "groups.activity.permission.delete"
+            }
+            (GroupActivityKind::Permission, _) => "groups.activity.permission.create",
+            (GroupActivityKind::Tag, ActionKind::Delete) => "groups.activity.tag.delete",
+            (GroupActivityKind::Tag, _) => "groups.activity.tag.create",
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self.kind {
+            GroupActivityKind::Details => "edit",
+            GroupActivityKind::Link => "link",
+            GroupActivityKind::Member => "person",
+            GroupActivityKind::Subgroup => "group",
+            GroupActivityKind::Permission => "key",
+            GroupActivityKind::Tag => "sell",
+        }
+    }
+}
+
+#[derive(sqlx::Type, UriDisplayQuery, FromFormField, PartialEq, Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
 #[sqlx(type_name = "action_kind", rename_all = "snake_case")]
 pub enum ActionKind {
     Create,
@@ -431,7 +1236,8 @@ impl fmt::Display for ActionKind {
     }
 }
 
-#[derive(sqlx::Type, UriDisplayQuery, FromFormField, PartialEq, Clone, Debug)]
+#[derive(sqlx::Type, UriDisplayQuery, FromFormField, PartialEq, Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
 #[sqlx(type_name = "target_kind", rename_all = "snake_case")]
 pub enum TargetKind {
     Group,
@@ -443,6 +1249,7 @@ pub enum TargetKind {
     Permission,
     PermissionAssignment,
     User,
+    OperationalYear,
 }
 
 impl fmt::Display for TargetKind {
@@ -457,27 +1264,30 @@ impl fmt::Display for TargetKind {
             TargetKind::Permission => write!(f, "Permission"),
             TargetKind::PermissionAssignment => write!(f, "PermissionAssignment"),
             TargetKind::User => write!(f, "User"),
+            TargetKind::OperationalYear => write!(f, "OperationalYear"),
         }
     }
 }
 
-#[derive(FromRow)]
+#[derive(FromRow, Serialize)]
 pub struct IntegrationTaskRun {
     pub run_id: Uuid,
     pub task_id: String,
     pub start_stamp: DateTime<Local>,
     pub end_stamp: Option<DateTime<Local>>,
     pub succeeded: Option<bool>,
+    pub skipped: bool, // because another run of the same task was ongoing
 }
 
-#[derive(FromRow)]
+#[derive(FromRow, Serialize)]
 pub struct IntegrationTaskLogEntry {
     pub kind: IntegrationTaskLogEntryKind,
     pub stamp: DateTime<Local>,
     pub message: String,
 }
 
-#[derive(sqlx::Type, Clone, Copy)]
+#[derive(sqlx::Type, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
 #[sqlx(
     type_name = "integration_task_log_entry_kind",
     rename_all = "snake_case"
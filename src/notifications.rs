@@ -0,0 +1,353 @@
+use std::{future::Future, pin::Pin, sync::OnceLock, time::Duration};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    mailer::Mailer,
+    secrets::{self, SecretsError},
+    services::api_tokens,
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const USER_AGENT: &str = "hive-notifications";
+
+// Operational alerts (as opposed to, e.g., manager digests, which are meant for
+// specific users) are sent to every configured channel that subscribed to
+// their event type, or to all channels without an explicit list of events.
+// Channels are configured in `hive.toml` like
+//   [[notification_channels]]
+//   kind = "slack"
+//   webhook_url = "vault://hive/slack#webhook"
+//   events = ["suspicious-login", "root-expiry"]
+// where any credentials can also be secret references (see `secrets`).
+// Global rather than managed state so that integration tasks can also use it.
+static CHANNELS: OnceLock<Vec<Subscription>> = OnceLock::new();
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotificationEvent {
+    SuspiciousLogin,   // see `logins::alert_admins`
+    RootExpiry,        // root@hive.internal is about to be left without members
+    ConsistencyReport, // the nightly health check found anomalies
+}
+
+impl NotificationEvent {
+    pub const fn key(&self) -> &'static str {
+        match self {
+            Self::SuspiciousLogin => "suspicious-login",
+            Self::RootExpiry => "root-expiry",
+            Self::ConsistencyReport => "consistency-report",
+        }
+    }
+}
+
+pub struct Notification {
+    pub event: NotificationEvent,
+    pub subject: String,
+    pub content: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum NotificationError {
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("failed to resolve credentials: {0}")]
+    Secret(#[from] SecretsError),
+    #[error("invalid URL `{0}`")]
+    InvalidUrl(String),
+}
+
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = Result<(), NotificationError>> + Send + 'a>>;
+
+// boxed futures so that channels of different kinds can be stored together
+pub trait NotificationChannel: Send + Sync {
+    fn kind(&self) -> &'static str;
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> SendFuture<'a>;
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ChannelConfig {
+    // empty means every event
+    #[serde(default)]
+    pub events: Vec<NotificationEvent>,
+
+    #[serde(flatten)]
+    pub kind: ChannelKind,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ChannelKind {
+    Email {
+        recipients: Vec<String>,
+    },
+    Slack {
+        webhook_url: String,
+    },
+    Matrix {
+        homeserver: String,
+        room_id: String,
+        access_token: String,
+    },
+    Webhook {
+        url: String,
+        #[serde(default)]
+        secret: Option<String>,
+    },
+}
+
+struct Subscription {
+    events: Vec<NotificationEvent>,
+    channel: Box<dyn NotificationChannel>,
+}
+
+impl Subscription {
+    fn wants(&self, event: NotificationEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+// `mailer` is only called upon for email channels (each gets its own)
+pub fn init(configs: Vec<ChannelConfig>, mailer: impl Fn() -> Option<Mailer>) {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(USER_AGENT)
+        .build()
+        .expect("failed to build notifications reqwest client");
+
+    let subscriptions = configs
+        .into_iter()
+        .map(|config| {
+            let channel: Box<dyn NotificationChannel> = match config.kind {
+                ChannelKind::Email { recipients } => {
+                    let Some(mailer) = mailer() else {
+                        panic!(
+                            "Fatal error: email notification channel is configured, but the \
+                             mailer is not"
+                        )
+                    };
+
+                    Box::new(EmailChannel { mailer, recipients })
+                }
+                ChannelKind::Slack { webhook_url } => Box::new(SlackChannel {
+                    webhook_url,
+                    client: client.clone(),
+                }),
+                ChannelKind::Matrix {
+                    homeserver,
+                    room_id,
+                    access_token,
+                } => Box::new(MatrixChannel {
+                    homeserver,
+                    room_id,
+                    access_token,
+                    client: client.clone(),
+                }),
+                ChannelKind::Webhook { url, secret } => Box::new(WebhookChannel {
+                    url,
+                    secret,
+                    client: client.clone(),
+                }),
+            };
+
+            Subscription {
+                events: config.events,
+                channel,
+            }
+        })
+        .collect();
+
+    if CHANNELS.set(subscriptions).is_err() {
+        warn!("Notification channels were already initialized; ignoring");
+    }
+}
+
+fn subscriptions() -> &'static [Subscription] {
+    CHANNELS.get().map_or(&[], Vec::as_slice)
+}
+
+pub fn is_subscribed(event: NotificationEvent) -> bool {
+    subscriptions().iter().any(|s| s.wants(event))
+}
+
+// never fails as a whole, since notifications are always secondary to whatever
+// triggered them; returns how many channels it was sent to, plus any failures
+// (which are left for the caller to report wherever is most appropriate)
+pub async fn dispatch(
+    notification: &Notification,
+) -> (usize, Vec<(&'static str, NotificationError)>) {
+    let mut sent = 0;
+    let mut failures = vec![];
+
+    for subscription in subscriptions() {
+        if !subscription.wants(notification.event) {
+            continue;
+        }
+
+        match subscription.channel.send(notification).await {
+            Ok(()) => sent += 1,
+            Err(e) => failures.push((subscription.channel.kind(), e)),
+        }
+    }
+
+    debug!(
+        "Dispatched {} notification \"{}\" to {sent} channels ({} failed)",
+        notification.event.key(),
+        notification.subject,
+        failures.len()
+    );
+
+    (sent, failures)
+}
+
+struct EmailChannel {
+    mailer: Mailer,
+    recipients: Vec<String>,
+}
+
+impl NotificationChannel for EmailChannel {
+    fn kind(&self) -> &'static str {
+        "email"
+    }
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> SendFuture<'a> {
+        Box::pin(async move {
+            let to: Vec<&str> = self.recipients.iter().map(String::as_str).collect();
+
+            self.mailer
+                .send(
+                    &to,
+                    &format!("[Hive] {}", notification.subject),
+                    &notification.content,
+                )
+                .await?;
+
+            Ok(())
+        })
+    }
+}
+
+// an incoming webhook, as set up for a specific Slack channel
+struct SlackChannel {
+    webhook_url: String,
+    client: reqwest::Client,
+}
+
+impl NotificationChannel for SlackChannel {
+    fn kind(&self) -> &'static str {
+        "slack"
+    }
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> SendFuture<'a> {
+        Box::pin(async move {
+            let webhook_url = secrets::resolve(&self.webhook_url).await?;
+
+            self.client
+                .post(webhook_url.as_ref())
+                .json(&json!({
+                    "text": format!("*{}*\n\n{}", notification.subject, notification.content),
+                }))
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)?;
+
+            Ok(())
+        })
+    }
+}
+
+// posts as whichever (bot) user the access token belongs to, which must have
+// already joined the room
+struct MatrixChannel {
+    homeserver: String,
+    room_id: String,
+    access_token: String,
+    client: reqwest::Client,
+}
+
+impl NotificationChannel for MatrixChannel {
+    fn kind(&self) -> &'static str {
+        "matrix"
+    }
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> SendFuture<'a> {
+        Box::pin(async move {
+            let access_token = secrets::resolve(&self.access_token).await?;
+
+            let invalid = || NotificationError::InvalidUrl(self.homeserver.clone());
+
+            // (room IDs contain reserved characters, which this escapes)
+            let mut url = reqwest::Url::parse(&self.homeserver).map_err(|_| invalid())?;
+            url.path_segments_mut()
+                .map_err(|_| invalid())?
+                .pop_if_empty()
+                .extend(["_matrix", "client", "v3", "rooms"])
+                .push(&self.room_id)
+                .extend(["send", "m.room.message"])
+                .push(&Uuid::new_v4().to_string()); // transaction ID
+
+            self.client
+                .put(url)
+                .bearer_auth(access_token)
+                .json(&json!({
+                    "msgtype": "m.text",
+                    "body": format!("{}\n\n{}", notification.subject, notification.content),
+                }))
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)?;
+
+            Ok(())
+        })
+    }
+}
+
+// for anything else; signed like system webhooks (see `webhooks::send`) if a
+// secret is given, but sent just once, without being recorded anywhere
+struct WebhookChannel {
+    url: String,
+    secret: Option<String>,
+    client: reqwest::Client,
+}
+
+impl NotificationChannel for WebhookChannel {
+    fn kind(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> SendFuture<'a> {
+        Box::pin(async move {
+            let body = json!({
+                "event": notification.event,
+                "subject": notification.subject,
+                "content": notification.content,
+            })
+            .to_string();
+
+            let mut request = self
+                .client
+                .post(&self.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header("X-Hive-Event", notification.event.key());
+
+            if let Some(secret) = &self.secret {
+                let secret = secrets::resolve(secret).await?;
+                let signature = api_tokens::hmac_sha256(secret.as_bytes(), body.as_bytes());
+
+                request = request.header("X-Hive-Signature", hex::encode(signature));
+            }
+
+            request
+                .body(body)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)?;
+
+            Ok(())
+        })
+    }
+}
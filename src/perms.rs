@@ -1,15 +1,69 @@
-use std::{cmp::Ordering, fmt};
+use std::{cmp::Ordering, fmt, sync::OnceLock};
 
-use chrono::Local;
+use log::*;
+use regex::Regex;
+use serde::Serialize;
 use sqlx::PgPool;
 
-use crate::{errors::AppResult, models::BasePermissionAssignment};
+use crate::{
+    clock,
+    errors::{AppError, AppResult},
+    models::{BasePermissionAssignment, ReachingAssignment},
+};
+
+pub mod queries;
+
+// Group domains for external users (e.g., alumni or collaborators), whose
+// memberships are purely informational (and for integrations, like mailing
+// lists): groups in them never confer permissions, and they can only be
+// subgroups of other external groups, so that their members can't inherit
+// any permissions from elsewhere either. Global rather than managed state
+// since it must hold everywhere permissions are resolved.
+static EXTERNAL_DOMAINS: OnceLock<Vec<String>> = OnceLock::new();
+
+pub fn init_external_domains(domains: Vec<String>) {
+    if EXTERNAL_DOMAINS.set(domains).is_err() {
+        warn!("External domains were already initialized; ignoring");
+    }
+}
+
+pub fn external_domains() -> &'static [String] {
+    EXTERNAL_DOMAINS.get().map_or(&[], Vec::as_slice)
+}
+
+pub fn is_external_domain(domain: &str) -> bool {
+    external_domains().iter().any(|d| d == domain)
+}
+
+pub fn require_internal_domain(domain: &str) -> AppResult<()> {
+    if is_external_domain(domain) {
+        Err(AppError::ExternalDomainPermission(domain.to_owned()))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn require_valid_nesting(
+    parent_domain: &str,
+    child_id: &str,
+    child_domain: &str,
+) -> AppResult<()> {
+    if is_external_domain(child_domain) && !is_external_domain(parent_domain) {
+        Err(AppError::ExternalSubgroup(
+            child_id.to_owned(),
+            child_domain.to_owned(),
+        ))
+    } else {
+        Ok(())
+    }
+}
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub enum HivePermission {
     ViewLogs,
     ViewGroups(GroupsScope),
     ManageGroups(GroupsScope),
+    CreateGroups(GroupsScope),
     ManageMembers(GroupsScope),
     ManageSystems,
     ManageSystem(SystemsScope),
@@ -17,10 +71,17 @@ pub enum HivePermission {
     AssignPerms(SystemsScope),
     ManageTags(SystemsScope),
     AssignTags(SystemsScope),
+    AssignTag(TagScope),
     LongTermAppointment(UpperBoundScope),
     ImpersonateUsers,
+    RestoreDeletions,
     ApiCheckPermissions,
     ApiListTagged,
+    ApiApplyManifest,
+    ApiListGroups,
+    ApiManageMembers,
+    ApiRunIntegrations,
+    ApiViewIntegrationRuns,
 }
 
 impl HivePermission {
@@ -29,6 +90,7 @@ impl HivePermission {
             Self::ViewLogs => "view-logs",
             Self::ViewGroups(..) => "view-groups",
             Self::ManageGroups(..) => "manage-groups",
+            Self::CreateGroups(..) => "create-groups",
             Self::ManageMembers(..) => "manage-members",
             Self::ManageSystems => "manage-systems",
             Self::ManageSystem(..) => "manage-system",
@@ -36,14 +98,159 @@ impl HivePermission {
             Self::AssignPerms(..) => "assign-perms",
             Self::ManageTags(..) => "manage-tags",
             Self::AssignTags(..) => "assign-tags",
+            Self::AssignTag(..) => "assign-tag",
             Self::LongTermAppointment(..) => "long-term-appointment",
             Self::ImpersonateUsers => "impersonate-users",
+            Self::RestoreDeletions => "restore-deletions",
             Self::ApiCheckPermissions => "api-check-permissions",
             Self::ApiListTagged => "api-list-tagged",
+            Self::ApiApplyManifest => "api-apply-manifest",
+            Self::ApiListGroups => "api-list-groups",
+            Self::ApiManageMembers => "api-manage-members",
+            Self::ApiRunIntegrations => "api-run-integrations",
+            Self::ApiViewIntegrationRuns => "api-view-integration-runs",
+        }
+    }
+}
+
+impl HivePermission {
+    // one of each variant (with pseudo-scopes where applicable), e.g. for the
+    // permission matrix page; new variants must be added here by hand, but
+    // everything else shown about them comes from exhaustive matches
+    pub const ALL: [Self; 22] = [
+        Self::ViewLogs,
+        Self::ViewGroups(GroupsScope::Any),
+        Self::ManageGroups(GroupsScope::Any),
+        Self::CreateGroups(GroupsScope::AnyDomain),
+        Self::ManageMembers(GroupsScope::Any),
+        Self::ManageSystems,
+        Self::ManageSystem(SystemsScope::Any),
+        Self::ManagePerms(SystemsScope::Any),
+        Self::AssignPerms(SystemsScope::Any),
+        Self::ManageTags(SystemsScope::Any),
+        Self::AssignTags(SystemsScope::Any),
+        Self::AssignTag(TagScope::Any),
+        Self::LongTermAppointment(UpperBoundScope::Any),
+        Self::ImpersonateUsers,
+        Self::RestoreDeletions,
+        Self::ApiCheckPermissions,
+        Self::ApiListTagged,
+        Self::ApiApplyManifest,
+        Self::ApiListGroups,
+        Self::ApiManageMembers,
+        Self::ApiRunIntegrations,
+        Self::ApiViewIntegrationRuns,
+    ];
+
+    pub const fn scope_kind(&self) -> ScopeKind {
+        match self {
+            Self::ViewLogs
+            | Self::ManageSystems
+            | Self::ImpersonateUsers
+            | Self::RestoreDeletions
+            | Self::ApiCheckPermissions
+            | Self::ApiListTagged
+            | Self::ApiApplyManifest
+            | Self::ApiListGroups
+            | Self::ApiManageMembers
+            | Self::ApiRunIntegrations
+            | Self::ApiViewIntegrationRuns => ScopeKind::Unscoped,
+            Self::ViewGroups(..) | Self::ManageGroups(..) | Self::ManageMembers(..) => {
+                ScopeKind::Groups
+            }
+            Self::CreateGroups(..) => ScopeKind::Domains,
+            Self::ManageSystem(..)
+            | Self::ManagePerms(..)
+            | Self::AssignPerms(..)
+            | Self::ManageTags(..)
+            | Self::AssignTags(..) => ScopeKind::Systems,
+            Self::AssignTag(..) => ScopeKind::Tag,
+            Self::LongTermAppointment(..) => ScopeKind::UpperBound,
         }
     }
 }
 
+// which of the scope types below a Hive permission takes
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize)]
+pub enum ScopeKind {
+    Unscoped,
+    Groups,
+    Domains, // a subset of Groups, for groups that don't exist yet
+    Systems,
+    Tag,
+    UpperBound,
+}
+
+impl ScopeKind {
+    // what the corresponding `TryFrom<&str>` impl accepts (excluding
+    // pseudo-scopes, which can't be assigned)
+    pub const fn syntaxes(&self) -> &'static [&'static str] {
+        match self {
+            Self::Unscoped => &[],
+            Self::Groups => &[
+                "*",
+                "#hive:<tag>",
+                "#hive:<tag>:<content>",
+                "#hive:<tag>:*",
+                "@<domain>",
+            ],
+            Self::Domains => &["*", "@<domain>"],
+            Self::Systems => &["*", "<system>"],
+            Self::Tag => &["<system>:<tag>"],
+            Self::UpperBound => &["*", "<n>"],
+        }
+    }
+
+    pub fn is_scoped(&self) -> bool {
+        *self != Self::Unscoped
+    }
+}
+
+impl HivePermission {
+    // parsing scopes is lenient (e.g., any string is a valid system ID), so
+    // this checks whether the scope could ever actually match anything
+    pub fn has_well_formed_scope(&self) -> bool {
+        match self {
+            Self::ViewGroups(s) | Self::ManageGroups(s) | Self::ManageMembers(s) => {
+                s.is_well_formed()
+            }
+            // a group that doesn't exist yet can't be tagged with anything
+            Self::CreateGroups(s) => {
+                matches!(s, GroupsScope::Wildcard | GroupsScope::Domain(_)) && s.is_well_formed()
+            }
+            Self::ManageSystem(s)
+            | Self::ManagePerms(s)
+            | Self::AssignPerms(s)
+            | Self::ManageTags(s)
+            | Self::AssignTags(s) => s.is_well_formed(),
+            Self::AssignTag(s) => s.is_well_formed(),
+            _ => true,
+        }
+    }
+}
+
+impl HivePermission {
+    // any of these allows (un)assigning a specific tag: either the delegated
+    // per-tag permission or the system-wide one (last, so that it is the one
+    // reported as missing)
+    pub fn assign_tag(system_id: &str, tag_id: &str) -> [Self; 2] {
+        [
+            Self::AssignTag(TagScope::Id(system_id.to_owned(), tag_id.to_owned())),
+            Self::AssignTags(SystemsScope::Id(system_id.to_owned())),
+        ]
+    }
+
+    // any of these allows creating groups in (or moving groups into) a domain:
+    // either the dedicated permission or full group management (first, so
+    // that the narrower one is reported as missing)
+    pub fn create_groups_in(domain: &str) -> [Self; 2] {
+        [
+            Self::ManageGroups(GroupsScope::Domain(domain.to_owned())),
+            Self::CreateGroups(GroupsScope::Domain(domain.to_owned())),
+        ]
+    }
+}
+
 impl fmt::Display for HivePermission {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let key = self.key();
@@ -52,11 +259,18 @@ impl fmt::Display for HivePermission {
             Self::ViewLogs
             | Self::ManageSystems
             | Self::ImpersonateUsers
+            | Self::RestoreDeletions
             | Self::ApiCheckPermissions
-            | Self::ApiListTagged => write!(f, "$hive:{key}"),
-            Self::ViewGroups(s) | Self::ManageGroups(s) | Self::ManageMembers(s) => {
-                write!(f, "$hive:{key}:{s}")
-            }
+            | Self::ApiListTagged
+            | Self::ApiApplyManifest
+            | Self::ApiListGroups
+            | Self::ApiManageMembers
+            | Self::ApiRunIntegrations
+            | Self::ApiViewIntegrationRuns => write!(f, "$hive:{key}"),
+            Self::ViewGroups(s)
+            | Self::ManageGroups(s)
+            | Self::CreateGroups(s)
+            | Self::ManageMembers(s) => write!(f, "$hive:{key}:{s}"),
             Self::ManageSystem(s)
             | Self::ManagePerms(s)
             | Self::AssignPerms(s)
@@ -64,6 +278,7 @@ impl fmt::Display for HivePermission {
             | Self::AssignTags(s) => {
                 write!(f, "$hive:{key}:{s}")
             }
+            Self::AssignTag(s) => write!(f, "$hive:{key}:{s}"),
             Self::LongTermAppointment(s) => write!(f, "$hive:{key}:{s}"),
         }
     }
@@ -78,12 +293,14 @@ impl PartialOrd for HivePermission {
         match (self, other) {
             (Self::ViewGroups(a), Self::ViewGroups(b)) => a.partial_cmp(b),
             (Self::ManageGroups(a), Self::ManageGroups(b)) => a.partial_cmp(b),
+            (Self::CreateGroups(a), Self::CreateGroups(b)) => a.partial_cmp(b),
             (Self::ManageMembers(a), Self::ManageMembers(b)) => a.partial_cmp(b),
             (Self::ManageSystem(a), Self::ManageSystem(b)) => a.partial_cmp(b),
             (Self::ManagePerms(a), Self::ManagePerms(b)) => a.partial_cmp(b),
             (Self::AssignPerms(a), Self::AssignPerms(b)) => a.partial_cmp(b),
             (Self::ManageTags(a), Self::ManageTags(b)) => a.partial_cmp(b),
             (Self::AssignTags(a), Self::AssignTags(b)) => a.partial_cmp(b),
+            (Self::AssignTag(a), Self::AssignTag(b)) => a.partial_cmp(b),
             (Self::LongTermAppointment(a), Self::LongTermAppointment(b)) => a.partial_cmp(b),
             _ => None,
         }
@@ -117,6 +334,11 @@ impl TryFrom<BasePermissionAssignment> for HivePermission {
 
                 Ok(Self::ManageGroups(scope))
             }
+            ("create-groups", Some(scope)) => {
+                let scope = GroupsScope::try_from(scope)?;
+
+                Ok(Self::CreateGroups(scope))
+            }
             ("manage-members", Some(scope)) => {
                 let scope = GroupsScope::try_from(scope)?;
 
@@ -148,14 +370,25 @@ impl TryFrom<BasePermissionAssignment> for HivePermission {
 
                 Ok(Self::AssignTags(scope))
             }
+            ("assign-tag", Some(scope)) => {
+                let scope = TagScope::try_from(scope)?;
+
+                Ok(Self::AssignTag(scope))
+            }
             ("long-term-appointment", Some(scope)) => {
                 let scope = UpperBoundScope::try_from(scope)?;
 
                 Ok(Self::LongTermAppointment(scope))
             }
             ("impersonate-users", None) => Ok(Self::ImpersonateUsers),
+            ("restore-deletions", None) => Ok(Self::RestoreDeletions),
             ("api-check-permissions", None) => Ok(Self::ApiCheckPermissions),
             ("api-list-tagged", None) => Ok(Self::ApiListTagged),
+            ("api-apply-manifest", None) => Ok(Self::ApiApplyManifest),
+            ("api-list-groups", None) => Ok(Self::ApiListGroups),
+            ("api-manage-members", None) => Ok(Self::ApiManageMembers),
+            ("api-run-integrations", None) => Ok(Self::ApiRunIntegrations),
+            ("api-view-integration-runs", None) => Ok(Self::ApiViewIntegrationRuns),
             _ => Err(InvalidHivePermissionError::Id),
         }
     }
@@ -194,6 +427,16 @@ impl TryFrom<&str> for GroupsScope {
     }
 }
 
+impl GroupsScope {
+    fn is_well_formed(&self) -> bool {
+        match self {
+            Self::Tag { id, .. } => is_slug(id),
+            Self::Domain(domain) => is_domain(domain),
+            _ => true,
+        }
+    }
+}
+
 impl fmt::Display for GroupsScope {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -276,6 +519,9 @@ impl From<&str> for TagContent {
     }
 }
 
+// unlike other systems' permissions checked through the API, scopes of $hive
+// permissions cannot be prefix wildcards (e.g. `committee/*`), since a system
+// ID is a plain slug; see `queries::scope_matches!` for the former
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub enum SystemsScope {
     Wildcard,
@@ -296,6 +542,15 @@ impl TryFrom<&str> for SystemsScope {
     }
 }
 
+impl SystemsScope {
+    fn is_well_formed(&self) -> bool {
+        match self {
+            Self::Id(id) => is_slug(id),
+            _ => true,
+        }
+    }
+}
+
 impl fmt::Display for SystemsScope {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -322,6 +577,64 @@ impl PartialOrd for SystemsScope {
     }
 }
 
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub enum TagScope {
+    Id(String, String), // (system ID, tag ID), e.g. `gworkspace:extra-member`
+    Any,                // pseudo-scope meaning "any tag"
+}
+
+impl TryFrom<&str> for TagScope {
+    type Error = InvalidHivePermissionError;
+
+    fn try_from(scope: &str) -> Result<Self, Self::Error> {
+        match scope.split_once(':') {
+            Some((system_id, tag_id)) => Ok(Self::Id(system_id.to_owned(), tag_id.to_owned())),
+            None => Err(InvalidHivePermissionError::Scope),
+        }
+        // intentionally not handling ? => Any since it's not a real scope
+    }
+}
+
+impl TagScope {
+    fn is_well_formed(&self) -> bool {
+        match self {
+            Self::Id(system_id, tag_id) => is_slug(system_id) && is_slug(tag_id),
+            Self::Any => true,
+        }
+    }
+}
+
+impl fmt::Display for TagScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id(system_id, tag_id) => write!(f, "{system_id}:{tag_id}"),
+            Self::Any => write!(f, "?"),
+        }
+    }
+}
+
+impl PartialOrd for TagScope {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self == other {
+            return Some(Ordering::Equal);
+        }
+
+        match (self, other) {
+            (Self::Any, _) => Some(Ordering::Less),
+            (_, Self::Any) => Some(Ordering::Greater),
+            _ => None,
+        }
+    }
+}
+
+fn is_slug(s: &str) -> bool {
+    Regex::new("^[a-z0-9]+(-[a-z0-9]+)*$").unwrap().is_match(s)
+}
+
+fn is_domain(s: &str) -> bool {
+    Regex::new("^[-a-z0-9]+\\.[a-z]+$").unwrap().is_match(s)
+}
+
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub enum UpperBoundScope {
     Wildcard,
@@ -383,25 +696,17 @@ pub async fn get_assignments(
     system_id: &str,
     perm_id: &str,
     db: &PgPool,
-) -> AppResult<Vec<BasePermissionAssignment>> {
-    let today = Local::now().date_naive();
-
-    let assignments = sqlx::query_as::<_, BasePermissionAssignment>(
-        "
-        SELECT *
-        FROM permission_assignments pa
-        JOIN all_groups_of($1, $2) ag
-            ON pa.group_id = ag.id
-            AND pa.group_domain = ag.domain
-        WHERE pa.system_id = $3
-        AND pa.perm_id = $4",
-    )
-    .bind(username)
-    .bind(today)
-    .bind(system_id)
-    .bind(perm_id)
-    .fetch_all(db)
-    .await?;
+) -> AppResult<Vec<ReachingAssignment>> {
+    let today = clock::today();
+
+    let assignments = sqlx::query_as::<_, ReachingAssignment>(queries::REACHING_ASSIGNMENTS)
+        .bind(username)
+        .bind(today)
+        .bind(system_id)
+        .bind(perm_id)
+        .bind(external_domains())
+        .fetch_all(db)
+        .await?;
 
     // can't use `fetch` instead of `fetch_all` (which would avoid deserializing
     // unless needed) because we want to cache *all* permission assignments;
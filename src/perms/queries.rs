@@ -0,0 +1,81 @@
+// SQL behind permission checks, kept free of any other crate modules so that
+// `hive-bench` and the criterion benches (which can't depend on Hive itself,
+// as it isn't a library crate) include this same file and time exactly what
+// production runs
+
+// condition that ignores assignments to groups in external domains (since they
+// never grant anything), for any query on `permission_assignments pa`; expands
+// to a literal so it can be used within `concat!`, with the external domains
+// bound to the given placeholder
+macro_rules! internal_assignments_only {
+    ($param:literal) => {
+        concat!("pa.group_domain <> ALL(", $param, ")")
+    };
+}
+
+#[allow(unused_imports)] // (not used by the benches)
+pub(crate) use internal_assignments_only;
+
+// condition for assignments on `permission_assignments pa` whose scope covers
+// the one bound to the given placeholder, i.e. the same scope, the `*`
+// wildcard or a hierarchical prefix wildcard (e.g. `committee/*`, covering
+// `committee/finance/budget` but not `committee`); expands to a literal so it
+// can be used within `concat!`. This is the only place where prefix wildcards
+// are interpreted (besides `ReachingAssignment::matches`, for cached checks):
+// $hive permissions don't support them (see `SystemsScope`)
+macro_rules! scope_matches {
+    ($param:literal) => {
+        concat!(
+            "(
+            pa.scope IS NOT DISTINCT FROM ",
+            $param,
+            "
+            OR pa.scope = '*'
+            OR (
+                pa.scope LIKE '_%/*'
+                AND STARTS_WITH(",
+            $param,
+            ", LEFT(pa.scope, -1))
+            )
+        )"
+        )
+    };
+}
+
+#[allow(unused_imports)] // (not used by the benches)
+pub(crate) use scope_matches;
+
+// what `PermsEvaluator` (via `perms::get_assignments`) loads for a user:
+// $1 username, $2 date, $3 system ID, $4 permission ID, $5 external domains
+pub const REACHING_ASSIGNMENTS: &str = concat!(
+    "
+    SELECT pa.id, pa.scope
+    FROM permission_assignments pa
+    JOIN all_group_refs_of($1, $2) ag
+        ON pa.group_id = ag.id
+        AND pa.group_domain = ag.domain
+    WHERE pa.system_id = $3
+    AND pa.perm_id = $4
+    AND ",
+    internal_assignments_only!("$5")
+);
+
+// assignments granting a (scoped) permission to a user, as used for every
+// permission check through the API (see `services::permissions`):
+// $1 username, $2 date, $3 system ID, $4 permission ID, $5 scope,
+// $6 external domains
+pub const USER_MATCHES: &str = concat!(
+    "
+    SELECT pa.id
+    FROM permission_assignments pa
+    JOIN all_group_refs_of($1, $2) ag
+        ON ag.id = pa.group_id
+        AND ag.domain = pa.group_domain
+    WHERE pa.system_id = $3
+        AND pa.perm_id = $4
+        AND ",
+    internal_assignments_only!("$6"),
+    "
+        AND ",
+    scope_matches!("$5")
+);
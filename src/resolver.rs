@@ -10,11 +10,12 @@ const USER_AGENT: &str = "hive-identity-resolver";
 
 pub struct IdentityResolver {
     endpoint: String,
+    search_endpoint: Option<String>,
     client: reqwest::Client,
 }
 
 impl IdentityResolver {
-    pub fn new(endpoint: Option<String>) -> Option<Self> {
+    pub fn new(endpoint: Option<String>, search_endpoint: Option<String>) -> Option<Self> {
         if let Some(endpoint) = endpoint {
             let client = reqwest::Client::builder()
                 .timeout(REQUEST_TIMEOUT)
@@ -22,7 +23,11 @@ impl IdentityResolver {
                 .build()
                 .expect("failed to build resolver reqwest client");
 
-            Some(Self { endpoint, client })
+            Some(Self {
+                endpoint,
+                search_endpoint,
+                client,
+            })
         } else {
             None
         }
@@ -84,6 +89,37 @@ impl IdentityResolver {
         Ok(Some(name))
     }
 
+    // finds users whose name or username matches the query, as (username,
+    // display name) pairs; always empty if no search endpoint is configured
+    pub async fn search(&self, query: &str, limit: usize) -> AppResult<Vec<(String, String)>> {
+        let Some(search_endpoint) = &self.search_endpoint else {
+            return Ok(vec![]);
+        };
+
+        let results: Vec<SearchResult> = self
+            .client
+            .get(search_endpoint)
+            .query(&[("query", query)])
+            .query(&[("limit", limit)])
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(AppError::IdentityResolutionError)?
+            .json()
+            .await
+            .map_err(AppError::IdentityResolutionError)?;
+
+        trace!("Identity search for `{query}` returned: {:?}", &results);
+
+        let suggestions = results
+            .into_iter()
+            .take(limit) // in case the limit isn't respected
+            .map(|r| (r.username, r.entry.display_name()))
+            .collect();
+
+        Ok(suggestions)
+    }
+
     pub async fn populate_identities<T>(
         &self,
         items: &mut [T],
@@ -104,6 +140,20 @@ impl IdentityResolver {
     }
 }
 
+// every user is assumed to have an email address at the same domain, with
+// their username as the local part
+pub struct UserEmailDomain(String);
+
+impl UserEmailDomain {
+    pub fn new(domain: String) -> Self {
+        Self(domain)
+    }
+
+    pub fn email_of(&self, username: &str) -> String {
+        format!("{username}@{}", self.0)
+    }
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 struct ResolvedEntry {
@@ -116,3 +166,11 @@ impl ResolvedEntry {
         format!("{} {}", self.first_name, self.family_name)
     }
 }
+
+#[derive(Deserialize, Debug)]
+struct SearchResult {
+    #[serde(alias = "kthid")]
+    username: String,
+    #[serde(flatten)]
+    entry: ResolvedEntry,
+}
@@ -1,6 +1,11 @@
 use rocket::Route;
 
 pub mod cors;
+pub mod maintenance;
+pub mod shutdown;
+
+#[cfg(test)]
+mod tests;
 
 // convenient for a modular distribution of routes across files,
 // without having to centralize a single list of all routes here
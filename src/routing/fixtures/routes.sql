@@ -0,0 +1,21 @@
+-- Resources that some routes look up (by ID) before they can check whether the
+-- user may act on them, so that requests reach that check instead of a 404.
+-- Applied on top of seeds/dev.sql; the IDs are referenced from tests.rs.
+
+INSERT INTO "direct_memberships" (id, username, group_id, group_domain, "from", "until", manager) VALUES
+    ('00000000-0000-4000-8000-000000000001', 'mallorym', 'd-sys', 'datasektionen.se',
+     CURRENT_DATE - 30, CURRENT_DATE + 30, FALSE);
+
+INSERT INTO "pending_changes" (id, kind, group_id, group_domain, child_id, child_domain, manager, proposed_by) VALUES
+    ('00000000-0000-4000-8000-000000000002', 'add_subgroup', 'd-sys', 'datasektionen.se',
+     'mottagningen', 'datasektionen.se', FALSE, 'davidd');
+
+INSERT INTO "pending_memberships" (id, username, group_id, group_domain, "from", "until", manager, added_by) VALUES
+    ('00000000-0000-4000-8000-000000000003', 'alicea', 'd-sys', 'datasektionen.se',
+     CURRENT_DATE, CURRENT_DATE + 30, FALSE, 'davidd');
+
+INSERT INTO "deletions" (id, target_kind, target_id, actor) VALUES
+    ('00000000-0000-4000-8000-000000000004', 'group', 'mottagningen@datasektionen.se', 'davidd');
+
+INSERT INTO "ownership_transfers" (group_id, group_domain, to_username, "until", proposed_by) VALUES
+    ('d-sys', 'datasektionen.se', 'alicea', CURRENT_DATE + 30, 'davidd');
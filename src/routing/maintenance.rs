@@ -0,0 +1,108 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::*;
+use rocket::{
+    Build, Data, Request, Rocket,
+    fairing::{self, Fairing},
+    http::{Method, uri::Origin},
+    routes,
+};
+
+use crate::errors::{AppError, AppResult};
+
+// global rather than managed state so that background tasks can also check it
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+// where state-changing requests are rerouted to while in maintenance mode;
+// also mounted under /api so that errors are still reported as JSON there
+const REJECTION_PATH: &str = "/maintenance/rejected";
+const API_REJECTION_PATH: &str = "/api/maintenance/rejected";
+
+// must remain usable, otherwise there would be no way to leave maintenance
+// mode without restarting
+const EXEMPT_PATHS: &[&str] = &["/maintenance"];
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+pub fn set_active(active: bool) {
+    if ACTIVE.swap(active, Ordering::Relaxed) != active {
+        if active {
+            warn!("Entered read-only maintenance mode");
+        } else {
+            info!("Left read-only maintenance mode");
+        }
+    }
+}
+
+// read-only mode: every request that could change state is answered with a
+// 503 error instead of reaching its route, while reads keep working normally
+pub struct MaintenanceMode;
+
+#[rocket::async_trait]
+impl Fairing for MaintenanceMode {
+    fn info(&self) -> fairing::Info {
+        fairing::Info {
+            name: "Maintenance Mode",
+            kind: fairing::Kind::Ignite | fairing::Kind::Request,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let routes = routes![reject_post, reject_put, reject_patch, reject_delete];
+        let rocket = rocket.mount("/", routes.clone()).mount("/api", routes);
+
+        Ok(rocket)
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        if !is_active() {
+            return;
+        }
+
+        if matches!(req.method(), Method::Get | Method::Head | Method::Options) {
+            // safe methods never change anything
+            return;
+        }
+
+        let path = req.uri().path();
+        if EXEMPT_PATHS.contains(&path.as_str()) {
+            return;
+        }
+
+        debug!("Rejecting [{req}] due to maintenance mode");
+
+        let target = if path.starts_with("/api/") {
+            API_REJECTION_PATH
+        } else {
+            REJECTION_PATH
+        };
+
+        req.set_uri(Origin::parse(target).unwrap());
+    }
+}
+
+fn reject() -> AppResult<()> {
+    Err(AppError::MaintenanceMode)
+}
+
+#[rocket::post("/maintenance/rejected")]
+fn reject_post() -> AppResult<()> {
+    reject()
+}
+
+#[rocket::put("/maintenance/rejected")]
+fn reject_put() -> AppResult<()> {
+    reject()
+}
+
+#[rocket::patch("/maintenance/rejected")]
+fn reject_patch() -> AppResult<()> {
+    reject()
+}
+
+#[rocket::delete("/maintenance/rejected")]
+fn reject_delete() -> AppResult<()> {
+    reject()
+}
@@ -0,0 +1,45 @@
+use log::*;
+use rocket::{
+    Orbit, Rocket,
+    fairing::{self, Fairing},
+};
+use sqlx::PgPool;
+
+use crate::services::{api_tokens, permissions};
+
+// writes out what background tasks have buffered in memory one last time, so
+// that it isn't lost when the server stops (e.g., on redeploys)
+pub struct FlushBuffers;
+
+#[rocket::async_trait]
+impl Fairing for FlushBuffers {
+    fn info(&self) -> fairing::Info {
+        fairing::Info {
+            name: "Flush Buffers on Shutdown",
+            kind: fairing::Kind::Shutdown,
+        }
+    }
+
+    async fn on_shutdown(&self, rocket: &Rocket<Orbit>) {
+        if super::maintenance::is_active() {
+            warn!("Discarding buffered usage data due to maintenance mode");
+            return;
+        }
+
+        let Some(db) = rocket.state::<PgPool>() else {
+            return;
+        };
+
+        match api_tokens::flush_usage(db).await {
+            Ok(0) => {}
+            Ok(n) => debug!("Flushed {n} buffered API token usage entries on shutdown"),
+            Err(e) => error!("Failed to flush API token usage on shutdown: {e}"),
+        }
+
+        match permissions::flush_matches(db).await {
+            Ok(0) => {}
+            Ok(n) => debug!("Updated {n} permission assignment match stamps on shutdown"),
+            Err(e) => error!("Failed to flush permission assignment matches on shutdown: {e}"),
+        }
+    }
+}
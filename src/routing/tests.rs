@@ -0,0 +1,399 @@
+// exercises every mounted route without sufficient credentials, so that a route
+// that forgets to require a logged-in user, a HivePermission or an
+// AuthorityInGroup makes these tests fail (as does adding a route without
+// classifying it below, if it turns out to be reachable)
+
+use rocket::{
+    Route,
+    http::{ContentType, Cookie, Header, Status, uri::Host},
+    local::asynchronous::{Client, LocalResponse},
+};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::{auth::oidc::OidcClient, config::Config, services::ReadReplica};
+
+// reachable without logging in
+const PUBLIC: &[&str] = &[
+    "GET /",
+    "GET /sw.js",
+    "GET /favicon.ico",
+    "GET /static/<path..>",
+    "OPTIONS /<path..>",
+    "GET /auth/login?<next>",
+    "GET /auth/oidc-callback?<code>&<state>",
+    "GET /auth/logout",
+    "GET /user/verify-email/<secret>",
+    "POST /user/verify-email/<secret>",
+    "GET /public/groups",
+    "GET /public/statistics.json",
+    "GET /public/group/<domain>/<id>/members?<lang>",
+    "GET /public/group/<domain>/<id>/members.json",
+    "GET /shared/<share_id>?<expires>&<signature>",
+    "GET /calendar/<secret>/feed.ics?<lang>",
+    "GET /api",
+    "GET /api/v0",
+    "GET /api/v0/docs",
+    "GET /api/v0/openapi.yaml",
+    "GET /api/v1",
+    "GET /api/v1/docs",
+    "GET /api/v1/openapi.yaml",
+    // legacy API, which never had any authentication of its own
+    "GET /api/v0/token/<secret>/<system_id>",
+    "GET /api/v0/token/<secret>/<system_id>/<perm_key>",
+    "GET /api/v0/user/<username>",
+    "GET /api/v0/user/<username>/<system_id>",
+    "GET /api/v0/user/<username>/<system_id>/<perm_key>",
+    // only ever reached through maintenance mode rewrites
+    "POST /maintenance/rejected",
+    "PUT /maintenance/rejected",
+    "PATCH /maintenance/rejected",
+    "DELETE /maintenance/rejected",
+    "POST /api/maintenance/rejected",
+    "PUT /api/maintenance/rejected",
+    "PATCH /api/maintenance/rejected",
+    "DELETE /api/maintenance/rejected",
+];
+
+// open to any logged-in user, since they only concern the user themselves (or
+// only show whatever the user is allowed to see anyway)
+const OPEN: &[&str] = &[
+    "GET /groups?<q>&<sort>&<layout>&<domain>&<inactive>",
+    "GET /groups/parent-suggestions?<id>&<domain>",
+    "GET /permission-scopes?<perm>",
+    "GET /permission-assignments/preview?<group>&<perm>&<scope>",
+    "GET /users/autocomplete?<q>",
+    "GET /user/<username>",
+    "GET /palette?<q>",
+    "GET /quick?<check>",
+    "GET /changes",
+    "GET /operational-years/shortcuts",
+    "GET /user/settings",
+    "POST /user/settings",
+    "POST /user/settings/digest",
+    "POST /user/settings/membership-emails",
+    "POST /user/settings/<key>/resend-verification",
+    "GET /user/sessions",
+    "POST /user/calendar-feed",
+    "DELETE /user/calendar-feed",
+    "DELETE /group/<domain>/<id>/calendar-feed",
+    // anyone who knows the invitation's secret
+    "GET /join/<secret>",
+    "POST /join/<secret>",
+];
+
+// denied as if the resource didn't exist, so as not to reveal that it does
+const HIDDEN: &[&str] = &[
+    "GET /group/<domain>/<id>",
+    "GET /group/<domain>/<id>/statistics",
+    "GET /group/<domain>/<id>/watch",
+    "POST /group/<domain>/<id>/watch?<watching>",
+    "POST /deletion/<id>/undo",
+    "POST /pending-membership/<pending_id>/accept",
+    "POST /pending-membership/<pending_id>/decline",
+];
+
+// granted to the dev seed's API token ($hive:api-check-permissions and
+// $hive:api-list-tagged, for calypso)
+const TOKEN_GRANTED: &[&str] = &[
+    "GET /api/v1/system/<id>",
+    "GET /api/v1/system/<id>/permissions",
+    "GET /api/v1/tagged/<tag_id>?<filter..>",
+    "GET /api/v1/tagged/<tag_id>/groups?<lang>&<description>",
+    "GET /api/v1/tagged/<tag_id>/memberships/<username>?<lang>&<description>",
+    "GET /api/v1/tagged/<tag_id>/users",
+    "GET /api/v1/token/<secret>/permissions",
+    "GET /api/v1/token/<secret>/permission/<perm_id>",
+    "GET /api/v1/token/<secret>/permission/<perm_id>/scopes",
+    "GET /api/v1/token/<secret>/permission/<perm_id>/scope/<scope>",
+    "GET /api/v1/user/<username>/permissions",
+    "GET /api/v1/user/<username>/permission/<perm_id>",
+    "GET /api/v1/user/<username>/permission/<perm_id>/scopes",
+    "GET /api/v1/user/<username>/permission/<perm_id>/scope/<scope>",
+];
+
+// required query parameters, so that requests aren't rejected before any
+// permission checks
+const QUERIES: &[(&str, &str)] = &[
+    (
+        "GET /groups/parent-suggestions?<id>&<domain>",
+        "id=d-sys&domain=datasektionen.se",
+    ),
+    ("GET /groups/graph/export?<format>&<domain>", "format=dot"),
+    ("GET /permission-scopes?<perm>", "perm=%24calypso%3Apost"),
+    ("GET /users/autocomplete?<q>", "q=eva"),
+    (
+        "GET /api/v1/group/<group_domain>/<group_id>/authority?<user>",
+        "user=evae",
+    ),
+    (
+        "POST /api/v1/group/<group_domain>/<group_id>/memberships?<member..>",
+        "username=evae&from=2026-01-01&until=2026-12-31",
+    ),
+];
+
+// valid forms, for the same reason (handlers with contextual forms usually
+// only check permissions once the form is known to be valid)
+const FORMS: &[(&str, &str)] = &[
+    (
+        "POST /groups",
+        "id=test&domain=datasektionen.se&name_sv=Test&name_en=Test\
+        &description_sv=En+testgrupp&description_en=A+test+group&proposed=false",
+    ),
+    (
+        "POST /groups/bulk-tag",
+        "tag=%23calypso%3Aauthor&selected=d-sys%40datasektionen.se",
+    ),
+    (
+        "POST /groups/bulk-untag",
+        "tag=%23calypso%3Aauthor&selected=d-sys%40datasektionen.se",
+    ),
+    ("POST /import", "dump=%5B%5D&format=json&dry_run=true"),
+    ("POST /group/<domain>/<id>/state", "state=dormant"),
+    (
+        "POST /group/<domain>/<id>/members/bulk-remove",
+        "selected=00000000-0000-4000-8000-000000000001",
+    ),
+    (
+        "POST /group/<domain>/<id>/members/bulk-extend",
+        "selected=00000000-0000-4000-8000-000000000001",
+    ),
+    (
+        "POST /group/<domain>/<id>/members/bulk-toggle-manager",
+        "selected=00000000-0000-4000-8000-000000000001",
+    ),
+    (
+        "POST /group/<domain>/<id>/members/bulk-toggle-contact",
+        "selected=00000000-0000-4000-8000-000000000001",
+    ),
+    ("POST /group/<domain>/<id>/exclusions", "username=evae"),
+    (
+        "POST /group/<domain>/<id>/permissions",
+        "perm=%24calypso%3Apost",
+    ),
+    ("POST /group/<domain>/<id>/tags", "tag=%23calypso%3Aauthor"),
+    (
+        "POST /system/<id>/task/<task_id>/schedule",
+        "schedule=daily",
+    ),
+    (
+        "POST /operational-year/<id>",
+        "name=2026&start=2026-07-01&end=2027-06-30",
+    ),
+];
+
+// see seeds/dev.sql and fixtures/routes.sql
+const DEV_API_TOKEN: &str = "deadbeef-0000-4000-8000-000000000000";
+const MEMBERSHIP_ID: &str = "00000000-0000-4000-8000-000000000001";
+const CHANGE_ID: &str = "00000000-0000-4000-8000-000000000002";
+const PENDING_MEMBERSHIP_ID: &str = "00000000-0000-4000-8000-000000000003";
+const DELETION_ID: &str = "00000000-0000-4000-8000-000000000004";
+const MISSING_ID: &str = "00000000-0000-0000-0000-000000000000";
+
+#[derive(Clone, Copy)]
+enum Credentials {
+    Anonymous,
+    Unprivileged, // logged in, but without any memberships
+    DevToken,
+}
+
+async fn client(db: PgPool) -> Client {
+    let config: Config = serde_json::from_value(json!({
+        "db_url": "",
+        "secret_key": "ab".repeat(64),
+        "oidc_issuer_url": "",
+        "oidc_client_id": "",
+        "oidc_client_secret": "",
+    }))
+    .unwrap();
+
+    let replica = ReadReplica::new(None, &db);
+    let rocket = crate::build(&config, db, replica, OidcClient::offline(), None);
+
+    Client::untracked(rocket).await.unwrap()
+}
+
+fn key(route: &Route) -> String {
+    format!("{} {}", route.method, route.uri)
+}
+
+fn listed(list: &[&str], route: &Route) -> bool {
+    list.contains(&key(route).as_str())
+}
+
+fn is_api(route: &Route) -> bool {
+    route.uri.path().starts_with("/api/")
+}
+
+// something that exists in the dev seed (or in fixtures/routes.sql), based on
+// the parameter's name and the static segment preceding it
+fn param_value(prev: &str, name: &str) -> &'static str {
+    match (prev, name) {
+        ("group", "id") | (_, "group_id" | "parent_id") => "d-sys",
+        ("system", "id") | (_, "system_id" | "subtag_system_id") => "calypso",
+        (_, "domain" | "group_domain" | "parent_domain" | "child_domain") => "datasektionen.se",
+        (_, "child_id") => "ior",
+        (_, "username") => "evae",
+        (_, "perm_id") => "post",
+        (_, "tag_id" | "subtag_tag_id") => "author",
+        (_, "secret") => DEV_API_TOKEN,
+        ("group-membership" | "extend", "id") => MEMBERSHIP_ID,
+        ("change", "id") => CHANGE_ID,
+        ("pending-membership", "pending_id") => PENDING_MEMBERSHIP_ID,
+        ("deletion", "id") => DELETION_ID,
+        _ => MISSING_ID,
+    }
+}
+
+fn target(route: &Route) -> String {
+    let mut target = String::new();
+    let mut prev = "";
+
+    for segment in route.uri.path().split('/').skip(1) {
+        target.push('/');
+
+        if let Some(name) = segment.strip_prefix('<') {
+            let name = name.trim_end_matches('>').trim_end_matches("..");
+            target.push_str(param_value(prev, name));
+        } else {
+            target.push_str(segment);
+            prev = segment;
+        }
+    }
+
+    let key = key(route);
+    if let Some((_, query)) = QUERIES.iter().find(|(k, _)| *k == key) {
+        target.push('?');
+        target.push_str(query);
+    }
+
+    target
+}
+
+async fn send<'c>(
+    client: &'c Client,
+    route: &Route,
+    credentials: Credentials,
+) -> LocalResponse<'c> {
+    let key = key(route);
+
+    let (content_type, body) = if is_api(route) {
+        (ContentType::JSON, "{}")
+    } else {
+        let form = FORMS.iter().find(|(k, _)| *k == key);
+        (ContentType::Form, form.map(|(_, body)| *body).unwrap_or(""))
+    };
+
+    let mut request = client
+        .req(route.method, target(route))
+        .header(content_type)
+        .header(Header::new("HX-Request", "true"))
+        .body(body);
+
+    // not derived from the Host header by local requests
+    request
+        .inner_mut()
+        .set_host(Host::parse("localhost").unwrap());
+
+    match credentials {
+        Credentials::Anonymous => {}
+        Credentials::Unprivileged => {
+            let session = json!({
+                "username": "nobody",
+                "display_name": "Nobody",
+                "expiration": "2999-01-01T00:00:00+00:00",
+            });
+
+            request = request.private_cookie(Cookie::new("Hive-Auth", session.to_string()));
+        }
+        Credentials::DevToken => {
+            let authorization = format!("Bearer {DEV_API_TOKEN}");
+            request = request.header(Header::new("Authorization", authorization));
+        }
+    }
+
+    request.dispatch().await
+}
+
+// sends a request to every selected route, reporting all unexpected responses
+// at once
+async fn check_routes<S, F>(client: &Client, credentials: Credentials, select: S, expect: F)
+where
+    S: Fn(&Route) -> bool,
+    F: Fn(&Route, &LocalResponse<'_>) -> bool,
+{
+    let routes: Vec<_> = client.rocket().routes().filter(|r| select(r)).collect();
+    assert!(!routes.is_empty());
+
+    let mut failures = vec![];
+    for route in routes {
+        let response = send(client, route, credentials).await;
+
+        if !expect(route, &response) {
+            failures.push(format!("{} -> {}", key(route), response.status()));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "unexpected responses:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[sqlx::test(fixtures(path = "../../seeds", scripts("dev")), fixtures("routes"))]
+async fn anonymous_requests_are_rejected(db: PgPool) {
+    let client = client(db).await;
+
+    check_routes(
+        &client,
+        Credentials::Anonymous,
+        |route| !listed(PUBLIC, route),
+        |route, response| {
+            if is_api(route) {
+                response.status() == Status::Unauthorized
+            } else {
+                let location = response.headers().get_one("Location").unwrap_or_default();
+
+                response.status() == Status::SeeOther && location.starts_with("/auth/login")
+            }
+        },
+    )
+    .await;
+}
+
+#[sqlx::test(fixtures(path = "../../seeds", scripts("dev")), fixtures("routes"))]
+async fn unprivileged_users_are_forbidden(db: PgPool) {
+    let client = client(db).await;
+
+    check_routes(
+        &client,
+        Credentials::Unprivileged,
+        |route| !is_api(route) && !listed(PUBLIC, route) && !listed(OPEN, route),
+        |route, response| {
+            if listed(HIDDEN, route) {
+                response.status() == Status::NotFound
+            } else {
+                response.status() == Status::Forbidden
+            }
+        },
+    )
+    .await;
+}
+
+#[sqlx::test(fixtures(path = "../../seeds", scripts("dev")), fixtures("routes"))]
+async fn api_tokens_without_permissions_are_forbidden(db: PgPool) {
+    let client = client(db).await;
+
+    check_routes(
+        &client,
+        Credentials::DevToken,
+        |route| {
+            route.uri.path().starts_with("/api/v1/")
+                && !listed(PUBLIC, route)
+                && !listed(TOKEN_GRANTED, route)
+        },
+        |_, response| response.status() == Status::Forbidden,
+    )
+    .await;
+}
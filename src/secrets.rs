@@ -0,0 +1,180 @@
+use std::{borrow::Cow, sync::OnceLock, time::Duration};
+
+use log::*;
+use sqlx::{PgPool, postgres::PgConnectOptions};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const USER_AGENT: &str = "hive-secrets";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// Sensitive values (the database URL and integration settings marked as
+// secret) can be given as references to an external secrets manager instead
+// of being stored in plaintext:
+//   vault://<mount>/<path>#<field>  -- HashiCorp Vault (KV version 2 engine)
+//   sops://<file>#<key>             -- SOPS-encrypted file, decrypted with the
+//                                      `sops` binary (which takes care of any
+//                                      KMS/age/PGP keys by itself)
+//   file://<file>[#<key>]           -- plain file, e.g. mounted by orchestrator
+// Anything else is taken literally. References are resolved every time the
+// value is needed, such that rotated secrets are picked up automatically.
+
+// global rather than managed state so that background tasks can also use it
+static BACKEND: OnceLock<SecretsBackend> = OnceLock::new();
+
+#[derive(thiserror::Error, Debug)]
+pub enum SecretsError {
+    #[error("malformed secret reference `{0}`")]
+    MalformedReference(String),
+    #[error("secret reference `{0}` requires Vault, which is not configured")]
+    VaultNotConfigured(String),
+    #[error("failed to query Vault: {0}")]
+    Vault(#[from] reqwest::Error),
+    #[error("failed to read secret file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to decrypt secret file with sops: {0}")]
+    Sops(String),
+    #[error("secret source is not a valid JSON object: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("secret source has no string value for key `{0}`")]
+    MissingKey(String),
+}
+
+pub struct VaultConfig {
+    pub addr: String,
+    pub token: String,
+}
+
+struct SecretsBackend {
+    vault: Option<VaultConfig>,
+    client: reqwest::Client,
+}
+
+pub fn init(vault: Option<VaultConfig>) {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(USER_AGENT)
+        .build()
+        .expect("failed to build secrets reqwest client");
+
+    if BACKEND.set(SecretsBackend { vault, client }).is_err() {
+        warn!("Secrets backend was already initialized; ignoring");
+    }
+}
+
+pub fn is_reference(value: &str) -> bool {
+    ["vault://", "sops://", "file://"]
+        .iter()
+        .any(|prefix| value.starts_with(prefix))
+}
+
+pub async fn resolve(value: &str) -> Result<Cow<'_, str>, SecretsError> {
+    let malformed = || SecretsError::MalformedReference(value.to_owned());
+
+    if let Some(reference) = value.strip_prefix("vault://") {
+        let (path, field) = reference.split_once('#').ok_or_else(malformed)?;
+        let (mount, path) = path.split_once('/').ok_or_else(malformed)?;
+
+        let backend = BACKEND.get();
+        let Some((vault, client)) = backend.and_then(|b| Some((b.vault.as_ref()?, &b.client)))
+        else {
+            return Err(SecretsError::VaultNotConfigured(value.to_owned()));
+        };
+
+        let url = format!(
+            "{}/v1/{mount}/data/{path}",
+            vault.addr.trim_end_matches('/')
+        );
+
+        let response: serde_json::Value = client
+            .get(url)
+            .header("X-Vault-Token", &vault.token)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)?
+            .json()
+            .await?;
+
+        // (KV v2 wraps the secret's key-value pairs in another `data`)
+        extract(&response["data"]["data"], field).map(Cow::Owned)
+    } else if let Some(reference) = value.strip_prefix("sops://") {
+        let (file, key) = reference.split_once('#').ok_or_else(malformed)?;
+        let file = file.to_owned();
+
+        // no async process support in Rocket's Tokio, so block elsewhere
+        let output = rocket::tokio::task::spawn_blocking(move || {
+            std::process::Command::new("sops")
+                .args(["--decrypt", "--output-type", "json"])
+                .arg(file)
+                .output()
+        })
+        .await
+        .map_err(|e| SecretsError::Sops(e.to_string()))??;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(SecretsError::Sops(stderr.trim().to_owned()));
+        }
+
+        let decrypted = serde_json::from_slice(&output.stdout)?;
+
+        extract(&decrypted, key).map(Cow::Owned)
+    } else if let Some(reference) = value.strip_prefix("file://") {
+        let (file, key) = match reference.split_once('#') {
+            Some((file, key)) => (file, Some(key)),
+            None => (reference, None),
+        };
+
+        let content = rocket::tokio::fs::read_to_string(file).await?;
+
+        match key {
+            Some(key) => extract(&serde_json::from_str(&content)?, key).map(Cow::Owned),
+            None => Ok(Cow::Owned(content.trim_end().to_owned())),
+        }
+    } else {
+        Ok(Cow::Borrowed(value))
+    }
+}
+
+fn extract(object: &serde_json::Value, key: &str) -> Result<String, SecretsError> {
+    object
+        .get(key)
+        .and_then(serde_json::Value::as_str)
+        .map(str::to_owned)
+        .ok_or_else(|| SecretsError::MissingKey(key.to_owned()))
+}
+
+// meant to be spawned as a background task on startup (only if the URL is a
+// reference); new connections then use rotated credentials, while existing
+// ones keep working until they are recycled by the pool
+pub async fn refresh_db_url_periodically(reference: String, db: PgPool) {
+    let mut interval = rocket::tokio::time::interval(REFRESH_INTERVAL);
+    let mut current = None;
+
+    loop {
+        interval.tick().await;
+
+        let url = match resolve(&reference).await {
+            Ok(url) => url.into_owned(),
+            Err(e) => {
+                error!("Failed to refresh database URL from secrets manager: {e}");
+                continue;
+            }
+        };
+
+        if current.as_ref() == Some(&url) {
+            continue;
+        }
+
+        match url.parse::<PgConnectOptions>() {
+            Ok(options) => {
+                if current.is_some() {
+                    info!("Database URL was rotated; using it for new connections");
+                }
+
+                db.set_connect_options(options);
+                current = Some(url);
+            }
+            Err(e) => error!("Database URL from secrets manager is invalid: {e}"),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use log::*;
+use sqlx::PgPool;
+
+// secret of the API token created by the seed data, which is well-known and
+// thus obviously only suitable for local development
+const DEV_API_TOKEN_SECRET: &str = "deadbeef-0000-4000-8000-000000000000";
+
+// populates a fresh database with example data (see `seeds/dev.sql`); one
+// that already has groups of its own is left untouched, so that real data
+// can't accidentally be mixed up with made-up data
+pub async fn seed_dev_data(db: &PgPool) -> sqlx::Result<()> {
+    let populated: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT FROM groups WHERE domain <> $1)")
+            .bind(crate::HIVE_INTERNAL_DOMAIN)
+            .fetch_one(db)
+            .await?;
+
+    if populated {
+        warn!("Not seeding development data, since the database already has groups");
+        return Ok(());
+    }
+
+    // (sent as a single simple query, which Postgres runs as one implicit
+    // transaction, so a failing seed doesn't leave anything half-applied)
+    sqlx::raw_sql(include_str!("../seeds/dev.sql"))
+        .execute(db)
+        .await?;
+
+    warn!("Seeded development data; example API token secret is {DEV_API_TOKEN_SECRET}");
+
+    Ok(())
+}
@@ -1,10 +1,41 @@
+use sqlx::PgPool;
+
 pub mod api_tokens;
 pub mod audit_logs;
+pub mod calendar_feeds;
+pub mod changes;
+pub mod deletions;
+pub mod digests;
 pub mod groups;
+pub mod imports;
 pub mod integrations;
+pub mod logins;
+pub mod membership_emails;
+pub mod operational_years;
+pub mod orphans;
 pub mod permissions;
+pub mod perms_cache;
+pub mod public_statistics;
+pub mod self_preservation;
 pub mod systems;
 pub mod tags;
+pub mod webhooks;
+
+// pool for a read-only replica of the database, to which heavy read-only
+// queries can be offloaded (falls back to the primary if none is configured);
+// anything that writes, or must immediately observe preceding writes (e.g.,
+// authorization in the web interface), should keep using the primary pool
+pub struct ReadReplica(PgPool);
+
+impl ReadReplica {
+    pub fn new(replica: Option<PgPool>, primary: &PgPool) -> Self {
+        Self(replica.unwrap_or_else(|| primary.clone())) // cloning is cheap (Arc)
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.0
+    }
+}
 
 macro_rules! pg_args {
     ($($arg:expr),+) => {
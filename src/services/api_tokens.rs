@@ -1,16 +1,45 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{LazyLock, Mutex, PoisonError},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, NaiveDate};
+use hmac::{Hmac, KeyInit, Mac};
+use log::*;
 use serde_json::json;
 use sha2::Digest;
+use sqlx::{PgPool, prelude::FromRow};
 use uuid::Uuid;
 
-use super::audit_logs;
+use super::{audit_logs, deletions, tags};
 use crate::{
+    clock,
     dto::api_tokens::CreateApiTokenDto,
     errors::{AppError, AppResult},
-    guards::{perms::PermsEvaluator, user::User},
+    guards::{api::token::SignedRequest, perms::PermsEvaluator, user::User},
     models::{ActionKind, ApiToken, TargetKind},
     perms::{HivePermission, SystemsScope},
 };
 
+// how far off a signed request's timestamp can be from the current time
+const SIGNATURE_MAX_SKEW_SECS: i64 = 5 * 60;
+
+// how often buffered token usage is written to the database
+const USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+// token usage is buffered in memory and only periodically flushed, since
+// otherwise every single API request would write to the same few (very hot)
+// rows in api_tokens and api_token_usage (what's still pending is flushed once
+// more on shutdown, see `routing::shutdown`)
+static PENDING_USAGE: LazyLock<Mutex<HashMap<(Uuid, NaiveDate), PendingUsage>>> =
+    LazyLock::new(Default::default);
+
+struct PendingUsage {
+    last_used_at: DateTime<Local>,
+    n_requests: i32,
+}
+
 pub async fn list_for_system<'x, X>(system_id: &str, db: X) -> AppResult<Vec<ApiToken>>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
@@ -33,6 +62,342 @@ where
     Ok(api_tokens)
 }
 
+// tokens (of any system) with at least one permission of the given system;
+// n_perms only counts those permissions
+pub async fn list_holding_permissions_of<'x, X>(system_id: &str, db: X) -> AppResult<Vec<ApiToken>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let api_tokens = sqlx::query_as(
+        "SELECT at.*, COUNT(*) AS n_perms
+        FROM api_tokens at
+        JOIN permission_assignments pa
+            ON pa.api_token_id = at.id
+        WHERE pa.system_id = $1
+        GROUP BY at.id
+        ORDER BY at.system_id, at.description, at.id",
+    )
+    .bind(system_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(api_tokens)
+}
+
+pub async fn list_idle<'x, X>(
+    system_ids: &[String],
+    cutoff: DateTime<Local>,
+    db: X,
+) -> AppResult<Vec<ApiToken>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    // tokens without a creation stamp predate it being tracked at all
+    let api_tokens = sqlx::query_as(
+        "SELECT at.*,
+            (
+                SELECT COUNT(*)
+                FROM permission_assignments pa
+                WHERE pa.api_token_id = at.id
+            ) AS n_perms
+        FROM api_tokens at
+        WHERE system_id = ANY($1)
+            AND COALESCE(last_used_at, created_at, '-infinity') < $2
+        ORDER BY last_used_at NULLS FIRST, system_id, id",
+    )
+    .bind(system_ids)
+    .bind(cutoff)
+    .fetch_all(db)
+    .await?;
+
+    Ok(api_tokens)
+}
+
+// finds a valid (i.e., non-expired) token by its secret, recording its usage
+pub async fn authenticate<'x, X>(secret: Uuid, db: X) -> AppResult<Option<ApiToken>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let now = Local::now();
+
+    let token: Option<ApiToken> = sqlx::query_as(
+        "SELECT *
+        FROM api_tokens
+        WHERE secret = $1
+            AND (expires_at IS NULL OR expires_at >= $2)",
+    )
+    .bind(hash_secret(secret))
+    .bind(now)
+    .fetch_optional(db)
+    .await?;
+
+    if let Some(token) = &token {
+        record_usage(token.id, now);
+    }
+
+    Ok(token)
+}
+
+// like `authenticate`, but for places where only the bare secret is available
+// (i.e., not the v1 API consumer guard), such that tokens requiring signed
+// requests cannot be used there at all
+pub async fn authenticate_unsigned<'x, X>(secret: Uuid, db: X) -> AppResult<Option<ApiToken>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let token = authenticate(secret, db).await?;
+
+    Ok(token.filter(|token| !token.requires_signing))
+}
+
+#[derive(FromRow)]
+struct SigningApiToken {
+    #[sqlx(flatten)]
+    token: ApiToken,
+    signing_key: Option<String>,
+}
+
+// like `authenticate`, but for tokens that require requests to be signed
+// instead of including the secret; the signature is an HMAC-SHA256 keyed by
+// a separate signing key derived from the secret (see `derive_signing_key`);
+// note that this key is stored in plaintext, so it only keeps the secret off
+// the wire, and anyone who can read the database can still forge requests
+pub async fn authenticate_signed<'x, X>(
+    request: &SignedRequest<'_>,
+    db: X,
+) -> AppResult<Option<ApiToken>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let now = Local::now();
+
+    if (now.timestamp() - request.timestamp).abs() > SIGNATURE_MAX_SKEW_SECS {
+        // most likely a replay (or a very wrong clock)
+        return Ok(None);
+    }
+
+    let token: Option<SigningApiToken> = sqlx::query_as(
+        "SELECT *
+        FROM api_tokens
+        WHERE id = $1
+            AND requires_signing
+            AND (expires_at IS NULL OR expires_at >= $2)",
+    )
+    .bind(request.token_id)
+    .bind(now)
+    .fetch_optional(db)
+    .await?;
+
+    let Some(SigningApiToken {
+        token,
+        signing_key: Some(signing_key),
+    }) = token
+    else {
+        // tokens from before signing keys existed cannot be used at all
+        return Ok(None);
+    };
+
+    let (Ok(key), Ok(signature)) = (hex::decode(signing_key), hex::decode(request.signature))
+    else {
+        return Ok(None);
+    };
+
+    if !verify_hmac_sha256(&key, request.message.as_bytes(), &signature) {
+        return Ok(None);
+    }
+
+    record_usage(token.id, now);
+
+    Ok(Some(token))
+}
+
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    // (HMAC accepts keys of any length, so this can't fail)
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC key should be valid");
+    mac.update(message);
+
+    mac.finalize().into_bytes().to_vec()
+}
+
+// in constant time, to avoid leaking how much of a signature was correct
+pub(crate) fn verify_hmac_sha256(key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC key should be valid");
+    mac.update(message);
+
+    mac.verify_slice(signature).is_ok()
+}
+
+fn record_usage(id: Uuid, now: DateTime<Local>) {
+    let mut pending = PENDING_USAGE.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // daily buckets follow the configured timezone, like membership periods
+    let usage = pending.entry((id, clock::today())).or_insert(PendingUsage {
+        last_used_at: now,
+        n_requests: 0,
+    });
+
+    usage.last_used_at = usage.last_used_at.max(now);
+    usage.n_requests += 1;
+}
+
+pub async fn flush_usage(db: &PgPool) -> AppResult<usize> {
+    let pending =
+        std::mem::take(&mut *PENDING_USAGE.lock().unwrap_or_else(PoisonError::into_inner));
+
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    if let Err(e) = write_usage(&pending, db).await {
+        // put everything back so it's retried on the next flush, merged with
+        // whatever was recorded in the meantime
+        let mut buffer = PENDING_USAGE.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for (key, usage) in pending {
+            let merged = buffer.entry(key).or_insert(PendingUsage {
+                last_used_at: usage.last_used_at,
+                n_requests: 0,
+            });
+
+            merged.last_used_at = merged.last_used_at.max(usage.last_used_at);
+            merged.n_requests += usage.n_requests;
+        }
+
+        return Err(e);
+    }
+
+    Ok(pending.len())
+}
+
+async fn write_usage(
+    pending: &HashMap<(Uuid, NaiveDate), PendingUsage>,
+    db: &PgPool,
+) -> AppResult<()> {
+    let n = pending.len();
+
+    let mut ids = Vec::with_capacity(n);
+    let mut days = Vec::with_capacity(n);
+    let mut stamps = Vec::with_capacity(n);
+    let mut counts = Vec::with_capacity(n);
+
+    for ((id, day), usage) in pending {
+        ids.push(*id);
+        days.push(*day);
+        stamps.push(usage.last_used_at);
+        counts.push(usage.n_requests);
+    }
+
+    let mut txn = db.begin().await?;
+
+    // the same token can show up once per day (around midnight)
+    sqlx::query(
+        "UPDATE api_tokens at
+        SET last_used_at = GREATEST(at.last_used_at, u.last_used_at)
+        FROM (
+            SELECT id, MAX(stamp) AS last_used_at
+            FROM UNNEST($1::uuid[], $2::timestamptz[]) AS x (id, stamp)
+            GROUP BY id
+        ) u
+        WHERE at.id = u.id",
+    )
+    .bind(&ids)
+    .bind(&stamps)
+    .execute(&mut *txn)
+    .await?;
+
+    // tokens might have been deleted in the meantime
+    sqlx::query(
+        "INSERT INTO api_token_usage (api_token_id, day, n_requests)
+        SELECT u.id, u.day, u.n
+        FROM UNNEST($1::uuid[], $2::date[], $3::integer[]) AS u (id, day, n)
+        WHERE EXISTS (SELECT 1 FROM api_tokens at WHERE at.id = u.id)
+        ON CONFLICT (api_token_id, day)
+            DO UPDATE SET n_requests = api_token_usage.n_requests + EXCLUDED.n_requests",
+    )
+    .bind(&ids)
+    .bind(&days)
+    .bind(&counts)
+    .execute(&mut *txn)
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+// meant to be spawned as a background task on startup
+pub async fn flush_usage_periodically(db: PgPool) {
+    let mut interval = rocket::tokio::time::interval(USAGE_FLUSH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if crate::routing::maintenance::is_active() {
+            continue;
+        }
+
+        match flush_usage(&db).await {
+            Ok(0) => {}
+            Ok(n) => debug!("Flushed {n} buffered API token usage entries"),
+            Err(e) => error!("Failed to flush API token usage: {e}"),
+        }
+    }
+}
+
+pub enum GroupVisibility {
+    All,
+    Only(HashSet<(String, String)>), // (id, domain)
+}
+
+impl GroupVisibility {
+    pub fn allows(&self, group_id: &str, group_domain: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Only(groups) => groups.contains(&(group_id.to_owned(), group_domain.to_owned())),
+        }
+    }
+}
+
+pub async fn get_group_visibility<'x, X>(id: Uuid, db: X) -> AppResult<GroupVisibility>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+{
+    let restricted: bool =
+        sqlx::query_scalar("SELECT restricted_to_groups FROM api_tokens WHERE id = $1")
+            .bind(id)
+            .fetch_optional(db)
+            .await?
+            .unwrap_or(true); // deleted in the meantime; err on the safe side
+
+    if !restricted {
+        return Ok(GroupVisibility::All);
+    }
+
+    // (subtags are considered as well, like in the API's tagged listings)
+    let groups = sqlx::query_as(
+        "SELECT g.id, g.domain
+        FROM groups g
+        JOIN api_token_group_restrictions r
+            ON r.group_domain = g.domain
+        WHERE r.api_token_id = $1
+        UNION
+        SELECT ta.group_id, ta.group_domain
+        FROM all_tag_assignments ta
+        JOIN api_token_group_restrictions r
+            ON r.system_id = ta.system_id
+            AND r.tag_id = ta.tag_id
+        WHERE r.api_token_id = $1
+            AND ta.group_id IS NOT NULL",
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(GroupVisibility::Only(HashSet::from_iter(groups)))
+}
+
 pub struct ApiTokenCreationResult {
     pub token: ApiToken,
     pub secret: Uuid,
@@ -49,21 +414,58 @@ where
 {
     let secret = Uuid::new_v4();
     let hash = hash_secret(secret);
+    let signing_key = derive_signing_key(secret);
 
     let mut txn = db.begin().await?;
 
+    let group_domains = dto.restricted_group_domains();
+    let group_tags = dto.restricted_group_tags();
+    let restricted_to_groups = !group_domains.is_empty() || !group_tags.is_empty();
+
     let token: ApiToken = sqlx::query_as(
-        "INSERT INTO api_tokens (secret, system_id, description, expires_at) VALUES ($1, $2, $3, \
-         $4) RETURNING *",
+        "INSERT INTO api_tokens (secret, signing_key, system_id, description, expires_at, \
+         restricted_to_groups, requires_signing, read_only, groups_only, permissions_only) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING *",
     )
     .bind(hash)
+    .bind(signing_key)
     .bind(system_id)
     .bind(dto.description)
     .bind(&dto.expiration)
+    .bind(restricted_to_groups)
+    .bind(dto.requires_signing)
+    .bind(dto.read_only)
+    .bind(dto.groups_only)
+    .bind(dto.permissions_only)
     .fetch_one(&mut *txn)
     .await
     .map_err(|e| AppError::AmbiguousApiToken(dto.description.to_string()).if_unique_violation(e))?;
 
+    for group_domain in &group_domains {
+        sqlx::query(
+            "INSERT INTO api_token_group_restrictions (api_token_id, group_domain) VALUES ($1, $2)",
+        )
+        .bind(token.id)
+        .bind(group_domain)
+        .execute(&mut *txn)
+        .await?;
+    }
+
+    for tag_id in &group_tags {
+        // only the system's own tags can be used
+        tags::require_one(system_id, tag_id, &mut *txn).await?;
+
+        sqlx::query(
+            "INSERT INTO api_token_group_restrictions (api_token_id, system_id, tag_id) VALUES \
+             ($1, $2, $3)",
+        )
+        .bind(token.id)
+        .bind(system_id)
+        .bind(tag_id)
+        .execute(&mut *txn)
+        .await?;
+    }
+
     audit_logs::add_entry(
         ActionKind::Create,
         TargetKind::ApiToken,
@@ -73,7 +475,13 @@ where
             "new": {
                 "system_id": system_id,
                 "description": dto.description,
-                "expires_at": dto.expiration
+                "expires_at": dto.expiration,
+                "group_domains": group_domains,
+                "group_tags": group_tags,
+                "requires_signing": dto.requires_signing,
+                "read_only": dto.read_only,
+                "groups_only": dto.groups_only,
+                "permissions_only": dto.permissions_only,
             }
         }),
         &mut *txn,
@@ -96,6 +504,9 @@ where
 {
     let mut txn = db.begin().await?;
 
+    // (only restorable from the trash, since there is no undo button for it)
+    deletions::track(TargetKind::ApiToken, id, user.username(), &mut *txn).await?;
+
     let old: ApiToken = sqlx::query_as("DELETE FROM api_tokens WHERE id = $1 RETURNING *")
         .bind(id)
         .fetch_optional(&mut *txn)
@@ -104,6 +515,8 @@ where
     // error is 403 instead of 404 to prevent enumeration; we haven't checked
     // any permissions yet
 
+    deletions::untrack(&mut *txn).await?;
+
     perms
         .require_any_of(&[
             HivePermission::ManageSystems,
@@ -133,8 +546,87 @@ where
     Ok(old)
 }
 
+pub async fn toggle_signing<'x, X>(
+    id: &Uuid,
+    db: X,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<ApiToken>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let token: ApiToken = sqlx::query_as(
+        "UPDATE api_tokens at
+        SET requires_signing = NOT requires_signing
+        WHERE id = $1
+        RETURNING at.*,
+            (
+                SELECT COUNT(*)
+                FROM permission_assignments pa
+                WHERE pa.api_token_id = at.id
+            ) AS n_perms",
+    )
+    .bind(id)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or_else(|| AppError::NotAllowed(HivePermission::ManageSystems))?;
+    // error is 403 instead of 404 to prevent enumeration; we haven't checked
+    // any permissions yet
+
+    perms
+        .require_any_of(&[
+            HivePermission::ManageSystems,
+            HivePermission::ManageSystem(SystemsScope::Id(token.system_id.to_owned())),
+        ])
+        .await?;
+
+    if token.requires_signing {
+        let has_signing_key: bool =
+            sqlx::query_scalar("SELECT signing_key IS NOT NULL FROM api_tokens WHERE id = $1")
+                .bind(id)
+                .fetch_one(&mut *txn)
+                .await?;
+
+        if !has_signing_key {
+            // the raw secret is long gone, so there is no way to derive one
+            return Err(AppError::UnsignableApiToken(*id));
+        }
+    }
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::ApiToken,
+        id,
+        user.username(),
+        json!({
+            "old": {
+                "system_id": token.system_id,
+                "requires_signing": !token.requires_signing,
+            },
+            "new": {
+                "system_id": token.system_id,
+                "requires_signing": token.requires_signing,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(token)
+}
+
 pub fn hash_secret(secret: Uuid) -> String {
     let hash = sha2::Sha256::new_with_prefix(secret).finalize();
 
     hex::encode(hash)
 }
+
+// clients compute the same thing from their copy of the secret; it cannot be
+// reconstructed from the hash, but is itself stored alongside it (unencrypted)
+fn derive_signing_key(secret: Uuid) -> String {
+    hex::encode(hmac_sha256(secret.as_bytes(), b"hive-signing"))
+}
@@ -1,7 +1,10 @@
+use chrono::{DateTime, Local};
+
 use crate::{
     dto::logs::LogsFilterDto,
     errors::AppResult,
-    models::{ActionKind, AuditLog, TargetKind},
+    models::{ActionKind, AuditLog, GroupActivity, TargetKind},
+    resolver::IdentityResolver,
 };
 
 pub async fn add_entry<'a, 'q, X>(
@@ -92,3 +95,107 @@ where
 
     Ok(ids.into_iter().map(|id| id.0).collect())
 }
+
+pub async fn list_recent_for_system<'a, X>(
+    system_id: &str,
+    limit: u32,
+    db: X,
+) -> AppResult<Vec<AuditLog>>
+where
+    X: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let logs = sqlx::query_as(
+        "SELECT action_kind,
+            target_kind,
+            target_id,
+            actor,
+            details,
+            stamp
+        FROM audit_logs
+        WHERE (target_kind = 'system' AND target_id = $1)
+            OR (
+                target_kind IN ('permission', 'permission_assignment')
+                AND STARTS_WITH(target_id, '$' || $1 || ':')
+            )
+            OR (
+                target_kind IN ('tag', 'tag_assignment')
+                AND STARTS_WITH(target_id, '#' || $1 || ':')
+            )
+            OR (
+                -- tokens might not exist anymore, so rely on details instead
+                target_kind = 'api_token'
+                AND COALESCE(
+                    details->'new'->>'system_id',
+                    details->'old'->>'system_id'
+                ) = $1
+            )
+        ORDER BY stamp DESC
+        LIMIT $2",
+    )
+    .bind(system_id)
+    .bind(i32::try_from(limit).unwrap_or(10))
+    .fetch_all(db)
+    .await?;
+
+    Ok(logs)
+}
+
+pub async fn list_recent_for_group<'a, X>(
+    id: &str,
+    domain: &str,
+    since: Option<DateTime<Local>>,
+    limit: u32,
+    db: X,
+    resolver: Option<&IdentityResolver>,
+) -> AppResult<Vec<GroupActivity>>
+where
+    X: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let logs: Vec<AuditLog> = sqlx::query_as(
+        "SELECT action_kind,
+            target_kind,
+            target_id,
+            actor,
+            details,
+            stamp
+        FROM audit_logs
+        WHERE (
+                (
+                    target_kind IN ('group', 'membership')
+                    AND target_id = $1 || '@' || $2
+                )
+                OR (
+                    -- assignments are keyed by tag/permission, so rely on details
+                    target_kind IN ('permission_assignment', 'tag_assignment')
+                    AND COALESCE(details->'new', details->'old') @> JSONB_BUILD_OBJECT(
+                        'entity_type', 'group',
+                        'group_id', $1,
+                        'group_domain', $2
+                    )
+                )
+            )
+            AND ($3::TIMESTAMPTZ IS NULL OR stamp >= $3)
+        ORDER BY stamp DESC
+        LIMIT $4",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(since)
+    .bind(i32::try_from(limit).unwrap_or(50))
+    .fetch_all(db)
+    .await?;
+
+    let mut activity: Vec<GroupActivity> = logs.into_iter().map(Into::into).collect();
+
+    if let Some(resolver) = resolver {
+        resolver
+            .populate_identities(
+                &mut activity,
+                |entry| &entry.actor,
+                |entry, name| entry.actor_display_name = Some(name),
+            )
+            .await?;
+    }
+
+    Ok(activity)
+}
@@ -0,0 +1,118 @@
+use chrono::Days;
+use uuid::Uuid;
+
+use crate::{
+    clock,
+    errors::AppResult,
+    models::{CalendarFeed, UserMembership},
+};
+
+// how far back past memberships are still included in feeds
+pub const FEED_HISTORY: Days = Days::new(365);
+
+pub async fn get_one<'x, X>(secret: &Uuid, db: X) -> AppResult<Option<CalendarFeed>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let feed = sqlx::query_as("SELECT * FROM calendar_feeds WHERE secret = $1")
+        .bind(secret)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(feed)
+}
+
+// group is None for the owner's personal feed
+pub async fn get_or_create<'x, X>(
+    owner: &str,
+    group: Option<(&str, &str)>,
+    db: X,
+) -> AppResult<CalendarFeed>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let (group_id, group_domain) = group.unzip();
+
+    let mut txn = db.begin().await?;
+
+    let created: Option<CalendarFeed> = sqlx::query_as(
+        "INSERT INTO calendar_feeds (owner, group_id, group_domain)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        RETURNING *",
+    )
+    .bind(owner)
+    .bind(group_id)
+    .bind(group_domain)
+    .fetch_optional(&mut *txn)
+    .await?;
+
+    let feed = if let Some(feed) = created {
+        feed
+    } else {
+        // already exists, so just reuse it
+        sqlx::query_as(
+            "SELECT *
+            FROM calendar_feeds
+            WHERE owner = $1
+                AND group_id IS NOT DISTINCT FROM $2
+                AND group_domain IS NOT DISTINCT FROM $3",
+        )
+        .bind(owner)
+        .bind(group_id)
+        .bind(group_domain)
+        .fetch_one(&mut *txn)
+        .await?
+    };
+
+    txn.commit().await?;
+
+    Ok(feed)
+}
+
+// the next call to `get_or_create` will then yield a brand new secret
+pub async fn revoke<'x, X>(owner: &str, group: Option<(&str, &str)>, db: X) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let (group_id, group_domain) = group.unzip();
+
+    sqlx::query(
+        "DELETE FROM calendar_feeds
+        WHERE owner = $1
+            AND group_id IS NOT DISTINCT FROM $2
+            AND group_domain IS NOT DISTINCT FROM $3",
+    )
+    .bind(owner)
+    .bind(group_id)
+    .bind(group_domain)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_user_memberships<'x, X>(username: &str, db: X) -> AppResult<Vec<UserMembership>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let since = clock::today() - FEED_HISTORY;
+
+    let memberships = sqlx::query_as(
+        "SELECT dm.id AS membership_id, dm.\"from\", dm.until, dm.manager,
+            g.id, g.domain, g.name_sv, g.name_en
+        FROM direct_memberships dm
+        JOIN groups g
+            ON g.id = dm.group_id
+            AND g.domain = dm.group_domain
+        WHERE dm.username = $1
+            AND dm.until >= $2
+        ORDER BY dm.until, g.id, g.domain",
+    )
+    .bind(username)
+    .bind(since)
+    .fetch_all(db)
+    .await?;
+
+    Ok(memberships)
+}
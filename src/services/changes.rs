@@ -0,0 +1,416 @@
+use std::collections::HashSet;
+
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{
+    audit_logs,
+    groups::{self, AuthorityInGroup},
+    permissions, perms_cache,
+};
+use crate::{
+    dto::{
+        groups::{AddSubgroupDto, GroupRefDto},
+        permissions::{AssignPermissionDto, PermissionKey},
+    },
+    errors::{AppError, AppResult},
+    guards::{perms::PermsEvaluator, user::User},
+    models::{ActionKind, PendingChange, PendingChangeKind, TargetKind},
+    perms::{self, HivePermission, SystemsScope},
+};
+
+// domains in which structural changes (new subgroups and permission
+// assignments) only take effect once approved by a second authorized person
+pub struct ProtectedDomains(HashSet<String>);
+
+impl ProtectedDomains {
+    pub fn new(domains: Vec<String>) -> Self {
+        Self(domains.into_iter().collect())
+    }
+
+    pub fn contains(&self, domain: &str) -> bool {
+        self.0.contains(domain)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// changes that the user either proposed themselves or can review
+pub async fn list_relevant<'x, X>(
+    db: X,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<Vec<PendingChange>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+{
+    let changes: Vec<PendingChange> =
+        sqlx::query_as("SELECT * FROM pending_changes ORDER BY proposed_at")
+            .fetch_all(db)
+            .await?;
+
+    let mut relevant = Vec::with_capacity(changes.len());
+    for mut change in changes {
+        if change.proposed_by == user.username() {
+            change.can_review = Some(false);
+        } else if is_authorized_for(&change, db, perms, user).await? {
+            change.can_review = Some(true);
+        } else {
+            continue;
+        }
+
+        relevant.push(change);
+    }
+
+    Ok(relevant)
+}
+
+pub async fn require_one<'x, X>(id: &Uuid, db: X) -> AppResult<PendingChange>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    sqlx::query_as("SELECT * FROM pending_changes WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(AppError::NoSuchPendingChange(*id))
+}
+
+// the user must be allowed to make the change directly in order to review it
+// (approve or reject), which is checked in the same way as when making it
+pub async fn require_authorized_for<'x, X>(
+    change: &PendingChange,
+    db: X,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+{
+    match (
+        change.kind,
+        &change.child_id,
+        &change.child_domain,
+        &change.system_id,
+    ) {
+        (PendingChangeKind::AddSubgroup, Some(child_id), Some(child_domain), _) => {
+            groups::details::require_authority(
+                AuthorityInGroup::ManageMembers,
+                &change.group_id,
+                &change.group_domain,
+                db,
+                perms,
+                user,
+            )
+            .await?;
+
+            groups::details::require_authority(
+                AuthorityInGroup::View,
+                child_id,
+                child_domain,
+                db,
+                perms,
+                user,
+            )
+            .await?;
+        }
+        (PendingChangeKind::AssignPermission, _, _, Some(system_id)) => {
+            let min = HivePermission::AssignPerms(SystemsScope::Id(system_id.clone()));
+            perms.require(min).await?;
+        }
+        _ => return Err(AppError::NoSuchPendingChange(change.id)), // prevented by DB constraint
+    }
+
+    Ok(())
+}
+
+pub async fn is_authorized_for<'x, X>(
+    change: &PendingChange,
+    db: X,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<bool>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+{
+    match require_authorized_for(change, db, perms, user).await {
+        Ok(()) => Ok(true),
+        Err(AppError::NotAllowed(..) | AppError::InsufficientAuthorityInGroup(..)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn audit_log_details(change: &PendingChange, state: &str) -> serde_json::Value {
+    match change.kind {
+        PendingChangeKind::AddSubgroup => json!({
+            "id": change.id,
+            "kind": "add_subgroup",
+            "child_id": change.child_id,
+            "child_domain": change.child_domain,
+            "manager": change.manager,
+            "proposed_by": change.proposed_by,
+            "state": state,
+        }),
+        PendingChangeKind::AssignPermission => json!({
+            "id": change.id,
+            "kind": "assign_permission",
+            "system_id": change.system_id,
+            "perm_id": change.perm_id,
+            "scope": change.scope,
+            "proposed_by": change.proposed_by,
+            "state": state,
+        }),
+    }
+}
+
+async fn log_state<'x, X>(
+    change: &PendingChange,
+    old_state: Option<&str>,
+    new_state: &str,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let details = if let Some(old_state) = old_state {
+        json!({
+            "old": {
+                "pending_change": audit_log_details(change, old_state),
+            },
+            "new": {
+                "pending_change": audit_log_details(change, new_state),
+            }
+        })
+    } else {
+        json!({
+            "new": {
+                "pending_change": audit_log_details(change, new_state),
+            }
+        })
+    };
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        change.group_key(),
+        user.username(),
+        details,
+        db,
+    )
+    .await
+}
+
+pub async fn propose_subgroup<'v, 'x, X>(
+    parent_id: &str,
+    parent_domain: &str,
+    dto: &AddSubgroupDto<'v>,
+    db: X,
+    user: &User,
+) -> AppResult<PendingChange>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    if parent_id == dto.child.id && parent_domain == dto.child.domain {
+        // can't be a subgroup of itself; other problems (e.g., loops or
+        // duplicates) are only detected upon approval, since the hierarchy
+        // might change in the meantime anyway
+        return Err(AppError::InvalidSubgroup(
+            parent_id.to_owned(),
+            parent_domain.to_owned(),
+        ));
+    }
+
+    perms::require_valid_nesting(parent_domain, dto.child.id, dto.child.domain)?;
+
+    let mut txn = db.begin().await?;
+
+    let change: PendingChange = sqlx::query_as(
+        "INSERT INTO pending_changes (kind, group_id, group_domain, child_id, child_domain, \
+         manager, proposed_by)
+        VALUES ('add_subgroup', $1, $2, $3, $4, $5, $6)
+        RETURNING *",
+    )
+    .bind(parent_id)
+    .bind(parent_domain)
+    .bind(dto.child.id)
+    .bind(dto.child.domain)
+    .bind(dto.manager)
+    .bind(user.username())
+    .fetch_one(&mut *txn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(err) if err.is_foreign_key_violation() => {
+            AppError::NoSuchGroup(dto.child.id.to_string(), dto.child.domain.to_string())
+        }
+        _ => e.into(),
+    })?;
+
+    log_state(&change, None, "pending", &mut *txn, user).await?;
+
+    txn.commit().await?;
+
+    Ok(change)
+}
+
+pub async fn propose_permission<'v, 'x, X>(
+    group_id: &str,
+    group_domain: &str,
+    dto: &AssignPermissionDto<'v>,
+    db: X,
+    user: &User,
+) -> AppResult<PendingChange>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    // validate what we can beforehand so that mistakes don't need a review
+    perms::require_internal_domain(group_domain)?;
+
+    let mut txn = db.begin().await?;
+
+    let has_scope = permissions::has_scope(dto.perm.system_id, dto.perm.perm_id, &mut *txn).await?;
+
+    if has_scope && dto.scope.is_none() {
+        return Err(AppError::MissingPermissionScope(
+            dto.perm.system_id.to_string(),
+            dto.perm.perm_id.to_string(),
+        ));
+    } else if !has_scope && dto.scope.is_some() {
+        return Err(AppError::ExtraneousPermissionScope(
+            dto.perm.system_id.to_string(),
+            dto.perm.perm_id.to_string(),
+        ));
+    }
+
+    permissions::validate_scope(
+        dto.perm.system_id,
+        dto.perm.perm_id,
+        dto.scope.as_deref().copied(),
+    )?;
+
+    let change: PendingChange = sqlx::query_as(
+        "INSERT INTO pending_changes (kind, group_id, group_domain, system_id, perm_id, scope, \
+         proposed_by)
+        VALUES ('assign_permission', $1, $2, $3, $4, $5, $6)
+        RETURNING *",
+    )
+    .bind(group_id)
+    .bind(group_domain)
+    .bind(dto.perm.system_id)
+    .bind(dto.perm.perm_id)
+    .bind(dto.scope)
+    .bind(user.username())
+    .fetch_one(&mut *txn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(err) if err.is_foreign_key_violation() => {
+            AppError::NoSuchGroup(group_id.to_string(), group_domain.to_string())
+        }
+        _ => e.into(),
+    })?;
+
+    log_state(&change, None, "pending", &mut *txn, user).await?;
+
+    txn.commit().await?;
+
+    Ok(change)
+}
+
+// removes the pending change, which only takes effect once the transaction is
+// committed (so callers can still bail out)
+async fn take<'x, X>(id: &Uuid, db: X) -> AppResult<PendingChange>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    sqlx::query_as("DELETE FROM pending_changes WHERE id = $1 RETURNING *")
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(AppError::NoSuchPendingChange(*id))
+}
+
+// callers must have checked `require_authorized_for` beforehand; the change is
+// applied as if the approver had made it themselves (including validation)
+pub async fn approve<'x, X>(id: &Uuid, db: X, user: &User) -> AppResult<PendingChange>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let change = take(id, &mut *txn).await?;
+
+    if change.proposed_by == user.username() {
+        return Err(AppError::SelfApproval);
+    }
+
+    match change.kind {
+        PendingChangeKind::AddSubgroup => {
+            let dto = AddSubgroupDto {
+                child: GroupRefDto {
+                    id: change.child_id.as_deref().unwrap_or_default(),
+                    domain: change.child_domain.as_deref().unwrap_or_default(),
+                },
+                manager: change.manager.unwrap_or_default(),
+            };
+
+            groups::members::add_subgroup(
+                &change.group_id,
+                &change.group_domain,
+                &dto,
+                &mut *txn,
+                user,
+            )
+            .await?;
+        }
+        PendingChangeKind::AssignPermission => {
+            let dto = AssignPermissionDto {
+                perm: PermissionKey {
+                    system_id: change.system_id.as_deref().unwrap_or_default(),
+                    perm_id: change.perm_id.as_deref().unwrap_or_default(),
+                },
+                scope: change.scope.as_deref().map(Into::into),
+            };
+
+            groups::permissions::assign(
+                &change.group_id,
+                &change.group_domain,
+                &dto,
+                &mut *txn,
+                user,
+            )
+            .await?;
+        }
+    }
+
+    log_state(&change, Some("pending"), "approved", &mut *txn, user).await?;
+
+    txn.commit().await?;
+    perms_cache::invalidate_all();
+
+    Ok(change)
+}
+
+// either rejected by a reviewer (callers must have checked
+// `require_authorized_for` beforehand) or withdrawn by whoever proposed it
+pub async fn reject<'x, X>(id: &Uuid, db: X, user: &User) -> AppResult<PendingChange>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let change = take(id, &mut *txn).await?;
+
+    let state = if change.proposed_by == user.username() {
+        "withdrawn"
+    } else {
+        "rejected"
+    };
+
+    log_state(&change, Some("pending"), state, &mut *txn, user).await?;
+
+    txn.commit().await?;
+
+    Ok(change)
+}
@@ -0,0 +1,319 @@
+use std::time::Duration;
+
+use chrono::{Local, TimeDelta};
+use log::*;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    errors::{AppError, AppResult},
+    guards::user::User,
+    models::{ActionKind, Deletion, TargetKind},
+    perms,
+    services::{audit_logs, changes::ProtectedDomains, perms_cache, self_preservation},
+};
+
+// how long the actor has to change their mind by themselves
+pub const UNDO_WINDOW: TimeDelta = TimeDelta::minutes(15);
+
+// how long deletions can still be restored from the trash before they become
+// final (by those with $hive:restore-deletions)
+pub const RETENTION_PERIOD: TimeDelta = TimeDelta::days(30);
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// tables with an archival trigger (see migrations); also acts as a whitelist,
+// since table names cannot be bound as query parameters. Queued membership
+// emails are deliberately left out, so that restoring never sends stale ones
+const ARCHIVED_TABLES: &[&str] = &[
+    "groups",
+    "direct_memberships",
+    "subgroups",
+    "membership_exclusions",
+    "calendar_feeds",
+    "permissions",
+    "permission_assignments",
+    "tags",
+    "tag_assignments",
+    "subtags",
+    "tag_derivations",
+    "group_links",
+    "api_tokens",
+    "api_token_group_restrictions",
+    "ownership_transfers",
+    "pending_changes",
+    "group_aliases",
+    "member_list_shares",
+    "group_watchers",
+    "group_member_counts",
+    "pending_memberships",
+    "group_invitations",
+    "group_comments",
+];
+
+// must be called in the same transaction as the actual DELETE query, before
+// it; every row deleted afterwards in that transaction (including cascades) is
+// archived so that it can be restored by `undo`, until `untrack` is called
+pub async fn track<'x, X>(
+    target_kind: TargetKind,
+    target_id: impl ToString,
+    actor: &str,
+    db: X,
+) -> AppResult<Uuid>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let id = sqlx::query_scalar(
+        "WITH d AS (
+            INSERT INTO deletions (target_kind, target_id, actor)
+            VALUES ($1, $2, $3)
+            RETURNING id
+        )
+        SELECT id
+        FROM d, set_config('hive.deletion_id', id::TEXT, TRUE)",
+    )
+    .bind(target_kind)
+    .bind(target_id.to_string())
+    .bind(actor)
+    .fetch_one(db)
+    .await?;
+
+    Ok(id)
+}
+
+// must be called right after the tracked DELETE query, so that any unrelated
+// deletions later in the same (possibly outer) transaction aren't archived
+// under the wrong deletion
+pub async fn untrack<'x, X>(db: X) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    sqlx::query("SELECT set_config('hive.deletion_id', '', TRUE)")
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+// most recent first
+pub async fn list_restorable<'x, X>(db: X) -> AppResult<Vec<Deletion>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let deletions = sqlx::query_as(
+        "SELECT d.*, COUNT(dr.seq) AS n_rows
+        FROM deletions d
+        LEFT JOIN deleted_rows dr
+            ON dr.deletion_id = d.id
+        WHERE d.deleted_at > $1
+        GROUP BY d.id
+        ORDER BY d.deleted_at DESC",
+    )
+    .bind(Local::now() - RETENTION_PERIOD)
+    .fetch_all(db)
+    .await?;
+
+    Ok(deletions)
+}
+
+// only for the deletion's own actor, shortly after deleting
+pub async fn undo<'x, X>(
+    id: &Uuid,
+    protected: &ProtectedDomains,
+    db: X,
+    user: &User,
+) -> AppResult<Deletion>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    restore_since(id, Some(user.username()), UNDO_WINDOW, protected, db, user).await
+}
+
+// for anyone's deletion from the trash; callers must have checked that the
+// user has $hive:restore-deletions beforehand
+pub async fn restore<'x, X>(
+    id: &Uuid,
+    protected: &ProtectedDomains,
+    db: X,
+    user: &User,
+) -> AppResult<Deletion>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    restore_since(id, None, RETENTION_PERIOD, protected, db, user).await
+}
+
+// archived rows are restored verbatim, so anything that the services would
+// refuse (or only propose for approval) when creating them is rejected here
+fn require_restorable(
+    table: &str,
+    row: &serde_json::Value,
+    protected: &ProtectedDomains,
+) -> AppResult<()> {
+    let field = |name| row.get(name).and_then(serde_json::Value::as_str);
+
+    match table {
+        "subgroups" => {
+            if let (Some(parent_domain), Some(child_id), Some(child_domain)) = (
+                field("parent_domain"),
+                field("child_id"),
+                field("child_domain"),
+            ) {
+                perms::require_valid_nesting(parent_domain, child_id, child_domain)?;
+
+                if protected.contains(parent_domain) {
+                    return Err(AppError::UnapprovableProtectedChange(
+                        parent_domain.to_owned(),
+                    ));
+                }
+            }
+        }
+        "permission_assignments" => {
+            // (API token assignments have no group)
+            if let Some(group_domain) = field("group_domain") {
+                perms::require_internal_domain(group_domain)?;
+
+                if protected.contains(group_domain) {
+                    return Err(AppError::UnapprovableProtectedChange(
+                        group_domain.to_owned(),
+                    ));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+async fn restore_since<'x, X>(
+    id: &Uuid,
+    actor: Option<&str>,
+    window: TimeDelta,
+    protected: &ProtectedDomains,
+    db: X,
+    user: &User,
+) -> AppResult<Deletion>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let deletion: Deletion = sqlx::query_as(
+        "SELECT *
+        FROM deletions
+        WHERE id = $1
+            AND ($2::USERNAME IS NULL OR actor = $2)
+            AND deleted_at > $3
+        FOR UPDATE",
+    )
+    .bind(id)
+    .bind(actor)
+    .bind(Local::now() - window)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or_else(|| AppError::NoSuchDeletion(*id))?;
+
+    let rows: Vec<(String, serde_json::Value)> = sqlx::query_as(
+        "SELECT table_name, row_data
+        FROM deleted_rows
+        WHERE deletion_id = $1
+        ORDER BY seq",
+    )
+    .bind(id)
+    .fetch_all(&mut *txn)
+    .await?;
+
+    // restoring must not grant (or revoke) any of Hive's own permissions,
+    // which would otherwise require other permissions than restoring does
+    let hive_grants = self_preservation::snapshot(&mut *txn).await?;
+
+    for (table, row) in &rows {
+        let Some(table) = ARCHIVED_TABLES.iter().find(|t| **t == table.as_str()) else {
+            error!("Refusing to restore row into unknown table `{table}`");
+            return Err(AppError::UndoConflict);
+        };
+
+        require_restorable(table, row, protected)?;
+
+        // rows are archived whole, so inserting all columns as-is is fine
+        let query = format!(
+            "INSERT INTO {table}
+            SELECT *
+            FROM jsonb_populate_record(NULL::{table}, $1)"
+        );
+
+        sqlx::query(&query)
+            .bind(row)
+            .execute(&mut *txn)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::Database(ref db_err)
+                    if db_err.is_unique_violation() || db_err.is_foreign_key_violation() =>
+                {
+                    AppError::UndoConflict
+                }
+                _ => AppError::from(err),
+            })?;
+    }
+
+    self_preservation::require_unchanged(&hive_grants, &mut txn, user.username()).await?;
+
+    sqlx::query("DELETE FROM deletions WHERE id = $1")
+        .bind(id)
+        .execute(&mut *txn)
+        .await?;
+
+    audit_logs::add_entry(
+        ActionKind::Create,
+        deletion.target_kind.clone(),
+        &deletion.target_id,
+        user.username(),
+        json!({
+            "new": {
+                "undone_deletion": deletion.id,
+                "restored_rows": rows.len(),
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+    perms_cache::invalidate_all();
+
+    Ok(deletion)
+}
+
+pub async fn purge_expired<'x, X>(db: X) -> AppResult<u64>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    // archived rows are removed via ON DELETE CASCADE
+    let result = sqlx::query("DELETE FROM deletions WHERE deleted_at <= $1")
+        .bind(Local::now() - RETENTION_PERIOD)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+// meant to be spawned as a background task on startup
+pub async fn purge_periodically(db: PgPool) {
+    let mut interval = rocket::tokio::time::interval(CLEANUP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if crate::routing::maintenance::is_active() {
+            continue;
+        }
+
+        match purge_expired(&db).await {
+            Ok(0) => {}
+            Ok(n) => debug!("Permanently purged {n} expired deletions"),
+            Err(e) => error!("Failed to purge expired deletions: {e}"),
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    collections::{BTreeMap, HashMap, hash_map::Entry},
+    time::Duration,
+};
+
+use chrono::{DateTime, Days, Local, TimeDelta};
+use log::*;
+use sqlx::{FromRow, PgPool, Row};
+
+use crate::{
+    clock,
+    errors::AppResult,
+    mailer::Mailer,
+    models::{ActionKind, Group, GroupActivityKind},
+    resolver::UserEmailDomain,
+    services::{audit_logs, groups},
+};
+
+// how often to check whether a new round of digests is due (rounds themselves
+// are only sent once every DIGEST_PERIOD)
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DIGEST_PERIOD: TimeDelta = TimeDelta::days(7);
+
+// memberships ending within this many days are mentioned as expiring
+const EXPIRY_HORIZON: Days = Days::new(14);
+
+// entries beyond this are summarized as "and more" (see the group's page)
+const MAX_ACTIVITY_PER_GROUP: u32 = 100;
+
+pub struct ManagerDigests(bool);
+
+impl ManagerDigests {
+    pub fn new(enabled: bool) -> Self {
+        Self(enabled)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0
+    }
+}
+
+pub async fn is_opted_out<'x, X>(username: &str, db: X) -> AppResult<bool>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let opted_out = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0
+        FROM digest_opt_outs
+        WHERE username = $1",
+    )
+    .bind(username)
+    .fetch_one(db)
+    .await?;
+
+    Ok(opted_out)
+}
+
+pub async fn set_opted_out<'x, X>(username: &str, opted_out: bool, db: X) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let query = if opted_out {
+        "INSERT INTO digest_opt_outs (username)
+        VALUES ($1)
+        ON CONFLICT DO NOTHING"
+    } else {
+        "DELETE FROM digest_opt_outs
+        WHERE username = $1"
+    };
+
+    sqlx::query(query).bind(username).execute(db).await?;
+
+    Ok(())
+}
+
+// meant to be spawned as a background task on startup (only if enabled)
+pub async fn send_periodically(db: PgPool, mailer: Mailer, email_domain: UserEmailDomain) {
+    let mut interval = rocket::tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if crate::routing::maintenance::is_active() {
+            continue;
+        }
+
+        match send_if_due(&db, &mailer, &email_domain).await {
+            Ok(None) => {}
+            Ok(Some(n)) => info!("Sent weekly digests to {n} managers"),
+            Err(e) => error!("Failed to send weekly digests: {e}"),
+        }
+    }
+}
+
+async fn send_if_due(
+    db: &PgPool,
+    mailer: &Mailer,
+    email_domain: &UserEmailDomain,
+) -> AppResult<Option<usize>> {
+    let mut txn = db.begin().await?;
+
+    // (so that multiple instances can't both decide that a round is due)
+    sqlx::query("LOCK TABLE digest_runs IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *txn)
+        .await?;
+
+    let last: Option<DateTime<Local>> = sqlx::query_scalar("SELECT MAX(stamp) FROM digest_runs")
+        .fetch_one(&mut *txn)
+        .await?;
+
+    let now = Local::now();
+    if last.is_some_and(|last| now - last < DIGEST_PERIOD) {
+        return Ok(None);
+    }
+
+    // recorded before sending anything, since missing a round is better than
+    // spamming everyone again if something fails halfway through
+    let stamp: DateTime<Local> =
+        sqlx::query_scalar("INSERT INTO digest_runs DEFAULT VALUES RETURNING stamp")
+            .fetch_one(&mut *txn)
+            .await?;
+
+    txn.commit().await?;
+
+    // never more than a period, e.g. if digests were disabled for a while
+    let since = last.map_or(now - DIGEST_PERIOD, |last| last.max(now - DIGEST_PERIOD));
+
+    let recipients = list_recipients(db).await?;
+
+    // (groups often have several managers, so each is only summarized once)
+    let mut summaries = HashMap::new(); // group key -> summary, if anything
+    for group in recipients.iter().flat_map(|(_, managed)| managed) {
+        if let Entry::Vacant(entry) = summaries.entry(group.key()) {
+            entry.insert(summarize(group, since, db).await?);
+        }
+    }
+
+    let mut n_sent = 0;
+
+    for (username, managed) in recipients {
+        let sections: Vec<&str> = managed
+            .iter()
+            .filter_map(|group| summaries.get(&group.key())?.as_deref())
+            .collect();
+
+        if sections.is_empty() {
+            // nothing worth mentioning, so don't bother them
+            continue;
+        }
+
+        let content = format!(
+            "Here is what happened during the past week in the groups you manage or \
+             watch in Hive:\n\n{}\n\nYou can stop receiving these digests in your user settings.",
+            sections.join("\n\n")
+        );
+
+        let to = email_domain.email_of(&username);
+
+        // one failure shouldn't prevent everyone else's digests from being sent
+        match mailer.send(&[&to], "[Hive] Weekly digest", &content).await {
+            Ok(()) => n_sent += 1,
+            Err(e) => warn!("Failed to send weekly digest to {username}: {e}"),
+        }
+    }
+
+    sqlx::query(
+        "UPDATE digest_runs
+        SET n_recipients = $1
+        WHERE stamp = $2",
+    )
+    .bind(i32::try_from(n_sent).unwrap_or(i32::MAX))
+    .bind(stamp)
+    .execute(db)
+    .await?;
+
+    Ok(Some(n_sent))
+}
+
+// current managers (directly or through a managing subgroup) and watchers who
+// haven't opted out, each with the groups they manage or watch
+async fn list_recipients(db: &PgPool) -> AppResult<Vec<(String, Vec<Group>)>> {
+    let today = clock::today();
+
+    let rows = sqlx::query(
+        "SELECT DISTINCT ON (am.username, g.domain, g.id) am.username AS manager_username, g.*
+        FROM groups g
+        JOIN LATERAL all_members_of(g.id, g.domain, $1) am
+            ON am.manager
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM digest_opt_outs doo
+            WHERE doo.username = am.username
+        )
+        ORDER BY am.username, g.domain, g.id",
+    )
+    .bind(today)
+    .fetch_all(db)
+    .await?;
+
+    let mut recipients: BTreeMap<String, Vec<Group>> = BTreeMap::new();
+
+    for row in rows {
+        let username: String = row.try_get("manager_username")?;
+        let group = Group::from_row(&row)?;
+
+        recipients.entry(username).or_default().push(group);
+    }
+
+    // (watchers might also manage the group they watch, or have opted out)
+    for (username, group) in groups::watchers::list_effective(db).await? {
+        if is_opted_out(&username, db).await? {
+            continue;
+        }
+
+        let relevant = recipients.entry(username).or_default();
+        if !relevant.iter().any(|g| g.key() == group.key()) {
+            relevant.push(group);
+        }
+    }
+
+    Ok(recipients.into_iter().collect())
+}
+
+// plain text section about a single group, or None if nothing happened
+async fn summarize(
+    group: &Group,
+    since: DateTime<Local>,
+    db: &PgPool,
+) -> AppResult<Option<String>> {
+    let activity = audit_logs::list_recent_for_group(
+        &group.id,
+        &group.domain,
+        Some(since),
+        MAX_ACTIVITY_PER_GROUP,
+        db,
+        None,
+    )
+    .await?;
+
+    let horizon = clock::today() + EXPIRY_HORIZON;
+    let expiring =
+        groups::members::get_expiring_members(&group.id, &group.domain, horizon, db, None).await?;
+
+    let mut lines = vec![];
+
+    // (oldest first reads more naturally in a summary)
+    for entry in activity.iter().rev() {
+        let Some(subject) = &entry.subject else {
+            continue;
+        };
+
+        let line = match (entry.kind, &entry.action_kind) {
+            (GroupActivityKind::Member, ActionKind::Create) => format!("+ member {subject}"),
+            (GroupActivityKind::Member, ActionKind::Delete) => format!("- member {subject}"),
+            (GroupActivityKind::Subgroup, ActionKind::Create) => format!("+ subgroup {subject}"),
+            (GroupActivityKind::Subgroup, ActionKind::Delete) => format!("- subgroup {subject}"),
+            (GroupActivityKind::Permission, ActionKind::Create) => {
+                format!("+ permission {subject}")
+            }
+            (GroupActivityKind::Permission, ActionKind::Delete) => {
+                format!("- permission {subject}")
+            }
+            _ => continue, // not interesting enough for a digest
+        };
+
+        lines.push(format!("  {line} (by {})", entry.actor));
+    }
+
+    if activity.len() >= MAX_ACTIVITY_PER_GROUP as usize {
+        lines.push("  ... and more, see the group's page".to_owned());
+    }
+
+    for member in &expiring {
+        lines.push(format!(
+            "  ! membership of {} ends on {}",
+            member.username, member.until
+        ));
+    }
+
+    if lines.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(format!(
+        "{} ({}):\n{}",
+        group.name_en,
+        group.key(),
+        lines.join("\n")
+    )))
+}
@@ -7,18 +7,33 @@ use crate::{
     models::GroupRef,
 };
 
+pub mod acceptances;
+pub mod comments;
 pub mod details;
+pub mod duplicates;
+pub mod graph;
+pub mod invitations;
+pub mod links;
 pub mod list;
 pub mod management;
 pub mod members;
 pub mod permissions;
+pub mod shares;
+pub mod statistics;
+pub mod suggestions;
 pub mod tags;
+pub mod transfers;
+pub mod watchers;
 
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum GroupMembershipKind {
     Indirect,
     Direct,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RoleInGroup {
     Member,
     Manager,
@@ -57,6 +72,7 @@ impl Add<&Option<RoleInGroup>> for AuthorityInGroup {
     }
 }
 
+#[derive(Serialize)]
 pub struct GroupRelevance {
     pub role: Option<RoleInGroup>,
     pub authority: AuthorityInGroup,
@@ -0,0 +1,374 @@
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{details, members};
+use crate::{
+    clock,
+    dto::{
+        datetime::{BrowserDateDto, BrowserTimeDto},
+        groups::AddMemberDto,
+    },
+    errors::{AppError, AppResult},
+    guards::{lang::Language, user::User},
+    mailer::Mailer,
+    models::{ActionKind, Group, GroupMember, PendingMembership, TargetKind},
+    perms,
+    resolver::{IdentityResolver, UserEmailDomain},
+    services::{audit_logs, perms_cache},
+};
+
+// whether memberships added to the group by `actor` must first be accepted;
+// users adding themselves have nothing to accept
+pub async fn is_required<'x, X>(
+    id: &str,
+    domain: &str,
+    username: &str,
+    actor: &str,
+    db: X,
+) -> AppResult<bool>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    if username == actor {
+        return Ok(false);
+    }
+
+    let group: Group = details::require_one(id, domain, db).await?;
+
+    Ok(group.requires_acceptance)
+}
+
+pub async fn list_for_group<'x, X>(
+    id: &str,
+    domain: &str,
+    db: X,
+    resolver: Option<&IdentityResolver>,
+) -> AppResult<Vec<PendingMembership>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut pending: Vec<PendingMembership> = sqlx::query_as(
+        "SELECT *
+        FROM pending_memberships
+        WHERE group_id = $1
+            AND group_domain = $2
+        ORDER BY added_at",
+    )
+    .bind(id)
+    .bind(domain)
+    .fetch_all(db)
+    .await?;
+
+    if let Some(resolver) = resolver {
+        resolver
+            .populate_identities(
+                &mut pending,
+                |membership| &membership.username,
+                |membership, name| membership.display_name = Some(name),
+            )
+            .await?;
+    }
+
+    Ok(pending)
+}
+
+pub async fn list_for_user<'x, X>(username: &str, db: X) -> AppResult<Vec<PendingMembership>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let pending = sqlx::query_as(
+        "SELECT *
+        FROM pending_memberships
+        WHERE username = $1
+        ORDER BY added_at",
+    )
+    .bind(username)
+    .fetch_all(db)
+    .await?;
+
+    Ok(pending)
+}
+
+// records the membership without it taking effect in any way; everything
+// else (redundancy, member caps, self-preservation...) is only checked once
+// it is accepted, since that is when it would actually change anything
+pub async fn propose<'v, 'x, X>(
+    id: &str,
+    domain: &str,
+    dto: &AddMemberDto<'v>,
+    db: X,
+    actor: &str,
+) -> AppResult<PendingMembership>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    // other domains only ever have whole-day memberships
+    let (from_time, until_time) = if clock::has_time_precision(domain) {
+        (dto.from_time, dto.until_time)
+    } else {
+        (None, None)
+    };
+
+    let pending: PendingMembership = sqlx::query_as(
+        "INSERT INTO pending_memberships(username, group_id, group_domain, \"from\", \"until\", \
+         from_time, until_time, manager, note, justification, operational_year_id, added_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING *",
+    )
+    .bind(dto.username)
+    .bind(id)
+    .bind(domain)
+    .bind(dto.from)
+    .bind(dto.until)
+    .bind(from_time)
+    .bind(until_time)
+    .bind(dto.manager)
+    .bind(dto.note)
+    .bind(dto.justification)
+    .bind(dto.operational_year)
+    .bind(actor)
+    .fetch_one(&mut *txn)
+    .await?;
+
+    audit_logs::add_entry(
+        ActionKind::Create,
+        TargetKind::Membership,
+        pending.group_key(),
+        actor,
+        json!({
+            "new": audit_log_details(&pending),
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(pending)
+}
+
+// the pending membership is removed before anything else, which only takes
+// effect once the transaction is committed (so callers can still bail out)
+async fn take<'x, X>(id: &Uuid, username: Option<&str>, db: X) -> AppResult<PendingMembership>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    sqlx::query_as(
+        "DELETE FROM pending_memberships
+        WHERE id = $1
+            AND ($2::TEXT IS NULL OR username = $2)
+        RETURNING *",
+    )
+    .bind(id)
+    .bind(username)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NoSuchPendingMembership(*id))
+}
+
+// by the user it was proposed to; it then becomes a regular direct membership
+// (with all the usual checks), just as if it had been added by the user
+pub async fn accept<'x, X>(
+    id: &Uuid,
+    db: X,
+    resolver: Option<&IdentityResolver>,
+    user: &User,
+) -> AppResult<GroupMember>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let pending = take(id, Some(user.username()), &mut *txn).await?;
+
+    let dto = AddMemberDto {
+        username: pending.username.as_str().into(),
+        from: BrowserDateDto(pending.from),
+        until: BrowserDateDto(pending.until),
+        from_time: pending.from_time.map(BrowserTimeDto),
+        until_time: pending.until_time.map(BrowserTimeDto),
+        manager: pending.manager,
+        note: pending.note.as_deref().into(),
+        justification: pending.justification.as_deref().into(),
+        operational_year: pending.operational_year_id,
+    };
+
+    let added = members::add_member(
+        &pending.group_id,
+        &pending.group_domain,
+        &dto,
+        &mut *txn,
+        resolver,
+        user.username(),
+    )
+    .await?;
+
+    audit_logs::add_entry(
+        ActionKind::Delete,
+        TargetKind::Membership,
+        pending.group_key(),
+        user.username(),
+        json!({
+            "old": audit_log_details(&pending),
+            "accepted_as": added.id,
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+    // (add_member only released a savepoint, which could still be rolled back)
+    perms_cache::invalidate_all();
+
+    Ok(added)
+}
+
+// by the user it was proposed to
+pub async fn decline<'x, X>(id: &Uuid, db: X, user: &User) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let pending = take(id, Some(user.username()), &mut *txn).await?;
+
+    audit_logs::add_entry(
+        ActionKind::Delete,
+        TargetKind::Membership,
+        pending.group_key(),
+        user.username(),
+        json!({
+            "old": audit_log_details(&pending),
+            "declined": true,
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+// by one of the group's managers (or someone otherwise authorized)
+pub async fn withdraw<'x, X>(
+    id: &Uuid,
+    group_id: &str,
+    group_domain: &str,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let pending = take(id, None, &mut *txn).await?;
+
+    if pending.group_id != group_id || pending.group_domain != group_domain {
+        // (just return without committing the transaction)
+        return Err(AppError::NoSuchPendingMembership(*id));
+    }
+
+    audit_logs::add_entry(
+        ActionKind::Delete,
+        TargetKind::Membership,
+        pending.group_key(),
+        user.username(),
+        json!({
+            "old": audit_log_details(&pending),
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+fn audit_log_details(pending: &PendingMembership) -> serde_json::Value {
+    json!({
+        "member_type": "pending_member",
+        "id": pending.id,
+        "username": pending.username,
+        "from": pending.from,
+        "until": pending.until,
+        "from_time": pending.from_time,
+        "until_time": pending.until_time,
+        "manager": pending.manager,
+        "note": pending.note,
+        "justification": pending.justification,
+        "operational_year": pending.operational_year_id,
+    })
+}
+
+// sent right away (unlike other membership emails, which are queued), since
+// nothing happens until the user follows the link; opting out of membership
+// emails doesn't apply, but external users (who might not have an address at
+// the user email domain) can only find it on their profile page
+pub async fn send_email<'x, X>(
+    pending: &PendingMembership,
+    base_url: &str,
+    mailer: &Mailer,
+    email_domain: &UserEmailDomain,
+    db: X,
+) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    if perms::is_external_domain(&pending.group_domain) {
+        return Ok(());
+    }
+
+    let group: Group = details::require_one(&pending.group_id, &pending.group_domain, db).await?;
+
+    let link = format!("{base_url}/user/{}", pending.username);
+
+    // there is no way of knowing which language each user prefers, so the
+    // email is written in all of them
+    let languages = [Language::Swedish, Language::English];
+
+    let subject = format!(
+        "[Hive] {} / {}: {}",
+        Language::Swedish.t("membership-acceptance.email.subject"),
+        Language::English.t("membership-acceptance.email.subject"),
+        group.key()
+    );
+
+    let content = languages
+        .iter()
+        .map(|lang| {
+            let role = if pending.manager {
+                lang.t("membership-emails.role.manager")
+            } else {
+                lang.t("membership-emails.role.member")
+            };
+
+            rust_i18n::t!(
+                "membership-acceptance.email.content",
+                locale = lang.i18n_locale(),
+                group = group.localized_name(lang),
+                group_key = group.key(),
+                role = role,
+                from = pending.from,
+                until = pending.until,
+                actor = pending.added_by,
+                link = link
+            )
+            .into_owned()
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n---\n\n");
+
+    mailer
+        .send(
+            &[&email_domain.email_of(&pending.username)],
+            &subject,
+            &content,
+        )
+        .await
+        .map_err(AppError::MailerError)
+}
@@ -0,0 +1,186 @@
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    dto::groups::AddGroupCommentDto,
+    errors::{AppError, AppResult},
+    guards::user::User,
+    models::{ActionKind, GroupComment, GroupCommentThread, TargetKind},
+    services::audit_logs,
+};
+
+// most recently started threads first, each with its replies in order
+pub async fn get_threads<'x, X>(id: &str, domain: &str, db: X) -> AppResult<Vec<GroupCommentThread>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let comments: Vec<GroupComment> = sqlx::query_as(
+        "SELECT *
+        FROM group_comments
+        WHERE group_id = $1
+            AND group_domain = $2
+        ORDER BY created_at",
+    )
+    .bind(id)
+    .bind(domain)
+    .fetch_all(db)
+    .await?;
+
+    let mut threads: Vec<GroupCommentThread> = vec![];
+    let mut replies = vec![];
+
+    for comment in comments {
+        if comment.reply_to.is_some() {
+            replies.push(comment);
+        } else {
+            threads.push(GroupCommentThread {
+                comment,
+                replies: vec![],
+            });
+        }
+    }
+
+    for reply in replies {
+        // (replies always come after what they reply to, since it must
+        // already exist)
+        if let Some(thread) = threads
+            .iter_mut()
+            .find(|thread| Some(thread.comment.id) == reply.reply_to)
+        {
+            thread.replies.push(reply);
+        }
+    }
+
+    threads.reverse();
+
+    Ok(threads)
+}
+
+fn audit_log_details(comment: &GroupComment) -> serde_json::Value {
+    json!({
+        "id": comment.id,
+        "reply_to": comment.reply_to,
+        "body": comment.body,
+    })
+}
+
+pub async fn add<'v, 'x, X>(
+    id: &str,
+    domain: &str,
+    dto: &AddGroupCommentDto<'v>,
+    db: X,
+    user: &User,
+) -> AppResult<GroupComment>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    if let Some(reply_to) = dto.reply_to {
+        // only top-level comments of the same group can be replied to, so
+        // that threads stay one level deep
+        let is_thread: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0
+            FROM group_comments
+            WHERE id = $1
+                AND group_id = $2
+                AND group_domain = $3
+                AND reply_to IS NULL",
+        )
+        .bind(reply_to)
+        .bind(id)
+        .bind(domain)
+        .fetch_one(&mut *txn)
+        .await?;
+
+        if !is_thread {
+            return Err(AppError::NoSuchGroupComment(reply_to));
+        }
+    }
+
+    let comment: GroupComment = sqlx::query_as(
+        "INSERT INTO group_comments (group_id, group_domain, reply_to, author, body)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING *",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(dto.reply_to)
+    .bind(user.username())
+    .bind(dto.body)
+    .fetch_one(&mut *txn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(err) if err.is_foreign_key_violation() => {
+            AppError::NoSuchGroup(id.to_string(), domain.to_string())
+        }
+        _ => e.into(),
+    })?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        format!("{id}@{domain}"),
+        user.username(),
+        json!({
+            "new": {
+                "comment": audit_log_details(&comment),
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(comment)
+}
+
+// only by the comment's own author; deleting a comment that starts a thread
+// also deletes all replies to it
+pub async fn remove<'x, X>(
+    comment_id: &Uuid,
+    id: &str,
+    domain: &str,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let comment: GroupComment = sqlx::query_as(
+        "DELETE FROM group_comments
+        WHERE id = $1
+            AND group_id = $2
+            AND group_domain = $3
+            AND author = $4
+        RETURNING *",
+    )
+    .bind(comment_id)
+    .bind(id)
+    .bind(domain)
+    .bind(user.username())
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or(AppError::NoSuchGroupComment(*comment_id))?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        format!("{id}@{domain}"),
+        user.username(),
+        json!({
+            "old": {
+                "comment": audit_log_details(&comment),
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
@@ -1,15 +1,16 @@
-use chrono::Local;
 use rocket::futures::TryStreamExt;
-use sqlx::Row;
+use serde::Serialize;
+use sqlx::{PgPool, Row};
 
 use super::{GroupRelevance, RoleInGroup};
 use crate::{
-    HIVE_SYSTEM_ID,
+    HIVE_SYSTEM_ID, clock,
     errors::{AppError, AppResult},
     guards::{perms::PermsEvaluator, user::User},
-    models::{GroupModel, GroupRef},
-    perms::{GroupsScope, HivePermission, TagContent},
-    services::{groups::AuthorityInGroup, pg_args},
+    models::{BasePermissionAssignment, GroupModel, GroupRef},
+    perms::{self, GroupsScope, HivePermission, TagContent},
+    resolver::IdentityResolver,
+    services::{groups::AuthorityInGroup, perms_cache, pg_args},
 };
 
 pub async fn get_one<'x, X, G: GroupModel>(id: &str, domain: &str, db: X) -> AppResult<Option<G>>
@@ -34,6 +35,29 @@ where
         .ok_or_else(|| AppError::NoSuchGroup(id.to_owned(), domain.to_owned()))
 }
 
+// the current (id, domain) of a group that used to be known as `id@domain`
+pub async fn resolve_alias<'x, X>(
+    id: &str,
+    domain: &str,
+    db: X,
+) -> AppResult<Option<(String, String)>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let current = sqlx::query_as(
+        "SELECT group_id, group_domain
+        FROM group_aliases
+        WHERE old_id = $1
+            AND old_domain = $2",
+    )
+    .bind(id)
+    .bind(domain)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(current)
+}
+
 pub async fn get_relevance<'x, X>(
     id: &str,
     domain: &str,
@@ -46,7 +70,9 @@ where
 {
     let (role, path) = get_role_in_group_with_paths(user.username(), id, domain, db).await?;
 
-    let authority = get_authority_from_permissions(id, domain, db, perms).await? + &role;
+    let authority = get_authority_from_permissions(id, domain, db, PermsSource::Evaluator(perms))
+        .await?
+        + &role;
 
     Ok(GroupRelevance::new(role, authority, path))
 }
@@ -63,17 +89,219 @@ where
     X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
 {
     let role = get_role_in_group(user.username(), id, domain, db).await?;
-    let authority = get_authority_from_permissions(id, domain, db, perms).await? + &role;
+    let authority = get_authority_from_permissions(id, domain, db, PermsSource::Evaluator(perms))
+        .await?
+        + &role;
 
     authority.require(min).map(|_| authority)
 }
 
+// for someone other than the current user, e.g. when asked through the API;
+// same rules as require_authority (role and Hive permissions combined)
+pub async fn get_authority_of(
+    username: &str,
+    id: &str,
+    domain: &str,
+    db: &PgPool,
+) -> AppResult<AuthorityInGroup> {
+    let role = get_role_in_group(username, id, domain, db).await?;
+    let source = PermsSource::Username(username, db);
+    let authority = get_authority_from_permissions(id, domain, db, source).await? + &role;
+
+    Ok(authority)
+}
+
+// one reason why a user has (at least) ManageMembers authority in a group
+#[derive(Serialize)]
+pub struct AuthorityGrant {
+    pub username: String,
+    pub display_name: Option<String>,
+    pub authority: AuthorityInGroup,
+    pub source: AuthoritySource,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", content = "via", rename_all = "snake_case")]
+pub enum AuthoritySource {
+    DirectManager,
+    ManagerSubgroup(Vec<GroupRef>), // subgroups through which the user is a manager
+    HivePermission(PermissionGrant),
+}
+
+#[derive(Serialize)]
+pub struct PermissionGrant {
+    pub perm_id: String,
+    pub scope: Option<String>,
+    pub group: GroupRef,     // the group the permission is assigned to
+    pub path: Vec<GroupRef>, // how the user is a member of it (empty => direct)
+}
+
+// every (current) way in which someone can manage this group's members, for
+// explaining authority without knowing how it's computed; must agree with
+// `get_authority_of` (manager role and Hive permissions combined)
+pub async fn explain_authority(
+    id: &str,
+    domain: &str,
+    db: &PgPool,
+    resolver: Option<&IdentityResolver>,
+) -> AppResult<Vec<AuthorityGrant>> {
+    let today = clock::today();
+
+    let mut grants = vec![];
+
+    let managers = sqlx::query(
+        "SELECT username, trim_array(path, 1) AS path
+        FROM all_members_of($1, $2, $3)
+        WHERE manager",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(today)
+    .fetch_all(db)
+    .await?;
+
+    for row in managers {
+        let mut path: Vec<GroupRef> = row.try_get("path")?;
+        path.reverse();
+
+        let source = if path.is_empty() {
+            AuthoritySource::DirectManager
+        } else {
+            AuthoritySource::ManagerSubgroup(path)
+        };
+
+        grants.push(AuthorityGrant {
+            username: row.try_get("username")?,
+            display_name: None,
+            authority: AuthorityInGroup::ManageMembers,
+            source,
+        });
+    }
+
+    let assignments = sqlx::query(
+        "SELECT perm_id, scope, group_id, group_domain,
+            (group_id, group_domain)::GROUP_REF AS \"group\"
+        FROM permission_assignments
+        WHERE system_id = $1
+            AND perm_id = ANY($2)
+            AND group_id IS NOT NULL
+            AND group_domain <> ALL($3)
+        ORDER BY perm_id, scope, group_domain, group_id",
+    )
+    .bind(HIVE_SYSTEM_ID)
+    .bind(vec![
+        HivePermission::ManageGroups(GroupsScope::Any).key(),
+        HivePermission::ManageMembers(GroupsScope::Any).key(),
+    ])
+    .bind(perms::external_domains())
+    .fetch_all(db)
+    .await?;
+
+    for row in assignments {
+        let perm_id: String = row.try_get("perm_id")?;
+        let scope: Option<String> = row.try_get("scope")?;
+        let group_id: String = row.try_get("group_id")?;
+        let group_domain: String = row.try_get("group_domain")?;
+        let group: GroupRef = row.try_get("group")?;
+
+        let perm = HivePermission::try_from(BasePermissionAssignment {
+            system_id: HIVE_SYSTEM_ID.to_owned(),
+            perm_id: perm_id.clone(),
+            scope: scope.clone(),
+        });
+
+        let (authority, groups_scope) = match perm {
+            Ok(HivePermission::ManageGroups(s)) => (AuthorityInGroup::FullyAuthorized, s),
+            Ok(HivePermission::ManageMembers(s)) => (AuthorityInGroup::ManageMembers, s),
+            _ => continue, // malformed, so it never grants anything anyway
+        };
+
+        let covered = match groups_scope {
+            GroupsScope::Wildcard => true,
+            GroupsScope::Domain(d) => d == domain,
+            GroupsScope::Tag {
+                id: tag_id,
+                content,
+            } => has_any_tag(id, domain, &[(tag_id, content)], db).await?,
+            GroupsScope::Any | GroupsScope::AnyDomain => false,
+        };
+
+        if !covered {
+            continue;
+        }
+
+        let members = sqlx::query(
+            "SELECT username, trim_array(path, 1) AS path
+            FROM all_members_of($1, $2, $3)",
+        )
+        .bind(&group_id)
+        .bind(&group_domain)
+        .bind(today)
+        .fetch_all(db)
+        .await?;
+
+        for member in members {
+            let mut path: Vec<GroupRef> = member.try_get("path")?;
+            path.reverse();
+
+            grants.push(AuthorityGrant {
+                username: member.try_get("username")?,
+                display_name: None,
+                authority,
+                source: AuthoritySource::HivePermission(PermissionGrant {
+                    perm_id: perm_id.clone(),
+                    scope: scope.clone(),
+                    group: group.clone(),
+                    path,
+                }),
+            });
+        }
+    }
+
+    if let Some(resolver) = resolver {
+        resolver
+            .populate_identities(
+                &mut grants,
+                |grant| &grant.username,
+                |grant, name| grant.display_name = Some(name),
+            )
+            .await?;
+    }
+
+    // each user's grants together, strongest first
+    grants.sort_by(|a, b| {
+        a.username
+            .cmp(&b.username)
+            .then_with(|| b.authority.cmp(&a.authority))
+    });
+
+    Ok(grants)
+}
+
+// where a user's Hive permissions come from when computing authority
+#[derive(Clone, Copy)]
+enum PermsSource<'a> {
+    Evaluator(&'a PermsEvaluator), // current user, with cache
+    Username(&'a str, &'a PgPool),
+}
+
+impl PermsSource<'_> {
+    async fn fetch_all_related(&self, probe: HivePermission) -> AppResult<Vec<HivePermission>> {
+        match self {
+            Self::Evaluator(perms) => perms.fetch_all_related(probe).await,
+            Self::Username(username, db) => {
+                perms_cache::get_hive_permissions(username, probe.key(), db).await
+            }
+        }
+    }
+}
+
 // does not take group role into account
 async fn get_authority_from_permissions<'x, X>(
     id: &str,
     domain: &str,
     db: X,
-    perms: &PermsEvaluator,
+    perms: PermsSource<'_>,
 ) -> AppResult<AuthorityInGroup>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
@@ -123,7 +351,7 @@ async fn try_authority_from_permissions<'x, X>(
     potential_value: AuthorityInGroup,
     authority: &mut AuthorityInGroup,
     db: X,
-    perms: &PermsEvaluator,
+    perms: PermsSource<'_>,
 ) -> AppResult<()>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
@@ -213,7 +441,7 @@ where
     Ok(count > 0)
 }
 
-async fn get_role_in_group<'x, X>(
+pub async fn get_role_in_group<'x, X>(
     username: &str,
     id: &str,
     domain: &str,
@@ -222,7 +450,7 @@ async fn get_role_in_group<'x, X>(
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let today = Local::now().date_naive();
+    let today = clock::today();
 
     let is_manager = sqlx::query_scalar(
         "SELECT manager
@@ -256,7 +484,7 @@ pub async fn get_role_in_group_with_paths<'x, X>(
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let today = Local::now().date_naive();
+    let today = clock::today();
 
     let mut result = sqlx::query(
         "SELECT manager, trim_array(path, 1) AS path
@@ -0,0 +1,219 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::{FromRow, Row};
+use uuid::Uuid;
+
+use crate::{
+    errors::AppResult,
+    guards::user::User,
+    models::{ActionKind, GroupMember, SimpleGroup, TargetKind},
+    services::{audit_logs, perms_cache},
+};
+
+// a user with multiple overlapping direct memberships in the same group, all
+// with the same manager flag (otherwise they aren't really redundant); these
+// predate the redundancy check when adding members
+#[derive(Serialize)]
+pub struct DuplicateMemberships {
+    pub username: String,
+    pub group: SimpleGroup,
+    pub memberships: Vec<GroupMember>,
+}
+
+pub async fn list_all<'x, X>(db: X) -> AppResult<Vec<DuplicateMemberships>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let rows = sqlx::query(
+        "SELECT dm.*, g.name_sv, g.name_en
+        FROM direct_memberships dm
+        JOIN groups g
+            ON g.id = dm.group_id
+            AND g.domain = dm.group_domain
+        WHERE EXISTS (
+            SELECT 1
+            FROM direct_memberships o
+            WHERE o.username = dm.username
+                AND o.group_id = dm.group_id
+                AND o.group_domain = dm.group_domain
+                AND o.manager = dm.manager
+                AND o.id <> dm.id
+                AND o.\"from\" <= dm.\"until\"
+                AND o.\"until\" >= dm.\"from\"
+        )
+        ORDER BY dm.group_domain, dm.group_id, dm.username, dm.manager, dm.\"from\"",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut duplicates: Vec<DuplicateMemberships> = vec![];
+
+    for row in rows {
+        let membership = GroupMember::from_row(&row)?;
+        let group = SimpleGroup {
+            id: row.try_get("group_id")?,
+            domain: row.try_get("group_domain")?,
+            name_sv: row.try_get("name_sv")?,
+            name_en: row.try_get("name_en")?,
+        };
+
+        match duplicates.last_mut() {
+            Some(last) if last.username == membership.username && last.group == group => {
+                last.memberships.push(membership)
+            }
+            _ => duplicates.push(DuplicateMemberships {
+                username: membership.username.clone(),
+                group,
+                memberships: vec![membership],
+            }),
+        }
+    }
+
+    Ok(duplicates)
+}
+
+// merges each run of overlapping memberships into its earliest one, extended
+// to the union of their periods; returns how many memberships were removed
+pub async fn consolidate<'x, X>(
+    username: &str,
+    group_id: &str,
+    group_domain: &str,
+    db: X,
+    user: &User,
+) -> AppResult<usize>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let memberships: Vec<GroupMember> = sqlx::query_as(
+        "SELECT *
+        FROM direct_memberships
+        WHERE username = $1
+            AND group_id = $2
+            AND group_domain = $3
+        ORDER BY manager, \"from\", \"until\" DESC
+        FOR UPDATE",
+    )
+    .bind(username)
+    .bind(group_id)
+    .bind(group_domain)
+    .fetch_all(&mut *txn)
+    .await?;
+
+    let key = format!("{group_id}@{group_domain}");
+    let mut removed = 0;
+
+    for cluster in overlapping_runs(memberships) {
+        let Some((kept, rest)) = cluster.split_first() else {
+            continue;
+        };
+
+        if rest.is_empty() {
+            continue; // nothing to consolidate
+        }
+
+        let until = cluster.iter().map(|m| m.until).max().unwrap_or(kept.until);
+        let contact = cluster.iter().any(|m| m.contact);
+        let note = cluster.iter().find_map(|m| m.note.clone());
+        let justification = cluster.iter().find_map(|m| m.justification.clone());
+        let ids: Vec<Uuid> = rest.iter().filter_map(|m| m.id).collect();
+
+        sqlx::query("DELETE FROM direct_memberships WHERE id = ANY($1)")
+            .bind(&ids)
+            .execute(&mut *txn)
+            .await?;
+
+        sqlx::query(
+            "UPDATE direct_memberships
+            SET \"until\" = $1, contact = $2, note = $3, justification = $4
+            WHERE id = $5",
+        )
+        .bind(until)
+        .bind(contact)
+        .bind(&note)
+        .bind(&justification)
+        .bind(kept.id)
+        .execute(&mut *txn)
+        .await?;
+
+        for member in rest {
+            audit_logs::add_entry(
+                ActionKind::Delete,
+                TargetKind::Membership,
+                key.clone(),
+                user.username(),
+                json!({
+                    "old": {
+                        "member_type": "member",
+                        "id": member.id,
+                        "username": member.username,
+                        "from": member.from,
+                        "until": member.until,
+                        "manager": member.manager,
+                    },
+                    "consolidated_into": kept.id,
+                }),
+                &mut *txn,
+            )
+            .await?;
+        }
+
+        audit_logs::add_entry(
+            ActionKind::Update,
+            TargetKind::Membership,
+            key.clone(),
+            user.username(),
+            json!({
+                "old": {
+                    "id": kept.id,
+                    "until": kept.until,
+                    "contact": kept.contact,
+                    "note": kept.note,
+                    "justification": kept.justification,
+                },
+                "new": {
+                    "id": kept.id,
+                    "until": until,
+                    "contact": contact,
+                    "note": note,
+                    "justification": justification,
+                },
+            }),
+            &mut *txn,
+        )
+        .await?;
+
+        removed += rest.len();
+    }
+
+    if removed > 0 {
+        txn.commit().await?;
+        perms_cache::invalidate_all();
+    }
+
+    Ok(removed)
+}
+
+// splits memberships (sorted by manager flag, then start) into maximal runs of
+// transitively overlapping periods with the same manager flag
+fn overlapping_runs(memberships: Vec<GroupMember>) -> Vec<Vec<GroupMember>> {
+    let mut runs: Vec<Vec<GroupMember>> = vec![];
+    let mut end = NaiveDate::MIN;
+
+    for membership in memberships {
+        match runs.last_mut() {
+            Some(run) if run[0].manager == membership.manager && membership.from <= end => {
+                end = end.max(membership.until);
+                run.push(membership);
+            }
+            _ => {
+                end = membership.until;
+                runs.push(vec![membership]);
+            }
+        }
+    }
+
+    runs
+}
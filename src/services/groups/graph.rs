@@ -0,0 +1,270 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+};
+
+use serde::Serialize;
+use sqlx::FromRow;
+
+use crate::{errors::AppResult, guards::lang::Language, models::SimpleGroup};
+
+// the subgroup hierarchy as a whole, e.g. for documenting the organization's
+// structure; a domain filter keeps groups in that domain, plus whichever
+// groups they are directly linked to elsewhere (so cross-domain edges show)
+#[derive(Serialize)]
+pub struct GroupGraph {
+    pub nodes: Vec<SimpleGroup>,
+    pub edges: Vec<GroupEdge>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct GroupEdge {
+    pub parent_id: String,
+    pub parent_domain: String,
+    pub child_id: String,
+    pub child_domain: String,
+    pub manager: bool,
+}
+
+impl GroupEdge {
+    pub fn parent_key(&self) -> String {
+        format!("{}@{}", self.parent_id, self.parent_domain)
+    }
+
+    pub fn child_key(&self) -> String {
+        format!("{}@{}", self.child_id, self.child_domain)
+    }
+}
+
+// one line of the graph flattened into a tree (starting from groups without
+// parents), in order; groups with multiple parents appear under each of them
+#[derive(Serialize)]
+pub struct GroupTreeRow<'a> {
+    pub group: &'a SimpleGroup,
+    pub manager: bool, // whether linked to its parent as a manager
+    pub has_children: bool,
+    pub closes: usize, // how many levels end after this row
+}
+
+pub async fn get<'x, X>(domain: Option<&str>, db: X) -> AppResult<GroupGraph>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+{
+    let edges: Vec<GroupEdge> = sqlx::query_as(
+        "SELECT parent_id, parent_domain, child_id, child_domain, manager
+        FROM subgroups
+        WHERE $1::TEXT IS NULL
+            OR parent_domain = $1
+            OR child_domain = $1
+        ORDER BY parent_domain, parent_id, child_domain, child_id",
+    )
+    .bind(domain)
+    .fetch_all(db)
+    .await?;
+
+    let nodes = sqlx::query_as(
+        "SELECT g.id, g.domain, g.name_sv, g.name_en
+        FROM groups g
+        WHERE $1::TEXT IS NULL
+            OR g.domain = $1
+            OR EXISTS (
+                SELECT 1
+                FROM subgroups sg
+                WHERE (sg.parent_id = g.id AND sg.parent_domain = g.domain AND sg.child_domain = $1)
+                    OR (sg.child_id = g.id AND sg.child_domain = g.domain AND sg.parent_domain = $1)
+            )
+        ORDER BY g.domain, g.id",
+    )
+    .bind(domain)
+    .fetch_all(db)
+    .await?;
+
+    Ok(GroupGraph { nodes, edges })
+}
+
+pub async fn list_domains<'x, X>(db: X) -> AppResult<Vec<String>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let domains = sqlx::query_scalar(
+        "SELECT DISTINCT domain
+        FROM groups
+        ORDER BY domain",
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(domains)
+}
+
+impl GroupGraph {
+    // Graphviz, with each node linking to its page (for rendering to SVG)
+    pub fn to_dot(&self, lang: &Language) -> String {
+        let mut dot = String::from("digraph groups {\n    rankdir=LR;\n    node [shape=box];\n");
+
+        for (domain, groups) in self.by_domain() {
+            let _ = writeln!(dot, "    subgraph \"cluster_{}\" {{", escape_dot(domain));
+            let _ = writeln!(dot, "        label=\"{}\";", escape_dot(domain));
+
+            for group in groups {
+                let _ = writeln!(
+                    dot,
+                    "        \"{}\" [label=\"{}\", URL=\"/group/{}/{}\"];",
+                    escape_dot(&group.key()),
+                    escape_dot(group.localized_name(lang)),
+                    escape_dot(&group.domain),
+                    escape_dot(&group.id),
+                );
+            }
+
+            dot.push_str("    }\n");
+        }
+
+        for edge in &self.edges {
+            let style = if edge.manager { " [style=bold]" } else { "" };
+            let _ = writeln!(
+                dot,
+                "    \"{}\" -> \"{}\"{style};",
+                escape_dot(&edge.parent_key()),
+                escape_dot(&edge.child_key()),
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n  \
+            <key id=\"domain\" for=\"node\" attr.name=\"domain\" attr.type=\"string\"/>\n  \
+            <key id=\"name_sv\" for=\"node\" attr.name=\"name_sv\" attr.type=\"string\"/>\n  \
+            <key id=\"name_en\" for=\"node\" attr.name=\"name_en\" attr.type=\"string\"/>\n  \
+            <key id=\"manager\" for=\"edge\" attr.name=\"manager\" attr.type=\"boolean\"/>\n  \
+            <graph id=\"groups\" edgedefault=\"directed\">\n",
+        );
+
+        for group in &self.nodes {
+            let _ = writeln!(
+                xml,
+                "    <node id=\"{}\">\n      \
+                <data key=\"domain\">{}</data>\n      \
+                <data key=\"name_sv\">{}</data>\n      \
+                <data key=\"name_en\">{}</data>\n    \
+                </node>",
+                escape_xml(&group.key()),
+                escape_xml(&group.domain),
+                escape_xml(&group.name_sv),
+                escape_xml(&group.name_en),
+            );
+        }
+
+        for edge in &self.edges {
+            let _ = writeln!(
+                xml,
+                "    <edge source=\"{}\" target=\"{}\">\n      \
+                <data key=\"manager\">{}</data>\n    \
+                </edge>",
+                escape_xml(&edge.parent_key()),
+                escape_xml(&edge.child_key()),
+                edge.manager,
+            );
+        }
+
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    // depth-first from every group without a parent (in the graph); cycles
+    // shouldn't exist, but are cut off just in case
+    pub fn to_tree(&self) -> Vec<GroupTreeRow<'_>> {
+        let nodes: BTreeMap<String, &SimpleGroup> =
+            self.nodes.iter().map(|g| (g.key(), g)).collect();
+
+        let mut children: BTreeMap<String, Vec<(&SimpleGroup, bool)>> = BTreeMap::new();
+        let mut has_parent = HashSet::new();
+
+        for edge in &self.edges {
+            let child_key = edge.child_key();
+            if let Some(child) = nodes.get(&child_key) {
+                children
+                    .entry(edge.parent_key())
+                    .or_default()
+                    .push((*child, edge.manager));
+                has_parent.insert(child_key);
+            }
+        }
+
+        let mut rows = vec![];
+        let mut path = vec![];
+
+        for group in &self.nodes {
+            if !has_parent.contains(&group.key()) {
+                push_subtree(group, false, &children, &mut path, &mut rows);
+            }
+        }
+
+        rows
+    }
+
+    fn by_domain(&self) -> BTreeMap<&str, Vec<&SimpleGroup>> {
+        let mut domains: BTreeMap<&str, Vec<&SimpleGroup>> = BTreeMap::new();
+
+        for group in &self.nodes {
+            domains.entry(&group.domain).or_default().push(group);
+        }
+
+        domains
+    }
+}
+
+fn push_subtree<'a>(
+    group: &'a SimpleGroup,
+    manager: bool,
+    children: &BTreeMap<String, Vec<(&'a SimpleGroup, bool)>>,
+    path: &mut Vec<String>,
+    rows: &mut Vec<GroupTreeRow<'a>>,
+) {
+    let key = group.key();
+    let below: Vec<_> = children
+        .get(&key)
+        .into_iter()
+        .flatten()
+        .filter(|(child, _)| !path.contains(&child.key()) && child.key() != key)
+        .collect();
+
+    rows.push(GroupTreeRow {
+        group,
+        manager,
+        has_children: !below.is_empty(),
+        closes: 0,
+    });
+
+    if below.is_empty() {
+        return;
+    }
+
+    path.push(key);
+    for (child, manager) in below {
+        push_subtree(child, *manager, children, path, rows);
+    }
+    path.pop();
+
+    // the last descendant closes this group's level as well
+    if let Some(last) = rows.last_mut() {
+        last.closes += 1;
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,269 @@
+use chrono::{Days, Local};
+use log::*;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::members;
+use crate::{
+    HIVE_INTERNAL_DOMAIN, clock,
+    dto::{
+        datetime::BrowserDateDto,
+        groups::{AddMemberDto, CreateInvitationDto},
+    },
+    errors::{AppError, AppResult},
+    guards::user::User,
+    models::{ActionKind, GroupInvitation, GroupMember, TargetKind},
+    resolver::IdentityResolver,
+    services::{audit_logs, perms_cache},
+};
+
+// only those that can still be used, most recent first
+pub async fn list_usable<'x, X>(id: &str, domain: &str, db: X) -> AppResult<Vec<GroupInvitation>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let invitations = sqlx::query_as(
+        "SELECT *
+        FROM group_invitations
+        WHERE group_id = $1
+            AND group_domain = $2
+            AND \"until\" >= $3
+            AND (max_uses IS NULL OR uses < max_uses)
+            AND (expires_at IS NULL OR expires_at > $4)
+        ORDER BY created_at DESC",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(clock::today())
+    .bind(Local::now())
+    .fetch_all(db)
+    .await?;
+
+    Ok(invitations)
+}
+
+// None for anything that can't be used (anymore), without telling why
+pub async fn get_usable<'x, X>(secret: &Uuid, db: X) -> AppResult<Option<GroupInvitation>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let invitation: Option<GroupInvitation> = sqlx::query_as(
+        "SELECT *
+        FROM group_invitations
+        WHERE secret = $1",
+    )
+    .bind(secret)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(invitation.filter(|invitation| invitation.is_usable(&Local::now())))
+}
+
+// appointment bounds are up to the caller, since whoever joins through the
+// link is only ever checked as adding themselves
+pub async fn create<'x, X>(
+    id: &str,
+    domain: &str,
+    dto: &CreateInvitationDto,
+    db: X,
+    user: &User,
+) -> AppResult<GroupInvitation>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    if domain == HIVE_INTERNAL_DOMAIN {
+        // would let anyone with the link gain $hive permissions
+        warn!(
+            "Disallowing invitation link to internal group from {}",
+            user.username()
+        );
+        return Err(AppError::SelfPreservation);
+    }
+
+    let now = Local::now();
+    let expires_at = dto.days.map(|days| now + Days::new(days.into()));
+
+    let mut txn = db.begin().await?;
+
+    // (unusable invitations are just clutter, so this is as good a time as any
+    // to clean up)
+    sqlx::query(
+        "DELETE FROM group_invitations
+        WHERE group_id = $1
+            AND group_domain = $2
+            AND (
+                \"until\" < $3
+                OR uses >= max_uses
+                OR expires_at <= $4
+            )",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(clock::today())
+    .bind(now)
+    .execute(&mut *txn)
+    .await?;
+
+    let invitation: GroupInvitation = sqlx::query_as(
+        "INSERT INTO group_invitations
+            (group_id, group_domain, \"from\", \"until\", max_uses, expires_at, created_by, \
+         created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING *",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(dto.from)
+    .bind(dto.until)
+    .bind(dto.max_uses)
+    .bind(expires_at)
+    .bind(user.username())
+    .bind(now)
+    .fetch_one(&mut *txn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(err) if err.is_foreign_key_violation() => {
+            AppError::NoSuchGroup(id.to_string(), domain.to_string())
+        }
+        _ => e.into(),
+    })?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        invitation.group_key(),
+        user.username(),
+        json!({
+            "new": {
+                "invitation": audit_log_details(&invitation),
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(invitation)
+}
+
+pub async fn revoke<'x, X>(
+    invitation_id: &Uuid,
+    id: &str,
+    domain: &str,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let invitation: GroupInvitation = sqlx::query_as(
+        "DELETE FROM group_invitations
+        WHERE id = $1
+            AND group_id = $2
+            AND group_domain = $3
+        RETURNING *",
+    )
+    .bind(invitation_id)
+    .bind(id)
+    .bind(domain)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or(AppError::NoSuchGroupInvitation(*invitation_id))?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        invitation.group_key(),
+        user.username(),
+        json!({
+            "old": {
+                "invitation": audit_log_details(&invitation),
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+// adds the user as a regular direct member for the invitation's period (from
+// today, if it already started); like adding oneself, this never requires
+// acceptance, but member caps and such still apply
+pub async fn redeem<'x, X>(
+    secret: &Uuid,
+    db: X,
+    resolver: Option<&IdentityResolver>,
+    user: &User,
+) -> AppResult<(GroupInvitation, GroupMember)>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    // locked so that concurrent uses can't exceed the limit
+    let invitation: GroupInvitation = sqlx::query_as(
+        "SELECT *
+        FROM group_invitations
+        WHERE secret = $1
+        FOR UPDATE",
+    )
+    .bind(secret)
+    .fetch_optional(&mut *txn)
+    .await?
+    .filter(|invitation: &GroupInvitation| invitation.is_usable(&Local::now()))
+    .ok_or(AppError::GroupInvitationUnusable)?;
+
+    let dto = AddMemberDto {
+        username: user.username().into(),
+        from: BrowserDateDto(invitation.from.max(clock::today())),
+        until: BrowserDateDto(invitation.until),
+        from_time: None,
+        until_time: None,
+        manager: false,
+        note: None.into(),
+        justification: None.into(),
+        operational_year: None,
+    };
+
+    let added = members::add_member(
+        &invitation.group_id,
+        &invitation.group_domain,
+        &dto,
+        &mut *txn,
+        resolver,
+        user.username(),
+    )
+    .await?;
+
+    sqlx::query(
+        "UPDATE group_invitations
+        SET uses = uses + 1
+        WHERE id = $1",
+    )
+    .bind(invitation.id)
+    .execute(&mut *txn)
+    .await?;
+
+    txn.commit().await?;
+    perms_cache::invalidate_all(); // (add_member only released a savepoint)
+
+    Ok((invitation, added))
+}
+
+// (never including the secret, since audit logs are seen by more people)
+fn audit_log_details(invitation: &GroupInvitation) -> serde_json::Value {
+    json!({
+        "id": invitation.id,
+        "from": invitation.from,
+        "until": invitation.until,
+        "max_uses": invitation.max_uses,
+        "uses": invitation.uses,
+        "expires_at": invitation.expires_at,
+    })
+}
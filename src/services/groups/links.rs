@@ -0,0 +1,218 @@
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    dto::groups::AddGroupLinkDto,
+    errors::{AppError, AppResult},
+    guards::user::User,
+    models::{ActionKind, GroupLink, TargetKind},
+    services::audit_logs,
+};
+
+pub async fn get_all<'x, X>(id: &str, domain: &str, db: X) -> AppResult<Vec<GroupLink>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let links = sqlx::query_as(
+        "SELECT *
+        FROM group_links
+        WHERE group_id = $1
+            AND group_domain = $2
+        ORDER BY position, name",
+    )
+    .bind(id)
+    .bind(domain)
+    .fetch_all(db)
+    .await?;
+
+    Ok(links)
+}
+
+fn audit_log_details(link: &GroupLink) -> serde_json::Value {
+    json!({
+        "id": link.id,
+        "name": link.name,
+        "url": link.url,
+    })
+}
+
+// new links are always added at the end
+pub async fn add<'v, 'x, X>(
+    id: &str,
+    domain: &str,
+    dto: &AddGroupLinkDto<'v>,
+    db: X,
+    user: &User,
+) -> AppResult<GroupLink>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let link: GroupLink = sqlx::query_as(
+        "INSERT INTO group_links (group_id, group_domain, name, url, position)
+        SELECT $1, $2, $3, $4, COALESCE(MAX(position) + 1, 0)
+        FROM group_links
+        WHERE group_id = $1
+            AND group_domain = $2
+        RETURNING *",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(dto.name)
+    .bind(dto.url)
+    .fetch_one(&mut *txn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(err) if err.is_foreign_key_violation() => {
+            AppError::NoSuchGroup(id.to_string(), domain.to_string())
+        }
+        _ => e.into(),
+    })?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        format!("{id}@{domain}"),
+        user.username(),
+        json!({
+            "new": {
+                "link": audit_log_details(&link),
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(link)
+}
+
+pub async fn remove<'x, X>(
+    link_id: &Uuid,
+    id: &str,
+    domain: &str,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let link: GroupLink = sqlx::query_as(
+        "DELETE FROM group_links
+        WHERE id = $1
+            AND group_id = $2
+            AND group_domain = $3
+        RETURNING *",
+    )
+    .bind(link_id)
+    .bind(id)
+    .bind(domain)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or(AppError::NoSuchGroupLink(*link_id))?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        format!("{id}@{domain}"),
+        user.username(),
+        json!({
+            "old": {
+                "link": audit_log_details(&link),
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+// swaps the link with its neighbour in the given direction (if any)
+pub async fn shift<'x, X>(
+    link_id: &Uuid,
+    id: &str,
+    domain: &str,
+    up: bool,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    // (locked so that concurrent reorderings can't end up interleaved)
+    let links: Vec<GroupLink> = sqlx::query_as(
+        "SELECT *
+        FROM group_links
+        WHERE group_id = $1
+            AND group_domain = $2
+        ORDER BY position, name
+        FOR UPDATE",
+    )
+    .bind(id)
+    .bind(domain)
+    .fetch_all(&mut *txn)
+    .await?;
+
+    let index = links
+        .iter()
+        .position(|link| link.id == *link_id)
+        .ok_or(AppError::NoSuchGroupLink(*link_id))?;
+
+    let other = if up {
+        index.checked_sub(1)
+    } else {
+        Some(index + 1).filter(|i| *i < links.len())
+    };
+
+    let Some(other) = other else {
+        // already first/last; nothing to do
+        return Ok(());
+    };
+
+    let old_order: Vec<_> = links.iter().map(|link| link.id).collect();
+
+    let mut new_order = old_order.clone();
+    new_order.swap(index, other);
+
+    // positions are renumbered from scratch, which also takes care of any
+    // ties (same position) or gaps (after removals)
+    sqlx::query(
+        "UPDATE group_links gl
+        SET position = o.position - 1
+        FROM UNNEST($1::UUID[]) WITH ORDINALITY AS o (id, position)
+        WHERE gl.id = o.id",
+    )
+    .bind(&new_order)
+    .execute(&mut *txn)
+    .await?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        format!("{id}@{domain}"),
+        user.username(),
+        json!({
+            "old": {
+                "link_order": old_order,
+            },
+            "new": {
+                "link_order": new_order,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
@@ -3,13 +3,14 @@ use std::{
     collections::{HashMap, HashSet, hash_map::Entry},
 };
 
-use chrono::{Local, NaiveDate};
+use chrono::NaiveDate;
 use rocket::futures::TryStreamExt;
+use serde::Serialize;
 use sqlx::{FromRow, Row};
 
 use super::{GroupMembershipKind, RoleInGroup};
 use crate::{
-    HIVE_SYSTEM_ID,
+    HIVE_SYSTEM_ID, clock,
     errors::AppResult,
     guards::{lang::Language, perms::PermsEvaluator, user::User},
     models::{Group, GroupRef, SimpleGroup},
@@ -18,6 +19,7 @@ use crate::{
     services::pg_args,
 };
 
+#[derive(Serialize)]
 pub struct GroupOverviewSummary {
     pub group: Group,
     pub membership_kind: Option<GroupMembershipKind>,
@@ -37,7 +39,7 @@ pub async fn list_summaries<'x, X>(
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
 {
-    let today = Local::now().date_naive();
+    let today = clock::today();
 
     let mut summaries = HashMap::new();
 
@@ -75,6 +77,117 @@ where
     Ok(summaries.into_values().collect())
 }
 
+// like `list_summaries`, but without any statistics, for when only the groups
+// themselves are needed
+pub async fn list_relevant<'x, X>(
+    q: Option<&str>,
+    db: X,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<Vec<Group>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+{
+    let today = clock::today();
+
+    let from_memberships = get_relevant_from_memberships(&today, q, None, db, user)
+        .await?
+        .into_iter()
+        .map(|entry| entry.group);
+    let from_permissions = get_relevant_from_permissions(q, None, db, perms).await?;
+
+    let mut seen = HashSet::new();
+    let mut groups = vec![];
+
+    for group in from_memberships.chain(from_permissions) {
+        if seen.insert(group.key()) {
+            groups.push(group);
+        }
+    }
+
+    Ok(groups)
+}
+
+// every single group, regardless of who is asking (e.g., for API exports)
+pub async fn list_all<'x, X>(db: X) -> AppResult<Vec<Group>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let groups = sqlx::query_as("SELECT * FROM groups ORDER BY domain, id")
+        .fetch_all(db)
+        .await?;
+
+    Ok(groups)
+}
+
+// whether groups tagged #hive:public are listed in a directory that can be
+// viewed without logging in (e.g., linked from the chapter's website)
+pub struct PublicDirectory(bool);
+
+impl PublicDirectory {
+    pub fn new(enabled: bool) -> Self {
+        Self(enabled)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0
+    }
+}
+
+// groups in which the user is currently a manager (directly or through a
+// managing subgroup), i.e., where they are most likely to need to take action
+pub async fn list_managed<'x, X>(db: X, user: &User) -> AppResult<Vec<Group>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+{
+    let today = clock::today();
+
+    let entries = get_relevant_from_memberships(&today, None, None, db, user).await?;
+
+    // (a group can appear multiple times, once for each path to the user)
+    let mut seen = HashSet::new();
+    let groups = entries
+        .into_iter()
+        .filter(|entry| matches!(entry.role, RoleInGroup::Manager))
+        .filter(|entry| seen.insert(entry.group.key()))
+        .map(|entry| entry.group)
+        .collect();
+
+    Ok(groups)
+}
+
+// active groups tagged #hive:public (directly or through a subtag), sorted by
+// name
+pub async fn list_public<'x, X>(lang: &Language, db: X) -> AppResult<Vec<Group>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut groups: Vec<Group> = sqlx::query_as(
+        "SELECT gs.*
+        FROM groups gs
+        WHERE gs.state = 'active'
+            AND EXISTS (
+                SELECT 1
+                FROM all_tag_assignments ta
+                WHERE ta.group_id = gs.id
+                    AND ta.group_domain = gs.domain
+                    AND ta.system_id = $1
+                    AND ta.tag_id = 'public'
+            )",
+    )
+    .bind(HIVE_SYSTEM_ID)
+    .fetch_all(db)
+    .await?;
+
+    groups.sort_by(|a, b| {
+        a.localized_name(lang)
+            .cmp(b.localized_name(lang))
+            .then_with(|| a.key().cmp(&b.key()))
+    });
+
+    Ok(groups)
+}
+
 struct GroupMembershipEntry {
     group: Group,
     membership_kind: GroupMembershipKind,
@@ -445,7 +558,7 @@ pub async fn list_all_permissible<'x, X>(
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
 {
-    let today = Local::now().date_naive();
+    let today = clock::today();
 
     let mut groups = HashSet::new();
 
@@ -2,17 +2,57 @@ use std::collections::HashMap;
 
 use log::*;
 use serde_json::json;
+use uuid::Uuid;
 
 use crate::{
     HIVE_INTERNAL_DOMAIN,
-    dto::groups::{CreateGroupDto, EditGroupDto},
+    dto::groups::{
+        AddSubgroupDto, CreateGroupDto, EditGroupDto, GroupRefDto, RenameGroupDto, SetGroupStateDto,
+    },
     errors::{AppError, AppResult},
-    guards::user::User,
-    models::{ActionKind, Group, TargetKind},
-    services::{audit_log_details_for_update, audit_logs, update_if_changed},
+    guards::{perms::PermsEvaluator, user::User},
+    models::{ActionKind, Group, GroupState, TargetKind},
+    perms::{self, GroupsScope, HivePermission},
+    services::{
+        audit_log_details_for_update, audit_logs,
+        changes::{self, ProtectedDomains},
+        deletions, groups, perms_cache, self_preservation, update_if_changed,
+    },
 };
 
-pub async fn create<'v, 'x, X>(dto: &CreateGroupDto<'v>, db: X, user: &User) -> AppResult<()>
+// domains in which the user may create groups, sorted; None means any domain
+pub async fn get_creatable_domains(perms: &PermsEvaluator) -> AppResult<Option<Vec<String>>> {
+    let mut domains = vec![];
+
+    for probe in [
+        HivePermission::ManageGroups(GroupsScope::AnyDomain),
+        HivePermission::CreateGroups(GroupsScope::AnyDomain),
+    ] {
+        for perm in perms.fetch_all_related(probe).await? {
+            match perm {
+                HivePermission::ManageGroups(GroupsScope::Wildcard)
+                | HivePermission::CreateGroups(GroupsScope::Wildcard) => return Ok(None),
+                HivePermission::ManageGroups(GroupsScope::Domain(domain))
+                | HivePermission::CreateGroups(GroupsScope::Domain(domain)) => domains.push(domain),
+                _ => {}
+            }
+        }
+    }
+
+    domains.sort();
+    domains.dedup();
+
+    Ok(Some(domains))
+}
+
+// authority over each of `dto.parents` must have been checked already; links
+// into protected domains are only proposed, like any other new subgroup
+pub async fn create<'v, 'x, X>(
+    dto: &CreateGroupDto<'v>,
+    protected: &ProtectedDomains,
+    db: X,
+    user: &User,
+) -> AppResult<()>
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
 {
@@ -25,11 +65,17 @@ where
         return Err(AppError::SelfPreservation);
     }
 
+    let state = if dto.proposed {
+        GroupState::Proposed
+    } else {
+        GroupState::Active
+    };
+
     let mut txn = db.begin().await?;
 
     sqlx::query(
-        "INSERT INTO groups (id, domain, name_sv, name_en, description_sv, description_en)
-        VALUES ($1, $2, $3, $4, $5, $6)",
+        "INSERT INTO groups (id, domain, name_sv, name_en, description_sv, description_en, state)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)",
     )
     .bind(dto.id)
     .bind(dto.domain)
@@ -37,6 +83,7 @@ where
     .bind(dto.name_en)
     .bind(dto.description_sv)
     .bind(dto.description_en)
+    .bind(state)
     .execute(&mut *txn)
     .await
     .map_err(|e| {
@@ -44,6 +91,17 @@ where
             .if_unique_violation(e)
     })?;
 
+    // a new group takes over the key from any group previously known by it
+    sqlx::query(
+        "DELETE FROM group_aliases
+        WHERE old_id = $1
+            AND old_domain = $2",
+    )
+    .bind(dto.id)
+    .bind(dto.domain)
+    .execute(&mut *txn)
+    .await?;
+
     audit_logs::add_entry(
         ActionKind::Create,
         TargetKind::Group,
@@ -55,18 +113,39 @@ where
                 "name_en": dto.name_en,
                 "description_sv": dto.description_sv,
                 "description_en": dto.description_en,
+                "state": state,
             }
         }),
         &mut *txn,
     )
     .await?;
 
+    for parent in &dto.parents {
+        let link = AddSubgroupDto {
+            child: GroupRefDto {
+                id: &dto.id,
+                domain: &dto.domain,
+            },
+            manager: false,
+        };
+
+        if protected.contains(parent.domain) {
+            changes::propose_subgroup(parent.id, parent.domain, &link, &mut *txn, user).await?;
+        } else {
+            groups::members::add_subgroup(parent.id, parent.domain, &link, &mut *txn, user).await?;
+        }
+    }
+
     txn.commit().await?;
 
+    if !dto.parents.is_empty() {
+        perms_cache::invalidate_all();
+    }
+
     Ok(())
 }
 
-pub async fn delete<'x, X>(id: &str, domain: &str, db: X, user: &User) -> AppResult<()>
+pub async fn delete<'x, X>(id: &str, domain: &str, db: X, user: &User) -> AppResult<Uuid>
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
 {
@@ -81,6 +160,16 @@ where
 
     let mut txn = db.begin().await?;
 
+    let hive_grants = self_preservation::snapshot_if_affected(id, domain, &mut txn).await?;
+
+    let deletion_id = deletions::track(
+        TargetKind::Group,
+        format!("{id}@{domain}"),
+        user.username(),
+        &mut *txn,
+    )
+    .await?;
+
     let old: Group = sqlx::query_as("DELETE FROM groups WHERE id = $1 AND domain = $2 RETURNING *")
         .bind(id)
         .bind(domain)
@@ -88,6 +177,12 @@ where
         .await?
         .ok_or_else(|| AppError::NoSuchGroup(id.to_owned(), domain.to_owned()))?;
 
+    deletions::untrack(&mut *txn).await?;
+
+    // its members might have been the only ones holding some Hive permission
+    // (through a permission assignment or a parent group)
+    self_preservation::enforce(hive_grants, &mut txn, user.username()).await?;
+
     audit_logs::add_entry(
         ActionKind::Delete,
         TargetKind::Group,
@@ -106,8 +201,9 @@ where
     .await?;
 
     txn.commit().await?;
+    perms_cache::invalidate_all();
 
-    Ok(())
+    Ok(deletion_id)
 }
 
 pub async fn update<'v, 'x, X>(
@@ -132,6 +228,10 @@ where
     update_if_changed!(changed, query, name_en, old, dto);
     update_if_changed!(changed, query, description_sv, old, dto);
     update_if_changed!(changed, query, description_en, old, dto);
+    update_if_changed!(changed, query, member_cap, old, dto);
+    update_if_changed!(changed, query, member_cap_enforced, old, dto);
+    update_if_changed!(changed, query, default_membership_duration, old, dto);
+    update_if_changed!(changed, query, requires_acceptance, old, dto);
 
     if !changed.is_empty() {
         query
@@ -158,3 +258,212 @@ where
 
     Ok(())
 }
+
+// only the state itself changes; e.g., members of dormant groups are kept (and
+// keep any permissions through them), since they are expected to be back
+pub async fn set_state<'x, X>(
+    id: &str,
+    domain: &str,
+    dto: &SetGroupStateDto,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    if domain == HIVE_INTERNAL_DOMAIN && dto.state != GroupState::Active {
+        // internal groups must always be in use
+        warn!(
+            "Disallowing internal group state change from {}",
+            user.username()
+        );
+        return Err(AppError::SelfPreservation);
+    }
+
+    let mut txn = db.begin().await?;
+
+    let old: Group = super::details::require_one(id, domain, &mut *txn).await?;
+
+    if old.state == dto.state {
+        return Ok(()); // nothing to do
+    }
+
+    if !old.state.can_transition_to(&dto.state) {
+        return Err(AppError::InvalidGroupStateTransition(old.state, dto.state));
+    }
+
+    sqlx::query(
+        "UPDATE groups
+        SET state = $3
+        WHERE id = $1
+            AND domain = $2",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(dto.state)
+    .execute(&mut *txn)
+    .await?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        old.key(),
+        user.username(),
+        json!({
+            "old": {
+                "state": old.state,
+            },
+            "new": {
+                "state": dto.state,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+// changes the group's key; references in other tables follow along via
+// ON UPDATE CASCADE, and the old key is kept as an alias
+pub async fn rename<'v, 'x, X>(
+    id: &str,
+    domain: &str,
+    dto: &RenameGroupDto<'v>,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    if domain == HIVE_INTERNAL_DOMAIN || *dto.domain == HIVE_INTERNAL_DOMAIN {
+        // internal groups are looked up by their well-known keys
+        warn!("Disallowing internal group rename from {}", user.username());
+        return Err(AppError::SelfPreservation);
+    }
+
+    if id == *dto.id && domain == *dto.domain {
+        return Ok(()); // nothing to do
+    }
+
+    let mut txn = db.begin().await?;
+
+    let renamed = sqlx::query(
+        "UPDATE groups
+        SET id = $3, domain = $4
+        WHERE id = $1
+            AND domain = $2",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(dto.id)
+    .bind(dto.domain)
+    .execute(&mut *txn)
+    .await
+    .map_err(|e| {
+        AppError::DuplicateGroupId(dto.id.to_string(), dto.domain.to_string())
+            .if_unique_violation(e)
+    })?;
+
+    if renamed.rows_affected() == 0 {
+        return Err(AppError::NoSuchGroup(id.to_owned(), domain.to_owned()));
+    }
+
+    if *dto.domain != domain {
+        // moving into (or out of) an external domain must not leave it nested
+        // where it couldn't have been added in the first place
+        let parent_domains: Vec<String> = sqlx::query_scalar(
+            "SELECT parent_domain
+            FROM subgroups
+            WHERE child_id = $1
+                AND child_domain = $2",
+        )
+        .bind(dto.id)
+        .bind(dto.domain)
+        .fetch_all(&mut *txn)
+        .await?;
+
+        for parent_domain in parent_domains {
+            perms::require_valid_nesting(&parent_domain, &dto.id, &dto.domain)?;
+        }
+
+        let children: Vec<(String, String)> = sqlx::query_as(
+            "SELECT child_id, child_domain
+            FROM subgroups
+            WHERE parent_id = $1
+                AND parent_domain = $2",
+        )
+        .bind(dto.id)
+        .bind(dto.domain)
+        .fetch_all(&mut *txn)
+        .await?;
+
+        for (child_id, child_domain) in children {
+            perms::require_valid_nesting(&dto.domain, &child_id, &child_domain)?;
+        }
+    }
+
+    // not referenced via foreign keys, since it's just a cache
+    sqlx::query(
+        "UPDATE membership_closure
+        SET group_id = $3, group_domain = $4
+        WHERE group_id = $1
+            AND group_domain = $2",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(dto.id)
+    .bind(dto.domain)
+    .execute(&mut *txn)
+    .await?;
+
+    // the new key is no longer an alias (e.g., when renaming back), but any
+    // aliases pointing at the old key have already followed along
+    sqlx::query(
+        "DELETE FROM group_aliases
+        WHERE old_id = $1
+            AND old_domain = $2",
+    )
+    .bind(dto.id)
+    .bind(dto.domain)
+    .execute(&mut *txn)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO group_aliases (old_id, old_domain, group_id, group_domain, renamed_by)
+        VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(dto.id)
+    .bind(dto.domain)
+    .bind(user.username())
+    .execute(&mut *txn)
+    .await?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        format!("{}@{}", *dto.id, *dto.domain),
+        user.username(),
+        json!({
+            "old": {
+                "id": id,
+                "domain": domain,
+            },
+            "new": {
+                "id": dto.id,
+                "domain": dto.domain,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+    perms_cache::invalidate_all();
+
+    Ok(())
+}
@@ -1,25 +1,41 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
-use chrono::{Date, Datelike, Local, NaiveDate};
+use chrono::{Date, Datelike, Days, Months, NaiveDate};
 use log::*;
 use rocket::form::Contextual;
 use serde_json::json;
-use sqlx::Row;
+use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
 use crate::{
+    HIVE_SYSTEM_ID, clock,
     dto::{
-        datetime::BrowserDateDto,
+        datetime::{BrowserDateDto, BrowserTimeDto},
         groups::{AddMemberDto, AddSubgroupDto, EditMemberDto},
     },
     errors::{AppError, AppResult},
     guards::{perms::PermsEvaluator, user::User},
-    models::{ActionKind, GroupMember, Subgroup, TargetKind},
-    perms::{HivePermission, UpperBoundScope},
+    models::{ActionKind, Group, GroupMember, MembershipExclusion, Subgroup, TargetKind},
+    perms::{self, HivePermission, UpperBoundScope},
     resolver::IdentityResolver,
-    services::{audit_log_details_for_update, audit_logs, groups, update_if_changed},
+    services::{
+        audit_log_details_for_update, audit_logs, deletions, groups,
+        membership_emails::{self, MembershipEmailKind},
+        operational_years, perms_cache, self_preservation, update_if_changed,
+    },
 };
 
+// how far ahead to look when checking if root@hive.internal will run out of
+// members (and so nobody would be able to administrate Hive anymore)
+pub const ROOT_EXPIRY_HORIZON: Days = Days::new(30);
+
+// how often to check if the membership closure needs to be rebuilt (until it
+// is, permission checks fall back to slower recursive queries)
+const CLOSURE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
 pub async fn get_one<'x, X>(membership_id: &Uuid, db: X) -> AppResult<Option<GroupMember>>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
@@ -50,7 +66,7 @@ pub async fn is_direct_member<'x, X>(
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let today = Local::now().date_naive();
+    let today = clock::today();
 
     let result = sqlx::query_scalar(
         "SELECT COUNT(*) > 0
@@ -83,7 +99,7 @@ where
     NaiveDate: std::ops::Sub<D, Output = NaiveDate>,
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let today = Local::now().date_naive();
+    let today = clock::today();
 
     let until = if let Some(days) = with_grace_period {
         today - days
@@ -122,29 +138,55 @@ where
     Ok(members)
 }
 
-pub async fn get_all_members<'x, X>(
+// direct memberships ending within the horizon, excluding those of users who
+// already have a later one (e.g., if re-appointed for the next term)
+pub async fn get_expiring_members<'x, X>(
     id: &str,
     domain: &str,
+    horizon: NaiveDate,
     db: X,
     resolver: Option<&IdentityResolver>,
 ) -> AppResult<Vec<GroupMember>>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let today = Local::now().date_naive();
+    let members = get_direct_members(id, domain, true, None::<Days>, db, resolver).await?;
+
+    let renewed: HashSet<_> = members
+        .iter()
+        .filter(|member| member.until > horizon)
+        .map(|member| member.username.clone())
+        .collect();
+
+    let expiring = members
+        .into_iter()
+        .filter(|member| member.until <= horizon && !renewed.contains(&member.username))
+        .collect();
+
+    Ok(expiring)
+}
 
-    let mut members: Vec<GroupMember> = sqlx::query_as(
-        "SELECT username,
-            bool_or(manager) AS manager,
-            min(\"from\") AS \"from\",
-            max(\"until\") AS \"until\"
-        FROM all_members_of($1, $2, $3)
-        GROUP BY username
-        ORDER BY manager DESC, username", // DESC makes true come first
+pub async fn get_direct_members_at<'x, X>(
+    id: &str,
+    domain: &str,
+    at: NaiveDate,
+    db: X,
+    resolver: Option<&IdentityResolver>,
+) -> AppResult<Vec<GroupMember>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut members = sqlx::query_as(
+        "SELECT *
+        FROM direct_memberships
+        WHERE group_id = $1
+            AND group_domain = $2
+            AND $3 BETWEEN \"from\" AND until
+        ORDER BY manager DESC, username, id", // DESC makes true come first
     )
     .bind(id)
     .bind(domain)
-    .bind(today)
+    .bind(at)
     .fetch_all(db)
     .await?;
 
@@ -153,6 +195,171 @@ where
     Ok(members)
 }
 
+pub async fn get_all_members<'x, X>(
+    id: &str,
+    domain: &str,
+    at: Option<NaiveDate>, // defaults to today
+    db: X,
+    resolver: Option<&IdentityResolver>,
+) -> AppResult<Vec<GroupMember>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let at = at.unwrap_or_else(clock::today);
+
+    // current members respect the time of day (see `clock`), while other days
+    // include everyone who is a member at any point during that day
+    let now = (at == clock::today()).then(clock::now);
+    let members_at = if now.is_some() { "$4" } else { "$3" };
+
+    let sql = format!(
+        "SELECT m.username,
+            bool_or(m.manager) AS manager,
+            EXISTS (
+                SELECT 1
+                FROM direct_memberships dm
+                WHERE dm.group_id = $1
+                    AND dm.group_domain = $2
+                    AND dm.username = m.username
+                    AND dm.contact
+                    AND $3 BETWEEN dm.\"from\" AND dm.until
+            ) AS contact,
+            min(m.\"from\") AS \"from\",
+            max(m.\"until\") AS \"until\"
+        FROM all_members_of($1, $2, {members_at}) m
+        GROUP BY m.username
+        ORDER BY manager DESC, username" // DESC makes true come first
+    );
+
+    let mut query = sqlx::query_as(&sql).bind(id).bind(domain).bind(at);
+    if let Some(now) = now {
+        query = query.bind(now);
+    }
+
+    let mut members: Vec<GroupMember> = query.fetch_all(db).await?;
+
+    populate_member_names(&mut members, resolver, None).await?;
+
+    Ok(members)
+}
+
+// current members of a group tagged #hive:public-members (directly or through
+// a subtag); None both if the group doesn't exist and if it isn't tagged, such
+// that unauthenticated callers can't tell the two apart
+pub async fn get_public_members<'x, X>(
+    id: &str,
+    domain: &str,
+    db: X,
+    resolver: Option<&IdentityResolver>,
+) -> AppResult<Option<(Group, Vec<GroupMember>)>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+{
+    if !groups::tags::is_tagged_with(id, domain, HIVE_SYSTEM_ID, "public-members", db).await? {
+        return Ok(None);
+    }
+
+    let Some(group) = groups::details::get_one(id, domain, db).await? else {
+        return Ok(None);
+    };
+
+    let members = get_all_members(id, domain, None, db, resolver).await?;
+
+    Ok(Some((group, members)))
+}
+
+// current direct members designated as the group's official contact persons
+pub async fn get_contacts<'x, X>(
+    id: &str,
+    domain: &str,
+    db: X,
+    resolver: Option<&IdentityResolver>,
+) -> AppResult<Vec<GroupMember>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut contacts = sqlx::query_as(
+        "SELECT *
+        FROM direct_memberships
+        WHERE group_id = $1
+            AND group_domain = $2
+            AND contact
+            AND $3 BETWEEN \"from\" AND until
+        ORDER BY username, id",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(clock::today())
+    .fetch_all(db)
+    .await?;
+
+    populate_member_names(&mut contacts, resolver, None).await?;
+
+    Ok(contacts)
+}
+
+// the group's member cap (and whether it's enforced), but only if the number of
+// distinct direct members overlapping the given period is above it
+pub async fn get_exceeded_member_cap<'x, X>(
+    id: &str,
+    domain: &str,
+    from: &NaiveDate,
+    until: &NaiveDate,
+    db: X,
+) -> AppResult<Option<(i32, bool)>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let exceeded = sqlx::query_as(
+        "SELECT g.member_cap, g.member_cap_enforced
+        FROM groups g
+        WHERE g.id = $1
+            AND g.domain = $2
+            AND g.member_cap < (
+                SELECT COUNT(DISTINCT dm.username)
+                FROM direct_memberships dm
+                WHERE dm.group_id = g.id
+                    AND dm.group_domain = g.domain
+                    AND dm.\"from\" <= $4
+                    AND dm.\"until\" >= $3
+            )",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(from)
+    .bind(until)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(exceeded)
+}
+
+// if root@hive.internal will have no members left within the horizon, returns
+// the last day on which it still has any (None if everything is fine)
+pub async fn get_root_expiry<'x, X>(db: X) -> AppResult<Option<NaiveDate>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let today = clock::today();
+
+    let expiry = sqlx::query_scalar(
+        "SELECT MAX(am.until)
+        FROM all_members_of($1, $2, $3) am
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM all_members_of($1, $2, $4)
+        )",
+    )
+    .bind(crate::HIVE_ROOT_GROUP_ID)
+    .bind(crate::HIVE_INTERNAL_DOMAIN)
+    .bind(today)
+    .bind(today + ROOT_EXPIRY_HORIZON)
+    .fetch_one(db)
+    .await?;
+
+    Ok(expiry)
+}
+
 pub async fn get_direct_subgroups<'x, X>(id: &str, domain: &str, db: X) -> AppResult<Vec<Subgroup>>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
@@ -219,8 +426,13 @@ where
         ));
     }
 
+    perms::require_valid_nesting(parent_domain, dto.child.id, dto.child.domain)?;
+
     let mut txn = db.begin().await?;
 
+    let hive_grants =
+        self_preservation::snapshot_if_affected(parent_id, parent_domain, &mut txn).await?;
+
     let loop_detected = sqlx::query_scalar(
         "SELECT COUNT(*) > 0
         FROM all_subgroups_of($1, $2)
@@ -262,6 +474,8 @@ where
         _ => e.into(),
     })?;
 
+    self_preservation::enforce(hive_grants, &mut txn, user.username()).await?;
+
     audit_logs::add_entry(
         ActionKind::Create,
         TargetKind::Membership,
@@ -280,6 +494,7 @@ where
     .await?;
 
     txn.commit().await?;
+    perms_cache::invalidate_all();
 
     Ok(())
 }
@@ -297,6 +512,9 @@ where
 {
     let mut txn = db.begin().await?;
 
+    let hive_grants =
+        self_preservation::snapshot_if_affected(parent_id, parent_domain, &mut txn).await?;
+
     let manager: Option<bool> = sqlx::query_scalar(
         "DELETE FROM subgroups
         WHERE parent_id = $1
@@ -318,6 +536,9 @@ where
         return Ok(());
     };
 
+    // e.g., the only members of root@hive.internal might come from the child
+    self_preservation::enforce(hive_grants, &mut txn, user.username()).await?;
+
     audit_logs::add_entry(
         ActionKind::Delete,
         TargetKind::Membership,
@@ -336,6 +557,7 @@ where
     .await?;
 
     txn.commit().await?;
+    perms_cache::invalidate_all();
 
     Ok(())
 }
@@ -346,13 +568,15 @@ pub async fn add_member<'v, 'x, X>(
     dto: &AddMemberDto<'v>,
     db: X,
     resolver: Option<&IdentityResolver>,
-    user: &User,
+    actor: &str,
 ) -> AppResult<GroupMember>
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
 {
     let mut txn = db.begin().await?;
 
+    let hive_grants = self_preservation::snapshot_if_affected(id, domain, &mut txn).await?;
+
     let redundant = sqlx::query_scalar(
         "SELECT COUNT(*) > 0
         FROM direct_memberships
@@ -376,10 +600,21 @@ where
         return Err(AppError::RedundantMembership(dto.username.to_string()));
     }
 
+    if let Some(year_id) = &dto.operational_year {
+        operational_years::check_membership(year_id, &dto.from.0, &dto.until.0, &mut *txn).await?;
+    }
+
+    // other domains only ever have whole-day memberships
+    let (from_time, until_time) = if clock::has_time_precision(domain) {
+        (dto.from_time, dto.until_time)
+    } else {
+        (None, None)
+    };
+
     let mut added: GroupMember = sqlx::query_as(
         "INSERT INTO direct_memberships(username, group_id, group_domain, \"from\", \"until\", \
-         manager)
-        VALUES ($1, $2, $3, $4, $5, $6)
+         from_time, until_time, manager, note, justification, operational_year_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
         RETURNING *",
     )
     .bind(dto.username)
@@ -387,16 +622,36 @@ where
     .bind(domain)
     .bind(&dto.from)
     .bind(&dto.until)
+    .bind(from_time)
+    .bind(until_time)
     .bind(dto.manager)
+    .bind(dto.note)
+    .bind(dto.justification)
+    .bind(dto.operational_year)
     .fetch_one(&mut *txn)
     .await?;
 
+    // checked after inserting so that the new membership is counted too
+    let exceeded =
+        get_exceeded_member_cap(id, domain, &dto.from.0, &dto.until.0, &mut *txn).await?;
+    match exceeded {
+        Some((cap, true)) => return Err(AppError::MemberCapExceeded(cap)),
+        Some((cap, false)) => warn!(
+            "Adding {} to {id}@{domain} exceeds its member cap of {cap}",
+            dto.username
+        ),
+        None => {}
+    }
+
+    // (only ever gains, but those are worth recording as well)
+    self_preservation::enforce(hive_grants, &mut txn, actor).await?;
+
     audit_logs::add_entry(
         ActionKind::Create,
         TargetKind::Membership,
         // FIXME: consider using added.id as target_id
         format!("{}@{}", id, domain),
-        user.username(),
+        actor,
         json!({
             "new": {
                 "member_type": "member",
@@ -404,14 +659,32 @@ where
                 "username": dto.username,
                 "from": dto.from,
                 "until": dto.until,
+                "from_time": from_time,
+                "until_time": until_time,
                 "manager": dto.manager,
+                "note": dto.note,
+                "justification": dto.justification,
+                "operational_year": dto.operational_year,
             }
         }),
         &mut *txn,
     )
     .await?;
 
+    membership_emails::enqueue(
+        MembershipEmailKind::Added,
+        &added,
+        id,
+        domain,
+        actor,
+        &mut *txn,
+    )
+    .await?;
+
     txn.commit().await?;
+    // if `db` was already a transaction, this only released a savepoint, so
+    // callers must invalidate again once they commit it themselves
+    perms_cache::invalidate_all();
 
     // design choice: a name resolution fail does not abort the transaction,
     // which (arguably) might make sense to allow management even when the
@@ -425,9 +698,9 @@ where
     Ok(added)
 }
 
-pub async fn update<'x, X>(
+pub async fn update<'v, 'x, X>(
     membership_id: &Uuid,
-    dto: &EditMemberDto,
+    dto: &EditMemberDto<'v>,
     group_id: &str,
     group_domain: &str,
     db: X,
@@ -438,6 +711,9 @@ where
 {
     let mut txn = db.begin().await?;
 
+    let hive_grants =
+        self_preservation::snapshot_if_affected(group_id, group_domain, &mut txn).await?;
+
     let old = require_one(membership_id, &mut *txn).await?;
 
     let redundant = sqlx::query_scalar(
@@ -468,6 +744,10 @@ where
     let old = EditMemberDto {
         from: BrowserDateDto(old.from),
         until: BrowserDateDto(old.until),
+        from_time: old.from_time.map(BrowserTimeDto),
+        until_time: old.until_time.map(BrowserTimeDto),
+        note: old.note.as_deref().into(),
+        justification: old.justification.as_deref().into(),
     };
 
     let mut query = sqlx::QueryBuilder::new("UPDATE direct_memberships SET");
@@ -475,6 +755,12 @@ where
 
     update_if_changed!(changed, query, from, old, dto);
     update_if_changed!(changed, query, until, old, dto);
+    if clock::has_time_precision(group_domain) {
+        update_if_changed!(changed, query, from_time, old, dto);
+        update_if_changed!(changed, query, until_time, old, dto);
+    }
+    update_if_changed!(changed, query, note, old, dto);
+    update_if_changed!(changed, query, justification, old, dto);
 
     if !changed.is_empty() {
         query
@@ -484,6 +770,28 @@ where
             .execute(&mut *txn)
             .await?;
 
+        // edited to no longer fit within its operational year, so it stops
+        // following along with it
+        sqlx::query(
+            "UPDATE direct_memberships dm
+            SET operational_year_id = NULL
+            FROM operational_years oy
+            WHERE dm.id = $1
+                AND oy.id = dm.operational_year_id
+                AND (dm.\"from\" < oy.\"start\" OR dm.\"until\" > oy.\"end\")",
+        )
+        .bind(membership_id)
+        .execute(&mut *txn)
+        .await?;
+
+        // e.g., cannot make our last administrator's membership end already
+        self_preservation::enforce(hive_grants, &mut txn, user.username()).await?;
+
+        // (notes and justifications are of no concern to the member)
+        let noteworthy = changed
+            .keys()
+            .any(|field| !matches!(*field, "note" | "justification"));
+
         audit_logs::add_entry(
             ActionKind::Update,
             TargetKind::Membership,
@@ -495,7 +803,22 @@ where
         )
         .await?;
 
+        if noteworthy {
+            let new = require_one(membership_id, &mut *txn).await?;
+
+            membership_emails::enqueue(
+                MembershipEmailKind::Edited,
+                &new,
+                group_id,
+                group_domain,
+                user.username(),
+                &mut *txn,
+            )
+            .await?;
+        }
+
         txn.commit().await?;
+        perms_cache::invalidate_all();
     }
 
     Ok(())
@@ -507,15 +830,24 @@ pub async fn remove_member<'x, X>(
     group_id: &str,
     group_domain: &str,
     db: X,
-    user: &User,
-) -> AppResult<()>
+    actor: &str,
+) -> AppResult<Option<Uuid>>
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
 {
-    let today = Local::now().date_naive();
-
     let mut txn = db.begin().await?;
 
+    let hive_grants =
+        self_preservation::snapshot_if_affected(group_id, group_domain, &mut txn).await?;
+
+    let deletion_id = deletions::track(
+        TargetKind::Membership,
+        format!("{}@{}", group_id, group_domain),
+        actor,
+        &mut *txn,
+    )
+    .await?;
+
     let member: Option<GroupMember> = sqlx::query_as(
         "DELETE FROM direct_memberships
         WHERE id = $1
@@ -532,39 +864,27 @@ where
     let Some(member) = member else {
         // ID was not associated with this group, so there's nothing to do
         // (just return without committing the transaction)
-        return Ok(());
+        return Ok(None);
     };
 
+    deletions::untrack(&mut *txn).await?;
+
     // ideally we would do this here instead of a separate query in the route
     // handler, but it doesn't work because &mut *txn is not Copy as required
     // let group = GroupRef::from_row(&row)?;
     // super::details::require_authority(...)
 
-    let last_root_member =
-        sqlx::query_scalar("SELECT COUNT(*) = 0 FROM all_members_of($1, $2, $3)")
-            .bind(crate::HIVE_ROOT_GROUP_ID)
-            .bind(crate::HIVE_INTERNAL_DOMAIN)
-            .bind(today)
-            .fetch_one(&mut *txn)
-            .await?;
-
-    if last_root_member {
-        // cannot remove our last administrator
-        // (note that this sadly doesn't prevent that their membership naturally
-        // expires and we end up with no administrators anyway)
-        warn!(
-            "Disallowing last administrator removal from {}",
-            user.username()
-        );
-        return Err(AppError::SelfPreservation);
-    };
+    // e.g., cannot remove our last administrator
+    // (natural expiry can't be prevented, but admins are warned about it in
+    // advance; see `get_root_expiry`)
+    self_preservation::enforce(hive_grants, &mut txn, actor).await?;
 
     audit_logs::add_entry(
         ActionKind::Delete,
         TargetKind::Membership,
         // FIXME: consider using membership_id as target_id
         format!("{}@{}", group_id, group_domain),
-        user.username(),
+        actor,
         json!({
             "old": {
                 "member_type": "member",
@@ -579,7 +899,474 @@ where
     )
     .await?;
 
+    membership_emails::enqueue(
+        MembershipEmailKind::Removed,
+        &member,
+        group_id,
+        group_domain,
+        actor,
+        &mut *txn,
+    )
+    .await?;
+
     txn.commit().await?;
+    perms_cache::invalidate_all();
+
+    Ok(Some(deletion_id))
+}
+
+// memberships not associated with this group are silently ignored; returns
+// None if nothing was removed at all
+pub async fn bulk_remove<'x, X>(
+    membership_ids: &[Uuid],
+    group_id: &str,
+    group_domain: &str,
+    db: X,
+    user: &User,
+) -> AppResult<Option<Uuid>>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let hive_grants =
+        self_preservation::snapshot_if_affected(group_id, group_domain, &mut txn).await?;
+
+    let deletion_id = deletions::track(
+        TargetKind::Membership,
+        format!("{}@{}", group_id, group_domain),
+        user.username(),
+        &mut *txn,
+    )
+    .await?;
+
+    let members: Vec<GroupMember> = sqlx::query_as(
+        "DELETE FROM direct_memberships
+        WHERE id = ANY($1)
+            AND group_id = $2
+            AND group_domain = $3
+        RETURNING *",
+    )
+    .bind(membership_ids)
+    .bind(group_id)
+    .bind(group_domain)
+    .fetch_all(&mut *txn)
+    .await?;
+
+    if members.is_empty() {
+        // (just return without committing the transaction)
+        return Ok(None);
+    }
+
+    deletions::untrack(&mut *txn).await?;
+
+    // e.g., cannot remove our last administrator(s)
+    self_preservation::enforce(hive_grants, &mut txn, user.username()).await?;
+
+    for member in members {
+        audit_logs::add_entry(
+            ActionKind::Delete,
+            TargetKind::Membership,
+            format!("{}@{}", group_id, group_domain),
+            user.username(),
+            json!({
+                "old": {
+                    "member_type": "member",
+                    "id": member.id,
+                    "username": member.username,
+                    "from": member.from,
+                    "until": member.until,
+                    "manager": member.manager,
+                }
+            }),
+            &mut *txn,
+        )
+        .await?;
+
+        membership_emails::enqueue(
+            MembershipEmailKind::Removed,
+            &member,
+            group_id,
+            group_domain,
+            user.username(),
+            &mut *txn,
+        )
+        .await?;
+    }
+
+    txn.commit().await?;
+    perms_cache::invalidate_all();
+
+    Ok(Some(deletion_id))
+}
+
+// pushes the end date of each membership one year into the future, provided
+// that it's still within the appointment bounds (see below), or that it is
+// already justified as a long-term appointment
+pub async fn bulk_extend<'x, X>(
+    membership_ids: &[Uuid],
+    group_id: &str,
+    group_domain: &str,
+    perms: &PermsEvaluator,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let members = lock_many(membership_ids, group_id, group_domain, &mut *txn).await?;
+
+    for mut member in members {
+        let until = member.until + Months::new(12);
+
+        if let Some(min) =
+            required_appointment_permission(&until, group_id, group_domain, &mut *txn).await?
+        {
+            if !perms.satisfies(min).await? {
+                return Err(AppError::AppointmentTooLong(member.username));
+            }
+
+            if member.justification.is_none() {
+                // there's nowhere to give one in bulk, it must be edited in
+                return Err(AppError::MissingAppointmentJustification(member.username));
+            }
+        }
+
+        sqlx::query("UPDATE direct_memberships SET \"until\" = $1 WHERE id = $2")
+            .bind(until)
+            .bind(member.id)
+            .execute(&mut *txn)
+            .await?;
+
+        audit_logs::add_entry(
+            ActionKind::Update,
+            TargetKind::Membership,
+            format!("{}@{}", group_id, group_domain),
+            user.username(),
+            json!({
+                "old": {
+                    "id": member.id,
+                    "username": member.username,
+                    "until": member.until,
+                },
+                "new": {
+                    "id": member.id,
+                    "username": member.username,
+                    "until": until,
+                }
+            }),
+            &mut *txn,
+        )
+        .await?;
+
+        member.until = until;
+        membership_emails::enqueue(
+            MembershipEmailKind::Edited,
+            &member,
+            group_id,
+            group_domain,
+            user.username(),
+            &mut *txn,
+        )
+        .await?;
+    }
+
+    txn.commit().await?;
+    perms_cache::invalidate_all();
+
+    Ok(())
+}
+
+pub async fn bulk_toggle_manager<'x, X>(
+    membership_ids: &[Uuid],
+    group_id: &str,
+    group_domain: &str,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let members = lock_many(membership_ids, group_id, group_domain, &mut *txn).await?;
+
+    for mut member in members {
+        sqlx::query("UPDATE direct_memberships SET manager = $1 WHERE id = $2")
+            .bind(!member.manager)
+            .bind(member.id)
+            .execute(&mut *txn)
+            .await?;
+
+        audit_logs::add_entry(
+            ActionKind::Update,
+            TargetKind::Membership,
+            format!("{}@{}", group_id, group_domain),
+            user.username(),
+            json!({
+                "old": {
+                    "id": member.id,
+                    "username": member.username,
+                    "manager": member.manager,
+                },
+                "new": {
+                    "id": member.id,
+                    "username": member.username,
+                    "manager": !member.manager,
+                }
+            }),
+            &mut *txn,
+        )
+        .await?;
+
+        member.manager = !member.manager;
+        membership_emails::enqueue(
+            MembershipEmailKind::Edited,
+            &member,
+            group_id,
+            group_domain,
+            user.username(),
+            &mut *txn,
+        )
+        .await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+pub async fn bulk_toggle_contact<'x, X>(
+    membership_ids: &[Uuid],
+    group_id: &str,
+    group_domain: &str,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let members = lock_many(membership_ids, group_id, group_domain, &mut *txn).await?;
+
+    for member in members {
+        sqlx::query("UPDATE direct_memberships SET contact = $1 WHERE id = $2")
+            .bind(!member.contact)
+            .bind(member.id)
+            .execute(&mut *txn)
+            .await?;
+
+        audit_logs::add_entry(
+            ActionKind::Update,
+            TargetKind::Membership,
+            format!("{}@{}", group_id, group_domain),
+            user.username(),
+            json!({
+                "old": {
+                    "id": member.id,
+                    "username": member.username,
+                    "contact": member.contact,
+                },
+                "new": {
+                    "id": member.id,
+                    "username": member.username,
+                    "contact": !member.contact,
+                }
+            }),
+            &mut *txn,
+        )
+        .await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+// fetches (and locks, for the rest of the transaction) the memberships in this
+// group with any of the specified ids
+async fn lock_many<'x, X>(
+    membership_ids: &[Uuid],
+    group_id: &str,
+    group_domain: &str,
+    db: X,
+) -> AppResult<Vec<GroupMember>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let members = sqlx::query_as(
+        "SELECT *
+        FROM direct_memberships
+        WHERE id = ANY($1)
+            AND group_id = $2
+            AND group_domain = $3
+        FOR UPDATE",
+    )
+    .bind(membership_ids)
+    .bind(group_id)
+    .bind(group_domain)
+    .fetch_all(db)
+    .await?;
+
+    Ok(members)
+}
+
+pub async fn get_exclusions<'x, X>(
+    id: &str,
+    domain: &str,
+    db: X,
+    resolver: Option<&IdentityResolver>,
+) -> AppResult<Vec<MembershipExclusion>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut exclusions: Vec<MembershipExclusion> = sqlx::query_as(
+        "SELECT id, username
+        FROM membership_exclusions
+        WHERE group_id = $1
+            AND group_domain = $2
+        ORDER BY username",
+    )
+    .bind(id)
+    .bind(domain)
+    .fetch_all(db)
+    .await?;
+
+    if let Some(resolver) = resolver {
+        resolver
+            .populate_identities(
+                &mut exclusions,
+                |exclusion| &exclusion.username,
+                |exclusion, name| exclusion.display_name = Some(name),
+            )
+            .await?;
+    }
+
+    Ok(exclusions)
+}
+
+pub async fn add_exclusion<'x, X>(
+    username: &str,
+    group_id: &str,
+    group_domain: &str,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let hive_grants =
+        self_preservation::snapshot_if_affected(group_id, group_domain, &mut txn).await?;
+
+    let exclusion_id: Option<Uuid> = sqlx::query_scalar(
+        "INSERT INTO membership_exclusions (username, group_id, group_domain)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        RETURNING id",
+    )
+    .bind(username)
+    .bind(group_id)
+    .bind(group_domain)
+    .fetch_optional(&mut *txn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(err) if err.is_foreign_key_violation() => {
+            AppError::NoSuchGroup(group_id.to_string(), group_domain.to_string())
+        }
+        _ => e.into(),
+    })?;
+
+    let Some(exclusion_id) = exclusion_id else {
+        // user was already excluded, so there's nothing to do
+        // (just return without committing the transaction)
+        return Ok(());
+    };
+
+    // an exclusion can cut someone off from root@hive.internal just as well as
+    // removing their membership, so the same safeguards apply
+    self_preservation::enforce(hive_grants, &mut txn, user.username()).await?;
+
+    audit_logs::add_entry(
+        ActionKind::Create,
+        TargetKind::Membership,
+        format!("{}@{}", group_id, group_domain),
+        user.username(),
+        json!({
+            "new": {
+                "member_type": "exclusion",
+                "id": exclusion_id,
+                "username": username,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+    perms_cache::invalidate_all();
+
+    Ok(())
+}
+
+pub async fn remove_exclusion<'x, X>(
+    username: &str,
+    group_id: &str,
+    group_domain: &str,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let hive_grants =
+        self_preservation::snapshot_if_affected(group_id, group_domain, &mut txn).await?;
+
+    let exclusion_id: Option<Uuid> = sqlx::query_scalar(
+        "DELETE FROM membership_exclusions
+        WHERE username = $1
+            AND group_id = $2
+            AND group_domain = $3
+        RETURNING id",
+    )
+    .bind(username)
+    .bind(group_id)
+    .bind(group_domain)
+    .fetch_optional(&mut *txn)
+    .await?;
+
+    let Some(exclusion_id) = exclusion_id else {
+        // user was not excluded from this group, so there's nothing to do
+        // (just return without committing the transaction)
+        return Ok(());
+    };
+
+    self_preservation::enforce(hive_grants, &mut txn, user.username()).await?;
+
+    audit_logs::add_entry(
+        ActionKind::Delete,
+        TargetKind::Membership,
+        format!("{}@{}", group_id, group_domain),
+        user.username(),
+        json!({
+            "old": {
+                "member_type": "exclusion",
+                "id": exclusion_id,
+                "username": username,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+    perms_cache::invalidate_all();
 
     Ok(())
 }
@@ -593,6 +1380,23 @@ pub async fn check_appointment_bounds<'x, X>(
     perms: &PermsEvaluator,
     db: X,
 ) -> AppResult<bool>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    match required_appointment_permission(until, id, domain, db).await? {
+        Some(min) => perms.satisfies(min).await,
+        None => Ok(true),
+    }
+}
+
+// Returns the permission needed to appoint someone with the given `until`
+// time, or None if it is within the default bounds (or the group is exempt)
+pub async fn required_appointment_permission<'x, X>(
+    until: &NaiveDate,
+    id: &str,
+    domain: &str,
+    db: X,
+) -> AppResult<Option<HivePermission>>
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
 {
@@ -610,13 +1414,13 @@ where
     txn.commit().await?;
 
     if exempt {
-        return Ok(true);
+        return Ok(None);
     }
 
     // the default limit for membership upper bound is either 31/Dec of the current
     // year or 30/Jun of the following year, whichever is closer but more
     // than 6 months away
-    let today = Local::now().date_naive();
+    let today = clock::today();
     let limit = if today < NaiveDate::from_ymd_opt(today.year(), 6, 30).unwrap() {
         NaiveDate::from_ymd_opt(today.year(), 12, 31).unwrap()
     } else {
@@ -624,7 +1428,7 @@ where
     };
 
     if *until <= limit {
-        return Ok(true);
+        return Ok(None);
     }
 
     // outside of base case, so need special permission
@@ -637,9 +1441,9 @@ where
     }
     let total_months = total_months.clamp(0, u8::MAX as _) as u8;
 
-    let min = HivePermission::LongTermAppointment(UpperBoundScope::UpTo(total_months));
-
-    perms.satisfies(min).await
+    Ok(Some(HivePermission::LongTermAppointment(
+        UpperBoundScope::UpTo(total_months),
+    )))
 }
 
 pub async fn conditional_bootstrap<'x, X>(username: &str, db: X) -> AppResult<bool>
@@ -648,7 +1452,7 @@ where
 {
     // add user to root group iff it currently has no members
 
-    let today = Local::now().date_naive();
+    let today = clock::today();
 
     let mut txn = db.begin().await?;
 
@@ -712,6 +1516,7 @@ where
     warn!("Bootstrapped user {username} as Hive root until {expiration}");
 
     txn.commit().await?;
+    perms_cache::invalidate_all();
 
     Ok(true)
 }
@@ -747,3 +1552,47 @@ async fn populate_member_names(
 
     Ok(())
 }
+
+// the closure is kept up-to-date for changes to a single user's memberships,
+// but is invalidated by any change to the group hierarchy (see migrations)
+pub async fn rebuild_closure_if_invalid<'x, X>(db: X) -> AppResult<bool>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let valid: bool = sqlx::query_scalar("SELECT valid FROM membership_closure_state")
+        .fetch_one(&mut *txn)
+        .await?;
+
+    if valid {
+        return Ok(false);
+    }
+
+    sqlx::query("SELECT rebuild_membership_closure()")
+        .execute(&mut *txn)
+        .await?;
+
+    txn.commit().await?;
+
+    Ok(true)
+}
+
+// meant to be spawned as a background task on startup
+pub async fn maintain_closure_periodically(db: PgPool) {
+    let mut interval = rocket::tokio::time::interval(CLOSURE_CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if crate::routing::maintenance::is_active() {
+            continue;
+        }
+
+        match rebuild_closure_if_invalid(&db).await {
+            Ok(false) => {}
+            Ok(true) => debug!("Rebuilt membership closure"),
+            Err(e) => error!("Failed to rebuild membership closure: {e}"),
+        }
+    }
+}
@@ -1,12 +1,18 @@
+use rocket::futures::TryStreamExt;
 use serde_json::json;
+use sqlx::{FromRow, Row};
+use uuid::Uuid;
 
 use crate::{
-    dto::permissions::AssignPermissionDto,
+    clock,
+    dto::permissions::{AssignPermissionDto, PermissionKey},
     errors::{AppError, AppResult},
     guards::{perms::PermsEvaluator, user::User},
-    models::{ActionKind, Permission, PermissionAssignment, TargetKind},
-    perms::{HivePermission, SystemsScope},
-    services::{audit_logs, permissions},
+    models::{
+        ActionKind, AssignmentPreview, GroupRef, Permission, PermissionAssignment, TargetKind,
+    },
+    perms::{self, HivePermission, SystemsScope, queries},
+    services::{audit_logs, permissions, perms_cache},
 };
 
 pub async fn get_all_assignments<'x, X>(
@@ -42,6 +48,125 @@ where
     Ok(assignments)
 }
 
+// everything that membership in the group grants, i.e., its own permission
+// assignments plus those inherited from any of its (indirect) parent groups;
+// each assignment comes with every path through which it is inherited, from
+// the closest parent up to the one it's assigned to (empty => direct)
+pub async fn get_effective_assignments<'x, X>(
+    id: &str,
+    domain: &str,
+    db: X,
+) -> AppResult<Vec<(PermissionAssignment, Vec<Vec<GroupRef>>)>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut rows = sqlx::query(
+        "WITH RECURSIVE group_hierarchy(group_id, group_domain, path) AS (
+            SELECT $1::SLUG, $2::DOMAIN, ARRAY[]::GROUP_REF[]
+
+            UNION
+
+            SELECT
+                sg.parent_id,
+                sg.parent_domain,
+                gh.path || (sg.parent_id, sg.parent_domain)::GROUP_REF
+            FROM subgroups sg
+            JOIN group_hierarchy gh
+                ON gh.group_id = sg.child_id
+                AND gh.group_domain = sg.child_domain
+            WHERE NOT (sg.parent_id, sg.parent_domain)::GROUP_REF = ANY(gh.path) -- prevent cycles
+                AND NOT (sg.parent_id = $1 AND sg.parent_domain = $2)
+        )
+        SELECT pa.*, ps.description, gh.path
+        FROM group_hierarchy gh
+        JOIN permission_assignments pa
+            ON pa.group_id = gh.group_id
+            AND pa.group_domain = gh.group_domain
+        JOIN permissions ps
+            ON pa.system_id = ps.system_id
+            AND pa.perm_id = ps.perm_id
+        ORDER BY pa.system_id, pa.perm_id, pa.scope, pa.id, CARDINALITY(gh.path)",
+    )
+    .bind(id)
+    .bind(domain)
+    .fetch(db);
+
+    let mut assignments: Vec<(PermissionAssignment, Vec<Vec<GroupRef>>)> = vec![];
+
+    while let Some(row) = rows.try_next().await? {
+        let path: Vec<GroupRef> = row.try_get("path")?;
+
+        // rows for the same assignment are adjacent, due to the ordering
+        match assignments.last_mut() {
+            Some((last, paths)) if last.id == row.try_get::<Uuid, _>("id")? => paths.push(path),
+            _ => assignments.push((PermissionAssignment::from_row(&row)?, vec![path])),
+        }
+    }
+
+    Ok(assignments)
+}
+
+// safety check against accidentally over-granting (e.g., via a large umbrella
+// group), shown before an assignment is confirmed
+pub async fn preview_assignment<'x, X>(
+    id: &str,
+    domain: &str,
+    perm: &PermissionKey<'_>,
+    scope: Option<&str>,
+    db: X,
+) -> AppResult<AssignmentPreview>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let today = clock::today();
+
+    // new holders are those not already matched like in permission checks
+    // (see `perms::queries::USER_MATCHES`), so wildcards count as held
+    let preview = sqlx::query_as(concat!(
+        "WITH members AS (
+            SELECT
+                am.username,
+                BOOL_AND(EXISTS (
+                    SELECT 1
+                    FROM UNNEST(am.path) node
+                    WHERE node.group_domain <> $2
+                )) AS outside_domain
+            FROM all_members_of($1, $2, $3) am
+            GROUP BY am.username
+        )
+        SELECT
+            COUNT(*) AS members,
+            COUNT(*) FILTER (WHERE NOT EXISTS (
+                SELECT 1
+                FROM permission_assignments pa
+                JOIN all_group_refs_of(m.username, $3) ag
+                    ON ag.id = pa.group_id
+                    AND ag.domain = pa.group_domain
+                WHERE pa.system_id = $4
+                    AND pa.perm_id = $5
+                    AND ",
+        queries::internal_assignments_only!("$7"),
+        "
+                    AND ",
+        queries::scope_matches!("$6"),
+        "
+            )) AS new_holders,
+            COUNT(*) FILTER (WHERE m.outside_domain) AS outside_domain
+        FROM members m"
+    ))
+    .bind(id)
+    .bind(domain)
+    .bind(today)
+    .bind(perm.system_id)
+    .bind(perm.perm_id)
+    .bind(scope)
+    .bind(perms::external_domains())
+    .fetch_one(db)
+    .await?;
+
+    Ok(preview)
+}
+
 pub async fn get_all_assignable<'x, X>(perms: &PermsEvaluator, db: X) -> AppResult<Vec<Permission>>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
@@ -94,6 +219,8 @@ pub async fn assign<'x, X>(
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
 {
+    perms::require_internal_domain(group_domain)?;
+
     let mut txn = db.begin().await?;
 
     let has_scope = permissions::has_scope(dto.perm.system_id, dto.perm.perm_id, &mut *txn).await?;
@@ -110,6 +237,12 @@ where
         ));
     }
 
+    permissions::validate_scope(
+        dto.perm.system_id,
+        dto.perm.perm_id,
+        dto.scope.as_deref().copied(),
+    )?;
+
     let assignment: PermissionAssignment = sqlx::query_as(
         "INSERT INTO permission_assignments (system_id, perm_id, scope, group_id, group_domain)
         VALUES ($1, $2, $3, $4, $5)
@@ -158,6 +291,7 @@ where
     .await?;
 
     txn.commit().await?;
+    perms_cache::invalidate_system(dto.perm.system_id);
 
     Ok(assignment)
 }
@@ -0,0 +1,244 @@
+use chrono::{DateTime, Days, Local};
+use serde_json::json;
+use sqlx::types::Json;
+use uuid::Uuid;
+
+use super::members;
+use crate::{
+    dto::groups::ShareMemberListDto,
+    errors::{AppError, AppResult},
+    guards::user::User,
+    models::{ActionKind, MemberListShare, SharedMember, TargetKind},
+    resolver::IdentityResolver,
+    services::{api_tokens, audit_logs},
+};
+
+// signs share links with a key derived from the application's secret key, such
+// that it's distinct from the one Rocket uses for private cookies
+pub struct ShareLinkSigner(Vec<u8>);
+
+impl ShareLinkSigner {
+    pub fn new(secret_key: &str) -> Self {
+        let secret_key =
+            hex::decode(secret_key).expect("Fatal error: secret key is invalid hex sequence");
+
+        Self(api_tokens::hmac_sha256(
+            &secret_key,
+            b"hive-member-list-shares",
+        ))
+    }
+
+    fn sign(&self, share_id: &Uuid, expires_at: i64) -> Vec<u8> {
+        api_tokens::hmac_sha256(&self.0, &Self::message(share_id, expires_at))
+    }
+
+    fn message(share_id: &Uuid, expires_at: i64) -> Vec<u8> {
+        format!("{share_id}:{expires_at}").into_bytes()
+    }
+
+    pub fn link(&self, share: &MemberListShare) -> String {
+        let expires_at = share.expires_at.timestamp();
+        let signature = hex::encode(self.sign(&share.id, expires_at));
+
+        format!(
+            "/shared/{}?expires={expires_at}&signature={signature}",
+            share.id
+        )
+    }
+
+    fn verify(&self, share_id: &Uuid, expires_at: i64, signature: &str) -> bool {
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+
+        api_tokens::verify_hmac_sha256(&self.0, &Self::message(share_id, expires_at), &signature)
+    }
+}
+
+pub async fn list_active<'x, X>(id: &str, domain: &str, db: X) -> AppResult<Vec<MemberListShare>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let shares = sqlx::query_as(
+        "SELECT *
+        FROM member_list_shares
+        WHERE group_id = $1
+            AND group_domain = $2
+            AND expires_at > $3
+        ORDER BY created_at DESC",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(Local::now())
+    .fetch_all(db)
+    .await?;
+
+    Ok(shares)
+}
+
+// None for anything that shouldn't be shown, without telling why (forged or
+// tampered links, expired or revoked shares)
+pub async fn get_valid<'x, X>(
+    share_id: &Uuid,
+    expires_at: i64,
+    signature: &str,
+    signer: &ShareLinkSigner,
+    db: X,
+) -> AppResult<Option<MemberListShare>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    if !signer.verify(share_id, expires_at, signature) {
+        return Ok(None);
+    }
+
+    let now = Local::now();
+    if expires_at <= now.timestamp() {
+        return Ok(None);
+    }
+
+    let share: Option<MemberListShare> = sqlx::query_as(
+        "SELECT *
+        FROM member_list_shares
+        WHERE id = $1
+            AND expires_at > $2",
+    )
+    .bind(share_id)
+    .bind(now)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(share.filter(|share| share.expires_at.timestamp() == expires_at))
+}
+
+pub async fn create<'x, X>(
+    id: &str,
+    domain: &str,
+    dto: &ShareMemberListDto,
+    db: X,
+    resolver: Option<&IdentityResolver>,
+    user: &User,
+) -> AppResult<MemberListShare>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let members: Vec<_> = members::get_all_members(id, domain, None, &mut *txn, resolver)
+        .await?
+        .into_iter()
+        .map(|member| SharedMember {
+            username: member.username,
+            display_name: member.display_name,
+            manager: member.manager,
+        })
+        .collect();
+
+    let now = Local::now();
+    let expires_at: DateTime<Local> = now + Days::new(dto.days.into());
+
+    // (expired shares are useless, so this is as good a time as any to clean up)
+    sqlx::query(
+        "DELETE FROM member_list_shares
+        WHERE group_id = $1
+            AND group_domain = $2
+            AND expires_at <= $3",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(now)
+    .execute(&mut *txn)
+    .await?;
+
+    let share: MemberListShare = sqlx::query_as(
+        "INSERT INTO member_list_shares
+            (group_id, group_domain, members, created_by, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING *",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(Json(&members))
+    .bind(user.username())
+    .bind(now)
+    .bind(expires_at)
+    .fetch_one(&mut *txn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(err) if err.is_foreign_key_violation() => {
+            AppError::NoSuchGroup(id.to_string(), domain.to_string())
+        }
+        _ => e.into(),
+    })?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        format!("{id}@{domain}"),
+        user.username(),
+        json!({
+            "new": {
+                "member_list_share": {
+                    "id": share.id,
+                    "n_members": members.len(),
+                    "expires_at": share.expires_at,
+                },
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(share)
+}
+
+pub async fn revoke<'x, X>(
+    share_id: &Uuid,
+    id: &str,
+    domain: &str,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let share: MemberListShare = sqlx::query_as(
+        "DELETE FROM member_list_shares
+        WHERE id = $1
+            AND group_id = $2
+            AND group_domain = $3
+        RETURNING *",
+    )
+    .bind(share_id)
+    .bind(id)
+    .bind(domain)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or(AppError::NoSuchMemberListShare(*share_id))?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        format!("{id}@{domain}"),
+        user.username(),
+        json!({
+            "old": {
+                "member_list_share": {
+                    "id": share.id,
+                    "n_members": share.members.len(),
+                    "expires_at": share.expires_at,
+                },
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use log::*;
+use sqlx::PgPool;
+
+use crate::{clock, errors::AppResult, models::MemberCountSnapshot};
+
+// a snapshot is only taken once per day, but the first check after midnight
+// shouldn't have to wait too long
+const SNAPSHOT_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub async fn list_member_counts<'x, X>(
+    id: &str,
+    domain: &str,
+    db: X,
+) -> AppResult<Vec<MemberCountSnapshot>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let snapshots = sqlx::query_as(
+        "SELECT day, n_direct, n_total
+        FROM group_member_counts
+        WHERE group_id = $1
+            AND group_domain = $2
+        ORDER BY day",
+    )
+    .bind(id)
+    .bind(domain)
+    .fetch_all(db)
+    .await?;
+
+    Ok(snapshots)
+}
+
+// records today's member counts of every group, unless already done (e.g., by
+// another instance); returns how many groups were snapshotted
+pub async fn snapshot_member_counts_if_due<'x, X>(db: X) -> AppResult<u64>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let today = clock::today();
+
+    // (so that multiple instances can't both compute a snapshot)
+    sqlx::query("LOCK TABLE group_member_counts IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *txn)
+        .await?;
+
+    let done: bool = sqlx::query_scalar(
+        "SELECT EXISTS (
+            SELECT 1
+            FROM group_member_counts
+            WHERE day = $1
+        )",
+    )
+    .bind(today)
+    .fetch_one(&mut *txn)
+    .await?;
+
+    if done {
+        return Ok(0);
+    }
+
+    let result = sqlx::query(
+        "INSERT INTO group_member_counts (group_id, group_domain, day, n_direct, n_total)
+        SELECT g.id,
+            g.domain,
+            $1,
+            COUNT(DISTINCT m.username) FILTER (WHERE ARRAY_LENGTH(m.path, 1) = 1),
+            COUNT(DISTINCT m.username)
+        FROM groups g
+        LEFT JOIN LATERAL all_members_of(g.id, g.domain, $1) m
+            ON TRUE
+        GROUP BY g.id, g.domain",
+    )
+    .bind(today)
+    .execute(&mut *txn)
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(result.rows_affected())
+}
+
+// meant to be spawned as a background task on startup
+pub async fn snapshot_periodically(db: PgPool) {
+    let mut interval = rocket::tokio::time::interval(SNAPSHOT_CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if crate::routing::maintenance::is_active() {
+            continue;
+        }
+
+        match snapshot_member_counts_if_due(&db).await {
+            Ok(0) => {}
+            Ok(n) => info!("Recorded member counts of {n} groups"),
+            Err(e) => error!("Failed to record member counts: {e}"),
+        }
+    }
+}
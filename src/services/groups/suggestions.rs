@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    errors::{AppError, AppResult},
+    guards::{perms::PermsEvaluator, user::User},
+    models::SimpleGroup,
+    services::groups::{self, AuthorityInGroup},
+};
+
+// how many groups in a domain some group must already be a parent of before
+// it is suggested as a parent for new groups in that domain
+const COMMON_PARENT_MIN_CHILDREN: i64 = 3;
+const COMMON_PARENT_LIMIT: i64 = 3;
+
+// domains in which new groups are linked as (non-manager) subgroups of their
+// best suggested parent by default, i.e., unless unchecked on creation
+pub struct AutoLinkDomains(HashSet<String>);
+
+impl AutoLinkDomains {
+    pub fn new(domains: Vec<String>) -> Self {
+        Self(domains.into_iter().collect())
+    }
+
+    pub fn contains(&self, domain: &str) -> bool {
+        self.0.contains(domain)
+    }
+}
+
+#[derive(Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionReason {
+    NamePrefix,   // e.g., mottagningen@x for mottagningen-2025@x
+    Umbrella,     // e.g., metaspexet@metaspexet.se, named after the domain
+    CommonParent, // already a parent of many other groups in the domain
+}
+
+#[derive(Serialize)]
+pub struct ParentSuggestion {
+    pub group: SimpleGroup,
+    pub reason: SuggestionReason,
+    pub preselected: bool,
+}
+
+// likely parents for a group about to be created, best first; only groups the
+// user could actually add a subgroup to are suggested
+pub async fn suggest_parents(
+    id: &str,
+    domain: &str,
+    auto_link: &AutoLinkDomains,
+    db: &PgPool,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<Vec<ParentSuggestion>> {
+    let mut candidates = vec![];
+
+    let by_prefix: Vec<SimpleGroup> = sqlx::query_as(
+        "SELECT id, domain, name_sv, name_en
+        FROM groups
+        WHERE domain = $1
+            AND $2 LIKE id || '-%'
+        ORDER BY length(id) DESC",
+    )
+    .bind(domain)
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+    candidates.extend(
+        by_prefix
+            .into_iter()
+            .map(|g| (g, SuggestionReason::NamePrefix)),
+    );
+
+    let umbrella_id = domain.split('.').next().unwrap_or(domain);
+    let umbrella: Option<SimpleGroup> = groups::details::get_one(umbrella_id, domain, db).await?;
+    candidates.extend(umbrella.map(|g| (g, SuggestionReason::Umbrella)));
+
+    let common: Vec<SimpleGroup> = sqlx::query_as(
+        "SELECT g.id, g.domain, g.name_sv, g.name_en
+        FROM subgroups sg
+        JOIN groups g
+            ON g.id = sg.parent_id
+            AND g.domain = sg.parent_domain
+        WHERE sg.child_domain = $1
+        GROUP BY g.id, g.domain, g.name_sv, g.name_en
+        HAVING COUNT(*) >= $2
+        ORDER BY COUNT(*) DESC, g.id
+        LIMIT $3",
+    )
+    .bind(domain)
+    .bind(COMMON_PARENT_MIN_CHILDREN)
+    .bind(COMMON_PARENT_LIMIT)
+    .fetch_all(db)
+    .await?;
+    candidates.extend(
+        common
+            .into_iter()
+            .map(|g| (g, SuggestionReason::CommonParent)),
+    );
+
+    let mut seen = HashSet::new();
+    let mut suggestions = Vec::with_capacity(candidates.len());
+
+    for (group, reason) in candidates {
+        if (group.id == id && group.domain == domain) || !seen.insert(group.key()) {
+            continue;
+        }
+
+        let authority = groups::details::require_authority(
+            AuthorityInGroup::ManageMembers,
+            &group.id,
+            &group.domain,
+            db,
+            perms,
+            user,
+        )
+        .await;
+
+        match authority {
+            Ok(_) => {}
+            Err(AppError::InsufficientAuthorityInGroup(_)) => continue,
+            Err(err) => return Err(err),
+        }
+
+        suggestions.push(ParentSuggestion {
+            group,
+            reason,
+            preselected: false,
+        });
+    }
+
+    // only the best suggestion is linked by default, since linking to e.g. an
+    // umbrella group as well as one of its subgroups would be redundant
+    if let Some(best) = suggestions.first_mut() {
+        best.preselected =
+            auto_link.contains(domain) && best.reason != SuggestionReason::CommonParent;
+    }
+
+    Ok(suggestions)
+}
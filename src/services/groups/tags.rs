@@ -6,8 +6,8 @@ use crate::{
     errors::{AppError, AppResult},
     guards::{perms::PermsEvaluator, user::User},
     models::{ActionKind, Tag, TagAssignment, TargetKind},
-    perms::{HivePermission, SystemsScope},
-    services::{audit_logs, tags},
+    perms::{HivePermission, SystemsScope, TagScope},
+    services::{audit_logs, deletions, tags},
 };
 
 pub async fn get_all_assignments<'x, X>(
@@ -36,9 +36,9 @@ where
     .await?;
 
     for assignment in &mut assignments {
-        let min = HivePermission::AssignTags(SystemsScope::Id(assignment.system_id.clone()));
+        let possibilities = HivePermission::assign_tag(&assignment.system_id, &assignment.tag_id);
         // query should be OK since perms are cached by perm_id
-        assignment.can_manage = Some(perms.satisfies(min).await?);
+        assignment.can_manage = Some(perms.satisfies_any_of(&possibilities).await?);
     }
 
     Ok(assignments)
@@ -57,13 +57,20 @@ where
     );
 
     if let Some(system_ids) = systems_filter {
-        if system_ids.is_empty() {
+        // tags that were delegated individually (regardless of system)
+        let (tag_system_ids, tag_ids) = get_tags_filter(perms).await?;
+
+        if system_ids.is_empty() && tag_ids.is_empty() {
             return Ok(vec![]);
         }
 
-        query.push(" AND system_id = ANY(");
+        query.push(" AND (system_id = ANY(");
         query.push_bind(system_ids);
-        query.push(")");
+        query.push(") OR (system_id, tag_id) IN (SELECT * FROM UNNEST(");
+        query.push_bind(tag_system_ids);
+        query.push("::TEXT[], ");
+        query.push_bind(tag_ids);
+        query.push("::TEXT[])))");
     }
 
     let permissions = query.build_query_as().fetch_all(db).await?;
@@ -90,6 +97,30 @@ async fn get_systems_filter(perms: &PermsEvaluator) -> AppResult<Option<Vec<Stri
     Ok(Some(systems_filter))
 }
 
+// returns (system IDs, tag IDs) of individually assignable tags, as parallel
+// arrays (to be unnested together)
+async fn get_tags_filter(perms: &PermsEvaluator) -> AppResult<(Vec<String>, Vec<String>)> {
+    let hive_perms = perms
+        .fetch_all_related(HivePermission::AssignTag(TagScope::Any))
+        .await?;
+
+    let mut system_ids = vec![];
+    let mut tag_ids = vec![];
+    for perm in hive_perms {
+        if let HivePermission::AssignTag(scope) = perm {
+            match scope {
+                TagScope::Id(system_id, tag_id) => {
+                    system_ids.push(system_id);
+                    tag_ids.push(tag_id);
+                }
+                TagScope::Any => unreachable!("? is not a real scope"),
+            }
+        }
+    }
+
+    Ok((system_ids, tag_ids))
+}
+
 pub async fn assign<'x, X>(
     group_id: &str,
     group_domain: &str,
@@ -173,7 +204,7 @@ where
         dto.tag.system_id,
         dto.tag.tag_id,
         true,
-        false, // we currently don't support bulk-assigning tags with content
+        dto.content.is_some(),
         &mut *txn,
     )
     .await?;
@@ -181,12 +212,13 @@ where
     for group in &dto.selected {
         let assignment_id: Option<Uuid> = sqlx::query_scalar(
             "INSERT INTO tag_assignments (system_id, tag_id, content, group_id, group_domain)
-            VALUES ($1, $2, NULL, $3, $4)
+            VALUES ($1, $2, $3, $4, $5)
             ON CONFLICT DO NOTHING
             RETURNING id",
         )
         .bind(dto.tag.system_id)
         .bind(dto.tag.tag_id)
+        .bind(dto.content)
         .bind(group.id)
         .bind(group.domain)
         .fetch_optional(&mut *txn)
@@ -204,7 +236,7 @@ where
                         "id": assignment_id,
                         "group_id": group.id,
                         "group_domain": group.domain,
-                        "content": None::<&str>,
+                        "content": dto.content,
                     }
                 }),
                 &mut *txn,
@@ -218,6 +250,81 @@ where
     Ok(())
 }
 
+// removes the tag from all selected groups (only with the given content, if
+// any); returns None if none of them were (directly) tagged to begin with
+pub async fn bulk_unassign<'x, X>(
+    dto: &BulkTagGroupsDto<'_>,
+    db: X,
+    user: &User,
+) -> AppResult<Option<Uuid>>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let deletion_id = deletions::track(
+        TargetKind::TagAssignment,
+        dto.tag.key(),
+        user.username(),
+        &mut *txn,
+    )
+    .await?;
+
+    let mut any = false;
+
+    for group in &dto.selected {
+        let removed: Vec<(Uuid, Option<String>)> = sqlx::query_as(
+            "DELETE FROM tag_assignments
+            WHERE system_id = $1
+                AND tag_id = $2
+                AND group_id = $3
+                AND group_domain = $4
+                AND ($5::TEXT IS NULL OR content = $5)
+            RETURNING id, content",
+        )
+        .bind(dto.tag.system_id)
+        .bind(dto.tag.tag_id)
+        .bind(group.id)
+        .bind(group.domain)
+        .bind(dto.content)
+        .fetch_all(&mut *txn)
+        .await?;
+
+        for (assignment_id, content) in removed {
+            any = true;
+
+            audit_logs::add_entry(
+                ActionKind::Delete,
+                TargetKind::TagAssignment,
+                dto.tag.key(),
+                user.username(),
+                json!({
+                    "old": {
+                        "entity_type": "group",
+                        "id": assignment_id,
+                        "group_id": group.id,
+                        "group_domain": group.domain,
+                        "content": content,
+                    }
+                }),
+                &mut *txn,
+            )
+            .await?;
+        }
+    }
+
+    deletions::untrack(&mut *txn).await?;
+
+    if !any {
+        // (just return without committing the transaction)
+        return Ok(None);
+    }
+
+    txn.commit().await?;
+
+    Ok(Some(deletion_id))
+}
+
 pub async fn is_tagged_for_system<'x, X>(
     id: &str,
     domain: &str,
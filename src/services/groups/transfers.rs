@@ -0,0 +1,470 @@
+use serde_json::json;
+
+use super::{RoleInGroup, details, members};
+use crate::{
+    clock,
+    dto::groups::{ProposeTransferDto, TransferRecipientDto},
+    errors::{AppError, AppResult},
+    guards::{perms::PermsEvaluator, user::User},
+    models::{ActionKind, GroupMember, OwnershipTransfer, TargetKind},
+    perms,
+    services::{
+        audit_logs,
+        changes::ProtectedDomains,
+        membership_emails::{self, MembershipEmailKind},
+        perms_cache,
+    },
+};
+
+pub async fn get_pending<'x, X>(
+    id: &str,
+    domain: &str,
+    db: X,
+) -> AppResult<Option<OwnershipTransfer>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let transfer = sqlx::query_as(
+        "SELECT *
+        FROM ownership_transfers
+        WHERE group_id = $1
+            AND group_domain = $2",
+    )
+    .bind(id)
+    .bind(domain)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(transfer)
+}
+
+// transfers that the user can accept, either directly or as a manager of the
+// receiving group
+pub async fn list_incoming<'x, X>(username: &str, db: X) -> AppResult<Vec<OwnershipTransfer>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let today = clock::today();
+
+    let transfers = sqlx::query_as(
+        "SELECT ot.*
+        FROM ownership_transfers ot
+        WHERE ot.to_username = $1
+            OR EXISTS (
+                SELECT 1
+                FROM all_members_of(ot.to_group_id, ot.to_group_domain, $2) am
+                WHERE am.username = $1
+                    AND am.manager
+            )
+        ORDER BY ot.proposed_at DESC",
+    )
+    .bind(username)
+    .bind(today)
+    .fetch_all(db)
+    .await?;
+
+    Ok(transfers)
+}
+
+pub async fn is_recipient<'x, X>(
+    transfer: &OwnershipTransfer,
+    username: &str,
+    db: X,
+) -> AppResult<bool>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    match (
+        &transfer.to_username,
+        &transfer.to_group_id,
+        &transfer.to_group_domain,
+    ) {
+        (Some(to_username), _, _) => Ok(to_username == username),
+        (None, Some(to_group_id), Some(to_group_domain)) => {
+            let role =
+                details::get_role_in_group(username, to_group_id, to_group_domain, db).await?;
+
+            Ok(matches!(role, Some(RoleInGroup::Manager)))
+        }
+        _ => Ok(false),
+    }
+}
+
+fn audit_log_details(transfer: &OwnershipTransfer, state: &str) -> serde_json::Value {
+    json!({
+        "recipient": transfer.recipient(),
+        "until": transfer.until,
+        "proposed_by": transfer.proposed_by,
+        "state": state,
+    })
+}
+
+// replaces any transfer that was already pending for the group
+pub async fn propose<'v, 'x, X>(
+    id: &str,
+    domain: &str,
+    dto: &ProposeTransferDto<'v>,
+    protected: &ProtectedDomains,
+    db: X,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<OwnershipTransfer>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    if domain == crate::HIVE_INTERNAL_DOMAIN {
+        // could (at best) leave nobody able to administrate Hive
+        return Err(AppError::SelfPreservation);
+    }
+
+    let mut txn = db.begin().await?;
+
+    let (to_username, to_group_id, to_group_domain, until) = match &dto.recipient {
+        TransferRecipientDto::User(username) => {
+            let until = dto.until.as_ref().map(|until| until.0);
+
+            if let Some(until) = &until
+                && !members::check_appointment_bounds(until, id, domain, perms, &mut *txn).await?
+            {
+                return Err(AppError::AppointmentTooLong(username.to_string()));
+            }
+
+            (Some(*username), None, None, until)
+        }
+        TransferRecipientDto::Group(group) => {
+            ensure_valid_subgroup(id, domain, group.id, group.domain, protected, &mut *txn).await?;
+
+            (None, Some(group.id), Some(group.domain), None)
+        }
+    };
+
+    let transfer: OwnershipTransfer = sqlx::query_as(
+        "INSERT INTO ownership_transfers (group_id, group_domain, to_username, to_group_id, \
+         to_group_domain, \"until\", proposed_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (group_id, group_domain) DO UPDATE
+        SET
+            to_username = EXCLUDED.to_username,
+            to_group_id = EXCLUDED.to_group_id,
+            to_group_domain = EXCLUDED.to_group_domain,
+            \"until\" = EXCLUDED.\"until\",
+            proposed_by = EXCLUDED.proposed_by,
+            proposed_at = now()
+        RETURNING *",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(to_username)
+    .bind(to_group_id)
+    .bind(to_group_domain)
+    .bind(until)
+    .bind(user.username())
+    .fetch_one(&mut *txn)
+    .await
+    .map_err(|e| match (e, &dto.recipient) {
+        (sqlx::Error::Database(err), TransferRecipientDto::Group(group))
+            if err.is_foreign_key_violation() =>
+        {
+            AppError::NoSuchGroup(group.id.to_string(), group.domain.to_string())
+        }
+        (e, _) => e.into(),
+    })?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        transfer.group_key(),
+        user.username(),
+        json!({
+            "new": {
+                "transfer": audit_log_details(&transfer, "pending"),
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(transfer)
+}
+
+// the receiving group becomes a subgroup, so the same rules apply
+async fn ensure_valid_subgroup<'x, X>(
+    parent_id: &str,
+    parent_domain: &str,
+    child_id: &str,
+    child_domain: &str,
+    protected: &ProtectedDomains,
+    db: X,
+) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    perms::require_valid_nesting(parent_domain, child_id, child_domain)?;
+
+    if protected.contains(parent_domain) {
+        // the link is made when accepting, not through a pending change
+        return Err(AppError::UnapprovableProtectedChange(
+            parent_domain.to_owned(),
+        ));
+    }
+
+    let invalid = if parent_id == child_id && parent_domain == child_domain {
+        true
+    } else {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) > 0
+            FROM all_subgroups_of($1, $2)
+            WHERE child_id = $3
+                AND child_domain = $4",
+        )
+        .bind(child_id)
+        .bind(child_domain)
+        .bind(parent_id)
+        .bind(parent_domain)
+        .fetch_one(db)
+        .await?
+    };
+
+    if invalid {
+        Err(AppError::InvalidSubgroup(
+            child_id.to_owned(),
+            child_domain.to_owned(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// removes the group's pending transfer, which only takes effect once the
+// transaction is committed (so callers can still bail out)
+async fn take_pending<'x, X>(id: &str, domain: &str, db: X) -> AppResult<OwnershipTransfer>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let transfer: OwnershipTransfer = sqlx::query_as(
+        "DELETE FROM ownership_transfers
+        WHERE group_id = $1
+            AND group_domain = $2
+        RETURNING *",
+    )
+    .bind(id)
+    .bind(domain)
+    .fetch_optional(db)
+    .await?
+    .ok_or(AppError::NoPendingTransfer)?;
+
+    Ok(transfer)
+}
+
+// by one of the group's managers (or someone otherwise authorized)
+pub async fn cancel<'x, X>(id: &str, domain: &str, db: X, user: &User) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let transfer = take_pending(id, domain, &mut *txn).await?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        transfer.group_key(),
+        user.username(),
+        json!({
+            "old": {
+                "transfer": audit_log_details(&transfer, "pending"),
+            },
+            "new": {
+                "transfer": audit_log_details(&transfer, "cancelled"),
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+// by the receiving party
+pub async fn decline<'x, X>(id: &str, domain: &str, db: X, user: &User) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let transfer = take_pending(id, domain, &mut *txn).await?;
+
+    if !is_recipient(&transfer, user.username(), &mut *txn).await? {
+        return Err(AppError::NotTransferRecipient);
+    }
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        transfer.group_key(),
+        user.username(),
+        json!({
+            "old": {
+                "transfer": audit_log_details(&transfer, "pending"),
+            },
+            "new": {
+                "transfer": audit_log_details(&transfer, "declined"),
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+// by the receiving party; all current managers (direct members and subgroups)
+// are demoted to regular members, and the recipient becomes the only manager
+pub async fn accept<'x, X>(
+    id: &str,
+    domain: &str,
+    protected: &ProtectedDomains,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let today = clock::today();
+
+    let mut txn = db.begin().await?;
+
+    let transfer = take_pending(id, domain, &mut *txn).await?;
+
+    if !is_recipient(&transfer, user.username(), &mut *txn).await? {
+        return Err(AppError::NotTransferRecipient);
+    }
+
+    if transfer.until.is_some_and(|until| until < today) {
+        // proposal was left pending for too long to make sense anymore, so it
+        // is just discarded (a new one is needed)
+        txn.commit().await?;
+
+        return Err(AppError::NoPendingTransfer);
+    }
+
+    let demoted: Vec<GroupMember> = sqlx::query_as(
+        "UPDATE direct_memberships
+        SET manager = FALSE
+        WHERE group_id = $1
+            AND group_domain = $2
+            AND manager
+            AND \"until\" >= $3
+        RETURNING *",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(today)
+    .fetch_all(&mut *txn)
+    .await?;
+
+    let old_manager_subgroups: Vec<(String, String)> = sqlx::query_as(
+        "UPDATE subgroups
+        SET manager = FALSE
+        WHERE parent_id = $1
+            AND parent_domain = $2
+            AND manager
+            AND (child_id, child_domain) IS DISTINCT FROM ($3, $4)
+        RETURNING child_id, child_domain",
+    )
+    .bind(id)
+    .bind(domain)
+    .bind(&transfer.to_group_id)
+    .bind(&transfer.to_group_domain)
+    .fetch_all(&mut *txn)
+    .await?;
+
+    if let (Some(username), Some(until)) = (&transfer.to_username, &transfer.until) {
+        sqlx::query(
+            "INSERT INTO direct_memberships (username, group_id, group_domain, \"from\", \
+             \"until\", manager)
+            VALUES ($1, $2, $3, $4, $5, TRUE)",
+        )
+        .bind(username)
+        .bind(id)
+        .bind(domain)
+        .bind(today)
+        .bind(until)
+        .execute(&mut *txn)
+        .await?;
+    } else if let (Some(child_id), Some(child_domain)) =
+        (&transfer.to_group_id, &transfer.to_group_domain)
+    {
+        // the hierarchy might have changed since the transfer was proposed
+        ensure_valid_subgroup(id, domain, child_id, child_domain, protected, &mut *txn).await?;
+
+        sqlx::query(
+            "INSERT INTO subgroups (parent_id, parent_domain, child_id, child_domain, manager)
+            VALUES ($1, $2, $3, $4, TRUE)
+            ON CONFLICT (parent_id, parent_domain, child_id, child_domain) DO UPDATE
+            SET manager = TRUE",
+        )
+        .bind(id)
+        .bind(domain)
+        .bind(child_id)
+        .bind(child_domain)
+        .execute(&mut *txn)
+        .await?;
+    }
+
+    for member in &demoted {
+        membership_emails::enqueue(
+            MembershipEmailKind::Edited,
+            member,
+            id,
+            domain,
+            user.username(),
+            &mut *txn,
+        )
+        .await?;
+    }
+
+    let old_managers: Vec<_> = demoted.into_iter().map(|member| member.username).collect();
+    let old_manager_subgroups: Vec<_> = old_manager_subgroups
+        .into_iter()
+        .map(|(id, domain)| format!("{id}@{domain}"))
+        .collect();
+
+    let (new_managers, new_manager_subgroups) = if transfer.to_username.is_some() {
+        (vec![transfer.recipient()], vec![])
+    } else {
+        (vec![], vec![transfer.recipient()])
+    };
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::Group,
+        transfer.group_key(),
+        user.username(),
+        json!({
+            "old": {
+                "transfer": audit_log_details(&transfer, "pending"),
+                "managers": old_managers,
+                "manager_subgroups": old_manager_subgroups,
+            },
+            "new": {
+                "transfer": audit_log_details(&transfer, "accepted"),
+                "managers": new_managers,
+                "manager_subgroups": new_manager_subgroups,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+    perms_cache::invalidate_all();
+
+    Ok(())
+}
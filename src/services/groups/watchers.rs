@@ -0,0 +1,90 @@
+use sqlx::{FromRow, PgPool, Row};
+
+use super::{AuthorityInGroup, details};
+use crate::{errors::AppResult, models::Group};
+
+pub async fn is_watching<'x, X>(username: &str, id: &str, domain: &str, db: X) -> AppResult<bool>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let watching = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0
+        FROM group_watchers
+        WHERE username = $1
+            AND group_id = $2
+            AND group_domain = $3",
+    )
+    .bind(username)
+    .bind(id)
+    .bind(domain)
+    .fetch_one(db)
+    .await?;
+
+    Ok(watching)
+}
+
+// (whether the user can view the group must be checked beforehand)
+pub async fn set_watching<'x, X>(
+    username: &str,
+    id: &str,
+    domain: &str,
+    watching: bool,
+    db: X,
+) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let query = if watching {
+        "INSERT INTO group_watchers (username, group_id, group_domain)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING"
+    } else {
+        "DELETE FROM group_watchers
+        WHERE username = $1
+            AND group_id = $2
+            AND group_domain = $3"
+    };
+
+    sqlx::query(query)
+        .bind(username)
+        .bind(id)
+        .bind(domain)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+// every watched group with its watcher, as long as they can still view it;
+// watchers who lost access since are simply skipped (rather than removed), in
+// case they regain it later
+pub async fn list_effective(db: &PgPool) -> AppResult<Vec<(String, Group)>> {
+    let rows = sqlx::query(
+        "SELECT gw.username AS watcher_username, g.*
+        FROM group_watchers gw
+        JOIN groups g
+            ON g.id = gw.group_id
+            AND g.domain = gw.group_domain
+        ORDER BY gw.username, g.domain, g.id",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut effective = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let username: String = row.try_get("watcher_username")?;
+        let group = Group::from_row(&row)?;
+
+        let role = details::get_role_in_group(&username, &group.id, &group.domain, db).await?;
+        let can_view = role.is_some()
+            || details::get_authority_of(&username, &group.id, &group.domain, db).await?
+                != AuthorityInGroup::None;
+
+        if can_view {
+            effective.push((username, group));
+        }
+    }
+
+    Ok(effective)
+}
@@ -0,0 +1,197 @@
+use log::*;
+
+use super::{changes::ProtectedDomains, groups, perms_cache};
+use crate::{
+    HIVE_INTERNAL_DOMAIN,
+    dto::{
+        datetime::BrowserDateDto,
+        groups::{AddMemberDto, AddSubgroupDto, CreateGroupDto, GroupRefDto},
+        imports::LegacyDumpDto,
+        permissions::{AssignPermissionDto, PermissionKey},
+    },
+    errors::{AppError, AppResult},
+    guards::user::User,
+    models::{ImportOutcome, ImportedEntry, ImportedEntryKind},
+};
+
+// everything is imported in a single transaction, but each entry is applied
+// on its own (in a savepoint, via the usual services, so also audit logged),
+// such that failures are just reported instead of aborting the whole import;
+// a dry run goes through exactly the same motions, but is never committed
+pub async fn import_legacy<'x, X>(
+    dump: &LegacyDumpDto,
+    dry_run: bool,
+    db: X,
+    user: &User,
+) -> AppResult<Vec<ImportedEntry>>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let mut report = Vec::with_capacity(
+        dump.groups.len() + dump.subgroups.len() + dump.memberships.len() + dump.permissions.len(),
+    );
+
+    // a dump is restored as-is, without any changes needing approval
+    let no_protection = ProtectedDomains::new(vec![]);
+
+    // groups must come first, since everything else refers to them
+    for group in &dump.groups {
+        let key = format!("{}@{}", group.id, group.domain);
+
+        let dto = CreateGroupDto {
+            id: group.id.as_str().into(),
+            domain: group.domain.as_str().into(),
+            name_sv: group.name_sv.as_str().into(),
+            name_en: group.name_en.as_str().into(),
+            description_sv: group.description_sv.as_str().into(),
+            description_en: group.description_en.as_str().into(),
+            parents: vec![], // linked separately below
+            proposed: false,
+        };
+
+        let result = groups::management::create(&dto, &no_protection, &mut *txn, user).await;
+        report.push(entry(ImportedEntryKind::Group, key.clone(), key, result));
+    }
+
+    for subgroup in &dump.subgroups {
+        let legacy = format!("{} ⊃ {}", subgroup.parent, subgroup.child);
+
+        let result = match (parse_key(&subgroup.parent), parse_key(&subgroup.child)) {
+            (Ok((parent_id, parent_domain)), Ok((child_id, child_domain))) => {
+                let dto = AddSubgroupDto {
+                    child: GroupRefDto {
+                        id: child_id,
+                        domain: child_domain,
+                    },
+                    manager: subgroup.manager,
+                };
+
+                groups::members::add_subgroup(parent_id, parent_domain, &dto, &mut *txn, user).await
+            }
+            (Err(err), _) | (_, Err(err)) => Err(err),
+        };
+
+        report.push(entry(
+            ImportedEntryKind::Subgroup,
+            legacy,
+            subgroup.child.clone(),
+            result,
+        ));
+    }
+
+    for membership in &dump.memberships {
+        let legacy = format!(
+            "{} ∈ {} ({} – {})",
+            membership.username, membership.group, membership.from, membership.until
+        );
+
+        let result = match parse_key(&membership.group) {
+            Ok((_, HIVE_INTERNAL_DOMAIN)) => Err(AppError::SelfPreservation),
+            Ok((id, domain)) => {
+                let dto = AddMemberDto {
+                    username: membership.username.as_str().into(),
+                    from: BrowserDateDto(membership.from),
+                    until: BrowserDateDto(membership.until),
+                    from_time: None,
+                    until_time: None,
+                    manager: membership.manager,
+                    note: None.into(),
+                    justification: None.into(),
+                    operational_year: None,
+                };
+
+                groups::members::add_member(id, domain, &dto, &mut *txn, None, user.username())
+                    .await
+                    .map(|_| ())
+            }
+            Err(err) => Err(err),
+        };
+
+        report.push(entry(
+            ImportedEntryKind::Membership,
+            legacy,
+            membership.group.clone(),
+            result,
+        ));
+    }
+
+    for permission in &dump.permissions {
+        let perm_key = match &permission.scope {
+            Some(scope) => format!("${}:{}:{scope}", permission.system_id, permission.perm_id),
+            None => format!("${}:{}", permission.system_id, permission.perm_id),
+        };
+        let legacy = format!("{perm_key} → {}", permission.group);
+
+        let result = match parse_key(&permission.group) {
+            Ok((_, HIVE_INTERNAL_DOMAIN)) => Err(AppError::SelfPreservation),
+            Ok((id, domain)) => {
+                let dto = AssignPermissionDto {
+                    perm: PermissionKey {
+                        system_id: &permission.system_id,
+                        perm_id: &permission.perm_id,
+                    },
+                    scope: permission.scope.as_deref().map(Into::into),
+                };
+
+                groups::permissions::assign(id, domain, &dto, &mut *txn, user)
+                    .await
+                    .map(|_| ())
+            }
+            Err(err) => Err(err),
+        };
+
+        report.push(entry(
+            ImportedEntryKind::Permission,
+            legacy,
+            perm_key,
+            result,
+        ));
+    }
+
+    if dry_run {
+        // (just return without committing the transaction)
+        return Ok(report);
+    }
+
+    txn.commit().await?;
+    perms_cache::invalidate_all();
+
+    info!(
+        "Legacy dump with {} entries imported by {}",
+        report.len(),
+        user.username()
+    );
+
+    Ok(report)
+}
+
+fn parse_key(key: &str) -> AppResult<(&str, &str)> {
+    key.split_once('@')
+        .ok_or_else(|| AppError::InvalidLegacyDump(format!("`{key}` is not a group key")))
+}
+
+fn entry(
+    kind: ImportedEntryKind,
+    legacy: String,
+    key: String,
+    result: AppResult<()>,
+) -> ImportedEntry {
+    let outcome = match result {
+        Ok(()) => ImportOutcome::Created(key),
+        Err(
+            AppError::DuplicateGroupId(..)
+            | AppError::DuplicateSubgroup(..)
+            | AppError::RedundantMembership(..)
+            | AppError::DuplicatePermissionAssignment(..),
+        ) => ImportOutcome::Existing(key),
+        Err(err) => ImportOutcome::Failed(err.to_string()),
+    };
+
+    ImportedEntry {
+        kind,
+        legacy,
+        outcome,
+    }
+}
@@ -1,22 +1,201 @@
+use std::collections::HashMap;
+
+use chrono::{Local, TimeDelta};
 use serde_json::json;
+use uuid::Uuid;
 
 use crate::{
-    errors::AppResult,
-    models::{ActionKind, TagAssignment, TargetKind},
+    errors::{AppError, AppResult},
+    guards::user::User,
+    models::{ActionKind, IntegrationTaskLogEntry, IntegrationTaskRun, TagAssignment, TargetKind},
     services::audit_logs,
 };
 
+pub async fn list_recent_task_runs<'x, X>(
+    integration_id: &str,
+    limit: i64,
+    db: X,
+) -> AppResult<Vec<IntegrationTaskRun>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let runs = sqlx::query_as(
+        "SELECT run_id, task_id, start_stamp, end_stamp, succeeded, skipped
+        FROM integration_task_runs
+        WHERE integration_id = $1
+        ORDER BY start_stamp DESC
+        LIMIT $2",
+    )
+    .bind(integration_id)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    Ok(runs)
+}
+
+pub async fn require_task_run<'x, X>(
+    integration_id: &str,
+    run_id: &Uuid,
+    db: X,
+) -> AppResult<IntegrationTaskRun>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    sqlx::query_as(
+        "SELECT run_id, task_id, start_stamp, end_stamp, succeeded, skipped
+        FROM integration_task_runs
+        WHERE integration_id = $1
+            AND run_id = $2",
+    )
+    .bind(integration_id)
+    .bind(run_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NoSuchTaskRun(*run_id))
+}
+
+pub async fn get_task_run_logs<'x, X>(
+    integration_id: &str,
+    run_id: &Uuid,
+    db: X,
+) -> AppResult<Vec<IntegrationTaskLogEntry>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    // join ensures that the run actually belongs to this integration
+    let logs = sqlx::query_as(
+        "SELECT l.kind, l.stamp, l.message
+        FROM integration_task_logs l
+        JOIN integration_task_runs r
+            ON r.run_id = l.run_id
+        WHERE r.integration_id = $1
+            AND l.run_id = $2
+        ORDER BY l.stamp, l.entry_id",
+    )
+    .bind(integration_id)
+    .bind(run_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(logs)
+}
+
+// task ID -> schedule, for tasks whose manifest schedule has been overridden
+pub async fn list_task_schedules<'x, X>(
+    integration_id: &str,
+    db: X,
+) -> AppResult<HashMap<String, String>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let schedules = sqlx::query_as(
+        "SELECT task_id, schedule
+        FROM integration_task_schedules
+        WHERE integration_id = $1",
+    )
+    .bind(integration_id)
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .collect();
+
+    Ok(schedules)
+}
+
+// `None` goes back to the schedule in the task's manifest; the new schedule is
+// only applied to the running scheduler once the change is committed
+pub async fn set_task_schedule<'x, X>(
+    integration_id: &str,
+    task_id: &str,
+    schedule: Option<&str>,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    if !crate::integrations::task_exists(integration_id, task_id) {
+        return Err(AppError::NoSuchIntegrationTask(
+            integration_id.to_owned(),
+            task_id.to_owned(),
+        ));
+    }
+
+    if let Some(invalid) = schedule.filter(|s| !crate::integrations::is_valid_schedule(s)) {
+        return Err(AppError::InvalidTaskSchedule(invalid.to_owned()));
+    }
+
+    let mut txn = db.begin().await?;
+
+    let old: Option<String> = sqlx::query_scalar(
+        "DELETE FROM integration_task_schedules
+        WHERE integration_id = $1
+            AND task_id = $2
+        RETURNING schedule",
+    )
+    .bind(integration_id)
+    .bind(task_id)
+    .fetch_optional(&mut *txn)
+    .await?;
+
+    if old.as_deref() == schedule {
+        return Ok(());
+    }
+
+    if let Some(schedule) = schedule {
+        sqlx::query(
+            "INSERT INTO integration_task_schedules (integration_id, task_id, schedule)
+            VALUES ($1, $2, $3)",
+        )
+        .bind(integration_id)
+        .bind(task_id)
+        .bind(schedule)
+        .execute(&mut *txn)
+        .await?;
+    }
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::System,
+        integration_id,
+        user.username(),
+        json!({
+            "old": {
+                "task": task_id,
+                "schedule": old,
+            },
+            "new": {
+                "task": task_id,
+                "schedule": schedule,
+            },
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    crate::integrations::reschedule_task(integration_id, task_id, schedule).await;
+
+    Ok(())
+}
+
+// how long a verification link remains valid after being sent
+const VERIFICATION_VALIDITY: TimeDelta = TimeDelta::days(2);
+
+// content and whether it has been verified
 pub async fn get_self_service<'x, X>(
     integration_id: &str,
     tag_id: &str,
     username: &str,
     db: X,
-) -> AppResult<Option<String>>
+) -> AppResult<Option<(String, bool)>>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let value = sqlx::query_scalar(
-        "SELECT content
+    let value = sqlx::query_as(
+        "SELECT content, verified
         FROM tag_assignments
         WHERE system_id = $1
             AND tag_id = $2
@@ -33,18 +212,71 @@ where
     Ok(value)
 }
 
+// addresses that users have set and confirmed themselves (through any self-service
+// tag with `verify_email`), keyed by username; users without one are omitted
+#[cfg(feature = "integrations")]
+pub async fn get_verified_emails<'x, X>(
+    usernames: &[&str],
+    db: X,
+) -> AppResult<HashMap<String, String>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let (system_ids, tag_ids): (Vec<&str>, Vec<&str>) = crate::integrations::MANIFESTS
+        .iter()
+        .flat_map(|manifest| {
+            manifest
+                .tags
+                .iter()
+                .filter(|tag| tag.verify_email)
+                .map(|tag| (manifest.id, tag.id))
+        })
+        .unzip();
+
+    if system_ids.is_empty() || usernames.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let emails: Vec<(String, String)> = sqlx::query_as(
+        "SELECT DISTINCT ON (username) username, content
+        FROM tag_assignments
+        WHERE (system_id, tag_id) IN (SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[]))
+            AND username = ANY($3)
+            AND content LIKE '%@%.%'
+            AND verified
+        ORDER BY username, id",
+    )
+    .bind(system_ids)
+    .bind(tag_ids)
+    .bind(usernames)
+    .fetch_all(db)
+    .await?;
+
+    Ok(emails.into_iter().collect())
+}
+
+// if `verify` is set, the new value is only marked as verified once the secret
+// returned here is presented (i.e., the link sent by the caller is clicked);
+// nothing is changed (and None returned) if the value is the same as before
 pub async fn set_self_service<'x, X>(
     integration_id: &str,
     tag_id: &str,
     username: &str,
     value: &str,
+    verify: bool,
     db: X,
-) -> AppResult<()>
+) -> AppResult<Option<Uuid>>
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
 {
     let mut txn = db.begin().await?;
 
+    let old = get_self_service(integration_id, tag_id, username, &mut *txn).await?;
+    if old.is_some_and(|(content, _)| content == value) {
+        // otherwise every save would send another verification link
+        return Ok(None);
+    }
+
     sqlx::query(
         "DELETE
         FROM tag_assignments
@@ -58,16 +290,21 @@ where
     .execute(&mut *txn)
     .await?;
 
+    let secret = verify.then(Uuid::new_v4);
+
     let assignment: TagAssignment = sqlx::query_as(
         "INSERT INTO tag_assignments
-            (system_id, tag_id, username, content)
-        VALUES ($1, $2, $3, $4)
+            (system_id, tag_id, username, content, verified, verification_secret, \
+         verification_sent_at)
+        VALUES ($1, $2, $3, $4, $5, $6, CASE WHEN $5 THEN NULL ELSE now() END)
         RETURNING *, '[unused]' AS description",
     )
     .bind(integration_id)
     .bind(tag_id)
     .bind(username)
     .bind(value)
+    .bind(secret.is_none())
+    .bind(secret)
     .fetch_one(&mut *txn)
     .await?;
 
@@ -82,6 +319,7 @@ where
                 "id": assignment.id,
                 "username": username,
                 "content": assignment.content,
+                "verified": secret.is_none(),
             },
         }),
         &mut *txn,
@@ -90,5 +328,82 @@ where
 
     txn.commit().await?;
 
-    Ok(())
+    Ok(secret)
+}
+
+// for when the previous link was lost or has expired; returns the new secret
+// and the content to send it to, or None if there is nothing to verify
+pub async fn renew_self_service_verification<'x, X>(
+    integration_id: &str,
+    tag_id: &str,
+    username: &str,
+    db: X,
+) -> AppResult<Option<(Uuid, String)>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let renewed = sqlx::query_as(
+        "UPDATE tag_assignments
+        SET verification_secret = gen_random_uuid(), verification_sent_at = now()
+        WHERE system_id = $1
+            AND tag_id = $2
+            AND username = $3
+            AND NOT verified
+        RETURNING verification_secret, content",
+    )
+    .bind(integration_id)
+    .bind(tag_id)
+    .bind(username)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(renewed)
+}
+
+pub async fn verify_self_service<'x, X>(secret: &Uuid, db: X) -> AppResult<TagAssignment>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let assignment: TagAssignment = sqlx::query_as(
+        "UPDATE tag_assignments
+        SET verified = TRUE, verification_secret = NULL, verification_sent_at = NULL
+        WHERE verification_secret = $1
+            AND verification_sent_at > $2
+        RETURNING *, '[unused]' AS description",
+    )
+    .bind(secret)
+    .bind(Local::now() - VERIFICATION_VALIDITY)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or(AppError::InvalidVerificationLink)?;
+
+    let username: String = sqlx::query_scalar("SELECT username FROM tag_assignments WHERE id = $1")
+        .bind(assignment.id)
+        .fetch_one(&mut *txn)
+        .await?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::TagAssignment,
+        assignment.key(),
+        &username,
+        json!({
+            "old": {
+                "id": assignment.id,
+                "verified": false,
+            },
+            "new": {
+                "id": assignment.id,
+                "verified": true,
+            },
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(assignment)
 }
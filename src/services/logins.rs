@@ -0,0 +1,270 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local, TimeDelta};
+use log::*;
+use sqlx::PgPool;
+
+use crate::{
+    HIVE_INTERNAL_DOMAIN, HIVE_ROOT_GROUP_ID,
+    errors::AppResult,
+    mailer::Mailer,
+    models::{LoginAlert, LoginEvent},
+    notifications::{self, Notification, NotificationEvent},
+    resolver::UserEmailDomain,
+    services::groups,
+};
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// long enough for users to notice something odd in their own history
+pub const LOGIN_EVENT_RETENTION_PERIOD: TimeDelta = TimeDelta::days(180);
+
+// this many failed attempts from the same IP address within the window raise
+// an alert (only once per burst, when the threshold is first reached)
+const REPEATED_FAILURES_THRESHOLD: i64 = 5;
+const REPEATED_FAILURES_WINDOW: TimeDelta = TimeDelta::minutes(15);
+
+// administrators whose previous login is older than this are considered
+// dormant, since a sudden login could mean that their account was taken over
+const DORMANT_ADMIN_PERIOD: TimeDelta = TimeDelta::days(90);
+
+const MAX_EVENTS_PER_USER: i64 = 50;
+
+pub struct LoginAttempt<'a> {
+    pub username: Option<&'a str>, // only known if successful
+    pub error: Option<String>,     // None => successful
+    pub ip: Option<String>,
+    pub user_agent: Option<&'a str>,
+}
+
+pub async fn list_for_user<'x, X>(username: &str, db: X) -> AppResult<Vec<LoginEvent>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let events = sqlx::query_as(
+        "SELECT *
+        FROM login_events
+        WHERE username = $1
+        ORDER BY stamp DESC
+        LIMIT $2",
+    )
+    .bind(username)
+    .bind(MAX_EVENTS_PER_USER)
+    .fetch_all(db)
+    .await?;
+
+    Ok(events)
+}
+
+// returns the recorded event, whose `alert` is set if it looks suspicious
+pub async fn record(attempt: &LoginAttempt<'_>, db: &PgPool) -> AppResult<LoginEvent> {
+    let alert = if attempt.error.is_some() {
+        check_repeated_failures(attempt.ip.as_deref(), db).await?
+    } else if let Some(username) = attempt.username {
+        check_dormant_admin(username, db).await?
+    } else {
+        None
+    };
+
+    let event = sqlx::query_as(
+        "INSERT INTO login_events (username, success, ip, user_agent, error, alert)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING *",
+    )
+    .bind(attempt.username)
+    .bind(attempt.error.is_none())
+    .bind(&attempt.ip)
+    .bind(attempt.user_agent)
+    .bind(&attempt.error)
+    .bind(alert)
+    .fetch_one(db)
+    .await?;
+
+    Ok(event)
+}
+
+async fn check_repeated_failures(ip: Option<&str>, db: &PgPool) -> AppResult<Option<LoginAlert>> {
+    let Some(ip) = ip else {
+        return Ok(None);
+    };
+
+    // (not yet including the attempt being recorded)
+    let previous: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*)
+        FROM login_events
+        WHERE NOT success
+            AND ip = $1
+            AND stamp > $2",
+    )
+    .bind(ip)
+    .bind(Local::now() - REPEATED_FAILURES_WINDOW)
+    .fetch_one(db)
+    .await?;
+
+    if previous + 1 == REPEATED_FAILURES_THRESHOLD {
+        Ok(Some(LoginAlert::RepeatedFailures))
+    } else {
+        Ok(None)
+    }
+}
+
+async fn check_dormant_admin(username: &str, db: &PgPool) -> AppResult<Option<LoginAlert>> {
+    let role =
+        groups::details::get_role_in_group(username, HIVE_ROOT_GROUP_ID, HIVE_INTERNAL_DOMAIN, db)
+            .await?;
+
+    if role.is_none() {
+        return Ok(None);
+    }
+
+    let last: Option<DateTime<Local>> = sqlx::query_scalar(
+        "SELECT MAX(stamp)
+        FROM login_events
+        WHERE username = $1
+            AND success",
+    )
+    .bind(username)
+    .fetch_one(db)
+    .await?;
+
+    // without any previous login on record (e.g., right after this was first
+    // deployed), there is no way of telling whether the account was dormant
+    if last.is_some_and(|last| Local::now() - last > DORMANT_ADMIN_PERIOD) {
+        Ok(Some(LoginAlert::DormantAdmin))
+    } else {
+        Ok(None)
+    }
+}
+
+// always logged, sent to any subscribed notification channels, and also emailed
+// to every administrator if possible; never fails, since the login itself
+// should go through regardless
+pub async fn alert_admins(
+    event: &LoginEvent,
+    db: &PgPool,
+    mailer: Option<&Mailer>,
+    email_domain: &UserEmailDomain,
+) {
+    let Some(alert) = event.alert else {
+        return;
+    };
+
+    let ip = event.ip.as_deref().unwrap_or("unknown IP address");
+
+    let summary = match alert {
+        LoginAlert::RepeatedFailures => format!(
+            "{REPEATED_FAILURES_THRESHOLD} failed login attempts from {ip} within {} minutes",
+            REPEATED_FAILURES_WINDOW.num_minutes()
+        ),
+        LoginAlert::DormantAdmin => format!(
+            "Administrator {} logged in from {ip} after more than {} days of inactivity",
+            event.username.as_deref().unwrap_or_default(),
+            DORMANT_ADMIN_PERIOD.num_days()
+        ),
+    };
+
+    warn!("Suspicious login activity: {summary}");
+
+    let content = format!(
+        "{summary}.\n\n\
+        Time: {}\n\
+        User agent: {}\n\n\
+        If this wasn't expected, consider checking the audit logs and revoking \
+        the affected account's access.",
+        event.stamp.format("%Y-%m-%d %H:%M:%S"),
+        event.user_agent.as_deref().unwrap_or("unknown"),
+    );
+
+    let notification = Notification {
+        event: NotificationEvent::SuspiciousLogin,
+        subject: "Suspicious login activity".to_owned(),
+        content,
+    };
+
+    let (_, failures) = notifications::dispatch(&notification).await;
+    for (kind, e) in failures {
+        error!("Failed to send login alert to {kind} notification channel: {e}");
+    }
+
+    let Some(mailer) = mailer else {
+        return;
+    };
+
+    let admins = match groups::members::get_all_members(
+        HIVE_ROOT_GROUP_ID,
+        HIVE_INTERNAL_DOMAIN,
+        None,
+        db,
+        None,
+    )
+    .await
+    {
+        Ok(admins) => admins,
+        Err(e) => {
+            error!("Failed to list administrators for login alert: {e}");
+            return;
+        }
+    };
+
+    let emails: Vec<String> = admins
+        .iter()
+        .map(|admin| email_domain.email_of(&admin.username))
+        .collect();
+    let to: Vec<&str> = emails.iter().map(String::as_str).collect();
+
+    if let Err(e) = mailer
+        .send(
+            &to,
+            "[Hive] Suspicious login activity",
+            &notification.content,
+        )
+        .await
+    {
+        error!("Failed to send login alert to administrators: {e}");
+    }
+}
+
+pub async fn purge_old_events<'x, X>(db: X) -> AppResult<u64>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    // each user's latest successful login is kept regardless, since it is
+    // needed to tell whether an administrator's account was dormant
+    let result = sqlx::query(
+        "DELETE FROM login_events le
+        WHERE le.stamp <= $1
+            AND NOT (
+                le.success
+                AND le.stamp = (
+                    SELECT MAX(o.stamp)
+                    FROM login_events o
+                    WHERE o.username = le.username
+                        AND o.success
+                )
+            )",
+    )
+    .bind(Local::now() - LOGIN_EVENT_RETENTION_PERIOD)
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// meant to be spawned as a background task on startup
+pub async fn purge_periodically(db: PgPool) {
+    let mut interval = rocket::tokio::time::interval(CLEANUP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if crate::routing::maintenance::is_active() {
+            continue;
+        }
+
+        match purge_old_events(&db).await {
+            Ok(0) => {}
+            Ok(n) => debug!("Purged {n} old login events"),
+            Err(e) => error!("Failed to purge old login events: {e}"),
+        }
+    }
+}
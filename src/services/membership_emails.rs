@@ -0,0 +1,266 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use chrono::NaiveDate;
+use log::*;
+use sqlx::{FromRow, PgPool};
+
+use crate::{
+    errors::AppResult, guards::lang::Language, mailer::Mailer, models::GroupMember, perms,
+    resolver::UserEmailDomain,
+};
+
+const SEND_INTERVAL: Duration = Duration::from_secs(60);
+const SEND_BATCH_SIZE: i64 = 50;
+
+// Whether users are emailed about changes to their memberships at all (which
+// requires the mailer). Global rather than managed state since memberships are
+// changed from all over the place (web, API, imports, transfers...), and there
+// is no point in queueing emails that will never be sent.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+#[derive(sqlx::Type, PartialEq, Eq, Clone, Copy, Debug)]
+#[sqlx(type_name = "membership_email_kind", rename_all = "snake_case")]
+pub enum MembershipEmailKind {
+    Added,
+    Edited,
+    Removed,
+}
+
+impl MembershipEmailKind {
+    pub const fn key(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Edited => "edited",
+            Self::Removed => "removed",
+        }
+    }
+}
+
+pub async fn is_opted_out<'x, X>(username: &str, db: X) -> AppResult<bool>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let opted_out = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0
+        FROM membership_email_opt_outs
+        WHERE username = $1",
+    )
+    .bind(username)
+    .fetch_one(db)
+    .await?;
+
+    Ok(opted_out)
+}
+
+pub async fn set_opted_out<'x, X>(username: &str, opted_out: bool, db: X) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let query = if opted_out {
+        "INSERT INTO membership_email_opt_outs (username)
+        VALUES ($1)
+        ON CONFLICT DO NOTHING"
+    } else {
+        "DELETE FROM membership_email_opt_outs
+        WHERE username = $1"
+    };
+
+    sqlx::query(query).bind(username).execute(db).await?;
+
+    Ok(())
+}
+
+// must be called in the same transaction as the change itself, with the
+// membership as it is after the change (or was before it, if removed); nothing
+// is queued for changes users make to their own memberships, nor for external
+// users (who might not have an address at the user email domain)
+pub async fn enqueue<'x, X>(
+    kind: MembershipEmailKind,
+    member: &GroupMember,
+    group_id: &str,
+    group_domain: &str,
+    actor: &str,
+    db: X,
+) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    if !is_enabled() || member.username == actor || perms::is_external_domain(group_domain) {
+        return Ok(());
+    }
+
+    sqlx::query(
+        "INSERT INTO membership_emails (kind, username, group_id, group_domain, \"from\", \
+         \"until\", manager, actor)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(kind)
+    .bind(&member.username)
+    .bind(group_id)
+    .bind(group_domain)
+    .bind(member.from)
+    .bind(member.until)
+    .bind(member.manager)
+    .bind(actor)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct PendingEmail {
+    kind: MembershipEmailKind,
+    username: String,
+    group_id: String,
+    group_domain: String,
+    from: NaiveDate,
+    until: NaiveDate,
+    manager: bool,
+    actor: String,
+    name_sv: String,
+    name_en: String,
+}
+
+impl PendingEmail {
+    fn group_key(&self) -> String {
+        format!("{}@{}", self.group_id, self.group_domain)
+    }
+
+    // there is no way of knowing which language each user prefers, so every
+    // email is written in all of them
+    fn subject(&self) -> String {
+        let key = format!("membership-emails.{}.subject", self.kind.key());
+
+        format!(
+            "[Hive] {} / {}: {}",
+            Language::Swedish.t(&key),
+            Language::English.t(&key),
+            self.group_key()
+        )
+    }
+
+    fn content(&self) -> String {
+        [Language::Swedish, Language::English]
+            .iter()
+            .map(|lang| self.localized_content(lang))
+            .collect::<Vec<_>>()
+            .join("\n\n---\n\n")
+    }
+
+    fn localized_content(&self, lang: &Language) -> String {
+        let locale = lang.i18n_locale();
+
+        let group = match lang {
+            Language::Swedish => &self.name_sv,
+            Language::English => &self.name_en,
+        };
+        let role = if self.manager {
+            lang.t("membership-emails.role.manager")
+        } else {
+            lang.t("membership-emails.role.member")
+        };
+
+        let key = format!("membership-emails.{}.content", self.kind.key());
+        let content = rust_i18n::t!(
+            key.as_str(),
+            locale = locale,
+            group = group,
+            group_key = self.group_key(),
+            role = role,
+            from = self.from,
+            until = self.until,
+            actor = self.actor
+        );
+
+        format!("{content}\n\n{}", lang.t("membership-emails.footer"))
+    }
+}
+
+// returns how many emails were sent; each is taken off the queue before being
+// sent, since missing an email is better than sending it multiple times (and
+// those to users who have opted out are just discarded)
+pub async fn send_pending(
+    db: &PgPool,
+    mailer: &Mailer,
+    email_domain: &UserEmailDomain,
+) -> AppResult<usize> {
+    let pending: Vec<PendingEmail> = sqlx::query_as(
+        "WITH taken AS (
+            DELETE FROM membership_emails
+            WHERE id IN (
+                SELECT id
+                FROM membership_emails
+                ORDER BY created_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+        )
+        SELECT t.*, g.name_sv, g.name_en
+        FROM taken t
+        JOIN groups g
+            ON g.id = t.group_id
+            AND g.domain = t.group_domain
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM membership_email_opt_outs meoo
+            WHERE meoo.username = t.username
+        )
+        ORDER BY t.created_at",
+    )
+    .bind(SEND_BATCH_SIZE)
+    .fetch_all(db)
+    .await?;
+
+    let mut n_sent = 0;
+
+    for email in &pending {
+        let to = email_domain.email_of(&email.username);
+
+        // one failure shouldn't prevent everyone else's emails from being sent
+        match mailer
+            .send(&[&to], &email.subject(), &email.content())
+            .await
+        {
+            Ok(()) => n_sent += 1,
+            Err(e) => warn!(
+                "Failed to email {} about their membership in {}: {e}",
+                email.username,
+                email.group_key()
+            ),
+        }
+    }
+
+    Ok(n_sent)
+}
+
+// meant to be spawned as a background task on startup (only if enabled)
+pub async fn send_periodically(db: PgPool, mailer: Mailer, email_domain: UserEmailDomain) {
+    let mut interval = rocket::tokio::time::interval(SEND_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if crate::routing::maintenance::is_active() {
+            continue;
+        }
+
+        match send_pending(&db, &mailer, &email_domain).await {
+            Ok(0) => {}
+            Ok(n) => debug!("Sent {n} membership emails"),
+            Err(e) => error!("Failed to send membership emails: {e}"),
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use chrono::NaiveDate;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    dto::operational_years::OperationalYearDto,
+    errors::{AppError, AppResult},
+    guards::user::User,
+    models::{ActionKind, OperationalYear, TargetKind},
+    services::{audit_logs, perms_cache},
+};
+
+// most recent first
+pub async fn list_all<'x, X>(db: X) -> AppResult<Vec<OperationalYear>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let years = sqlx::query_as(
+        "SELECT *
+        FROM operational_years
+        ORDER BY \"start\" DESC",
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(years)
+}
+
+pub async fn require_one<'x, X>(id: &Uuid, db: X) -> AppResult<OperationalYear>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    sqlx::query_as(
+        "SELECT *
+        FROM operational_years
+        WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NoSuchOperationalYear(*id))
+}
+
+// the year that `day` falls within (if any), and the first one after it
+pub async fn get_current_and_next<'x, X>(
+    day: NaiveDate,
+    db: X,
+) -> AppResult<(Option<OperationalYear>, Option<OperationalYear>)>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut years: Vec<OperationalYear> = sqlx::query_as(
+        "SELECT *
+        FROM operational_years
+        WHERE \"end\" >= $1
+        ORDER BY \"start\"
+        LIMIT 2",
+    )
+    .bind(day)
+    .fetch_all(db)
+    .await?;
+
+    let current = match years.first() {
+        Some(first) if first.start <= day => Some(years.remove(0)),
+        _ => None,
+    };
+
+    Ok((current, years.into_iter().next()))
+}
+
+// memberships can only reference a year that fully contains them
+pub async fn check_membership<'x, X>(
+    id: &Uuid,
+    from: &NaiveDate,
+    until: &NaiveDate,
+    db: X,
+) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let year = require_one(id, db).await?;
+
+    if year.contains(from, until) {
+        Ok(())
+    } else {
+        Err(AppError::MembershipOutsideOperationalYear(year.name))
+    }
+}
+
+pub async fn create<'v, 'x, X>(
+    dto: &OperationalYearDto<'v>,
+    db: X,
+    user: &User,
+) -> AppResult<OperationalYear>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    ensure_no_overlap(None, dto, &mut *txn).await?;
+
+    let year: OperationalYear = sqlx::query_as(
+        "INSERT INTO operational_years (name, \"start\", \"end\")
+        VALUES ($1, $2, $3)
+        RETURNING *",
+    )
+    .bind(dto.name)
+    .bind(dto.start)
+    .bind(dto.end)
+    .fetch_one(&mut *txn)
+    .await
+    .map_err(|e| {
+        AppError::DuplicateOperationalYearName(dto.name.to_string()).if_unique_violation(e)
+    })?;
+
+    audit_logs::add_entry(
+        ActionKind::Create,
+        TargetKind::OperationalYear,
+        year.id,
+        user.username(),
+        json!({
+            "new": {
+                "name": year.name,
+                "start": year.start,
+                "end": year.end,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(year)
+}
+
+// memberships referencing the year follow along wherever they started or ended
+// exactly on its old bounds (i.e., unless they were added mid-year or ended
+// early), and any that then no longer fit within it stop referencing it
+pub async fn update<'v, 'x, X>(
+    id: &Uuid,
+    dto: &OperationalYearDto<'v>,
+    db: X,
+    user: &User,
+) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let old = require_one(id, &mut *txn).await?;
+
+    ensure_no_overlap(Some(id), dto, &mut *txn).await?;
+
+    sqlx::query(
+        "UPDATE operational_years
+        SET name = $2, \"start\" = $3, \"end\" = $4
+        WHERE id = $1",
+    )
+    .bind(id)
+    .bind(dto.name)
+    .bind(dto.start)
+    .bind(dto.end)
+    .execute(&mut *txn)
+    .await
+    .map_err(|e| {
+        AppError::DuplicateOperationalYearName(dto.name.to_string()).if_unique_violation(e)
+    })?;
+
+    let moved = sqlx::query(
+        "UPDATE direct_memberships
+        SET \"from\" = CASE WHEN \"from\" = $2 AND $4 <= \"until\" THEN $4 ELSE \"from\" END,
+            \"until\" = CASE WHEN \"until\" = $3 AND $5 >= \"from\" THEN $5 ELSE \"until\" END
+        WHERE operational_year_id = $1
+            AND ((\"from\" = $2 AND $2 <> $4) OR (\"until\" = $3 AND $3 <> $5))",
+    )
+    .bind(id)
+    .bind(old.start)
+    .bind(old.end)
+    .bind(dto.start)
+    .bind(dto.end)
+    .execute(&mut *txn)
+    .await?
+    .rows_affected();
+
+    sqlx::query(
+        "UPDATE direct_memberships
+        SET operational_year_id = NULL
+        WHERE operational_year_id = $1
+            AND (\"from\" < $2 OR \"until\" > $3)",
+    )
+    .bind(id)
+    .bind(dto.start)
+    .bind(dto.end)
+    .execute(&mut *txn)
+    .await?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::OperationalYear,
+        id,
+        user.username(),
+        json!({
+            "old": {
+                "name": old.name,
+                "start": old.start,
+                "end": old.end,
+            },
+            "new": {
+                "name": dto.name,
+                "start": dto.start,
+                "end": dto.end,
+            },
+            "moved_memberships": moved,
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    if moved > 0 {
+        perms_cache::invalidate_all();
+    }
+
+    Ok(())
+}
+
+// memberships that referenced it keep their dates, just not the reference
+pub async fn delete(id: &Uuid, db: &PgPool, user: &User) -> AppResult<()> {
+    let mut txn = db.begin().await?;
+
+    let old: OperationalYear = sqlx::query_as(
+        "DELETE FROM operational_years
+        WHERE id = $1
+        RETURNING *",
+    )
+    .bind(id)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or_else(|| AppError::NoSuchOperationalYear(*id))?;
+
+    audit_logs::add_entry(
+        ActionKind::Delete,
+        TargetKind::OperationalYear,
+        id,
+        user.username(),
+        json!({
+            "old": {
+                "name": old.name,
+                "start": old.start,
+                "end": old.end,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+// (also enforced by an exclusion constraint, but this gives a better error)
+async fn ensure_no_overlap<'v, 'x, X>(
+    except: Option<&Uuid>,
+    dto: &OperationalYearDto<'v>,
+    db: X,
+) -> AppResult<()>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let overlapping: Option<String> = sqlx::query_scalar(
+        "SELECT name
+        FROM operational_years
+        WHERE \"start\" <= $2
+            AND \"end\" >= $1
+            AND ($3::UUID IS NULL OR id <> $3)
+        LIMIT 1",
+    )
+    .bind(dto.start)
+    .bind(dto.end)
+    .bind(except)
+    .fetch_optional(db)
+    .await?;
+
+    match overlapping {
+        Some(name) => Err(AppError::OverlappingOperationalYear(name)),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,192 @@
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    HIVE_INTERNAL_DOMAIN, HIVE_SYSTEM_ID,
+    dto::maintenance::CleanupOrphansDto,
+    errors::AppResult,
+    guards::{perms::PermsEvaluator, user::User},
+    models::{ApiToken, Permission, SimpleGroup, Tag},
+    services::{api_tokens, groups, permissions, tags},
+};
+
+// entities that nothing refers to anymore, typically left behind after a
+// system or group was reorganized; none of them are necessarily mistakes, so
+// they are only ever removed after an administrator picks them out
+#[derive(Serialize)]
+pub struct Orphans {
+    pub permissions: Vec<Permission>,
+    pub tags: Vec<Tag>,
+    pub groups: Vec<SimpleGroup>,
+    pub api_tokens: Vec<ApiToken>,
+}
+
+impl Orphans {
+    pub fn is_empty(&self) -> bool {
+        self.permissions.is_empty()
+            && self.tags.is_empty()
+            && self.groups.is_empty()
+            && self.api_tokens.is_empty()
+    }
+}
+
+pub async fn list_all(db: &PgPool) -> AppResult<Orphans> {
+    // (our own permissions are managed via database migrations)
+    let permissions = sqlx::query_as(
+        "SELECT p.*
+        FROM permissions p
+        WHERE p.system_id <> $1
+            AND NOT EXISTS (
+                SELECT 1
+                FROM permission_assignments pa
+                WHERE pa.system_id = p.system_id
+                    AND pa.perm_id = p.perm_id
+            )
+        ORDER BY p.system_id, p.perm_id",
+    )
+    .bind(HIVE_SYSTEM_ID)
+    .fetch_all(db)
+    .await?;
+
+    // tags in a subtag relationship are still part of a hierarchy, and our own
+    // tags might still be referenced by permission scopes (e.g., for
+    // $hive:manage-groups:#hive:tag) even with nothing tagged yet
+    let mut tags: Vec<Tag> = sqlx::query_as(
+        "SELECT t.*
+        FROM tags t
+        WHERE NOT EXISTS (
+                SELECT 1
+                FROM tag_assignments ta
+                WHERE ta.system_id = t.system_id
+                    AND ta.tag_id = t.tag_id
+            )
+            AND NOT EXISTS (
+                SELECT 1
+                FROM subtags st
+                WHERE (st.parent_system_id = t.system_id AND st.parent_id = t.tag_id)
+                    OR (st.child_system_id = t.system_id AND st.child_id = t.tag_id)
+            )
+            AND NOT EXISTS (
+                SELECT 1
+                FROM permission_assignments pa
+                WHERE t.system_id = $1
+                    AND pa.system_id = $1
+                    AND (
+                        pa.scope = '#' || t.system_id || ':' || t.tag_id
+                        OR pa.scope LIKE '#' || t.system_id || ':' || t.tag_id || ':%'
+                    )
+            )
+        ORDER BY t.system_id, t.tag_id",
+    )
+    .bind(HIVE_SYSTEM_ID)
+    .fetch_all(db)
+    .await?;
+
+    // integration tags are declared in their manifest, so can't be deleted
+    // (unless they no longer are)
+    tags.retain(|tag| !tag.managed);
+
+    // past members don't count, but future ones do (e.g., a group that was
+    // just created for next year's committee)
+    let groups = sqlx::query_as(
+        "SELECT g.id, g.domain, g.name_sv, g.name_en
+        FROM groups g
+        WHERE g.domain <> $1
+            AND NOT EXISTS (
+                SELECT 1
+                FROM direct_memberships dm
+                WHERE dm.group_id = g.id
+                    AND dm.group_domain = g.domain
+                    AND dm.\"until\" >= $2
+            )
+            AND NOT EXISTS (
+                SELECT 1
+                FROM subgroups sg
+                WHERE sg.parent_id = g.id
+                    AND sg.parent_domain = g.domain
+            )
+        ORDER BY g.domain, g.id",
+    )
+    .bind(HIVE_INTERNAL_DOMAIN)
+    .bind(crate::clock::today())
+    .fetch_all(db)
+    .await?;
+
+    let api_tokens = sqlx::query_as(
+        "SELECT t.*
+        FROM api_tokens t
+        WHERE NOT EXISTS (
+                SELECT 1
+                FROM permission_assignments pa
+                WHERE pa.api_token_id = t.id
+            )
+        ORDER BY t.system_id, t.description",
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(Orphans {
+        permissions,
+        tags,
+        groups,
+        api_tokens,
+    })
+}
+
+// deletes whichever of the selected entities are still orphaned (something
+// might have been assigned in the meantime); each deletion is tracked and can
+// be restored from the trash individually. Returns how many were deleted
+pub async fn cleanup(
+    selected: &CleanupOrphansDto<'_>,
+    db: &PgPool,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<usize> {
+    let orphans = list_all(db).await?;
+    let mut deleted = 0;
+
+    for key in &selected.permissions {
+        let still_orphaned = orphans
+            .permissions
+            .iter()
+            .any(|p| p.system_id == key.system_id && p.perm_id == key.perm_id);
+
+        if still_orphaned {
+            permissions::delete(key.system_id, key.perm_id, db, user).await?;
+            deleted += 1;
+        }
+    }
+
+    for key in &selected.tags {
+        let still_orphaned = orphans
+            .tags
+            .iter()
+            .any(|t| t.system_id == key.system_id && t.tag_id == key.tag_id);
+
+        if still_orphaned {
+            tags::delete(key.system_id, key.tag_id, db, user).await?;
+            deleted += 1;
+        }
+    }
+
+    for group in &selected.groups {
+        let still_orphaned = orphans
+            .groups
+            .iter()
+            .any(|g| g.id == group.id && g.domain == group.domain);
+
+        if still_orphaned {
+            groups::management::delete(group.id, group.domain, db, user).await?;
+            deleted += 1;
+        }
+    }
+
+    for id in &selected.api_tokens {
+        if orphans.api_tokens.iter().any(|t| t.id == *id) {
+            api_tokens::delete(id, db, perms, user).await?;
+            deleted += 1;
+        }
+    }
+
+    Ok(deleted)
+}
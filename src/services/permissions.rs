@@ -1,10 +1,18 @@
-use chrono::Local;
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex, PoisonError},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
 use log::*;
 use serde_json::json;
+use sqlx::PgPool;
 use uuid::Uuid;
 
-use super::{api_tokens, audit_logs, pg_args};
+use super::{api_tokens, audit_logs, deletions, perms_cache, pg_args};
 use crate::{
+    clock,
     dto::permissions::{
         AssignPermissionToApiTokenDto, AssignPermissionToGroupDto, CreatePermissionDto,
     },
@@ -12,11 +20,27 @@ use crate::{
     guards::{lang::Language, perms::PermsEvaluator, user::User},
     models::{
         ActionKind, AffiliatedPermissionAssignment, BasePermissionAssignment, Permission,
-        TargetKind,
+        PermissionGrant, PermissionHolder, TargetKind,
     },
-    perms::{HivePermission, SystemsScope},
+    perms::{self, HivePermission, InvalidHivePermissionError, SystemsScope},
+    sanitizers::SearchTerm,
 };
 
+#[cfg(test)]
+mod tests;
+
+// how often recorded assignment matches are written to the database
+const MATCHES_FLUSH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// stored match stamps are only overwritten once they're at least this old,
+// since we only care about months-long stretches of inactivity anyway
+const MATCH_STAMP_RESOLUTION_HOURS: i32 = 24;
+
+// like API token usage, matches are buffered in memory and only sampled once
+// per assignment per flush, to avoid writing on every single permission check
+static PENDING_MATCHES: LazyLock<Mutex<HashMap<Uuid, DateTime<Local>>>> =
+    LazyLock::new(Default::default);
+
 pub async fn get_one<'x, X>(system_id: &str, perm_id: &str, db: X) -> AppResult<Option<Permission>>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
@@ -60,6 +84,33 @@ where
     Ok(permissions)
 }
 
+// q matches against both the id and description
+pub async fn list_matching<'x, X>(
+    system_ids: &[String],
+    q: Option<&str>,
+    db: X,
+) -> AppResult<Vec<Permission>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut query = sqlx::QueryBuilder::new("SELECT * FROM permissions WHERE system_id = ANY(");
+    query.push_bind(system_ids);
+    query.push(")");
+
+    if let Some(search) = q {
+        let term = SearchTerm::from(search).anywhere();
+        query.push(" AND (perm_id ILIKE ");
+        query.push_bind(term.clone());
+        query.push(" OR description ILIKE ");
+        query.push_bind(term);
+        query.push(")");
+    }
+
+    query.push(" ORDER BY system_id, perm_id");
+
+    Ok(query.build_query_as().fetch_all(db).await?)
+}
+
 pub async fn list_all_assignments_for_user<'x, X>(
     username: &str,
     db: X,
@@ -67,18 +118,20 @@ pub async fn list_all_assignments_for_user<'x, X>(
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let today = Local::now().date_naive();
+    let today = clock::today();
 
     let assignments = sqlx::query_as(
         "SELECT DISTINCT pa.system_id, pa.perm_id, pa.scope
         FROM permission_assignments pa
-        JOIN all_groups_of($1, $2) ag
+        JOIN all_group_refs_of($1, $2) ag
             ON ag.id = pa.group_id
             AND ag.domain = pa.group_domain
+        WHERE pa.group_domain <> ALL($3)
         ORDER BY pa.system_id, pa.perm_id, pa.scope",
     )
     .bind(username)
     .bind(today)
+    .bind(perms::external_domains())
     .fetch_all(db)
     .await?;
 
@@ -93,20 +146,22 @@ pub async fn list_all_assignments_for_user_system<'x, X>(
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let today = Local::now().date_naive();
+    let today = clock::today();
 
     let assignments = sqlx::query_as(
         "SELECT DISTINCT pa.system_id, pa.perm_id, pa.scope
         FROM permission_assignments pa
-        JOIN all_groups_of($1, $2) ag
+        JOIN all_group_refs_of($1, $2) ag
             ON ag.id = pa.group_id
             AND ag.domain = pa.group_domain
         WHERE pa.system_id = $3
+            AND pa.group_domain <> ALL($4)
         ORDER BY pa.perm_id, pa.scope",
     )
     .bind(username)
     .bind(today)
     .bind(system_id)
+    .bind(perms::external_domains())
     .fetch_all(db)
     .await?;
 
@@ -119,28 +174,20 @@ pub async fn list_all_assignments_for_token_system<'x, X>(
     db: X,
 ) -> AppResult<Vec<BasePermissionAssignment>>
 where
-    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+    X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
 {
-    let now = Local::now();
-    let hash = api_tokens::hash_secret(secret);
+    let Some(token) = api_tokens::authenticate_unsigned(secret, db).await? else {
+        return Ok(vec![]);
+    };
 
     let assignments = sqlx::query_as(
-        "WITH updated AS (
-            UPDATE api_tokens
-            SET last_used_at = $1
-            WHERE secret = $2
-                AND (expires_at IS NULL OR expires_at >= $1)
-            RETURNING id
-        )
-        SELECT DISTINCT pa.system_id, pa.perm_id, pa.scope
-        FROM permission_assignments pa
-        JOIN updated u
-            ON pa.api_token_id = u.id
-        WHERE pa.system_id = $3
-        ORDER BY pa.perm_id, pa.scope",
+        "SELECT DISTINCT system_id, perm_id, scope
+        FROM permission_assignments
+        WHERE api_token_id = $1
+            AND system_id = $2
+        ORDER BY perm_id, scope",
     )
-    .bind(now)
-    .bind(hash)
+    .bind(token.id)
     .bind(system_id)
     .fetch_all(db)
     .await?;
@@ -157,22 +204,24 @@ pub async fn list_all_scopes_for_user_permission<'x, X>(
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let today = Local::now().date_naive();
+    let today = clock::today();
 
     let assignments = sqlx::query_scalar(
         "SELECT DISTINCT pa.scope
         FROM permission_assignments pa
-        JOIN all_groups_of($1, $2) ag
+        JOIN all_group_refs_of($1, $2) ag
             ON ag.id = pa.group_id
             AND ag.domain = pa.group_domain
         WHERE pa.perm_id = $3
             AND pa.system_id = $4
+            AND pa.group_domain <> ALL($5)
         ORDER BY pa.scope",
     )
     .bind(username)
     .bind(today)
     .bind(perm_id)
     .bind(system_id)
+    .bind(perms::external_domains())
     .fetch_all(db)
     .await?;
 
@@ -186,29 +235,21 @@ pub async fn list_all_scopes_for_token_permission<'x, X>(
     db: X,
 ) -> AppResult<Vec<String>>
 where
-    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+    X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
 {
-    let now = Local::now();
-    let hash = api_tokens::hash_secret(secret);
+    let Some(token) = api_tokens::authenticate_unsigned(secret, db).await? else {
+        return Ok(vec![]);
+    };
 
     let assignments = sqlx::query_scalar(
-        "WITH updated AS (
-            UPDATE api_tokens
-            SET last_used_at = $1
-            WHERE secret = $2
-                AND (expires_at IS NULL OR expires_at >= $1)
-            RETURNING id
-        )
-        SELECT DISTINCT pa.scope
-        FROM permission_assignments pa
-        JOIN updated u
-            ON pa.api_token_id = u.id
-        WHERE pa.perm_id = $3
-            AND pa.system_id = $4
-        ORDER BY pa.perm_id, pa.scope",
+        "SELECT DISTINCT scope
+        FROM permission_assignments
+        WHERE api_token_id = $1
+            AND perm_id = $2
+            AND system_id = $3
+        ORDER BY scope",
     )
-    .bind(now)
-    .bind(hash)
+    .bind(token.id)
     .bind(perm_id)
     .bind(system_id)
     .fetch_all(db)
@@ -217,83 +258,321 @@ where
     Ok(assignments)
 }
 
-pub async fn user_has_permission<'x, X>(
+pub async fn user_has_permission(
     username: &str,
     system_id: &str,
     perm_id: &str,
     scope: Option<&str>,
+    db: &PgPool,
+) -> AppResult<bool> {
+    // same as `find_user_matches`, but through the cache
+    let matched: Vec<_> = perms_cache::get_assignments(username, system_id, perm_id, db)
+        .await?
+        .iter()
+        .filter(|assignment| assignment.matches(scope))
+        .map(|assignment| assignment.id)
+        .collect();
+
+    record_matches(&matched, Local::now());
+
+    Ok(!matched.is_empty())
+}
+
+pub async fn token_has_permission<'x, X>(
+    secret: Uuid,
+    system_id: &str,
+    perm_id: &str,
+    scope: Option<&str>,
     db: X,
 ) -> AppResult<bool>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+{
+    let Some(token) = api_tokens::authenticate_unsigned(secret, db).await? else {
+        return Ok(false);
+    };
+
+    let matched = find_token_matches(token.id, system_id, perm_id, scope, db).await?;
+
+    record_matches(&matched, Local::now());
+
+    Ok(!matched.is_empty())
+}
+
+// IDs of the assignments that grant the permission (with the scope) to the
+// user; this is what permission checks boil down to, so anything that must
+// agree with the API (e.g., the simulator) should use it as well
+pub async fn find_user_matches<'x, X>(
+    username: &str,
+    system_id: &str,
+    perm_id: &str,
+    scope: Option<&str>,
+    db: X,
+) -> AppResult<Vec<Uuid>>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let today = Local::now().date_naive();
+    let today = clock::today();
+
+    let matched = sqlx::query_scalar(perms::queries::USER_MATCHES)
+        .bind(username)
+        .bind(today)
+        .bind(system_id)
+        .bind(perm_id)
+        .bind(scope)
+        .bind(perms::external_domains())
+        .fetch_all(db)
+        .await?;
+
+    Ok(matched)
+}
 
-    let authorized = sqlx::query_scalar(
-        "SELECT COUNT(pa.*) > 0
+// like `find_user_matches`, but for an (already authenticated) API token
+pub async fn find_token_matches<'x, X>(
+    token_id: Uuid,
+    system_id: &str,
+    perm_id: &str,
+    scope: Option<&str>,
+    db: X,
+) -> AppResult<Vec<Uuid>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let matched = sqlx::query_scalar(concat!(
+        "SELECT pa.id
         FROM permission_assignments pa
-        JOIN all_groups_of($1, $2) ag
-            ON ag.id = pa.group_id
-            AND ag.domain = pa.group_domain
-        WHERE pa.system_id = $3
-            AND pa.perm_id = $4
-            AND (
-                pa.scope IS NOT DISTINCT FROM $5
-                OR pa.scope = '*'
-            )",
-    )
-    .bind(username)
-    .bind(today)
+        WHERE pa.api_token_id = $1
+            AND pa.system_id = $2
+            AND pa.perm_id = $3
+            AND ",
+        perms::queries::scope_matches!("$4")
+    ))
+    .bind(token_id)
     .bind(system_id)
     .bind(perm_id)
     .bind(scope)
-    .fetch_one(db)
+    .fetch_all(db)
     .await?;
 
-    Ok(authorized)
+    Ok(matched)
 }
 
-pub async fn token_has_permission<'x, X>(
-    secret: Uuid,
-    system_id: &str,
-    perm_id: &str,
-    scope: Option<&str>,
+// how each of the matched assignments reaches the user, i.e. the assigned
+// scope and the chain of groups from the assignee down to a group the user is
+// a direct member of (empty for API tokens, which are assigned directly)
+pub async fn explain_matches<'x, X>(
+    matched: &[Uuid],
+    username: Option<&str>,
     db: X,
-) -> AppResult<bool>
+) -> AppResult<Vec<PermissionGrant>>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let now = Local::now();
-    let hash = api_tokens::hash_secret(secret);
-
-    let authorized = sqlx::query_scalar(
-        "WITH updated AS (
-            UPDATE api_tokens
-            SET last_used_at = $1
-            WHERE secret = $2
-                AND (expires_at IS NULL OR expires_at >= $1)
-            RETURNING id
+    let today = clock::today();
+
+    let mut grants: Vec<PermissionGrant> = if let Some(username) = username {
+        sqlx::query_as(
+            "SELECT pa.scope, ag.path
+            FROM permission_assignments pa
+            JOIN all_groups_of($1, $2) ag
+                ON ag.id = pa.group_id
+                AND ag.domain = pa.group_domain
+            WHERE pa.id = ANY($3)
+            ORDER BY pa.scope, CARDINALITY(ag.path)",
         )
-        SELECT COUNT(pa.*) > 0
-        FROM permission_assignments pa
-        JOIN updated u
-            ON pa.api_token_id = u.id
-        WHERE pa.system_id = $3
-            AND pa.perm_id = $4
+        .bind(username)
+        .bind(today)
+        .bind(matched)
+        .fetch_all(db)
+        .await?
+    } else {
+        sqlx::query_as(
+            "SELECT scope, '{}'::GROUP_REF[] AS path
+            FROM permission_assignments
+            WHERE id = ANY($1)
+            ORDER BY scope",
+        )
+        .bind(matched)
+        .fetch_all(db)
+        .await?
+    };
+
+    for grant in &mut grants {
+        // (all_groups_of starts from the user's side)
+        grant.path.reverse();
+    }
+
+    Ok(grants)
+}
+
+fn record_matches(ids: &[Uuid], now: DateTime<Local>) {
+    if ids.is_empty() {
+        return;
+    }
+
+    let mut pending = PENDING_MATCHES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    for id in ids {
+        pending.insert(*id, now);
+    }
+}
+
+pub async fn flush_matches(db: &PgPool) -> AppResult<usize> {
+    let pending = std::mem::take(
+        &mut *PENDING_MATCHES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner),
+    );
+
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    let (ids, stamps): (Vec<_>, Vec<_>) = pending.into_iter().unzip();
+
+    match write_matches(&ids, &stamps, db).await {
+        Ok(n) => Ok(n),
+        Err(e) => {
+            // put everything back so it's retried on the next flush, unless
+            // there has been a newer match in the meantime
+            let mut buffer = PENDING_MATCHES
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+
+            for (id, stamp) in ids.into_iter().zip(stamps) {
+                let merged = buffer.entry(id).or_insert(stamp);
+                *merged = (*merged).max(stamp);
+            }
+
+            Err(e)
+        }
+    }
+}
+
+async fn write_matches(ids: &[Uuid], stamps: &[DateTime<Local>], db: &PgPool) -> AppResult<usize> {
+    // assignments might have been deleted in the meantime, which is fine
+    let result = sqlx::query(
+        "UPDATE permission_assignments pa
+        SET last_matched_at = m.stamp
+        FROM UNNEST($1::uuid[], $2::timestamptz[]) AS m (id, stamp)
+        WHERE pa.id = m.id
             AND (
-                pa.scope IS NOT DISTINCT FROM $5
-                OR pa.scope = '*'
+                pa.last_matched_at IS NULL
+                OR pa.last_matched_at < m.stamp - MAKE_INTERVAL(hours => $3)
             )",
     )
-    .bind(now)
-    .bind(hash)
-    .bind(system_id)
-    .bind(perm_id)
-    .bind(scope)
-    .fetch_one(db)
+    .bind(ids)
+    .bind(stamps)
+    .bind(MATCH_STAMP_RESOLUTION_HOURS)
+    .execute(db)
     .await?;
 
-    Ok(authorized)
+    Ok(result.rows_affected() as usize)
+}
+
+pub async fn flush_matches_periodically(db: PgPool) {
+    let mut interval = rocket::tokio::time::interval(MATCHES_FLUSH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if crate::routing::maintenance::is_active() {
+            continue;
+        }
+
+        match flush_matches(&db).await {
+            Ok(0) => {}
+            Ok(n) => debug!("Updated {n} permission assignment match stamps"),
+            Err(e) => error!("Failed to flush permission assignment matches: {e}"),
+        }
+    }
+}
+
+// every assignment (to groups or API tokens) of any of the system's permissions
+pub async fn list_all_assignments_for_system<'x, X>(
+    system_id: &str,
+    label_lang: &Language,
+    db: X,
+) -> AppResult<Vec<AffiliatedPermissionAssignment>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let name_column = match label_lang {
+        Language::Swedish => "name_sv",
+        Language::English => "name_en",
+    };
+
+    let sql = format!(
+        "SELECT pa.*,
+            at.system_id AS api_token_system_id,
+            COALESCE(gs.{name_column}, at.description) AS label
+        FROM permission_assignments pa
+        LEFT JOIN groups gs
+            ON gs.id = pa.group_id
+            AND gs.domain = pa.group_domain
+        LEFT JOIN api_tokens at
+            ON at.id = pa.api_token_id
+        WHERE pa.system_id = $1
+        ORDER BY pa.perm_id, pa.scope NULLS FIRST, pa.group_domain, pa.group_id"
+    );
+
+    let assignments = sqlx::query_as(&sql).bind(system_id).fetch_all(db).await?;
+
+    Ok(assignments)
+}
+
+// only considers assignments in systems other than hive itself, since hive's
+// own permissions are never checked through the API
+pub async fn list_unused_assignments<'x, X>(
+    cutoff: DateTime<Local>,
+    label_lang: &Language,
+    db: X,
+    perms: &PermsEvaluator,
+) -> AppResult<Vec<AffiliatedPermissionAssignment>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let name_column = match label_lang {
+        Language::Swedish => "name_sv",
+        Language::English => "name_en",
+    };
+
+    // assignments without a creation stamp predate it being tracked at all
+    let sql = format!(
+        "SELECT pa.*,
+            at.system_id AS api_token_system_id,
+            COALESCE(gs.{name_column}, at.description) AS label
+        FROM permission_assignments pa
+        LEFT JOIN groups gs
+            ON gs.id = pa.group_id
+            AND gs.domain = pa.group_domain
+        LEFT JOIN api_tokens at
+            ON at.id = pa.api_token_id
+        WHERE pa.system_id <> $1
+            AND COALESCE(pa.last_matched_at, pa.created_at, '-infinity') < $2
+        ORDER BY pa.last_matched_at NULLS FIRST, pa.system_id, pa.perm_id, pa.scope"
+    );
+
+    let candidates: Vec<AffiliatedPermissionAssignment> = sqlx::query_as(&sql)
+        .bind(crate::HIVE_SYSTEM_ID)
+        .bind(cutoff)
+        .fetch_all(db)
+        .await?;
+
+    let mut assignments = vec![];
+
+    for mut assignment in candidates {
+        let min = HivePermission::AssignPerms(SystemsScope::Id(assignment.system_id.clone()));
+        // query should be OK since perms are cached by perm_id
+        if perms.satisfies(min).await? {
+            assignment.can_manage = Some(true);
+            assignments.push(assignment);
+        }
+    }
+
+    Ok(assignments)
 }
 
 pub async fn list_group_assignments<'x, X>(
@@ -346,6 +625,76 @@ where
     Ok(assignments)
 }
 
+// resolves group nesting, so this is everyone who currently has the permission
+// (via groups; API tokens aren't included), optionally with a specific scope
+pub async fn list_effective_holders<'x, X>(
+    system_id: &str,
+    perm_id: &str,
+    scope: Option<&str>,
+    db: X,
+) -> AppResult<Vec<PermissionHolder>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let holders = sqlx::query_as(concat!(
+        "SELECT
+            am.username,
+            pa.scope,
+            ARRAY_AGG(DISTINCT (pa.group_id, pa.group_domain)::GROUP_REF) AS groups
+        FROM permission_assignments pa
+        CROSS JOIN LATERAL all_members_of(pa.group_id, pa.group_domain, $1) am
+        WHERE pa.system_id = $2
+            AND pa.perm_id = $3
+            AND ($4::TEXT IS NULL OR pa.scope = $4)
+            AND pa.group_id IS NOT NULL
+            AND pa.group_domain IS NOT NULL
+            AND ",
+        perms::queries::internal_assignments_only!("$5"),
+        "
+        GROUP BY am.username, pa.scope
+        ORDER BY am.username, pa.scope"
+    ))
+    .bind(clock::today())
+    .bind(system_id)
+    .bind(perm_id)
+    .bind(scope)
+    .bind(perms::external_domains())
+    .fetch_all(db)
+    .await?;
+
+    Ok(holders)
+}
+
+// distinct users per permission, resolved like `list_effective_holders`; see
+// `perms_cache::get_holder_counts`, which should usually be used instead
+pub async fn count_effective_holders<'x, X>(
+    system_id: &str,
+    db: X,
+) -> AppResult<HashMap<String, i64>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let counts: Vec<(String, i64)> = sqlx::query_as(concat!(
+        "SELECT pa.perm_id, COUNT(DISTINCT am.username)
+        FROM permission_assignments pa
+        CROSS JOIN LATERAL all_members_of(pa.group_id, pa.group_domain, $1) am
+        WHERE pa.system_id = $2
+            AND pa.group_id IS NOT NULL
+            AND pa.group_domain IS NOT NULL
+            AND ",
+        perms::queries::internal_assignments_only!("$3"),
+        "
+        GROUP BY pa.perm_id"
+    ))
+    .bind(clock::today())
+    .bind(system_id)
+    .bind(perms::external_domains())
+    .fetch_all(db)
+    .await?;
+
+    Ok(counts.into_iter().collect())
+}
+
 pub async fn list_api_token_assignments<'x, X>(
     system_id: &str,
     perm_id: &str,
@@ -447,7 +796,7 @@ where
     Ok(permission)
 }
 
-pub async fn delete<'x, X>(system_id: &str, perm_id: &str, db: X, user: &User) -> AppResult<()>
+pub async fn delete<'x, X>(system_id: &str, perm_id: &str, db: X, user: &User) -> AppResult<Uuid>
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
 {
@@ -459,6 +808,14 @@ where
 
     let mut txn = db.begin().await?;
 
+    let deletion_id = deletions::track(
+        TargetKind::Permission,
+        format!("${system_id}:{perm_id}"),
+        user.username(),
+        &mut *txn,
+    )
+    .await?;
+
     let old: Permission = sqlx::query_as(
         "DELETE FROM permissions
         WHERE system_id = $1
@@ -471,6 +828,8 @@ where
     .await?
     .ok_or_else(|| AppError::NoSuchPermission(system_id.to_owned(), perm_id.to_owned()))?;
 
+    deletions::untrack(&mut *txn).await?;
+
     audit_logs::add_entry(
         ActionKind::Delete,
         TargetKind::Permission,
@@ -487,8 +846,9 @@ where
     .await?;
 
     txn.commit().await?;
+    perms_cache::invalidate_system(system_id);
 
-    Ok(())
+    Ok(deletion_id)
 }
 
 pub async fn assign_to_group<'v, 'x, X>(
@@ -502,6 +862,8 @@ pub async fn assign_to_group<'v, 'x, X>(
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
 {
+    perms::require_internal_domain(dto.group.domain)?;
+
     let mut txn = db.begin().await?;
 
     let has_scope = has_scope(system_id, perm_id, &mut *txn).await?;
@@ -518,6 +880,8 @@ where
         ));
     }
 
+    validate_scope(system_id, perm_id, dto.scope.as_deref().copied())?;
+
     let mut query = sqlx::QueryBuilder::with_arguments(
         "INSERT INTO permission_assignments (system_id, perm_id, scope, group_id, group_domain)
         VALUES ($1, $2, $3, $4, $5)
@@ -584,6 +948,7 @@ where
     .await?;
 
     txn.commit().await?;
+    perms_cache::invalidate_system(system_id);
 
     Ok(assignment)
 }
@@ -615,6 +980,8 @@ where
         ));
     }
 
+    validate_scope(system_id, perm_id, dto.scope.as_deref().copied())?;
+
     let mut query = sqlx::QueryBuilder::with_arguments(
         "INSERT INTO permission_assignments (system_id, perm_id, scope, api_token_id)
         VALUES ($1, $2, $3, $4)
@@ -754,10 +1121,61 @@ where
     .await?;
 
     txn.commit().await?;
+    perms_cache::invalidate_system(&old.system_id);
 
     Ok(old)
 }
 
+pub async fn list_known_scopes<'x, X>(
+    system_id: &str,
+    perm_id: &str,
+    db: X,
+) -> AppResult<Vec<String>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let scopes = sqlx::query_scalar(
+        "SELECT DISTINCT scope
+        FROM permission_assignments
+        WHERE system_id = $1
+            AND perm_id = $2
+            AND scope IS NOT NULL
+        ORDER BY scope",
+    )
+    .bind(system_id)
+    .bind(perm_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(scopes)
+}
+
+// hive's own permissions are only interpreted at evaluation time, where a
+// mistyped scope would otherwise just silently never match anything
+pub fn validate_scope(system_id: &str, perm_id: &str, scope: Option<&str>) -> AppResult<()> {
+    if system_id != crate::HIVE_SYSTEM_ID {
+        return Ok(());
+    }
+
+    let assignment = BasePermissionAssignment {
+        system_id: system_id.to_owned(),
+        perm_id: perm_id.to_owned(),
+        scope: scope.map(str::to_owned),
+    };
+
+    match HivePermission::try_from(assignment) {
+        Ok(perm) if !perm.has_well_formed_scope() => {}
+        Err(InvalidHivePermissionError::Scope) => {}
+        _ => return Ok(()), // (unknown IDs might just not be modelled)
+    }
+
+    Err(AppError::InvalidPermissionScope(
+        system_id.to_owned(),
+        perm_id.to_owned(),
+        scope.unwrap_or_default().to_owned(),
+    ))
+}
+
 pub async fn has_scope<'x, X>(system_id: &str, perm_id: &str, db: X) -> AppResult<bool>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
@@ -0,0 +1,130 @@
+// scope matching rules for permission checks, which must be the same whether
+// checked for a user (directly or through the cache) or for an API token
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{models::ReachingAssignment, services::api_tokens};
+
+const USERNAME: &str = "scopetester";
+const GROUP_ID: &str = "kassor";
+const GROUP_DOMAIN: &str = "datasektionen.se";
+
+// (assigned scope, checked scope, whether it should match)
+const CASES: &[(&str, &str, bool)] = &[
+    ("committee", "committee", true),
+    ("committee", "committee/finance", false),
+    // the full wildcard covers everything, even prefix wildcards...
+    ("*", "committee", true),
+    ("*", "committee/finance", true),
+    ("*", "committee/*", true),
+    // ...but not the other way around
+    ("committee/*", "*", false),
+    ("committee/*", "committee/*", true),
+    // prefix wildcards cover anything below, but not the prefix itself
+    ("committee/*", "committee/finance", true),
+    ("committee/*", "committee/finance/budget", true),
+    ("committee/*", "committee", false),
+    ("committee/*", "committees/finance", false),
+    // a trailing slash alone is no wildcard
+    ("committee/", "committee/", true),
+    ("committee/", "committee/finance", false),
+    // nor is a prefix wildcard without any prefix
+    ("/*", "/finance", false),
+    // nested prefixes
+    ("committee/finance/*", "committee/finance/budget", true),
+    ("committee/finance/*", "committee/finance/budget/q1", true),
+    ("committee/finance/*", "committee/finance", false),
+    ("committee/finance/*", "committee/events", false),
+];
+
+async fn setup(db: &PgPool) -> Uuid {
+    sqlx::query(
+        "INSERT INTO permissions (system_id, perm_id, has_scope, description)
+        VALUES ('cashflow', 'approve', TRUE, 'Approve budgets for a given committee')",
+    )
+    .execute(db)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        "INSERT INTO direct_memberships (username, group_id, group_domain, \"from\", \"until\")
+        VALUES ($1, $2, $3, CURRENT_DATE - 1, CURRENT_DATE + 1)",
+    )
+    .bind(USERNAME)
+    .bind(GROUP_ID)
+    .bind(GROUP_DOMAIN)
+    .execute(db)
+    .await
+    .unwrap();
+
+    sqlx::query_scalar(
+        "INSERT INTO api_tokens (secret, system_id, description)
+        VALUES ($1, 'cashflow', 'Scope matching test token')
+        RETURNING id",
+    )
+    .bind(api_tokens::hash_secret(Uuid::new_v4()))
+    .fetch_one(db)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test(fixtures(path = "../../../seeds", scripts("dev")))]
+async fn scopes_match_the_same_for_users_and_tokens(db: PgPool) {
+    let token_id = setup(&db).await;
+
+    let mut failures = vec![];
+
+    for &(assigned, checked, expected) in CASES {
+        let ids: Vec<Uuid> = sqlx::query_scalar(
+            "INSERT INTO permission_assignments
+                (system_id, perm_id, scope, group_id, group_domain, api_token_id)
+            VALUES
+                ('cashflow', 'approve', $1, $2, $3, NULL),
+                ('cashflow', 'approve', $1, NULL, NULL, $4)
+            RETURNING id",
+        )
+        .bind(assigned)
+        .bind(GROUP_ID)
+        .bind(GROUP_DOMAIN)
+        .bind(token_id)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+
+        let user = super::find_user_matches(USERNAME, "cashflow", "approve", Some(checked), &db)
+            .await
+            .unwrap();
+        let token = super::find_token_matches(token_id, "cashflow", "approve", Some(checked), &db)
+            .await
+            .unwrap();
+        let cached = ReachingAssignment {
+            id: ids[0],
+            scope: Some(assigned.to_owned()),
+        };
+
+        let results = [
+            ("user", !user.is_empty()),
+            ("token", !token.is_empty()),
+            ("cached", cached.matches(Some(checked))),
+        ];
+
+        for (kind, matched) in results {
+            if matched != expected {
+                failures.push(format!("{assigned} vs {checked} ({kind}): {matched}"));
+            }
+        }
+
+        sqlx::query("DELETE FROM permission_assignments WHERE id = ANY($1)")
+            .bind(&ids)
+            .execute(&db)
+            .await
+            .unwrap();
+    }
+
+    assert!(
+        failures.is_empty(),
+        "unexpected matches:\n{}",
+        failures.join("\n")
+    );
+}
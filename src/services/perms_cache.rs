@@ -0,0 +1,264 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, LazyLock, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDate;
+use log::*;
+use sqlx::PgPool;
+
+use crate::{
+    HIVE_SYSTEM_ID, clock,
+    errors::AppResult,
+    models::{BasePermissionAssignment, ReachingAssignment},
+    perms::{self, HivePermission},
+    services::permissions,
+};
+
+// Application-wide cache of which assignments reach each user for a given
+// (system, permission) pair, shared by the web interface (on top of each
+// request's PermsEvaluator) and the API. Permission checks tend to come in
+// bursts (a page load, or a system checking a batch of users), each of which
+// would otherwise recompute the user's groups from scratch.
+//
+// Writes that affect memberships or assignments must call one of the
+// `invalidate_*` functions *after* committing; the TTL only bounds staleness
+// that invalidation can't catch (e.g., read replica lag).
+const ENTRY_TTL: Duration = Duration::from_secs(60);
+
+// once reached, expired entries are evicted before inserting (and everything
+// if that's still not enough), to keep memory bounded
+const MAX_ENTRIES: usize = 50_000;
+
+// how often hit rates are logged and expired entries evicted
+const REPORT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+// holder counts are only an at-a-glance indicator on system pages, but each
+// one resolves every assignment of a system through all nested groups
+const HOLDER_COUNTS_TTL: Duration = Duration::from_secs(10 * 60);
+
+type CacheKey = (String, String, String); // (username, system_id, perm_id)
+
+struct CacheEntry {
+    assignments: Arc<[ReachingAssignment]>,
+    fetched_at: Instant,
+    day: NaiveDate, // memberships start and end at day boundaries
+}
+
+impl CacheEntry {
+    fn is_fresh(&self, today: NaiveDate) -> bool {
+        self.day == today && self.fetched_at.elapsed() < ENTRY_TTL
+    }
+}
+
+struct HolderCountsEntry {
+    counts: Arc<HashMap<String, i64>>, // perm_id => distinct users
+    fetched_at: Instant,
+    day: NaiveDate,
+}
+
+impl HolderCountsEntry {
+    fn is_fresh(&self, today: NaiveDate) -> bool {
+        self.day == today && self.fetched_at.elapsed() < HOLDER_COUNTS_TTL
+    }
+}
+
+#[derive(Default)]
+struct PermsCache {
+    entries: HashMap<CacheKey, CacheEntry>,
+    holder_counts: HashMap<String, HolderCountsEntry>, // by system_id
+    generation: u64,                                   // bumped on every invalidation
+}
+
+impl PermsCache {
+    fn evict_expired(&mut self, today: NaiveDate) {
+        self.entries.retain(|_, entry| entry.is_fresh(today));
+        self.holder_counts.retain(|_, entry| entry.is_fresh(today));
+    }
+}
+
+static CACHE: LazyLock<Mutex<PermsCache>> = LazyLock::new(Default::default);
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+fn lock() -> MutexGuard<'static, PermsCache> {
+    CACHE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub async fn get_assignments(
+    username: &str,
+    system_id: &str,
+    perm_id: &str,
+    db: &PgPool,
+) -> AppResult<Arc<[ReachingAssignment]>> {
+    let today = clock::today();
+    let key = (
+        username.to_owned(),
+        system_id.to_owned(),
+        perm_id.to_owned(),
+    );
+
+    let generation = {
+        let cache = lock();
+
+        let cached = cache
+            .entries
+            .get(&key)
+            .filter(|entry| entry.is_fresh(today));
+        if let Some(entry) = cached {
+            HITS.fetch_add(1, Ordering::Relaxed);
+            return Ok(entry.assignments.clone());
+        }
+
+        cache.generation
+    };
+
+    MISSES.fetch_add(1, Ordering::Relaxed);
+
+    let assignments: Arc<[_]> = perms::get_assignments(username, system_id, perm_id, db)
+        .await?
+        .into();
+
+    let mut cache = lock();
+
+    // if anything was invalidated while the query ran, the result might not
+    // reflect it, so it's only used this once instead of being cached
+    if cache.generation == generation {
+        if cache.entries.len() >= MAX_ENTRIES {
+            cache.evict_expired(today);
+
+            if cache.entries.len() >= MAX_ENTRIES {
+                warn!("Permissions cache is full of fresh entries; clearing it");
+                cache.entries.clear();
+            }
+        }
+
+        cache.entries.insert(
+            key,
+            CacheEntry {
+                assignments: assignments.clone(),
+                fetched_at: Instant::now(),
+                day: today,
+            },
+        );
+    }
+
+    Ok(assignments)
+}
+
+pub async fn get_hive_permissions(
+    username: &str,
+    perm_id: &'static str,
+    db: &PgPool,
+) -> AppResult<Vec<HivePermission>> {
+    let perms = get_assignments(username, HIVE_SYSTEM_ID, perm_id, db)
+        .await?
+        .iter()
+        .map(|assignment| {
+            HivePermission::try_from(BasePermissionAssignment {
+                system_id: HIVE_SYSTEM_ID.to_owned(),
+                perm_id: perm_id.to_owned(),
+                scope: assignment.scope.clone(),
+            })
+        })
+        .inspect(|r| {
+            if let Err(err) = r {
+                warn!("Got invalid Hive permission: {err:?}");
+            }
+        })
+        .filter_map(Result::ok)
+        .collect();
+
+    Ok(perms)
+}
+
+// how many distinct users currently hold each of a system's permissions
+// (through groups; API tokens aren't included), computed lazily
+pub async fn get_holder_counts(
+    system_id: &str,
+    db: &PgPool,
+) -> AppResult<Arc<HashMap<String, i64>>> {
+    let today = clock::today();
+
+    let generation = {
+        let cache = lock();
+
+        let cached = cache
+            .holder_counts
+            .get(system_id)
+            .filter(|entry| entry.is_fresh(today));
+        if let Some(entry) = cached {
+            return Ok(entry.counts.clone());
+        }
+
+        cache.generation
+    };
+
+    let counts = Arc::new(permissions::count_effective_holders(system_id, db).await?);
+
+    let mut cache = lock();
+
+    // (same reasoning as in `get_assignments`)
+    if cache.generation == generation {
+        cache.holder_counts.insert(
+            system_id.to_owned(),
+            HolderCountsEntry {
+                counts: counts.clone(),
+                fetched_at: Instant::now(),
+                day: today,
+            },
+        );
+    }
+
+    Ok(counts)
+}
+
+// for changes to memberships or the group hierarchy, which can affect any
+// number of users (through subgroups) and systems
+pub fn invalidate_all() {
+    let mut cache = lock();
+
+    cache.generation += 1;
+    cache.entries.clear();
+    cache.holder_counts.clear();
+}
+
+// for changes to a single system's permissions or their assignments
+pub fn invalidate_system(system_id: &str) {
+    let mut cache = lock();
+
+    cache.generation += 1;
+    cache
+        .entries
+        .retain(|(_, entry_system_id, _), _| entry_system_id != system_id);
+    cache.holder_counts.remove(system_id);
+}
+
+pub async fn report_periodically() {
+    let mut interval = rocket::tokio::time::interval(REPORT_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let hits = HITS.swap(0, Ordering::Relaxed);
+        let misses = MISSES.swap(0, Ordering::Relaxed);
+
+        let size = {
+            let mut cache = lock();
+            cache.evict_expired(clock::today());
+            cache.entries.len()
+        };
+
+        if hits + misses > 0 {
+            debug!(
+                "Permissions cache: {hits} hits, {misses} misses ({:.1}% hit rate), {size} entries",
+                100.0 * hits as f64 / (hits + misses) as f64
+            );
+        }
+    }
+}
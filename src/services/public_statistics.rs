@@ -0,0 +1,162 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::{HIVE_INTERNAL_DOMAIN, clock, errors::AppResult, perms};
+
+// recomputed at most this often, no matter how often they're requested (since
+// the endpoint is unauthenticated and the numbers hardly ever change anyway)
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+// aggregate numbers that can be published without logging in; each must be
+// explicitly enabled, and none may ever be about specific users
+#[derive(ValueEnum, Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum PublicMetric {
+    ActiveVolunteers,  // distinct users with a current direct membership
+    GroupsPerDomain,   // number of groups in each domain
+    MandatesPerDomain, // current direct memberships in each domain
+}
+
+#[derive(Serialize, Clone)]
+pub struct PublicStatisticsDto {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_volunteers: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups_per_domain: Option<BTreeMap<String, i64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mandates_per_domain: Option<BTreeMap<String, i64>>,
+}
+
+pub struct PublicStatistics {
+    metrics: Vec<PublicMetric>,
+    cached: Mutex<Option<(Instant, PublicStatisticsDto)>>,
+}
+
+impl PublicStatistics {
+    pub fn new(metrics: Vec<PublicMetric>) -> Self {
+        Self {
+            metrics,
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.metrics.is_empty()
+    }
+
+    fn includes(&self, metric: PublicMetric) -> bool {
+        self.metrics.contains(&metric)
+    }
+
+    pub async fn get<'x, X>(&self, db: X) -> AppResult<PublicStatisticsDto>
+    where
+        X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+    {
+        let cached = self
+            .cached
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if let Some((_, dto)) = cached.filter(|(computed_at, _)| computed_at.elapsed() < CACHE_TTL)
+        {
+            return Ok(dto);
+        }
+
+        // (concurrent misses might both compute, which is harmless)
+        let dto = self.compute(db).await?;
+
+        *self.cached.lock().unwrap_or_else(PoisonError::into_inner) =
+            Some((Instant::now(), dto.clone()));
+
+        Ok(dto)
+    }
+
+    // internal groups are never counted, and neither are external users
+    async fn compute<'x, X>(&self, db: X) -> AppResult<PublicStatisticsDto>
+    where
+        X: sqlx::Executor<'x, Database = sqlx::Postgres> + Copy,
+    {
+        let today = clock::today();
+
+        let active_volunteers = if self.includes(PublicMetric::ActiveVolunteers) {
+            let n = sqlx::query_scalar(
+                "SELECT COUNT(DISTINCT username)
+                FROM direct_memberships
+                WHERE $1 BETWEEN \"from\" AND \"until\"
+                    AND group_domain <> $2
+                    AND NOT group_domain = ANY($3)",
+            )
+            .bind(today)
+            .bind(HIVE_INTERNAL_DOMAIN)
+            .bind(perms::external_domains())
+            .fetch_one(db)
+            .await?;
+
+            Some(n)
+        } else {
+            None
+        };
+
+        let groups_per_domain = if self.includes(PublicMetric::GroupsPerDomain) {
+            let rows = sqlx::query(
+                "SELECT domain, COUNT(*) AS n
+                FROM groups
+                WHERE domain <> $1
+                    AND NOT domain = ANY($2)
+                GROUP BY domain",
+            )
+            .bind(HIVE_INTERNAL_DOMAIN)
+            .bind(perms::external_domains())
+            .fetch_all(db)
+            .await?;
+
+            Some(count_by_domain(rows)?)
+        } else {
+            None
+        };
+
+        let mandates_per_domain = if self.includes(PublicMetric::MandatesPerDomain) {
+            let rows = sqlx::query(
+                "SELECT group_domain AS domain, COUNT(*) AS n
+                FROM direct_memberships
+                WHERE $1 BETWEEN \"from\" AND \"until\"
+                    AND group_domain <> $2
+                    AND NOT group_domain = ANY($3)
+                GROUP BY group_domain",
+            )
+            .bind(today)
+            .bind(HIVE_INTERNAL_DOMAIN)
+            .bind(perms::external_domains())
+            .fetch_all(db)
+            .await?;
+
+            Some(count_by_domain(rows)?)
+        } else {
+            None
+        };
+
+        Ok(PublicStatisticsDto {
+            active_volunteers,
+            groups_per_domain,
+            mandates_per_domain,
+        })
+    }
+}
+
+fn count_by_domain(rows: Vec<sqlx::postgres::PgRow>) -> AppResult<BTreeMap<String, i64>> {
+    let mut counts = BTreeMap::new();
+
+    for row in rows {
+        counts.insert(row.try_get("domain")?, row.try_get("n")?);
+    }
+
+    Ok(counts)
+}
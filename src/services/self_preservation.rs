@@ -0,0 +1,316 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use log::*;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    HIVE_INTERNAL_DOMAIN, HIVE_ROOT_GROUP_ID, HIVE_SYSTEM_ID, clock,
+    errors::{AppError, AppResult},
+    models::{ActionKind, TargetKind},
+    perms,
+    services::audit_logs,
+};
+
+// every change that could affect who holds any of Hive's own permissions is
+// checked against these after being made (but before being committed); each
+// rule only blocks changes that would newly break it, since changes can't be
+// blamed for (nor be expected to fix) an already broken state
+enum Rule {
+    // root@hive.internal must always have a member, as a last resort
+    AnyAdministrator,
+    // permissions (with a given scope, if any) that nobody could ever be
+    // granted again if they were left without any holder
+    AnyHolder(&'static str, Option<&'static str>),
+}
+
+const RULES: &[Rule] = &[
+    Rule::AnyAdministrator,
+    Rule::AnyHolder("manage-systems", None),
+    Rule::AnyHolder("manage-perms", Some("*")),
+    Rule::AnyHolder("assign-perms", Some("*")),
+    Rule::AnyHolder("manage-groups", Some("*")),
+];
+
+// who currently holds which of Hive's own permissions (by their full key,
+// like `$hive:manage-groups:*`), as of the transaction it was taken in
+#[derive(Default, PartialEq)]
+pub struct HiveGrants {
+    administrators: BTreeSet<String>,
+    holders: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl HiveGrants {
+    fn holders_of(&self, key: &str) -> Option<&BTreeSet<String>> {
+        self.holders.get(key).filter(|holders| !holders.is_empty())
+    }
+}
+
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GrantChange {
+    pub username: String,
+    pub permission: String,
+}
+
+// which permissions would be gained/lost by whom
+#[derive(Serialize, Default)]
+pub struct Impact {
+    pub gained: Vec<GrantChange>,
+    pub lost: Vec<GrantChange>,
+}
+
+impl Impact {
+    pub fn between(before: &HiveGrants, after: &HiveGrants) -> Self {
+        let mut impact = Self::default();
+
+        let keys: BTreeSet<_> = before.holders.keys().chain(after.holders.keys()).collect();
+        let none = BTreeSet::new();
+
+        for key in keys {
+            let old = before.holders.get(key).unwrap_or(&none);
+            let new = after.holders.get(key).unwrap_or(&none);
+
+            for username in new.difference(old) {
+                impact.gained.push(GrantChange {
+                    username: username.clone(),
+                    permission: key.clone(),
+                });
+            }
+            for username in old.difference(new) {
+                impact.lost.push(GrantChange {
+                    username: username.clone(),
+                    permission: key.clone(),
+                });
+            }
+        }
+
+        impact
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gained.is_empty() && self.lost.is_empty()
+    }
+}
+
+fn permission_key(perm_id: &str, scope: Option<&str>) -> String {
+    match scope {
+        Some(scope) => format!("${HIVE_SYSTEM_ID}:{perm_id}:{scope}"),
+        None => format!("${HIVE_SYSTEM_ID}:{perm_id}"),
+    }
+}
+
+// whether changing this group's members (or subgroups) could change who holds
+// any of Hive's own permissions, i.e., if it has any of them assigned itself or
+// is (transitively) a subgroup of a group that does
+pub async fn affects_hive<'x, X>(group_id: &str, group_domain: &str, db: X) -> AppResult<bool>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let affects = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0
+        FROM permission_assignments pa
+        WHERE pa.system_id = $1
+            AND pa.group_id IS NOT NULL
+            AND pa.group_domain IS NOT NULL
+            AND (
+                (pa.group_id = $2 AND pa.group_domain = $3)
+                OR EXISTS (
+                    SELECT 1
+                    FROM all_subgroups_of(pa.group_id, pa.group_domain) sg
+                    WHERE sg.child_id = $2
+                        AND sg.child_domain = $3
+                )
+            )",
+    )
+    .bind(HIVE_SYSTEM_ID)
+    .bind(group_id)
+    .bind(group_domain)
+    .fetch_one(db)
+    .await?;
+
+    Ok(affects)
+}
+
+pub async fn snapshot<'x, X>(db: X) -> AppResult<HiveGrants>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    // administrators are returned without any permission
+    let rows: Vec<(Option<String>, Option<String>, String)> = sqlx::query_as(concat!(
+        "SELECT DISTINCT pa.perm_id, pa.scope, am.username
+        FROM permission_assignments pa
+        CROSS JOIN LATERAL all_members_of(pa.group_id, pa.group_domain, $1) am
+        WHERE pa.system_id = $2
+            AND pa.group_id IS NOT NULL
+            AND pa.group_domain IS NOT NULL
+            AND ",
+        perms::queries::internal_assignments_only!("$5"),
+        "
+        UNION
+        SELECT NULL, NULL, am.username
+        FROM all_members_of($3, $4, $1) am"
+    ))
+    .bind(clock::today())
+    .bind(HIVE_SYSTEM_ID)
+    .bind(HIVE_ROOT_GROUP_ID)
+    .bind(HIVE_INTERNAL_DOMAIN)
+    .bind(perms::external_domains())
+    .fetch_all(db)
+    .await?;
+
+    let mut grants = HiveGrants::default();
+
+    for (perm_id, scope, username) in rows {
+        match perm_id {
+            Some(perm_id) => grants
+                .holders
+                .entry(permission_key(&perm_id, scope.as_deref()))
+                .or_default()
+                .insert(username),
+            None => grants.administrators.insert(username),
+        };
+    }
+
+    Ok(grants)
+}
+
+// like `snapshot`, but only if changing the group's members could matter at
+// all (see `affects_hive`); to be passed to `enforce` after the change
+pub async fn snapshot_if_affected(
+    group_id: &str,
+    group_domain: &str,
+    conn: &mut sqlx::PgConnection,
+) -> AppResult<Option<HiveGrants>> {
+    if affects_hive(group_id, group_domain, &mut *conn).await? {
+        Ok(Some(snapshot(&mut *conn).await?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn check(before: &HiveGrants, after: &HiveGrants) -> AppResult<()> {
+    for rule in RULES {
+        match rule {
+            Rule::AnyAdministrator => {
+                // (the first user is only bootstrapped into root@hive.internal
+                // on login, so it is legitimately empty before that)
+                if !before.administrators.is_empty() && after.administrators.is_empty() {
+                    return Err(AppError::SelfPreservation);
+                }
+            }
+            Rule::AnyHolder(perm_id, scope) => {
+                let key = permission_key(perm_id, *scope);
+
+                if before.holders_of(&key).is_some() && after.holders_of(&key).is_none() {
+                    return Err(AppError::LastHivePermissionHolder(key));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// for changes that must not affect who holds Hive's own permissions at all (in
+// either direction), such as restoring deleted rows verbatim, which bypasses
+// the checks that would otherwise apply when granting them
+pub async fn require_unchanged(
+    before: &HiveGrants,
+    conn: &mut sqlx::PgConnection,
+    actor: &str,
+) -> AppResult<()> {
+    let after = snapshot(&mut *conn).await?;
+
+    if *before != after {
+        warn!(
+            "Disallowing change by {actor} that would affect Hive permissions: {}",
+            summarize(&Impact::between(before, &after))
+        );
+        return Err(AppError::SelfPreservation);
+    }
+
+    Ok(())
+}
+
+// must be called in the same transaction as the change itself (with the
+// snapshot taken before it), so that a violation can still roll it back; the
+// resulting gains and losses are logged, and recorded in the audit logs for
+// each affected permission
+pub async fn enforce(
+    before: Option<HiveGrants>,
+    conn: &mut sqlx::PgConnection,
+    actor: &str,
+) -> AppResult<()> {
+    let Some(before) = before else {
+        return Ok(());
+    };
+
+    let after = snapshot(&mut *conn).await?;
+    let impact = Impact::between(&before, &after);
+
+    if let Err(e) = check(&before, &after) {
+        warn!(
+            "Disallowing change by {actor} that would break self-preservation rules ({e}); \
+             it would have caused: {}",
+            summarize(&impact)
+        );
+        return Err(e);
+    }
+
+    if impact.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        "Hive permissions changed by {actor}: {}",
+        summarize(&impact)
+    );
+
+    let none = BTreeSet::new();
+    let changed: BTreeSet<_> = impact
+        .gained
+        .iter()
+        .chain(&impact.lost)
+        .map(|change| &change.permission)
+        .collect();
+
+    for key in changed {
+        audit_logs::add_entry(
+            ActionKind::Update,
+            TargetKind::Permission,
+            key,
+            actor,
+            json!({
+                "old": {
+                    "holders": before.holders.get(key).unwrap_or(&none),
+                },
+                "new": {
+                    "holders": after.holders.get(key).unwrap_or(&none),
+                }
+            }),
+            &mut *conn,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+fn summarize(impact: &Impact) -> String {
+    let gained = impact
+        .gained
+        .iter()
+        .map(|change| format!("+{} {}", change.username, change.permission));
+    let lost = impact
+        .lost
+        .iter()
+        .map(|change| format!("-{} {}", change.username, change.permission));
+
+    let summary: Vec<_> = gained.chain(lost).collect();
+
+    if summary.is_empty() {
+        "no permission changes".to_owned()
+    } else {
+        summary.join(", ")
+    }
+}
@@ -1,13 +1,21 @@
+use std::collections::BTreeMap;
+
+use chrono::{Local, TimeDelta};
 use log::*;
 use rocket::futures::TryStreamExt;
 use serde_json::json;
+use uuid::Uuid;
 
-use super::audit_logs;
+use super::{audit_logs, perms_cache};
 use crate::{
-    dto::systems::{CreateSystemDto, EditSystemDto},
+    clock,
+    dto::systems::{CreateSystemDto, EditSystemDto, SystemManifestDto},
     errors::{AppError, AppResult},
     guards::{perms::PermsEvaluator, user::User},
-    models::{ActionKind, System, TargetKind},
+    models::{
+        ActionKind, DailyApiUsage, ManifestChanges, Permission, System, SystemDeletionRequest,
+        SystemUsageStats, Tag, TargetKind,
+    },
     perms::{HivePermission, SystemsScope},
     sanitizers::SearchTerm,
 };
@@ -80,6 +88,61 @@ where
     }
 }
 
+pub async fn get_daily_usage<'x, X>(id: &str, days: u32, db: X) -> AppResult<Vec<DailyApiUsage>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let today = clock::today();
+
+    // one row per day (most recent first), even if there was no usage at all
+    let usage = sqlx::query_as(
+        "SELECT d::date AS day, COALESCE(SUM(u.n_requests), 0) AS n_requests
+        FROM GENERATE_SERIES($2::date - ($3 - 1), $2::date, '1 day') d
+        LEFT JOIN api_token_usage u
+            ON u.day = d::date
+            AND u.api_token_id IN (SELECT id FROM api_tokens WHERE system_id = $1)
+        GROUP BY d
+        ORDER BY d DESC",
+    )
+    .bind(id)
+    .bind(today)
+    .bind(i32::try_from(days).unwrap_or(30))
+    .fetch_all(db)
+    .await?;
+
+    Ok(usage)
+}
+
+pub async fn get_usage_stats<'x, X>(id: &str, db: X) -> AppResult<SystemUsageStats>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let stats = sqlx::query_as(
+        "SELECT
+            (
+                SELECT MAX(last_used_at)
+                FROM api_tokens
+                WHERE system_id = $1
+            ) AS last_token_usage,
+            (
+                SELECT COUNT(DISTINCT (group_id, group_domain))
+                FROM tag_assignments
+                WHERE system_id = $1
+                    AND group_id IS NOT NULL
+            ) AS n_tagged_groups,
+            (
+                SELECT COUNT(DISTINCT username)
+                FROM tag_assignments
+                WHERE system_id = $1
+            ) AS n_tagged_users",
+    )
+    .bind(id)
+    .fetch_one(db)
+    .await?;
+
+    Ok(stats)
+}
+
 pub async fn create_new<'v, 'x, X>(dto: &CreateSystemDto<'v>, db: X, user: &User) -> AppResult<()>
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
@@ -108,7 +171,66 @@ where
     Ok(())
 }
 
-pub async fn delete<'x, X>(id: &str, db: X, user: &User) -> AppResult<()>
+// systems with permission assignments, or with API tokens used within this
+// period, require a second person to confirm their deletion
+const DELETION_PROTECTION_PERIOD: TimeDelta = TimeDelta::days(30);
+
+pub enum SystemDeletion {
+    Done,
+    Requested, // awaiting confirmation by someone else
+}
+
+pub async fn is_deletion_protected<'x, X>(id: &str, db: X) -> AppResult<bool>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let protected = sqlx::query_scalar(
+        "SELECT EXISTS (
+            SELECT 1
+            FROM permission_assignments
+            WHERE system_id = $1
+        ) OR EXISTS (
+            SELECT 1
+            FROM api_tokens
+            WHERE system_id = $1
+                AND last_used_at > $2
+        )",
+    )
+    .bind(id)
+    .bind(Local::now() - DELETION_PROTECTION_PERIOD)
+    .fetch_one(db)
+    .await?;
+
+    Ok(protected)
+}
+
+pub async fn get_deletion_request<'x, X>(
+    id: &str,
+    db: X,
+) -> AppResult<Option<SystemDeletionRequest>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let request = sqlx::query_as(
+        "SELECT *
+        FROM system_deletion_requests
+        WHERE system_id = $1",
+    )
+    .bind(id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(request)
+}
+
+// `confirmation` is what the user typed to confirm, which must be the system's
+// ID; it is only required (and checked) for protected systems
+pub async fn delete<'x, X>(
+    id: &str,
+    confirmation: Option<&str>,
+    db: X,
+    user: &User,
+) -> AppResult<SystemDeletion>
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
 {
@@ -128,22 +250,107 @@ where
 
     let mut txn = db.begin().await?;
 
-    let old: System = sqlx::query_as("DELETE FROM systems WHERE id = $1 RETURNING *")
+    // (locked so that two people can't both end up only requesting deletion)
+    sqlx::query("SELECT id FROM systems WHERE id = $1 FOR UPDATE")
         .bind(id)
         .fetch_optional(&mut *txn)
         .await?
         .ok_or_else(|| AppError::NoSuchSystem(id.to_owned()))?;
 
+    let mut requested_by = None;
+
+    if is_deletion_protected(id, &mut *txn).await? {
+        if confirmation.map(str::trim) != Some(id) {
+            return Err(AppError::DeletionNotConfirmed(id.to_owned()));
+        }
+
+        match get_deletion_request(id, &mut *txn).await? {
+            None => {
+                sqlx::query(
+                    "INSERT INTO system_deletion_requests (system_id, requested_by)
+                    VALUES ($1, $2)",
+                )
+                .bind(id)
+                .bind(user.username())
+                .execute(&mut *txn)
+                .await?;
+
+                audit_logs::add_entry(
+                    ActionKind::Update,
+                    TargetKind::System,
+                    id,
+                    user.username(),
+                    json!({"new": {"deletion_requested": true}}),
+                    &mut *txn,
+                )
+                .await?;
+
+                txn.commit().await?;
+
+                return Ok(SystemDeletion::Requested);
+            }
+            Some(request) if request.requested_by == user.username() => {
+                return Err(AppError::SelfConfirmedDeletion);
+            }
+            Some(request) => requested_by = Some(request.requested_by),
+        }
+    }
+
+    let old: System = sqlx::query_as("DELETE FROM systems WHERE id = $1 RETURNING *")
+        .bind(id)
+        .fetch_one(&mut *txn)
+        .await?;
+
+    let details = if let Some(requested_by) = requested_by {
+        json!({"old": {"description": old.description, "deletion_requested_by": requested_by}})
+    } else {
+        json!({"old": {"description": old.description}})
+    };
+
     audit_logs::add_entry(
         ActionKind::Delete,
         TargetKind::System,
         id,
         user.username(),
-        json!({"old": {"description": old.description}}),
+        details,
         &mut *txn,
     )
     .await?;
 
+    txn.commit().await?;
+    perms_cache::invalidate_system(id);
+
+    Ok(SystemDeletion::Done)
+}
+
+pub async fn cancel_deletion_request<'x, X>(id: &str, db: X, user: &User) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let cancelled = sqlx::query(
+        "DELETE FROM system_deletion_requests
+        WHERE system_id = $1",
+    )
+    .bind(id)
+    .execute(&mut *txn)
+    .await?
+    .rows_affected()
+        > 0;
+
+    if cancelled {
+        audit_logs::add_entry(
+            ActionKind::Update,
+            TargetKind::System,
+            id,
+            user.username(),
+            json!({"old": {"deletion_requested": true}}),
+            &mut *txn,
+        )
+        .await?;
+    }
+
     txn.commit().await?;
 
     Ok(())
@@ -200,3 +407,289 @@ where
 
     Ok(())
 }
+
+// makes the system's permissions and tags match the manifest, all or nothing;
+// with `dry_run`, everything is rolled back but the changes are still reported
+pub async fn apply_manifest<'x, X>(
+    id: &str,
+    manifest: &SystemManifestDto,
+    dry_run: bool,
+    api_token_id: Uuid,
+    db: X,
+) -> AppResult<ManifestChanges>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    if id == crate::HIVE_SYSTEM_ID {
+        // we manage our own permissions via database migrations
+        warn!("Disallowing manifest application to ourselves via API token {api_token_id}");
+        return Err(AppError::SelfPreservation);
+    } else if crate::integrations::integration_exists(id) {
+        // integration systems are managed via their own (static) manifest
+        warn!(
+            "Disallowing manifest application to integration system {id} via API token \
+             {api_token_id}"
+        );
+        return Err(AppError::SelfPreservation);
+    }
+
+    manifest
+        .validate()
+        .map_err(AppError::InvalidSystemManifest)?;
+
+    let mut txn = db.begin().await?;
+
+    // lock the system row so that concurrent applications are serialized
+    sqlx::query("SELECT id FROM systems WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut *txn)
+        .await?
+        .ok_or_else(|| AppError::NoSuchSystem(id.to_owned()))?;
+
+    let mut changes = ManifestChanges::default();
+
+    if let Some(permissions) = &manifest.permissions {
+        let mut existing: BTreeMap<String, Permission> =
+            sqlx::query_as("SELECT * FROM permissions WHERE system_id = $1")
+                .bind(id)
+                .fetch_all(&mut *txn)
+                .await?
+                .into_iter()
+                .map(|perm: Permission| (perm.perm_id.clone(), perm))
+                .collect();
+
+        for perm in permissions {
+            let description = perm.description.trim();
+
+            if let Some(old) = existing.remove(&perm.id) {
+                if old.has_scope != perm.has_scope {
+                    // would leave existing assignments in an inconsistent state
+                    return Err(AppError::InvalidSystemManifest(format!(
+                        "permission `{}` cannot change whether it has a scope",
+                        perm.id
+                    )));
+                }
+
+                if old.description != description {
+                    sqlx::query(
+                        "UPDATE permissions
+                        SET description = $1
+                        WHERE system_id = $2
+                            AND perm_id = $3",
+                    )
+                    .bind(description)
+                    .bind(id)
+                    .bind(&perm.id)
+                    .execute(&mut *txn)
+                    .await?;
+
+                    audit_logs::add_entry(
+                        ActionKind::Update,
+                        TargetKind::Permission,
+                        old.key(),
+                        crate::HIVE_API_ACTOR,
+                        json!({
+                            "old": {"description": old.description},
+                            "new": {"description": description},
+                            "api_token_id": api_token_id,
+                        }),
+                        &mut *txn,
+                    )
+                    .await?;
+
+                    changes.updated.push(old.key());
+                }
+            } else {
+                let new: Permission = sqlx::query_as(
+                    "INSERT INTO permissions (system_id, perm_id, has_scope, description)
+                    VALUES ($1, $2, $3, $4)
+                    RETURNING *",
+                )
+                .bind(id)
+                .bind(&perm.id)
+                .bind(perm.has_scope)
+                .bind(description)
+                .fetch_one(&mut *txn)
+                .await?;
+
+                audit_logs::add_entry(
+                    ActionKind::Create,
+                    TargetKind::Permission,
+                    new.key(),
+                    crate::HIVE_API_ACTOR,
+                    json!({
+                        "new": {
+                            "has_scope": new.has_scope,
+                            "description": new.description,
+                        },
+                        "api_token_id": api_token_id,
+                    }),
+                    &mut *txn,
+                )
+                .await?;
+
+                changes.created.push(new.key());
+            }
+        }
+
+        // whatever is left over is no longer in the manifest
+        for old in existing.into_values() {
+            sqlx::query("DELETE FROM permissions WHERE system_id = $1 AND perm_id = $2")
+                .bind(id)
+                .bind(&old.perm_id)
+                .execute(&mut *txn)
+                .await?;
+
+            audit_logs::add_entry(
+                ActionKind::Delete,
+                TargetKind::Permission,
+                old.key(),
+                crate::HIVE_API_ACTOR,
+                json!({
+                    "old": {
+                        "has_scope": old.has_scope,
+                        "description": old.description,
+                    },
+                    "api_token_id": api_token_id,
+                }),
+                &mut *txn,
+            )
+            .await?;
+
+            changes.deleted.push(old.key());
+        }
+    }
+
+    if let Some(tags) = &manifest.tags {
+        let mut existing: BTreeMap<String, Tag> =
+            sqlx::query_as("SELECT * FROM tags WHERE system_id = $1")
+                .bind(id)
+                .fetch_all(&mut *txn)
+                .await?
+                .into_iter()
+                .map(|tag: Tag| (tag.tag_id.clone(), tag))
+                .collect();
+
+        for tag in tags {
+            let description = tag.description.trim();
+
+            if let Some(old) = existing.remove(&tag.id) {
+                if old.supports_groups != tag.supports_groups
+                    || old.supports_users != tag.supports_users
+                    || old.has_content != tag.has_content
+                {
+                    // would leave existing assignments in an inconsistent state
+                    return Err(AppError::InvalidSystemManifest(format!(
+                        "tag `{}` cannot change what it supports",
+                        tag.id
+                    )));
+                }
+
+                if old.description != description {
+                    sqlx::query(
+                        "UPDATE tags
+                        SET description = $1
+                        WHERE system_id = $2
+                            AND tag_id = $3",
+                    )
+                    .bind(description)
+                    .bind(id)
+                    .bind(&tag.id)
+                    .execute(&mut *txn)
+                    .await?;
+
+                    audit_logs::add_entry(
+                        ActionKind::Update,
+                        TargetKind::Tag,
+                        old.key(),
+                        crate::HIVE_API_ACTOR,
+                        json!({
+                            "old": {"description": old.description},
+                            "new": {"description": description},
+                            "api_token_id": api_token_id,
+                        }),
+                        &mut *txn,
+                    )
+                    .await?;
+
+                    changes.updated.push(old.key());
+                }
+            } else {
+                let new: Tag = sqlx::query_as(
+                    "INSERT INTO tags
+                        (system_id, tag_id, supports_groups, supports_users, has_content, description)
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                    RETURNING *",
+                )
+                .bind(id)
+                .bind(&tag.id)
+                .bind(tag.supports_groups)
+                .bind(tag.supports_users)
+                .bind(tag.has_content)
+                .bind(description)
+                .fetch_one(&mut *txn)
+                .await?;
+
+                audit_logs::add_entry(
+                    ActionKind::Create,
+                    TargetKind::Tag,
+                    new.key(),
+                    crate::HIVE_API_ACTOR,
+                    json!({
+                        "new": {
+                            "supports_groups": new.supports_groups,
+                            "supports_users": new.supports_users,
+                            "has_content": new.has_content,
+                            "description": new.description,
+                        },
+                        "api_token_id": api_token_id,
+                    }),
+                    &mut *txn,
+                )
+                .await?;
+
+                changes.created.push(new.key());
+            }
+        }
+
+        // whatever is left over is no longer in the manifest
+        for old in existing.into_values() {
+            sqlx::query("DELETE FROM tags WHERE system_id = $1 AND tag_id = $2")
+                .bind(id)
+                .bind(&old.tag_id)
+                .execute(&mut *txn)
+                .await?;
+
+            audit_logs::add_entry(
+                ActionKind::Delete,
+                TargetKind::Tag,
+                old.key(),
+                crate::HIVE_API_ACTOR,
+                json!({
+                    "old": {
+                        "supports_groups": old.supports_groups,
+                        "supports_users": old.supports_users,
+                        "has_content": old.has_content,
+                        "description": old.description,
+                    },
+                    "api_token_id": api_token_id,
+                }),
+                &mut *txn,
+            )
+            .await?;
+
+            changes.deleted.push(old.key());
+        }
+    }
+
+    if dry_run {
+        txn.rollback().await?;
+    } else {
+        txn.commit().await?;
+
+        // deleted permissions take their assignments with them
+        perms_cache::invalidate_system(id);
+    }
+
+    Ok(changes)
+}
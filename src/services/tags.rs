@@ -1,16 +1,28 @@
-use chrono::Local;
 use log::*;
+use rocket::futures::TryStreamExt;
 use serde_json::json;
+use sqlx::Row;
 use uuid::Uuid;
 
-use super::{audit_logs, pg_args};
+use super::{
+    api_tokens::GroupVisibility, audit_logs, changes::ProtectedDomains, deletions, permissions,
+    perms_cache, pg_args,
+};
 use crate::{
-    dto::tags::{AssignTagToGroupDto, AssignTagToUserDto, CreateSubtagDto, CreateTagDto},
+    clock,
+    dto::tags::{
+        AssignTagToGroupDto, AssignTagToUserDto, CreateSubtagDto, CreateTagDerivationDto,
+        CreateTagDto, DelegateTagDto, EditTagAssignmentDto, TaggedEntityKind, TaggedFilterDto,
+    },
     errors::{AppError, AppResult},
     guards::{lang::Language, perms::PermsEvaluator, user::User},
-    models::{ActionKind, AffiliatedTagAssignment, Tag, TagMorphology, TargetKind},
-    perms::{HivePermission, SystemsScope},
+    models::{
+        ActionKind, AffiliatedTagAssignment, EffectiveTagAssignment, Tag, TagDelegation,
+        TagDerivation, TagMorphology, TagRef, TaggedEntity, TargetKind,
+    },
+    perms::{self, HivePermission, SystemsScope, TagScope},
     resolver::IdentityResolver,
+    sanitizers::SearchTerm,
 };
 
 pub async fn get_one<'x, X>(system_id: &str, tag_id: &str, db: X) -> AppResult<Option<Tag>>
@@ -56,6 +68,33 @@ where
     Ok(tags)
 }
 
+// q matches against both the id and description
+pub async fn list_matching<'x, X>(
+    system_ids: &[String],
+    q: Option<&str>,
+    db: X,
+) -> AppResult<Vec<Tag>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut query = sqlx::QueryBuilder::new("SELECT * FROM tags WHERE system_id = ANY(");
+    query.push_bind(system_ids);
+    query.push(")");
+
+    if let Some(search) = q {
+        let term = SearchTerm::from(search).anywhere();
+        query.push(" AND (tag_id ILIKE ");
+        query.push_bind(term.clone());
+        query.push(" OR description ILIKE ");
+        query.push_bind(term);
+        query.push(")");
+    }
+
+    query.push(" ORDER BY system_id, tag_id");
+
+    Ok(query.build_query_as().fetch_all(db).await?)
+}
+
 pub async fn list_group_assignments<'x, X>(
     system_id: &str,
     tag_id: &str,
@@ -68,7 +107,7 @@ pub async fn list_group_assignments<'x, X>(
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
 {
-    let today = Local::now().date_naive();
+    let today = clock::today();
     let mut query = sqlx::QueryBuilder::new("SELECT ta.*");
 
     match label_lang {
@@ -105,7 +144,7 @@ where
 
     if let Some(username) = username {
         // filter for specific user
-        query.push(" JOIN all_groups_of(");
+        query.push(" JOIN all_group_refs_of(");
         query.push_bind(username);
         query.push(", ");
         query.push_bind(today);
@@ -133,9 +172,10 @@ where
 
     if let Some(perms) = perms {
         for assignment in &mut assignments {
-            let min = HivePermission::AssignTags(SystemsScope::Id(assignment.system_id.clone()));
+            let possibilities =
+                HivePermission::assign_tag(&assignment.system_id, &assignment.tag_id);
             // query should be OK since perms are cached by perm_id
-            assignment.can_manage = Some(perms.satisfies(min).await?);
+            assignment.can_manage = Some(perms.satisfies_any_of(&possibilities).await?);
         }
     }
 
@@ -170,9 +210,10 @@ where
 
     if let Some(perms) = perms {
         for assignment in &mut assignments {
-            let min = HivePermission::AssignTags(SystemsScope::Id(assignment.system_id.clone()));
+            let possibilities =
+                HivePermission::assign_tag(&assignment.system_id, &assignment.tag_id);
             // query should be OK since perms are cached by perm_id
-            assignment.can_manage = Some(perms.satisfies(min).await?);
+            assignment.can_manage = Some(perms.satisfies_any_of(&possibilities).await?);
         }
     }
 
@@ -199,6 +240,76 @@ where
     Ok(assignments)
 }
 
+// ordered (groups first) so that results can be paged through consistently
+pub async fn list_tagged_paged<'x, X>(
+    system_id: &str,
+    tag_id: &str,
+    filter: &TaggedFilterDto<'_>,
+    visibility: &GroupVisibility,
+    limit: u32,
+    db: X,
+) -> AppResult<Vec<TaggedEntity>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut query = sqlx::QueryBuilder::new(
+        "SELECT
+            ta.username,
+            ta.group_id,
+            ta.group_domain,
+            ta.content,
+            BOOL_OR(ta.id IS NOT NULL) AS direct",
+    );
+
+    if filter.transitive {
+        query.push(" FROM all_tag_assignments ta");
+    } else {
+        query.push(" FROM tag_assignments ta");
+    }
+
+    query.push(" WHERE ta.system_id = ");
+    query.push_bind(system_id);
+    query.push(" AND ta.tag_id = ");
+    query.push_bind(tag_id);
+
+    if let Some(content) = filter.content {
+        // indirect assignments never have content, as in `all_tag_assignments`
+        query.push(" AND ta.content = ");
+        query.push_bind(content);
+    }
+
+    match filter.kind {
+        Some(TaggedEntityKind::Group) => {
+            query.push(" AND ta.group_id IS NOT NULL");
+        }
+        Some(TaggedEntityKind::User) => {
+            query.push(" AND ta.username IS NOT NULL");
+        }
+        None => {}
+    }
+
+    if let GroupVisibility::Only(groups) = visibility {
+        let (ids, domains): (Vec<_>, Vec<_>) = groups.iter().cloned().unzip();
+
+        query.push(" AND (ta.group_id IS NULL OR (ta.group_id, ta.group_domain) IN (");
+        query.push("SELECT * FROM UNNEST(");
+        query.push_bind(ids);
+        query.push("::TEXT[], ");
+        query.push_bind(domains);
+        query.push("::TEXT[])))");
+    }
+
+    query.push(
+        " GROUP BY ta.username, ta.group_id, ta.group_domain, ta.content
+        ORDER BY ta.group_domain NULLS LAST, ta.group_id, ta.username, ta.content NULLS FIRST",
+    );
+
+    query.push(" OFFSET ").push_bind(i64::from(filter.offset));
+    query.push(" LIMIT ").push_bind(i64::from(limit));
+
+    Ok(query.build_query_as().fetch_all(db).await?)
+}
+
 pub async fn create_new<'v, 'x, X>(
     system_id: &str,
     dto: &CreateTagDto<'v>,
@@ -262,25 +373,34 @@ where
     Ok(tag)
 }
 
-pub async fn delete<'x, X>(system_id: &str, tag_id: &str, db: X, user: &User) -> AppResult<()>
+pub async fn delete<'x, X>(system_id: &str, tag_id: &str, db: X, user: &User) -> AppResult<Uuid>
 where
     X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
 {
     // managing HIVE_SYSTEM_ID tags is not a self-preservation error because
     // these are necessary for $hive:manage-groups:tag == #hive:tag
 
-    if crate::integrations::integration_exists(system_id) {
-        // shouldn't add tags for integration systems,
-        // since they're managed via manifest
+    let mut txn = db.begin().await?;
+
+    if require_one(system_id, tag_id, &mut *txn).await?.managed {
+        // shouldn't delete tags that integrations rely on, since they're
+        // declared in their manifest (and would only be recreated on startup)
         warn!(
-            "Disallowing tag deletion for integration system {} from {}",
+            "Disallowing deletion of managed tag #{}:{} from {}",
             system_id,
+            tag_id,
             user.username()
         );
         return Err(AppError::SelfPreservation);
     }
 
-    let mut txn = db.begin().await?;
+    let deletion_id = deletions::track(
+        TargetKind::Tag,
+        format!("#{system_id}:{tag_id}"),
+        user.username(),
+        &mut *txn,
+    )
+    .await?;
 
     let old: Tag = sqlx::query_as(
         "DELETE FROM tags
@@ -294,6 +414,8 @@ where
     .await?
     .ok_or_else(|| AppError::NoSuchTag(system_id.to_owned(), tag_id.to_owned()))?;
 
+    deletions::untrack(&mut *txn).await?;
+
     audit_logs::add_entry(
         ActionKind::Delete,
         TargetKind::Tag,
@@ -313,7 +435,7 @@ where
 
     txn.commit().await?;
 
-    Ok(())
+    Ok(deletion_id)
 }
 
 pub async fn assign_to_group<'v, 'x, X>(
@@ -494,8 +616,8 @@ where
     // ^ not a permissions problem, but prevents enumeration (we haven't checked
     // permissions yet)
 
-    let min = HivePermission::AssignTags(SystemsScope::Id(old.system_id.clone()));
-    perms.require(min).await?;
+    let possibilities = HivePermission::assign_tag(&old.system_id, &old.tag_id);
+    perms.require_any_of(&possibilities).await?;
 
     let details = if let Some(ref username) = old.username {
         json!({
@@ -537,6 +659,112 @@ where
     Ok(old)
 }
 
+// (as with `unassign`, a missing assignment is reported as a permissions
+// problem, to prevent enumeration)
+pub async fn require_assignment<'x, X>(
+    assignment_id: Uuid,
+    db: X,
+    perms: &PermsEvaluator,
+) -> AppResult<AffiliatedTagAssignment>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut assignment: AffiliatedTagAssignment = sqlx::query_as(
+        "SELECT *
+        FROM tag_assignments
+        WHERE id = $1",
+    )
+    .bind(assignment_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotAllowed(HivePermission::AssignTags(SystemsScope::Wildcard)))?;
+
+    let possibilities = HivePermission::assign_tag(&assignment.system_id, &assignment.tag_id);
+    perms.require_any_of(&possibilities).await?;
+
+    assignment.can_manage = Some(true);
+
+    Ok(assignment)
+}
+
+// only for assignments with content, since that's all there is to change
+// (anything else is a different assignment altogether)
+pub async fn update_content<'v, 'x, X>(
+    assignment_id: Uuid,
+    dto: &EditTagAssignmentDto<'v>,
+    db: X,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<AffiliatedTagAssignment>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let old: AffiliatedTagAssignment = sqlx::query_as(
+        "SELECT *
+        FROM tag_assignments
+        WHERE id = $1
+        FOR UPDATE",
+    )
+    .bind(assignment_id)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or_else(|| AppError::NotAllowed(HivePermission::AssignTags(SystemsScope::Wildcard)))?;
+    // ^ not a permissions problem, but prevents enumeration (see `unassign`)
+
+    let possibilities = HivePermission::assign_tag(&old.system_id, &old.tag_id);
+    perms.require_any_of(&possibilities).await?;
+
+    let Some(old_content) = old.content else {
+        return Err(AppError::ExtraneousTagContent(old.system_id, old.tag_id));
+    };
+
+    let mut new: AffiliatedTagAssignment = sqlx::query_as(
+        "UPDATE tag_assignments
+        SET content = $1
+        WHERE id = $2
+        RETURNING *",
+    )
+    .bind(dto.content)
+    .bind(assignment_id)
+    .fetch_one(&mut *txn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(err) if err.is_unique_violation() => {
+            AppError::DuplicateTagAssignment(
+                old.system_id.clone(),
+                old.tag_id.clone(),
+                Some(dto.content.to_string()),
+            )
+        }
+        _ => e.into(),
+    })?;
+
+    new.can_manage = Some(true);
+
+    if *dto.content != old_content {
+        audit_logs::add_entry(
+            ActionKind::Update,
+            TargetKind::TagAssignment,
+            // FIXME: consider using assignment_id as target_id
+            new.key(),
+            user.username(),
+            json!({
+                "old": {"content": old_content},
+                "new": {"content": new.content},
+                "id": assignment_id,
+            }),
+            &mut *txn,
+        )
+        .await?;
+
+        txn.commit().await?;
+    }
+
+    Ok(new)
+}
+
 pub async fn list_subtags<'v, 'x, X>(system_id: &str, tag_id: &str, db: X) -> AppResult<Vec<Tag>>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
@@ -558,6 +786,180 @@ where
     Ok(subtags)
 }
 
+// tags that could be added as subtags without being rejected by
+// `create_subtag`: not already a subtag, not an ancestor (nor the tag itself)
+// and supporting at least one of the same kinds of entities
+pub async fn list_subtag_candidates<'x, X>(
+    system_id: &str,
+    tag_id: &str,
+    db: X,
+) -> AppResult<Vec<Tag>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let candidates = sqlx::query_as(
+        "SELECT t.*
+        FROM tags t
+        JOIN tags p
+            ON p.tag_id = $1
+            AND p.system_id = $2
+        WHERE ((t.supports_groups AND p.supports_groups)
+                OR (t.supports_users AND p.supports_users))
+            AND NOT EXISTS (
+                SELECT 1
+                FROM tag_ancestry ta
+                WHERE ta.descendant_id = p.tag_id
+                    AND ta.descendant_system_id = p.system_id
+                    AND ta.ancestor_id = t.tag_id
+                    AND ta.ancestor_system_id = t.system_id
+            )
+            AND NOT EXISTS (
+                SELECT 1
+                FROM subtags st
+                WHERE st.parent_id = p.tag_id
+                    AND st.parent_system_id = p.system_id
+                    AND st.child_id = t.tag_id
+                    AND st.child_system_id = t.system_id
+            )
+        ORDER BY t.system_id, t.tag_id",
+    )
+    .bind(tag_id)
+    .bind(system_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(candidates)
+}
+
+#[derive(Clone, Copy)]
+pub enum HierarchyDirection {
+    Ancestors,   // tags implied by this one (parents, grandparents, ...)
+    Descendants, // tags that imply this one (subtags, sub-subtags, ...)
+}
+
+// every maximal path through the subtag DAG starting at (but excluding) the
+// given tag, in the given direction; shared prefixes are thus repeated
+pub async fn list_hierarchy_paths<'x, X>(
+    system_id: &str,
+    tag_id: &str,
+    direction: HierarchyDirection,
+    db: X,
+) -> AppResult<Vec<Vec<TagRef>>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let (from, to) = match direction {
+        HierarchyDirection::Ancestors => ("child", "parent"),
+        HierarchyDirection::Descendants => ("parent", "child"),
+    };
+
+    // keys (system_id:tag_id) are only used to detect cycles, which shouldn't
+    // be possible anyway, but better safe than sorry
+    let query = format!(
+        "WITH RECURSIVE hierarchy(system_id, tag_id, system_ids, tag_ids, keys) AS (
+            SELECT
+                st.{to}_system_id,
+                st.{to}_id,
+                ARRAY[st.{to}_system_id::TEXT],
+                ARRAY[st.{to}_id::TEXT],
+                ARRAY[st.{to}_system_id || ':' || st.{to}_id]
+            FROM subtags st
+            WHERE st.{from}_system_id = $1
+                AND st.{from}_id = $2
+
+            UNION ALL
+
+            SELECT
+                st.{to}_system_id,
+                st.{to}_id,
+                h.system_ids || st.{to}_system_id::TEXT,
+                h.tag_ids || st.{to}_id::TEXT,
+                h.keys || (st.{to}_system_id || ':' || st.{to}_id)
+            FROM subtags st
+            JOIN hierarchy h
+                ON st.{from}_system_id = h.system_id
+                AND st.{from}_id = h.tag_id
+            WHERE NOT (st.{to}_system_id || ':' || st.{to}_id) = ANY(h.keys)
+        )
+        SELECT h.system_ids, h.tag_ids
+        FROM hierarchy h
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM subtags st
+            WHERE st.{from}_system_id = h.system_id
+                AND st.{from}_id = h.tag_id
+                AND NOT (st.{to}_system_id || ':' || st.{to}_id) = ANY(h.keys)
+        )
+        ORDER BY h.keys"
+    );
+
+    let mut rows = sqlx::query(&query).bind(system_id).bind(tag_id).fetch(db);
+
+    let mut paths = vec![];
+
+    while let Some(row) = rows.try_next().await? {
+        let system_ids: Vec<String> = row.try_get("system_ids")?;
+        let tag_ids: Vec<String> = row.try_get("tag_ids")?;
+
+        let path = system_ids
+            .into_iter()
+            .zip(tag_ids)
+            .map(|(system_id, tag_id)| TagRef { system_id, tag_id })
+            .collect();
+
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+// groups and users that carry the tag, whether directly, via subtags or as
+// derived from a permission (see `TagDerivation`)
+pub async fn list_effective_assignments<'x, X>(
+    system_id: &str,
+    tag_id: &str,
+    db: X,
+) -> AppResult<Vec<EffectiveTagAssignment>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let assignments = sqlx::query_as(
+        "SELECT
+            ta.username,
+            ta.group_id,
+            ta.group_domain,
+            CASE WHEN th.descendant_id = th.ancestor_id
+                AND th.descendant_system_id = th.ancestor_system_id
+                THEN ta.content
+            END AS content,
+            ta.system_id AS via_system_id,
+            ta.tag_id AS via_tag_id,
+            (th.descendant_id = th.ancestor_id
+                AND th.descendant_system_id = th.ancestor_system_id
+                AND NOT ta.derived) AS direct,
+            ta.derived
+        FROM (
+            SELECT username, group_id, group_domain, content, system_id, tag_id, FALSE AS derived
+            FROM tag_assignments
+            UNION ALL
+            SELECT NULL, group_id, group_domain, NULL, system_id, tag_id, TRUE
+            FROM derived_tag_assignments
+        ) ta
+        JOIN tag_ancestry th
+            ON ta.tag_id = th.descendant_id
+            AND ta.system_id = th.descendant_system_id
+        WHERE th.ancestor_system_id = $1
+            AND th.ancestor_id = $2
+        ORDER BY ta.group_domain, ta.group_id, ta.username, direct DESC, ta.system_id, ta.tag_id",
+    )
+    .bind(system_id)
+    .bind(tag_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(assignments)
+}
+
 pub async fn create_subtag<'v, 'x, X>(
     system_id: &str,
     tag_id: &str,
@@ -701,6 +1103,359 @@ where
     Ok(())
 }
 
+pub async fn list_derivations<'x, X>(
+    system_id: &str,
+    tag_id: &str,
+    db: X,
+) -> AppResult<Vec<TagDerivation>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let derivations = sqlx::query_as(
+        "SELECT id, perm_system_id, perm_id, scope
+        FROM tag_derivations
+        WHERE system_id = $1
+            AND tag_id = $2
+        ORDER BY perm_system_id, perm_id, scope NULLS FIRST",
+    )
+    .bind(system_id)
+    .bind(tag_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(derivations)
+}
+
+// derived assignments have no content, and are only ever to groups
+pub async fn create_derivation<'v, 'x, X>(
+    system_id: &str,
+    tag_id: &str,
+    dto: &CreateTagDerivationDto<'v>,
+    db: X,
+    user: &User,
+) -> AppResult<TagDerivation>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let tag = require_one(system_id, tag_id, &mut *txn).await?;
+
+    if !tag.supports_groups || tag.has_content {
+        return Err(AppError::InvalidTagDerivation(tag.system_id, tag.tag_id));
+    }
+
+    let perm = permissions::require_one(dto.perm.system_id, dto.perm.perm_id, &mut *txn).await?;
+
+    if !perm.has_scope && dto.scope.is_some() {
+        return Err(AppError::ExtraneousPermissionScope(
+            perm.system_id,
+            perm.perm_id,
+        ));
+    }
+
+    let scope = dto.scope.as_deref().copied();
+
+    if scope.is_some() {
+        permissions::validate_scope(&perm.system_id, &perm.perm_id, scope)?;
+    }
+
+    let derivation: TagDerivation = sqlx::query_as(
+        "INSERT INTO tag_derivations (system_id, tag_id, perm_system_id, perm_id, scope)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, perm_system_id, perm_id, scope",
+    )
+    .bind(system_id)
+    .bind(tag_id)
+    .bind(&perm.system_id)
+    .bind(&perm.perm_id)
+    .bind(scope)
+    .fetch_one(&mut *txn)
+    .await
+    .map_err(|e| {
+        let key = match scope {
+            Some(scope) => format!("{}:{scope}", perm.key()),
+            None => perm.key(),
+        };
+        AppError::DuplicateTagDerivation(key).if_unique_violation(e)
+    })?;
+
+    audit_logs::add_entry(
+        ActionKind::Create,
+        TargetKind::TagAssignment, // FIXME: consider independent Derivation target
+        format!("#{system_id}:{tag_id}"),
+        user.username(),
+        json!({
+            "new": {
+                "entity_type": "derivation",
+                "id": derivation.id,
+                "perm_system_id": derivation.perm_system_id,
+                "perm_id": derivation.perm_id,
+                "scope": derivation.scope,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(derivation)
+}
+
+pub async fn delete_derivation<'x, X>(
+    system_id: &str,
+    tag_id: &str,
+    id: Uuid,
+    db: X,
+    user: &User,
+) -> AppResult<TagDerivation>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    // (scoped to the tag so that it can't be deleted through another one)
+    let old: TagDerivation = sqlx::query_as(
+        "DELETE FROM tag_derivations
+        WHERE id = $1
+            AND system_id = $2
+            AND tag_id = $3
+        RETURNING id, perm_system_id, perm_id, scope",
+    )
+    .bind(id)
+    .bind(system_id)
+    .bind(tag_id)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or_else(|| AppError::NoSuchTagDerivation(id))?;
+
+    audit_logs::add_entry(
+        ActionKind::Delete,
+        TargetKind::TagAssignment, // FIXME: consider independent Derivation target
+        format!("#{system_id}:{tag_id}"),
+        user.username(),
+        json!({
+            "old": {
+                "entity_type": "derivation",
+                "id": old.id,
+                "perm_system_id": old.perm_system_id,
+                "perm_id": old.perm_id,
+                "scope": old.scope,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(old)
+}
+
+// delegations are plain `$hive:assign-tag:<system>:<tag>` assignments, just
+// managed by the system's owners instead of whoever can assign hive perms
+pub async fn list_delegations<'x, X>(
+    system_id: &str,
+    label_lang: Option<&Language>,
+    db: X,
+) -> AppResult<Vec<TagDelegation>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let mut query = sqlx::QueryBuilder::new(
+        "SELECT pa.id, split_part(pa.scope, ':', 2) AS tag_id, pa.group_id, pa.group_domain",
+    );
+
+    match label_lang {
+        Some(Language::Swedish) => {
+            query.push(", gs.name_sv AS label");
+        }
+        Some(Language::English) => {
+            query.push(", gs.name_en AS label");
+        }
+        None => {}
+    }
+
+    query.push(
+        " FROM permission_assignments pa
+        JOIN groups gs
+            ON gs.id = pa.group_id
+            AND gs.domain = pa.group_domain
+        WHERE pa.system_id = ",
+    );
+    query.push_bind(crate::HIVE_SYSTEM_ID);
+    query.push(" AND pa.perm_id = ");
+    query.push_bind(HivePermission::AssignTag(TagScope::Any).key());
+    query.push(" AND split_part(pa.scope, ':', 1) = ");
+    query.push_bind(system_id);
+    query.push(" ORDER BY tag_id, pa.group_domain, pa.group_id");
+
+    let delegations = query.build_query_as().fetch_all(db).await?;
+
+    Ok(delegations)
+}
+
+// delegations are permission assignments, but can't be pending changes (which
+// are reviewed by whoever can assign $hive permissions, not the tag's owners)
+pub async fn delegate<'v, 'x, X>(
+    system_id: &str,
+    dto: &DelegateTagDto<'v>,
+    label_lang: Option<&Language>,
+    protected: &ProtectedDomains,
+    db: X,
+    user: &User,
+) -> AppResult<TagDelegation>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    perms::require_internal_domain(dto.group.domain)?;
+
+    if protected.contains(dto.group.domain) {
+        return Err(AppError::UnapprovableProtectedChange(
+            dto.group.domain.to_string(),
+        ));
+    }
+
+    let mut txn = db.begin().await?;
+
+    let tag = require_one(system_id, *dto.tag, &mut *txn).await?;
+
+    let perm_id = HivePermission::AssignTag(TagScope::Any).key();
+    let scope = TagScope::Id(tag.system_id, tag.tag_id).to_string();
+
+    let mut query = sqlx::QueryBuilder::with_arguments(
+        "INSERT INTO permission_assignments (system_id, perm_id, scope, group_id, group_domain)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, split_part(scope, ':', 2) AS tag_id, group_id, group_domain",
+        pg_args!(
+            crate::HIVE_SYSTEM_ID,
+            perm_id,
+            &scope,
+            dto.group.id,
+            dto.group.domain
+        ),
+    );
+
+    if let Some(lang) = label_lang {
+        query.push(", (SELECT ");
+        match lang {
+            Language::Swedish => query.push("name_sv"),
+            Language::English => query.push("name_en"),
+        };
+        query.push(
+            " FROM groups gs
+            WHERE gs.id = $4
+                AND gs.domain = $5
+            ) AS label",
+        );
+    }
+
+    let delegation: TagDelegation =
+        query
+            .build_query_as()
+            .fetch_one(&mut *txn)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(err) if err.is_unique_violation() => {
+                    AppError::DuplicatePermissionAssignment(
+                        crate::HIVE_SYSTEM_ID.to_string(),
+                        perm_id.to_string(),
+                        Some(scope.clone()),
+                    )
+                }
+                sqlx::Error::Database(err) if err.is_foreign_key_violation() => {
+                    AppError::NoSuchGroup(dto.group.id.to_string(), dto.group.domain.to_string())
+                }
+                _ => e.into(),
+            })?;
+
+    audit_logs::add_entry(
+        ActionKind::Create,
+        TargetKind::PermissionAssignment,
+        format!("${}:{perm_id}", crate::HIVE_SYSTEM_ID),
+        user.username(),
+        json!({
+            "new": {
+                "entity_type": "group",
+                "id": delegation.id,
+                "group_id": delegation.group_id,
+                "group_domain": delegation.group_domain,
+                "scope": scope,
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    // (delegations are $hive:assign-tag assignments)
+    perms_cache::invalidate_system(crate::HIVE_SYSTEM_ID);
+
+    Ok(delegation)
+}
+
+pub async fn revoke_delegation<'x, X>(
+    system_id: &str,
+    delegation_id: Uuid,
+    db: X,
+    user: &User,
+) -> AppResult<TagDelegation>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    let perm_id = HivePermission::AssignTag(TagScope::Any).key();
+
+    // scoping the delete to this system means a delegation can't be revoked
+    // through another system's page (whose owners aren't allowed to)
+    let old: TagDelegation = sqlx::query_as(
+        "DELETE FROM permission_assignments
+        WHERE id = $1
+            AND system_id = $2
+            AND perm_id = $3
+            AND split_part(scope, ':', 1) = $4
+        RETURNING id, split_part(scope, ':', 2) AS tag_id, group_id, group_domain",
+    )
+    .bind(delegation_id)
+    .bind(crate::HIVE_SYSTEM_ID)
+    .bind(perm_id)
+    .bind(system_id)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or_else(|| {
+        AppError::NotAllowed(HivePermission::AssignPerms(SystemsScope::Id(
+            crate::HIVE_SYSTEM_ID.to_owned(),
+        )))
+    })?;
+
+    audit_logs::add_entry(
+        ActionKind::Delete,
+        TargetKind::PermissionAssignment,
+        format!("${}:{perm_id}", crate::HIVE_SYSTEM_ID),
+        user.username(),
+        json!({
+            "old": {
+                "entity_type": "group",
+                "id": old.id,
+                "group_id": old.group_id,
+                "group_domain": old.group_domain,
+                "scope": format!("{system_id}:{}", old.tag_id),
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    perms_cache::invalidate_system(crate::HIVE_SYSTEM_ID);
+
+    Ok(old)
+}
+
 pub async fn get_morphology<'x, X>(system_id: &str, tag_id: &str, db: X) -> AppResult<TagMorphology>
 where
     X: sqlx::Executor<'x, Database = sqlx::Postgres>,
@@ -0,0 +1,374 @@
+use std::time::Duration;
+
+use chrono::{Local, TimeDelta};
+use log::*;
+use serde_json::json;
+use sqlx::{FromRow, PgPool, types::JsonValue};
+use uuid::Uuid;
+
+use super::{api_tokens, audit_logs};
+use crate::{
+    dto::systems::CreateWebhookDto,
+    errors::{AppError, AppResult},
+    guards::user::User,
+    models::{ActionKind, TargetKind, Webhook, WebhookDelivery, WebhookEvent},
+};
+
+const DELIVERY_INTERVAL: Duration = Duration::from_secs(15);
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const DELIVERY_BATCH_SIZE: i64 = 20;
+const USER_AGENT: &str = "hive-webhooks";
+const INTERRUPTED_DELIVERY_ERROR: &str = "delivery was interrupted before any response";
+
+// failed deliveries can be replayed manually for as long as they're kept
+pub const DELIVERY_RETENTION_PERIOD: TimeDelta = TimeDelta::days(30);
+
+// counted towards `Webhook::n_recent_failures`
+const RECENT_FAILURES_PERIOD: TimeDelta = TimeDelta::days(7);
+
+pub async fn list_for_system<'x, X>(system_id: &str, db: X) -> AppResult<Vec<Webhook>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let webhooks = sqlx::query_as(
+        "SELECT wh.*,
+            (
+                SELECT COUNT(*)
+                FROM webhook_deliveries d
+                WHERE d.webhook_id = wh.id
+                    AND d.attempted_at >= $2
+                    AND (d.status_code IS NULL OR d.status_code NOT BETWEEN 200 AND 299)
+            ) AS n_recent_failures
+        FROM webhooks wh
+        WHERE wh.system_id = $1
+        ORDER BY wh.created_at",
+    )
+    .bind(system_id)
+    .bind(Local::now() - RECENT_FAILURES_PERIOD)
+    .fetch_all(db)
+    .await?;
+
+    Ok(webhooks)
+}
+
+pub async fn require_one<'x, X>(system_id: &str, id: Uuid, db: X) -> AppResult<Webhook>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    sqlx::query_as(
+        "SELECT *
+        FROM webhooks
+        WHERE system_id = $1
+            AND id = $2",
+    )
+    .bind(system_id)
+    .bind(id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NoSuchWebhook(id))
+}
+
+pub async fn list_recent_deliveries<'x, X>(
+    webhook_id: Uuid,
+    limit: u32,
+    db: X,
+) -> AppResult<Vec<WebhookDelivery>>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let deliveries = sqlx::query_as(
+        "SELECT *
+        FROM webhook_deliveries
+        WHERE webhook_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2",
+    )
+    .bind(webhook_id)
+    .bind(i64::from(limit))
+    .fetch_all(db)
+    .await?;
+
+    Ok(deliveries)
+}
+
+pub async fn create_new<'v, 'x, X>(
+    system_id: &str,
+    dto: &CreateWebhookDto<'v>,
+    db: X,
+    user: &User,
+) -> AppResult<Webhook>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    // (also gets rid of any duplicates)
+    let events: Vec<_> = WebhookEvent::ALL
+        .into_iter()
+        .filter(|event| dto.events.contains(event))
+        .collect();
+
+    let webhook: Webhook = sqlx::query_as(
+        "INSERT INTO webhooks (system_id, url, events, created_by)
+        VALUES ($1, $2, $3, $4)
+        RETURNING *",
+    )
+    .bind(system_id)
+    .bind(*dto.url)
+    .bind(&events)
+    .bind(user.username())
+    .fetch_one(&mut *txn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(err) if err.is_foreign_key_violation() => {
+            AppError::NoSuchSystem(system_id.to_owned())
+        }
+        _ => e.into(),
+    })?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::System,
+        system_id,
+        user.username(),
+        json!({
+            "new": {
+                "webhook": {
+                    "id": webhook.id,
+                    "url": webhook.url,
+                    "events": webhook.events,
+                }
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(webhook)
+}
+
+pub async fn delete<'x, X>(system_id: &str, id: Uuid, db: X, user: &User) -> AppResult<()>
+where
+    X: sqlx::Acquire<'x, Database = sqlx::Postgres>,
+{
+    let mut txn = db.begin().await?;
+
+    // deliveries are removed via ON DELETE CASCADE
+    let old: Webhook = sqlx::query_as(
+        "DELETE FROM webhooks
+        WHERE system_id = $1
+            AND id = $2
+        RETURNING *",
+    )
+    .bind(system_id)
+    .bind(id)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or_else(|| AppError::NoSuchWebhook(id))?;
+
+    audit_logs::add_entry(
+        ActionKind::Update,
+        TargetKind::System,
+        system_id,
+        user.username(),
+        json!({
+            "old": {
+                "webhook": {
+                    "id": old.id,
+                    "url": old.url,
+                    "events": old.events,
+                }
+            }
+        }),
+        &mut *txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+// queues a copy of a previous failed delivery, such that the original's
+// outcome is still kept around in the history
+pub async fn replay<'x, X>(webhook_id: Uuid, delivery_id: Uuid, db: X) -> AppResult<Uuid>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    sqlx::query_scalar(
+        "INSERT INTO webhook_deliveries (webhook_id, event, payload, replay_of)
+        SELECT webhook_id, event, payload, id
+        FROM webhook_deliveries
+        WHERE id = $1
+            AND webhook_id = $2
+            AND attempted_at IS NOT NULL
+            AND (status_code IS NULL OR status_code NOT BETWEEN 200 AND 299)
+        RETURNING id",
+    )
+    .bind(delivery_id)
+    .bind(webhook_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NoSuchWebhookDelivery(delivery_id))
+}
+
+#[derive(FromRow)]
+struct PendingDelivery {
+    id: Uuid,
+    event: WebhookEvent,
+    payload: JsonValue,
+    url: String,
+    secret: Uuid,
+}
+
+struct DeliveryOutcome {
+    status_code: Option<i32>,
+    error: Option<String>,
+}
+
+pub async fn deliver_pending(db: &PgPool, client: &reqwest::Client) -> AppResult<usize> {
+    // deliveries are claimed (and committed as attempted) before being sent,
+    // so that multiple instances never send the same one twice, without
+    // holding any locks while waiting for responses; the placeholder error is
+    // only kept if sending is interrupted, so that it can still be replayed
+    let pending: Vec<PendingDelivery> = sqlx::query_as(
+        "UPDATE webhook_deliveries d
+        SET attempted_at = $2,
+            error = $3
+        FROM webhooks wh
+        WHERE wh.id = d.webhook_id
+            AND d.id IN (
+                SELECT id
+                FROM webhook_deliveries
+                WHERE attempted_at IS NULL
+                ORDER BY created_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+        RETURNING d.id, d.event, d.payload, wh.url, wh.secret",
+    )
+    .bind(DELIVERY_BATCH_SIZE)
+    .bind(Local::now())
+    .bind(INTERRUPTED_DELIVERY_ERROR)
+    .fetch_all(db)
+    .await?;
+
+    for delivery in &pending {
+        let outcome = send(delivery, client).await;
+
+        sqlx::query(
+            "UPDATE webhook_deliveries
+            SET status_code = $2,
+                error = $3
+            WHERE id = $1",
+        )
+        .bind(delivery.id)
+        .bind(outcome.status_code)
+        .bind(outcome.error)
+        .execute(db)
+        .await?;
+    }
+
+    Ok(pending.len())
+}
+
+// signed like API requests to hive (see `api_tokens::authenticate_signed`),
+// i.e. covering a timestamp so that captured deliveries can't be replayed later
+// on, but keyed by the webhook's secret, so receivers can tell they're genuine
+async fn send(delivery: &PendingDelivery, client: &reqwest::Client) -> DeliveryOutcome {
+    let body = delivery.payload.to_string();
+    let timestamp = Local::now().timestamp();
+    let signature = api_tokens::hmac_sha256(
+        delivery.secret.to_string().as_bytes(),
+        format!("{timestamp}\n{body}").as_bytes(),
+    );
+
+    let result = client
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Hive-Event", delivery.event.key())
+        .header("X-Hive-Delivery", delivery.id.to_string())
+        .header("X-Hive-Timestamp", timestamp.to_string())
+        .header("X-Hive-Signature", hex::encode(signature))
+        .body(body)
+        .send()
+        .await;
+
+    match result {
+        Ok(response) => DeliveryOutcome {
+            status_code: Some(response.status().as_u16().into()),
+            error: None,
+        },
+        Err(e) => {
+            debug!("Failed to deliver webhook {}: {e}", delivery.id);
+
+            DeliveryOutcome {
+                status_code: None,
+                error: Some(e.to_string()),
+            }
+        }
+    }
+}
+
+pub async fn purge_old_deliveries<'x, X>(db: X) -> AppResult<u64>
+where
+    X: sqlx::Executor<'x, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
+        "DELETE FROM webhook_deliveries
+        WHERE attempted_at IS NOT NULL
+            AND created_at <= $1",
+    )
+    .bind(Local::now() - DELIVERY_RETENTION_PERIOD)
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// meant to be spawned as a background task on startup
+pub async fn deliver_periodically(db: PgPool) {
+    let client = reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .user_agent(USER_AGENT)
+        .build()
+        .expect("failed to build webhooks reqwest client");
+
+    let mut interval = rocket::tokio::time::interval(DELIVERY_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if crate::routing::maintenance::is_active() {
+            continue;
+        }
+
+        match deliver_pending(&db, &client).await {
+            Ok(0) => {}
+            Ok(n) => debug!("Attempted {n} webhook deliveries"),
+            Err(e) => error!("Failed to deliver webhooks: {e}"),
+        }
+    }
+}
+
+// meant to be spawned as a background task on startup
+pub async fn purge_periodically(db: PgPool) {
+    let mut interval = rocket::tokio::time::interval(CLEANUP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if crate::routing::maintenance::is_active() {
+            continue;
+        }
+
+        match purge_old_deliveries(&db).await {
+            Ok(0) => {}
+            Ok(n) => debug!("Purged {n} old webhook deliveries"),
+            Err(e) => error!("Failed to purge old webhook deliveries: {e}"),
+        }
+    }
+}
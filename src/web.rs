@@ -2,29 +2,80 @@ pub use catchers::catchers;
 use rinja::Template;
 use rocket::{
     Responder,
+    fs::NamedFile,
     http::{Header, uri::Reference},
-    response::{Redirect, content::RawHtml},
+    response::{
+        Redirect,
+        content::{RawHtml, RawJson},
+    },
     uri,
 };
+use serde::Serialize;
+use sqlx::PgPool;
 
 use crate::{
     api::{self, ApiVersionInfo},
-    errors::AppResult,
-    guards::{context::PageContext, lang::Language},
+    errors::{AppError, AppResult},
+    guards::{context::PageContext, format::ResponseFormat, lang::Language, user::User},
     routing::RouteTree,
 };
 
 mod api_tokens;
 mod auth;
+mod calendar_feeds;
 mod catchers;
+mod changes;
+mod deletions;
 mod groups;
+mod imports;
 mod logs;
+mod maintenance;
+mod operational_years;
+mod palette;
 mod permissions;
+mod public;
+mod quick;
 mod systems;
 mod tags;
 mod user;
 
-type RenderedTemplate = RawHtml<String>;
+// every page and fragment can also be requested as JSON (see ResponseFormat),
+// such that other clients (e.g., an eventual SPA or mobile app) can use the
+// exact same routes; the view struct itself is what gets serialized
+#[derive(Responder)]
+enum RenderedTemplate {
+    Html(RawHtml<String>),
+    Json(RawJson<String>),
+}
+
+fn render<T: Template + Serialize>(
+    template: &T,
+    format: ResponseFormat,
+) -> AppResult<RenderedTemplate> {
+    match format {
+        ResponseFormat::Html => Ok(RenderedTemplate::Html(RawHtml(template.render()?))),
+        ResponseFormat::Json => {
+            let json =
+                serde_json::to_string(template).map_err(AppError::ResponseSerializationError)?;
+
+            Ok(RenderedTemplate::Json(RawJson(json)))
+        }
+    }
+}
+
+// form contexts can't be serialized as-is; for JSON clients, the only
+// relevant part is which fields were rejected and why
+fn serialize_form_errors<S: serde::Serializer>(
+    form: &&rocket::form::Context<'_>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(form.errors().map(|error| {
+        serde_json::json!({
+            "field": error.name.as_ref().map(ToString::to_string),
+            "message": error.kind.to_string(),
+        })
+    }))
+}
 
 #[derive(Responder)]
 enum GracefulRedirect {
@@ -52,17 +103,43 @@ enum Either<T, U> {
     Right(U),
 }
 
+// i.e., root members, for pages that affect the whole instance
+async fn require_admin(user: &User, db: &PgPool) -> AppResult<()> {
+    let role = crate::services::groups::details::get_role_in_group(
+        user.username(),
+        crate::HIVE_ROOT_GROUP_ID,
+        crate::HIVE_INTERNAL_DOMAIN,
+        db,
+    )
+    .await?;
+
+    if role.is_some() {
+        Ok(())
+    } else {
+        Err(AppError::AdministratorsOnly)
+    }
+}
+
 pub fn tree() -> RouteTree {
     RouteTree::Branch(vec![
         api_tokens::routes(),
         auth::routes(),
+        calendar_feeds::routes(),
+        changes::routes(),
+        deletions::routes(),
         groups::routes(),
+        imports::routes(),
         permissions::routes(),
+        public::routes(),
+        quick::routes(),
         user::routes(),
         systems::routes(),
         tags::routes(),
         logs::routes(),
-        rocket::routes![favicon, home, api_versions].into(),
+        maintenance::routes(),
+        operational_years::routes(),
+        palette::routes(),
+        rocket::routes![favicon, service_worker, home, api_versions].into(),
     ])
 }
 
@@ -72,7 +149,14 @@ fn favicon() -> Redirect {
     Redirect::permanent(uri!("/static/icons/favicon.ico"))
 }
 
-#[derive(Template)]
+// service workers can only control pages under their own path, so this can't
+// be served from /static like everything else
+#[rocket::get("/sw.js")]
+async fn service_worker() -> Option<NamedFile> {
+    NamedFile::open("static/sw.js").await.ok()
+}
+
+#[derive(Template, Serialize)]
 #[template(path = "home.html.j2")]
 struct HomeView {
     ctx: PageContext,
@@ -82,10 +166,10 @@ struct HomeView {
 fn home(ctx: PageContext) -> AppResult<RenderedTemplate> {
     let template = HomeView { ctx };
 
-    Ok(RawHtml(template.render()?))
+    render(&template, template.ctx.format)
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "api-versions.html.j2")]
 struct ApiVersionsView<'v> {
     ctx: PageContext,
@@ -101,7 +185,7 @@ fn api_versions(ctx: PageContext) -> AppResult<RenderedTemplate> {
         docs: cfg!(feature = "api-docs"),
     };
 
-    Ok(RawHtml(template.render()?))
+    render(&template, template.ctx.format)
 }
 
 mod filters {
@@ -1,15 +1,17 @@
+use chrono::{Local, TimeDelta};
 use log::*;
 use rinja::Template;
 use rocket::{
     State,
     form::{self, Contextual, Form},
-    response::{Redirect, content::RawHtml},
+    response::Redirect,
     uri,
 };
+use serde::Serialize;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use super::{Either, RenderedTemplate, filters};
+use super::{Either, RenderedTemplate, filters, render};
 use crate::{
     dto::api_tokens::CreateApiTokenDto,
     errors::AppResult,
@@ -21,27 +23,53 @@ use crate::{
 };
 
 pub fn routes() -> RouteTree {
-    rocket::routes![list_api_tokens, create_api_token, delete_api_token].into()
+    rocket::routes![
+        list_api_tokens,
+        list_idle_api_tokens,
+        create_api_token,
+        delete_api_token,
+        toggle_api_token_signing
+    ]
+    .into()
 }
 
-#[derive(Template)]
+// how long a token must not have been used for to be considered idle
+const IDLE_TOKEN_DAYS: i64 = 90;
+
+#[derive(Template, Serialize)]
 #[template(path = "api-tokens/list.html.j2")]
 struct ListApiTokensView {
     ctx: PageContext,
     api_tokens: Vec<ApiToken>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
+#[template(path = "api-tokens/row-cells.html.j2")]
+struct PartialApiTokenRowView {
+    ctx: PageContext,
+    token: ApiToken,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "api-tokens/idle.html.j2")]
+struct ListIdleApiTokensView {
+    ctx: PageContext,
+    api_tokens: Vec<ApiToken>,
+    idle_days: i64,
+}
+
+#[derive(Template, Serialize)]
 #[template(
     path = "api-tokens/create.html.j2",
     block = "inner_create_api_token_form"
 )]
 struct PartialCreateApiTokenView<'f, 'v> {
     ctx: PageContext,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     api_token_create_form: &'f form::Context<'v>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "api-tokens/created.html.j2")]
 struct ApiTokenCreatedView<'a> {
     ctx: PageContext,
@@ -50,7 +78,7 @@ struct ApiTokenCreatedView<'a> {
     secret: Uuid,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(
     path = "api-tokens/created.html.j2",
     block = "api_token_created_partial"
@@ -69,7 +97,7 @@ async fn list_api_tokens(
     perms: &PermsEvaluator,
     partial: Option<HxRequest<'_>>,
 ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-    if partial.is_none() {
+    if partial.is_none() && !ctx.format.is_json() {
         // we only know how to render a table, not a full page;
         // redirect to system details
 
@@ -92,7 +120,39 @@ async fn list_api_tokens(
 
     let template = ListApiTokensView { ctx, api_tokens };
 
-    Ok(Either::Left(RawHtml(template.render()?)))
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
+
+#[rocket::get("/api-tokens/idle")]
+async fn list_idle_api_tokens(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+) -> AppResult<RenderedTemplate> {
+    let fully_authorized = perms.satisfies(HivePermission::ManageSystems).await?;
+
+    if !fully_authorized {
+        perms
+            .require(HivePermission::ManageSystem(SystemsScope::Any))
+            .await?;
+    }
+
+    let system_ids: Vec<_> = systems::list_manageable(None, fully_authorized, db.inner(), perms)
+        .await?
+        .into_iter()
+        .map(|system| system.id)
+        .collect();
+
+    let cutoff = Local::now() - TimeDelta::days(IDLE_TOKEN_DAYS);
+    let api_tokens = api_tokens::list_idle(&system_ids, cutoff, db.inner()).await?;
+
+    let template = ListIdleApiTokensView {
+        ctx,
+        api_tokens,
+        idle_days: IDLE_TOKEN_DAYS,
+    };
+
+    render(&template, template.ctx.format)
 }
 
 #[rocket::post("/system/<system_id>/api-tokens", data = "<form>")]
@@ -128,7 +188,7 @@ async fn create_api_token<'v>(
                 secret: result.secret,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             let template = ApiTokenCreatedView {
                 ctx,
@@ -137,7 +197,7 @@ async fn create_api_token<'v>(
                 secret: result.secret,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         }
     } else {
         // some errors are present; show the form again
@@ -149,7 +209,7 @@ async fn create_api_token<'v>(
                 api_token_create_form: &form.context,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
             // any validation error indicators... but there isn't a great
@@ -182,3 +242,28 @@ pub async fn delete_api_token(
         Ok(Either::Right(Redirect::to(target)))
     }
 }
+
+#[rocket::post("/api-token/<id>/toggle-signing")]
+async fn toggle_api_token_signing(
+    id: Uuid,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    // perms can only be checked later because they depend on the system
+
+    // TODO: anti-CSRF
+
+    let token = api_tokens::toggle_signing(&id, db.inner(), perms, &user).await?;
+
+    if partial.is_some() {
+        let template = PartialApiTokenRowView { ctx, token };
+
+        Ok(Either::Left(render(&template, template.ctx.format)?))
+    } else {
+        let target = uri!(super::systems::system_details(token.system_id));
+        Ok(Either::Right(Redirect::to(target)))
+    }
+}
@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use log::*;
 use rocket::{
     State,
@@ -15,12 +17,16 @@ use crate::{
         oidc::{OidcAuthenticationResult, OidcClient},
     },
     errors::AppResult,
-    guards::{perms::PermsEvaluator, scheme::RequestScheme, user::User},
+    guards::{headers::UserAgent, perms::PermsEvaluator, scheme::RequestScheme, user::User},
+    mailer::Mailer,
     models::{ActionKind, TargetKind},
     perms::HivePermission,
-    resolver::IdentityResolver,
+    resolver::{IdentityResolver, UserEmailDomain},
     routing::RouteTree,
-    services::{audit_logs, groups},
+    services::{
+        audit_logs, groups,
+        logins::{self, LoginAttempt},
+    },
 };
 
 pub fn routes() -> RouteTree {
@@ -55,15 +61,36 @@ async fn login(
 }
 
 #[rocket::get("/auth/oidc-callback?<code>&<state>")]
+#[allow(clippy::too_many_arguments)]
 async fn oidc_callback(
     code: &str,
     state: &str,
     oidc_client: &State<OidcClient>,
     db: &State<PgPool>,
+    mailer: &State<Option<Mailer>>,
+    email_domain: &State<UserEmailDomain>,
+    ip: Option<IpAddr>,
+    user_agent: Option<UserAgent<'_>>,
     jar: &CookieJar<'_>,
 ) -> AppResult<Redirect> {
-    let OidcAuthenticationResult { session, next } =
-        auth::finish_authentication(code, state, oidc_client, jar).await?;
+    let result = auth::finish_authentication(code, state, oidc_client, jar).await;
+
+    let attempt = LoginAttempt {
+        username: result.as_ref().ok().map(|r| r.session.username.as_str()),
+        error: result.as_ref().err().map(ToString::to_string),
+        ip: ip.map(|ip| ip.to_string()),
+        user_agent: user_agent.map(Into::into),
+    };
+
+    // (failing to record it shouldn't prevent anyone from logging in)
+    match logins::record(&attempt, db.inner()).await {
+        Ok(event) => {
+            logins::alert_admins(&event, db.inner(), mailer.inner().as_ref(), email_domain).await
+        }
+        Err(e) => error!("Failed to record login event: {e}"),
+    }
+
+    let OidcAuthenticationResult { session, next } = result?;
 
     groups::members::conditional_bootstrap(&session.username, db.inner()).await?;
 
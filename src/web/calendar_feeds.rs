@@ -0,0 +1,298 @@
+use chrono::{Days, NaiveDate, Utc};
+use rinja::Template;
+use rocket::{State, http::ContentType};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    errors::{AppError, AppResult},
+    guards::{context::PageContext, lang::Language, perms::PermsEvaluator, user::User},
+    models::{CalendarFeed, SimpleGroup},
+    resolver::IdentityResolver,
+    routing::RouteTree,
+    services::{
+        calendar_feeds::{self, FEED_HISTORY},
+        groups::{self, AuthorityInGroup},
+    },
+    web::{RenderedTemplate, render},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![
+        get_feed,
+        create_group_feed,
+        revoke_group_feed,
+        create_personal_feed,
+        revoke_personal_feed,
+    ]
+    .into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "calendar-feeds/feed.html.j2")]
+struct PartialCalendarFeedView {
+    ctx: PageContext,
+    endpoint: String, // where to (re)create or revoke the feed
+    feed: Option<CalendarFeed>,
+}
+
+#[rocket::get("/calendar/<secret>/feed.ics?<lang>")]
+async fn get_feed(
+    secret: Uuid,
+    lang: Option<Language>,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+) -> AppResult<Option<(ContentType, String)>> {
+    // this is unauthenticated by design (calendar applications can't log in),
+    // so any failure is just reported as a 404 to avoid leaking anything
+
+    let Some(feed) = calendar_feeds::get_one(&secret, db.inner()).await? else {
+        return Ok(None);
+    };
+
+    let lang = lang.unwrap_or(Language::Swedish);
+
+    let mut calendar = ICalendar::new();
+
+    if let (Some(group_id), Some(group_domain)) = (&feed.group_id, &feed.group_domain) {
+        // the owner might have left the group since the feed was created
+        let role =
+            groups::details::get_role_in_group(&feed.owner, group_id, group_domain, db.inner())
+                .await?;
+        if role.is_none() {
+            return Ok(None);
+        }
+
+        let group: SimpleGroup =
+            groups::details::require_one(group_id, group_domain, db.inner()).await?;
+        let group_name = group.localized_name(&lang);
+
+        calendar.name(&lang.t1("calendar.feed.name.group", group_name));
+
+        let members = groups::members::get_direct_members(
+            group_id,
+            group_domain,
+            true,
+            Some(FEED_HISTORY),
+            db.inner(),
+            resolver.as_ref(),
+        )
+        .await?;
+
+        for member in members {
+            let Some(id) = member.id else { continue };
+            let name = member.display_name.as_deref().unwrap_or(&member.username);
+
+            calendar.event(
+                &format!("{id}-from"),
+                member.from,
+                &format!(
+                    "{group_name}: {}",
+                    lang.t1("calendar.feed.event.member.from", name)
+                ),
+            );
+            calendar.event(
+                &format!("{id}-until"),
+                member.until,
+                &format!(
+                    "{group_name}: {}",
+                    lang.t1("calendar.feed.event.member.until", name)
+                ),
+            );
+        }
+    } else {
+        calendar.name(&lang.t("calendar.feed.name.personal"));
+
+        let memberships = calendar_feeds::get_user_memberships(&feed.owner, db.inner()).await?;
+
+        for membership in memberships {
+            calendar.event(
+                &format!("{}-until", membership.membership_id),
+                membership.until,
+                &lang.t1(
+                    "calendar.feed.event.own.until",
+                    membership.group.localized_name(&lang),
+                ),
+            );
+        }
+    }
+
+    Ok(Some((ContentType::Calendar, calendar.finish())))
+}
+
+#[rocket::post("/group/<domain>/<id>/calendar-feed")]
+async fn create_group_feed(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    require_group_feed_eligibility(id, domain, db.inner(), perms, &user).await?;
+
+    let feed =
+        calendar_feeds::get_or_create(user.username(), Some((id, domain)), db.inner()).await?;
+
+    let template = PartialCalendarFeedView {
+        ctx,
+        endpoint: format!("/group/{domain}/{id}/calendar-feed"),
+        feed: Some(feed),
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::delete("/group/<domain>/<id>/calendar-feed")]
+async fn revoke_group_feed(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    // no need to check anything, since we only ever touch the user's own feed
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    calendar_feeds::revoke(user.username(), Some((id, domain)), db.inner()).await?;
+
+    let template = PartialCalendarFeedView {
+        ctx,
+        endpoint: format!("/group/{domain}/{id}/calendar-feed"),
+        feed: None,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/user/calendar-feed")]
+async fn create_personal_feed(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    let feed = calendar_feeds::get_or_create(user.username(), None, db.inner()).await?;
+
+    let template = PartialCalendarFeedView {
+        ctx,
+        endpoint: "/user/calendar-feed".to_owned(),
+        feed: Some(feed),
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::delete("/user/calendar-feed")]
+async fn revoke_personal_feed(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    calendar_feeds::revoke(user.username(), None, db.inner()).await?;
+
+    let template = PartialCalendarFeedView {
+        ctx,
+        endpoint: "/user/calendar-feed".to_owned(),
+        feed: None,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+// feeds are only served while their owner is still a member of the group (see
+// `get_feed`), so there's no point in creating one otherwise, even if the user
+// could view the group through some permission
+async fn require_group_feed_eligibility(
+    id: &str,
+    domain: &str,
+    db: &PgPool,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<()> {
+    groups::details::require_authority(AuthorityInGroup::View, id, domain, db, perms, user).await?;
+
+    let role = groups::details::get_role_in_group(user.username(), id, domain, db).await?;
+    if role.is_none() {
+        // not entirely accurate, but close enough
+        return Err(AppError::InsufficientAuthorityInGroup(
+            AuthorityInGroup::View,
+        ));
+    }
+
+    Ok(())
+}
+
+// minimal iCalendar (RFC 5545) writer, supporting only all-day events
+struct ICalendar {
+    buf: String,
+    stamp: String,
+}
+
+impl ICalendar {
+    fn new() -> Self {
+        let mut calendar = Self {
+            buf: String::new(),
+            stamp: Utc::now().format("%Y%m%dT%H%M%SZ").to_string(),
+        };
+
+        calendar.line("BEGIN:VCALENDAR");
+        calendar.line("VERSION:2.0");
+        calendar.line("PRODID:-//Konglig Datasektionen//Hive//EN");
+        calendar.line("CALSCALE:GREGORIAN");
+        calendar.line("METHOD:PUBLISH");
+
+        calendar
+    }
+
+    fn name(&mut self, name: &str) {
+        self.line(&format!("X-WR-CALNAME:{}", escape_text(name)));
+    }
+
+    fn event(&mut self, uid: &str, date: NaiveDate, summary: &str) {
+        // DTEND is exclusive, so the next day for a single all-day event
+        let end = date.checked_add_days(Days::new(1)).unwrap_or(date);
+
+        self.line("BEGIN:VEVENT");
+        self.line(&format!("UID:{uid}@hive"));
+        self.line(&format!("DTSTAMP:{}", self.stamp));
+        self.line(&format!("DTSTART;VALUE=DATE:{}", date.format("%Y%m%d")));
+        self.line(&format!("DTEND;VALUE=DATE:{}", end.format("%Y%m%d")));
+        self.line(&format!("SUMMARY:{}", escape_text(summary)));
+        self.line("TRANSP:TRANSPARENT"); // don't show as busy
+        self.line("END:VEVENT");
+    }
+
+    fn finish(mut self) -> String {
+        self.line("END:VCALENDAR");
+
+        self.buf
+    }
+
+    // lines must be folded at 75 octets (not chars!) and end in CRLF
+    fn line(&mut self, content: &str) {
+        let mut width = 0;
+
+        for c in content.chars() {
+            if width + c.len_utf8() > 75 {
+                self.buf.push_str("\r\n ");
+                width = 1; // the leading space counts
+            }
+
+            self.buf.push(c);
+            width += c.len_utf8();
+        }
+
+        self.buf.push_str("\r\n");
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
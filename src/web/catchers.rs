@@ -4,11 +4,12 @@ use rocket::{
     response::{Redirect, content::RawHtml},
     uri,
 };
+use serde_json::json;
 
-use super::RenderedTemplate;
+use super::Either;
 use crate::{
     errors::render_error_page,
-    guards::{context::PageContext, headers::HxRequest},
+    guards::{context::PageContext, format::ResponseFormat, headers::HxRequest},
 };
 
 pub fn catchers() -> Vec<rocket::Catcher> {
@@ -17,8 +18,9 @@ pub fn catchers() -> Vec<rocket::Catcher> {
 
 #[derive(Responder)]
 pub enum Caught {
-    Partial(RenderedTemplate, Header<'static>),
-    Full(RenderedTemplate),
+    Partial(RawHtml<String>, Header<'static>),
+    Full(RawHtml<String>),
+    Json(serde_json::Value),
 }
 
 // note: an alternative implementation would be for catchers to simply return
@@ -33,6 +35,16 @@ macro_rules! show_error_page {
     ($name:ident, $num:expr, $status:expr, $i18n_key:expr) => {
         #[rocket::catch($num)]
         async fn $name(req: &Request<'_>) -> Caught {
+            if ResponseFormat::of(req).is_json() {
+                // same format as AppErrorDto when serialized
+                return Caught::Json(json!({
+                    "error": true,
+                    "info": {
+                        "key": concat!("caught.", $i18n_key)
+                    }
+                }));
+            }
+
             let ctx = req
                 .guard::<PageContext>()
                 .await
@@ -67,7 +79,18 @@ show_error_page!(
 show_error_page!(unknown, default, Status::InternalServerError, "unknown");
 
 #[rocket::catch(401)]
-fn unauthenticated(req: &Request<'_>) -> Redirect {
+fn unauthenticated(req: &Request<'_>) -> Either<Redirect, serde_json::Value> {
+    if ResponseFormat::of(req).is_json() {
+        // non-browser clients can't follow the login flow anyway
+        // (same format as AppErrorDto when serialized)
+        return Either::Right(json!({
+            "error": true,
+            "info": {
+                "key": "caught.unauthenticated"
+            }
+        }));
+    }
+
     let next = if req.method() == Method::Get {
         // ensure user is redirected to this page after logging in
         Some(req.uri().to_string())
@@ -75,5 +98,5 @@ fn unauthenticated(req: &Request<'_>) -> Redirect {
         None
     };
 
-    Redirect::to(uri!(super::auth::login(next)))
+    Either::Left(Redirect::to(uri!(super::auth::login(next))))
 }
@@ -0,0 +1,78 @@
+use rinja::Template;
+use rocket::State;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{GracefulRedirect, RenderedTemplate, filters, render};
+use crate::{
+    errors::AppResult,
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    models::{PendingChange, PendingChangeKind},
+    routing::RouteTree,
+    services::changes,
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![list_changes, approve_change, reject_change].into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "changes/list.html.j2")]
+struct ListChangesView {
+    ctx: PageContext,
+    changes: Vec<PendingChange>,
+}
+
+#[rocket::get("/changes")]
+async fn list_changes(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    let changes = changes::list_relevant(db.inner(), perms, &user).await?;
+
+    let template = ListChangesView { ctx, changes };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/change/<id>/approve")]
+async fn approve_change(
+    id: Uuid,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    let change = changes::require_one(&id, db.inner()).await?;
+    changes::require_authorized_for(&change, db.inner(), perms, &user).await?;
+
+    // TODO: anti-CSRF
+
+    changes::approve(&id, db.inner(), &user).await?;
+
+    Ok(GracefulRedirect::to("/changes", partial.is_some()))
+}
+
+#[rocket::delete("/change/<id>")]
+async fn reject_change(
+    id: Uuid,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    let change = changes::require_one(&id, db.inner()).await?;
+    if change.proposed_by != user.username() {
+        // anyone can withdraw their own proposals
+        changes::require_authorized_for(&change, db.inner(), perms, &user).await?;
+    }
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    changes::reject(&id, db.inner(), &user).await?;
+
+    Ok(GracefulRedirect::to("/changes", partial.is_some()))
+}
@@ -0,0 +1,126 @@
+use rinja::Template;
+use rocket::{State, response::Flash};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{GracefulRedirect, RenderedTemplate, filters, render};
+use crate::{
+    errors::AppResult,
+    guards::{
+        context::{PageContext, UNDO_FLASH_KIND},
+        headers::HxRequest,
+        perms::PermsEvaluator,
+        user::User,
+    },
+    models::{Deletion, TargetKind},
+    perms::HivePermission,
+    routing::RouteTree,
+    services::{changes::ProtectedDomains, deletions},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![undo_deletion, list_trash, restore_deletion].into()
+}
+
+// to be swapped out-of-band alongside partial responses, since the page isn't
+// reloaded (and the flash message shown) after e.g. removing a table row
+#[derive(Template, Serialize)]
+#[template(path = "deletions/undo-oob.html.j2")]
+pub struct PartialUndoView {
+    pub ctx: PageContext,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "deletions/trash.html.j2")]
+struct TrashView {
+    ctx: PageContext,
+    deletions: Vec<Deletion>,
+}
+
+// makes the undo button show up on the next rendered page
+pub fn undoable<R>(responder: R, deletion_id: Uuid) -> Flash<R> {
+    Flash::new(responder, UNDO_FLASH_KIND, deletion_id.to_string())
+}
+
+#[rocket::post("/deletion/<id>/undo")]
+async fn undo_deletion(
+    id: Uuid,
+    protected: &State<ProtectedDomains>,
+    db: &State<PgPool>,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    // no permission checks needed: only the deletion's actor can undo it, and
+    // they must have been authorized to do so when deleting
+
+    // TODO: anti-CSRF
+
+    let deletion = deletions::undo(&id, protected, db.inner(), &user).await?;
+
+    Ok(GracefulRedirect::to(
+        return_path(&deletion),
+        partial.is_some(),
+    ))
+}
+
+#[rocket::get("/trash")]
+async fn list_trash(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+) -> AppResult<RenderedTemplate> {
+    perms.require(HivePermission::RestoreDeletions).await?;
+
+    let deletions = deletions::list_restorable(db.inner()).await?;
+
+    let template = TrashView { ctx, deletions };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/trash/<id>/restore")]
+async fn restore_deletion(
+    id: Uuid,
+    protected: &State<ProtectedDomains>,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    perms.require(HivePermission::RestoreDeletions).await?;
+
+    // TODO: anti-CSRF
+
+    let deletion = deletions::restore(&id, protected, db.inner(), &user).await?;
+
+    Ok(GracefulRedirect::to(
+        return_path(&deletion),
+        partial.is_some(),
+    ))
+}
+
+// where the restored entity can be seen
+fn return_path(deletion: &Deletion) -> String {
+    let target = &deletion.target_id;
+
+    match deletion.target_kind {
+        TargetKind::Group | TargetKind::Membership => match target.split_once('@') {
+            Some((id, domain)) => format!("/group/{domain}/{id}"),
+            None => "/groups".to_owned(),
+        },
+        // skip the leading `$` or `#`
+        TargetKind::Permission | TargetKind::Tag => {
+            match target.get(1..).and_then(|t| t.split_once(':')) {
+                Some((system_id, _)) => format!("/system/{system_id}"),
+                None => "/systems".to_owned(),
+            }
+        }
+        TargetKind::TagAssignment => match target.get(1..).and_then(|t| t.split_once(':')) {
+            Some((system_id, tag_id)) => format!("/system/{system_id}/tag/{tag_id}"),
+            None => "/systems".to_owned(),
+        },
+        TargetKind::ApiToken => "/trash".to_owned(), // (no page of its own)
+        _ => "/".to_owned(),
+    }
+}
@@ -9,52 +9,84 @@ use rocket::{
     Responder, State, UriDisplayQuery,
     form::{self, Contextual, Form, FromFormField},
     http::Header,
-    response::{Redirect, content::RawHtml},
+    response::{Flash, Redirect},
     uri,
 };
+use serde::Serialize;
 use sqlx::PgPool;
 
-use super::{Either, GracefulRedirect, RenderedTemplate, filters};
+use super::{Either, GracefulRedirect, RenderedTemplate, deletions, filters, render};
 use crate::{
-    dto::groups::{CreateGroupDto, EditGroupDto},
+    dto::groups::{CreateGroupDto, EditGroupDto, RenameGroupDto, SetGroupStateDto},
     errors::{AppError, AppResult},
     guards::{
         context::PageContext, headers::HxRequest, lang::Language, perms::PermsEvaluator, user::User,
     },
     models::{
-        Group, GroupMember, Permission, PermissionAssignment, SimpleGroup, Subgroup, Tag,
-        TagAssignment,
+        Group, GroupMember, GroupState, PendingChange, Permission, PermissionAssignment,
+        SimpleGroup, Subgroup, Tag, TagAssignment,
     },
-    perms::{GroupsScope, HivePermission},
+    perms::{self, HivePermission},
+    resolver::IdentityResolver,
     routing::RouteTree,
-    services::groups::{
-        self, AuthorityInGroup, GroupMembershipKind, GroupRelevance, RoleInGroup,
-        list::GroupOverviewSummary,
+    services::{
+        ReadReplica,
+        changes::ProtectedDomains,
+        groups::{
+            self, AuthorityInGroup, GroupMembershipKind, GroupRelevance, RoleInGroup,
+            list::GroupOverviewSummary,
+            suggestions::{AutoLinkDomains, ParentSuggestion, SuggestionReason},
+        },
     },
 };
 
+mod acceptances;
+mod activity;
+mod comments;
+mod graph;
+mod invitations;
+mod links;
+mod managers;
 mod members;
 mod permissions;
+mod shares;
+mod statistics;
 mod tags;
+mod transfers;
+mod watchers;
 
 pub fn routes() -> RouteTree {
     RouteTree::Branch(vec![
         rocket::routes![
             list_groups,
             create_group,
+            suggest_parents,
             group_details,
             delete_group,
             edit_group,
+            rename_group,
+            set_group_state,
             group_info_tooltip
         ]
         .into(),
+        acceptances::routes(),
+        activity::routes(),
+        comments::routes(),
+        graph::routes(),
+        invitations::routes(),
+        links::routes(),
+        managers::routes(),
         members::routes(),
         permissions::routes(),
+        shares::routes(),
+        statistics::routes(),
         tags::routes(),
+        transfers::routes(),
+        watchers::routes(),
     ])
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/list.html.j2")]
 struct ListGroupsView<'r, 'f, 'v> {
     ctx: PageContext,
@@ -64,12 +96,15 @@ struct ListGroupsView<'r, 'f, 'v> {
     layout: ListGroupsLayout,
     domain_filter: Option<&'r str>,
     domains: Vec<String>,
+    show_inactive: bool,
     can_create: bool,
+    creatable_domains: Option<Vec<String>>, // None => any
+    #[serde(serialize_with = "super::serialize_form_errors")]
     create_form: &'f form::Context<'v>,
     create_modal_open: bool,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/list.html.j2", block = "inner_groups_listing")]
 struct PartialListGroupsView<'q> {
     ctx: PageContext,
@@ -78,27 +113,42 @@ struct PartialListGroupsView<'q> {
     layout: ListGroupsLayout,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/create.html.j2", block = "inner_create_form")]
 struct PartialCreateGroupView<'f, 'v> {
     ctx: PageContext,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     create_form: &'f form::Context<'v>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
+#[template(path = "groups/parent-suggestions.html.j2")]
+struct PartialParentSuggestionsView {
+    ctx: PageContext,
+    suggestions: Vec<ParentSuggestion>,
+}
+
+#[derive(Template, Serialize)]
 #[template(path = "groups/details.html.j2")]
 struct GroupDetailsView<'f, 'v> {
     ctx: PageContext,
     group: Group,
     relevance: GroupRelevance,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     add_subgroup_form: &'f form::Context<'v>,
     add_subgroup_success: Option<Subgroup>,
+    add_subgroup_proposal: Option<PendingChange>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     add_member_form: &'f form::Context<'v>,
     add_member_success: Option<GroupMember>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     assign_permission_form: &'f form::Context<'v>,
     assign_permission_success: Option<PermissionAssignment>,
+    assign_permission_proposal: Option<PendingChange>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     assign_tag_form: &'f form::Context<'v>,
     assign_tag_success: Option<TagAssignment>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     edit_form: &'f form::Context<'v>,
     edit_modal_open: bool,
     // for autocomplete
@@ -107,31 +157,35 @@ struct GroupDetailsView<'f, 'v> {
     assignable_tags: Vec<Tag>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/edit.html.j2", block = "inner_edit_form")]
 struct PartialEditGroupView<'f, 'v> {
     ctx: PageContext,
     group: Group,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     edit_form: &'f form::Context<'v>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/edited.html.j2")]
 struct GroupEditedView<'f, 'v> {
     ctx: PageContext,
     group: Group,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     edit_form: &'f form::Context<'v>,
     edit_modal_open: bool,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/info-tooltip.html.j2")]
 struct GroupInfoTooltipView {
     ctx: PageContext,
     group: SimpleGroup,
+    contacts: Option<String>, // comma-separated names, if any
 }
 
-#[derive(FromFormField, UriDisplayQuery, PartialEq, Eq, Default)]
+#[derive(FromFormField, UriDisplayQuery, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
 enum ListGroupsSort {
     #[default]
     Name,
@@ -203,7 +257,8 @@ impl ListGroupsSort {
     }
 }
 
-#[derive(FromFormField, UriDisplayQuery, PartialEq, Eq, Default)]
+#[derive(FromFormField, UriDisplayQuery, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
 enum ListGroupsLayout {
     #[default]
     Normal,
@@ -219,14 +274,15 @@ impl fmt::Display for ListGroupsLayout {
     }
 }
 
-#[rocket::get("/groups?<q>&<sort>&<layout>&<domain>")]
+#[rocket::get("/groups?<q>&<sort>&<layout>&<domain>&<inactive>")]
 #[allow(clippy::too_many_arguments)]
 async fn list_groups(
     q: Option<&str>,
     sort: Option<ListGroupsSort>,
     layout: Option<ListGroupsLayout>,
     domain: Option<&str>,
-    db: &State<PgPool>,
+    inactive: bool,
+    replica: &State<ReadReplica>,
     ctx: PageContext,
     perms: &PermsEvaluator,
     user: User,
@@ -237,7 +293,14 @@ async fn list_groups(
     let domain_lower = domain.map(str::to_lowercase);
     let domain = domain_lower.as_deref();
 
-    let mut summaries = groups::list::list_summaries(q, domain, db.inner(), perms, &user).await?;
+    let mut summaries =
+        groups::list::list_summaries(q, domain, replica.pool(), perms, &user).await?;
+
+    // dormant and archived groups are still there (e.g., to be linked to), just
+    // not listed unless explicitly asked for
+    if !inactive {
+        summaries.retain(|summary| summary.group.state.is_listed());
+    }
 
     // unstable is faster, and we should have no equal elements anyway
     summaries.sort_unstable_by(|a, b| sort.ordering(a, b, &ctx.lang));
@@ -254,7 +317,7 @@ async fn list_groups(
             layout,
         };
 
-        Ok(RawHtml(template.render()?))
+        render(&template, template.ctx.format)
     } else {
         if let Some(filter) = domain.map(str::to_owned) {
             // ensure current value can be shown to be selected
@@ -263,9 +326,10 @@ async fn list_groups(
             }
         }
 
-        let can_create = perms
-            .satisfies(HivePermission::ManageGroups(GroupsScope::AnyDomain))
-            .await?;
+        let creatable_domains = groups::management::get_creatable_domains(perms).await?;
+        let can_create = creatable_domains
+            .as_ref()
+            .is_none_or(|domains| !domains.is_empty());
 
         let template = ListGroupsView {
             ctx,
@@ -275,19 +339,51 @@ async fn list_groups(
             layout,
             domain_filter: domain,
             domains,
+            show_inactive: inactive,
             can_create,
+            creatable_domains,
             create_form: &form::Context::default(),
             create_modal_open: false,
         };
 
-        Ok(RawHtml(template.render()?))
+        render(&template, template.ctx.format)
     }
 }
 
+#[rocket::get("/groups/parent-suggestions?<id>&<domain>")]
+async fn suggest_parents(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    auto_link: &State<AutoLinkDomains>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    let id = id.trim();
+    let domain = domain.trim();
+
+    let can_create = perms
+        .satisfies_any_of(&HivePermission::create_groups_in(domain))
+        .await?;
+
+    let suggestions = if can_create && !id.is_empty() && !domain.is_empty() {
+        groups::suggestions::suggest_parents(id, domain, auto_link, db.inner(), perms, &user)
+            .await?
+    } else {
+        vec![]
+    };
+
+    let template = PartialParentSuggestionsView { ctx, suggestions };
+
+    render(&template, template.ctx.format)
+}
+
 #[rocket::post("/groups", data = "<form>")]
 async fn create_group<'v>(
     form: Form<Contextual<'v, CreateGroupDto<'v>>>,
     db: &State<PgPool>,
+    protected: &State<ProtectedDomains>,
     ctx: PageContext,
     perms: &PermsEvaluator,
     user: User,
@@ -300,13 +396,46 @@ async fn create_group<'v>(
     if let Some(dto) = &form.value {
         // validation passed
 
-        let min = HivePermission::ManageGroups(GroupsScope::Domain(dto.domain.to_string()));
-        perms.require(min).await?;
+        perms
+            .require_any_of(&HivePermission::create_groups_in(&dto.domain))
+            .await?;
+
+        // checked upfront, so that the group isn't created only to then fail
+        // to be linked where the user asked
+        for parent in &dto.parents {
+            perms::require_valid_nesting(parent.domain, &dto.id, &dto.domain)?;
+
+            groups::details::require_authority(
+                AuthorityInGroup::ManageMembers,
+                parent.id,
+                parent.domain,
+                db.inner(),
+                perms,
+                &user,
+            )
+            .await?;
+        }
+
+        groups::management::create(dto, protected, db.inner(), &user).await?;
 
-        groups::management::create(dto, db.inner(), &user).await?;
+        // $hive:create-groups alone doesn't allow seeing the group afterwards
+        let relevance =
+            groups::details::get_relevance(&dto.id, &dto.domain, db.inner(), perms, &user).await?;
+
+        let target = if relevance.is_some() {
+            uri!(group_details(id = *dto.id, domain = *dto.domain))
+        } else {
+            uri!(list_groups(
+                None::<&str>,
+                None::<ListGroupsSort>,
+                None::<ListGroupsLayout>,
+                Some(*dto.domain),
+                false
+            ))
+        };
 
         Ok(Either::Right(GracefulRedirect::to(
-            uri!(group_details(id = *dto.id, domain = *dto.domain)),
+            target,
             partial.is_some(),
         )))
     } else {
@@ -319,7 +448,7 @@ async fn create_group<'v>(
                 create_form: &form.context,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: the list route handler is pretty complex, so we really
             // shouldn't be replicating it here... but we also want to make sure
@@ -330,6 +459,7 @@ async fn create_group<'v>(
 
             let mut summaries =
                 groups::list::list_summaries(None, None, db.inner(), perms, &user).await?;
+            summaries.retain(|summary| summary.group.state.is_listed());
             // unstable is faster, and we should have no equal elements anyway
             summaries.sort_unstable_by(|a, b| sort.ordering(a, b, &ctx.lang));
 
@@ -337,9 +467,10 @@ async fn create_group<'v>(
             domains.sort();
             domains.dedup();
 
-            let can_create = perms
-                .satisfies(HivePermission::ManageGroups(GroupsScope::AnyDomain))
-                .await?;
+            let creatable_domains = groups::management::get_creatable_domains(perms).await?;
+            let can_create = creatable_domains
+                .as_ref()
+                .is_none_or(|domains| !domains.is_empty());
 
             let template = ListGroupsView {
                 ctx,
@@ -349,12 +480,14 @@ async fn create_group<'v>(
                 layout,
                 domain_filter: None,
                 domains,
+                show_inactive: false,
                 can_create,
+                creatable_domains,
                 create_form: &form.context,
                 create_modal_open: true,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         }
     }
 }
@@ -367,8 +500,24 @@ async fn group_details(
     ctx: PageContext,
     perms: &PermsEvaluator,
     user: User,
-) -> AppResult<RenderedTemplate> {
-    let group = groups::details::require_one(id, domain, db.inner()).await?;
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    let group: Option<Group> = groups::details::get_one(id, domain, db.inner()).await?;
+    let Some(group) = group else {
+        // maybe the group was renamed, in which case old links should still work
+        let not_found = || AppError::NoSuchGroup(id.to_owned(), domain.to_owned());
+
+        let (new_id, new_domain) = groups::details::resolve_alias(id, domain, db.inner())
+            .await?
+            .ok_or_else(not_found)?;
+
+        groups::details::get_relevance(&new_id, &new_domain, db.inner(), perms, &user)
+            .await?
+            .ok_or_else(not_found)?;
+        // ^ don't reveal the new key to whoever couldn't see the group anyway
+
+        let target = uri!(group_details(id = new_id, domain = new_domain));
+        return Ok(Either::Right(Redirect::to(target)));
+    };
 
     let relevance = groups::details::get_relevance(id, domain, db.inner(), perms, &user)
         .await?
@@ -388,10 +537,12 @@ async fn group_details(
         relevance,
         add_subgroup_form: &empty_form,
         add_subgroup_success: None,
+        add_subgroup_proposal: None,
         add_member_form: &empty_form,
         add_member_success: None,
         assign_permission_form: &empty_form,
         assign_permission_success: None,
+        assign_permission_proposal: None,
         assign_tag_form: &empty_form,
         assign_tag_success: None,
         edit_form: &empty_form,
@@ -401,7 +552,7 @@ async fn group_details(
         assignable_tags,
     };
 
-    Ok(RawHtml(template.render()?))
+    Ok(Either::Left(render(&template, template.ctx.format)?))
 }
 
 #[rocket::delete("/group/<domain>/<id>")]
@@ -412,7 +563,7 @@ pub async fn delete_group(
     perms: &PermsEvaluator,
     user: User,
     partial: Option<HxRequest<'_>>,
-) -> AppResult<GracefulRedirect> {
+) -> AppResult<Flash<GracefulRedirect>> {
     groups::details::require_authority(
         AuthorityInGroup::FullyAuthorized,
         id,
@@ -425,17 +576,19 @@ pub async fn delete_group(
 
     // TODO: anti-CSRF(?), DELETE isn't a normal form method
 
-    groups::management::delete(id, domain, db.inner(), &user).await?;
-
-    // TODO: show visual confirmation of successful delete in groups list
-    Ok(GracefulRedirect::to(
-        uri!(list_groups(
-            None::<&str>,
-            None::<ListGroupsSort>,
-            None::<ListGroupsLayout>,
-            None::<&str>
-        )),
-        partial.is_some(),
+    let deletion_id = groups::management::delete(id, domain, db.inner(), &user).await?;
+
+    let target = uri!(list_groups(
+        None::<&str>,
+        None::<ListGroupsSort>,
+        None::<ListGroupsLayout>,
+        None::<&str>,
+        false
+    ));
+
+    Ok(deletions::undoable(
+        GracefulRedirect::to(target, partial.is_some()),
+        deletion_id,
     ))
 }
 
@@ -476,22 +629,18 @@ pub async fn edit_group<'v>(
         groups::management::update(id, domain, dto, db.inner(), &user).await?;
 
         if partial.is_some() {
+            // (re-read, since not everything about the group is in the form)
+            let group = groups::details::require_one(id, domain, db.inner()).await?;
+
             let template = GroupEditedView {
                 ctx,
-                group: Group {
-                    id: id.to_owned(),
-                    domain: domain.to_owned(),
-                    name_sv: dto.name_sv.to_string(),
-                    name_en: dto.name_en.to_string(),
-                    description_sv: dto.description_sv.to_string(),
-                    description_en: dto.description_en.to_string(),
-                },
+                group,
                 edit_form: &form::Context::default(),
                 edit_modal_open: false,
             };
 
             Ok(EditGroupResponse::SuccessPartial(
-                RawHtml(template.render()?),
+                render(&template, template.ctx.format)?,
                 Header::new("HX-Retarget", "#edit-group"),
                 Header::new("HX-Reswap", "outerHTML"),
             ))
@@ -512,7 +661,10 @@ pub async fn edit_group<'v>(
                 edit_form: &form.context,
             };
 
-            Ok(EditGroupResponse::Invalid(RawHtml(template.render()?)))
+            Ok(EditGroupResponse::Invalid(render(
+                &template,
+                template.ctx.format,
+            )?))
         } else {
             let relevance = groups::details::get_relevance(id, domain, db.inner(), perms, &user)
                 .await?
@@ -533,10 +685,12 @@ pub async fn edit_group<'v>(
                 relevance,
                 add_subgroup_form: &empty_form,
                 add_subgroup_success: None,
+                add_subgroup_proposal: None,
                 add_member_form: &empty_form,
                 add_member_success: None,
                 assign_permission_form: &empty_form,
                 assign_permission_success: None,
+                assign_permission_proposal: None,
                 assign_tag_form: &empty_form,
                 assign_tag_success: None,
                 edit_form: &form.context,
@@ -546,22 +700,106 @@ pub async fn edit_group<'v>(
                 assignable_tags,
             };
 
-            Ok(EditGroupResponse::Invalid(RawHtml(template.render()?)))
+            Ok(EditGroupResponse::Invalid(render(
+                &template,
+                template.ctx.format,
+            )?))
         }
     }
 }
 
+#[rocket::post("/group/<domain>/<id>/rename", data = "<form>")]
+async fn rename_group<'v>(
+    id: &str,
+    domain: &str,
+    form: Form<Contextual<'v, RenameGroupDto<'v>>>,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    groups::details::require_authority(
+        AuthorityInGroup::FullyAuthorized,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    if let Some(dto) = &form.value {
+        // validation passed
+
+        if *dto.domain != domain {
+            // same as creating a group in the new domain
+            perms
+                .require_any_of(&HivePermission::create_groups_in(&dto.domain))
+                .await?;
+        }
+
+        groups::management::rename(id, domain, dto, db.inner(), &user).await?;
+
+        let target = uri!(group_details(id = *dto.id, domain = *dto.domain));
+        Ok(GracefulRedirect::to(target, partial.is_some()))
+    } else {
+        // some errors are present; reload page
+        debug!("Rename group form errors: {:?}", &form.context);
+
+        let target = uri!(group_details(id = id, domain = domain));
+        Ok(GracefulRedirect::to(target, partial.is_some()))
+    }
+}
+
+#[rocket::post("/group/<domain>/<id>/state", data = "<form>")]
+async fn set_group_state(
+    id: &str,
+    domain: &str,
+    form: Form<SetGroupStateDto>,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    groups::details::require_authority(
+        AuthorityInGroup::FullyAuthorized,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    groups::management::set_state(id, domain, &form, db.inner(), &user).await?;
+
+    info!(
+        "Group {id}@{domain} marked as {} by {}",
+        form.state,
+        user.username()
+    );
+
+    let target = uri!(group_details(id = id, domain = domain));
+    Ok(GracefulRedirect::to(target, partial.is_some()))
+}
+
 #[rocket::get("/group/<domain>/<id>/tooltip")]
+#[allow(clippy::too_many_arguments)]
 async fn group_info_tooltip(
     id: &str,
     domain: &str,
     db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
     ctx: PageContext,
     perms: &PermsEvaluator,
     user: User,
     partial: Option<HxRequest<'_>>,
 ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-    if partial.is_none() {
+    if partial.is_none() && !ctx.format.is_json() {
         // we only know how to render a tooltip, a tiny fragment, not a full
         // page - so redirect to group details
 
@@ -582,7 +820,18 @@ async fn group_info_tooltip(
     // no enumeration vuln because we already checked permissions
     let group = groups::details::require_one(id, domain, db.inner()).await?;
 
-    let template = GroupInfoTooltipView { ctx, group };
+    let contacts = groups::members::get_contacts(id, domain, db.inner(), resolver.as_ref())
+        .await?
+        .into_iter()
+        .map(|contact| contact.display_name.unwrap_or(contact.username))
+        .collect::<Vec<_>>();
+    let contacts = (!contacts.is_empty()).then(|| contacts.join(", "));
+
+    let template = GroupInfoTooltipView {
+        ctx,
+        group,
+        contacts,
+    };
 
-    Ok(Either::Left(RawHtml(template.render()?)))
+    Ok(Either::Left(render(&template, template.ctx.format)?))
 }
@@ -0,0 +1,87 @@
+use rocket::{State, response::Redirect, uri};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    errors::AppResult,
+    guards::{headers::HxRequest, perms::PermsEvaluator, user::User},
+    resolver::IdentityResolver,
+    routing::RouteTree,
+    services::groups::{self, AuthorityInGroup},
+    web::{Either, GracefulRedirect},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![
+        withdraw_pending_membership,
+        accept_pending_membership,
+        decline_pending_membership,
+    ]
+    .into()
+}
+
+#[rocket::delete("/group/<domain>/<id>/pending-member/<pending_id>")]
+async fn withdraw_pending_membership(
+    id: &str,
+    domain: &str,
+    pending_id: Uuid,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<(), Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    groups::acceptances::withdraw(&pending_id, id, domain, db.inner(), &user).await?;
+
+    if partial.is_some() {
+        Ok(Either::Left(()))
+    } else {
+        let target = uri!(super::group_details(id = id, domain = domain));
+        Ok(Either::Right(Redirect::to(target)))
+    }
+}
+
+// only the user the membership was proposed to can accept or decline it, which
+// is checked by the service (they have no authority in the group beforehand)
+
+#[rocket::post("/pending-membership/<pending_id>/accept")]
+async fn accept_pending_membership(
+    pending_id: Uuid,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    // TODO: anti-CSRF
+
+    groups::acceptances::accept(&pending_id, db.inner(), resolver.as_ref(), &user).await?;
+
+    let target = format!("/user/{}", user.username());
+    Ok(GracefulRedirect::to(target, partial.is_some()))
+}
+
+#[rocket::post("/pending-membership/<pending_id>/decline")]
+async fn decline_pending_membership(
+    pending_id: Uuid,
+    db: &State<PgPool>,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    // TODO: anti-CSRF
+
+    groups::acceptances::decline(&pending_id, db.inner(), &user).await?;
+
+    let target = format!("/user/{}", user.username());
+    Ok(GracefulRedirect::to(target, partial.is_some()))
+}
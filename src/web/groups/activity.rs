@@ -0,0 +1,79 @@
+use rinja::Template;
+use rocket::{State, response::Redirect, uri};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    errors::AppResult,
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    models::GroupActivity,
+    resolver::IdentityResolver,
+    routing::RouteTree,
+    services::{
+        audit_logs,
+        groups::{self, AuthorityInGroup},
+    },
+    web::{Either, RenderedTemplate, filters, render},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![group_activity].into()
+}
+
+// older entries can still be found via the full audit logs (for admins)
+const ACTIVITY_LIMIT: u32 = 50;
+
+#[derive(Template, Serialize)]
+#[template(path = "groups/activity.html.j2")]
+struct PartialActivityView {
+    ctx: PageContext,
+    activity: Vec<GroupActivity>,
+}
+
+#[rocket::get("/group/<domain>/<id>/activity")]
+#[allow(clippy::too_many_arguments)]
+async fn group_activity(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a fragment, not a full page;
+        // redirect to group details
+        return Ok(Either::Right(Redirect::to(uri!(super::group_details(
+            id = id,
+            domain = domain
+        )))));
+    }
+
+    // history includes past members and who did what, so it's only for those
+    // who could have made such changes themselves
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    let activity = audit_logs::list_recent_for_group(
+        id,
+        domain,
+        None,
+        ACTIVITY_LIMIT,
+        db.inner(),
+        resolver.as_ref(),
+    )
+    .await?;
+
+    let template = PartialActivityView { ctx, activity };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
@@ -0,0 +1,181 @@
+use log::*;
+use rinja::Template;
+use rocket::{
+    State,
+    form::{self, Contextual, Form},
+    response::Redirect,
+    uri,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    dto::groups::AddGroupCommentDto,
+    errors::AppResult,
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    models::GroupCommentThread,
+    routing::RouteTree,
+    services::groups::{self, AuthorityInGroup},
+    web::{Either, RenderedTemplate, filters, render},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![list_comments, add_comment, remove_comment].into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "groups/comments.html.j2")]
+struct PartialCommentsView<'f, 'v> {
+    ctx: PageContext,
+    group_id: &'f str,
+    group_domain: &'f str,
+    threads: Vec<GroupCommentThread>,
+    username: &'f str, // to tell which comments can be deleted
+    #[serde(serialize_with = "crate::web::serialize_form_errors")]
+    add_form: &'f form::Context<'v>,
+}
+
+async fn render_comments<'f, 'v>(
+    id: &'f str,
+    domain: &'f str,
+    add_form: form::Context<'v>,
+    db: &PgPool,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<RenderedTemplate> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db,
+        perms,
+        user,
+    )
+    .await?;
+
+    let threads = groups::comments::get_threads(id, domain, db).await?;
+
+    let template = PartialCommentsView {
+        ctx,
+        group_id: id,
+        group_domain: domain,
+        threads,
+        username: user.username(),
+        add_form: &add_form,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+// for when an action was performed without HTMX
+fn back_to_group(id: &str, domain: &str) -> Redirect {
+    Redirect::to(uri!(super::group_details(id = id, domain = domain)))
+}
+
+#[rocket::get("/group/<domain>/<id>/comments")]
+async fn list_comments(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a fragment, not a full page;
+        // redirect to group details
+        return Ok(Either::Right(back_to_group(id, domain)));
+    }
+
+    let empty_form = form::Context::default();
+    let template = render_comments(id, domain, empty_form, db.inner(), ctx, perms, &user).await?;
+
+    Ok(Either::Left(template))
+}
+
+#[rocket::post("/group/<domain>/<id>/comments", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn add_comment<'v>(
+    id: &str,
+    domain: &str,
+    form: Form<Contextual<'v, AddGroupCommentDto<'v>>>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    let empty_form = form::Context::default();
+    let add_form = if let Some(dto) = &form.value {
+        // validation passed
+
+        groups::comments::add(id, domain, dto, db.inner(), &user).await?;
+
+        empty_form
+    } else {
+        // some errors are present; show the form again
+        debug!("Add group comment form errors: {:?}", &form.context);
+
+        form.into_inner().context
+    };
+
+    if partial.is_some() {
+        let template = render_comments(id, domain, add_form, db.inner(), ctx, perms, &user).await?;
+
+        Ok(Either::Left(template))
+    } else {
+        Ok(Either::Right(back_to_group(id, domain)))
+    }
+}
+
+#[rocket::delete("/group/<domain>/<id>/comment/<comment_id>")]
+#[allow(clippy::too_many_arguments)]
+async fn remove_comment(
+    id: &str,
+    domain: &str,
+    comment_id: Uuid,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    groups::comments::remove(&comment_id, id, domain, db.inner(), &user).await?;
+
+    if partial.is_some() {
+        let empty_form = form::Context::default();
+        let template =
+            render_comments(id, domain, empty_form, db.inner(), ctx, perms, &user).await?;
+
+        Ok(Either::Left(template))
+    } else {
+        Ok(Either::Right(back_to_group(id, domain)))
+    }
+}
@@ -0,0 +1,118 @@
+use rinja::Template;
+use rocket::{
+    FromFormField, Responder, State,
+    http::{ContentType, Header},
+};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    errors::AppResult,
+    guards::{context::PageContext, user::User},
+    routing::RouteTree,
+    services::{
+        ReadReplica,
+        groups::graph::{self, GroupGraph},
+    },
+    web::{RenderedTemplate, render, require_admin},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![group_graph, export_group_graph].into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "groups/graph.html.j2")]
+struct GroupGraphView<'r> {
+    ctx: PageContext,
+    graph: GroupGraph,
+    domain_filter: Option<&'r str>,
+    domains: Vec<String>,
+}
+
+#[derive(FromFormField)]
+enum GraphFormat {
+    Dot,
+    #[field(value = "graphml")]
+    GraphMl,
+}
+
+#[derive(Responder)]
+struct GraphExport {
+    content: (ContentType, String),
+    disposition: Header<'static>,
+}
+
+// the whole structure, regardless of what anyone could otherwise see, so only
+// for administrators
+#[rocket::get("/groups/graph?<domain>")]
+async fn group_graph(
+    domain: Option<&str>,
+    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    require_admin(&user, db.inner()).await?;
+
+    let domain_lower = domain.filter(|d| !d.is_empty()).map(str::to_lowercase);
+    let domain = domain_lower.as_deref();
+
+    let graph = graph::get(domain, replica.pool()).await?;
+    let domains = graph::list_domains(replica.pool()).await?;
+
+    let template = GroupGraphView {
+        ctx,
+        graph,
+        domain_filter: domain,
+        domains,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::get("/groups/graph/export?<format>&<domain>")]
+async fn export_group_graph(
+    format: GraphFormat,
+    domain: Option<&str>,
+    db: &State<PgPool>,
+    replica: &State<ReadReplica>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<GraphExport> {
+    require_admin(&user, db.inner()).await?;
+
+    let domain_lower = domain.filter(|d| !d.is_empty()).map(str::to_lowercase);
+    let domain = domain_lower.as_deref();
+
+    let graph = graph::get(domain, replica.pool()).await?;
+
+    let (content_type, extension, content) = match format {
+        GraphFormat::Dot => (
+            ContentType::new("text", "vnd.graphviz"),
+            "dot",
+            graph.to_dot(&ctx.lang),
+        ),
+        GraphFormat::GraphMl => (
+            ContentType::new("application", "graphml+xml"),
+            "graphml",
+            graph.to_graphml(),
+        ),
+    };
+
+    // (the domain is only whatever was asked for, so not necessarily safe)
+    let suffix: String = domain
+        .unwrap_or("all")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '-')
+        .collect();
+    let filename = format!("groups-{suffix}.{extension}");
+
+    Ok(GraphExport {
+        content: (content_type, content),
+        disposition: Header::new(
+            "Content-Disposition",
+            format!("attachment; filename=\"{filename}\""),
+        ),
+    })
+}
@@ -0,0 +1,293 @@
+use chrono::NaiveDate;
+use log::*;
+use rinja::Template;
+use rocket::{
+    State,
+    form::{self, Contextual, Form},
+    response::Redirect,
+    uri,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    clock,
+    dto::groups::CreateInvitationDto,
+    errors::AppResult,
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    models::{Group, GroupInvitation},
+    resolver::IdentityResolver,
+    routing::RouteTree,
+    services::groups::{self, AuthorityInGroup, invitations},
+    web::{Either, RenderedTemplate, filters, render},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![
+        list_invitations,
+        create_invitation,
+        revoke_invitation,
+        show_invitation,
+        redeem_invitation
+    ]
+    .into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "groups/invitations.html.j2")]
+struct PartialInvitationsView<'f, 'v> {
+    ctx: PageContext,
+    group_id: &'f str,
+    group_domain: &'f str,
+    invitations: Vec<GroupInvitation>,
+    created: Option<Uuid>,
+    today: NaiveDate,
+    #[serde(serialize_with = "crate::web::serialize_form_errors")]
+    create_form: &'f form::Context<'v>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "groups/join.html.j2")]
+struct JoinGroupView {
+    ctx: PageContext,
+    group: Group,
+    invitation: GroupInvitation,
+    from: NaiveDate, // when they would actually join
+    is_direct_member: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn render_invitations<'f, 'v>(
+    id: &'f str,
+    domain: &'f str,
+    created: Option<Uuid>,
+    create_form: form::Context<'v>,
+    db: &PgPool,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<RenderedTemplate> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db,
+        perms,
+        user,
+    )
+    .await?;
+
+    let invitations = invitations::list_usable(id, domain, db).await?;
+
+    let template = PartialInvitationsView {
+        ctx,
+        group_id: id,
+        group_domain: domain,
+        invitations,
+        created,
+        today: clock::today(),
+        create_form: &create_form,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+// for when an action was performed without HTMX
+fn back_to_group(id: &str, domain: &str) -> Redirect {
+    Redirect::to(uri!(super::group_details(id = id, domain = domain)))
+}
+
+#[rocket::get("/group/<domain>/<id>/invitations")]
+async fn list_invitations(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a fragment, not a full page;
+        // redirect to group details
+        return Ok(Either::Right(back_to_group(id, domain)));
+    }
+
+    let empty_form = form::Context::default();
+    let template =
+        render_invitations(id, domain, None, empty_form, db.inner(), ctx, perms, &user).await?;
+
+    Ok(Either::Left(template))
+}
+
+#[rocket::post("/group/<domain>/<id>/invitations", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn create_invitation<'v>(
+    id: &str,
+    domain: &str,
+    mut form: Form<Contextual<'v, CreateInvitationDto>>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    // there is nobody to justify a long-term appointment for each user joining
+    // through the link, so only periods within the default bounds are allowed
+    if let Some(until) = form.value.as_ref().map(|dto| dto.until.0) {
+        let long_term =
+            groups::members::required_appointment_permission(&until, id, domain, db.inner())
+                .await?
+                .is_some();
+
+        if long_term {
+            let error = form::Error::validation("Too far in the future").with_name("until");
+            form.context.push_error(error);
+            form.value = None;
+        }
+    }
+
+    let empty_form = form::Context::default();
+    let (created, create_form) = if let Some(dto) = &form.value {
+        // validation passed
+
+        let invitation = invitations::create(id, domain, dto, db.inner(), &user).await?;
+
+        (Some(invitation.id), empty_form)
+    } else {
+        // some errors are present; show the form again
+        debug!("Create invitation form errors: {:?}", &form.context);
+
+        (None, form.into_inner().context)
+    };
+
+    if partial.is_some() {
+        let template = render_invitations(
+            id,
+            domain,
+            created,
+            create_form,
+            db.inner(),
+            ctx,
+            perms,
+            &user,
+        )
+        .await?;
+
+        Ok(Either::Left(template))
+    } else {
+        Ok(Either::Right(back_to_group(id, domain)))
+    }
+}
+
+#[rocket::delete("/group/<domain>/<id>/invitation/<invitation_id>")]
+#[allow(clippy::too_many_arguments)]
+async fn revoke_invitation(
+    id: &str,
+    domain: &str,
+    invitation_id: Uuid,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    invitations::revoke(&invitation_id, id, domain, db.inner(), &user).await?;
+
+    if partial.is_some() {
+        let empty_form = form::Context::default();
+        let template =
+            render_invitations(id, domain, None, empty_form, db.inner(), ctx, perms, &user).await?;
+
+        Ok(Either::Left(template))
+    } else {
+        Ok(Either::Right(back_to_group(id, domain)))
+    }
+}
+
+// anyone logged in may follow the link, regardless of their authority in the
+// group (404 for anything that can't be used, without revealing why)
+#[rocket::get("/join/<secret>")]
+async fn show_invitation(
+    secret: Uuid,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<Option<RenderedTemplate>> {
+    let Some(invitation) = invitations::get_usable(&secret, db.inner()).await? else {
+        return Ok(None);
+    };
+
+    let Some(group): Option<Group> =
+        groups::details::get_one(&invitation.group_id, &invitation.group_domain, db.inner())
+            .await?
+    else {
+        return Ok(None);
+    };
+
+    let is_direct_member =
+        groups::members::is_direct_member(user.username(), &group.id, &group.domain, db.inner())
+            .await?;
+
+    let template = JoinGroupView {
+        ctx,
+        from: invitation.from.max(clock::today()),
+        group,
+        invitation,
+        is_direct_member,
+    };
+
+    Ok(Some(render(&template, template.ctx.format)?))
+}
+
+#[rocket::post("/join/<secret>")]
+async fn redeem_invitation(
+    secret: Uuid,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+    user: User,
+) -> AppResult<Redirect> {
+    // TODO: anti-CSRF
+
+    let (invitation, added) =
+        invitations::redeem(&secret, db.inner(), resolver.as_ref(), &user).await?;
+
+    info!(
+        "User {} joined {} ({} – {}) through invitation {}",
+        user.username(),
+        invitation.group_key(),
+        added.from,
+        added.until,
+        invitation.id
+    );
+
+    Ok(back_to_group(
+        &invitation.group_id,
+        &invitation.group_domain,
+    ))
+}
@@ -0,0 +1,211 @@
+use log::*;
+use rinja::Template;
+use rocket::{
+    State,
+    form::{self, Contextual, Form},
+    response::Redirect,
+    uri,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    dto::groups::AddGroupLinkDto,
+    errors::AppResult,
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    models::GroupLink,
+    routing::RouteTree,
+    services::groups::{self, AuthorityInGroup},
+    web::{Either, RenderedTemplate, render},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![list_links, add_link, remove_link, move_link].into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "groups/links.html.j2")]
+struct PartialLinksView<'f, 'v> {
+    ctx: PageContext,
+    group_id: &'f str,
+    group_domain: &'f str,
+    links: Vec<GroupLink>,
+    can_manage: bool,
+    #[serde(serialize_with = "crate::web::serialize_form_errors")]
+    add_form: &'f form::Context<'v>,
+}
+
+async fn render_links<'f, 'v>(
+    id: &'f str,
+    domain: &'f str,
+    add_form: form::Context<'v>,
+    db: &PgPool,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<RenderedTemplate> {
+    let authority =
+        groups::details::require_authority(AuthorityInGroup::View, id, domain, db, perms, user)
+            .await?;
+
+    let links = groups::links::get_all(id, domain, db).await?;
+
+    let template = PartialLinksView {
+        ctx,
+        group_id: id,
+        group_domain: domain,
+        links,
+        can_manage: authority >= AuthorityInGroup::ManageMembers,
+        add_form: &add_form,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+// for when an action was performed without HTMX
+fn back_to_group(id: &str, domain: &str) -> Redirect {
+    Redirect::to(uri!(super::group_details(id = id, domain = domain)))
+}
+
+#[rocket::get("/group/<domain>/<id>/links")]
+async fn list_links(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a fragment, not a full page;
+        // redirect to group details
+        return Ok(Either::Right(back_to_group(id, domain)));
+    }
+
+    let empty_form = form::Context::default();
+    let template = render_links(id, domain, empty_form, db.inner(), ctx, perms, &user).await?;
+
+    Ok(Either::Left(template))
+}
+
+#[rocket::post("/group/<domain>/<id>/links", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn add_link<'v>(
+    id: &str,
+    domain: &str,
+    form: Form<Contextual<'v, AddGroupLinkDto<'v>>>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    let empty_form = form::Context::default();
+    let add_form = if let Some(dto) = &form.value {
+        // validation passed
+
+        groups::links::add(id, domain, dto, db.inner(), &user).await?;
+
+        empty_form
+    } else {
+        // some errors are present; show the form again
+        debug!("Add group link form errors: {:?}", &form.context);
+
+        form.into_inner().context
+    };
+
+    if partial.is_some() {
+        let template = render_links(id, domain, add_form, db.inner(), ctx, perms, &user).await?;
+
+        Ok(Either::Left(template))
+    } else {
+        Ok(Either::Right(back_to_group(id, domain)))
+    }
+}
+
+#[rocket::delete("/group/<domain>/<id>/link/<link_id>")]
+#[allow(clippy::too_many_arguments)]
+async fn remove_link(
+    id: &str,
+    domain: &str,
+    link_id: Uuid,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    groups::links::remove(&link_id, id, domain, db.inner(), &user).await?;
+
+    if partial.is_some() {
+        let empty_form = form::Context::default();
+        let template = render_links(id, domain, empty_form, db.inner(), ctx, perms, &user).await?;
+
+        Ok(Either::Left(template))
+    } else {
+        Ok(Either::Right(back_to_group(id, domain)))
+    }
+}
+
+#[rocket::post("/group/<domain>/<id>/link/<link_id>/move?<up>")]
+#[allow(clippy::too_many_arguments)]
+async fn move_link(
+    id: &str,
+    domain: &str,
+    link_id: Uuid,
+    up: bool,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    groups::links::shift(&link_id, id, domain, up, db.inner(), &user).await?;
+
+    if partial.is_some() {
+        let empty_form = form::Context::default();
+        let template = render_links(id, domain, empty_form, db.inner(), ctx, perms, &user).await?;
+
+        Ok(Either::Left(template))
+    } else {
+        Ok(Either::Right(back_to_group(id, domain)))
+    }
+}
@@ -0,0 +1,68 @@
+use rinja::Template;
+use rocket::{State, response::Redirect, uri};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    errors::AppResult,
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    resolver::IdentityResolver,
+    routing::RouteTree,
+    services::groups::{
+        self, AuthorityInGroup,
+        details::{AuthorityGrant, AuthoritySource},
+    },
+    web::{Either, RenderedTemplate, render},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![group_managers].into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "groups/managers.html.j2")]
+struct PartialManagersView {
+    ctx: PageContext,
+    grants: Vec<AuthorityGrant>,
+}
+
+#[rocket::get("/group/<domain>/<id>/managers")]
+#[allow(clippy::too_many_arguments)]
+async fn group_managers(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a fragment, not a full page;
+        // redirect to group details
+        return Ok(Either::Right(Redirect::to(uri!(super::group_details(
+            id = id,
+            domain = domain
+        )))));
+    }
+
+    // reveals which permissions are assigned where, so only for those who
+    // could (at least) manage members themselves
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    let grants =
+        groups::details::explain_authority(id, domain, db.inner(), resolver.as_ref()).await?;
+
+    let template = PartialManagersView { ctx, grants };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
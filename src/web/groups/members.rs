@@ -1,43 +1,73 @@
-use chrono::{Datelike, Local, NaiveDate};
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
 use log::*;
 use rinja::Template;
 use rocket::{
     Responder, State,
     form::{self, Contextual, Form},
-    http::Header,
-    response::{Redirect, content::RawHtml},
+    http::{Header, uri::Host},
+    response::{Flash, Redirect},
     uri,
 };
+use serde::Serialize;
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
-    dto::groups::{AddMemberDto, AddSubgroupDto, EditMemberDto},
+    clock,
+    dto::{
+        datetime::BrowserDateDto,
+        groups::{AddExclusionDto, AddMemberDto, AddSubgroupDto, BulkMembersDto, EditMemberDto},
+    },
     errors::{AppError, AppResult},
-    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
-    models::{GroupMember, GroupRef, SimpleGroup, Subgroup},
+    guards::{
+        context::PageContext, headers::HxRequest, perms::PermsEvaluator, scheme::RequestScheme,
+        user::User,
+    },
+    mailer::Mailer,
+    models::{
+        Group, GroupMember, GroupRef, MembershipExclusion, PendingChange, PendingMembership,
+        SimpleGroup, Subgroup,
+    },
     perms::{HivePermission, UpperBoundScope},
-    resolver::IdentityResolver,
+    resolver::{IdentityResolver, UserEmailDomain},
     routing::RouteTree,
-    services::groups::{self, AuthorityInGroup},
-    web::{Either, RenderedTemplate, groups::GroupDetailsView},
+    services::{
+        changes::{self, ProtectedDomains},
+        groups::{self, AuthorityInGroup},
+    },
+    web::{
+        Either, RenderedTemplate,
+        deletions::{self, PartialUndoView},
+        groups::GroupDetailsView,
+        render,
+    },
 };
 
 pub fn routes() -> RouteTree {
     rocket::routes![
         list_members,
+        list_contacts,
         add_subgroup,
         add_member,
         edit_member_form,
         edit_member,
         remove_subgroup,
         remove_member,
-        get_membership_details
+        bulk_remove_members,
+        bulk_extend_members,
+        bulk_toggle_manager,
+        bulk_toggle_contact,
+        add_exclusion,
+        remove_exclusion,
+        get_membership_details,
+        list_member_emails
     ]
     .into()
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/members/list.html.j2")]
 struct ListMembersView<'a> {
     ctx: PageContext,
@@ -45,24 +75,37 @@ struct ListMembersView<'a> {
     group_domain: &'a str,
     subgroups: Vec<Subgroup>,
     members: Vec<GroupMember>,
+    upcoming: Vec<GroupMember>, // future direct members, shown separately
+    pending: Vec<PendingMembership>, // not yet accepted, also shown separately
+    exclusions: Vec<MembershipExclusion>,
     show_indirect: bool,
+    at: Option<NaiveDate>, // None => today (incl. upcoming members if direct)
     can_manage: bool,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
+#[template(path = "groups/members/contacts.html.j2")]
+struct PartialContactsView {
+    ctx: PageContext,
+    contacts: Vec<GroupMember>,
+}
+
+#[derive(Template, Serialize)]
 #[template(
     path = "groups/members/add-subgroup.html.j2",
     block = "inner_add_subgroup_form"
 )]
 struct PartialAddSubgroupView<'f, 'v> {
     ctx: PageContext,
+    #[serde(serialize_with = "crate::web::serialize_form_errors")]
     add_subgroup_form: &'f form::Context<'v>,
     add_subgroup_success: Option<Subgroup>,
+    add_subgroup_proposal: Option<PendingChange>,
     group: SimpleGroup,
     permissible_groups: Vec<SimpleGroup>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(
     path = "groups/members/add-member.html.j2",
     block = "inner_add_member_form"
@@ -71,11 +114,15 @@ struct PartialAddMemberView<'r, 'f, 'v> {
     ctx: PageContext,
     group_id: &'r str,
     group_domain: &'r str,
+    #[serde(serialize_with = "crate::web::serialize_form_errors")]
     add_member_form: &'f form::Context<'v>,
     add_member_success: Option<GroupMember>,
+    add_member_pending: Option<PendingMembership>, // awaiting acceptance instead
+    add_member_cap_warning: Option<i32>,           // exceeded (non-enforced) member cap
+    add_member_default_until: Option<NaiveDate>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/members/member-details.html.j2")]
 struct PartialMembershipDetailsView<'r> {
     ctx: PageContext,
@@ -84,17 +131,18 @@ struct PartialMembershipDetailsView<'r> {
     is_direct_member: bool,    // false doesn't mean indirect! might be none
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/members/edit.html.j2")]
 struct MemberEditView<'r, 'f, 'v> {
     ctx: PageContext,
     member: GroupMember,
     group_id: &'r str,
     group_domain: &'r str,
+    #[serde(serialize_with = "crate::web::serialize_form_errors")]
     member_edit_form: &'f form::Context<'v>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/members/edited.html.j2")]
 struct MemberEditedView<'r> {
     ctx: PageContext,
@@ -106,6 +154,18 @@ struct MemberEditedView<'r> {
     is_future_member: bool,
 }
 
+#[derive(Template, Serialize)]
+#[template(path = "groups/members/emails.html.j2")]
+struct PartialMemberEmailsView<'r> {
+    ctx: PageContext,
+    group_id: &'r str,
+    group_domain: &'r str,
+    members: Vec<String>,
+    managers: Vec<String>,
+    derived: usize, // how many addresses are `username@domain` guesses
+    upcoming: bool,
+}
+
 #[derive(Responder)]
 pub enum EditMemberResponse {
     SuccessPartial(RenderedTemplate, Header<'static>, Header<'static>),
@@ -113,12 +173,13 @@ pub enum EditMemberResponse {
     Invalid(RenderedTemplate),
 }
 
-#[rocket::get("/group/<domain>/<id>/members?<show_indirect>")]
+#[rocket::get("/group/<domain>/<id>/members?<show_indirect>&<at>")]
 #[allow(clippy::too_many_arguments)]
 pub async fn list_members(
     id: &str,
     domain: &str,
     show_indirect: bool,
+    at: Option<BrowserDateDto>,
     db: &State<PgPool>,
     resolver: &State<Option<IdentityResolver>>,
     ctx: PageContext,
@@ -126,7 +187,7 @@ pub async fn list_members(
     user: User,
     partial: Option<HxRequest<'_>>,
 ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-    if partial.is_none() {
+    if partial.is_none() && !ctx.format.is_json() {
         // we only know how to render a table, not a full page;
         // redirect to group details
 
@@ -144,37 +205,131 @@ pub async fn list_members(
     )
     .await?;
 
-    let (subgroups, members) = if show_indirect {
+    let html = render_members_table(
+        id,
+        domain,
+        show_indirect,
+        at.map(|at| at.0),
+        authority >= AuthorityInGroup::ManageMembers,
+        ctx,
+        db.inner(),
+        resolver.as_ref(),
+    )
+    .await?;
+
+    Ok(Either::Left(html))
+}
+
+#[rocket::get("/group/<domain>/<id>/contacts")]
+#[allow(clippy::too_many_arguments)]
+async fn list_contacts(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a fragment, not a full page;
+        // redirect to group details
+
+        let target = uri!(super::group_details(id = id, domain = domain));
+        return Ok(Either::Right(Redirect::to(target)));
+    }
+
+    groups::details::require_authority(
+        AuthorityInGroup::View,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    let contacts = groups::members::get_contacts(id, domain, db.inner(), resolver.as_ref()).await?;
+
+    let template = PartialContactsView { ctx, contacts };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn render_members_table(
+    id: &str,
+    domain: &str,
+    show_indirect: bool,
+    at: Option<NaiveDate>,
+    can_manage: bool,
+    ctx: PageContext,
+    db: &PgPool,
+    resolver: Option<&IdentityResolver>,
+) -> AppResult<RenderedTemplate> {
+    let (subgroups, mut members, exclusions) = if show_indirect {
         (
             vec![],
-            groups::members::get_all_members(id, domain, db.inner(), resolver.as_ref()).await?,
+            groups::members::get_all_members(id, domain, at, db, resolver).await?,
+            vec![],
         )
     } else {
-        (
-            groups::members::get_direct_subgroups(id, domain, db.inner()).await?,
+        let members = if let Some(at) = at {
+            groups::members::get_direct_members_at(id, domain, at, db, resolver).await?
+        } else {
             groups::members::get_direct_members(
                 id,
                 domain,
                 true,
                 None::<chrono::Days>,
-                db.inner(),
-                resolver.as_ref(),
+                db,
+                resolver,
             )
-            .await?,
+            .await?
+        };
+
+        (
+            groups::members::get_direct_subgroups(id, domain, db).await?,
+            members,
+            groups::members::get_exclusions(id, domain, db, resolver).await?,
         )
     };
 
+    // only possible when listing direct members as of today; these can still
+    // be cancelled before they start, so they get a section of their own
+    let upcoming = if at.is_none() && !show_indirect {
+        let today = clock::today();
+        let (upcoming, current): (Vec<_>, Vec<_>) =
+            members.into_iter().partition(|m| m.from > today);
+        members = current;
+        upcoming
+    } else {
+        vec![]
+    };
+
+    // likewise, but only for those who could withdraw them
+    let pending = if at.is_none() && !show_indirect && can_manage {
+        groups::acceptances::list_for_group(id, domain, db, resolver).await?
+    } else {
+        vec![]
+    };
+
     let template = ListMembersView {
         ctx,
         group_id: id,
         group_domain: domain,
         subgroups,
         members,
+        upcoming,
+        pending,
+        exclusions,
         show_indirect,
-        can_manage: authority >= AuthorityInGroup::ManageMembers,
+        at,
+        can_manage,
     };
 
-    Ok(Either::Left(RawHtml(template.render()?)))
+    render(&template, template.ctx.format)
 }
 
 #[rocket::post("/group/<domain>/<id>/subgroups", data = "<form>")]
@@ -184,6 +339,7 @@ async fn add_subgroup<'v>(
     domain: &str,
     form: Form<Contextual<'v, AddSubgroupDto<'v>>>,
     db: &State<PgPool>,
+    protected: &State<ProtectedDomains>,
     ctx: PageContext,
     perms: &PermsEvaluator,
     user: User,
@@ -224,6 +380,26 @@ async fn add_subgroup<'v>(
         )
         .await?;
 
+        if protected.contains(domain) {
+            // needs to be approved by someone else first
+            let proposal = changes::propose_subgroup(id, domain, dto, db.inner(), &user).await?;
+
+            return if partial.is_some() {
+                let template = PartialAddSubgroupView {
+                    ctx,
+                    add_subgroup_form: &form::Context::default(),
+                    add_subgroup_success: None,
+                    add_subgroup_proposal: Some(proposal),
+                    group,
+                    permissible_groups,
+                };
+
+                Ok(Either::Left(render(&template, template.ctx.format)?))
+            } else {
+                Ok(Either::Right(Redirect::to(uri!("/changes"))))
+            };
+        }
+
         groups::members::add_subgroup(id, domain, dto, db.inner(), &user).await?;
 
         if partial.is_some() {
@@ -241,11 +417,12 @@ async fn add_subgroup<'v>(
                     manager: dto.manager,
                     group: added,
                 }),
+                add_subgroup_proposal: None,
                 group,
                 permissible_groups,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: maybe allow passing ?added_subgroup=id@domain
 
@@ -261,11 +438,12 @@ async fn add_subgroup<'v>(
                 ctx,
                 add_subgroup_form: &form.context,
                 add_subgroup_success: None,
+                add_subgroup_proposal: None,
                 group,
                 permissible_groups,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
             // any validation error indicators... but there isn't a great
@@ -277,6 +455,13 @@ async fn add_subgroup<'v>(
     }
 }
 
+// to prefill the add member form again (e.g., when adding several in a row)
+async fn get_default_until(id: &str, domain: &str, db: &PgPool) -> AppResult<Option<NaiveDate>> {
+    let group: Group = groups::details::require_one(id, domain, db).await?;
+
+    Ok(group.default_until(clock::today()))
+}
+
 #[rocket::post("/group/<domain>/<id>/members", data = "<form>")]
 #[allow(clippy::too_many_arguments)]
 async fn add_member<'v>(
@@ -285,6 +470,10 @@ async fn add_member<'v>(
     mut form: Form<Contextual<'v, AddMemberDto<'v>>>,
     db: &State<PgPool>,
     resolver: &State<Option<IdentityResolver>>,
+    mailer: &State<Option<Mailer>>,
+    email_domain: &State<UserEmailDomain>,
+    scheme: RequestScheme,
+    host: &Host<'_>,
     ctx: PageContext,
     perms: &PermsEvaluator,
     user: User,
@@ -315,23 +504,115 @@ async fn add_member<'v>(
         }
     }
 
+    // long-term appointments must be justified (bounds were checked above)
+    let unjustified = form
+        .value
+        .as_ref()
+        .filter(|dto| dto.justification.is_none());
+    if let Some(until) = unjustified.map(|dto| dto.until.0) {
+        let long_term =
+            groups::members::required_appointment_permission(&until, id, domain, db.inner())
+                .await?
+                .is_some();
+
+        if long_term {
+            let error = form::Error::validation("Missing justification").with_name("justification");
+            form.context.push_error(error);
+            form.value = None;
+        }
+    }
+
     if let Some(dto) = &form.value {
         // validation passed
 
-        let added =
-            groups::members::add_member(id, domain, dto, db.inner(), resolver.as_ref(), &user)
-                .await?;
+        let requires_acceptance = groups::acceptances::is_required(
+            id,
+            domain,
+            &dto.username,
+            user.username(),
+            db.inner(),
+        )
+        .await?;
+
+        if requires_acceptance {
+            let pending =
+                groups::acceptances::propose(id, domain, dto, db.inner(), user.username()).await?;
+
+            if let Some(mailer) = mailer.inner() {
+                // it can still be found on their profile page if this fails
+                let base_url = format!("{scheme}://{host}");
+                if let Err(e) = groups::acceptances::send_email(
+                    &pending,
+                    &base_url,
+                    mailer,
+                    email_domain,
+                    db.inner(),
+                )
+                .await
+                {
+                    warn!(
+                        "Failed to email {} about their pending membership in {id}@{domain}: {e}",
+                        pending.username
+                    );
+                }
+            }
+
+            if partial.is_some() {
+                let default_until = get_default_until(id, domain, db.inner()).await?;
+
+                let template = PartialAddMemberView {
+                    ctx,
+                    group_id: id,
+                    group_domain: domain,
+                    add_member_form: &form::Context::default(),
+                    add_member_success: None,
+                    add_member_pending: Some(pending),
+                    add_member_cap_warning: None,
+                    add_member_default_until: default_until,
+                };
+
+                return Ok(Either::Left(render(&template, template.ctx.format)?));
+            } else {
+                let target = uri!(super::group_details(id = id, domain = domain));
+                return Ok(Either::Right(Redirect::to(target)));
+            }
+        }
+
+        let added = groups::members::add_member(
+            id,
+            domain,
+            dto,
+            db.inner(),
+            resolver.as_ref(),
+            user.username(),
+        )
+        .await?;
 
         if partial.is_some() {
+            // enforced caps would have failed above, so this can only warn
+            let exceeded = groups::members::get_exceeded_member_cap(
+                id,
+                domain,
+                &dto.from.0,
+                &dto.until.0,
+                db.inner(),
+            )
+            .await?;
+
+            let default_until = get_default_until(id, domain, db.inner()).await?;
+
             let template = PartialAddMemberView {
                 ctx,
                 group_id: id,
                 group_domain: domain,
                 add_member_form: &form::Context::default(),
                 add_member_success: Some(added),
+                add_member_pending: None,
+                add_member_cap_warning: exceeded.map(|(cap, _)| cap),
+                add_member_default_until: default_until,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: maybe allow passing ?added_member=id@domain
 
@@ -343,15 +624,20 @@ async fn add_member<'v>(
         debug!("Add member form errors: {:?}", &form.context);
 
         if partial.is_some() {
+            let default_until = get_default_until(id, domain, db.inner()).await?;
+
             let template = PartialAddMemberView {
                 ctx,
                 group_id: id,
                 group_domain: domain,
                 add_member_form: &form.context,
                 add_member_success: None,
+                add_member_pending: None,
+                add_member_cap_warning: None,
+                add_member_default_until: default_until,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
             // any validation error indicators... but there isn't a great
@@ -419,7 +705,7 @@ async fn edit_member_form<'v>(
         .await?
         .ok_or(AppError::NoSuchMembership(id.to_string()))?;
 
-    if partial.is_none() {
+    if partial.is_none() && !ctx.format.is_json() {
         // we only know how to render a form, not a full page;
         // redirect to group details
 
@@ -447,7 +733,7 @@ async fn edit_member_form<'v>(
         member_edit_form: &form::Context::default(),
     };
 
-    Ok(Either::Left(RawHtml(template.render()?)))
+    Ok(Either::Left(render(&template, template.ctx.format)?))
 }
 
 #[rocket::patch("/group-membership/<id>?<show_indirect>", data = "<form>")]
@@ -455,7 +741,7 @@ async fn edit_member_form<'v>(
 async fn edit_member<'v>(
     id: Uuid,
     show_indirect: bool,
-    mut form: Form<Contextual<'v, EditMemberDto>>,
+    mut form: Form<Contextual<'v, EditMemberDto<'v>>>,
     db: &State<PgPool>,
     resolver: &State<Option<IdentityResolver>>,
     ctx: PageContext,
@@ -495,6 +781,28 @@ async fn edit_member<'v>(
         }
     }
 
+    // long-term appointments must be justified (bounds were checked above)
+    let unjustified = form
+        .value
+        .as_ref()
+        .filter(|dto| dto.justification.is_none());
+    if let Some(until) = unjustified.map(|dto| dto.until.0) {
+        let long_term = groups::members::required_appointment_permission(
+            &until,
+            &group_id,
+            &group_domain,
+            db.inner(),
+        )
+        .await?
+        .is_some();
+
+        if long_term {
+            let error = form::Error::validation("Missing justification").with_name("justification");
+            form.context.push_error(error);
+            form.value = None;
+        }
+    }
+
     if let Some(dto) = &form.value {
         groups::members::update(&id, dto, &group_id, &group_domain, db.inner(), &user).await?;
 
@@ -505,7 +813,7 @@ async fn edit_member<'v>(
                 changed.display_name = resolver.resolve_one(&changed.username).await?;
             }
 
-            let is_future_member = changed.from > Local::now().date_naive();
+            let is_future_member = changed.from > clock::today();
 
             let template = MemberEditedView {
                 ctx,
@@ -518,7 +826,7 @@ async fn edit_member<'v>(
             };
 
             Ok(EditMemberResponse::SuccessPartial(
-                RawHtml(template.render()?),
+                render(&template, template.ctx.format)?,
                 Header::new("HX-Retarget", format!("#member-{}", id)),
                 Header::new("HX-Reswap", "outerHTML"),
             ))
@@ -541,7 +849,10 @@ async fn edit_member<'v>(
                 member_edit_form: &form.context,
             };
 
-            return Ok(EditMemberResponse::Invalid(RawHtml(template.render()?)));
+            return Ok(EditMemberResponse::Invalid(render(
+                &template,
+                template.ctx.format,
+            )?));
         } else {
             let group = groups::details::require_one(&group_id, &group_domain, db.inner()).await?;
 
@@ -566,10 +877,12 @@ async fn edit_member<'v>(
                 relevance,
                 add_subgroup_form: &empty_form,
                 add_subgroup_success: None,
+                add_subgroup_proposal: None,
                 add_member_form: &empty_form,
                 add_member_success: None,
                 assign_permission_form: &empty_form,
                 assign_permission_success: None,
+                assign_permission_proposal: None,
                 assign_tag_form: &empty_form,
                 assign_tag_success: None,
                 edit_form: &empty_form,
@@ -579,7 +892,10 @@ async fn edit_member<'v>(
                 assignable_tags,
             };
 
-            return Ok(EditMemberResponse::Invalid(RawHtml(template.render()?)));
+            return Ok(EditMemberResponse::Invalid(render(
+                &template,
+                template.ctx.format,
+            )?));
         }
     }
 }
@@ -588,10 +904,11 @@ async fn edit_member<'v>(
 async fn remove_member<'v>(
     id: Uuid,
     db: &State<PgPool>,
+    mut ctx: PageContext,
     perms: &PermsEvaluator,
     user: User,
     partial: Option<HxRequest<'_>>,
-) -> AppResult<Either<(), Redirect>> {
+) -> AppResult<Either<RenderedTemplate, Flash<Redirect>>> {
     // cannot check perms yet, with only this information
 
     // TODO: anti-CSRF(?), DELETE isn't a normal form method
@@ -612,19 +929,270 @@ async fn remove_member<'v>(
     )
     .await?;
 
-    groups::members::remove_member(
+    let deletion_id = groups::members::remove_member(
         &id,
         group_id.as_str(),
         group_domain.as_str(),
         db.inner(),
+        user.username(),
+    )
+    .await?
+    .ok_or_else(|| AppError::NoSuchMembership(id.to_string()))?;
+    // ^ only if it was removed concurrently, since we just found its group
+
+    if partial.is_some() {
+        ctx.undo = Some(deletion_id);
+
+        let template = PartialUndoView { ctx };
+
+        Ok(Either::Left(render(&template, template.ctx.format)?))
+    } else {
+        let target = uri!(super::group_details(id = group_id, domain = group_domain));
+        Ok(Either::Right(deletions::undoable(
+            Redirect::to(target),
+            deletion_id,
+        )))
+    }
+}
+
+#[rocket::post("/group/<domain>/<id>/members/bulk-remove", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn bulk_remove_members(
+    id: &str,
+    domain: &str,
+    form: Form<BulkMembersDto>,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+    mut ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Flash<Redirect>>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    let deletion_id = groups::members::bulk_remove(&form.selected, id, domain, db.inner(), &user)
+        .await?
+        .ok_or_else(|| AppError::NoSuchMembership(form.selected[0].to_string()))?;
+    // ^ none of the selected memberships were (still) in this group
+
+    if partial.is_some() {
+        // the list template also includes the undo toast (out-of-band)
+        ctx.undo = Some(deletion_id);
+
+        let table = render_members_table(
+            id,
+            domain,
+            false,
+            form.at.map(|at| at.0),
+            true,
+            ctx,
+            db.inner(),
+            resolver.as_ref(),
+        )
+        .await?;
+
+        Ok(Either::Left(table))
+    } else {
+        let target = uri!(super::group_details(id = id, domain = domain));
+        Ok(Either::Right(deletions::undoable(
+            Redirect::to(target),
+            deletion_id,
+        )))
+    }
+}
+
+#[rocket::post("/group/<domain>/<id>/members/bulk-extend", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn bulk_extend_members(
+    id: &str,
+    domain: &str,
+    form: Form<BulkMembersDto>,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    groups::members::bulk_extend(&form.selected, id, domain, perms, db.inner(), &user).await?;
+
+    bulk_members_response(id, domain, &form, db, resolver, ctx, partial).await
+}
+
+#[rocket::post("/group/<domain>/<id>/members/bulk-toggle-manager", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn bulk_toggle_manager(
+    id: &str,
+    domain: &str,
+    form: Form<BulkMembersDto>,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    groups::members::bulk_toggle_manager(&form.selected, id, domain, db.inner(), &user).await?;
+
+    bulk_members_response(id, domain, &form, db, resolver, ctx, partial).await
+}
+
+#[rocket::post("/group/<domain>/<id>/members/bulk-toggle-contact", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn bulk_toggle_contact(
+    id: &str,
+    domain: &str,
+    form: Form<BulkMembersDto>,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    groups::members::bulk_toggle_contact(&form.selected, id, domain, db.inner(), &user).await?;
+
+    bulk_members_response(id, domain, &form, db, resolver, ctx, partial).await
+}
+
+// bulk actions are only available in the direct members table, which is
+// re-rendered as a whole since any number of rows may have changed
+async fn bulk_members_response(
+    id: &str,
+    domain: &str,
+    form: &BulkMembersDto,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+    ctx: PageContext,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_some() {
+        let table = render_members_table(
+            id,
+            domain,
+            false,
+            form.at.map(|at| at.0),
+            true,
+            ctx,
+            db.inner(),
+            resolver.as_ref(),
+        )
+        .await?;
+
+        Ok(Either::Left(table))
+    } else {
+        let target = uri!(super::group_details(id = id, domain = domain));
+        Ok(Either::Right(Redirect::to(target)))
+    }
+}
+
+#[rocket::post("/group/<domain>/<id>/exclusions", data = "<form>")]
+async fn add_exclusion<'v>(
+    id: &str,
+    domain: &str,
+    form: Form<AddExclusionDto<'v>>,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<(), Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
         &user,
     )
     .await?;
 
+    // TODO: anti-CSRF
+
+    groups::members::add_exclusion(*form.username, id, domain, db.inner(), &user).await?;
+
     if partial.is_some() {
+        // the (indirect) member's row is simply removed from the table
         Ok(Either::Left(()))
     } else {
-        let target = uri!(super::group_details(id = group_id, domain = group_domain));
+        let target = uri!(super::group_details(id = id, domain = domain));
+        Ok(Either::Right(Redirect::to(target)))
+    }
+}
+
+#[rocket::delete("/group/<domain>/<id>/exclusion/<username>")]
+async fn remove_exclusion(
+    id: &str,
+    domain: &str,
+    username: &str,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<(), Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    groups::members::remove_exclusion(username, id, domain, db.inner(), &user).await?;
+
+    if partial.is_some() {
+        Ok(Either::Left(()))
+    } else {
+        let target = uri!(super::group_details(id = id, domain = domain));
         Ok(Either::Right(Redirect::to(target)))
     }
 }
@@ -641,7 +1209,7 @@ pub async fn get_membership_details(
     user: User,
     partial: Option<HxRequest<'_>>,
 ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-    if partial.is_none() {
+    if partial.is_none() && !ctx.format.is_json() {
         // we only know how to render a table, not a full page;
         // redirect to group details
 
@@ -681,5 +1249,99 @@ pub async fn get_membership_details(
         is_direct_member,
     };
 
-    Ok(Either::Left(RawHtml(template.render()?)))
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
+
+#[rocket::get("/group/<domain>/<id>/emails?<upcoming>")]
+#[allow(clippy::too_many_arguments)]
+async fn list_member_emails(
+    id: &str,
+    domain: &str,
+    upcoming: bool, // also include direct members who haven't started yet
+    db: &State<PgPool>,
+    email_domain: &State<UserEmailDomain>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    // same requirement as for listing members, since the addresses shown to
+    // viewers are trivially derived from their usernames anyway
+    #[cfg_attr(not(feature = "integrations"), allow(unused_variables))]
+    let authority = groups::details::require_authority(
+        AuthorityInGroup::View,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    let mut all = groups::members::get_all_members(id, domain, None, db.inner(), None).await?;
+
+    if upcoming {
+        let today = clock::today();
+        let direct = groups::members::get_direct_members(
+            id,
+            domain,
+            true,
+            None::<chrono::Days>,
+            db.inner(),
+            None,
+        )
+        .await?;
+
+        for member in direct {
+            // might already be a current (e.g., indirect) member as well
+            if member.from > today && !all.iter().any(|m| m.username == member.username) {
+                all.push(member);
+            }
+        }
+    }
+
+    // personal addresses are private, so only those who manage the members get
+    // them; everyone else only sees derived ones
+    #[cfg(feature = "integrations")]
+    let verified = if authority >= AuthorityInGroup::ManageMembers {
+        use crate::services::integrations;
+
+        let usernames: Vec<&str> = all.iter().map(|member| member.username.as_str()).collect();
+
+        integrations::get_verified_emails(&usernames, db.inner()).await?
+    } else {
+        HashMap::new()
+    };
+    #[cfg(not(feature = "integrations"))]
+    let verified: HashMap<String, String> = HashMap::new();
+
+    // falls back to the institutional address, which might not actually exist
+    let email_of = |member: &GroupMember| {
+        verified
+            .get(&member.username)
+            .cloned()
+            .unwrap_or_else(|| email_domain.email_of(&member.username))
+    };
+
+    let managers = all
+        .iter()
+        .filter(|member| member.manager)
+        .map(email_of)
+        .collect();
+    let members = all.iter().map(email_of).collect();
+    let derived = all
+        .iter()
+        .filter(|member| !verified.contains_key(&member.username))
+        .count();
+
+    let template = PartialMemberEmailsView {
+        ctx,
+        group_id: id,
+        group_domain: domain,
+        members,
+        managers,
+        derived,
+        upcoming,
+    };
+
+    render(&template, template.ctx.format)
 }
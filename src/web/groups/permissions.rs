@@ -3,27 +3,31 @@ use rinja::Template;
 use rocket::{
     State,
     form::{self, Contextual, Form},
-    response::{Redirect, content::RawHtml},
+    response::Redirect,
     uri,
 };
+use serde::Serialize;
 use sqlx::PgPool;
 
 use crate::{
     dto::permissions::AssignPermissionDto,
     errors::AppResult,
     guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
-    models::{Permission, PermissionAssignment, SimpleGroup},
+    models::{GroupRef, PendingChange, Permission, PermissionAssignment, SimpleGroup},
     perms::{HivePermission, SystemsScope},
     routing::RouteTree,
-    services::groups::{self, AuthorityInGroup},
-    web::{Either, RenderedTemplate},
+    services::{
+        changes::{self, ProtectedDomains},
+        groups::{self, AuthorityInGroup},
+    },
+    web::{Either, RenderedTemplate, render},
 };
 
 pub fn routes() -> RouteTree {
     rocket::routes![list_permission_assignments, assign_permission].into()
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/permissions/list.html.j2")]
 struct ListPermissionAssignmentsView {
     ctx: PageContext,
@@ -31,7 +35,15 @@ struct ListPermissionAssignmentsView {
     can_manage_any: bool,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
+#[template(path = "groups/permissions/effective.html.j2")]
+struct ListEffectivePermissionsView {
+    ctx: PageContext,
+    effective_assignments: Vec<(PermissionAssignment, Vec<Vec<GroupRef>>)>,
+    can_manage_any: bool, // always false, but needed for row-cells
+}
+
+#[derive(Template, Serialize)]
 #[template(
     path = "groups/permissions/assign.html.j2",
     block = "inner_assign_permission_form"
@@ -40,21 +52,25 @@ struct PartialAssignPermissionView<'f, 'v> {
     ctx: PageContext,
     group: SimpleGroup,
     assignable_permissions: Vec<Permission>,
+    #[serde(serialize_with = "crate::web::serialize_form_errors")]
     assign_permission_form: &'f form::Context<'v>,
     assign_permission_success: Option<PermissionAssignment>,
+    assign_permission_proposal: Option<PendingChange>,
 }
 
-#[rocket::get("/group/<domain>/<id>/permissions")]
+#[rocket::get("/group/<domain>/<id>/permissions?<show_inherited>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn list_permission_assignments(
     id: &str,
     domain: &str,
+    show_inherited: bool,
     db: &State<PgPool>,
     ctx: PageContext,
     perms: &PermsEvaluator,
     user: User,
     partial: Option<HxRequest<'_>>,
 ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-    if partial.is_none() {
+    if partial.is_none() && !ctx.format.is_json() {
         // we only know how to render a table, not a full page;
         // redirect to group details
 
@@ -72,6 +88,19 @@ pub async fn list_permission_assignments(
     )
     .await?;
 
+    if show_inherited {
+        let effective_assignments =
+            groups::permissions::get_effective_assignments(id, domain, db.inner()).await?;
+
+        let template = ListEffectivePermissionsView {
+            ctx,
+            effective_assignments,
+            can_manage_any: false,
+        };
+
+        return Ok(Either::Left(render(&template, template.ctx.format)?));
+    }
+
     let permission_assignments =
         groups::permissions::get_all_assignments(id, domain, db.inner(), perms).await?;
 
@@ -87,7 +116,7 @@ pub async fn list_permission_assignments(
         can_manage_any,
     };
 
-    Ok(Either::Left(RawHtml(template.render()?)))
+    Ok(Either::Left(render(&template, template.ctx.format)?))
 }
 
 #[rocket::post("/group/<domain>/<id>/permissions", data = "<form>")]
@@ -97,6 +126,7 @@ pub async fn assign_permission<'v>(
     domain: &str,
     form: Form<Contextual<'v, AssignPermissionDto<'v>>>,
     db: &State<PgPool>,
+    protected: &State<ProtectedDomains>,
     ctx: PageContext,
     perms: &PermsEvaluator,
     user: User,
@@ -112,6 +142,26 @@ pub async fn assign_permission<'v>(
         let min = HivePermission::AssignPerms(SystemsScope::Id(dto.perm.system_id.to_owned()));
         perms.require(min).await?;
 
+        if protected.contains(domain) {
+            // needs to be approved by someone else first
+            let proposal = changes::propose_permission(id, domain, dto, db.inner(), &user).await?;
+
+            return if partial.is_some() {
+                let template = PartialAssignPermissionView {
+                    ctx,
+                    assign_permission_form: &form::Context::default(),
+                    assign_permission_success: None,
+                    assign_permission_proposal: Some(proposal),
+                    group,
+                    assignable_permissions,
+                };
+
+                Ok(Either::Left(render(&template, template.ctx.format)?))
+            } else {
+                Ok(Either::Right(Redirect::to(uri!("/changes"))))
+            };
+        }
+
         let assignment = groups::permissions::assign(id, domain, dto, db.inner(), &user).await?;
 
         if partial.is_some() {
@@ -119,11 +169,12 @@ pub async fn assign_permission<'v>(
                 ctx,
                 assign_permission_form: &form::Context::default(),
                 assign_permission_success: Some(assignment),
+                assign_permission_proposal: None,
                 group,
                 assignable_permissions,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: maybe allow passing ?added_permission=$key
 
@@ -139,11 +190,12 @@ pub async fn assign_permission<'v>(
                 ctx,
                 assign_permission_form: &form.context,
                 assign_permission_success: None,
+                assign_permission_proposal: None,
                 group,
                 assignable_permissions,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
             // any validation error indicators... but there isn't a great
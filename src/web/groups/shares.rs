@@ -0,0 +1,232 @@
+use log::*;
+use rinja::Template;
+use rocket::{
+    State,
+    form::{self, Contextual, Form},
+    response::Redirect,
+    uri,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    dto::groups::ShareMemberListDto,
+    errors::AppResult,
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    models::MemberListShare,
+    resolver::IdentityResolver,
+    routing::RouteTree,
+    services::groups::{
+        self, AuthorityInGroup,
+        shares::{self, ShareLinkSigner},
+    },
+    web::{Either, RenderedTemplate, filters, render},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![list_shares, create_share, revoke_share].into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "groups/shares.html.j2")]
+struct PartialSharesView<'f, 'v> {
+    ctx: PageContext,
+    group_id: &'f str,
+    group_domain: &'f str,
+    shares: Vec<(MemberListShare, String)>, // with link
+    created: Option<Uuid>,
+    #[serde(serialize_with = "crate::web::serialize_form_errors")]
+    create_form: &'f form::Context<'v>,
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn render_shares<'f, 'v>(
+    id: &'f str,
+    domain: &'f str,
+    created: Option<Uuid>,
+    create_form: form::Context<'v>,
+    db: &PgPool,
+    signer: &ShareLinkSigner,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<RenderedTemplate> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db,
+        perms,
+        user,
+    )
+    .await?;
+
+    let shares = shares::list_active(id, domain, db)
+        .await?
+        .into_iter()
+        .map(|share| {
+            let link = signer.link(&share);
+            (share, link)
+        })
+        .collect();
+
+    let template = PartialSharesView {
+        ctx,
+        group_id: id,
+        group_domain: domain,
+        shares,
+        created,
+        create_form: &create_form,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+// for when an action was performed without HTMX
+fn back_to_group(id: &str, domain: &str) -> Redirect {
+    Redirect::to(uri!(super::group_details(id = id, domain = domain)))
+}
+
+#[rocket::get("/group/<domain>/<id>/shares")]
+#[allow(clippy::too_many_arguments)]
+async fn list_shares(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    signer: &State<ShareLinkSigner>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a fragment, not a full page;
+        // redirect to group details
+        return Ok(Either::Right(back_to_group(id, domain)));
+    }
+
+    let empty_form = form::Context::default();
+    let template = render_shares(
+        id,
+        domain,
+        None,
+        empty_form,
+        db.inner(),
+        signer,
+        ctx,
+        perms,
+        &user,
+    )
+    .await?;
+
+    Ok(Either::Left(template))
+}
+
+#[rocket::post("/group/<domain>/<id>/shares", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn create_share<'v>(
+    id: &str,
+    domain: &str,
+    form: Form<Contextual<'v, ShareMemberListDto>>,
+    db: &State<PgPool>,
+    signer: &State<ShareLinkSigner>,
+    resolver: &State<Option<IdentityResolver>>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    let empty_form = form::Context::default();
+    let (created, create_form) = if let Some(dto) = &form.value {
+        // validation passed
+
+        let share = shares::create(id, domain, dto, db.inner(), resolver.as_ref(), &user).await?;
+
+        (Some(share.id), empty_form)
+    } else {
+        // some errors are present; show the form again
+        debug!("Share member list form errors: {:?}", &form.context);
+
+        (None, form.into_inner().context)
+    };
+
+    if partial.is_some() {
+        let template = render_shares(
+            id,
+            domain,
+            created,
+            create_form,
+            db.inner(),
+            signer,
+            ctx,
+            perms,
+            &user,
+        )
+        .await?;
+
+        Ok(Either::Left(template))
+    } else {
+        Ok(Either::Right(back_to_group(id, domain)))
+    }
+}
+
+#[rocket::delete("/group/<domain>/<id>/share/<share_id>")]
+#[allow(clippy::too_many_arguments)]
+async fn revoke_share(
+    id: &str,
+    domain: &str,
+    share_id: Uuid,
+    db: &State<PgPool>,
+    signer: &State<ShareLinkSigner>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    shares::revoke(&share_id, id, domain, db.inner(), &user).await?;
+
+    if partial.is_some() {
+        let empty_form = form::Context::default();
+        let template = render_shares(
+            id,
+            domain,
+            None,
+            empty_form,
+            db.inner(),
+            signer,
+            ctx,
+            perms,
+            &user,
+        )
+        .await?;
+
+        Ok(Either::Left(template))
+    } else {
+        Ok(Either::Right(back_to_group(id, domain)))
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt::Write;
+
+use chrono::NaiveDate;
+use rinja::Template;
+use rocket::{State, response::Redirect, uri};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    errors::{AppError, AppResult},
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    models::MemberCountSnapshot,
+    routing::RouteTree,
+    services::groups,
+    web::{Either, RenderedTemplate, render},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![group_statistics].into()
+}
+
+// dimensions of the chart's SVG viewBox (it's scaled to fit anyway)
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 150.0;
+
+#[derive(Template, Serialize)]
+#[template(path = "groups/statistics.html.j2")]
+struct PartialStatisticsView {
+    ctx: PageContext,
+    chart: Option<MemberCountChart>, // None if there are no snapshots yet
+    latest: Option<MemberCountSnapshot>,
+}
+
+// pre-computed, since templates aren't great at arithmetic
+#[derive(Serialize)]
+struct MemberCountChart {
+    direct_points: String, // for an SVG <polyline>
+    total_points: String,  // (idem)
+    max: i32,
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+}
+
+impl MemberCountChart {
+    fn new(snapshots: &[MemberCountSnapshot]) -> Option<Self> {
+        let first_day = snapshots.first()?.day;
+        let last_day = snapshots.last()?.day;
+
+        let max = snapshots.iter().map(|s| s.n_total).max().unwrap_or(0);
+
+        let span = (last_day - first_day).num_days().max(1) as f64;
+        let scale = f64::from(max.max(1));
+
+        let mut direct_points = String::new();
+        let mut total_points = String::new();
+
+        for snapshot in snapshots {
+            let x = (snapshot.day - first_day).num_days() as f64 / span * CHART_WIDTH;
+            let y = |n: i32| CHART_HEIGHT - f64::from(n) / scale * CHART_HEIGHT;
+
+            let _ = write!(direct_points, "{x:.1},{:.1} ", y(snapshot.n_direct));
+            let _ = write!(total_points, "{x:.1},{:.1} ", y(snapshot.n_total));
+        }
+
+        Some(Self {
+            direct_points,
+            total_points,
+            max,
+            first_day,
+            last_day,
+        })
+    }
+}
+
+#[rocket::get("/group/<domain>/<id>/statistics")]
+#[allow(clippy::too_many_arguments)]
+async fn group_statistics(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a fragment, not a full page;
+        // redirect to group details
+        return Ok(Either::Right(Redirect::to(uri!(super::group_details(
+            id = id,
+            domain = domain
+        )))));
+    }
+
+    // only counts, so anyone who can see the group can see these
+    groups::details::get_relevance(id, domain, db.inner(), perms, &user)
+        .await?
+        .ok_or_else(|| AppError::NoSuchGroup(id.to_owned(), domain.to_owned()))?;
+
+    let mut snapshots = groups::statistics::list_member_counts(id, domain, db.inner()).await?;
+
+    let chart = MemberCountChart::new(&snapshots);
+    let latest = snapshots.pop();
+
+    let template = PartialStatisticsView { ctx, chart, latest };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
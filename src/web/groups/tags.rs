@@ -3,9 +3,10 @@ use rinja::Template;
 use rocket::{
     State,
     form::{self, Contextual, Form},
-    response::{Redirect, content::RawHtml},
+    response::{Flash, Redirect},
     uri,
 };
+use serde::Serialize;
 use sqlx::PgPool;
 
 use crate::{
@@ -13,20 +14,27 @@ use crate::{
     errors::AppResult,
     guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
     models::{SimpleGroup, Tag, TagAssignment},
-    perms::{HivePermission, SystemsScope},
+    perms::HivePermission,
     routing::RouteTree,
     services::groups::{self, AuthorityInGroup},
     web::{
-        self, Either, RenderedTemplate,
+        self, Either, RenderedTemplate, deletions,
         groups::{ListGroupsLayout, ListGroupsSort},
+        render,
     },
 };
 
 pub fn routes() -> RouteTree {
-    rocket::routes![list_tag_assignments, assign_tag, bulk_assign_tag].into()
+    rocket::routes![
+        list_tag_assignments,
+        assign_tag,
+        bulk_assign_tag,
+        bulk_unassign_tag
+    ]
+    .into()
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/tags/list.html.j2")]
 struct ListTagAssignmentsView {
     ctx: PageContext,
@@ -34,12 +42,13 @@ struct ListTagAssignmentsView {
     can_manage_any: bool,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "groups/tags/assign.html.j2", block = "inner_assign_tag_form")]
 struct PartialAssignTagView<'f, 'v> {
     ctx: PageContext,
     group: SimpleGroup,
     assignable_tags: Vec<Tag>,
+    #[serde(serialize_with = "crate::web::serialize_form_errors")]
     assign_tag_form: &'f form::Context<'v>,
     assign_tag_success: Option<TagAssignment>,
 }
@@ -54,7 +63,7 @@ pub async fn list_tag_assignments(
     user: User,
     partial: Option<HxRequest<'_>>,
 ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-    if partial.is_none() {
+    if partial.is_none() && !ctx.format.is_json() {
         // we only know how to render a table, not a full page;
         // redirect to group details
 
@@ -86,7 +95,7 @@ pub async fn list_tag_assignments(
         can_manage_any,
     };
 
-    Ok(Either::Left(RawHtml(template.render()?)))
+    Ok(Either::Left(render(&template, template.ctx.format)?))
 }
 
 #[rocket::post("/group/<domain>/<id>/tags", data = "<form>")]
@@ -108,8 +117,8 @@ pub async fn assign_tag<'v>(
     if let Some(dto) = &form.value {
         // validation passed
 
-        let min = HivePermission::AssignPerms(SystemsScope::Id(dto.tag.system_id.to_owned()));
-        perms.require(min).await?;
+        let possibilities = HivePermission::assign_tag(dto.tag.system_id, dto.tag.tag_id);
+        perms.require_any_of(&possibilities).await?;
 
         let assignment = groups::tags::assign(id, domain, dto, db.inner(), &user).await?;
 
@@ -122,7 +131,7 @@ pub async fn assign_tag<'v>(
                 assignable_tags,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: maybe allow passing ?added_tag=$key
 
@@ -142,7 +151,7 @@ pub async fn assign_tag<'v>(
                 assignable_tags,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
             // any validation error indicators... but there isn't a great
@@ -162,8 +171,8 @@ pub async fn bulk_assign_tag<'v>(
     user: User,
 ) -> AppResult<Redirect> {
     if let Some(dto) = &form.value {
-        let min = HivePermission::AssignPerms(SystemsScope::Id(dto.tag.system_id.to_owned()));
-        perms.require(min).await?;
+        let possibilities = HivePermission::assign_tag(dto.tag.system_id, dto.tag.tag_id);
+        perms.require_any_of(&possibilities).await?;
 
         groups::tags::bulk_assign(dto, db.inner(), &user).await?;
 
@@ -180,8 +189,52 @@ pub async fn bulk_assign_tag<'v>(
             None::<&str>,
             None::<ListGroupsSort>,
             Some(ListGroupsLayout::Compact),
-            None::<&str>
+            None::<&str>,
+            false
         ));
         Ok(Redirect::to(target))
     }
 }
+
+#[rocket::post("/groups/bulk-untag", data = "<form>")]
+pub async fn bulk_unassign_tag<'v>(
+    form: Form<Contextual<'v, BulkTagGroupsDto<'v>>>,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+) -> AppResult<Either<Redirect, Flash<Redirect>>> {
+    if let Some(dto) = &form.value {
+        let possibilities = HivePermission::assign_tag(dto.tag.system_id, dto.tag.tag_id);
+        perms.require_any_of(&possibilities).await?;
+
+        // TODO: anti-CSRF
+
+        let deletion_id = groups::tags::bulk_unassign(dto, db.inner(), &user).await?;
+
+        let target = uri!(web::tags::tag_details(
+            system_id = dto.tag.system_id,
+            tag_id = dto.tag.tag_id
+        ));
+
+        match deletion_id {
+            Some(deletion_id) => Ok(Either::Right(deletions::undoable(
+                Redirect::to(target),
+                deletion_id,
+            ))),
+            // none of the selected groups had the tag; nothing to undo
+            None => Ok(Either::Left(Redirect::to(target))),
+        }
+    } else {
+        // some errors are present; reload page
+        debug!("Bulk unassign tag form errors: {:?}", &form.context);
+
+        let target = uri!(super::list_groups(
+            None::<&str>,
+            None::<ListGroupsSort>,
+            Some(ListGroupsLayout::Compact),
+            None::<&str>,
+            false
+        ));
+        Ok(Either::Left(Redirect::to(target)))
+    }
+}
@@ -0,0 +1,227 @@
+use log::*;
+use rinja::Template;
+use rocket::{
+    State,
+    form::{self, Contextual, Form},
+    response::Redirect,
+    uri,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    dto::groups::ProposeTransferDto,
+    errors::AppResult,
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    models::OwnershipTransfer,
+    routing::RouteTree,
+    services::{
+        changes::ProtectedDomains,
+        groups::{self, AuthorityInGroup},
+    },
+    web::{Either, GracefulRedirect, RenderedTemplate, filters, render},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![
+        show_transfer,
+        propose_transfer,
+        cancel_transfer,
+        accept_transfer,
+        decline_transfer,
+    ]
+    .into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "groups/transfer.html.j2")]
+struct PartialTransferView<'f, 'v> {
+    ctx: PageContext,
+    group_id: &'f str,
+    group_domain: &'f str,
+    transfer: Option<OwnershipTransfer>,
+    is_recipient: bool,
+    can_manage: bool,
+    #[serde(serialize_with = "crate::web::serialize_form_errors")]
+    propose_form: &'f form::Context<'v>,
+}
+
+async fn render_transfer<'f, 'v>(
+    id: &'f str,
+    domain: &'f str,
+    propose_form: form::Context<'v>,
+    db: &PgPool,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<RenderedTemplate> {
+    let authority =
+        groups::details::require_authority(AuthorityInGroup::View, id, domain, db, perms, user)
+            .await?;
+
+    let transfer = groups::transfers::get_pending(id, domain, db).await?;
+
+    let is_recipient = match &transfer {
+        Some(transfer) => groups::transfers::is_recipient(transfer, user.username(), db).await?,
+        None => false,
+    };
+
+    let template = PartialTransferView {
+        ctx,
+        group_id: id,
+        group_domain: domain,
+        transfer,
+        is_recipient,
+        can_manage: authority >= AuthorityInGroup::ManageMembers
+            && domain != crate::HIVE_INTERNAL_DOMAIN,
+        propose_form: &propose_form,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::get("/group/<domain>/<id>/transfer")]
+async fn show_transfer(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a fragment, not a full page;
+        // redirect to group details
+
+        let target = uri!(super::group_details(id = id, domain = domain));
+        return Ok(Either::Right(Redirect::to(target)));
+    }
+
+    let empty_form = form::Context::default();
+    let template = render_transfer(id, domain, empty_form, db.inner(), ctx, perms, &user).await?;
+
+    Ok(Either::Left(template))
+}
+
+#[rocket::post("/group/<domain>/<id>/transfer", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn propose_transfer<'v>(
+    id: &str,
+    domain: &str,
+    form: Form<Contextual<'v, ProposeTransferDto<'v>>>,
+    protected: &State<ProtectedDomains>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    let empty_form = form::Context::default();
+    let propose_form = if let Some(dto) = &form.value {
+        // validation passed
+
+        groups::transfers::propose(id, domain, dto, protected, db.inner(), perms, &user).await?;
+
+        empty_form
+    } else {
+        // some errors are present; show the form again
+        debug!("Propose transfer form errors: {:?}", &form.context);
+
+        form.into_inner().context
+    };
+
+    if partial.is_some() {
+        let template =
+            render_transfer(id, domain, propose_form, db.inner(), ctx, perms, &user).await?;
+
+        Ok(Either::Left(template))
+    } else {
+        let target = uri!(super::group_details(id = id, domain = domain));
+        Ok(Either::Right(Redirect::to(target)))
+    }
+}
+
+#[rocket::delete("/group/<domain>/<id>/transfer")]
+async fn cancel_transfer(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        id,
+        domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    groups::transfers::cancel(id, domain, db.inner(), &user).await?;
+
+    if partial.is_some() {
+        let empty_form = form::Context::default();
+        let template =
+            render_transfer(id, domain, empty_form, db.inner(), ctx, perms, &user).await?;
+
+        Ok(Either::Left(template))
+    } else {
+        let target = uri!(super::group_details(id = id, domain = domain));
+        Ok(Either::Right(Redirect::to(target)))
+    }
+}
+
+// the receiving party might not have any authority in the group beforehand, so
+// these are checked by the service instead of via require_authority
+
+#[rocket::post("/group/<domain>/<id>/transfer/accept")]
+async fn accept_transfer(
+    id: &str,
+    domain: &str,
+    protected: &State<ProtectedDomains>,
+    db: &State<PgPool>,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    // TODO: anti-CSRF
+
+    groups::transfers::accept(id, domain, protected, db.inner(), &user).await?;
+
+    let target = uri!(super::group_details(id = id, domain = domain));
+    Ok(GracefulRedirect::to(target, partial.is_some()))
+}
+
+#[rocket::post("/group/<domain>/<id>/transfer/decline")]
+async fn decline_transfer(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    // TODO: anti-CSRF
+
+    groups::transfers::decline(id, domain, db.inner(), &user).await?;
+
+    let target = format!("/user/{}", user.username());
+    Ok(GracefulRedirect::to(target, partial.is_some()))
+}
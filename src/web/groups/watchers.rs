@@ -0,0 +1,119 @@
+use rinja::Template;
+use rocket::{State, response::Redirect, uri};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    errors::{AppError, AppResult},
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    routing::RouteTree,
+    services::{
+        digests::ManagerDigests,
+        groups::{self, watchers},
+    },
+    web::{Either, RenderedTemplate, render},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![watch_button, set_watching].into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "groups/watch-button.html.j2")]
+struct PartialWatchButtonView<'r> {
+    ctx: PageContext,
+    group_id: &'r str,
+    group_domain: &'r str,
+    watching: Option<bool>, // None if digests aren't enabled at all
+}
+
+// watching only has an effect through weekly digests
+#[rocket::get("/group/<domain>/<id>/watch")]
+#[allow(clippy::too_many_arguments)]
+async fn watch_button(
+    id: &str,
+    domain: &str,
+    db: &State<PgPool>,
+    manager_digests: &State<ManagerDigests>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a fragment, not a full page;
+        // redirect to group details
+        return Ok(Either::Right(Redirect::to(uri!(super::group_details(
+            id = id,
+            domain = domain
+        )))));
+    }
+
+    require_visible(id, domain, db.inner(), perms, &user).await?;
+
+    let watching = if manager_digests.is_enabled() {
+        Some(watchers::is_watching(user.username(), id, domain, db.inner()).await?)
+    } else {
+        None
+    };
+
+    let template = PartialWatchButtonView {
+        ctx,
+        group_id: id,
+        group_domain: domain,
+        watching,
+    };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
+
+#[rocket::post("/group/<domain>/<id>/watch?<watching>")]
+#[allow(clippy::too_many_arguments)]
+async fn set_watching(
+    id: &str,
+    domain: &str,
+    watching: bool,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    require_visible(id, domain, db.inner(), perms, &user).await?;
+
+    // TODO: anti-CSRF
+
+    watchers::set_watching(user.username(), id, domain, watching, db.inner()).await?;
+
+    if partial.is_some() {
+        let template = PartialWatchButtonView {
+            ctx,
+            group_id: id,
+            group_domain: domain,
+            watching: Some(watching),
+        };
+
+        Ok(Either::Left(render(&template, template.ctx.format)?))
+    } else {
+        Ok(Either::Right(Redirect::to(uri!(super::group_details(
+            id = id,
+            domain = domain
+        )))))
+    }
+}
+
+// anyone who can view the group can watch it
+async fn require_visible(
+    id: &str,
+    domain: &str,
+    db: &PgPool,
+    perms: &PermsEvaluator,
+    user: &User,
+) -> AppResult<()> {
+    groups::details::get_relevance(id, domain, db, perms, user)
+        .await?
+        .ok_or_else(|| AppError::NoSuchGroup(id.to_owned(), domain.to_owned()))?;
+    // ^ technically it's a permissions problem, but this prevents enumeration
+
+    Ok(())
+}
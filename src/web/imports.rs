@@ -0,0 +1,99 @@
+use rinja::Template;
+use rocket::{State, form::Form, tokio::io::AsyncReadExt};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use super::{RenderedTemplate, render, require_admin};
+use crate::{
+    dto::imports::{LegacyDumpDto, LegacyImportDto},
+    errors::{AppError, AppResult},
+    guards::{context::PageContext, user::User},
+    models::{ImportOutcome, ImportedEntry, ImportedEntryKind},
+    routing::RouteTree,
+    services::imports,
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![import_page, import_legacy].into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "import.html.j2")]
+struct ImportView {
+    ctx: PageContext,
+    report: Option<Vec<ImportedEntry>>,
+    dry_run: bool,
+}
+
+impl ImportView {
+    fn count(&self, f: fn(&ImportOutcome) -> bool) -> usize {
+        self.report
+            .iter()
+            .flatten()
+            .filter(|entry| f(&entry.outcome))
+            .count()
+    }
+
+    fn created(&self) -> usize {
+        self.count(|o| matches!(o, ImportOutcome::Created(..)))
+    }
+
+    fn existing(&self) -> usize {
+        self.count(|o| matches!(o, ImportOutcome::Existing(..)))
+    }
+
+    fn failed(&self) -> usize {
+        self.count(|o| matches!(o, ImportOutcome::Failed(..)))
+    }
+}
+
+// only administrators, since this bypasses the usual per-domain delegation
+#[rocket::get("/import")]
+async fn import_page(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    require_admin(&user, db.inner()).await?;
+
+    let template = ImportView {
+        ctx,
+        report: None,
+        dry_run: true,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/import", data = "<form>")]
+async fn import_legacy(
+    form: Form<LegacyImportDto<'_>>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    require_admin(&user, db.inner()).await?;
+
+    // TODO: anti-CSRF
+
+    let mut content = String::new();
+    form.dump
+        .open()
+        .await
+        .map_err(|e| AppError::InvalidLegacyDump(e.to_string()))?
+        .read_to_string(&mut content)
+        .await
+        .map_err(|e| AppError::InvalidLegacyDump(e.to_string()))?;
+
+    let dump = LegacyDumpDto::parse(&content, form.format).map_err(AppError::InvalidLegacyDump)?;
+
+    let report = imports::import_legacy(&dump, form.dry_run, db.inner(), &user).await?;
+
+    let template = ImportView {
+        ctx,
+        report: Some(report),
+        dry_run: form.dry_run,
+    };
+
+    render(&template, template.ctx.format)
+}
@@ -1,5 +1,6 @@
 use rinja::Template;
-use rocket::{response::content::RawHtml, Either, State};
+use rocket::{Either, State};
+use serde::Serialize;
 use sqlx::PgPool;
 
 use crate::{
@@ -10,7 +11,7 @@ use crate::{
     perms::HivePermission,
     routing::RouteTree,
     services::audit_logs,
-    web::RenderedTemplate,
+    web::{RenderedTemplate, render},
 };
 
 const PAGE_SIZE: u32 = 50;
@@ -19,10 +20,11 @@ pub fn routes() -> RouteTree {
     rocket::routes![get_audit_logs].into()
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "logs/details.html.j2")]
 struct ListLogsView<'r> {
     ctx: PageContext,
+    #[serde(skip)] // (just echoes the query)
     filter: LogsFilterDto<'r>,
     actors: Vec<String>,
     ids: Vec<String>,
@@ -30,11 +32,12 @@ struct ListLogsView<'r> {
     next_page: u32,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "logs/log-cells.html.j2")]
 struct ListLogsPartial<'r> {
     ctx: PageContext,
     logs: Vec<AuditLog>,
+    #[serde(skip)] // (just echoes the query)
     filter: LogsFilterDto<'r>,
     next_page: u32,
 }
@@ -71,7 +74,7 @@ async fn get_audit_logs<'r>(
             next_page: page + 1,
         };
 
-        Ok(RawHtml(template.render()?))
+        render(&template, template.ctx.format)
     } else {
         let template = ListLogsView {
             ctx,
@@ -82,6 +85,6 @@ async fn get_audit_logs<'r>(
             ids,
         };
 
-        Ok(RawHtml(template.render()?))
+        render(&template, template.ctx.format)
     }
 }
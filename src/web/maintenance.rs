@@ -0,0 +1,174 @@
+use log::*;
+use rinja::Template;
+use rocket::{State, form::Form, response::Redirect, uri};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use super::{Either, RenderedTemplate, render, require_admin};
+use crate::{
+    dto::maintenance::CleanupOrphansDto,
+    errors::AppResult,
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    routing::{RouteTree, maintenance},
+    services::{
+        groups::{self, duplicates::DuplicateMemberships},
+        orphans::{self, Orphans},
+    },
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![
+        maintenance_details,
+        toggle_maintenance,
+        list_duplicate_memberships,
+        consolidate_duplicate_memberships,
+        list_orphans,
+        cleanup_orphans
+    ]
+    .into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "maintenance.html.j2")]
+struct MaintenanceView {
+    ctx: PageContext,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "maintenance/duplicates.html.j2")]
+struct DuplicateMembershipsView {
+    ctx: PageContext,
+    duplicates: Vec<DuplicateMemberships>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "maintenance/orphans.html.j2")]
+struct OrphansView {
+    ctx: PageContext,
+    orphans: Orphans,
+    deleted: Option<usize>, // after a cleanup
+}
+
+// only administrators, since this affects everyone
+#[rocket::get("/maintenance")]
+async fn maintenance_details(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    require_admin(&user, db.inner()).await?;
+
+    let template = MaintenanceView { ctx };
+
+    render(&template, template.ctx.format)
+}
+
+// (exempt from maintenance mode itself, otherwise it could never be left)
+#[rocket::post("/maintenance?<active>")]
+async fn toggle_maintenance(active: bool, db: &State<PgPool>, user: User) -> AppResult<Redirect> {
+    require_admin(&user, db.inner()).await?;
+
+    // TODO: anti-CSRF
+
+    // not in the audit logs, since the database might not be writable
+    info!("Maintenance mode set to {active} by {}", user.username());
+
+    maintenance::set_active(active);
+
+    Ok(Redirect::to(uri!(maintenance_details)))
+}
+
+#[rocket::get("/maintenance/duplicate-memberships")]
+async fn list_duplicate_memberships(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    require_admin(&user, db.inner()).await?;
+
+    let duplicates = groups::duplicates::list_all(db.inner()).await?;
+
+    let template = DuplicateMembershipsView { ctx, duplicates };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/maintenance/duplicate-memberships/<domain>/<id>/<username>")]
+async fn consolidate_duplicate_memberships(
+    domain: &str,
+    id: &str,
+    username: &str,
+    db: &State<PgPool>,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<(), Redirect>> {
+    require_admin(&user, db.inner()).await?;
+
+    // TODO: anti-CSRF
+
+    let removed = groups::duplicates::consolidate(username, id, domain, db.inner(), &user).await?;
+
+    info!(
+        "Consolidated {removed} duplicate membership(s) of {username} in {id}@{domain} by {}",
+        user.username()
+    );
+
+    if partial.is_some() {
+        // the consolidated entry is simply removed from the list
+        Ok(Either::Left(()))
+    } else {
+        Ok(Either::Right(Redirect::to(uri!(
+            list_duplicate_memberships
+        ))))
+    }
+}
+
+#[rocket::get("/maintenance/orphans")]
+async fn list_orphans(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    require_admin(&user, db.inner()).await?;
+
+    let orphans = orphans::list_all(db.inner()).await?;
+
+    let template = OrphansView {
+        ctx,
+        orphans,
+        deleted: None,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/maintenance/orphans", data = "<form>")]
+async fn cleanup_orphans(
+    form: Form<CleanupOrphansDto<'_>>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    require_admin(&user, db.inner()).await?;
+
+    // TODO: anti-CSRF
+
+    let deleted = orphans::cleanup(&form, db.inner(), perms, &user).await?;
+
+    info!(
+        "Cleaned up {deleted} orphaned entities by {}",
+        user.username()
+    );
+
+    // (re-listed afterwards, since whatever wasn't selected is still there)
+    let orphans = orphans::list_all(db.inner()).await?;
+
+    let template = OrphansView {
+        ctx,
+        orphans,
+        deleted: Some(deleted),
+    };
+
+    render(&template, template.ctx.format)
+}
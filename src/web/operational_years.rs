@@ -0,0 +1,187 @@
+use chrono::NaiveDate;
+use log::*;
+use rinja::Template;
+use rocket::{
+    State,
+    form::{self, Contextual, Form},
+    response::Redirect,
+    uri,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{Either, RenderedTemplate, render, require_admin};
+use crate::{
+    clock,
+    dto::operational_years::OperationalYearDto,
+    errors::AppResult,
+    guards::{context::PageContext, headers::HxRequest, user::User},
+    models::OperationalYear,
+    routing::RouteTree,
+    services::operational_years,
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![
+        list_operational_years,
+        create_operational_year,
+        edit_operational_year,
+        delete_operational_year,
+        operational_year_shortcuts
+    ]
+    .into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "operational-years/list.html.j2")]
+struct ListOperationalYearsView<'f, 'v> {
+    ctx: PageContext,
+    years: Vec<OperationalYear>,
+    today: NaiveDate,
+    #[serde(serialize_with = "super::serialize_form_errors")]
+    create_form: &'f form::Context<'v>,
+}
+
+// a (from, until) pair to prefill the add member form with
+#[derive(Serialize)]
+struct OperationalYearShortcut {
+    year: OperationalYear,
+    from: NaiveDate,
+    until: NaiveDate,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "operational-years/shortcuts.html.j2")]
+struct PartialOperationalYearShortcutsView {
+    ctx: PageContext,
+    rest_of_current: Option<OperationalYearShortcut>,
+    next: Option<OperationalYearShortcut>,
+}
+
+// only administrators, since years are shared by every group
+#[rocket::get("/operational-years")]
+async fn list_operational_years(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    require_admin(&user, db.inner()).await?;
+
+    let years = operational_years::list_all(db.inner()).await?;
+
+    let template = ListOperationalYearsView {
+        ctx,
+        years,
+        today: clock::today(),
+        create_form: &form::Context::default(),
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/operational-years", data = "<form>")]
+async fn create_operational_year<'v>(
+    form: Form<Contextual<'v, OperationalYearDto<'v>>>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    require_admin(&user, db.inner()).await?;
+
+    // TODO: anti-CSRF
+
+    if let Some(dto) = &form.value {
+        // validation passed
+
+        let year = operational_years::create(dto, db.inner(), &user).await?;
+
+        info!(
+            "Operational year {} ({} – {}) created by {}",
+            year.name,
+            year.start,
+            year.end,
+            user.username()
+        );
+
+        Ok(Either::Right(Redirect::to(uri!(list_operational_years))))
+    } else {
+        // some errors are present; show the form again
+        debug!("Create operational year form errors: {:?}", &form.context);
+
+        let years = operational_years::list_all(db.inner()).await?;
+
+        let template = ListOperationalYearsView {
+            ctx,
+            years,
+            today: clock::today(),
+            create_form: &form.context,
+        };
+
+        Ok(Either::Left(render(&template, template.ctx.format)?))
+    }
+}
+
+#[rocket::post("/operational-year/<id>", data = "<form>")]
+async fn edit_operational_year(
+    id: Uuid,
+    form: Form<OperationalYearDto<'_>>,
+    db: &State<PgPool>,
+    user: User,
+) -> AppResult<Redirect> {
+    require_admin(&user, db.inner()).await?;
+
+    // TODO: anti-CSRF
+
+    operational_years::update(&id, &form, db.inner(), &user).await?;
+
+    Ok(Redirect::to(uri!(list_operational_years)))
+}
+
+#[rocket::delete("/operational-year/<id>")]
+async fn delete_operational_year(
+    id: Uuid,
+    db: &State<PgPool>,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<(), Redirect>> {
+    require_admin(&user, db.inner()).await?;
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    operational_years::delete(&id, db.inner(), &user).await?;
+
+    if partial.is_some() {
+        Ok(Either::Left(()))
+    } else {
+        Ok(Either::Right(Redirect::to(uri!(list_operational_years))))
+    }
+}
+
+// buttons for the add member form; empty if no years have been set up
+#[rocket::get("/operational-years/shortcuts")]
+async fn operational_year_shortcuts(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    _user: User, // only logged-in users may add members anyway
+) -> AppResult<RenderedTemplate> {
+    let today = clock::today();
+
+    let (current, next) = operational_years::get_current_and_next(today, db.inner()).await?;
+
+    let template = PartialOperationalYearShortcutsView {
+        ctx,
+        rest_of_current: current.map(|year| OperationalYearShortcut {
+            from: today,
+            until: year.end,
+            year,
+        }),
+        next: next.map(|year| OperationalYearShortcut {
+            from: year.start,
+            until: year.end,
+            year,
+        }),
+    };
+
+    render(&template, template.ctx.format)
+}
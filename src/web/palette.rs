@@ -0,0 +1,95 @@
+use rocket::{State, serde::json::Json};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    errors::AppResult,
+    guards::{lang::Language, perms::PermsEvaluator, user::User},
+    perms::HivePermission,
+    routing::RouteTree,
+    services::{groups, permissions, systems, tags},
+};
+
+pub fn routes() -> RouteTree {
+    rocket::routes![list_palette_entries].into()
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum PaletteEntryKind {
+    Group,
+    System,
+    Permission,
+    Tag,
+}
+
+#[derive(Serialize)]
+struct PaletteEntry {
+    kind: PaletteEntryKind,
+    label: String,
+    detail: String,
+    href: String,
+}
+
+// consumed by the command palette (see static/palette.js), which does fuzzy
+// matching client-side; q is only meant to narrow down the results beforehand
+#[rocket::get("/palette?<q>")]
+async fn list_palette_entries(
+    q: Option<&str>,
+    db: &State<PgPool>,
+    lang: Language,
+    perms: &PermsEvaluator,
+    user: User,
+) -> AppResult<Json<Vec<PaletteEntry>>> {
+    let mut entries = vec![];
+
+    for group in groups::list::list_relevant(q, db.inner(), perms, &user).await? {
+        entries.push(PaletteEntry {
+            kind: PaletteEntryKind::Group,
+            label: group.localized_name(&lang).to_owned(),
+            detail: group.key(),
+            href: format!("/group/{}/{}", group.domain, group.id),
+        });
+    }
+
+    let fully_authorized = perms.satisfies(HivePermission::ManageSystems).await?;
+
+    for system in systems::list_manageable(q, fully_authorized, db.inner(), perms).await? {
+        entries.push(PaletteEntry {
+            kind: PaletteEntryKind::System,
+            href: format!("/system/{}", system.id),
+            label: system.id,
+            detail: system.description,
+        });
+    }
+
+    // permissions and tags should be found even if their system doesn't match
+    let system_ids: Vec<_> = systems::list_manageable(None, fully_authorized, db.inner(), perms)
+        .await?
+        .into_iter()
+        .map(|system| system.id)
+        .collect();
+
+    for permission in permissions::list_matching(&system_ids, q, db.inner()).await? {
+        entries.push(PaletteEntry {
+            kind: PaletteEntryKind::Permission,
+            label: permission.key(),
+            href: format!(
+                "/system/{}/permission/{}",
+                permission.system_id, permission.perm_id
+            ),
+            detail: permission.description,
+        });
+    }
+
+    for tag in tags::list_matching(&system_ids, q, db.inner()).await? {
+        entries.push(PaletteEntry {
+            kind: PaletteEntryKind::Tag,
+            label: tag.key(),
+            href: format!("/system/{}/tag/{}", tag.system_id, tag.tag_id),
+            detail: tag.description,
+        });
+    }
+
+    Ok(Json(entries))
+}
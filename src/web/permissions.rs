@@ -1,25 +1,45 @@
+use std::collections::HashMap;
+
+use chrono::{Local, Months};
 use log::*;
 use rinja::Template;
 use rocket::{
     State,
     form::{self, Contextual, Form},
-    response::{Redirect, content::RawHtml},
+    response::{Flash, Redirect},
     uri,
 };
+use serde::Serialize;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use super::{Either, GracefulRedirect, RenderedTemplate};
+use super::{
+    Either, GracefulRedirect, RenderedTemplate, deletions, filters, render, require_admin,
+};
 use crate::{
-    dto::permissions::{
-        AssignPermissionToApiTokenDto, AssignPermissionToGroupDto, CreatePermissionDto,
+    dto::{
+        groups::GroupRefDto,
+        permissions::{
+            AssignPermissionDto, AssignPermissionToApiTokenDto, AssignPermissionToGroupDto,
+            CreatePermissionDto, PermissionKey,
+        },
+    },
+    errors::{AppError, AppResult},
+    guards::{
+        context::PageContext, format::ResponseFormat, headers::HxRequest, perms::PermsEvaluator,
+        user::User,
+    },
+    models::{
+        AffiliatedPermissionAssignment, AssignmentPreview, PendingChange, Permission,
+        PermissionHolder,
     },
-    errors::AppResult,
-    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
-    models::{AffiliatedPermissionAssignment, Permission},
-    perms::{HivePermission, SystemsScope},
+    perms::{HivePermission, ScopeKind, SystemsScope},
     routing::RouteTree,
-    services::{permissions, systems},
+    services::{
+        changes::{self, ProtectedDomains},
+        groups::{self, AuthorityInGroup},
+        permissions, perms_cache, systems,
+    },
 };
 
 pub fn routes() -> RouteTree {
@@ -30,44 +50,106 @@ pub fn routes() -> RouteTree {
         delete_permission,
         list_permission_groups,
         list_permission_api_tokens,
+        list_permission_holders,
         assign_permission_to_group,
         assign_permission_to_api_token,
-        unassign_permission
+        unassign_permission,
+        list_permission_scope_suggestions,
+        preview_permission_assignment,
+        list_unused_permission_assignments,
+        hive_permission_matrix
     ]
     .into()
 }
 
-#[derive(Template)]
+// how long an assignment must not have been matched for to be considered unused
+const DEFAULT_UNUSED_MONTHS: u32 = 6;
+const MAX_UNUSED_MONTHS: u32 = 36;
+
+#[derive(Template, Serialize)]
+#[template(path = "permissions/unused.html.j2")]
+struct ListUnusedPermissionAssignmentsView {
+    ctx: PageContext,
+    assignments: Vec<AffiliatedPermissionAssignment>,
+    months: u32,
+    max_months: u32,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "permissions/hive-matrix.html.j2")]
+struct HivePermissionMatrixView {
+    ctx: PageContext,
+    rows: Vec<HivePermissionMatrixRow>,
+    unknown: Vec<Permission>, // in the database, but not in the code
+}
+
+#[derive(Serialize)]
+struct HivePermissionMatrixRow {
+    perm_id: &'static str,
+    scope_kind: ScopeKind,
+    registered: Option<Permission>, // None if missing from the database
+    assignments: Vec<AffiliatedPermissionAssignment>,
+}
+
+impl HivePermissionMatrixRow {
+    // i.e., the database disagrees with the code about taking a scope
+    fn scope_mismatch(&self) -> bool {
+        self.registered
+            .as_ref()
+            .is_some_and(|registered| registered.has_scope != self.scope_kind.is_scoped())
+    }
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "permissions/scope-suggestions.html.j2")]
+struct PartialScopeSuggestionsView {
+    scopes: Vec<String>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "permissions/preview.html.j2")]
+struct PartialAssignmentPreviewView<'r> {
+    ctx: PageContext,
+    group_domain: &'r str,
+    preview: Option<AssignmentPreview>,
+}
+
+#[derive(Template, Serialize)]
 #[template(path = "permissions/list.html.j2")]
 struct ListPermissionsView {
     ctx: PageContext,
     permissions: Vec<Permission>,
+    holder_counts: HashMap<String, i64>, // perm_id => distinct users
     can_manage: bool,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(
     path = "permissions/create.html.j2",
     block = "inner_create_permission_form"
 )]
 struct PartialCreatePermissionView<'f, 'v> {
     ctx: PageContext,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     permission_create_form: &'f form::Context<'v>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "permissions/details.html.j2")]
 struct PermissionDetailsView<'f, 'v> {
     ctx: PageContext,
     permission: Permission,
     fully_authorized: bool,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     assign_to_group_form: &'f form::Context<'v>,
     assign_to_group_success: Option<AffiliatedPermissionAssignment>,
+    assign_to_group_proposal: Option<PendingChange>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     assign_to_api_token_form: &'f form::Context<'v>,
     assign_to_api_token_success: Option<AffiliatedPermissionAssignment>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "permissions/groups/list.html.j2")]
 struct PartialListPermissionGroupsView {
     ctx: PageContext,
@@ -76,7 +158,7 @@ struct PartialListPermissionGroupsView {
     permission_assignments: Vec<AffiliatedPermissionAssignment>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "permissions/api-tokens/list.html.j2")]
 struct PartialListPermissionApiTokensView {
     ctx: PageContext,
@@ -85,7 +167,15 @@ struct PartialListPermissionApiTokensView {
     permission_assignments: Vec<AffiliatedPermissionAssignment>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
+#[template(path = "permissions/holders.html.j2")]
+struct PartialListPermissionHoldersView {
+    ctx: PageContext,
+    has_scope: bool,
+    holders: Vec<PermissionHolder>,
+}
+
+#[derive(Template, Serialize)]
 #[template(
     path = "permissions/groups/assign.html.j2",
     block = "inner_assign_to_group_form"
@@ -93,11 +183,13 @@ struct PartialListPermissionApiTokensView {
 struct AssignPermissionToGroupView<'f, 'v> {
     ctx: PageContext,
     permission: Permission,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     assign_to_group_form: &'f form::Context<'v>,
     assign_to_group_success: Option<AffiliatedPermissionAssignment>,
+    assign_to_group_proposal: Option<PendingChange>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(
     path = "permissions/api-tokens/assign.html.j2",
     block = "inner_assign_to_api_token_form"
@@ -105,6 +197,7 @@ struct AssignPermissionToGroupView<'f, 'v> {
 struct AssignPermissionToApiTokenView<'f, 'v> {
     ctx: PageContext,
     permission: Permission,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     assign_to_api_token_form: &'f form::Context<'v>,
     assign_to_api_token_success: Option<AffiliatedPermissionAssignment>,
 }
@@ -117,7 +210,7 @@ async fn list_permissions(
     perms: &PermsEvaluator,
     partial: Option<HxRequest<'_>>,
 ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-    if partial.is_none() {
+    if partial.is_none() && !ctx.format.is_json() {
         // we only know how to render a table, not a full page;
         // redirect to system details
 
@@ -139,9 +232,12 @@ async fn list_permissions(
         systems::ensure_exists(system_id, db.inner()).await?;
     }
 
+    let holder_counts = perms_cache::get_holder_counts(system_id, db.inner()).await?;
+
     let template = ListPermissionsView {
         ctx,
         permissions,
+        holder_counts: (*holder_counts).clone(),
         can_manage: perms
             .satisfies_any_of(&[
                 HivePermission::AssignPerms(SystemsScope::Id(system_id.to_owned())),
@@ -150,7 +246,7 @@ async fn list_permissions(
             .await?,
     };
 
-    Ok(Either::Left(RawHtml(template.render()?)))
+    Ok(Either::Left(render(&template, template.ctx.format)?))
 }
 
 #[rocket::post("/system/<system_id>/permissions", data = "<form>")]
@@ -192,7 +288,7 @@ async fn create_permission<'v>(
                 permission_create_form: &form.context,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
             // any validation error indicators... but there isn't a great
@@ -229,11 +325,12 @@ async fn permission_details(
         fully_authorized: perms.satisfies(min).await?,
         assign_to_group_form: &empty_form,
         assign_to_group_success: None,
+        assign_to_group_proposal: None,
         assign_to_api_token_form: &empty_form,
         assign_to_api_token_success: None,
     };
 
-    Ok(RawHtml(template.render()?))
+    render(&template, template.ctx.format)
 }
 
 #[rocket::delete("/system/<system_id>/permission/<perm_id>")]
@@ -244,18 +341,19 @@ pub async fn delete_permission(
     perms: &PermsEvaluator,
     user: User,
     partial: Option<HxRequest<'_>>,
-) -> AppResult<GracefulRedirect> {
+) -> AppResult<Flash<GracefulRedirect>> {
     let min = HivePermission::ManagePerms(SystemsScope::Id(system_id.to_owned()));
     perms.require(min).await?;
 
     // TODO: anti-CSRF(?), DELETE isn't a normal form method
 
-    permissions::delete(system_id, perm_id, db.inner(), &user).await?;
+    let deletion_id = permissions::delete(system_id, perm_id, db.inner(), &user).await?;
+
+    let target = uri!(super::systems::system_details(system_id));
 
-    // TODO: show visual confirmation of successful delete in permissions list
-    Ok(GracefulRedirect::to(
-        uri!(super::systems::system_details(system_id)),
-        partial.is_some(),
+    Ok(deletions::undoable(
+        GracefulRedirect::to(target, partial.is_some()),
+        deletion_id,
     ))
 }
 
@@ -270,7 +368,7 @@ macro_rules! list_permission_assignments {
             perms: &PermsEvaluator,
             partial: Option<HxRequest<'_>>,
         ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-            if partial.is_none() {
+            if partial.is_none() && !ctx.format.is_json() {
                 // we only know how to render a table, not a full page;
                 // redirect to permission details
 
@@ -304,7 +402,7 @@ macro_rules! list_permission_assignments {
                 permission_assignments,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         }
     };
 }
@@ -323,6 +421,49 @@ list_permission_assignments!(
     PartialListPermissionApiTokensView
 );
 
+#[rocket::get("/system/<system_id>/permission/<perm_id>/holders?<scope>")]
+#[allow(clippy::too_many_arguments)]
+async fn list_permission_holders(
+    system_id: &str,
+    perm_id: &str,
+    scope: Option<&str>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a table, not a full page;
+        // redirect to permission details
+
+        let target = uri!(permission_details(system_id = system_id, perm_id = perm_id));
+        return Ok(Either::Right(Redirect::to(target)));
+    }
+
+    perms
+        .require_any_of(&[
+            HivePermission::AssignPerms(SystemsScope::Id(system_id.to_owned())),
+            HivePermission::ManagePerms(SystemsScope::Id(system_id.to_owned())),
+        ])
+        .await?;
+
+    let has_scope = permissions::has_scope(system_id, perm_id, db.inner()).await?;
+
+    // the filter input is submitted even when empty
+    let scope = scope.filter(|scope| has_scope && !scope.is_empty());
+
+    let holders =
+        permissions::list_effective_holders(system_id, perm_id, scope, db.inner()).await?;
+
+    let template = PartialListPermissionHoldersView {
+        ctx,
+        has_scope,
+        holders,
+    };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
+
 #[rocket::post("/system/<system_id>/permission/<perm_id>/groups", data = "<form>")]
 #[allow(clippy::too_many_arguments)]
 async fn assign_permission_to_group<'v>(
@@ -330,6 +471,7 @@ async fn assign_permission_to_group<'v>(
     perm_id: &str,
     form: Form<Contextual<'v, AssignPermissionToGroupDto<'v>>>,
     db: &State<PgPool>,
+    protected: &State<ProtectedDomains>,
     ctx: PageContext,
     perms: &PermsEvaluator,
     user: User,
@@ -345,6 +487,37 @@ async fn assign_permission_to_group<'v>(
     if let Some(dto) = &form.value {
         // validation passed
 
+        if protected.contains(dto.group.domain) {
+            // needs to be approved by someone else first
+            let proposed = AssignPermissionDto {
+                perm: PermissionKey { system_id, perm_id },
+                scope: dto.scope,
+            };
+
+            let proposal = changes::propose_permission(
+                dto.group.id,
+                dto.group.domain,
+                &proposed,
+                db.inner(),
+                &user,
+            )
+            .await?;
+
+            return if partial.is_some() {
+                let template = AssignPermissionToGroupView {
+                    ctx,
+                    permission,
+                    assign_to_group_form: &form::Context::default(),
+                    assign_to_group_success: None,
+                    assign_to_group_proposal: Some(proposal),
+                };
+
+                Ok(Either::Left(render(&template, template.ctx.format)?))
+            } else {
+                Ok(Either::Right(Redirect::to(uri!("/changes"))))
+            };
+        }
+
         let assignment = permissions::assign_to_group(
             system_id,
             perm_id,
@@ -361,9 +534,10 @@ async fn assign_permission_to_group<'v>(
                 permission,
                 assign_to_group_form: &form::Context::default(),
                 assign_to_group_success: Some(assignment),
+                assign_to_group_proposal: None,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: maybe allow passing ?assigned_to_group=id@domain
 
@@ -383,9 +557,10 @@ async fn assign_permission_to_group<'v>(
                 permission,
                 assign_to_group_form: &form.context,
                 assign_to_group_success: None,
+                assign_to_group_proposal: None,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
             // any validation error indicators... but there isn't a great
@@ -437,7 +612,7 @@ async fn assign_permission_to_api_token<'v>(
                 assign_to_api_token_success: Some(assignment),
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: maybe allow passing ?assigned_to_api_token=id
 
@@ -459,7 +634,7 @@ async fn assign_permission_to_api_token<'v>(
                 assign_to_api_token_success: None,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
             // any validation error indicators... but there isn't a great
@@ -495,3 +670,155 @@ async fn unassign_permission(
         Ok(Either::Right(Redirect::to(target)))
     }
 }
+
+// meant to be used as options for a <datalist> in assignment forms
+#[rocket::get("/permission-scopes?<perm>")]
+async fn list_permission_scope_suggestions(
+    perm: PermissionKey<'_>,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    format: ResponseFormat,
+) -> AppResult<RenderedTemplate> {
+    let min = HivePermission::AssignPerms(SystemsScope::Id(perm.system_id.to_owned()));
+    perms.require(min).await?;
+
+    let scopes = permissions::list_known_scopes(perm.system_id, perm.perm_id, db.inner()).await?;
+
+    let template = PartialScopeSuggestionsView { scopes };
+
+    render(&template, format)
+}
+
+// shown while filling in either form for assigning a permission to a group
+// (from the group or from the permission), so incomplete or otherwise unusable
+// input just results in an empty preview instead of an error
+#[rocket::get("/permission-assignments/preview?<group>&<perm>&<scope>")]
+async fn preview_permission_assignment(
+    group: Option<GroupRefDto<'_>>,
+    perm: Option<PermissionKey<'_>>,
+    scope: Option<&str>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    let mut template = PartialAssignmentPreviewView {
+        ctx,
+        group_domain: group.as_ref().map(|group| group.domain).unwrap_or_default(),
+        preview: None,
+    };
+
+    let (Some(group), Some(perm)) = (group, perm) else {
+        return render(&template, template.ctx.format);
+    };
+
+    let min = HivePermission::AssignPerms(SystemsScope::Id(perm.system_id.to_owned()));
+    if !perms.satisfies(min).await? {
+        return render(&template, template.ctx.format);
+    }
+
+    match groups::details::require_authority(
+        AuthorityInGroup::View,
+        group.id,
+        group.domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await
+    {
+        Ok(_) => {}
+        Err(AppError::NotAllowed(..) | AppError::InsufficientAuthorityInGroup(..)) => {
+            return render(&template, template.ctx.format);
+        }
+        Err(e) => return Err(e),
+    }
+
+    let scope = scope.map(str::trim).filter(|scope| !scope.is_empty());
+
+    let permission = permissions::get_one(perm.system_id, perm.perm_id, db.inner()).await?;
+    let scope = match permission {
+        Some(permission) if !permission.has_scope => None,
+        Some(_) if scope.is_some() => scope,
+        _ => return render(&template, template.ctx.format), // unknown or missing scope
+    };
+
+    template.preview = Some(
+        groups::permissions::preview_assignment(group.id, group.domain, &perm, scope, db.inner())
+            .await?,
+    );
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::get("/permission-assignments/unused?<months>")]
+async fn list_unused_permission_assignments(
+    months: Option<u32>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+) -> AppResult<RenderedTemplate> {
+    perms
+        .require(HivePermission::AssignPerms(SystemsScope::Any))
+        .await?;
+
+    let months = months
+        .unwrap_or(DEFAULT_UNUSED_MONTHS)
+        .clamp(1, MAX_UNUSED_MONTHS);
+
+    let cutoff = Local::now() - Months::new(months);
+    let assignments =
+        permissions::list_unused_assignments(cutoff, &ctx.lang, db.inner(), perms).await?;
+
+    let template = ListUnusedPermissionAssignmentsView {
+        ctx,
+        assignments,
+        months,
+        max_months: MAX_UNUSED_MONTHS,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+// living documentation of Hive's own permissions, generated from the code
+// (`HivePermission`) and cross-checked against what's in the database
+#[rocket::get("/permissions/hive")]
+async fn hive_permission_matrix(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    require_admin(&user, db.inner()).await?;
+
+    let mut registered = permissions::list_for_system(crate::HIVE_SYSTEM_ID, db.inner()).await?;
+    let mut assignments =
+        permissions::list_all_assignments_for_system(crate::HIVE_SYSTEM_ID, &ctx.lang, db.inner())
+            .await?;
+
+    let rows = HivePermission::ALL
+        .iter()
+        .map(|perm| {
+            let perm_id = perm.key();
+
+            HivePermissionMatrixRow {
+                perm_id,
+                scope_kind: perm.scope_kind(),
+                registered: registered
+                    .iter()
+                    .position(|p| p.perm_id == perm_id)
+                    .map(|i| registered.remove(i)),
+                assignments: assignments
+                    .extract_if(.., |a| a.perm_id == perm_id)
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let template = HivePermissionMatrixView {
+        ctx,
+        rows,
+        unknown: registered, // (whatever wasn't matched above)
+    };
+
+    render(&template, template.ctx.format)
+}
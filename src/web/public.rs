@@ -0,0 +1,232 @@
+use rinja::Template;
+use rocket::{Responder, State, http::Header, response::content::RawHtml, serde::json::Json};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{RenderedTemplate, filters, render};
+use crate::{
+    errors::AppResult,
+    guards::{context::PageContext, lang::Language},
+    models::{Group, GroupMember, MemberListShare},
+    resolver::IdentityResolver,
+    routing::RouteTree,
+    services::{
+        ReadReplica,
+        groups::{
+            self,
+            list::PublicDirectory,
+            shares::{self, ShareLinkSigner},
+        },
+        public_statistics::{PublicStatistics, PublicStatisticsDto},
+    },
+};
+
+// pages that can be viewed without logging in; they must never reveal more
+// than what is explicitly marked as public
+pub fn routes() -> RouteTree {
+    rocket::routes![
+        public_groups,
+        public_members_json,
+        public_members_embed,
+        public_statistics,
+        shared_members
+    ]
+    .into()
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "public/groups.html.j2")]
+struct PublicGroupsView {
+    ctx: PageContext,
+    groups: Vec<Group>,
+}
+
+// how long browsers and proxies may cache embeddable member lists for; they
+// are fetched on every visit to e.g. the chapter website, but rarely change
+const PUBLIC_MEMBERS_MAX_AGE: u32 = 5 * 60;
+
+#[derive(Template)]
+#[template(path = "public/members.html.j2")]
+struct PublicMembersEmbedView {
+    lang: Language,
+    group: Group,
+    members: Vec<GroupMember>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "public/shared-members.html.j2")]
+struct SharedMembersView {
+    ctx: PageContext,
+    group: Group,
+    share: MemberListShare,
+}
+
+#[derive(Serialize)]
+struct PublicMembersDto {
+    id: String,
+    domain: String,
+    name_sv: String,
+    name_en: String,
+    members: Vec<PublicMemberDto>,
+}
+
+// usernames are deliberately left out, since anyone can see these
+#[derive(Serialize)]
+struct PublicMemberDto {
+    display_name: Option<String>,
+    manager: bool,
+}
+
+#[derive(Responder)]
+enum PublicMembersResponse {
+    Json(Json<PublicMembersDto>, Header<'static>, Header<'static>),
+    Embed(RawHtml<String>, Header<'static>, Header<'static>),
+}
+
+#[derive(Responder)]
+struct PublicStatisticsResponse {
+    inner: Json<PublicStatisticsDto>,
+    cache_control: Header<'static>,
+    cors: Header<'static>,
+}
+
+fn cache_control() -> Header<'static> {
+    Header::new(
+        "Cache-Control",
+        format!("public, max-age={PUBLIC_MEMBERS_MAX_AGE}"),
+    )
+}
+
+// (404 if disabled, as if it didn't exist at all)
+#[rocket::get("/public/groups")]
+async fn public_groups(
+    directory: &State<PublicDirectory>,
+    replica: &State<ReadReplica>,
+    ctx: PageContext,
+) -> AppResult<Option<RenderedTemplate>> {
+    if !directory.is_enabled() {
+        return Ok(None);
+    }
+
+    let groups = groups::list::list_public(&ctx.lang, replica.pool()).await?;
+
+    let template = PublicGroupsView { ctx, groups };
+
+    Ok(Some(render(&template, template.ctx.format)?))
+}
+
+// meant to be fetched by scripts on other websites, hence any origin is
+// allowed (which is fine since no credentials are involved anyway)
+#[rocket::get("/public/group/<domain>/<id>/members.json")]
+async fn public_members_json(
+    id: &str,
+    domain: &str,
+    replica: &State<ReadReplica>,
+    resolver: &State<Option<IdentityResolver>>,
+) -> AppResult<Option<PublicMembersResponse>> {
+    // (404 unless tagged, as if the group didn't exist at all)
+    let Some((group, members)) =
+        groups::members::get_public_members(id, domain, replica.pool(), resolver.as_ref()).await?
+    else {
+        return Ok(None);
+    };
+
+    let dto = PublicMembersDto {
+        id: group.id,
+        domain: group.domain,
+        name_sv: group.name_sv,
+        name_en: group.name_en,
+        members: members
+            .into_iter()
+            .map(|member| PublicMemberDto {
+                display_name: member.display_name,
+                manager: member.manager,
+            })
+            .collect(),
+    };
+
+    Ok(Some(PublicMembersResponse::Json(
+        Json(dto),
+        cache_control(),
+        Header::new("Access-Control-Allow-Origin", "*"),
+    )))
+}
+
+// meant to be embedded in an <iframe> on other websites; Rocket's default
+// X-Frame-Options only allows the same origin, but browsers give precedence to
+// the CSP frame-ancestors directive
+#[rocket::get("/public/group/<domain>/<id>/members?<lang>")]
+async fn public_members_embed(
+    id: &str,
+    domain: &str,
+    lang: Option<Language>,
+    replica: &State<ReadReplica>,
+    resolver: &State<Option<IdentityResolver>>,
+) -> AppResult<Option<PublicMembersResponse>> {
+    let Some((group, members)) =
+        groups::members::get_public_members(id, domain, replica.pool(), resolver.as_ref()).await?
+    else {
+        return Ok(None);
+    };
+
+    let template = PublicMembersEmbedView {
+        lang: lang.unwrap_or(Language::Swedish),
+        group,
+        members,
+    };
+
+    Ok(Some(PublicMembersResponse::Embed(
+        RawHtml(template.render()?),
+        cache_control(),
+        Header::new("Content-Security-Policy", "frame-ancestors *"),
+    )))
+}
+
+// e.g. for "X active volunteers" on the chapter website; only includes the
+// metrics that were explicitly enabled (404 if none, as if it didn't exist)
+#[rocket::get("/public/statistics.json")]
+async fn public_statistics(
+    statistics: &State<PublicStatistics>,
+    replica: &State<ReadReplica>,
+) -> AppResult<Option<PublicStatisticsResponse>> {
+    if !statistics.is_enabled() {
+        return Ok(None);
+    }
+
+    let dto = statistics.get(replica.pool()).await?;
+
+    Ok(Some(PublicStatisticsResponse {
+        inner: Json(dto),
+        cache_control: cache_control(),
+        cors: Header::new("Access-Control-Allow-Origin", "*"),
+    }))
+}
+
+// signed links handed out by group managers to external parties; the snapshot
+// is read from the primary since it might have just been created or revoked
+// (404 for anything invalid, without revealing why)
+#[rocket::get("/shared/<share_id>?<expires>&<signature>")]
+async fn shared_members(
+    share_id: Uuid,
+    expires: i64,
+    signature: &str,
+    db: &State<PgPool>,
+    signer: &State<ShareLinkSigner>,
+    ctx: PageContext,
+) -> AppResult<Option<RenderedTemplate>> {
+    let Some(share) = shares::get_valid(&share_id, expires, signature, signer, db.inner()).await?
+    else {
+        return Ok(None);
+    };
+
+    let Some(group) =
+        groups::details::get_one(&share.group_id, &share.group_domain, db.inner()).await?
+    else {
+        return Ok(None);
+    };
+
+    let template = SharedMembersView { ctx, group, share };
+
+    Ok(Some(render(&template, template.ctx.format)?))
+}
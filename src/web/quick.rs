@@ -0,0 +1,138 @@
+use chrono::Days;
+use rinja::Template;
+use rocket::State;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{GracefulRedirect, RenderedTemplate, render};
+use crate::{
+    clock,
+    errors::{AppError, AppResult},
+    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
+    models::{Group, GroupMember, PendingChange, PendingChangeKind},
+    resolver::IdentityResolver,
+    routing::RouteTree,
+    services::{
+        changes,
+        groups::{self, AuthorityInGroup},
+    },
+};
+
+// condensed page for what managers most often need to do from their phones;
+// it's also what the installed app (PWA) opens on
+pub fn routes() -> RouteTree {
+    rocket::routes![quick_actions, extend_membership].into()
+}
+
+// how soon a membership must end to be suggested for extension
+const EXPIRY_HORIZON_DAYS: u64 = 30;
+
+#[derive(Serialize)]
+struct ManagedGroupOverview {
+    group: Group,
+    expiring: Vec<GroupMember>, // direct members that haven't been renewed yet
+    checked: Option<GroupMember>, // membership of the user being checked
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "quick.html.j2")]
+struct QuickActionsView<'r> {
+    ctx: PageContext,
+    check: Option<&'r str>,
+    groups: Vec<ManagedGroupOverview>,
+    any_expiring: bool,
+    changes: Vec<PendingChange>,
+    horizon: u64,
+}
+
+#[rocket::get("/quick?<check>")]
+async fn quick_actions(
+    check: Option<&str>,
+    db: &State<PgPool>,
+    resolver: &State<Option<IdentityResolver>>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    let check = check.map(str::trim).filter(|username| !username.is_empty());
+
+    let horizon = clock::today() + Days::new(EXPIRY_HORIZON_DAYS);
+
+    let mut overviews = vec![];
+    for group in groups::list::list_managed(db.inner(), &user).await? {
+        let expiring = groups::members::get_expiring_members(
+            &group.id,
+            &group.domain,
+            horizon,
+            db.inner(),
+            resolver.as_ref(),
+        )
+        .await?;
+
+        let checked = if let Some(username) = check {
+            groups::members::get_all_members(&group.id, &group.domain, None, db.inner(), None)
+                .await?
+                .into_iter()
+                .find(|member| member.username == username)
+        } else {
+            None
+        };
+
+        overviews.push(ManagedGroupOverview {
+            group,
+            expiring,
+            checked,
+        });
+    }
+
+    let changes = changes::list_relevant(db.inner(), perms, &user)
+        .await?
+        .into_iter()
+        .filter(|change| change.can_review == Some(true))
+        .collect();
+
+    let any_expiring = overviews
+        .iter()
+        .any(|overview| !overview.expiring.is_empty());
+
+    let template = QuickActionsView {
+        ctx,
+        check,
+        groups: overviews,
+        any_expiring,
+        changes,
+        horizon: EXPIRY_HORIZON_DAYS,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/quick/extend/<id>")]
+async fn extend_membership(
+    id: Uuid,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    let (group_id, group_domain) = groups::members::get_membership_group(&id, db.inner())
+        .await?
+        .ok_or_else(|| AppError::NoSuchMembership(id.to_string()))?;
+
+    groups::details::require_authority(
+        AuthorityInGroup::ManageMembers,
+        &group_id,
+        &group_domain,
+        db.inner(),
+        perms,
+        &user,
+    )
+    .await?;
+
+    // TODO: anti-CSRF
+
+    groups::members::bulk_extend(&[id], &group_id, &group_domain, perms, db.inner(), &user).await?;
+
+    Ok(GracefulRedirect::to("/quick", partial.is_some()))
+}
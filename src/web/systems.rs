@@ -1,43 +1,78 @@
+use chrono::Local;
 use log::*;
 use rinja::Template;
 use rocket::{
     Responder, State,
     form::{self, Contextual, Form},
     http::Header,
-    response::{Redirect, content::RawHtml},
+    response::Redirect,
     uri,
 };
+use serde::Serialize;
 use sqlx::PgPool;
+use uuid::Uuid;
 
-use super::{Either, GracefulRedirect, RenderedTemplate, filters};
+use super::{Either, GracefulRedirect, RenderedTemplate, filters, render};
 use crate::{
-    dto::systems::{CreateSystemDto, EditSystemDto},
+    dto::systems::{CreateSystemDto, CreateWebhookDto, EditSystemDto, TaskScheduleDto},
     errors::{AppError, AppResult},
     guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
-    models::System,
+    models::{
+        ActionKind, ApiToken, AuditLog, DailyApiUsage, IntegrationTaskLogEntry,
+        IntegrationTaskLogEntryKind, IntegrationTaskRun, Permission, PermissionGrant, System,
+        SystemDeletionRequest, SystemUsageStats, Webhook, WebhookDelivery, WebhookEvent,
+    },
     perms::{HivePermission, SystemsScope},
     routing::RouteTree,
-    services::systems,
+    services::{
+        api_tokens, audit_logs, integrations, permissions,
+        systems::{self, SystemDeletion},
+        webhooks,
+    },
 };
 
+// how many of the most recent task runs to show for an integration
+const TASK_RUNS_LIMIT: i64 = 50;
+
+// how many days of API usage to show in a system's statistics
+const USAGE_DAYS: u32 = 30;
+
+// how many of the most recent audit log entries to show for a system
+const RECENT_LOGS_LIMIT: u32 = 10;
+
+// how many of the most recent deliveries to show for a webhook
+const WEBHOOK_DELIVERIES_LIMIT: u32 = 50;
+
 pub fn routes() -> RouteTree {
     rocket::routes![
         list_systems,
         create_system,
         system_details,
+        system_stats,
         delete_system,
-        edit_system
+        cancel_system_deletion,
+        edit_system,
+        list_task_runs,
+        task_run_logs,
+        set_task_schedule,
+        simulate_permission_check,
+        list_webhooks,
+        create_webhook,
+        delete_webhook,
+        list_webhook_deliveries,
+        replay_webhook_delivery
     ]
     .into()
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "systems/list.html.j2")]
 struct ListSystemsView<'q, 'f, 'v> {
     ctx: PageContext,
     systems: Vec<System>,
     q: Option<&'q str>,
     fully_authorized: bool,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     create_form: &'f form::Context<'v>,
     create_modal_open: bool,
 }
@@ -45,7 +80,7 @@ struct ListSystemsView<'q, 'f, 'v> {
 // FIXME: separate Partial struct is only needed until the next Askama/Rinja
 // release; after that use new attr `blocks` (feature-gated) to impl many
 // methods for the same template struct
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "systems/list.html.j2", block = "inner_systems_listing")]
 struct PartialListSystemsView<'q> {
     ctx: PageContext,
@@ -53,14 +88,15 @@ struct PartialListSystemsView<'q> {
     q: Option<&'q str>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "systems/create.html.j2", block = "inner_create_form")]
 struct PartialCreateSystemView<'f, 'v> {
     ctx: PageContext,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     create_form: &'f form::Context<'v>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "systems/details.html.j2")]
 struct SystemDetailsView<'f, 'v> {
     ctx: PageContext,
@@ -69,30 +105,123 @@ struct SystemDetailsView<'f, 'v> {
     fully_authorized: bool,
     can_manage_permissions: bool,
     can_manage_tags: bool,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     api_token_create_form: &'f form::Context<'v>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     permission_create_form: &'f form::Context<'v>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     tag_create_form: &'f form::Context<'v>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     edit_form: &'f form::Context<'v>,
     edit_modal_open: bool,
+    deletion_protected: bool,
+    deletion_request: Option<SystemDeletionRequest>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "systems/stats.html.j2")]
+struct PartialSystemStatsView {
+    ctx: PageContext,
+    usage: Vec<DailyApiUsage>,
+    peak_usage: i64,
+    stats: SystemUsageStats,
+    recent_logs: Vec<AuditLog>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "systems/simulate.html.j2")]
+struct PartialSimulatorView<'r> {
+    ctx: PageContext,
+    system_id: &'r str,
+    permissions: Vec<Permission>,
+    api_tokens: Vec<ApiToken>,
+    username: Option<&'r str>,
+    token: Option<Uuid>,
+    perm: Option<&'r str>,
+    scope: Option<&'r str>,
+    outcome: Option<SimulationOutcome>,
+}
+
+#[derive(Serialize)]
+struct SimulationOutcome {
+    passed: bool,
+    grants: Vec<PermissionGrant>,
+    token_expired: bool, // (then it's never checked at all)
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "systems/edit.html.j2", block = "inner_edit_form")]
 struct PartialEditSystemView<'f, 'v> {
     ctx: PageContext,
     system: System,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     edit_form: &'f form::Context<'v>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "systems/edited.html.j2")]
 struct SystemEditedView<'f, 'v> {
     ctx: PageContext,
     system: System,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     edit_form: &'f form::Context<'v>,
     edit_modal_open: bool,
 }
 
+#[derive(Template, Serialize)]
+#[template(path = "systems/runs.html.j2")]
+struct ListTaskRunsView {
+    ctx: PageContext,
+    system: System,
+    tasks: Vec<TaskSummary>,
+    runs: Vec<IntegrationTaskRun>,
+}
+
+#[derive(Serialize)]
+struct TaskSummary {
+    id: &'static str,
+    default_schedule: &'static str,
+    schedule_override: Option<String>,
+    timeout_secs: u64,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "systems/run-logs.html.j2")]
+struct PartialTaskRunLogsView {
+    ctx: PageContext,
+    logs: Vec<IntegrationTaskLogEntry>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "systems/webhooks/list.html.j2")]
+struct ListWebhooksView<'f, 'v> {
+    ctx: PageContext,
+    system: System,
+    webhooks: Vec<Webhook>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
+    create_webhook_form: &'f form::Context<'v>,
+}
+
+#[derive(Template, Serialize)]
+#[template(
+    path = "systems/webhooks/create.html.j2",
+    block = "inner_create_webhook_form"
+)]
+struct PartialCreateWebhookView<'f, 'v> {
+    ctx: PageContext,
+    system: System,
+    #[serde(serialize_with = "super::serialize_form_errors")]
+    create_webhook_form: &'f form::Context<'v>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "systems/webhooks/deliveries.html.j2")]
+struct PartialListWebhookDeliveriesView {
+    ctx: PageContext,
+    webhook: Webhook,
+    deliveries: Vec<WebhookDelivery>,
+}
+
 #[rocket::get("/systems?<q>")]
 async fn list_systems(
     q: Option<&str>,
@@ -115,7 +244,7 @@ async fn list_systems(
     if partial.is_some() {
         let template = PartialListSystemsView { ctx, systems, q };
 
-        Ok(RawHtml(template.render()?))
+        render(&template, template.ctx.format)
     } else {
         let template = ListSystemsView {
             ctx,
@@ -126,7 +255,7 @@ async fn list_systems(
             create_modal_open: false,
         };
 
-        Ok(RawHtml(template.render()?))
+        render(&template, template.ctx.format)
     }
 }
 
@@ -162,7 +291,7 @@ async fn create_system<'v>(
                 create_form: &form.context,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             let systems = systems::list_manageable(None, true, db.inner(), perms).await?;
 
@@ -175,7 +304,7 @@ async fn create_system<'v>(
                 create_modal_open: true,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         }
     }
 }
@@ -209,6 +338,9 @@ pub async fn system_details(
         .satisfies(HivePermission::ManageTags(SystemsScope::Id(id.to_owned())))
         .await?;
 
+    let deletion_protected = systems::is_deletion_protected(id, db.inner()).await?;
+    let deletion_request = systems::get_deletion_request(id, db.inner()).await?;
+
     let empty_form = form::Context::default();
 
     let template = SystemDetailsView {
@@ -223,14 +355,53 @@ pub async fn system_details(
         tag_create_form: &empty_form,
         edit_form: &empty_form,
         edit_modal_open: false,
+        deletion_protected,
+        deletion_request,
     };
 
-    Ok(RawHtml(template.render()?))
+    render(&template, template.ctx.format)
 }
 
-#[rocket::delete("/system/<id>")]
+#[rocket::get("/system/<id>/stats")]
+async fn system_stats(
+    id: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // stats are only shown as part of system details
+        return Ok(Either::Right(Redirect::to(uri!(system_details(id)))));
+    }
+
+    if !perms.satisfies(HivePermission::ManageSystems).await? {
+        let scope = SystemsScope::Id(id.to_owned());
+        perms.require(HivePermission::ManageSystem(scope)).await?;
+    }
+
+    systems::ensure_exists(id, db.inner()).await?;
+
+    let usage = systems::get_daily_usage(id, USAGE_DAYS, db.inner()).await?;
+    let peak_usage = usage.iter().map(|u| u.n_requests).max().unwrap_or(0);
+    let stats = systems::get_usage_stats(id, db.inner()).await?;
+    let recent_logs = audit_logs::list_recent_for_system(id, RECENT_LOGS_LIMIT, db.inner()).await?;
+
+    let template = PartialSystemStatsView {
+        ctx,
+        usage,
+        peak_usage,
+        stats,
+        recent_logs,
+    };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
+
+#[rocket::delete("/system/<id>?<confirmation>")]
 pub async fn delete_system(
     id: &str,
+    confirmation: Option<&str>,
     db: &State<PgPool>,
     perms: &PermsEvaluator,
     user: User,
@@ -240,11 +411,36 @@ pub async fn delete_system(
 
     // TODO: anti-CSRF(?), DELETE isn't a normal form method
 
-    systems::delete(id, db.inner(), &user).await?;
+    match systems::delete(id, confirmation, db.inner(), &user).await? {
+        // TODO: show visual confirmation of successful delete in systems list
+        SystemDeletion::Done => Ok(GracefulRedirect::to(
+            uri!(list_systems(None::<&str>)),
+            partial.is_some(),
+        )),
+        // (details page shows that confirmation is pending)
+        SystemDeletion::Requested => Ok(GracefulRedirect::to(
+            uri!(system_details(id)),
+            partial.is_some(),
+        )),
+    }
+}
+
+#[rocket::delete("/system/<id>/deletion-request")]
+async fn cancel_system_deletion(
+    id: &str,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    perms.require(HivePermission::ManageSystems).await?;
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    systems::cancel_deletion_request(id, db.inner(), &user).await?;
 
-    // TODO: show visual confirmation of successful delete in systems list
     Ok(GracefulRedirect::to(
-        uri!(list_systems(None::<&str>)),
+        uri!(system_details(id)),
         partial.is_some(),
     ))
 }
@@ -287,7 +483,7 @@ pub async fn edit_system<'v>(
             };
 
             Ok(EditSystemResponse::SuccessPartial(
-                RawHtml(template.render()?),
+                render(&template, template.ctx.format)?,
                 Header::new("HX-Retarget", "#edit-system"),
                 Header::new("HX-Reswap", "outerHTML"),
             ))
@@ -310,7 +506,10 @@ pub async fn edit_system<'v>(
                 edit_form: &form.context,
             };
 
-            Ok(EditSystemResponse::Invalid(RawHtml(template.render()?)))
+            Ok(EditSystemResponse::Invalid(render(
+                &template,
+                template.ctx.format,
+            )?))
         } else {
             let is_integration = crate::integrations::integration_exists(id);
 
@@ -321,6 +520,9 @@ pub async fn edit_system<'v>(
                 .satisfies(HivePermission::ManageTags(SystemsScope::Id(id.to_owned())))
                 .await?;
 
+            let deletion_protected = systems::is_deletion_protected(id, db.inner()).await?;
+            let deletion_request = systems::get_deletion_request(id, db.inner()).await?;
+
             let empty_form = form::Context::default();
 
             let template = SystemDetailsView {
@@ -335,9 +537,371 @@ pub async fn edit_system<'v>(
                 tag_create_form: &empty_form,
                 edit_form: &form.context,
                 edit_modal_open: true,
+                deletion_protected,
+                deletion_request,
+            };
+
+            Ok(EditSystemResponse::Invalid(render(
+                &template,
+                template.ctx.format,
+            )?))
+        }
+    }
+}
+
+#[rocket::get("/system/<id>/runs")]
+async fn list_task_runs(
+    id: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+) -> AppResult<RenderedTemplate> {
+    if !perms.satisfies(HivePermission::ManageSystems).await? {
+        let scope = SystemsScope::Id(id.to_owned());
+        perms.require(HivePermission::ManageSystem(scope)).await?;
+    }
+
+    if !crate::integrations::integration_exists(id) {
+        // only integrations have tasks
+        return Err(AppError::NoSuchSystem(id.to_owned()));
+    }
+
+    let system = systems::get_one(id, db.inner())
+        .await?
+        .ok_or_else(|| AppError::NoSuchSystem(id.to_owned()))?;
+
+    let mut overrides = integrations::list_task_schedules(id, db.inner()).await?;
+
+    let tasks = crate::integrations::MANIFESTS
+        .iter()
+        .filter(|manifest| manifest.id == id)
+        .flat_map(|manifest| manifest.tasks.iter().map(move |task| (manifest, task)))
+        .map(|(manifest, task)| TaskSummary {
+            id: task.id,
+            default_schedule: task.schedule,
+            schedule_override: overrides.remove(task.id),
+            timeout_secs: crate::integrations::task_timeout(manifest, task).as_secs(),
+        })
+        .collect();
+
+    let runs = integrations::list_recent_task_runs(id, TASK_RUNS_LIMIT, db.inner()).await?;
+
+    let template = ListTaskRunsView {
+        ctx,
+        system,
+        tasks,
+        runs,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::get("/system/<id>/run/<run_id>/logs")]
+async fn task_run_logs(
+    id: &str,
+    run_id: Uuid,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+) -> AppResult<RenderedTemplate> {
+    if !perms.satisfies(HivePermission::ManageSystems).await? {
+        let scope = SystemsScope::Id(id.to_owned());
+        perms.require(HivePermission::ManageSystem(scope)).await?;
+    }
+
+    let logs = integrations::get_task_run_logs(id, &run_id, db.inner()).await?;
+
+    let template = PartialTaskRunLogsView { ctx, logs };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/system/<id>/task/<task_id>/schedule", data = "<form>")]
+async fn set_task_schedule(
+    id: &str,
+    task_id: &str,
+    form: Form<TaskScheduleDto<'_>>,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+) -> AppResult<Redirect> {
+    if !perms.satisfies(HivePermission::ManageSystems).await? {
+        let scope = SystemsScope::Id(id.to_owned());
+        perms.require(HivePermission::ManageSystem(scope)).await?;
+    }
+
+    // TODO: anti-CSRF
+
+    let schedule = Some(*form.schedule).filter(|schedule| !schedule.is_empty());
+
+    integrations::set_task_schedule(id, task_id, schedule, db.inner(), &user).await?;
+
+    info!(
+        "Schedule of task {task_id} (integration {id}) set to {schedule:?} by {}",
+        user.username()
+    );
+
+    Ok(Redirect::to(uri!(list_task_runs(id))))
+}
+
+// renders the simulator form, and also the outcome once everything needed for
+// a check is given; matching goes through exactly the same service functions
+// as the API, so the outcome is guaranteed to be what a system would get
+#[rocket::get("/system/<id>/simulate?<username>&<token>&<perm>&<scope>")]
+#[allow(clippy::too_many_arguments)]
+async fn simulate_permission_check<'r>(
+    id: &'r str,
+    username: Option<&'r str>,
+    token: Option<Uuid>,
+    perm: Option<&'r str>,
+    scope: Option<&'r str>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // the simulator is only shown as part of system details
+        return Ok(Either::Right(Redirect::to(uri!(system_details(id)))));
+    }
+
+    if !perms.satisfies(HivePermission::ManageSystems).await? {
+        let scope = SystemsScope::Id(id.to_owned());
+        perms.require(HivePermission::ManageSystem(scope)).await?;
+    }
+
+    systems::ensure_exists(id, db.inner()).await?;
+
+    let username = username.map(str::trim).filter(|u| !u.is_empty());
+    let perm = perm.filter(|p| !p.is_empty());
+    let scope = scope.map(str::trim).filter(|s| !s.is_empty());
+
+    let permissions = permissions::list_for_system(id, db.inner()).await?;
+    let api_tokens = api_tokens::list_holding_permissions_of(id, db.inner()).await?;
+
+    // unknown tokens are ignored, just like unknown secrets in the API
+    let api_token = token.and_then(|token| api_tokens.iter().find(|t| t.id == token));
+
+    let outcome = match (api_token, username, perm) {
+        (Some(api_token), _, Some(perm_id)) => {
+            if api_token
+                .expires_at
+                .is_some_and(|expires_at| expires_at < Local::now())
+            {
+                Some(SimulationOutcome {
+                    passed: false,
+                    grants: vec![],
+                    token_expired: true,
+                })
+            } else {
+                let matched =
+                    permissions::find_token_matches(api_token.id, id, perm_id, scope, db.inner())
+                        .await?;
+                let grants = permissions::explain_matches(&matched, None, db.inner()).await?;
+
+                Some(SimulationOutcome {
+                    passed: !matched.is_empty(),
+                    grants,
+                    token_expired: false,
+                })
+            }
+        }
+        (None, Some(username), Some(perm_id)) => {
+            let matched =
+                permissions::find_user_matches(username, id, perm_id, scope, db.inner()).await?;
+            let grants = permissions::explain_matches(&matched, Some(username), db.inner()).await?;
+
+            Some(SimulationOutcome {
+                passed: !matched.is_empty(),
+                grants,
+                token_expired: false,
+            })
+        }
+        _ => None, // incomplete input
+    };
+
+    let token = api_token.map(|t| t.id);
+
+    let template = PartialSimulatorView {
+        ctx,
+        system_id: id,
+        permissions,
+        api_tokens,
+        username,
+        token,
+        perm,
+        scope,
+        outcome,
+    };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
+
+#[rocket::get("/system/<id>/webhooks")]
+async fn list_webhooks(
+    id: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+) -> AppResult<RenderedTemplate> {
+    if !perms.satisfies(HivePermission::ManageSystems).await? {
+        let scope = SystemsScope::Id(id.to_owned());
+        perms.require(HivePermission::ManageSystem(scope)).await?;
+    }
+
+    let system = systems::get_one(id, db.inner())
+        .await?
+        .ok_or_else(|| AppError::NoSuchSystem(id.to_owned()))?;
+
+    let webhooks = webhooks::list_for_system(id, db.inner()).await?;
+
+    let template = ListWebhooksView {
+        ctx,
+        system,
+        webhooks,
+        create_webhook_form: &form::Context::default(),
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/system/<id>/webhooks", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn create_webhook<'v>(
+    id: &str,
+    form: Form<Contextual<'v, CreateWebhookDto<'v>>>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, GracefulRedirect>> {
+    if !perms.satisfies(HivePermission::ManageSystems).await? {
+        let scope = SystemsScope::Id(id.to_owned());
+        perms.require(HivePermission::ManageSystem(scope)).await?;
+    }
+
+    // TODO: anti-CSRF
+
+    let target = uri!(list_webhooks(id));
+
+    if let Some(dto) = &form.value {
+        // validation passed
+
+        webhooks::create_new(id, dto, db.inner(), &user).await?;
+
+        Ok(Either::Right(GracefulRedirect::to(
+            target,
+            partial.is_some(),
+        )))
+    } else {
+        // some errors are present; show the form again
+        debug!("Create webhook form errors: {:?}", &form.context);
+
+        if partial.is_some() {
+            let system = systems::get_one(id, db.inner())
+                .await?
+                .ok_or_else(|| AppError::NoSuchSystem(id.to_owned()))?;
+
+            let template = PartialCreateWebhookView {
+                ctx,
+                system,
+                create_webhook_form: &form.context,
             };
 
-            Ok(EditSystemResponse::Invalid(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
+        } else {
+            // FIXME: this just resets the form without actually showing
+            // any validation error indicators... but there isn't a great
+            // alternative, and it might be fine for such a tiny form
+
+            Ok(Either::Right(GracefulRedirect::to(target, false)))
         }
     }
 }
+
+#[rocket::delete("/system/<id>/webhook/<webhook_id>")]
+async fn delete_webhook(
+    id: &str,
+    webhook_id: Uuid,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    if !perms.satisfies(HivePermission::ManageSystems).await? {
+        let scope = SystemsScope::Id(id.to_owned());
+        perms.require(HivePermission::ManageSystem(scope)).await?;
+    }
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    webhooks::delete(id, webhook_id, db.inner(), &user).await?;
+
+    Ok(GracefulRedirect::to(
+        uri!(list_webhooks(id)),
+        partial.is_some(),
+    ))
+}
+
+#[rocket::get("/system/<id>/webhook/<webhook_id>/deliveries")]
+async fn list_webhook_deliveries(
+    id: &str,
+    webhook_id: Uuid,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a table, not a full page;
+        // redirect to the system's webhooks
+
+        return Ok(Either::Right(Redirect::to(uri!(list_webhooks(id)))));
+    }
+
+    if !perms.satisfies(HivePermission::ManageSystems).await? {
+        let scope = SystemsScope::Id(id.to_owned());
+        perms.require(HivePermission::ManageSystem(scope)).await?;
+    }
+
+    let webhook = webhooks::require_one(id, webhook_id, db.inner()).await?;
+
+    let deliveries =
+        webhooks::list_recent_deliveries(webhook_id, WEBHOOK_DELIVERIES_LIMIT, db.inner()).await?;
+
+    let template = PartialListWebhookDeliveriesView {
+        ctx,
+        webhook,
+        deliveries,
+    };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
+
+#[rocket::post("/system/<id>/webhook/<webhook_id>/delivery/<delivery_id>/replay")]
+async fn replay_webhook_delivery(
+    id: &str,
+    webhook_id: Uuid,
+    delivery_id: Uuid,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<GracefulRedirect> {
+    if !perms.satisfies(HivePermission::ManageSystems).await? {
+        let scope = SystemsScope::Id(id.to_owned());
+        perms.require(HivePermission::ManageSystem(scope)).await?;
+    }
+
+    // TODO: anti-CSRF
+
+    // (ensures the webhook actually belongs to this system)
+    webhooks::require_one(id, webhook_id, db.inner()).await?;
+
+    webhooks::replay(webhook_id, delivery_id, db.inner()).await?;
+
+    Ok(GracefulRedirect::to(
+        uri!(list_webhooks(id)),
+        partial.is_some(),
+    ))
+}
@@ -3,22 +3,38 @@ use rinja::Template;
 use rocket::{
     State,
     form::{self, Contextual, Form},
-    response::{Redirect, content::RawHtml},
+    response::{Flash, Redirect},
     uri,
 };
+use serde::Serialize;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use super::{Either, GracefulRedirect, RenderedTemplate};
+use super::{Either, GracefulRedirect, RenderedTemplate, deletions, render};
 use crate::{
-    dto::tags::{AssignTagToGroupDto, AssignTagToUserDto, CreateSubtagDto, CreateTagDto},
-    errors::AppResult,
-    guards::{context::PageContext, headers::HxRequest, perms::PermsEvaluator, user::User},
-    models::{AffiliatedTagAssignment, Tag},
-    perms::{HivePermission, SystemsScope},
+    dto::{
+        errors::AppErrorDto,
+        tags::{
+            AssignTagToGroupDto, AssignTagToUserDto, CreateSubtagDto, CreateTagDerivationDto,
+            CreateTagDto, DelegateTagDto, EditTagAssignmentDto,
+        },
+    },
+    errors::{AppError, AppResult},
+    guards::{
+        context::PageContext, format::ResponseFormat, headers::HxRequest, perms::PermsEvaluator,
+        user::User,
+    },
+    models::{
+        AffiliatedTagAssignment, EffectiveTagAssignment, Tag, TagDelegation, TagDerivation, TagRef,
+    },
+    perms::{HivePermission, SystemsScope, TagScope},
     resolver::IdentityResolver,
     routing::RouteTree,
-    services::{systems, tags},
+    services::{
+        changes::ProtectedDomains,
+        systems,
+        tags::{self, HierarchyDirection},
+    },
 };
 
 pub fn routes() -> RouteTree {
@@ -32,14 +48,24 @@ pub fn routes() -> RouteTree {
         assign_tag_to_group,
         assign_tag_to_user,
         unassign_tag,
+        edit_tag_assignment_content,
         list_subtags,
+        show_tag_hierarchy,
+        list_effective_tag_assignments,
         create_subtag,
-        unlink_subtag
+        unlink_subtag,
+        list_subtag_suggestions,
+        list_tag_derivations,
+        create_tag_derivation,
+        delete_tag_derivation,
+        list_tag_delegations,
+        delegate_tag,
+        revoke_tag_delegation
     ]
     .into()
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "tags/list.html.j2")]
 struct ListTagsView {
     ctx: PageContext,
@@ -47,28 +73,36 @@ struct ListTagsView {
     can_manage: bool,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "tags/create.html.j2", block = "inner_create_tag_form")]
 struct PartialCreateTagView<'f, 'v> {
     ctx: PageContext,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     tag_create_form: &'f form::Context<'v>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "tags/details.html.j2")]
 struct TagDetailsView<'f, 'v> {
     ctx: PageContext,
     tag: Tag,
     fully_authorized: bool,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     assign_to_group_form: &'f form::Context<'v>,
     assign_to_group_success: Option<AffiliatedTagAssignment>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     assign_to_user_form: &'f form::Context<'v>,
     assign_to_user_success: Option<AffiliatedTagAssignment>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     add_subtag_form: &'f form::Context<'v>,
     add_subtag_success: Option<Tag>,
+    add_subtag_error: Option<AppErrorDto>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
+    add_derivation_form: &'f form::Context<'v>,
+    add_derivation_success: Option<TagDerivation>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "tags/groups/list.html.j2")]
 struct PartialListTagGroupsView {
     ctx: PageContext,
@@ -77,7 +111,7 @@ struct PartialListTagGroupsView {
     tag_assignments: Vec<AffiliatedTagAssignment>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "tags/users/list.html.j2")]
 struct PartialListTagUsersView {
     ctx: PageContext,
@@ -86,7 +120,23 @@ struct PartialListTagUsersView {
     tag_assignments: Vec<AffiliatedTagAssignment>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
+#[template(path = "tags/content-cell.html.j2")]
+struct PartialTagAssignmentContentView {
+    ctx: PageContext,
+    assignment: AffiliatedTagAssignment,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "tags/content-cell.html.j2")]
+struct PartialEditTagAssignmentContentView<'f, 'v> {
+    ctx: PageContext,
+    assignment: AffiliatedTagAssignment,
+    #[serde(serialize_with = "super::serialize_form_errors")]
+    edit_content_form: &'f form::Context<'v>,
+}
+
+#[derive(Template, Serialize)]
 #[template(path = "tags/subtags/list.html.j2")]
 struct PartialListSubtagsView {
     ctx: PageContext,
@@ -95,7 +145,38 @@ struct PartialListSubtagsView {
     can_unassign: bool,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
+#[template(path = "tags/subtags/suggestions.html.j2")]
+struct PartialSubtagSuggestionsView {
+    candidates: Vec<Tag>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "tags/derivations/list.html.j2")]
+struct PartialListTagDerivationsView {
+    ctx: PageContext,
+    tag: Tag,
+    derivations: Vec<TagDerivation>,
+    can_delete: bool,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "tags/hierarchy.html.j2")]
+struct PartialTagHierarchyView {
+    ctx: PageContext,
+    tag: Tag,
+    ancestor_paths: Vec<Vec<TagRef>>,
+    descendant_paths: Vec<Vec<TagRef>>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "tags/effective.html.j2")]
+struct PartialListEffectiveTagAssignmentsView {
+    ctx: PageContext,
+    assignments: Vec<EffectiveTagAssignment>,
+}
+
+#[derive(Template, Serialize)]
 #[template(
     path = "tags/groups/assign.html.j2",
     block = "inner_assign_to_group_form"
@@ -103,11 +184,12 @@ struct PartialListSubtagsView {
 struct AssignTagToGroupView<'f, 'v> {
     ctx: PageContext,
     tag: Tag,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     assign_to_group_form: &'f form::Context<'v>,
     assign_to_group_success: Option<AffiliatedTagAssignment>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(
     path = "tags/users/assign.html.j2",
     block = "inner_assign_to_user_form"
@@ -115,17 +197,59 @@ struct AssignTagToGroupView<'f, 'v> {
 struct AssignTagToUserView<'f, 'v> {
     ctx: PageContext,
     tag: Tag,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     assign_to_user_form: &'f form::Context<'v>,
     assign_to_user_success: Option<AffiliatedTagAssignment>,
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "tags/subtags/add.html.j2", block = "inner_add_subtag_form")]
 struct AddSubtagView<'f, 'v> {
     ctx: PageContext,
     tag: Tag,
+    #[serde(serialize_with = "super::serialize_form_errors")]
     add_subtag_form: &'f form::Context<'v>,
     add_subtag_success: Option<Tag>,
+    add_subtag_error: Option<AppErrorDto>,
+}
+
+#[derive(Template, Serialize)]
+#[template(
+    path = "tags/derivations/add.html.j2",
+    block = "inner_add_derivation_form"
+)]
+struct AddTagDerivationView<'f, 'v> {
+    ctx: PageContext,
+    tag: Tag,
+    #[serde(serialize_with = "super::serialize_form_errors")]
+    add_derivation_form: &'f form::Context<'v>,
+    add_derivation_success: Option<TagDerivation>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "tags/delegations/list.html.j2")]
+struct TagDelegationsView<'f, 'v> {
+    ctx: PageContext,
+    system_id: String,
+    tags: Vec<Tag>,
+    delegations: Vec<TagDelegation>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
+    delegate_form: &'f form::Context<'v>,
+    delegate_success: Option<TagDelegation>,
+}
+
+#[derive(Template, Serialize)]
+#[template(
+    path = "tags/delegations/delegate.html.j2",
+    block = "inner_delegate_form"
+)]
+struct DelegateTagView<'f, 'v> {
+    ctx: PageContext,
+    system_id: String,
+    tags: Vec<Tag>,
+    #[serde(serialize_with = "super::serialize_form_errors")]
+    delegate_form: &'f form::Context<'v>,
+    delegate_success: Option<TagDelegation>,
 }
 
 #[rocket::get("/system/<system_id>/tags")]
@@ -136,7 +260,7 @@ async fn list_tags(
     perms: &PermsEvaluator,
     partial: Option<HxRequest<'_>>,
 ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-    if partial.is_none() {
+    if partial.is_none() && !ctx.format.is_json() {
         // we only know how to render a table, not a full page;
         // redirect to system details
 
@@ -169,7 +293,7 @@ async fn list_tags(
             .await?,
     };
 
-    Ok(Either::Left(RawHtml(template.render()?)))
+    Ok(Either::Left(render(&template, template.ctx.format)?))
 }
 
 #[rocket::post("/system/<system_id>/tags", data = "<form>")]
@@ -208,7 +332,7 @@ async fn create_tag<'v>(
                 tag_create_form: &form.context,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
             // any validation error indicators... but there isn't a great
@@ -229,6 +353,7 @@ async fn tag_details(
     perms: &PermsEvaluator,
 ) -> AppResult<RenderedTemplate> {
     let possibilities = [
+        HivePermission::AssignTag(TagScope::Id(system_id.to_owned(), tag_id.to_owned())),
         HivePermission::AssignTags(SystemsScope::Id(system_id.to_owned())),
         HivePermission::ManageTags(SystemsScope::Id(system_id.to_owned())),
     ];
@@ -250,9 +375,12 @@ async fn tag_details(
         assign_to_user_success: None,
         add_subtag_form: &empty_form,
         add_subtag_success: None,
+        add_subtag_error: None,
+        add_derivation_form: &empty_form,
+        add_derivation_success: None,
     };
 
-    Ok(RawHtml(template.render()?))
+    render(&template, template.ctx.format)
 }
 
 #[rocket::delete("/system/<system_id>/tag/<tag_id>")]
@@ -263,18 +391,19 @@ pub async fn delete_tag(
     perms: &PermsEvaluator,
     user: User,
     partial: Option<HxRequest<'_>>,
-) -> AppResult<GracefulRedirect> {
+) -> AppResult<Flash<GracefulRedirect>> {
     let min = HivePermission::ManageTags(SystemsScope::Id(system_id.to_owned()));
     perms.require(min).await?;
 
     // TODO: anti-CSRF(?), DELETE isn't a normal form method
 
-    tags::delete(system_id, tag_id, db.inner(), &user).await?;
+    let deletion_id = tags::delete(system_id, tag_id, db.inner(), &user).await?;
+
+    let target = uri!(super::systems::system_details(system_id));
 
-    // TODO: show visual confirmation of successful delete in tags list
-    Ok(GracefulRedirect::to(
-        uri!(super::systems::system_details(system_id)),
-        partial.is_some(),
+    Ok(deletions::undoable(
+        GracefulRedirect::to(target, partial.is_some()),
+        deletion_id,
     ))
 }
 
@@ -290,7 +419,7 @@ macro_rules! list_tag_assignments {
             perms: &PermsEvaluator,
             partial: Option<HxRequest<'_>>,
         ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-            if partial.is_none() {
+            if partial.is_none() && !ctx.format.is_json() {
                 // we only know how to render a table, not a full page;
                 // redirect to tag details
 
@@ -301,6 +430,10 @@ macro_rules! list_tag_assignments {
 
             perms
                 .require_any_of(&[
+                    HivePermission::AssignTag(TagScope::Id(
+                        system_id.to_owned(),
+                        tag_id.to_owned(),
+                    )),
                     HivePermission::AssignTags(SystemsScope::Id(system_id.to_owned())),
                     HivePermission::ManageTags(SystemsScope::Id(system_id.to_owned())),
                 ])
@@ -333,7 +466,7 @@ macro_rules! list_tag_assignments {
                 tag_assignments,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         }
     };
 }
@@ -368,8 +501,8 @@ async fn assign_tag_to_group<'v>(
     user: User,
     partial: Option<HxRequest<'_>>,
 ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-    let min = HivePermission::AssignTags(SystemsScope::Id(system_id.to_string()));
-    perms.require(min).await?;
+    let possibilities = HivePermission::assign_tag(system_id, tag_id);
+    perms.require_any_of(&possibilities).await?;
 
     // TODO: anti-CSRF
 
@@ -390,7 +523,7 @@ async fn assign_tag_to_group<'v>(
                 assign_to_group_success: Some(assignment),
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: maybe allow passing ?assigned_to_group=id@domain
 
@@ -409,7 +542,7 @@ async fn assign_tag_to_group<'v>(
                 assign_to_group_success: None,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
             // any validation error indicators... but there isn't a great
@@ -434,8 +567,8 @@ async fn assign_tag_to_user<'v>(
     user: User,
     partial: Option<HxRequest<'_>>,
 ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-    let min = HivePermission::AssignTags(SystemsScope::Id(system_id.to_string()));
-    perms.require(min).await?;
+    let possibilities = HivePermission::assign_tag(system_id, tag_id);
+    perms.require_any_of(&possibilities).await?;
 
     // TODO: anti-CSRF
 
@@ -456,7 +589,7 @@ async fn assign_tag_to_user<'v>(
                 assign_to_user_success: Some(assignment),
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: maybe allow passing ?assigned_to_user=username
 
@@ -475,7 +608,7 @@ async fn assign_tag_to_user<'v>(
                 assign_to_user_success: None,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
             // any validation error indicators... but there isn't a great
@@ -509,6 +642,63 @@ async fn unassign_tag(
     }
 }
 
+#[rocket::patch("/tag-assignment/<id>", data = "<form>")]
+async fn edit_tag_assignment_content<'v>(
+    id: Uuid,
+    form: Form<Contextual<'v, EditTagAssignmentDto<'v>>>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    // perms can only be checked later, not enough info now
+
+    // TODO: anti-CSRF
+
+    if let Some(dto) = &form.value {
+        // validation passed
+
+        let assignment = tags::update_content(id, dto, db.inner(), perms, &user).await?;
+
+        if partial.is_some() {
+            let template = PartialTagAssignmentContentView { ctx, assignment };
+
+            Ok(Either::Left(render(&template, template.ctx.format)?))
+        } else {
+            let target = uri!(tag_details(
+                system_id = assignment.system_id,
+                tag_id = assignment.tag_id
+            ));
+            Ok(Either::Right(Redirect::to(target)))
+        }
+    } else {
+        // some errors are present; show the form again
+        debug!("Edit tag assignment form errors: {:?}", &form.context);
+
+        let assignment = tags::require_assignment(id, db.inner(), perms).await?;
+
+        if partial.is_some() {
+            let template = PartialEditTagAssignmentContentView {
+                ctx,
+                assignment,
+                edit_content_form: &form.context,
+            };
+
+            Ok(Either::Left(render(&template, template.ctx.format)?))
+        } else {
+            // FIXME: this just resets the form without actually showing
+            // any validation error indicators (see `create_subtag`)
+
+            let target = uri!(tag_details(
+                system_id = assignment.system_id,
+                tag_id = assignment.tag_id
+            ));
+            Ok(Either::Right(Redirect::to(target)))
+        }
+    }
+}
+
 #[rocket::get("/system/<system_id>/tag/<tag_id>/subtags")]
 async fn list_subtags(
     system_id: &str,
@@ -518,7 +708,7 @@ async fn list_subtags(
     perms: &PermsEvaluator,
     partial: Option<HxRequest<'_>>,
 ) -> AppResult<Either<RenderedTemplate, Redirect>> {
-    if partial.is_none() {
+    if partial.is_none() && !ctx.format.is_json() {
         // we only know how to render a table, not a full page;
         // redirect to tag details
 
@@ -551,7 +741,88 @@ async fn list_subtags(
         can_unassign,
     };
 
-    Ok(Either::Left(RawHtml(template.render()?)))
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
+
+#[rocket::get("/system/<system_id>/tag/<tag_id>/hierarchy")]
+async fn show_tag_hierarchy(
+    system_id: &str,
+    tag_id: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a fragment, not a full page;
+        // redirect to tag details
+
+        let target = uri!(tag_details(system_id = system_id, tag_id = tag_id));
+        return Ok(Either::Right(Redirect::to(target)));
+    }
+
+    perms
+        .require_any_of(&[
+            HivePermission::AssignTag(TagScope::Id(system_id.to_owned(), tag_id.to_owned())),
+            HivePermission::AssignTags(SystemsScope::Id(system_id.to_owned())),
+            HivePermission::ManageTags(SystemsScope::Id(system_id.to_owned())),
+        ])
+        .await?;
+
+    let tag = tags::require_one(system_id, tag_id, db.inner()).await?;
+
+    let ancestor_paths =
+        tags::list_hierarchy_paths(system_id, tag_id, HierarchyDirection::Ancestors, db.inner())
+            .await?;
+
+    let descendant_paths = tags::list_hierarchy_paths(
+        system_id,
+        tag_id,
+        HierarchyDirection::Descendants,
+        db.inner(),
+    )
+    .await?;
+
+    let template = PartialTagHierarchyView {
+        ctx,
+        tag,
+        ancestor_paths,
+        descendant_paths,
+    };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
+
+#[rocket::get("/system/<system_id>/tag/<tag_id>/effective")]
+async fn list_effective_tag_assignments(
+    system_id: &str,
+    tag_id: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a table, not a full page;
+        // redirect to tag details
+
+        let target = uri!(tag_details(system_id = system_id, tag_id = tag_id));
+        return Ok(Either::Right(Redirect::to(target)));
+    }
+
+    perms
+        .require_any_of(&[
+            HivePermission::AssignTag(TagScope::Id(system_id.to_owned(), tag_id.to_owned())),
+            HivePermission::AssignTags(SystemsScope::Id(system_id.to_owned())),
+            HivePermission::ManageTags(SystemsScope::Id(system_id.to_owned())),
+        ])
+        .await?;
+
+    let assignments = tags::list_effective_assignments(system_id, tag_id, db.inner()).await?;
+
+    let template = PartialListEffectiveTagAssignmentsView { ctx, assignments };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
 }
 
 #[rocket::post("/system/<system_id>/tag/<tag_id>/subtags", data = "<form>")]
@@ -559,7 +830,7 @@ async fn list_subtags(
 async fn create_subtag<'v>(
     system_id: &str,
     tag_id: &str,
-    form: Form<Contextual<'v, CreateSubtagDto<'v>>>,
+    mut form: Form<Contextual<'v, CreateSubtagDto<'v>>>,
     db: &State<PgPool>,
     ctx: PageContext,
     perms: &PermsEvaluator,
@@ -573,44 +844,233 @@ async fn create_subtag<'v>(
 
     // TODO: anti-CSRF
 
+    let mut add_subtag_error = None;
+
     if let Some(dto) = &form.value {
         // validation passed
 
-        let mut subtag = tags::create_subtag(system_id, tag_id, dto, db.inner(), &user).await?;
-        subtag.set_can_view(perms).await?;
+        match tags::create_subtag(system_id, tag_id, dto, db.inner(), &user).await {
+            Ok(mut subtag) => {
+                subtag.set_can_view(perms).await?;
+
+                if partial.is_some() {
+                    let template = AddSubtagView {
+                        ctx,
+                        tag,
+                        add_subtag_form: &form::Context::default(),
+                        add_subtag_success: Some(subtag),
+                        add_subtag_error: None,
+                    };
+
+                    return Ok(Either::Left(render(&template, template.ctx.format)?));
+                } else {
+                    // FIXME: maybe allow passing ?added_subtag=id@domain
+
+                    let target = uri!(tag_details(system_id = system_id, tag_id = tag_id));
+                    return Ok(Either::Right(Redirect::to(target)));
+                }
+            }
+            Err(
+                e @ (AppError::InvalidSubtag(..)
+                | AppError::DuplicateSubtag(..)
+                | AppError::NoSuchTag(..)),
+            ) if partial.is_some() => {
+                // (e.g., a loop) better shown next to the input than as a
+                // dialog, so that the user can just pick another tag
+                let error = form::Error::validation(e.to_string()).with_name("subtag");
+                form.context.push_error(error);
+                add_subtag_error = Some(e.into());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    // some errors are present; show the form again
+    debug!("Add subtag form errors: {:?}", &form.context);
+
+    if partial.is_some() {
+        let template = AddSubtagView {
+            ctx,
+            tag,
+            add_subtag_form: &form.context,
+            add_subtag_success: None,
+            add_subtag_error,
+        };
+
+        Ok(Either::Left(render(&template, template.ctx.format)?))
+    } else {
+        // FIXME: this just resets the form without actually showing
+        // any validation error indicators... but there isn't a great
+        // alternative, and it might be fine for such a tiny form
+
+        let target = uri!(tag_details(system_id = system_id, tag_id = tag_id));
+        Ok(Either::Right(Redirect::to(target)))
+    }
+}
+
+#[rocket::delete("/system/<system_id>/tag/<tag_id>/subtag/<subtag_system_id>/<subtag_tag_id>")]
+#[allow(clippy::too_many_arguments)]
+async fn unlink_subtag<'v>(
+    system_id: &str,
+    tag_id: &str,
+    subtag_system_id: &str,
+    subtag_tag_id: &str,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<(), Redirect>> {
+    let min = HivePermission::AssignTags(SystemsScope::Id(system_id.to_string()));
+    perms.require(min).await?;
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    tags::unlink_subtag(
+        system_id,
+        tag_id,
+        subtag_system_id,
+        subtag_tag_id,
+        db.inner(),
+        &user,
+    )
+    .await?;
+
+    if partial.is_some() {
+        Ok(Either::Left(()))
+    } else {
+        let target = uri!(tag_details(system_id = system_id, tag_id = tag_id));
+        Ok(Either::Right(Redirect::to(target)))
+    }
+}
+
+// meant to be used as options for a <datalist> in the add subtag form; only
+// includes tags the user could open anyway, so as not to enumerate all tags
+#[rocket::get("/system/<system_id>/tag/<tag_id>/subtag-suggestions")]
+async fn list_subtag_suggestions(
+    system_id: &str,
+    tag_id: &str,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    format: ResponseFormat,
+) -> AppResult<RenderedTemplate> {
+    let min = HivePermission::AssignTags(SystemsScope::Id(system_id.to_string()));
+    perms.require(min).await?;
+
+    let mut candidates = tags::list_subtag_candidates(system_id, tag_id, db.inner()).await?;
+
+    for candidate in &mut candidates {
+        // performance should be OK since perms are cached by perm_id
+        candidate.set_can_view(perms).await?;
+    }
+
+    candidates.retain(|candidate| candidate.can_view == Some(true));
+
+    let template = PartialSubtagSuggestionsView { candidates };
+
+    render(&template, format)
+}
+
+#[rocket::get("/system/<system_id>/tag/<tag_id>/derivations")]
+async fn list_tag_derivations(
+    system_id: &str,
+    tag_id: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    if partial.is_none() && !ctx.format.is_json() {
+        // we only know how to render a table, not a full page;
+        // redirect to tag details
+
+        let target = uri!(tag_details(system_id = system_id, tag_id = tag_id));
+        return Ok(Either::Right(Redirect::to(target)));
+    }
+
+    let scope = SystemsScope::Id(system_id.to_owned());
+    let can_delete = perms
+        .satisfies(HivePermission::AssignTags(scope.clone()))
+        .await?;
+
+    if !can_delete {
+        perms.require(HivePermission::ManageTags(scope)).await?;
+    }
+
+    let tag = tags::require_one(system_id, tag_id, db.inner()).await?;
+
+    let derivations = tags::list_derivations(system_id, tag_id, db.inner()).await?;
+
+    let template = PartialListTagDerivationsView {
+        ctx,
+        tag,
+        derivations,
+        can_delete,
+    };
+
+    Ok(Either::Left(render(&template, template.ctx.format)?))
+}
+
+// like subtags, deriving a tag is akin to assigning it (to many groups at once)
+#[rocket::post("/system/<system_id>/tag/<tag_id>/derivations", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn create_tag_derivation<'v>(
+    system_id: &str,
+    tag_id: &str,
+    form: Form<Contextual<'v, CreateTagDerivationDto<'v>>>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    let min = HivePermission::AssignTags(SystemsScope::Id(system_id.to_string()));
+    perms.require(min).await?;
+
+    let tag = tags::require_one(system_id, tag_id, db.inner()).await?;
+
+    // TODO: anti-CSRF
+
+    if let Some(dto) = &form.value {
+        // validation passed
+
+        let derivation = tags::create_derivation(system_id, tag_id, dto, db.inner(), &user).await?;
+
+        info!(
+            "Tag {} is now derived from {} by {}",
+            tag.key(),
+            derivation.perm_key(),
+            user.username()
+        );
 
         if partial.is_some() {
-            let template = AddSubtagView {
+            let template = AddTagDerivationView {
                 ctx,
                 tag,
-                add_subtag_form: &form::Context::default(),
-                add_subtag_success: Some(subtag),
+                add_derivation_form: &form::Context::default(),
+                add_derivation_success: Some(derivation),
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
-            // FIXME: maybe allow passing ?added_subtag=id@domain
-
             let target = uri!(tag_details(system_id = system_id, tag_id = tag_id));
             Ok(Either::Right(Redirect::to(target)))
         }
     } else {
         // some errors are present; show the form again
-        debug!("Add subtag form errors: {:?}", &form.context);
+        debug!("Add tag derivation form errors: {:?}", &form.context);
 
         if partial.is_some() {
-            let template = AddSubtagView {
+            let template = AddTagDerivationView {
                 ctx,
                 tag,
-                add_subtag_form: &form.context,
-                add_subtag_success: None,
+                add_derivation_form: &form.context,
+                add_derivation_success: None,
             };
 
-            Ok(Either::Left(RawHtml(template.render()?)))
+            Ok(Either::Left(render(&template, template.ctx.format)?))
         } else {
             // FIXME: this just resets the form without actually showing
-            // any validation error indicators... but there isn't a great
-            // alternative, and it might be fine for such a tiny form
+            // any validation error indicators (see `create_subtag`)
 
             let target = uri!(tag_details(system_id = system_id, tag_id = tag_id));
             Ok(Either::Right(Redirect::to(target)))
@@ -618,13 +1078,11 @@ async fn create_subtag<'v>(
     }
 }
 
-#[rocket::delete("/system/<system_id>/tag/<tag_id>/subtag/<subtag_system_id>/<subtag_tag_id>")]
-#[allow(clippy::too_many_arguments)]
-async fn unlink_subtag<'v>(
+#[rocket::delete("/system/<system_id>/tag/<tag_id>/derivation/<id>")]
+async fn delete_tag_derivation(
     system_id: &str,
     tag_id: &str,
-    subtag_system_id: &str,
-    subtag_tag_id: &str,
+    id: Uuid,
     db: &State<PgPool>,
     perms: &PermsEvaluator,
     user: User,
@@ -635,15 +1093,7 @@ async fn unlink_subtag<'v>(
 
     // TODO: anti-CSRF(?), DELETE isn't a normal form method
 
-    tags::unlink_subtag(
-        system_id,
-        tag_id,
-        subtag_system_id,
-        subtag_tag_id,
-        db.inner(),
-        &user,
-    )
-    .await?;
+    tags::delete_derivation(system_id, tag_id, id, db.inner(), &user).await?;
 
     if partial.is_some() {
         Ok(Either::Left(()))
@@ -652,3 +1102,123 @@ async fn unlink_subtag<'v>(
         Ok(Either::Right(Redirect::to(target)))
     }
 }
+
+#[rocket::get("/system/<system_id>/delegations")]
+async fn list_tag_delegations(
+    system_id: &str,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+) -> AppResult<RenderedTemplate> {
+    let min = HivePermission::ManageTags(SystemsScope::Id(system_id.to_owned()));
+    perms.require(min).await?;
+
+    systems::ensure_exists(system_id, db.inner()).await?;
+
+    let tags = tags::list_for_system(system_id, db.inner()).await?;
+    let delegations = tags::list_delegations(system_id, Some(&ctx.lang), db.inner()).await?;
+
+    let template = TagDelegationsView {
+        ctx,
+        system_id: system_id.to_owned(),
+        tags,
+        delegations,
+        delegate_form: &form::Context::default(),
+        delegate_success: None,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/system/<system_id>/delegations", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+async fn delegate_tag<'v>(
+    system_id: &str,
+    form: Form<Contextual<'v, DelegateTagDto<'v>>>,
+    protected: &State<ProtectedDomains>,
+    db: &State<PgPool>,
+    ctx: PageContext,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<RenderedTemplate, Redirect>> {
+    let min = HivePermission::ManageTags(SystemsScope::Id(system_id.to_owned()));
+    perms.require(min).await?;
+
+    // TODO: anti-CSRF
+
+    let target = uri!(list_tag_delegations(system_id));
+
+    if let Some(dto) = &form.value {
+        // validation passed
+
+        let delegation = tags::delegate(
+            system_id,
+            dto,
+            Some(&ctx.lang),
+            protected,
+            db.inner(),
+            &user,
+        )
+        .await?;
+
+        if partial.is_some() {
+            let template = DelegateTagView {
+                ctx,
+                system_id: system_id.to_owned(),
+                tags: tags::list_for_system(system_id, db.inner()).await?,
+                delegate_form: &form::Context::default(),
+                delegate_success: Some(delegation),
+            };
+
+            Ok(Either::Left(render(&template, template.ctx.format)?))
+        } else {
+            Ok(Either::Right(Redirect::to(target)))
+        }
+    } else {
+        // some errors are present; show the form again
+        debug!("Delegate tag form errors: {:?}", &form.context);
+
+        if partial.is_some() {
+            let template = DelegateTagView {
+                ctx,
+                system_id: system_id.to_owned(),
+                tags: tags::list_for_system(system_id, db.inner()).await?,
+                delegate_form: &form.context,
+                delegate_success: None,
+            };
+
+            Ok(Either::Left(render(&template, template.ctx.format)?))
+        } else {
+            // FIXME: this just resets the form without actually showing
+            // any validation error indicators... but there isn't a great
+            // alternative, and it might be fine for such a tiny form
+
+            Ok(Either::Right(Redirect::to(target)))
+        }
+    }
+}
+
+#[rocket::delete("/system/<system_id>/delegation/<id>")]
+async fn revoke_tag_delegation(
+    system_id: &str,
+    id: Uuid,
+    db: &State<PgPool>,
+    perms: &PermsEvaluator,
+    user: User,
+    partial: Option<HxRequest<'_>>,
+) -> AppResult<Either<(), Redirect>> {
+    let min = HivePermission::ManageTags(SystemsScope::Id(system_id.to_owned()));
+    perms.require(min).await?;
+
+    // TODO: anti-CSRF(?), DELETE isn't a normal form method
+
+    tags::revoke_delegation(system_id, id, db.inner(), &user).await?;
+
+    if partial.is_some() {
+        Ok(Either::Left(()))
+    } else {
+        let target = uri!(list_tag_delegations(system_id));
+        Ok(Either::Right(Redirect::to(target)))
+    }
+}
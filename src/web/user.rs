@@ -1,25 +1,52 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+use chrono::{DateTime, Local};
+use log::*;
 use rinja::Template;
-use rocket::{State, form::Form, response::content::RawHtml};
+use rocket::{State, form::Form, http::uri::Host};
+use serde::Serialize;
 use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::{
-    errors::AppResult,
-    guards::{context::PageContext, perms::PermsEvaluator, user::User},
-    models::{BasePermissionAssignment, SimpleGroup},
+    dto::users::{DigestSettingsDto, MembershipEmailSettingsDto},
+    errors::{AppError, AppResult},
+    guards::{
+        context::PageContext, format::ResponseFormat, perms::PermsEvaluator, scheme::RequestScheme,
+        user::User,
+    },
+    mailer::Mailer,
+    models::{
+        BasePermissionAssignment, LoginEvent, OwnershipTransfer, PendingMembership, SimpleGroup,
+        TagAssignment,
+    },
     perms::HivePermission,
     resolver::IdentityResolver,
     routing::RouteTree,
-    services::{groups, permissions},
-    web::RenderedTemplate,
+    services::{
+        digests::{self, ManagerDigests},
+        groups, logins, membership_emails, permissions,
+    },
+    web::{RenderedTemplate, render},
 };
 
 pub fn routes() -> RouteTree {
-    rocket::routes![show_profile, show_settings, update_settings].into()
+    rocket::routes![
+        show_profile,
+        show_settings,
+        show_sessions,
+        update_settings,
+        update_digest_settings,
+        update_membership_email_settings,
+        resend_verification,
+        confirm_email_verification,
+        verify_email,
+        autocomplete_usernames
+    ]
+    .into()
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "user/profile.html.j2")]
 struct ProfileView<'a> {
     ctx: PageContext,
@@ -29,16 +56,53 @@ struct ProfileView<'a> {
     display_name: String,
     known_groups: Vec<SimpleGroup>,
     permissions: Vec<BasePermissionAssignment>,
+    incoming_transfers: Vec<OwnershipTransfer>, // only for own profile
+    pending_memberships: Vec<PendingMembership>, // only for own profile
 }
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "user/settings.html.j2")]
 struct SettingsView {
     ctx: PageContext,
     settings: HashMap<String, Option<String>>,
     // ^ generated dynamically
+    unverified: HashSet<String>, // keys of settings pending verification
+    digest_subscribed: Option<bool>, // None if digests aren't enabled at all
+    membership_emails_subscribed: Option<bool>, // idem
 }
 
+#[derive(Template, Serialize)]
+#[template(path = "user/sessions.html.j2")]
+struct SessionsView {
+    ctx: PageContext,
+    expiration: DateTime<Local>, // of the current session
+    logins: Vec<LoginEvent>,
+    retention_days: i64,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "user/verify.html.j2")]
+struct VerifyView {
+    ctx: PageContext,
+    secret: Uuid,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "user/verified.html.j2")]
+struct VerifiedView {
+    ctx: PageContext,
+    assignment: TagAssignment,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "user/autocomplete.html.j2")]
+struct PartialAutocompleteView {
+    suggestions: Vec<(String, String)>, // (username, display name)
+}
+
+const AUTOCOMPLETE_MIN_QUERY_LEN: usize = 2;
+const AUTOCOMPLETE_LIMIT: usize = 10;
+
 #[rocket::get("/user/<username>")]
 async fn show_profile(
     username: &str,
@@ -85,6 +149,15 @@ async fn show_profile(
 
     let permissions = permissions::list_all_assignments_for_user(username, db.inner()).await?;
 
+    let (incoming_transfers, pending_memberships) = if own {
+        (
+            groups::transfers::list_incoming(username, db.inner()).await?,
+            groups::acceptances::list_for_user(username, db.inner()).await?,
+        )
+    } else {
+        (vec![], vec![])
+    };
+
     let template = ProfileView {
         ctx,
         own,
@@ -93,9 +166,11 @@ async fn show_profile(
         display_name,
         known_groups,
         permissions,
+        incoming_transfers,
+        pending_memberships,
     };
 
-    Ok(RawHtml(template.render()?))
+    render(&template, template.ctx.format)
 }
 
 // technically this URL prevents viewing the profile of a user named `settings`,
@@ -103,10 +178,12 @@ async fn show_profile(
 #[rocket::get("/user/settings")]
 async fn show_settings(
     db: &State<PgPool>,
+    manager_digests: &State<ManagerDigests>,
     ctx: PageContext,
     user: User,
 ) -> AppResult<RenderedTemplate> {
     let mut settings = HashMap::new();
+    let mut unverified = HashSet::new();
 
     #[cfg(feature = "integrations")]
     {
@@ -129,41 +206,252 @@ async fn show_settings(
                     // in the POST route, since rocket very helpfully(!)
                     // interprets dots for us as nesting
                     // (and we can't use dashes because slugs may contain them)
-                    settings.insert(format!("integration_{}_{}", manifest.id, tag.id), value);
+                    let key = format!("integration_{}_{}", manifest.id, tag.id);
+
+                    if let Some((_, false)) = value {
+                        unverified.insert(key.clone());
+                    }
+
+                    settings.insert(key, value.map(|(content, _)| content));
                 }
             }
         }
     }
 
-    let template = SettingsView { ctx, settings };
+    let digest_subscribed = if manager_digests.is_enabled() {
+        Some(!digests::is_opted_out(user.username(), db.inner()).await?)
+    } else {
+        None
+    };
+
+    let membership_emails_subscribed = if membership_emails::is_enabled() {
+        Some(!membership_emails::is_opted_out(user.username(), db.inner()).await?)
+    } else {
+        None
+    };
+
+    let template = SettingsView {
+        ctx,
+        settings,
+        unverified,
+        digest_subscribed,
+        membership_emails_subscribed,
+    };
+
+    render(&template, template.ctx.format)
+}
+
+// (idem for a user named `sessions`)
+#[rocket::get("/user/sessions")]
+async fn show_sessions(
+    db: &State<PgPool>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    let logins = logins::list_for_user(user.username(), db.inner()).await?;
+
+    let template = SessionsView {
+        ctx,
+        expiration: user.session_expiration(),
+        logins,
+        retention_days: logins::LOGIN_EVENT_RETENTION_PERIOD.num_days(),
+    };
+
+    render(&template, template.ctx.format)
+}
+
+#[cfg(feature = "integrations")]
+fn find_self_service_tag(key: &str) -> Option<(&'static str, &'static crate::integrations::Tag)> {
+    let (integration_id, tag_id) = key.strip_prefix("integration_")?.split_once('_')?;
+
+    for manifest in &*crate::integrations::MANIFESTS {
+        if manifest.id == integration_id {
+            let tag = manifest.tags.iter().find(|tag| tag.id == tag_id)?;
+
+            if tag.self_service && tag.supports_users && tag.has_content {
+                return Some((manifest.id, tag));
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(feature = "integrations")]
+async fn send_verification_email(
+    to: &str,
+    secret: &Uuid,
+    mailer: &Mailer,
+    scheme: &RequestScheme,
+    host: &Host<'_>,
+    ctx: &PageContext,
+) -> AppResult<()> {
+    let link = format!("{scheme}://{host}/user/verify-email/{secret}");
 
-    Ok(RawHtml(template.render()?))
+    mailer
+        .send(
+            &[to],
+            &ctx.t("user.settings.verification.email.subject"),
+            &ctx.t1("user.settings.verification.email.content", link),
+        )
+        .await
+        .map_err(AppError::MailerError)
 }
 
 #[rocket::post("/user/settings", data = "<mappings>")]
+#[allow(clippy::too_many_arguments)]
 async fn update_settings(
     mappings: Form<HashMap<String, String>>,
     db: &State<PgPool>,
+    manager_digests: &State<ManagerDigests>,
+    mailer: &State<Option<Mailer>>,
+    scheme: RequestScheme,
+    host: &Host<'_>,
     ctx: PageContext,
     user: User,
 ) -> AppResult<RenderedTemplate> {
     for (key, value) in mappings.into_inner() {
         #[cfg(feature = "integrations")]
-        if let Some(scoped) = key.strip_prefix("integration_") {
-            if let Some((integration_id, tag_id)) = scoped.split_once('_') {
-                use crate::services::integrations;
-
-                integrations::set_self_service(
-                    integration_id,
-                    tag_id,
-                    user.username(),
-                    &value,
-                    db.inner(),
-                )
-                .await?;
+        if let Some((integration_id, tag)) = find_self_service_tag(&key) {
+            use crate::services::integrations;
+
+            // without a mailer, there would be no way to ever verify anything
+            let mailer = mailer.inner().as_ref().filter(|_| tag.verify_email);
+
+            let secret = integrations::set_self_service(
+                integration_id,
+                tag.id,
+                user.username(),
+                &value,
+                mailer.is_some(),
+                db.inner(),
+            )
+            .await?;
+
+            if let (Some(secret), Some(mailer)) = (secret, mailer) {
+                send_verification_email(&value, &secret, mailer, &scheme, host, &ctx).await?;
             }
         }
     }
 
-    show_settings(db, ctx, user).await
+    show_settings(db, manager_digests, ctx, user).await
+}
+
+#[rocket::post("/user/settings/digest", data = "<form>")]
+async fn update_digest_settings(
+    form: Form<DigestSettingsDto>,
+    db: &State<PgPool>,
+    manager_digests: &State<ManagerDigests>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    // TODO: anti-CSRF
+
+    digests::set_opted_out(user.username(), !form.subscribed, db.inner()).await?;
+
+    show_settings(db, manager_digests, ctx, user).await
+}
+
+#[rocket::post("/user/settings/membership-emails", data = "<form>")]
+async fn update_membership_email_settings(
+    form: Form<MembershipEmailSettingsDto>,
+    db: &State<PgPool>,
+    manager_digests: &State<ManagerDigests>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    // TODO: anti-CSRF
+
+    membership_emails::set_opted_out(user.username(), !form.subscribed, db.inner()).await?;
+
+    show_settings(db, manager_digests, ctx, user).await
+}
+
+#[rocket::post("/user/settings/<key>/resend-verification")]
+#[allow(clippy::too_many_arguments)]
+async fn resend_verification(
+    key: &str,
+    db: &State<PgPool>,
+    manager_digests: &State<ManagerDigests>,
+    mailer: &State<Option<Mailer>>,
+    scheme: RequestScheme,
+    host: &Host<'_>,
+    ctx: PageContext,
+    user: User,
+) -> AppResult<RenderedTemplate> {
+    #[cfg(feature = "integrations")]
+    if let (Some((integration_id, tag)), Some(mailer)) =
+        (find_self_service_tag(key), mailer.inner())
+    {
+        use crate::services::integrations;
+
+        let renewed = integrations::renew_self_service_verification(
+            integration_id,
+            tag.id,
+            user.username(),
+            db.inner(),
+        )
+        .await?;
+
+        if let Some((secret, to)) = renewed {
+            send_verification_email(&to, &secret, mailer, &scheme, host, &ctx).await?;
+        }
+    }
+
+    show_settings(db, manager_digests, ctx, user).await
+}
+
+// no login required, since the link might be opened elsewhere (and the secret
+// already proves that it was received); only asks for confirmation, so that
+// links merely fetched (e.g., by email scanners) don't verify anything
+#[rocket::get("/user/verify-email/<secret>")]
+async fn confirm_email_verification(secret: Uuid, ctx: PageContext) -> AppResult<RenderedTemplate> {
+    let template = VerifyView { ctx, secret };
+
+    render(&template, template.ctx.format)
+}
+
+#[rocket::post("/user/verify-email/<secret>")]
+async fn verify_email(
+    secret: Uuid,
+    db: &State<PgPool>,
+    ctx: PageContext,
+) -> AppResult<RenderedTemplate> {
+    use crate::services::integrations;
+
+    let assignment = integrations::verify_self_service(&secret, db.inner()).await?;
+
+    let template = VerifiedView { ctx, assignment };
+
+    render(&template, template.ctx.format)
+}
+
+// meant to populate a <datalist> for username inputs; the query parameter is
+// set via hx-vals, since such inputs have different names in different forms
+#[rocket::get("/users/autocomplete?<q>")]
+async fn autocomplete_usernames(
+    q: &str,
+    resolver: &State<Option<IdentityResolver>>,
+    format: ResponseFormat,
+    _user: User, // only logged-in users may search
+) -> AppResult<RenderedTemplate> {
+    let q = q.trim();
+
+    let suggestions = match resolver.inner() {
+        Some(resolver) if q.chars().count() >= AUTOCOMPLETE_MIN_QUERY_LEN => {
+            // suggestions are merely a convenience, so don't fail loudly
+            resolver
+                .search(q, AUTOCOMPLETE_LIMIT)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to search for users matching `{q}`: {e}");
+                    vec![]
+                })
+        }
+        _ => vec![],
+    };
+
+    let template = PartialAutocompleteView { suggestions };
+
+    render(&template, format)
 }
@@ -63,6 +63,16 @@ function closeModal(id) {
   }, 300);
 }
 
+// fill in a membership's dates from one of the operational year shortcuts,
+// remembering which year they came from (cleared again if edited by hand)
+function useOperationalYear(button) {
+  const fields = button.form.elements;
+
+  fields.from.value = button.dataset.from;
+  fields.until.value = button.dataset.until;
+  fields.operational_year.value = button.dataset.year;
+}
+
 // these 2 handlers make hx-indicator automatically work with Pico loading
 document.body.addEventListener("htmx:beforeSend", () => {
   for (const el of document.getElementsByClassName("htmx-request")) {
@@ -106,3 +116,8 @@ for (const btn of document.querySelectorAll("[data-require-validity]")) {
   });
   btn.disabled = !btn.form.checkValidity();
 }
+
+if ("serviceWorker" in navigator) {
+  // (only caches static assets; see /sw.js)
+  navigator.serviceWorker.register("/sw.js");
+}
@@ -0,0 +1,135 @@
+// Command palette (Ctrl+K) with client-side fuzzy matching over everything
+// returned by `/palette` (see src/web/palette.rs)
+
+const MAX_PALETTE_RESULTS = 20;
+
+let paletteEntries = null; // fetched lazily, once per page
+let paletteSelected = 0;
+
+// returns null if not all chars of the needle appear in order in the haystack;
+// otherwise a score that favors consecutive matches and matches near the start
+function fuzzyScore(needle, haystack) {
+  let score = 0;
+  let streak = 0;
+  let pos = 0;
+
+  for (const c of needle) {
+    const found = haystack.indexOf(c, pos);
+    if (found === -1) {
+      return null;
+    }
+
+    streak = found === pos ? streak + 1 : 0;
+    score += 1 + streak * 2 - Math.min(found - pos, 10) * 0.1;
+    pos = found + 1;
+  }
+
+  return score - pos * 0.01;
+}
+
+function renderPaletteResults() {
+  const dialog = document.getElementById("command-palette");
+  const input = document.getElementById("command-palette-input");
+  const ul = document.getElementById("command-palette-results");
+
+  const needle = input.value.trim().toLowerCase();
+
+  const results = (paletteEntries ?? [])
+    .map((entry) => {
+      const haystack = `${entry.label} ${entry.detail}`.toLowerCase();
+      return { entry, score: needle ? fuzzyScore(needle, haystack) : 0 };
+    })
+    .filter((result) => result.score !== null)
+    .sort((a, b) => b.score - a.score)
+    .slice(0, MAX_PALETTE_RESULTS);
+
+  paletteSelected = Math.min(paletteSelected, Math.max(results.length - 1, 0));
+
+  ul.replaceChildren(
+    ...results.map(({ entry }, i) => {
+      const li = document.createElement("li");
+      li.setAttribute("role", "option");
+      li.setAttribute("aria-selected", i === paletteSelected);
+
+      const a = document.createElement("a");
+      a.href = entry.href;
+      a.className = "secondary";
+
+      const kind = document.createElement("small");
+      kind.className = "primary";
+      kind.textContent = dialog.dataset[`kind${entry.kind[0].toUpperCase()}${entry.kind.slice(1)}`];
+
+      const label = document.createElement("strong");
+      label.textContent = entry.label;
+
+      const detail = document.createElement("span");
+      detail.className = "secondary";
+      detail.textContent = entry.detail;
+
+      a.append(kind, " ", label, " ", detail);
+      li.append(a);
+
+      return li;
+    })
+  );
+}
+
+async function openPalette() {
+  const dialog = document.getElementById("command-palette");
+  if (!dialog || dialog.open) {
+    return;
+  }
+
+  const input = document.getElementById("command-palette-input");
+  input.value = "";
+  paletteSelected = 0;
+
+  openModal("command-palette");
+  input.focus();
+
+  if (paletteEntries === null) {
+    input.setAttribute("aria-busy", "true");
+
+    try {
+      const response = await fetch("/palette");
+      paletteEntries = response.ok ? await response.json() : [];
+    } finally {
+      input.removeAttribute("aria-busy");
+    }
+  }
+
+  renderPaletteResults();
+}
+
+document.addEventListener("keydown", (event) => {
+  if ((event.ctrlKey || event.metaKey) && event.key === "k") {
+    event.preventDefault();
+    openPalette();
+  }
+});
+
+document.getElementById("command-palette-input")?.addEventListener("input", () => {
+  paletteSelected = 0;
+  renderPaletteResults();
+});
+
+document.getElementById("command-palette-input")?.addEventListener("keydown", (event) => {
+  const options = document.querySelectorAll("#command-palette-results li");
+
+  if (event.key === "ArrowDown" || event.key === "ArrowUp") {
+    event.preventDefault();
+
+    const delta = event.key === "ArrowDown" ? 1 : -1;
+    paletteSelected = (paletteSelected + delta + options.length) % Math.max(options.length, 1);
+
+    renderPaletteResults();
+    document.querySelector("#command-palette-results li[aria-selected=true]")?.scrollIntoView({ block: "nearest" });
+  } else if (event.key === "Enter") {
+    event.preventDefault();
+
+    const selected = options[paletteSelected]?.querySelector("a");
+    if (selected) {
+      window.location.href = selected.href;
+    }
+  }
+});
@@ -0,0 +1,59 @@
+// Served from /sw.js (not /static/sw.js) so that its scope covers the whole
+// site, which is required for Hive to be installable as an app.
+//
+// Only static assets are cached; pages are never stored, since they contain
+// personal data and must always reflect current permissions.
+
+const CACHE = "hive-static-v1";
+
+self.addEventListener("install", () => {
+  self.skipWaiting();
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        Promise.all(
+          keys.filter((key) => key !== CACHE).map((key) => caches.delete(key)),
+        ),
+      )
+      .then(() => self.clients.claim()),
+  );
+});
+
+self.addEventListener("fetch", (event) => {
+  const url = new URL(event.request.url);
+
+  if (
+    event.request.method !== "GET" ||
+    url.origin !== self.location.origin ||
+    !url.pathname.startsWith("/static/")
+  ) {
+    return; // let the browser handle it as usual
+  }
+
+  // stale-while-revalidate
+  event.respondWith(
+    caches.open(CACHE).then(async (cache) => {
+      const cached = await cache.match(event.request);
+
+      const fresh = fetch(event.request)
+        .then((response) => {
+          if (response.ok) {
+            cache.put(event.request, response.clone());
+          }
+          return response;
+        })
+        .catch((err) => {
+          if (cached) {
+            return cached;
+          }
+          throw err;
+        });
+
+      return cached ?? fresh;
+    }),
+  );
+});